@@ -1,4 +1,5 @@
 use k256::ecdsa;
+use subtle::{Choice, ConstantTimeEq};
 
 use coins_core::prelude::{Hash160, Hash160Digest, MarkedDigest, MarkedDigestOutput};
 
@@ -42,7 +43,8 @@ pub trait DerivedKey {
     }
 }
 
-/// An XPriv with its derivation.
+/// An XPriv with its derivation. The secret scalar is always wiped when it is dropped. The
+/// `zeroize` feature also wipes the chain code and key info.
 #[derive(Debug, Clone)]
 #[cfg_attr(
     any(feature = "mainnet", feature = "testnet"),
@@ -55,6 +57,19 @@ pub struct DerivedXPriv {
 
 inherit_signer!(DerivedXPriv.xpriv);
 
+impl ConstantTimeEq for DerivedXPriv {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        // The derivation is not secret
+        self.xpriv.ct_eq(&other.xpriv) & Choice::from((self.derivation == other.derivation) as u8)
+    }
+}
+
+impl PartialEq for DerivedXPriv {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
 impl AsRef<XPriv> for DerivedXPriv {
     fn as_ref(&self) -> &XPriv {
         &self.xpriv
@@ -67,12 +82,6 @@ impl AsRef<XKeyInfo> for DerivedXPriv {
     }
 }
 
-impl AsRef<ecdsa::SigningKey> for DerivedXPriv {
-    fn as_ref(&self) -> &ecdsa::SigningKey {
-        &self.xpriv.key
-    }
-}
-
 impl DerivedKey for DerivedXPriv {
     fn derivation(&self) -> &KeyDerivation {
         &self.derivation
@@ -147,6 +156,13 @@ impl DerivedXPriv {
         Self::custom_root_node(SEED, data, hint)
     }
 
+    /// Build the signing key. It is not wiped when dropped, so drop it as soon as possible.
+    ///
+    /// `DerivedXPriv` does not implement `AsRef<ecdsa::SigningKey>`. Use this method instead.
+    pub fn signing_key(&self) -> ecdsa::SigningKey {
+        self.xpriv.signing_key()
+    }
+
     /// Derive the corresponding xpub
     pub fn verify_key(&self) -> DerivedXPub {
         DerivedXPub {
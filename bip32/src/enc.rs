@@ -1,46 +1,29 @@
-use coins_core::hashes::{Digest, Hash256};
+use coins_core::enc::base58check;
 use k256::ecdsa;
-use std::marker::PhantomData;
+use std::{convert::TryFrom, marker::PhantomData};
+use zeroize::Zeroizing;
 
 use crate::{
     primitives::{ChainCode, Hint, KeyFingerprint, XKeyInfo},
-    xkeys::{XPriv, XPub},
+    slip132,
+    xkeys::{secret_bytes, XPriv, XPub},
     Bip32Error,
 };
 
 /// Decode a bytevector from a base58 check string
 pub fn decode_b58_check(s: &str) -> Result<Vec<u8>, Bip32Error> {
-    let data: Vec<u8> = bs58::decode(s).into_vec()?;
-    let idx = data.len() - 4;
-    let payload = &data[..idx];
-    let checksum = &data[idx..];
-
-    let digest = &Hash256::digest(payload);
-
-    let mut expected = [0u8; 4];
-    expected.copy_from_slice(&digest.as_slice()[..4]);
-    if expected != checksum {
-        Err(Bip32Error::BadB58Checksum)
-    } else {
-        Ok(payload.to_vec())
-    }
+    Ok(base58check::decode(s)?)
 }
 
 /// Encode a vec into a base58 check String
 pub fn encode_b58_check(v: &[u8]) -> String {
-    let digest = &Hash256::digest(v);
-
-    let mut checksum = [0u8; 4];
-    checksum.copy_from_slice(&digest.as_slice()[..4]);
-
-    let mut data = v.to_vec();
-    data.extend(&checksum);
-
-    bs58::encode(data).into_string()
+    base58check::encode(v)
 }
 
 /// Contains network-specific serialization information
 pub trait NetworkParams {
+    /// A human-readable name for the network, used in error messages
+    const NETWORK: &'static str;
     /// The Bip32 privkey version bytes
     const PRIV_VERSION: u32;
     /// The Bip49 privkey version bytes
@@ -58,6 +41,7 @@ pub trait NetworkParams {
 params!(
     /// Mainnet encoding param
     Main {
+        network: "mainnet",
         bip32: 0x0488_ADE4,
         bip49: 0x049d_7878,
         bip84: 0x04b2_430c,
@@ -70,6 +54,7 @@ params!(
 params!(
     /// Testnet encoding param
     Test {
+        network: "testnet",
         bip32: 0x0435_8394,
         bip49: 0x044a_4e28,
         bip84: 0x045f_18bc,
@@ -79,6 +64,42 @@ params!(
     }
 );
 
+fn is_xpriv_version<P: NetworkParams>(version: u32) -> bool {
+    version == P::PRIV_VERSION
+        || version == P::BIP49_PRIV_VERSION
+        || version == P::BIP84_PRIV_VERSION
+}
+
+fn is_xpub_version<P: NetworkParams>(version: u32) -> bool {
+    version == P::PUB_VERSION || version == P::BIP49_PUB_VERSION || version == P::BIP84_PUB_VERSION
+}
+
+/// Determine which known network an xpriv's version bytes belong to, if any
+pub fn xpriv_version_network(version: u32) -> Option<&'static str> {
+    if is_xpriv_version::<Main>(version) {
+        Some(Main::NETWORK)
+    } else if is_xpriv_version::<Test>(version) {
+        Some(Test::NETWORK)
+    } else {
+        slip132::lookup(version)
+            .filter(|v| v.private)
+            .map(|v| v.network)
+    }
+}
+
+/// Determine which known network an xpub's version bytes belong to, if any
+pub fn xpub_version_network(version: u32) -> Option<&'static str> {
+    if is_xpub_version::<Main>(version) {
+        Some(Main::NETWORK)
+    } else if is_xpub_version::<Test>(version) {
+        Some(Test::NETWORK)
+    } else {
+        slip132::lookup(version)
+            .filter(|v| !v.private)
+            .map(|v| v.network)
+    }
+}
+
 /// Parameterizable Bitcoin encoder
 #[derive(Debug, Clone)]
 pub struct BitcoinEncoder<P: NetworkParams>(PhantomData<fn(P) -> P>);
@@ -172,12 +193,12 @@ pub trait XKeyEncoder {
             return Err(Bip32Error::BadPadding(buf[0]));
         }
 
-        let mut buf = [0u8; 32];
-        reader.read_exact(&mut buf)?;
-        let key = ecdsa::SigningKey::from_bytes(&buf)?;
+        let mut buf = Zeroizing::new([0u8; 32]);
+        reader.read_exact(&mut buf[..])?;
+        let key = k256::NonZeroScalar::try_from(&buf[..])?;
 
         Ok(XPriv {
-            key,
+            key: Zeroizing::new(key),
             xkey_info: XKeyInfo {
                 depth,
                 parent,
@@ -275,8 +296,8 @@ pub trait XKeyEncoder {
     where
         K: AsRef<XPriv>,
     {
-        let mut v: Vec<u8> = vec![];
-        Self::write_xpriv(&mut v, k)?;
+        let mut v = Zeroizing::new(vec![]);
+        Self::write_xpriv(&mut *v, k)?;
         Ok(encode_b58_check(&v))
     }
 
@@ -303,7 +324,7 @@ pub trait XKeyEncoder {
     /// ```
     fn xpriv_from_base58(s: &str) -> Result<XPriv, Bip32Error>
 where {
-        let data = decode_b58_check(s)?;
+        let data = Zeroizing::new(decode_b58_check(s)?);
         Self::read_xpriv(&mut &data[..])
     }
 
@@ -357,7 +378,7 @@ impl<P: NetworkParams> XKeyEncoder for BitcoinEncoder<P> {
         let mut written = writer.write(&version.to_be_bytes())?;
         written += Self::write_key_details(writer, key.as_ref())?;
         written += writer.write(&[0])?;
-        written += writer.write(&key.as_ref().key.to_bytes())?;
+        written += writer.write(&secret_bytes(&key.as_ref().key)[..])?;
         Ok(written)
     }
 
@@ -376,6 +397,16 @@ impl<P: NetworkParams> XKeyEncoder for BitcoinEncoder<P> {
             Hint::Compatibility
         } else if version_bytes == P::BIP84_PRIV_VERSION {
             Hint::SegWit
+        } else if let Some(v) =
+            slip132::lookup(version_bytes).filter(|v| v.private && v.network == P::NETWORK)
+        {
+            // SLIP-132 multisig version bytes
+            v.script_type.hint()
+        } else if let Some(got) = xpriv_version_network(version_bytes) {
+            return Err(Bip32Error::WrongNetworkVersionBytes {
+                expected: P::NETWORK,
+                got,
+            });
         } else {
             return Err(Bip32Error::BadXPrivVersionBytes(buf));
         };
@@ -397,8 +428,18 @@ impl<P: NetworkParams> XKeyEncoder for BitcoinEncoder<P> {
             Hint::Compatibility
         } else if version_bytes == P::BIP84_PUB_VERSION {
             Hint::SegWit
+        } else if let Some(v) =
+            slip132::lookup(version_bytes).filter(|v| !v.private && v.network == P::NETWORK)
+        {
+            // SLIP-132 multisig version bytes
+            v.script_type.hint()
+        } else if let Some(got) = xpub_version_network(version_bytes) {
+            return Err(Bip32Error::WrongNetworkVersionBytes {
+                expected: P::NETWORK,
+                got,
+            });
         } else {
-            return Err(Bip32Error::BadXPrivVersionBytes(buf));
+            return Err(Bip32Error::BadXPubVersionBytes(buf));
         };
         Self::read_xpub_body(reader, hint)
     }
@@ -63,6 +63,13 @@ pub mod xkeys;
 /// Provides keys that are coupled with their derivation path
 pub mod derived;
 
+/// SLIP-132 version bytes, and conversion between extended key prefixes
+pub mod slip132;
+
+/// Signing with overridden nonces, for known-answer tests
+#[cfg(any(test, feature = "test-nonces"))]
+pub mod nonces;
+
 #[doc(hidden)]
 #[cfg(any(feature = "mainnet", feature = "testnet"))]
 pub mod defaults;
@@ -124,17 +131,26 @@ pub enum Bip32Error {
     #[error("Version bytes 0x{0:x?} don't match any network xpub version bytes")]
     BadXPubVersionBytes([u8; 4]),
 
+    /// Version bytes don't match any registered SLIP-132 version bytes
+    #[error("Version bytes 0x{0:x?} don't match any SLIP-132 version bytes")]
+    UnknownVersionBytes([u8; 4]),
+
+    /// Version bytes belong to a known network, but not the one the encoder expects
+    #[error("Extended key version bytes are for {got}, expected {expected}")]
+    WrongNetworkVersionBytes {
+        /// The network the encoder expects
+        expected: &'static str,
+        /// The network the version bytes belong to
+        got: &'static str,
+    },
+
     /// Bad padding byte on serialized xprv
     #[error("Expected 0 padding byte. Got {0}")]
     BadPadding(u8),
 
-    /// Bad Checks on b58check
-    #[error("Checksum mismatch on b58 deserialization")]
-    BadB58Checksum,
-
-    /// Bubbled up error from bs58 library
+    /// Bubbled up error from Base58Check decoding
     #[error(transparent)]
-    B58Error(#[from] bs58::decode::Error),
+    B58Error(#[from] coins_core::enc::Base58CheckError),
 
     /// Parsing an string derivation failed because an index string was malformatted
     #[error("Malformatted index during derivation: {0}")]
@@ -149,6 +165,45 @@ pub enum Bip32Error {
     InvalidBip32Path,
 }
 
+impl Bip32Error {
+    /// A stable, machine-readable `bip32.*` code for the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            Bip32Error::BackendError(_) => "bip32.backend",
+            Bip32Error::EllipticCurveError(_) => "bip32.elliptic_curve",
+            Bip32Error::IoError(_) => "bip32.io",
+            Bip32Error::SerError(_) => "bip32.ser",
+            Bip32Error::SeedTooShort => "bip32.seed_too_short",
+            Bip32Error::InvalidKey => "bip32.invalid_key",
+            Bip32Error::HardenedDerivationFailed => "bip32.hardened_derivation_failed",
+            Bip32Error::BadTweak => "bip32.bad_tweak",
+            Bip32Error::BadXPrivVersionBytes(_) => "bip32.bad_xpriv_version_bytes",
+            Bip32Error::BadXPubVersionBytes(_) => "bip32.bad_xpub_version_bytes",
+            Bip32Error::UnknownVersionBytes(_) => "bip32.unknown_version_bytes",
+            Bip32Error::WrongNetworkVersionBytes { .. } => "bip32.wrong_network_version_bytes",
+            Bip32Error::BadPadding(_) => "bip32.bad_padding",
+            Bip32Error::B58Error(_) => "bip32.base58check",
+            Bip32Error::MalformattedDerivation(_) => "bip32.malformatted_derivation",
+            Bip32Error::NoRecoveryId => "bip32.no_recovery_id",
+            Bip32Error::InvalidBip32Path => "bip32.invalid_path",
+        }
+    }
+
+    /// True if the error was caused by the caller's input, e.g. a malformed key, path, or seed.
+    /// False for backend failures, and for `InvalidKey`, which derivation hits with negligible
+    /// probability.
+    pub fn is_user_error(&self) -> bool {
+        match self {
+            Bip32Error::BackendError(_)
+            | Bip32Error::EllipticCurveError(_)
+            | Bip32Error::InvalidKey => false,
+            Bip32Error::IoError(e) => e.kind() == std::io::ErrorKind::UnexpectedEof,
+            Bip32Error::SerError(e) => e.is_user_error(),
+            _ => true,
+        }
+    }
+}
+
 impl From<ecdsa::Error> for Bip32Error {
     fn from(e: ecdsa::Error) -> Self {
         Self::BackendError(e)
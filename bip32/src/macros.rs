@@ -1,5 +1,13 @@
 macro_rules! inherit_signer {
     ($struct_name:ident.$attr:ident) => {
+        inherit_signer!(@impl $struct_name, .$attr);
+    };
+
+    ($struct_name:ident.$method:ident()) => {
+        inherit_signer!(@impl $struct_name, .$method());
+    };
+
+    (@impl $struct_name:ident, $($signer:tt)+) => {
         impl<D> k256::ecdsa::signature::DigestSigner<D, k256::ecdsa::Signature> for $struct_name
         where
             D: digest::BlockInput
@@ -14,7 +22,7 @@ macro_rules! inherit_signer {
                 &self,
                 digest: D,
             ) -> Result<k256::ecdsa::Signature, k256::ecdsa::Error> {
-                self.$attr.try_sign_digest(digest)
+                self$($signer)+.try_sign_digest(digest)
             }
         }
 
@@ -33,7 +41,7 @@ macro_rules! inherit_signer {
                 &self,
                 digest: D,
             ) -> Result<k256::ecdsa::recoverable::Signature, k256::ecdsa::Error> {
-                self.$attr.try_sign_digest(digest)
+                self$($signer)+.try_sign_digest(digest)
             }
         }
     };
@@ -85,6 +93,7 @@ macro_rules! params {
     (
         $(#[$outer:meta])*
         $name:ident{
+            network: $network:expr,
             bip32: $bip32:expr,
             bip49: $bip49:expr,
             bip84: $bip84:expr,
@@ -98,6 +107,7 @@ macro_rules! params {
         pub struct $name;
 
         impl crate::enc::NetworkParams for $name {
+            const NETWORK: &'static str = $network;
             const PRIV_VERSION: u32 = $bip32;
             const BIP49_PRIV_VERSION: u32 = $bip49;
             const BIP84_PRIV_VERSION: u32 = $bip84;
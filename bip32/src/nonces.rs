@@ -0,0 +1,161 @@
+//! Signing with overridden nonces, for known-answer tests against published signature vectors.
+//!
+//! The standard signing path derives nonces deterministically with RFC6979. Some published
+//! vectors instead specify the nonce directly, or use RFC6979 with additional entropy (as in
+//! libsecp256k1's `ndata`). The `NonceOverrideSigner` trait reproduces these.
+//!
+//! **Never use this in production.** Reusing a nonce across two messages, or using a nonce known
+//! to anyone else, reveals the private key. This module is only available with the
+//! `test-nonces` feature.
+
+use digest::{BlockInput, FixedOutput, Reset, Update};
+use ecdsa_core::hazmat::{FromDigest, RecoverableSignPrimitive};
+use k256::{
+    ecdsa::{recoverable, signature::RandomizedDigestSigner},
+    elliptic_curve::{
+        consts::U32,
+        rand_core::{self, CryptoRng, RngCore},
+    },
+    Scalar,
+};
+use zeroize::Zeroizing;
+
+use crate::{xkeys::XPriv, Bip32Error};
+
+/// An RNG that emits a fixed 32-byte value. Used to pass additional entropy to RFC6979.
+struct FixedEntropy([u8; 32]);
+
+impl RngCore for FixedEntropy {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for (d, s) in dest.iter_mut().zip(self.0.iter().cycle()) {
+            *d = *s;
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for FixedEntropy {}
+
+/// Sign digests with a caller-specified nonce or nonce entropy. Implemented for all private key
+/// types. See the module documentation before using this.
+pub trait NonceOverrideSigner: AsRef<XPriv> {
+    /// Sign a digest, deriving the nonce with RFC6979 and 32 bytes of additional entropy.
+    fn sign_digest_with_entropy<D>(
+        &self,
+        digest: D,
+        entropy: [u8; 32],
+    ) -> Result<recoverable::Signature, Bip32Error>
+    where
+        D: BlockInput + FixedOutput<OutputSize = U32> + Clone + Default + Reset + Update,
+    {
+        Ok(self
+            .as_ref()
+            .signing_key()
+            .try_sign_digest_with_rng(FixedEntropy(entropy), digest)?)
+    }
+
+    /// Sign a digest with an explicit nonce. The nonce is a big-endian scalar, and is reduced
+    /// modulo the curve order. Errors if the nonce is 0. The signature is normalized to low-s.
+    fn sign_digest_with_nonce<D>(
+        &self,
+        digest: D,
+        nonce: [u8; 32],
+    ) -> Result<recoverable::Signature, Bip32Error>
+    where
+        D: BlockInput + FixedOutput<OutputSize = U32> + Clone + Default + Reset + Update,
+    {
+        let secret = Zeroizing::new(*self.as_ref().key);
+        let nonce = Scalar::from_bytes_reduced(&nonce.into());
+        let msg = Scalar::from_digest(digest);
+        let (signature, recovery_id) = secret.try_sign_recoverable_prehashed(&nonce, &msg)?;
+        Ok(recoverable::Signature::new(
+            &signature,
+            recoverable::Id::new(recovery_id as u8)?,
+        )?)
+    }
+}
+
+impl<T> NonceOverrideSigner for T where T: AsRef<XPriv> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        primitives::{ChainCode, Hint, KeyFingerprint, XKeyInfo},
+        xkeys::XPriv,
+    };
+    use k256::ecdsa::{
+        self,
+        signature::{DigestSigner, DigestVerifier, Signature as SigTrait},
+    };
+    use sha2::Sha256;
+
+    fn key_one() -> XPriv {
+        let mut key = [0u8; 32];
+        key[31] = 1;
+        XPriv::new(
+            ecdsa::SigningKey::from_bytes(&key).unwrap(),
+            XKeyInfo {
+                depth: 0,
+                parent: KeyFingerprint([0; 4]),
+                index: 0,
+                chain_code: ChainCode([0; 32]),
+                hint: Hint::Legacy,
+            },
+        )
+    }
+
+    #[test]
+    fn it_signs_with_overridden_nonces() {
+        let key = key_one();
+        let mut digest = Sha256::default();
+        Update::update(&mut digest, b"Satoshi Nakamoto");
+
+        // RFC6979, from the Trezor and python-ecdsa test suites
+        let sig: recoverable::Signature = key.sign_digest(digest.clone());
+        assert_eq!(
+            hex::encode(&sig.as_bytes()[..64]),
+            "934b1ea10a4b3c1757e2b0c017d0b6143ce3c9a7e6a4a49860d7a6ab210ee3d82442ce9d2b916064108014783e923ec36b49743e2ffa1c4496f01a512aafd9e5"
+        );
+
+        // With nonce 1, r is the x coordinate of the generator
+        let mut nonce = [0u8; 32];
+        nonce[31] = 1;
+        let sig = key.sign_digest_with_nonce(digest.clone(), nonce).unwrap();
+        assert_eq!(
+            hex::encode(&sig.as_bytes()[..32]),
+            "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"
+        );
+        key.verify_key()
+            .verify_digest(digest.clone(), &sig)
+            .unwrap();
+        assert!(key.sign_digest_with_nonce(digest.clone(), [0; 32]).is_err());
+
+        // Entropy changes the nonce, deterministically
+        let with_entropy = key
+            .sign_digest_with_entropy(digest.clone(), [7; 32])
+            .unwrap();
+        assert_ne!(with_entropy.as_bytes(), sig.as_bytes());
+        assert_eq!(
+            with_entropy.as_bytes(),
+            key.sign_digest_with_entropy(digest.clone(), [7; 32])
+                .unwrap()
+                .as_bytes()
+        );
+        key.verify_key()
+            .verify_digest(digest, &with_entropy)
+            .unwrap();
+    }
+}
@@ -42,7 +42,7 @@ fn encode_index(idx: u32, harden: char) -> String {
 }
 
 /// A Bip32 derivation path
-#[derive(Default, Debug, Clone, Eq, PartialEq)]
+#[derive(Default, Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
 pub struct DerivationPath(Vec<u32>);
 
 impl serde::Serialize for DerivationPath {
@@ -185,7 +185,9 @@ impl FromStr for DerivationPath {
 }
 
 /// A Derivation Path for a bip32 key
-#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
 pub struct KeyDerivation {
     /// The root key fingerprint
     pub root: KeyFingerprint,
@@ -15,6 +15,9 @@ pub use k256::ecdsa::{
     Signature, SigningKey, VerifyingKey,
 };
 
+/// Re-exported constant-time equality trait, implemented by secret key material
+pub use subtle::ConstantTimeEq;
+
 /// shortcut for easy usage
 pub fn fingerprint_of(k: &k256::ecdsa::VerifyingKey) -> KeyFingerprint {
     use coins_core::hashes::Digest;
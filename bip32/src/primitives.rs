@@ -1,6 +1,7 @@
 use crate::Bip32Error;
 use coins_core::ser::ByteFormat;
 use std::io::{Read, Write};
+use subtle::{Choice, ConstantTimeEq};
 
 /// We treat the bip32 xpub bip49 ypub and bip84 zpub convention as a hint regarding address type.
 /// Downstream crates are free to follow or ignore these hints when generating addresses from
@@ -16,7 +17,9 @@ pub enum Hint {
 }
 
 /// A 4-byte key fingerprint
-#[derive(Eq, PartialEq, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Eq, PartialEq, Clone, Copy, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
 pub struct KeyFingerprint(pub [u8; 4]);
 
 impl From<[u8; 4]> for KeyFingerprint {
@@ -63,10 +66,32 @@ impl std::fmt::Debug for KeyFingerprint {
     }
 }
 
-/// A 32-byte chain code
-#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+/// A 32-byte chain code. Chain codes are secret when the extended key is private, so they are
+/// compared in constant time.
+#[derive(Debug, Clone, Copy)]
 pub struct ChainCode(pub [u8; 32]);
 
+impl ConstantTimeEq for ChainCode {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for ChainCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for ChainCode {}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for ChainCode {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
 impl From<[u8; 32]> for ChainCode {
     fn from(v: [u8; 32]) -> Self {
         Self(v)
@@ -89,11 +114,25 @@ pub struct XKeyInfo {
     pub hint: Hint,
 }
 
+impl ConstantTimeEq for XKeyInfo {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.depth.ct_eq(&other.depth)
+            & self.parent.0.ct_eq(&other.parent.0)
+            & self.index.ct_eq(&other.index)
+            & self.chain_code.ct_eq(&other.chain_code)
+    }
+}
+
+/// Only the chain code is secret, so only the chain code is wiped
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for XKeyInfo {
+    fn zeroize(&mut self) {
+        self.chain_code.zeroize();
+    }
+}
+
 impl PartialEq for XKeyInfo {
     fn eq(&self, other: &XKeyInfo) -> bool {
-        self.depth == other.depth
-            && self.parent == other.parent
-            && self.index == other.index
-            && self.chain_code == other.chain_code
+        self.ct_eq(other).into()
     }
 }
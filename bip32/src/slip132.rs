@@ -0,0 +1,267 @@
+//! SLIP-132 extended key version bytes.
+//!
+//! Wallets signal the script type an extended key is intended for through its version bytes.
+//! BIP49 and BIP84 define the `ypub` and `zpub` prefixes for single-key scripts. SLIP-132 adds
+//! `Ypub` and `Zpub` for multisig, and the testnet equivalents. The key material is the same
+//! regardless of prefix, so converting between them only rewrites the version bytes.
+//!
+//! ```
+//! use coins_bip32::slip132::{self, ScriptType};
+//! # fn main() -> Result<(), coins_bip32::Bip32Error> {
+//! let xpub = "xpub68NZiKmJWnxxS6aaHmn81bvJeTESw724CRDs6HbuccFQN9Ku14VQrADWgqbhhTHBaohPX4CjNLf9fq9MYo6oDaPPLPxSb7gwQN3ih19Zm4Y";
+//!
+//! let zpub = slip132::convert(xpub, ScriptType::P2wpkh)?;
+//! assert!(zpub.starts_with("zpub"));
+//! assert_eq!(slip132::key_version(&zpub)?.script_type, ScriptType::P2wpkh);
+//! assert_eq!(slip132::convert(&zpub, ScriptType::P2pkh)?, xpub);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::Read;
+
+use crate::{
+    enc::{decode_b58_check, encode_b58_check, Main, NetworkParams, Test},
+    primitives::Hint,
+    Bip32Error,
+};
+
+/// The script type an extended key's version bytes advertise
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum ScriptType {
+    /// P2PKH or P2SH. The standard `xpub` and `tpub` prefixes.
+    P2pkh,
+    /// P2WPKH nested in P2SH. The `ypub` and `upub` prefixes.
+    P2shP2wpkh,
+    /// Multisig P2WSH nested in P2SH. The `Ypub` and `Upub` prefixes.
+    P2shP2wsh,
+    /// Native P2WPKH. The `zpub` and `vpub` prefixes.
+    P2wpkh,
+    /// Native multisig P2WSH. The `Zpub` and `Vpub` prefixes.
+    P2wsh,
+}
+
+impl ScriptType {
+    /// The address type hint given to keys with this script type
+    pub fn hint(self) -> Hint {
+        match self {
+            ScriptType::P2pkh => Hint::Legacy,
+            ScriptType::P2shP2wpkh | ScriptType::P2shP2wsh => Hint::Compatibility,
+            ScriptType::P2wpkh | ScriptType::P2wsh => Hint::SegWit,
+        }
+    }
+
+    /// True if the script type is intended for multisig
+    pub fn is_multisig(self) -> bool {
+        matches!(self, ScriptType::P2shP2wsh | ScriptType::P2wsh)
+    }
+}
+
+impl From<Hint> for ScriptType {
+    fn from(hint: Hint) -> Self {
+        match hint {
+            Hint::Legacy => ScriptType::P2pkh,
+            Hint::Compatibility => ScriptType::P2shP2wpkh,
+            Hint::SegWit => ScriptType::P2wpkh,
+        }
+    }
+}
+
+/// A registered set of extended key version bytes
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct KeyVersion {
+    /// The version bytes
+    pub version: u32,
+    /// The prefix of base58 strings with these version bytes
+    pub prefix: &'static str,
+    /// The network, as named by the encoder's `NetworkParams`
+    pub network: &'static str,
+    /// The script type the key is intended for
+    pub script_type: ScriptType,
+    /// True if the version bytes are for private keys
+    pub private: bool,
+}
+
+macro_rules! key_version {
+    ($version:expr, $prefix:expr, $network:ty, $script_type:ident, $private:expr) => {
+        KeyVersion {
+            version: $version,
+            prefix: $prefix,
+            network: <$network as NetworkParams>::NETWORK,
+            script_type: ScriptType::$script_type,
+            private: $private,
+        }
+    };
+}
+
+/// The Bitcoin mainnet and testnet version bytes registered in SLIP-132
+pub const KEY_VERSIONS: [KeyVersion; 20] = [
+    key_version!(0x0488_b21e, "xpub", Main, P2pkh, false),
+    key_version!(0x0488_ade4, "xprv", Main, P2pkh, true),
+    key_version!(0x049d_7cb2, "ypub", Main, P2shP2wpkh, false),
+    key_version!(0x049d_7878, "yprv", Main, P2shP2wpkh, true),
+    key_version!(0x0295_b43f, "Ypub", Main, P2shP2wsh, false),
+    key_version!(0x0295_b005, "Yprv", Main, P2shP2wsh, true),
+    key_version!(0x04b2_4746, "zpub", Main, P2wpkh, false),
+    key_version!(0x04b2_430c, "zprv", Main, P2wpkh, true),
+    key_version!(0x02aa_7ed3, "Zpub", Main, P2wsh, false),
+    key_version!(0x02aa_7a99, "Zprv", Main, P2wsh, true),
+    key_version!(0x0435_87cf, "tpub", Test, P2pkh, false),
+    key_version!(0x0435_8394, "tprv", Test, P2pkh, true),
+    key_version!(0x044a_5262, "upub", Test, P2shP2wpkh, false),
+    key_version!(0x044a_4e28, "uprv", Test, P2shP2wpkh, true),
+    key_version!(0x0242_89ef, "Upub", Test, P2shP2wsh, false),
+    key_version!(0x0242_85b5, "Uprv", Test, P2shP2wsh, true),
+    key_version!(0x045f_1cf6, "vpub", Test, P2wpkh, false),
+    key_version!(0x045f_18bc, "vprv", Test, P2wpkh, true),
+    key_version!(0x0257_5483, "Vpub", Test, P2wsh, false),
+    key_version!(0x0257_5048, "Vprv", Test, P2wsh, true),
+];
+
+/// Look up registered version bytes
+pub fn lookup(version: u32) -> Option<KeyVersion> {
+    KEY_VERSIONS.iter().copied().find(|v| v.version == version)
+}
+
+/// Find the version bytes for a network, script type, and key privacy
+pub fn find(network: &str, script_type: ScriptType, private: bool) -> Option<KeyVersion> {
+    KEY_VERSIONS
+        .iter()
+        .copied()
+        .find(|v| v.network == network && v.script_type == script_type && v.private == private)
+}
+
+/// Read the version bytes of a base58check extended key
+fn split_version(s: &str) -> Result<(u32, Vec<u8>), Bip32Error> {
+    let data = decode_b58_check(s)?;
+    let mut buf = [0u8; 4];
+    (&data[..]).read_exact(&mut buf)?;
+    Ok((u32::from_be_bytes(buf), data))
+}
+
+/// Determine the registered version of a base58check extended key
+pub fn key_version(s: &str) -> Result<KeyVersion, Bip32Error> {
+    let (version, _) = split_version(s)?;
+    lookup(version).ok_or(Bip32Error::UnknownVersionBytes(version.to_be_bytes()))
+}
+
+/// Re-encode a base58check extended key with the version bytes for another script type. The
+/// network and key privacy are preserved. Converting to `ScriptType::P2pkh` produces a standard
+/// `xpub`, `xprv`, `tpub` or `tprv`.
+pub fn convert(s: &str, script_type: ScriptType) -> Result<String, Bip32Error> {
+    let (version, mut data) = split_version(s)?;
+    let current = lookup(version).ok_or(Bip32Error::UnknownVersionBytes(version.to_be_bytes()))?;
+    let target = find(current.network, script_type, current.private)
+        .expect("every network registers every script type");
+    data[..4].copy_from_slice(&target.version.to_be_bytes());
+    Ok(encode_b58_check(&data))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        enc::{MainnetEncoder, TestnetEncoder, XKeyEncoder},
+        xkeys::XPriv,
+    };
+
+    #[test]
+    fn it_registers_each_network_and_script_type() {
+        for network in [Main::NETWORK, Test::NETWORK].iter() {
+            for script_type in [
+                ScriptType::P2pkh,
+                ScriptType::P2shP2wpkh,
+                ScriptType::P2shP2wsh,
+                ScriptType::P2wpkh,
+                ScriptType::P2wsh,
+            ]
+            .iter()
+            {
+                for private in [false, true].iter() {
+                    let v = find(network, *script_type, *private).unwrap();
+                    assert_eq!(lookup(v.version), Some(v));
+                }
+            }
+        }
+        assert_eq!(
+            Main::PUB_VERSION,
+            find("mainnet", ScriptType::P2pkh, false).unwrap().version
+        );
+        assert_eq!(
+            Test::BIP84_PRIV_VERSION,
+            find("testnet", ScriptType::P2wpkh, true).unwrap().version
+        );
+        assert_eq!(lookup(0), None);
+    }
+
+    #[test]
+    fn it_converts_between_prefixes() {
+        let xpriv = XPriv::root_from_seed(&[7u8; 32], Some(Hint::Legacy)).unwrap();
+        let xpub = xpriv.verify_key();
+        let encoded = [
+            MainnetEncoder::xpriv_to_base58(&xpriv).unwrap(),
+            MainnetEncoder::xpub_to_base58(&xpub).unwrap(),
+            TestnetEncoder::xpriv_to_base58(&xpriv).unwrap(),
+            TestnetEncoder::xpub_to_base58(&xpub).unwrap(),
+        ];
+        for s in encoded.iter() {
+            let original = key_version(s).unwrap();
+            assert_eq!(original.script_type, ScriptType::P2pkh);
+            for v in KEY_VERSIONS
+                .iter()
+                .filter(|v| v.network == original.network && v.private == original.private)
+            {
+                let converted = convert(s, v.script_type).unwrap();
+                assert!(converted.starts_with(v.prefix));
+                assert_eq!(key_version(&converted).unwrap(), *v);
+                assert_eq!(convert(&converted, ScriptType::P2pkh).unwrap(), *s);
+            }
+        }
+    }
+
+    #[test]
+    fn it_decodes_multisig_prefixes() {
+        let xpriv = XPriv::root_from_seed(&[7u8; 32], Some(Hint::Legacy)).unwrap();
+        let xpub = xpriv.verify_key();
+        let xpub_str = MainnetEncoder::xpub_to_base58(&xpub).unwrap();
+
+        let zpub = convert(&xpub_str, ScriptType::P2wsh).unwrap();
+        assert!(zpub.starts_with("Zpub"));
+        let decoded = MainnetEncoder::xpub_from_base58(&zpub).unwrap();
+        assert_eq!(decoded.xkey_info.hint, Hint::SegWit);
+        assert_eq!(decoded, xpub);
+
+        let yprv = convert(
+            &MainnetEncoder::xpriv_to_base58(&xpriv).unwrap(),
+            ScriptType::P2shP2wsh,
+        )
+        .unwrap();
+        assert!(yprv.starts_with("Yprv"));
+        let decoded = MainnetEncoder::xpriv_from_base58(&yprv).unwrap();
+        assert_eq!(decoded.xkey_info.hint, Hint::Compatibility);
+
+        let vpub = convert(
+            &TestnetEncoder::xpub_to_base58(&xpub).unwrap(),
+            ScriptType::P2wsh,
+        )
+        .unwrap();
+        assert!(vpub.starts_with("Vpub"));
+        assert!(matches!(
+            MainnetEncoder::xpub_from_base58(&vpub),
+            Err(Bip32Error::WrongNetworkVersionBytes {
+                expected: "mainnet",
+                got: "testnet"
+            })
+        ));
+
+        let unknown = encode_b58_check(&[0u8; 78]);
+        assert!(matches!(
+            key_version(&unknown),
+            Err(Bip32Error::UnknownVersionBytes([0, 0, 0, 0]))
+        ));
+        assert!(matches!(
+            convert(&encode_b58_check(&[4, 136]), ScriptType::P2wpkh),
+            Err(Bip32Error::IoError(_))
+        ));
+    }
+}
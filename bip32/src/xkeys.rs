@@ -9,6 +9,8 @@ use std::{
     convert::{TryFrom, TryInto},
     ops::{AddAssign, Mul},
 };
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::{Zeroize, Zeroizing};
 
 use crate::{
     path::DerivationPath,
@@ -23,16 +25,26 @@ fn hmac_and_split(
     seed: &[u8],
     data: &[u8],
 ) -> Result<(k256::NonZeroScalar, ChainCode), Bip32Error> {
-    let mut mac:Hmac::<Sha512> = hmac::NewMac::new_from_slice(seed).expect("key length is ok");
+    let mut mac: Hmac<Sha512> = hmac::NewMac::new_from_slice(seed).expect("key length is ok");
     mac.update(data);
-    let result = mac.finalize().into_bytes();
+    let mut result = mac.finalize().into_bytes();
 
-    let left = k256::NonZeroScalar::try_from(&result[..32])?;
+    let left = k256::NonZeroScalar::try_from(&result[..32]);
 
     let mut right = [0u8; 32];
     right.copy_from_slice(&result[32..]);
+    result[..].zeroize();
 
-    Ok((left, ChainCode(right)))
+    Ok((left?, ChainCode(right)))
+}
+
+/// Copy a secret scalar into a buffer that is wiped when dropped
+pub(crate) fn secret_bytes(key: &k256::NonZeroScalar) -> Zeroizing<[u8; 32]> {
+    let mut bytes = key.to_bytes();
+    let mut secret = Zeroizing::new([0u8; 32]);
+    secret.copy_from_slice(&bytes);
+    bytes[..].zeroize();
+    secret
 }
 
 /// A Parent key can be used to derive children.
@@ -64,27 +76,46 @@ pub trait Parent: Sized + Clone {
 }
 
 /// A BIP32 eXtended Privkey
+///
+/// The secret scalar is wiped when the key is dropped. `k256`'s `SigningKey` does not wipe itself,
+/// so signing keys are built on demand by `signing_key`, and only live as long as the operation
+/// using them.
 pub struct XPriv {
-    pub(crate) key: ecdsa::SigningKey,
+    pub(crate) key: Zeroizing<k256::NonZeroScalar>,
     pub(crate) xkey_info: XKeyInfo,
 }
 
+impl ConstantTimeEq for XPriv {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.key.ct_eq(&other.key) & self.xkey_info.ct_eq(&other.xkey_info)
+    }
+}
+
 impl PartialEq for XPriv {
     fn eq(&self, other: &XPriv) -> bool {
-        self.fingerprint() == other.fingerprint() && self.xkey_info == other.xkey_info
+        self.ct_eq(other).into()
     }
 }
 
 impl Clone for XPriv {
     fn clone(&self) -> Self {
         Self {
-            key: ecdsa::SigningKey::from_bytes(&self.key.to_bytes()).unwrap(),
+            key: self.key.clone(),
             xkey_info: self.xkey_info,
         }
     }
 }
 
-inherit_signer!(XPriv.key);
+/// The secret scalar wipes itself when dropped, so only the chain code is wiped here. XPrivs do
+/// not implement `Zeroize`, as a wiped scalar is not a valid key.
+#[cfg(feature = "zeroize")]
+impl Drop for XPriv {
+    fn drop(&mut self) {
+        self.xkey_info.zeroize();
+    }
+}
+
+inherit_signer!(XPriv.signing_key());
 
 impl std::fmt::Debug for XPriv {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -107,22 +138,28 @@ impl AsRef<XKeyInfo> for XPriv {
     }
 }
 
-impl AsRef<ecdsa::SigningKey> for XPriv {
-    fn as_ref(&self) -> &ecdsa::SigningKey {
-        &self.key
-    }
-}
-
 impl XPriv {
     /// Instantiate a new XPriv.
     pub fn new(key: ecdsa::SigningKey, xkey_info: XKeyInfo) -> Self {
-        Self { key, xkey_info }
+        let secret = k256::SecretKey::from(key);
+        Self {
+            key: Zeroizing::new(k256::NonZeroScalar::from(&secret)),
+            xkey_info,
+        }
+    }
+
+    /// Build the signing key. It is not wiped when dropped, so drop it as soon as possible.
+    ///
+    /// The signing key is no longer stored, so `XPriv` does not implement
+    /// `AsRef<ecdsa::SigningKey>`. Use this method instead.
+    pub fn signing_key(&self) -> ecdsa::SigningKey {
+        ecdsa::SigningKey::from(&*self.key)
     }
 
     /// Derive the associated XPub
     pub fn verify_key(&self) -> XPub {
         XPub {
-            key: self.key.verifying_key(),
+            key: k256::PublicKey::from_secret_scalar(&self.key).into(),
             xkey_info: self.xkey_info,
         }
     }
@@ -167,10 +204,8 @@ impl XPriv {
             return Err(Bip32Error::InvalidKey);
         }
 
-        let key = ecdsa::SigningKey::from(key);
-
         Ok(XPriv {
-            key,
+            key: Zeroizing::new(key),
             xkey_info: XKeyInfo {
                 depth: 0,
                 parent,
@@ -216,31 +251,27 @@ impl Parent for XPriv {
     fn derive_child(&self, index: u32) -> Result<Self, Bip32Error> {
         let hardened = index >= BIP32_HARDEN;
 
-        let key: &ecdsa::SigningKey = self.as_ref();
-
-        let mut data: Vec<u8> = vec![];
+        let mut data = Zeroizing::new(Vec::with_capacity(37));
         if hardened {
             data.push(0);
-            data.extend(&key.to_bytes());
+            data.extend(secret_bytes(&self.key).iter());
             data.extend(&index.to_be_bytes());
         } else {
-            data.extend(&key.verifying_key().to_bytes());
+            data.extend(&self.verify_key().to_bytes());
             data.extend(&index.to_be_bytes());
         };
 
-        let res = hmac_and_split(&self.xkey_info.chain_code.0, &data);
-        let (tweak, chain_code) = match res {
-            Ok((tweak, chain_code)) => (tweak, chain_code),
+        let (tweak, chain_code) = match hmac_and_split(&self.xkey_info.chain_code.0, &data) {
+            Ok((tweak, chain_code)) => (Zeroizing::new(tweak), chain_code),
             _ => return self.derive_child(index + 1),
         };
 
-        let parent_key = k256::NonZeroScalar::from_repr(key.to_bytes()).unwrap();
-        let tweaked = tweak.clone().add(&parent_key);
+        let tweaked = Zeroizing::new(tweak.add(&self.key));
 
-        let tweaked = k256::NonZeroScalar::new(tweaked).ok_or(Bip32Error::BadTweak)?;
+        let tweaked = k256::NonZeroScalar::new(*tweaked).ok_or(Bip32Error::BadTweak)?;
 
         Ok(Self {
-            key: ecdsa::SigningKey::from(tweaked),
+            key: Zeroizing::new(tweaked),
             xkey_info: XKeyInfo {
                 depth: self.xkey_info.depth + 1,
                 parent: self.fingerprint(),
@@ -366,8 +397,9 @@ impl Parent for XPub {
 mod test {
     use super::*;
     use crate::{
-        enc::{MainnetEncoder, XKeyEncoder},
+        enc::{MainnetEncoder, TestnetEncoder, XKeyEncoder},
         primitives::*,
+        Bip32Error,
     };
     use coins_core::hashes::Hash256;
     use k256::ecdsa::{
@@ -595,4 +627,64 @@ mod test {
         let xpriv: XPriv = MainnetEncoder::xpriv_from_base58(&xpriv_str).unwrap();
         println!("{:?}", xpriv);
     }
+
+    #[test]
+    fn it_rejects_keys_from_other_networks() {
+        let xpriv_str = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi".to_owned();
+        let xpriv: XPriv = MainnetEncoder::xpriv_from_base58(&xpriv_str).unwrap();
+        let xpub = xpriv.verify_key();
+
+        let tprv = TestnetEncoder::xpriv_to_base58(&xpriv).unwrap();
+        let tpub = TestnetEncoder::xpub_to_base58(&xpub).unwrap();
+        assert!(tprv.starts_with("tprv"));
+        assert!(tpub.starts_with("tpub"));
+        TestnetEncoder::xpriv_from_base58(&tprv).unwrap();
+        TestnetEncoder::xpub_from_base58(&tpub).unwrap();
+
+        match MainnetEncoder::xpriv_from_base58(&tprv) {
+            Err(Bip32Error::WrongNetworkVersionBytes { expected, got }) => {
+                assert_eq!(expected, "mainnet");
+                assert_eq!(got, "testnet");
+            }
+            _ => panic!("expected network mismatch"),
+        }
+        let xpub_str = MainnetEncoder::xpub_to_base58(&xpub).unwrap();
+        match TestnetEncoder::xpub_from_base58(&xpub_str) {
+            Err(Bip32Error::WrongNetworkVersionBytes { expected, got }) => {
+                assert_eq!(expected, "testnet");
+                assert_eq!(got, "mainnet");
+            }
+            _ => panic!("expected network mismatch"),
+        }
+    }
+
+    #[test]
+    fn it_compares_secrets_in_constant_time() {
+        let seed = [7u8; 32];
+        let xpriv = XPriv::root_from_seed(&seed, Some(Hint::Legacy)).unwrap();
+        assert!(bool::from(xpriv.ct_eq(&xpriv.clone())));
+        assert_eq!(xpriv, xpriv.clone());
+
+        // Same key, different chain code
+        let mut other = xpriv.clone();
+        other.xkey_info.chain_code.0[31] ^= 1;
+        assert!(!bool::from(xpriv.ct_eq(&other)));
+        assert_ne!(xpriv, other);
+        assert_ne!(xpriv.xkey_info.chain_code, other.xkey_info.chain_code);
+
+        // Same chain code, different key
+        let mut other = xpriv.derive_child(0).unwrap();
+        other.xkey_info = xpriv.xkey_info;
+        assert_ne!(xpriv, other);
+    }
+
+    #[test]
+    #[cfg(feature = "zeroize")]
+    fn it_zeroizes_secrets() {
+        let xpriv = XPriv::root_from_seed(&[7u8; 32], Some(Hint::Legacy)).unwrap();
+        let mut info = xpriv.xkey_info;
+        info.zeroize();
+        assert_eq!(info.chain_code, ChainCode([0u8; 32]));
+        assert_eq!(info.depth, xpriv.xkey_info.depth);
+    }
 }
@@ -7,6 +7,7 @@ use rand::Rng;
 use sha2::{Digest, Sha256, Sha512};
 use std::{convert::TryInto, marker::PhantomData};
 use thiserror::Error;
+use zeroize::{Zeroize, Zeroizing};
 
 const PBKDF2_ROUNDS: u32 = 2048;
 const PBKDF2_BYTES: usize = 64;
@@ -21,6 +22,20 @@ pub struct Mnemonic<W: Wordlist> {
     _wordlist: PhantomData<W>,
 }
 
+#[cfg(feature = "zeroize")]
+impl<W: Wordlist> Zeroize for Mnemonic<W> {
+    fn zeroize(&mut self) {
+        self.entropy.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<W: Wordlist> Drop for Mnemonic<W> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
 #[derive(Debug, Error)]
 /// The error type returned while interacting with mnemonics.
 pub enum MnemonicError {
@@ -44,7 +59,7 @@ pub enum MnemonicError {
 impl<W: Wordlist> Mnemonic<W> {
     /// Returns a new mnemonic generated using the provided random number generator.
     pub fn new<R: Rng>(rng: &mut R) -> Self {
-        let entropy: [u8; 16] = rng.gen();
+        let entropy = Zeroizing::new(rng.gen::<[u8; 16]>());
         Self {
             entropy: entropy.to_vec(),
             _wordlist: PhantomData,
@@ -62,7 +77,7 @@ impl<W: Wordlist> Mnemonic<W> {
             24 => 32,
             wc => return Err(MnemonicError::InvalidWordCount(wc)),
         };
-        let entropy: [u8; 32] = rng.gen();
+        let entropy = Zeroizing::new(rng.gen::<[u8; 32]>());
         Ok(Self {
             entropy: entropy[0..length].to_vec(),
             _wordlist: PhantomData,
@@ -82,9 +97,15 @@ impl<W: Wordlist> Mnemonic<W> {
             wc => return Err(MnemonicError::InvalidWordCount(wc)),
         };
 
+        let indices = Zeroizing::new(
+            words
+                .into_iter()
+                .map(W::get_index)
+                .collect::<Result<Vec<usize>, _>>()?,
+        );
+
         let mut entropy: BitVec<Msb0, u8> = BitVec::new();
-        for word in words {
-            let index = W::get_index(word)?;
+        for &index in indices.iter() {
             let index_u8: [u8; 2] = (index as u16).to_be_bytes();
 
             // 11-bits per word as per BIP-39, and max index (2047) can be represented in 11-bits.
@@ -93,8 +114,9 @@ impl<W: Wordlist> Mnemonic<W> {
             entropy.append(&mut BitVec::<Msb0, u8>::from_bitslice(index_slice));
         }
 
+        let entropy = Zeroizing::new(entropy.into_vec());
         let mnemonic = Self {
-            entropy: entropy.as_slice()[0..length].to_vec(),
+            entropy: entropy[0..length].to_vec(),
             _wordlist: PhantomData,
         };
 
@@ -137,6 +159,8 @@ impl<W: Wordlist> Mnemonic<W> {
             })
             .collect::<Vec<&str>>();
 
+        encoding.into_vec().zeroize();
+
         Ok(phrase.join(" "))
     }
 
@@ -155,10 +179,8 @@ impl<W: Wordlist> Mnemonic<W> {
 impl<W: Wordlist> Mnemonic<W> {
     /// Returns the master private key of the corresponding mnemonic.
     pub fn master_key(&self, password: Option<&str>) -> Result<XPriv, MnemonicError> {
-        Ok(XPriv::root_from_seed(
-            self.to_seed(password)?.as_slice(),
-            None,
-        )?)
+        let seed = self.to_seed(password)?;
+        Ok(XPriv::root_from_seed(seed.as_slice(), None)?)
     }
 
     /// Returns the derived child private key of the corresponding mnemonic at the given index.
@@ -170,16 +192,11 @@ impl<W: Wordlist> Mnemonic<W> {
         Ok(self.master_key(password)?.derive_path(path)?)
     }
 
-    fn to_seed(&self, password: Option<&str>) -> Result<Vec<u8>, MnemonicError> {
-        let mut seed = vec![0u8; PBKDF2_BYTES];
-        let salt = format!("mnemonic{}", password.unwrap_or(""));
-        pbkdf2::<Hmac<Sha512>>(
-            &self.to_phrase()?.as_bytes(),
-            salt.as_bytes(),
-            PBKDF2_ROUNDS,
-            &mut seed,
-        );
-
+    fn to_seed(&self, password: Option<&str>) -> Result<Zeroizing<Vec<u8>>, MnemonicError> {
+        let mut seed = Zeroizing::new(vec![0u8; PBKDF2_BYTES]);
+        let salt = Zeroizing::new(format!("mnemonic{}", password.unwrap_or("")));
+        let phrase = Zeroizing::new(self.to_phrase()?);
+        pbkdf2::<Hmac<Sha512>>(phrase.as_bytes(), salt.as_bytes(), PBKDF2_ROUNDS, &mut seed);
         Ok(seed)
     }
 }
@@ -413,7 +430,7 @@ mod tests {
                 };
                 assert_eq!(
                     expected_seed,
-                    &hex::encode(mnemonic.to_seed(Some("TREZOR")).unwrap()),
+                    &hex::encode(&*mnemonic.to_seed(Some("TREZOR")).unwrap()),
                 )
             });
     }
@@ -431,4 +448,16 @@ mod tests {
                 );
             });
     }
+
+    #[test]
+    #[cfg(feature = "zeroize")]
+    fn test_zeroize() {
+        let (entropy_str, _, _, _) = TESTCASES[0];
+        let mut mnemonic = Mnemonic::<W> {
+            entropy: hex::decode(entropy_str).unwrap(),
+            _wordlist: PhantomData,
+        };
+        mnemonic.zeroize();
+        assert!(mnemonic.entropy.iter().all(|b| *b == 0));
+    }
 }
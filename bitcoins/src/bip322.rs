@@ -0,0 +1,429 @@
+//! BIP322 generic signed messages.
+//!
+//! A BIP322 signature proves control of a script pubkey, rather than of a key. The signer signs a
+//! virtual `to_sign` transaction, spending a virtual `to_spend` transaction output that pays to
+//! the script pubkey and commits to the message. A "simple" proof is the witness of the signed
+//! `to_sign` input. A "full" proof is the entire signed `to_sign` transaction, and is required
+//! for script pubkeys spent with a script sig, such as P2PKH.
+//!
+//! Signing follows the external-signer flow of `signing::SigningSession`. `signing_session`
+//! exports the digest to sign. Once the signature is inserted and the session finalized,
+//! `Bip322Proof::from_signed` selects the proof format.
+//!
+//! P2PKH, P2WPKH, and P2SH-nested P2WPKH script pubkeys are supported. Taproot, P2WSH, and
+//! multi-input proofs of funds are not yet supported.
+//!
+//! ```
+//! use bitcoins::{bip322, types::ScriptPubkey};
+//!
+//! let script_pubkey =
+//!     ScriptPubkey::from_hex("00142b05d564e6a7a33c087f16e0f730d1440123799d").unwrap();
+//! let proof = bip322::Bip322Proof::from_base64("AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=").unwrap();
+//! assert!(bip322::verify(&script_pubkey, b"Hello World", &proof).is_ok());
+//! ```
+
+use std::io::Cursor;
+
+use coins_core::{
+    hashes::{Digest, Hash160, Hash160Digest, Sha256},
+    ser::{self, ByteFormat, SerError},
+    types::tx::Transaction,
+};
+use thiserror::Error;
+
+use crate::{
+    signing::{SigningError, SigningSession},
+    types::{
+        legacy::LegacyTx,
+        script::{Script, ScriptPubkey, ScriptType, Witness, WitnessStackItem},
+        tx::{BitcoinTransaction, BitcoinTx, Sighash, TxError},
+        utxo::{SpendScript, Utxo},
+        witness::WitnessTx,
+    },
+    virtual_tx::{self, SpendError},
+};
+
+/// The BIP340-style tag of the BIP322 message hash
+pub const BIP322_TAG: &[u8] = b"BIP0322-signed-message";
+
+/// An error signing or verifying a BIP322 message.
+#[derive(Debug, Error)]
+pub enum Bip322Error {
+    /// Proofs for the script pubkey's type are not supported
+    #[error("BIP322 proofs for this script pubkey type are not supported")]
+    UnsupportedScript,
+
+    /// The proof is not a witness stack or a transaction, or does not have the shape the script
+    /// pubkey requires
+    #[error("Malformed BIP322 proof")]
+    MalformedProof,
+
+    /// A full proof's transaction is not a `to_sign` transaction for the message
+    #[error("Proof does not spend the message's to_spend transaction")]
+    WrongToSign,
+
+    /// The public key does not match the script pubkey
+    #[error("Public key does not match the script pubkey")]
+    WrongKey,
+
+    /// The signature is not valid for the public key and message
+    #[error("Invalid signature")]
+    InvalidSignature,
+
+    /// Bubbled up from the signing session
+    #[error(transparent)]
+    SigningError(#[from] SigningError),
+
+    /// Bubbled up from the transaction
+    #[error(transparent)]
+    TxError(#[from] TxError),
+
+    /// Bubbled up from deserialization
+    #[error(transparent)]
+    SerError(#[from] SerError),
+}
+
+impl From<SpendError> for Bip322Error {
+    fn from(e: SpendError) -> Self {
+        match e {
+            SpendError::Malformed => Bip322Error::MalformedProof,
+            SpendError::WrongKey => Bip322Error::WrongKey,
+            SpendError::InvalidSignature => Bip322Error::InvalidSignature,
+        }
+    }
+}
+
+/// Calculate the BIP322 message hash. This is the tagged SHA256 of the message.
+pub fn message_hash(message: &[u8]) -> [u8; 32] {
+    let tag = Sha256::digest(BIP322_TAG);
+    let mut hasher = Sha256::new();
+    hasher.update(tag);
+    hasher.update(tag);
+    hasher.update(message);
+    hasher.finalize().into()
+}
+
+/// Build the virtual `to_spend` transaction. Its only output pays to `script_pubkey`, and its
+/// only input commits to the message.
+pub fn to_spend(script_pubkey: &ScriptPubkey, message: &[u8]) -> LegacyTx {
+    virtual_tx::to_spend(script_pubkey, &message_hash(message))
+}
+
+/// Build the unsigned virtual `to_sign` transaction, spending the output of `to_spend`
+pub fn to_sign(to_spend: &LegacyTx) -> WitnessTx {
+    virtual_tx::to_sign(to_spend)
+}
+
+/// Prepare to sign a message for `script_pubkey`. P2SH script pubkeys require their redeem
+/// script. The session holds a single signing request, for the `to_sign` input.
+pub fn signing_session(
+    script_pubkey: &ScriptPubkey,
+    message: &[u8],
+    redeem_script: Option<Script>,
+) -> Result<SigningSession, Bip322Error> {
+    let to_spend = to_spend(script_pubkey, message);
+    let to_sign = to_sign(&to_spend);
+    let spend_script = redeem_script
+        .map(SpendScript::Known)
+        .unwrap_or(SpendScript::Missing);
+    let prevout = Utxo::new(
+        to_sign.inputs()[0].outpoint,
+        0,
+        script_pubkey.clone(),
+        spend_script,
+    );
+    SigningSession::new(&BitcoinTx::Witness(to_sign), &[prevout], &[], Sighash::All).map_err(|e| {
+        match e {
+            SigningError::UnsupportedInput(_) => Bip322Error::UnsupportedScript,
+            e => e.into(),
+        }
+    })
+}
+
+/// A BIP322 proof. Its base64 encoding is the message signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bip322Proof {
+    /// The witness of the signed `to_sign` input
+    Simple(Witness),
+    /// The signed `to_sign` transaction
+    Full(BitcoinTx),
+}
+
+impl Bip322Proof {
+    /// Wrap a signed `to_sign` transaction. The proof is simple if the input is spent by its
+    /// witness alone, and full otherwise.
+    pub fn from_signed(tx: BitcoinTx) -> Self {
+        let witness = tx.witnesses().first().cloned().unwrap_or_default();
+        let script_sig_empty = tx
+            .inputs()
+            .first()
+            .is_none_or(|input| input.script_sig.is_empty());
+        if script_sig_empty && !witness.is_empty() && tx.inputs().len() == 1 {
+            Bip322Proof::Simple(witness)
+        } else {
+            Bip322Proof::Full(tx)
+        }
+    }
+
+    /// Serialize the proof. A simple proof is the witness stack, and a full proof the
+    /// transaction.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        match self {
+            Bip322Proof::Simple(witness) => {
+                ser::write_prefix_vec::<_, SerError, _>(&mut buf, witness)
+                    .expect("No IOError from Vec");
+            }
+            Bip322Proof::Full(tx) => {
+                tx.write_to(&mut buf).expect("No IOError from Vec");
+            }
+        }
+        buf
+    }
+
+    /// Deserialize a proof. Data that is exactly one witness stack is a simple proof. Otherwise
+    /// it must be a full proof.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Bip322Error> {
+        let mut cursor = Cursor::new(data);
+        if let Ok(witness) = read_witness(&mut cursor) {
+            if cursor.position() as usize == data.len() {
+                return Ok(Bip322Proof::Simple(witness));
+            }
+        }
+        let mut cursor = Cursor::new(data);
+        let tx = BitcoinTx::read_from(&mut cursor).map_err(|_| Bip322Error::MalformedProof)?;
+        if cursor.position() as usize != data.len() {
+            return Err(Bip322Error::MalformedProof);
+        }
+        Ok(Bip322Proof::Full(tx))
+    }
+
+    /// Encode the proof as a base64 message signature
+    pub fn to_base64(&self) -> String {
+        base64::encode(self.to_bytes())
+    }
+
+    /// Decode a base64 message signature
+    pub fn from_base64(s: &str) -> Result<Self, Bip322Error> {
+        let data = base64::decode(s).map_err(SerError::from)?;
+        Self::from_bytes(&data)
+    }
+}
+
+/// Read a witness stack from untrusted bytes. Each length prefix is bounded by the bytes left
+/// in the cursor, so a malformed prefix errors instead of allocating.
+fn read_witness(cursor: &mut Cursor<&[u8]>) -> Result<Witness, SerError> {
+    let remaining = |c: &Cursor<&[u8]>| c.get_ref().len() - c.position() as usize;
+    let items = ser::read_prefix_len(cursor, remaining(cursor))?;
+    (0..items)
+        .map(|_| {
+            let limit = remaining(cursor);
+            ser::read_prefix_vec_limited(cursor, limit).map(WitnessStackItem::from)
+        })
+        .collect()
+}
+
+/// Verify a BIP322 proof that the signer of `message` controls `script_pubkey`
+pub fn verify(
+    script_pubkey: &ScriptPubkey,
+    message: &[u8],
+    proof: &Bip322Proof,
+) -> Result<(), Bip322Error> {
+    let to_spend = to_spend(script_pubkey, message);
+    let tx = match proof {
+        Bip322Proof::Simple(witness) => {
+            let mut tx = to_sign(&to_spend);
+            tx.witnesses[0] = witness.clone();
+            tx
+        }
+        Bip322Proof::Full(tx) => {
+            let tx = tx.clone().into_witness();
+            if tx.inputs().len() != 1 {
+                return Err(Bip322Error::UnsupportedScript);
+            }
+            let expected = to_sign(&to_spend);
+            if tx.inputs()[0].outpoint != expected.inputs()[0].outpoint
+                || tx.outputs() != expected.outputs()
+            {
+                return Err(Bip322Error::WrongToSign);
+            }
+            tx
+        }
+    };
+
+    let script_sig = &tx.inputs()[0].script_sig;
+    match script_pubkey.standard_type() {
+        ScriptType::Pkh(hash) => Ok(virtual_tx::verify_pkh(&tx, script_pubkey, &hash)?),
+        ScriptType::Wpkh(hash) => {
+            if !script_sig.is_empty() {
+                return Err(Bip322Error::MalformedProof);
+            }
+            Ok(virtual_tx::verify_wpkh(&tx, &hash)?)
+        }
+        ScriptType::Sh(hash) => {
+            let items = virtual_tx::pushes(script_sig).ok_or(Bip322Error::MalformedProof)?;
+            if items.len() != 1 {
+                return Err(Bip322Error::MalformedProof);
+            }
+            let redeem_hash: Hash160Digest = Hash160::digest(items[0]).into();
+            if redeem_hash != hash {
+                return Err(Bip322Error::WrongKey);
+            }
+            match ScriptPubkey::from(items[0].to_vec()).standard_type() {
+                ScriptType::Wpkh(hash) => Ok(virtual_tx::verify_wpkh(&tx, &hash)?),
+                _ => Err(Bip322Error::UnsupportedScript),
+            }
+        }
+        _ => Err(Bip322Error::UnsupportedScript),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::{
+        legacy::LegacySighashArgs,
+        witness::{WitnessSighashArgs, WitnessTransaction},
+    };
+    use coins_bip32::prelude::*;
+    use coins_core::hashes::{Hash256, Hash256Digest};
+
+    // From the BIP322 test vectors. The key is L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k
+    const WPKH: &str = "00142b05d564e6a7a33c087f16e0f730d1440123799d";
+    const EMPTY_SIG: &str = "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+    const HELLO_SIG: &str = "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+
+    #[test]
+    fn it_builds_virtual_transactions() {
+        assert_eq!(
+            hex::encode(message_hash(b"")),
+            "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1"
+        );
+        assert_eq!(
+            hex::encode(message_hash(b"Hello World")),
+            "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a"
+        );
+
+        let script_pubkey = ScriptPubkey::from_hex(WPKH).unwrap();
+        let cases = [
+            (
+                &b""[..],
+                "c5680aa69bb8d860bf82d4e9cd3504b55dde018de765a91bb566283c545a99a7",
+                "1e9654e951a5ba44c8604c4de6c67fd78a27e81dcadcfe1edf638ba3aaebaed6",
+            ),
+            (
+                &b"Hello World"[..],
+                "b79d196740ad5217771c1098fc4a4b51e0535c32236c71f1ea4d61a2d603352b",
+                "88737ae86f2077145f93cc4b153ae9a1cb8d56afa511988c149c5c8c9d93bddf",
+            ),
+        ];
+        for (message, to_spend_id, to_sign_id) in cases.iter() {
+            let to_spend = to_spend(&script_pubkey, message);
+            assert_eq!(to_spend.txid().to_be_hex(), *to_spend_id);
+            assert_eq!(to_sign(&to_spend).txid().to_be_hex(), *to_sign_id);
+        }
+    }
+
+    #[test]
+    fn it_verifies_simple_proofs() {
+        let script_pubkey = ScriptPubkey::from_hex(WPKH).unwrap();
+        let empty = Bip322Proof::from_base64(EMPTY_SIG).unwrap();
+        let hello = Bip322Proof::from_base64(HELLO_SIG).unwrap();
+        assert!(matches!(empty, Bip322Proof::Simple(_)));
+        assert_eq!(hello.to_base64(), HELLO_SIG);
+
+        assert!(verify(&script_pubkey, b"", &empty).is_ok());
+        assert!(verify(&script_pubkey, b"Hello World", &hello).is_ok());
+        assert!(matches!(
+            verify(&script_pubkey, b"", &hello),
+            Err(Bip322Error::InvalidSignature)
+        ));
+        let other = ScriptPubkey::from_hex("00140000000000000000000000000000000000000000").unwrap();
+        assert!(matches!(
+            verify(&other, b"", &empty),
+            Err(Bip322Error::WrongKey)
+        ));
+    }
+
+    #[test]
+    fn it_rejects_oversized_length_prefixes() {
+        // One witness item, claiming 2^40 bytes
+        let data = [0x01, 0xff, 0, 0, 0, 0, 0, 1, 0, 0];
+        assert!(matches!(
+            read_witness(&mut Cursor::new(&data[..])),
+            Err(SerError::ExcessiveLength { .. })
+        ));
+        // The same bytes happen to encode a legacy tx with no inputs or outputs
+        assert!(!matches!(
+            Bip322Proof::from_base64(&base64::encode(data)),
+            Ok(Bip322Proof::Simple(_))
+        ));
+    }
+
+    #[test]
+    fn it_signs_and_verifies_messages() {
+        let root = DerivedXPriv::root_from_seed(&[7u8; 32], Some(Hint::SegWit)).unwrap();
+        let key = root.derive_child(0).unwrap();
+        let pubkey = key.verify_key();
+        let message = b"BIP322 round trip";
+
+        let wpkh = ScriptPubkey::p2wpkh(&pubkey);
+        let pkh = ScriptPubkey::p2pkh(&pubkey);
+        let redeem_script = Script::from(wpkh.items().to_vec());
+        let sh_wpkh = ScriptPubkey::p2sh(&redeem_script);
+
+        let cases = [
+            (wpkh, None, true),
+            (pkh, None, false),
+            (sh_wpkh, Some(redeem_script), false),
+        ];
+        for (script_pubkey, redeem_script, simple) in cases.iter() {
+            let mut session =
+                signing_session(script_pubkey, message, redeem_script.clone()).unwrap();
+            // An external signer would sign the exported digest directly
+            let to_sign = to_sign(&to_spend(script_pubkey, message));
+            let mut hasher = Hash256::default();
+            if redeem_script.is_none() && !simple {
+                let args = LegacySighashArgs {
+                    index: 0,
+                    sighash_flag: Sighash::All,
+                    prevout_script: script_pubkey.into(),
+                };
+                to_sign
+                    .write_legacy_sighash_preimage(&mut hasher, &args)
+                    .unwrap();
+            } else {
+                let args = WitnessSighashArgs::wpkh(0, Sighash::All, &pubkey, 0);
+                to_sign
+                    .write_witness_sighash_preimage(&mut hasher, &args)
+                    .unwrap();
+            }
+            assert_eq!(
+                Hash256Digest::from(hasher.clone().finalize()),
+                session.requests()[0].digest
+            );
+            let signature: Signature = key.sign_digest(hasher);
+            session
+                .insert_signature(0, &signature, pubkey.as_ref())
+                .unwrap();
+            let proof = Bip322Proof::from_signed(session.finalize().unwrap());
+            assert_eq!(matches!(proof, Bip322Proof::Simple(_)), *simple);
+
+            let decoded = Bip322Proof::from_base64(&proof.to_base64()).unwrap();
+            assert_eq!(decoded, proof);
+            assert!(verify(script_pubkey, message, &decoded).is_ok());
+            // A full proof's to_sign transaction commits to the message it was signed for
+            match verify(script_pubkey, b"another message", &decoded) {
+                Err(Bip322Error::InvalidSignature) => assert!(*simple),
+                Err(Bip322Error::WrongToSign) => assert!(!*simple),
+                other => panic!("expected a verification failure, got {:?}", other),
+            }
+        }
+
+        let bare = ScriptPubkey::from(vec![0x51]);
+        assert!(matches!(
+            signing_session(&bare, message, None),
+            Err(Bip322Error::UnsupportedScript)
+        ));
+    }
+}
@@ -6,23 +6,259 @@
 //! They can simply use the builder transparently to produce the desired tx type.
 //!
 //! The builder is best accessed via the preconstructed network objects in `nets.rs`.
+//!
+//! Before inputs are signed, the builder can estimate the final weight and vsize of the
+//! transaction from the spend type of each input. This allows fees to be calculated up front.
+//!
+//! The builder can signal BIP125 opt-in replace-by-fee, and can rebuild a signalling transaction
+//! as a higher-fee replacement with `bump_fee`. Given a feerate and a set of selected UTXOs,
+//! `fund` adds the inputs and pays the fee, either into a change output set by `add_change`, or
+//! by deducting it from a recipient set by `subtract_fee_from`.
+//!
+//! By default outputs are kept in insertion order, which may fingerprint the wallet. The builder
+//! can instead shuffle outputs, or sort them per BIP69, when the transaction is built.
 
 use std::marker::PhantomData;
 
-use coins_core::{builder::TxBuilder, enc::AddressEncoder, types::tx::Transaction};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use thiserror::Error;
+
+use coins_core::{
+    builder::TxBuilder,
+    enc::AddressEncoder,
+    ser::{prefix_byte_len, ByteFormat},
+    types::tx::Transaction,
+};
 
 use crate::{
-    enc::encoder::{Address, BitcoinEncoderMarker},
+    enc::encoder::{Address, AddressType, BitcoinEncoderMarker},
     types::{
+        feerate::FeeRate,
         legacy::LegacyTx,
         script::{ScriptPubkey, ScriptSig, Witness},
-        tx::{BitcoinTransaction, BitcoinTx},
+        tx::{BitcoinTransaction, BitcoinTx, TxError},
         txin::{BitcoinOutpoint, BitcoinTxIn},
-        txout::TxOut,
+        txout::{TxOut, DEFAULT_DUST_RELAY_FEERATE},
+        utxo::Utxo,
         witness::{WitnessTransaction, WitnessTx},
     },
 };
 
+/// The maximum size of a low-S DER-encoded ECDSA signature, including the sighash flag byte.
+const MAX_ECDSA_SIG_LEN: usize = 72;
+
+/// The size of a compressed SEC1 public key.
+const COMPRESSED_PUBKEY_LEN: usize = 33;
+
+/// The size of a Schnorr signature using the default sighash flag.
+const SCHNORR_SIG_LEN: usize = 64;
+
+/// The highest sequence number that signals BIP125 opt-in replace-by-fee.
+pub const RBF_SEQUENCE: u32 = 0xffff_fffd;
+
+/// The highest sequence number that enforces the locktime without signalling RBF.
+pub const NON_FINAL_SEQUENCE: u32 = 0xffff_fffe;
+
+/// The maximum number of blocks that anti-fee-sniping may backdate the locktime by.
+pub const MAX_FEE_SNIPING_BACKDATE: u32 = 100;
+
+/// The minimum feerate increase required to replace a transaction, 1 sat/vbyte. This is the
+/// default incremental relay feerate in Bitcoin Core.
+pub const INCREMENTAL_RELAY_FEERATE: FeeRate = FeeRate::from_sat_per_vb(1);
+
+/// An error produced while building a fee-bumping replacement transaction.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum FeeBumpError {
+    /// The original transaction does not signal replaceability
+    #[error("Original transaction does not signal BIP125 replaceability")]
+    NotReplaceable,
+
+    /// The number of input values or input types does not match the number of inputs
+    #[error("Expected input values and types for {} inputs", .0)]
+    InputCountMismatch(usize),
+
+    /// The change index is out of bounds
+    #[error("Change index {} out of bounds", .0)]
+    BadChangeIndex(usize),
+
+    /// The original transaction spends more than its inputs
+    #[error("Original transaction outputs exceed the input values")]
+    InvalidInputValues,
+
+    /// The new feerate does not exceed the original feerate
+    #[error("New feerate of {} does not exceed the original feerate", .0)]
+    FeerateTooLow(FeeRate),
+
+    /// The change output cannot cover the additional fee without becoming dust
+    #[error(
+        "Change output of {available} sat cannot cover the required {required} sat additional fee"
+    )]
+    InsufficientChange {
+        /// The additional fee required
+        required: u64,
+        /// The value of the change output
+        available: u64,
+    },
+}
+
+/// The default maximum size of OP_RETURN data, in bytes. This is the standard relay limit in
+/// Bitcoin Core.
+pub const DEFAULT_MAX_OP_RETURN_SIZE: usize = 80;
+
+/// The order of the outputs in the built transaction.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputOrdering {
+    /// Keep outputs in the order they were added
+    #[default]
+    Preserve,
+    /// Sort outputs by value, then by script pubkey bytes, per BIP69. Inputs are not reordered
+    Bip69,
+    /// Shuffle outputs using an RNG seeded with this value
+    Shuffle(u64),
+}
+
+/// An error produced while adding an OP_RETURN output.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum OpReturnError {
+    /// The data exceeds the configured size limit
+    #[error("OP_RETURN data of {size} bytes exceeds the {limit} byte limit")]
+    TooLarge {
+        /// The size of the data
+        size: usize,
+        /// The configured limit
+        limit: usize,
+    },
+
+    /// The transaction already has an OP_RETURN output
+    #[error("Transaction already has an OP_RETURN output")]
+    MultipleDataOutputs,
+}
+
+/// An error produced while funding a transaction.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum FundingError {
+    /// The inputs cannot pay for the outputs and the fee
+    #[error("Insufficient funds. Required {required} sat. Available {available} sat")]
+    InsufficientFunds {
+        /// The total value of the outputs, plus the fee unless it is subtracted from an output
+        required: u64,
+        /// The total value of the inputs
+        available: u64,
+    },
+
+    /// The output to subtract the fee from is out of bounds
+    #[error("Output index {} out of bounds", .0)]
+    BadOutputIndex(usize),
+
+    /// Subtracting the fee would leave the recipient output as dust
+    #[error("Output {} cannot pay the {} sat fee without becoming dust", .0, .1)]
+    OutputTooSmall(usize, u64),
+
+    /// The value left for the drain output after the fee would be dust
+    #[error("Drain output value {value} sat is below the {limit} sat dust limit")]
+    DrainToDust {
+        /// The value left for the drain output
+        value: u64,
+        /// The dust limit of the drain output
+        limit: u64,
+    },
+}
+
+/// The spend type of a transaction input. Used to estimate the size of the input's script sig
+/// and witness before the input is signed. Estimates assume compressed public keys, and use the
+/// maximum low-S ECDSA signature size, so they may exceed the final size by a few bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputType {
+    /// A P2PKH input
+    Pkh,
+    /// A P2WPKH input
+    Wpkh,
+    /// A P2WPKH input nested in P2SH
+    ShWpkh,
+    /// A P2WSH input spending an `m`-of-`n` `CHECKMULTISIG` script
+    WshMultisig {
+        /// The number of signatures required
+        m: usize,
+        /// The number of public keys in the script
+        n: usize,
+    },
+    /// A P2TR key path spend, using the default sighash flag
+    TrKeyPath,
+}
+
+impl InputType {
+    /// The input type that spends a single-key address of the specified type. `Sh` is assumed to
+    /// be P2WPKH nested in P2SH. Returns `None` for `Wsh`, as the witness script is unknown.
+    pub fn from_address_type(address_type: AddressType) -> Option<Self> {
+        match address_type {
+            AddressType::Pkh => Some(InputType::Pkh),
+            AddressType::Sh => Some(InputType::ShWpkh),
+            AddressType::Wpkh => Some(InputType::Wpkh),
+            AddressType::Wsh => None,
+            AddressType::Tr => Some(InputType::TrKeyPath),
+        }
+    }
+
+    /// The address type of the output spent by this input
+    pub fn address_type(&self) -> AddressType {
+        match self {
+            InputType::Pkh => AddressType::Pkh,
+            InputType::ShWpkh => AddressType::Sh,
+            InputType::Wpkh => AddressType::Wpkh,
+            InputType::WshMultisig { .. } => AddressType::Wsh,
+            InputType::TrKeyPath => AddressType::Tr,
+        }
+    }
+
+    /// True if the input type requires a witness
+    pub fn is_witness(&self) -> bool {
+        self.address_type().is_witness()
+    }
+
+    /// The estimated length of the input's script sig, without its length prefix
+    pub fn script_sig_len(&self) -> usize {
+        match self {
+            // <sig> <pubkey>
+            InputType::Pkh => 1 + MAX_ECDSA_SIG_LEN + 1 + COMPRESSED_PUBKEY_LEN,
+            // <0 <20-byte-hash>>
+            InputType::ShWpkh => 1 + 22,
+            _ => 0,
+        }
+    }
+
+    /// The estimated length of the input's serialized witness, including its item count. This is
+    /// 0 for non-witness inputs.
+    pub fn witness_len(&self) -> usize {
+        match self {
+            InputType::Pkh => 0,
+            // 2 <sig> <pubkey>
+            InputType::Wpkh | InputType::ShWpkh => {
+                1 + 1 + MAX_ECDSA_SIG_LEN + 1 + COMPRESSED_PUBKEY_LEN
+            }
+            // m+2 <> <sig>... <OP_m <pubkey>... OP_n OP_CHECKMULTISIG>
+            InputType::WshMultisig { m, n } => {
+                let script_len = 3 + n * (1 + COMPRESSED_PUBKEY_LEN);
+                prefix_byte_len((m + 2) as u64) as usize
+                    + 1
+                    + m * (1 + MAX_ECDSA_SIG_LEN)
+                    + prefix_byte_len(script_len as u64) as usize
+                    + script_len
+            }
+            // 1 <sig>
+            InputType::TrKeyPath => 1 + 1 + SCHNORR_SIG_LEN,
+        }
+    }
+
+    /// The estimated weight of the input, excluding the 1-byte empty witness that a non-witness
+    /// input requires in a witness transaction.
+    pub fn weight(&self) -> usize {
+        let script_sig_len = self.script_sig_len();
+        // outpoint, script sig, sequence
+        let base_len = 36 + prefix_byte_len(script_sig_len as u64) as usize + script_sig_len + 4;
+        base_len * 4 + self.witness_len()
+    }
+}
+
 /// This is a generic builder for Bitcoin transactions. It allows you to easily build legacy and
 /// witness transactions.
 ///
@@ -39,6 +275,14 @@ pub struct BitcoinTxBuilder<T: AddressEncoder> {
     locktime: u32,
     witnesses: Vec<Witness>,
     produce_witness: bool,
+    rbf: bool,
+    enforce_locktime: bool,
+    dust_relay_feerate: Option<FeeRate>,
+    change: Option<ScriptPubkey>,
+    subtract_fee_from: Option<usize>,
+    max_op_return_size: usize,
+    allow_multiple_op_returns: bool,
+    output_ordering: OutputOrdering,
     encoder: PhantomData<fn(T) -> T>,
 }
 
@@ -55,20 +299,93 @@ where
         self
     }
 
-    /// Insert a witness at a speicified index
-    pub fn insert_witness(
-        mut self,
-        index: usize,
-        witness: <<Self as TxBuilder>::Transaction as Transaction>::TxIn,
-    ) -> Self {
-        let index = std::cmp::min(index, self.vin.len());
-        self.vin.insert(index, witness);
+    /// Insert a witness at a specified index. Witnesses after that are shifted to later indices.
+    pub fn insert_witness(mut self, index: usize, witness: Witness) -> Self {
+        let index = std::cmp::min(index, self.witnesses.len());
+        self.witnesses.insert(index, witness);
+        self
+    }
+
+    /// Set the witness at a specific input. Do nothing if the vin is not that long.
+    pub fn set_witness(mut self, input_idx: usize, witness: Witness) -> Self {
+        if input_idx >= self.vin.len() {
+            return self;
+        }
+        if self.witnesses.len() < self.vin.len() {
+            self.witnesses.resize(self.vin.len(), Witness::default());
+        }
+        self.witnesses[input_idx] = witness;
+        self
+    }
+
+    /// Remove the input at a specific index, along with its witness. Do nothing if the vin is
+    /// not that long.
+    pub fn remove_input(mut self, input_idx: usize) -> Self {
+        if input_idx < self.vin.len() {
+            self.vin.remove(input_idx);
+            if input_idx < self.witnesses.len() {
+                self.witnesses.remove(input_idx);
+            }
+        }
+        self
+    }
+
+    /// Remove the output at a specific index. Do nothing if the vout is not that long.
+    ///
+    /// ## Note
+    ///
+    /// This may invalidate signatures made using SINGLE.
+    pub fn remove_output(mut self, output_idx: usize) -> Self {
+        if output_idx < self.vout.len() {
+            self.vout.remove(output_idx);
+        }
+        self
+    }
+
+    /// Set the value of the output at a specific index. Do nothing if the vout is not that long.
+    pub fn set_output_value(mut self, output_idx: usize, value: u64) -> Self {
+        if let Some(output) = self.vout.get_mut(output_idx) {
+            output.value = value;
+        }
         self
     }
 
-    /// Add an op_return output. Using this twice may render the transaction non-standard.
-    pub fn op_return(mut self, message: &[u8]) -> Self {
-        self.vout.push(TxOut::op_return(message));
+    /// Discard all script sigs and witnesses, so that the transaction can be modified and
+    /// re-signed. Whether a witness transaction is produced is unchanged.
+    pub fn strip_signatures(mut self) -> Self {
+        self.vin
+            .iter_mut()
+            .for_each(|txin| txin.script_sig = ScriptSig::default());
+        self.witnesses.clear();
+        self
+    }
+
+    /// Add an OP_RETURN output carrying `data`. Errors if the data exceeds the maximum size, or
+    /// if the transaction already has an OP_RETURN output and multiple are not allowed. Either
+    /// would render the transaction non-standard.
+    pub fn op_return(mut self, data: &[u8]) -> Result<Self, OpReturnError> {
+        if data.len() > self.max_op_return_size {
+            return Err(OpReturnError::TooLarge {
+                size: data.len(),
+                limit: self.max_op_return_size,
+            });
+        }
+        if !self.allow_multiple_op_returns && self.vout.iter().any(TxOut::is_op_return) {
+            return Err(OpReturnError::MultipleDataOutputs);
+        }
+        self.vout.push(TxOut::op_return(data));
+        Ok(self)
+    }
+
+    /// Set the maximum size of OP_RETURN data. Defaults to `DEFAULT_MAX_OP_RETURN_SIZE`.
+    pub fn max_op_return_size(mut self, size: usize) -> Self {
+        self.max_op_return_size = size;
+        self
+    }
+
+    /// Allow more than one OP_RETURN output. Disallowed by default.
+    pub fn allow_multiple_op_returns(mut self, allow: bool) -> Self {
+        self.allow_multiple_op_returns = allow;
         self
     }
 
@@ -82,13 +399,118 @@ where
         }
     }
 
+    /// Signal BIP125 opt-in replace-by-fee. When set, any input sequence above `RBF_SEQUENCE` is
+    /// lowered to `RBF_SEQUENCE` when the transaction is built. Lower sequences, including
+    /// relative timelocks, are left unchanged.
+    pub fn rbf(mut self, rbf: bool) -> Self {
+        self.rbf = rbf;
+        self
+    }
+
+    /// Discourage fee sniping, as Bitcoin Core does. Sets the locktime to `tip_height`, so that
+    /// the transaction can only be mined in the next block. In 1 of 10 transactions, the
+    /// locktime is instead backdated by up to `MAX_FEE_SNIPING_BACKDATE` blocks, so that
+    /// transactions that were delayed by high latency are not distinguishable.
+    ///
+    /// When the transaction is built, any final input sequence is lowered to
+    /// `NON_FINAL_SEQUENCE`, so that the locktime is enforced.
+    pub fn anti_fee_sniping<R: Rng>(mut self, rng: &mut R, tip_height: u32) -> Self {
+        let mut locktime = tip_height;
+        if rng.gen_ratio(1, 10) {
+            locktime = locktime.saturating_sub(rng.gen_range(0..MAX_FEE_SNIPING_BACKDATE));
+        }
+        self.locktime = locktime;
+        self.enforce_locktime = true;
+        self
+    }
+
+    /// Set the order of the outputs in the built transaction. Defaults to
+    /// `OutputOrdering::Preserve`. The ordering is applied when the transaction is built, so it
+    /// includes outputs added later, e.g. by `fund` or `drain_to`.
+    ///
+    /// ## Note
+    ///
+    /// Reordering outputs invalidates signatures made using SINGLE.
+    pub fn output_ordering(mut self, ordering: OutputOrdering) -> Self {
+        self.output_ordering = ordering;
+        self
+    }
+
+    /// Shuffle the outputs when the transaction is built. This is shorthand for
+    /// `OutputOrdering::Shuffle` with a seed drawn from `rng`.
+    pub fn shuffle_outputs<R: Rng>(self, rng: &mut R) -> Self {
+        let seed = rng.gen();
+        self.output_ordering(OutputOrdering::Shuffle(seed))
+    }
+
+    /// Reject outputs below their dust limit at `relay_feerate` when the transaction is built.
+    /// By default, dust outputs are allowed.
+    pub fn reject_dust(mut self, relay_feerate: FeeRate) -> Self {
+        self.dust_relay_feerate = Some(relay_feerate);
+        self
+    }
+
+    /// Return the indices of outputs below their dust limit at `relay_feerate`. This allows
+    /// callers to warn about dust outputs without rejecting them.
+    pub fn dust_outputs(&self, relay_feerate: FeeRate) -> Vec<usize> {
+        self.vout
+            .iter()
+            .enumerate()
+            .filter(|(_, output)| output.is_dust(relay_feerate))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    fn check_dust(&self) -> Result<(), TxError> {
+        let relay_feerate = match self.dust_relay_feerate {
+            Some(relay_feerate) => relay_feerate,
+            None => return Ok(()),
+        };
+        match self.dust_outputs(relay_feerate).first() {
+            Some(&index) => Err(TxError::DustOutput {
+                index,
+                value: self.vout[index].value,
+                limit: self.vout[index].dust_limit(relay_feerate),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn apply_output_ordering(&mut self) {
+        match self.output_ordering {
+            OutputOrdering::Preserve => {}
+            OutputOrdering::Bip69 => self.vout.sort(),
+            OutputOrdering::Shuffle(seed) => {
+                self.vout.shuffle(&mut StdRng::seed_from_u64(seed));
+            }
+        }
+    }
+
+    fn apply_sequences(&mut self) {
+        if self.enforce_locktime {
+            self.vin
+                .iter_mut()
+                .filter(|txin| txin.sequence > NON_FINAL_SEQUENCE)
+                .for_each(|txin| txin.sequence = NON_FINAL_SEQUENCE);
+        }
+        if self.rbf {
+            self.vin.iter_mut().for_each(BitcoinTxIn::enable_rbf);
+        }
+    }
+
     /// Consume self, produce a legacy tx. Discard any witness information in the builder
-    pub fn build_legacy(self) -> Result<LegacyTx, <LegacyTx as Transaction>::TxError> {
+    pub fn build_legacy(mut self) -> Result<LegacyTx, <LegacyTx as Transaction>::TxError> {
+        self.apply_output_ordering();
+        self.check_dust()?;
+        self.apply_sequences();
         LegacyTx::new(self.version, self.vin, self.vout, self.locktime)
     }
 
     /// Consume self, produce a witness tx
-    pub fn build_witness(self) -> Result<WitnessTx, <WitnessTx as Transaction>::TxError> {
+    pub fn build_witness(mut self) -> Result<WitnessTx, <WitnessTx as Transaction>::TxError> {
+        self.apply_output_ordering();
+        self.check_dust()?;
+        self.apply_sequences();
         <WitnessTx as WitnessTransaction>::new(
             self.version,
             self.vin,
@@ -98,6 +520,260 @@ where
         )
     }
 
+    /// Estimate the weight of the final transaction, given the spend type of each input, in
+    /// order. Returns `None` if the number of input types does not match the number of inputs.
+    /// Outputs are measured as currently set in the builder.
+    pub fn estimate_weight(&self, input_types: &[InputType]) -> Option<usize> {
+        if input_types.len() != self.vin.len() {
+            return None;
+        }
+        let has_witness = input_types.iter().any(InputType::is_witness);
+
+        // version, vin length, vout length, locktime
+        let mut weight = 4
+            * (4 + prefix_byte_len(self.vin.len() as u64) as usize
+                + prefix_byte_len(self.vout.len() as u64) as usize
+                + 4);
+        weight += 4 * self
+            .vout
+            .iter()
+            .map(TxOut::serialized_length)
+            .sum::<usize>();
+        weight += input_types.iter().map(InputType::weight).sum::<usize>();
+        if has_witness {
+            // segwit marker and flag, plus an empty witness for each non-witness input
+            weight += 2;
+            weight += input_types.iter().filter(|t| !t.is_witness()).count();
+        }
+        Some(weight)
+    }
+
+    /// Estimate the virtual size of the final transaction in vbytes, given the spend type of each
+    /// input, in order. Returns `None` if the number of input types does not match the number of
+    /// inputs.
+    pub fn estimate_vsize(&self, input_types: &[InputType]) -> Option<usize> {
+        self.estimate_weight(input_types)
+            .map(|weight| weight.div_ceil(4))
+    }
+
+    /// Estimate the fee of the final transaction at `feerate`, given the spend type of each
+    /// input, in order. The fee is rounded up, as in Bitcoin Core. Returns `None` if the number
+    /// of input types does not match the number of inputs.
+    pub fn estimate_fee(&self, input_types: &[InputType], feerate: FeeRate) -> Option<u64> {
+        self.estimate_weight(input_types)
+            .map(|weight| feerate.fee_for_weight(weight))
+    }
+
+    /// Rebuild a replacement for `original` that pays `new_feerate`. The replacement
+    /// spends the same inputs and pays the same outputs, except that the additional fee is
+    /// deducted from the output at `change_index`. Signatures are discarded, and the returned
+    /// builder signals RBF.
+    ///
+    /// `input_values` and `input_types` describe the outputs spent by each input, in order.
+    ///
+    /// The BIP125 replacement rules are enforced. The original must signal replaceability, and
+    /// the replacement must pay a higher feerate, a higher absolute fee, and an additional fee
+    /// of at least `INCREMENTAL_RELAY_FEERATE` for its own vsize. Errors if the change output
+    /// cannot cover the additional fee without becoming dust.
+    pub fn bump_fee(
+        original: &BitcoinTx,
+        input_values: &[u64],
+        input_types: &[InputType],
+        change_index: usize,
+        new_feerate: FeeRate,
+    ) -> Result<Self, FeeBumpError> {
+        let n = original.inputs().len();
+        if input_values.len() != n || input_types.len() != n {
+            return Err(FeeBumpError::InputCountMismatch(n));
+        }
+        if !original.signals_rbf() {
+            return Err(FeeBumpError::NotReplaceable);
+        }
+        let change = original
+            .outputs()
+            .get(change_index)
+            .ok_or(FeeBumpError::BadChangeIndex(change_index))?
+            .value;
+
+        let input_value: u64 = input_values.iter().sum();
+        let output_value: u64 = original.outputs().iter().map(|o| o.value).sum();
+        let original_fee = input_value
+            .checked_sub(output_value)
+            .ok_or(FeeBumpError::InvalidInputValues)?;
+
+        // Compare against the original's actual vsize. Rounding the original feerate down is
+        // safe, as replacements must strictly exceed it
+        let original_feerate =
+            FeeRate::from_fee_and_vsize(original_fee, original.vsize()).unwrap_or(FeeRate::ZERO);
+        if new_feerate <= original_feerate {
+            return Err(FeeBumpError::FeerateTooLow(new_feerate));
+        }
+
+        let mut builder = Self::from_tx_ref(original).rbf(true).strip_signatures();
+        builder.produce_witness = input_types.iter().any(InputType::is_witness);
+
+        let vsize = builder
+            .estimate_vsize(input_types)
+            .expect("checked input count");
+        let required_fee = std::cmp::max(
+            new_feerate.fee_for_vsize(vsize),
+            original_fee + INCREMENTAL_RELAY_FEERATE.fee_for_vsize(vsize),
+        );
+        let additional = required_fee - original_fee;
+        let change_output = &mut builder.vout[change_index];
+        if change < additional
+            || change - additional < change_output.dust_limit(DEFAULT_DUST_RELAY_FEERATE)
+        {
+            return Err(FeeBumpError::InsufficientChange {
+                required: additional,
+                available: change,
+            });
+        }
+        change_output.value = change - additional;
+        Ok(builder)
+    }
+
+    /// Set the script pubkey that receives change when the transaction is funded. Change that
+    /// would be dust is dropped, and paid to fees instead.
+    pub fn add_change(mut self, script_pubkey: ScriptPubkey) -> Self {
+        self.change = Some(script_pubkey);
+        self
+    }
+
+    /// Deduct the fee from the output at `output_index` when the transaction is funded, instead
+    /// of paying it from the inputs.
+    pub fn subtract_fee_from(mut self, output_index: usize) -> Self {
+        self.subtract_fee_from = Some(output_index);
+        self
+    }
+
+    /// Spend the selected UTXOs, and pay `feerate`. Each UTXO is spent with sequence
+    /// `0xffff_fffe`, so that the locktime is enforced. Any existing inputs are discarded, as
+    /// their values are unknown.
+    ///
+    /// By default, the fee is paid from the inputs. If `subtract_fee_from` was set, the fee is
+    /// instead deducted from that output. If `add_change` was set, any remaining value is paid
+    /// to a change output at the end of the vout, unless it would be dust. If no change is set,
+    /// the remaining value is paid to fees.
+    pub fn fund(
+        mut self,
+        utxos: &[(Utxo, InputType)],
+        feerate: FeeRate,
+    ) -> Result<Self, FundingError> {
+        if let Some(idx) = self.subtract_fee_from {
+            if idx >= self.vout.len() {
+                return Err(FundingError::BadOutputIndex(idx));
+            }
+        }
+
+        let input_types = self.spend_utxos(utxos);
+        let available: u64 = utxos.iter().map(|(utxo, _)| utxo.value).sum();
+        let outputs: u64 = self.vout.iter().map(|o| o.value).sum();
+        let fee_without_change = self
+            .estimate_fee(&input_types, feerate)
+            .expect("input count set above");
+
+        // The value left after outputs. When subtracting the fee, outputs are not yet reduced
+        let required = if self.subtract_fee_from.is_some() {
+            outputs
+        } else {
+            outputs + fee_without_change
+        };
+        let excess = available
+            .checked_sub(required)
+            .ok_or(FundingError::InsufficientFunds {
+                required,
+                available,
+            })?;
+
+        let mut fee = fee_without_change;
+        let mut change_added = false;
+        if let Some(script_pubkey) = self.change.clone() {
+            self.vout.push(TxOut::new(0, script_pubkey));
+            let fee_with_change = self
+                .estimate_fee(&input_types, feerate)
+                .expect("input count set above");
+            let change_value = if self.subtract_fee_from.is_some() {
+                excess
+            } else {
+                excess.saturating_sub(fee_with_change - fee_without_change)
+            };
+            let change_output = self.vout.last_mut().expect("just pushed");
+            change_output.value = change_value;
+            if !change_output.is_dust(DEFAULT_DUST_RELAY_FEERATE) {
+                fee = fee_with_change;
+                change_added = true;
+            } else {
+                self.vout.pop();
+            }
+        }
+
+        if let Some(idx) = self.subtract_fee_from {
+            // Without change, any excess is paid to fees
+            let deduction = if change_added {
+                fee
+            } else {
+                fee.saturating_sub(excess)
+            };
+            let output = &mut self.vout[idx];
+            if output.value < deduction
+                || output.value - deduction < output.dust_limit(DEFAULT_DUST_RELAY_FEERATE)
+            {
+                return Err(FundingError::OutputTooSmall(idx, deduction));
+            }
+            output.value -= deduction;
+        }
+        Ok(self)
+    }
+
+    /// Spend all of the UTXOs, paying everything left after the existing outputs and the fee
+    /// to a new output at the end of the vout. With no other outputs, this sweeps the UTXOs to
+    /// `script_pubkey`. Inputs are set as in `fund`. The `add_change` and `subtract_fee_from`
+    /// settings are ignored.
+    pub fn drain_to(
+        mut self,
+        utxos: &[(Utxo, InputType)],
+        script_pubkey: ScriptPubkey,
+        feerate: FeeRate,
+    ) -> Result<Self, FundingError> {
+        let input_types = self.spend_utxos(utxos);
+        let available: u64 = utxos.iter().map(|(utxo, _)| utxo.value).sum();
+        let outputs: u64 = self.vout.iter().map(|o| o.value).sum();
+
+        self.vout.push(TxOut::new(0, script_pubkey));
+        let fee = self
+            .estimate_fee(&input_types, feerate)
+            .expect("input count set above");
+        let required = outputs + fee;
+        let value = available
+            .checked_sub(required)
+            .ok_or(FundingError::InsufficientFunds {
+                required,
+                available,
+            })?;
+
+        let drain_output = self.vout.last_mut().expect("just pushed");
+        drain_output.value = value;
+        let limit = drain_output.dust_limit(DEFAULT_DUST_RELAY_FEERATE);
+        if value < limit {
+            return Err(FundingError::DrainToDust { value, limit });
+        }
+        Ok(self)
+    }
+
+    /// Replace the inputs with the UTXOs, and return their input types
+    fn spend_utxos(&mut self, utxos: &[(Utxo, InputType)]) -> Vec<InputType> {
+        self.vin = utxos
+            .iter()
+            .map(|(utxo, _)| {
+                BitcoinTxIn::new(utxo.outpoint, ScriptSig::default(), NON_FINAL_SEQUENCE)
+            })
+            .collect();
+        let input_types: Vec<InputType> = utxos.iter().map(|(_, t)| *t).collect();
+        self.produce_witness = input_types.iter().any(InputType::is_witness);
+        input_types
+    }
+
     /// Add an output paying `value` to `script_pubkey`
     pub fn pay_script_pubkey(mut self, value: u64, script_pubkey: ScriptPubkey) -> Self {
         let output = TxOut::new(value, script_pubkey);
@@ -106,6 +782,24 @@ where
     }
 }
 
+impl<T> From<LegacyTx> for BitcoinTxBuilder<T>
+where
+    T: BitcoinEncoderMarker,
+{
+    fn from(tx: LegacyTx) -> Self {
+        Self::from_tx(tx.into())
+    }
+}
+
+impl<T> From<WitnessTx> for BitcoinTxBuilder<T>
+where
+    T: BitcoinEncoderMarker,
+{
+    fn from(tx: WitnessTx) -> Self {
+        Self::from_tx(tx.into())
+    }
+}
+
 impl<T> TxBuilder for BitcoinTxBuilder<T>
 where
     T: BitcoinEncoderMarker,
@@ -121,6 +815,14 @@ where
             locktime: 0,
             witnesses: vec![],
             produce_witness: false,
+            rbf: false,
+            enforce_locktime: false,
+            dust_relay_feerate: None,
+            change: None,
+            subtract_fee_from: None,
+            max_op_return_size: DEFAULT_MAX_OP_RETURN_SIZE,
+            allow_multiple_op_returns: false,
+            output_ordering: OutputOrdering::Preserve,
             encoder: PhantomData,
         }
     }
@@ -133,6 +835,14 @@ where
             locktime: tx.locktime(),
             witnesses: tx.witnesses().to_vec(),
             produce_witness: tx.is_witness(),
+            rbf: false,
+            enforce_locktime: false,
+            dust_relay_feerate: None,
+            change: None,
+            subtract_fee_from: None,
+            max_op_return_size: DEFAULT_MAX_OP_RETURN_SIZE,
+            allow_multiple_op_returns: false,
+            output_ordering: OutputOrdering::Preserve,
             encoder: PhantomData,
         }
     }
@@ -145,6 +855,14 @@ where
             locktime: tx.locktime(),
             witnesses: tx.witnesses().to_vec(),
             produce_witness: tx.is_witness(),
+            rbf: false,
+            enforce_locktime: false,
+            dust_relay_feerate: None,
+            change: None,
+            subtract_fee_from: None,
+            max_op_return_size: DEFAULT_MAX_OP_RETURN_SIZE,
+            allow_multiple_op_returns: false,
+            output_ordering: OutputOrdering::Preserve,
             encoder: PhantomData,
         }
     }
@@ -178,6 +896,10 @@ where
     ) -> Self {
         let index = std::cmp::min(index, self.vin.len());
         self.vin.insert(index, input);
+        // keep existing witnesses aligned with their inputs
+        if index < self.witnesses.len() {
+            self.witnesses.insert(index, Witness::default());
+        }
         self
     }
 
@@ -212,7 +934,10 @@ where
         self
     }
 
-    fn build(self) -> Result<Self::Transaction, <Self::Transaction as Transaction>::TxError> {
+    fn build(mut self) -> Result<Self::Transaction, <Self::Transaction as Transaction>::TxError> {
+        self.apply_output_ordering();
+        self.check_dust()?;
+        self.apply_sequences();
         if self.produce_witness || !self.witnesses.is_empty() {
             Ok(<WitnessTx as WitnessTransaction>::new(
                 self.version,
@@ -227,3 +952,506 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        enc::MainnetEncoder,
+        types::{tx::BitcoinTransaction, utxo::SpendScript},
+    };
+
+    type Builder = BitcoinTxBuilder<MainnetEncoder>;
+
+    fn spk(hex: &str) -> ScriptPubkey {
+        ScriptPubkey::from_hex(hex).unwrap()
+    }
+
+    #[test]
+    fn it_estimates_input_weights() {
+        let cases = [
+            (InputType::Pkh, 592),
+            (InputType::Wpkh, 272),
+            (InputType::ShWpkh, 364),
+            (InputType::WshMultisig { m: 2, n: 3 }, 418),
+            (InputType::TrKeyPath, 230),
+        ];
+        for case in cases.iter() {
+            assert_eq!(case.0.weight(), case.1);
+        }
+    }
+
+    #[test]
+    fn it_estimates_tx_sizes() {
+        let p2pkh = spk("76a9141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b92888ac");
+        let p2wpkh = spk("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928");
+        let p2tr = spk("5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c");
+
+        let builder = Builder::new()
+            .spend(BitcoinOutpoint::default(), 0xffff_ffff)
+            .pay_script_pubkey(1, p2pkh.clone())
+            .pay_script_pubkey(1, p2pkh.clone());
+        assert_eq!(builder.estimate_vsize(&[InputType::Pkh]), Some(226));
+        assert_eq!(builder.estimate_vsize(&[]), None);
+
+        let builder = Builder::new()
+            .spend(BitcoinOutpoint::default(), 0xffff_ffff)
+            .pay_script_pubkey(1, p2wpkh);
+        assert_eq!(builder.estimate_vsize(&[InputType::Wpkh]), Some(110));
+        assert_eq!(
+            builder.estimate_fee(&[InputType::Wpkh], FeeRate::from_sat_per_kvb(1_501)),
+            Some(166)
+        );
+
+        let builder = Builder::new()
+            .spend(BitcoinOutpoint::default(), 0xffff_ffff)
+            .pay_script_pubkey(1, p2tr);
+        assert_eq!(builder.estimate_vsize(&[InputType::TrKeyPath]), Some(111));
+
+        // A signed tx with 1 p2wpkh input and a 72-byte signature
+        let tx = BitcoinTx::deserialize_hex("0100000000010115e180dc28a2327e687facc33f10f2a20da717e5548406f7ae8b4c811072f8560100000000ffffffff0100b4f505000000001976a9141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b92888ac02483045022100df7b7e5cda14ddf91290e02ea10786e03eb11ee36ec02dd862fe9a326bbcb7fd02203f5b4496b667e6e281cc654a2da9e4f08660c620a1051337fa8965f727eb19190121038262a6c6cec93c2d3ecd6c6072efea86d02ff8e3328bbd0242b20af3425990ac00000000").unwrap();
+        let weight = tx.as_legacy().serialized_length() * 3 + tx.serialized_length();
+        let mut builder = Builder::from_tx_ref(&tx);
+        builder.witnesses.clear();
+        assert_eq!(builder.estimate_weight(&[InputType::Wpkh]), Some(weight));
+    }
+
+    #[test]
+    fn it_signals_rbf() {
+        let tx = Builder::new()
+            .spend(BitcoinOutpoint::default(), 0xffff_ffff)
+            .spend(BitcoinOutpoint::default(), 0xffff_fffe)
+            .spend(BitcoinOutpoint::default(), 144)
+            .pay_script_pubkey(1, spk("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928"))
+            .rbf(true)
+            .build()
+            .unwrap();
+        let sequences: Vec<u32> = tx.inputs().iter().map(|i| i.sequence).collect();
+        assert_eq!(sequences, vec![RBF_SEQUENCE, RBF_SEQUENCE, 144]);
+    }
+
+    #[test]
+    fn it_bumps_fees() {
+        let p2wpkh = spk("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928");
+        // The signed tx above pays 100_000_000 - 99_988_480 = 11_520 sat in 113 vbytes
+        let tx = BitcoinTx::deserialize_hex("0100000000010115e180dc28a2327e687facc33f10f2a20da717e5548406f7ae8b4c811072f8560100000000ffffffff0100b4f505000000001976a9141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b92888ac02483045022100df7b7e5cda14ddf91290e02ea10786e03eb11ee36ec02dd862fe9a326bbcb7fd02203f5b4496b667e6e281cc654a2da9e4f08660c620a1051337fa8965f727eb19190121038262a6c6cec93c2d3ecd6c6072efea86d02ff8e3328bbd0242b20af3425990ac00000000").unwrap();
+        let values = [100_000_000];
+        let types = [InputType::Wpkh];
+        assert_eq!(
+            Builder::bump_fee(&tx, &values, &types, 0, FeeRate::from_sat_per_vb(200)).unwrap_err(),
+            FeeBumpError::NotReplaceable
+        );
+
+        let original = Builder::from_tx_ref(&tx).rbf(true).build().unwrap();
+        assert_eq!(
+            Builder::bump_fee(&original, &values, &types, 1, FeeRate::from_sat_per_vb(200))
+                .unwrap_err(),
+            FeeBumpError::BadChangeIndex(1)
+        );
+        assert_eq!(
+            Builder::bump_fee(&original, &[], &types, 0, FeeRate::from_sat_per_vb(200))
+                .unwrap_err(),
+            FeeBumpError::InputCountMismatch(1)
+        );
+        assert_eq!(
+            Builder::bump_fee(&original, &[1], &types, 0, FeeRate::from_sat_per_vb(200))
+                .unwrap_err(),
+            FeeBumpError::InvalidInputValues
+        );
+        assert_eq!(
+            Builder::bump_fee(&original, &values, &types, 0, FeeRate::from_sat_per_vb(101))
+                .unwrap_err(),
+            FeeBumpError::FeerateTooLow(FeeRate::from_sat_per_vb(101))
+        );
+
+        // 113 vbytes at 102 sat/vbyte is only 6 sat more, less than the incremental relay fee
+        let replacement =
+            Builder::bump_fee(&original, &values, &types, 0, FeeRate::from_sat_per_vb(102))
+                .unwrap()
+                .build()
+                .unwrap();
+        assert_eq!(replacement.outputs()[0].value, 99_988_480 - 113);
+        assert!(replacement.witnesses()[0].is_empty());
+
+        let replacement =
+            Builder::bump_fee(&original, &values, &types, 0, FeeRate::from_sat_per_vb(200))
+                .unwrap()
+                .build()
+                .unwrap();
+        assert_eq!(replacement.inputs()[0].sequence, RBF_SEQUENCE);
+        assert_eq!(replacement.outputs()[0].value, 100_000_000 - 113 * 200);
+
+        let small_change = Builder::new()
+            .version(2)
+            .spend(BitcoinOutpoint::default(), RBF_SEQUENCE)
+            .pay_script_pubkey(1000, p2wpkh)
+            .build()
+            .unwrap();
+        assert_eq!(
+            Builder::bump_fee(
+                &small_change,
+                &[1200],
+                &types,
+                0,
+                FeeRate::from_sat_per_vb(10)
+            )
+            .unwrap_err(),
+            FeeBumpError::InsufficientChange {
+                required: 900,
+                available: 1000
+            }
+        );
+    }
+
+    #[test]
+    fn it_funds_transactions() {
+        let p2wpkh = spk("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928");
+        let utxo = |value| {
+            let outpoint = BitcoinOutpoint::default();
+            let utxo = Utxo::new(outpoint, value, p2wpkh.clone(), SpendScript::None);
+            [(utxo, InputType::Wpkh)]
+        };
+        let builder = || Builder::new().pay_script_pubkey(50_000, p2wpkh.clone());
+
+        // 110 vbytes without change, 141 vbytes with change
+        let tx = builder()
+            .add_change(p2wpkh.clone())
+            .fund(&utxo(100_000), FeeRate::from_sat_per_vb(10))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(tx.inputs()[0].sequence, NON_FINAL_SEQUENCE);
+        assert_eq!(tx.outputs()[1].value, 100_000 - 50_000 - 1410);
+
+        // Dust change is dropped
+        let tx = builder()
+            .add_change(p2wpkh.clone())
+            .fund(&utxo(51_500), FeeRate::from_sat_per_vb(10))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(tx.outputs().len(), 1);
+
+        assert_eq!(
+            builder()
+                .fund(&utxo(50_500), FeeRate::from_sat_per_vb(10))
+                .unwrap_err(),
+            FundingError::InsufficientFunds {
+                required: 51_100,
+                available: 50_500
+            }
+        );
+        assert_eq!(
+            builder()
+                .subtract_fee_from(3)
+                .fund(&utxo(50_500), FeeRate::from_sat_per_vb(10))
+                .unwrap_err(),
+            FundingError::BadOutputIndex(3)
+        );
+
+        let tx = builder()
+            .add_change(p2wpkh.clone())
+            .subtract_fee_from(0)
+            .fund(&utxo(100_000), FeeRate::from_sat_per_vb(10))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(tx.outputs()[0].value, 50_000 - 1410);
+        assert_eq!(tx.outputs()[1].value, 50_000);
+
+        // The dust excess pays part of the fee
+        let tx = Builder::new()
+            .pay_script_pubkey(99_800, p2wpkh.clone())
+            .add_change(p2wpkh.clone())
+            .subtract_fee_from(0)
+            .fund(&utxo(100_000), FeeRate::from_sat_per_vb(10))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(tx.outputs().len(), 1);
+        assert_eq!(tx.outputs()[0].value, 99_800 - 900);
+
+        assert_eq!(
+            builder()
+                .subtract_fee_from(0)
+                .fund(&utxo(1000), FeeRate::from_sat_per_vb(10))
+                .unwrap_err(),
+            FundingError::InsufficientFunds {
+                required: 50_000,
+                available: 1000
+            }
+        );
+        assert_eq!(
+            Builder::new()
+                .pay_script_pubkey(1300, p2wpkh.clone())
+                .subtract_fee_from(0)
+                .fund(&utxo(1300), FeeRate::from_sat_per_vb(10))
+                .unwrap_err(),
+            FundingError::OutputTooSmall(0, 1100)
+        );
+    }
+
+    #[test]
+    fn it_drains_utxos() {
+        let p2wpkh = spk("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928");
+        let utxo = |value| {
+            let outpoint = BitcoinOutpoint::default();
+            let utxo = Utxo::new(outpoint, value, p2wpkh.clone(), SpendScript::None);
+            [(utxo, InputType::Wpkh)]
+        };
+
+        // 110 vbytes
+        let tx = Builder::new()
+            .drain_to(&utxo(100_000), p2wpkh.clone(), FeeRate::from_sat_per_vb(10))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(tx.outputs().len(), 1);
+        assert_eq!(tx.outputs()[0].value, 100_000 - 1100);
+
+        // Send max after paying the existing outputs. 141 vbytes
+        let tx = Builder::new()
+            .pay_script_pubkey(50_000, p2wpkh.clone())
+            .drain_to(&utxo(100_000), p2wpkh.clone(), FeeRate::from_sat_per_vb(10))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(tx.outputs()[0].value, 50_000);
+        assert_eq!(tx.outputs()[1].value, 100_000 - 50_000 - 1410);
+
+        assert_eq!(
+            Builder::new()
+                .drain_to(&utxo(1300), p2wpkh.clone(), FeeRate::from_sat_per_vb(10))
+                .unwrap_err(),
+            FundingError::DrainToDust {
+                value: 200,
+                limit: 294
+            }
+        );
+        assert_eq!(
+            Builder::new()
+                .drain_to(&utxo(1000), p2wpkh.clone(), FeeRate::from_sat_per_vb(10))
+                .unwrap_err(),
+            FundingError::InsufficientFunds {
+                required: 1100,
+                available: 1000
+            }
+        );
+    }
+
+    #[test]
+    fn it_adds_op_returns() {
+        let builder = Builder::new().op_return(&[0xab; 80]).unwrap();
+        let script = &builder.vout[0].script_pubkey;
+        // OP_RETURN PUSHDATA1 80
+        assert_eq!(&script.items()[..3], &[0x6a, 0x4c, 0x50]);
+        assert_eq!(
+            builder.vout[0].extract_op_return_data(),
+            Some(vec![0xab; 80])
+        );
+
+        assert_eq!(
+            builder.clone().op_return(&[0x01]).unwrap_err(),
+            OpReturnError::MultipleDataOutputs
+        );
+        let builder = builder
+            .allow_multiple_op_returns(true)
+            .op_return(&[0x01])
+            .unwrap();
+        assert_eq!(builder.vout[1].script_pubkey.items(), &[0x6a, 0x01, 0x01]);
+
+        assert_eq!(
+            Builder::new().op_return(&[0; 81]).unwrap_err(),
+            OpReturnError::TooLarge {
+                size: 81,
+                limit: 80
+            }
+        );
+        assert!(Builder::new()
+            .max_op_return_size(100)
+            .op_return(&[0; 81])
+            .is_ok());
+    }
+
+    #[test]
+    fn it_discourages_fee_sniping() {
+        let build = |seed: u64, tip_height: u32| {
+            Builder::new()
+                .spend(BitcoinOutpoint::default(), 0xffff_ffff)
+                .spend(BitcoinOutpoint::default(), 144)
+                .pay_script_pubkey(1, spk("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928"))
+                .anti_fee_sniping(&mut StdRng::seed_from_u64(seed), tip_height)
+                .build()
+                .unwrap()
+        };
+
+        // Usually the locktime is the tip
+        let tx = build(0, 700_000);
+        assert_eq!(tx.locktime(), 700_000);
+        assert_eq!(tx.inputs()[0].sequence, NON_FINAL_SEQUENCE);
+        assert_eq!(tx.inputs()[1].sequence, 144);
+
+        // Sometimes it is backdated
+        let tx = build(3, 700_000);
+        assert!(tx.locktime() < 700_000);
+        assert!(tx.locktime() > 700_000 - MAX_FEE_SNIPING_BACKDATE);
+        assert_eq!(tx.inputs()[0].sequence, NON_FINAL_SEQUENCE);
+
+        // Backdating saturates at 0
+        let tx = build(3, 50);
+        assert_eq!(tx.locktime(), 0);
+
+        let tx = Builder::new()
+            .spend(BitcoinOutpoint::default(), 0xffff_ffff)
+            .pay_script_pubkey(1, spk("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928"))
+            .anti_fee_sniping(&mut StdRng::seed_from_u64(0), 50)
+            .rbf(true)
+            .build()
+            .unwrap();
+        assert_eq!(tx.locktime(), 50);
+        assert_eq!(tx.inputs()[0].sequence, RBF_SEQUENCE);
+    }
+
+    #[test]
+    fn it_edits_existing_transactions() {
+        let tx = BitcoinTx::deserialize_hex("0100000000010115e180dc28a2327e687facc33f10f2a20da717e5548406f7ae8b4c811072f8560100000000ffffffff0100b4f505000000001976a9141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b92888ac02483045022100df7b7e5cda14ddf91290e02ea10786e03eb11ee36ec02dd862fe9a326bbcb7fd02203f5b4496b667e6e281cc654a2da9e4f08660c620a1051337fa8965f727eb19190121038262a6c6cec93c2d3ecd6c6072efea86d02ff8e3328bbd0242b20af3425990ac00000000").unwrap();
+        let witness_tx = tx.clone().into_witness();
+        let signed = witness_tx.witnesses()[0].clone();
+
+        // Round trips unchanged
+        let rebuilt = Builder::from(witness_tx.clone()).build().unwrap();
+        assert_eq!(rebuilt, tx);
+
+        let new_input = BitcoinTxIn::new(BitcoinOutpoint::default(), ScriptSig::default(), 0);
+        let edited = Builder::from(witness_tx.clone())
+            .insert_input(0, new_input)
+            .set_output_value(0, 1000)
+            .pay_script_pubkey(2000, spk("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928"))
+            .build()
+            .unwrap();
+        assert_eq!(edited.inputs().len(), 2);
+        assert!(edited.witnesses()[0].is_empty());
+        assert_eq!(edited.witnesses()[1], signed);
+        assert_eq!(edited.outputs()[0].value, 1000);
+
+        let edited = Builder::from(edited.into_witness())
+            .remove_input(0)
+            .remove_output(1)
+            .set_output_value(0, 99_988_480)
+            .build()
+            .unwrap();
+        assert_eq!(edited, tx);
+
+        let stripped = Builder::from(witness_tx)
+            .strip_signatures()
+            .build()
+            .unwrap();
+        assert!(stripped.is_witness());
+        assert!(stripped.witnesses()[0].is_empty());
+        assert_eq!(stripped.txid(), tx.txid());
+
+        let resigned = Builder::from(stripped.into_witness())
+            .set_witness(0, signed)
+            .build()
+            .unwrap();
+        assert_eq!(resigned, tx);
+
+        let legacy = Builder::from(tx.into_legacy()).build().unwrap();
+        assert!(!legacy.is_witness());
+    }
+
+    #[test]
+    fn it_rejects_dust() {
+        let p2pkh = spk("76a9141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b92888ac");
+        let p2wpkh = spk("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928");
+        let builder = Builder::new()
+            .spend(BitcoinOutpoint::default(), 0xffff_ffff)
+            .pay_script_pubkey(400, p2pkh)
+            .pay_script_pubkey(400, p2wpkh);
+        assert_eq!(builder.dust_outputs(DEFAULT_DUST_RELAY_FEERATE), vec![0]);
+        assert!(builder.clone().build().is_ok());
+
+        match builder
+            .reject_dust(DEFAULT_DUST_RELAY_FEERATE)
+            .build_legacy()
+        {
+            Err(TxError::DustOutput {
+                index: 0,
+                value: 400,
+                limit: 546,
+            }) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn it_orders_outputs() {
+        let a = spk("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928");
+        let b = spk("0014e88869b88866281ab166541ad8aafba8f8ab");
+        let c = spk("76a9141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b92888ac");
+        let builder = Builder::new()
+            .spend(BitcoinOutpoint::default(), 0xffff_ffff)
+            .pay_script_pubkey(3_000, c.clone())
+            .pay_script_pubkey(1_000, b.clone())
+            .pay_script_pubkey(1_000, a.clone())
+            .pay_script_pubkey(2_000, b.clone());
+        let scripts = |tx: &BitcoinTx| -> Vec<(u64, ScriptPubkey)> {
+            tx.outputs()
+                .iter()
+                .map(|o| (o.value, o.script_pubkey.clone()))
+                .collect()
+        };
+
+        let tx = builder.clone().build().unwrap();
+        assert_eq!(tx.outputs()[0].script_pubkey, c);
+
+        // Ties on value are broken by script pubkey bytes
+        let tx = builder
+            .clone()
+            .output_ordering(OutputOrdering::Bip69)
+            .build()
+            .unwrap();
+        assert_eq!(
+            scripts(&tx),
+            vec![
+                (1_000, a),
+                (1_000, b.clone()),
+                (2_000, b),
+                (3_000, c.clone())
+            ]
+        );
+
+        // Outputs added after the ordering is set are ordered too
+        let sorted = builder
+            .clone()
+            .output_ordering(OutputOrdering::Bip69)
+            .pay_script_pubkey(500, c)
+            .build_legacy()
+            .unwrap();
+        assert_eq!(sorted.outputs()[0].value, 500);
+
+        // Shuffles are reproducible from the seed, and keep every output
+        let shuffled = builder
+            .clone()
+            .output_ordering(OutputOrdering::Shuffle(7))
+            .build()
+            .unwrap();
+        let again = builder
+            .clone()
+            .output_ordering(OutputOrdering::Shuffle(7))
+            .build()
+            .unwrap();
+        assert_eq!(shuffled, again);
+        let mut outputs = scripts(&shuffled);
+        let mut expected = scripts(&builder.clone().build().unwrap());
+        outputs.sort_by(|x, y| x.0.cmp(&y.0).then_with(|| x.1.items().cmp(y.1.items())));
+        expected.sort_by(|x, y| x.0.cmp(&y.0).then_with(|| x.1.items().cmp(y.1.items())));
+        assert_eq!(outputs, expected);
+
+        let tx = builder
+            .shuffle_outputs(&mut rand::thread_rng())
+            .build()
+            .unwrap();
+        assert_eq!(tx.outputs().len(), 4);
+    }
+}
@@ -0,0 +1,228 @@
+//! Classification of transactions from the perspective of a wallet.
+//!
+//! Given the set of script pubkeys a wallet controls, a `TxClassifier` labels a transaction as
+//! incoming, outgoing, a self-transfer, a consolidation, or coinjoin-like, and computes the net
+//! change in the wallet's balance.
+//!
+//! Inputs are attributed to the wallet by the script pubkey of the prevout they spend. Callers
+//! supply the prevouts they know, keyed by outpoint. A wallet generally knows every prevout it
+//! owns, so inputs with unknown prevouts are treated as foreign.
+//!
+//! ```
+//! use std::collections::HashMap;
+//! use bitcoins::{classify::{TxClass, TxClassifier}, types::{LegacyTx, ScriptPubkey}};
+//!
+//! let classifier = TxClassifier::new(vec![
+//!     ScriptPubkey::from_hex("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928").unwrap(),
+//! ]);
+//! let report = classifier.classify(&LegacyTx::default(), &HashMap::new());
+//! assert_eq!(report.class, TxClass::Unrelated);
+//! ```
+
+use std::collections::{HashMap, HashSet};
+
+use crate::types::{
+    script::ScriptPubkey, tx::BitcoinTransaction, txin::BitcoinOutpoint, txout::TxOut,
+};
+
+/// The relationship of a transaction to a wallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxClass {
+    /// The transaction neither spends from nor pays to the wallet
+    Unrelated,
+    /// The transaction pays to the wallet, and spends no wallet inputs
+    Incoming,
+    /// The transaction spends only wallet inputs, and pays to others
+    Outgoing,
+    /// The transaction spends only wallet inputs, and pays only to the wallet
+    SelfTransfer,
+    /// A self-transfer with more inputs than outputs
+    Consolidation,
+    /// The transaction spends inputs from both the wallet and others, as in coinjoins and
+    /// payjoins
+    CoinjoinLike,
+}
+
+/// The result of classifying a transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxClassification {
+    /// The relationship of the transaction to the wallet
+    pub class: TxClass,
+    /// The indices of the inputs spending wallet prevouts
+    pub inputs: Vec<usize>,
+    /// The indices of the outputs paying to the wallet
+    pub outputs: Vec<usize>,
+    /// The total value of the wallet prevouts spent
+    pub sent: u64,
+    /// The total value of the outputs paying to the wallet
+    pub received: u64,
+    /// The fee paid by the transaction. `None` if any prevout is unknown
+    pub fee: Option<u64>,
+}
+
+impl TxClassification {
+    /// The net change in the wallet's balance, in satoshis. Negative for outgoing transactions.
+    pub fn net(&self) -> i64 {
+        self.received as i64 - self.sent as i64
+    }
+}
+
+/// Classifies transactions for a wallet with a known set of script pubkeys.
+#[derive(Debug, Clone, Default)]
+pub struct TxClassifier {
+    scripts: HashSet<ScriptPubkey>,
+}
+
+impl TxClassifier {
+    /// Instantiate a classifier for a wallet controlling `scripts`
+    pub fn new<I>(scripts: I) -> Self
+    where
+        I: IntoIterator<Item = ScriptPubkey>,
+    {
+        Self {
+            scripts: scripts.into_iter().collect(),
+        }
+    }
+
+    /// Add a script pubkey to the wallet's script set
+    pub fn insert_script(&mut self, script_pubkey: ScriptPubkey) {
+        self.scripts.insert(script_pubkey);
+    }
+
+    /// True if the script pubkey belongs to the wallet
+    pub fn is_mine(&self, script_pubkey: &ScriptPubkey) -> bool {
+        self.scripts.contains(script_pubkey)
+    }
+
+    /// Classify a transaction. `prevouts` holds the outputs spent by the transaction, where
+    /// known. Prevouts that the transaction does not spend are ignored.
+    pub fn classify<T: BitcoinTransaction>(
+        &self,
+        tx: &T,
+        prevouts: &HashMap<BitcoinOutpoint, TxOut>,
+    ) -> TxClassification {
+        let mut inputs = vec![];
+        let mut sent = 0u64;
+        let mut input_value = Some(0u64);
+        for (i, input) in tx.inputs().iter().enumerate() {
+            let prevout = prevouts.get(&input.outpoint);
+            input_value = input_value
+                .zip(prevout)
+                .and_then(|(total, p)| total.checked_add(p.value));
+            if let Some(p) = prevout.filter(|p| self.is_mine(&p.script_pubkey)) {
+                inputs.push(i);
+                sent = sent.saturating_add(p.value);
+            }
+        }
+
+        let mut outputs = vec![];
+        let mut received = 0u64;
+        for (i, output) in tx.outputs().iter().enumerate() {
+            if self.is_mine(&output.script_pubkey) {
+                outputs.push(i);
+                received = received.saturating_add(output.value);
+            }
+        }
+
+        let output_value = tx
+            .outputs()
+            .iter()
+            .try_fold(0u64, |total, o| total.checked_add(o.value));
+        let fee = input_value
+            .zip(output_value)
+            .and_then(|(i, o)| i.checked_sub(o));
+
+        let all_inputs = inputs.len() == tx.inputs().len();
+        let all_outputs = outputs.len() == tx.outputs().len();
+        let class = match (inputs.is_empty(), outputs.is_empty()) {
+            (true, true) => TxClass::Unrelated,
+            (true, false) => TxClass::Incoming,
+            _ if !all_inputs => TxClass::CoinjoinLike,
+            _ if !all_outputs => TxClass::Outgoing,
+            _ if inputs.len() > outputs.len() => TxClass::Consolidation,
+            _ => TxClass::SelfTransfer,
+        };
+
+        TxClassification {
+            class,
+            inputs,
+            outputs,
+            sent,
+            received,
+            fee,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        hashes::TXID,
+        types::{legacy::LegacyTx, txin::BitcoinTxIn},
+    };
+    use coins_core::{ser::ByteFormat, types::tx::Transaction};
+
+    #[test]
+    fn it_classifies_wallet_transactions() {
+        let spk = |byte: &str| ScriptPubkey::from_hex(&format!("0014{}", byte.repeat(20))).unwrap();
+        let (mine, change, theirs) = (spk("01"), spk("02"), spk("03"));
+        let classifier = TxClassifier::new(vec![mine.clone(), change.clone()]);
+
+        let txid = TXID::deserialize_hex(&"ab".repeat(32)).unwrap();
+        let outpoint = |idx| BitcoinOutpoint::new(txid, idx);
+        let mut prevouts = HashMap::new();
+        prevouts.insert(outpoint(0), TxOut::new(50_000, mine.clone()));
+        prevouts.insert(outpoint(1), TxOut::new(30_000, change.clone()));
+        prevouts.insert(outpoint(2), TxOut::new(40_000, theirs.clone()));
+
+        let tx = |vin: &[u32], vout: &[(u64, &ScriptPubkey)]| {
+            let vin: Vec<_> = vin
+                .iter()
+                .map(|idx| BitcoinTxIn::new(outpoint(*idx), vec![], 0xffff_ffff))
+                .collect();
+            let vout: Vec<_> = vout
+                .iter()
+                .map(|(value, spk)| TxOut::new(*value, (*spk).clone()))
+                .collect();
+            LegacyTx::new(2, vin, vout, 0).unwrap()
+        };
+
+        let cases = [
+            (tx(&[2], &[(39_000, &theirs)]), TxClass::Unrelated, 0),
+            (tx(&[2], &[(39_000, &mine)]), TxClass::Incoming, 39_000),
+            (
+                tx(&[0], &[(20_000, &theirs), (29_000, &change)]),
+                TxClass::Outgoing,
+                -21_000,
+            ),
+            (
+                tx(&[0], &[(49_000, &change)]),
+                TxClass::SelfTransfer,
+                -1_000,
+            ),
+            (
+                tx(&[0, 1], &[(79_000, &change)]),
+                TxClass::Consolidation,
+                -1_000,
+            ),
+            (
+                tx(&[0, 2], &[(45_000, &theirs), (44_000, &mine)]),
+                TxClass::CoinjoinLike,
+                -6_000,
+            ),
+        ];
+        for (tx, class, net) in cases.iter() {
+            let report = classifier.classify(tx, &prevouts);
+            assert_eq!(report.class, *class);
+            assert_eq!(report.net(), *net);
+            assert_eq!(report.fee, Some(1_000));
+        }
+
+        // Unknown prevouts are treated as foreign
+        let report = classifier.classify(&tx(&[3], &[(1_000, &mine)]), &prevouts);
+        assert_eq!(report.class, TxClass::Incoming);
+        assert_eq!(report.outputs, vec![0]);
+        assert_eq!(report.fee, None);
+    }
+}
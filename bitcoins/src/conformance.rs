@@ -0,0 +1,580 @@
+//! Official test vectors for consensus-critical behavior, packaged as a reusable harness.
+//!
+//! Each `check_*` function runs a set of vectors and reports the first failure. The runners are
+//! generic over the encoder where the vectors are network-specific, so that forks and new
+//! network backends can run them against their own implementations. `run_all` runs every
+//! bundled vector set against the mainnet implementations in this crate.
+//!
+//! Bundled vectors cover BIP143 (witness sighash), BIP173 (bech32 witness v0 addresses), BIP350
+//! (bech32m invalid addresses), BIP32 (extended key derivation), and BIP39 (mnemonic seeds).
+//!
+//! BIP341 and BIP174 vectors are deliberately out of scope, and `run_all` does not cover them.
+//! This crate does not implement the taproot sighash or PSBTs, so there is nothing for those
+//! vectors to check.
+//!
+//! ```
+//! bitcoins::conformance::run_all().unwrap();
+//! ```
+
+use thiserror::Error;
+
+use coins_bip32::{enc::XKeyEncoder, primitives::Hint, xkeys::XPriv, BIP32_HARDEN};
+use coins_bip39::{Mnemonic, Wordlist};
+use coins_core::ser::ByteFormat;
+
+use crate::{
+    enc::encoder::{BitcoinEncoderMarker, MainnetEncoder},
+    types::{
+        script::Script,
+        tx::{BitcoinTransaction, Sighash},
+        witness::{SighashCache, WitnessSighashArgs, WitnessTx},
+    },
+};
+
+/// A failed conformance vector.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("{suite} vector {index} failed: {reason}")]
+pub struct ConformanceError {
+    /// The name of the vector set
+    pub suite: &'static str,
+    /// The index of the failing vector in its set
+    pub index: usize,
+    /// A description of the failure
+    pub reason: String,
+}
+
+/// Return an error for vector `index` of `suite` if `ok` is false
+fn ensure(
+    ok: bool,
+    suite: &'static str,
+    index: usize,
+    reason: impl FnOnce() -> String,
+) -> Result<(), ConformanceError> {
+    if ok {
+        Ok(())
+    } else {
+        Err(ConformanceError {
+            suite,
+            index,
+            reason: reason(),
+        })
+    }
+}
+
+/// A BIP143 sighash vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bip143Vector {
+    /// The unsigned transaction, hex-encoded
+    pub tx: &'static str,
+    /// The index of the input being signed
+    pub index: usize,
+    /// The script code, hex-encoded with its length prefix
+    pub script_code: &'static str,
+    /// The value of the prevout
+    pub value: u64,
+    /// The sighash flag
+    pub sighash_flag: Sighash,
+    /// The expected sighash digest, hex-encoded
+    pub sighash: &'static str,
+}
+
+/// The native P2WPKH, P2SH-P2WPKH, and P2SH-P2WSH multisig examples from BIP143. The multisig
+/// example is signed with every sighash flag, including SIGHASH_NONE.
+pub const BIP143_VECTORS: &[Bip143Vector] = &[
+    Bip143Vector {
+        tx: "0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000",
+        index: 1,
+        script_code: "1976a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac",
+        value: 600_000_000,
+        sighash_flag: Sighash::All,
+        sighash: "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670",
+    },
+    Bip143Vector {
+        tx: "0100000001db6b1b20aa0fd7b23880be2ecbd4a98130974cf4748fb66092ac4d3ceb1a54770100000000feffffff02b8b4eb0b000000001976a914a457b684d7f0d539a46a45bbc043f35b59d0d96388ac0008af2f000000001976a914fd270b1ee6abcaea97fea7ad0402e8bd8ad6d77c88ac92040000",
+        index: 0,
+        script_code: "1976a91479091972186c449eb1ded22b78e40d009bdf008988ac",
+        value: 1_000_000_000,
+        sighash_flag: Sighash::All,
+        sighash: "64f3b0f4dd2bb3aa1ce8566d220cc74dda9df97d8490cc81d89d735c92e59fb6",
+    },
+    // P2SH-P2WSH 6-of-6 multisig, signed with each sighash type
+    Bip143Vector {
+        tx: "010000000136641869ca081e70f394c6948e8af409e18b619df2ed74aa106c1ca29787b96e0100000000ffffffff0200e9a435000000001976a914389ffce9cd9ae88dcc0631e88a821ffdbe9bfe2688acc0832f05000000001976a9147480a33f950689af511e6e84c138dbbd3c3ee41588ac00000000",
+        index: 0,
+        script_code: "cf56210307b8ae49ac90a048e9b53357a2354b3334e9c8bee813ecb98e99a7e07e8c3ba32103b28f0c28bfab54554ae8c658ac5c3e0ce6e79ad336331f78c428dd43eea8449b21034b8113d703413d57761b8b9781957b8c0ac1dfe69f492580ca4195f50376ba4a21033400f6afecb833092a9a21cfdf1ed1376e58c5d1f47de74683123987e967a8f42103a6d48b1131e94ba04d9737d61acdaa1322008af9602b3b14862c07a1789aac162102d8b661b0b3302ee2f162b09e07a55ad5dfbe673a9f01d9f0c19617681024306b56ae",
+        value: 987_654_321,
+        sighash_flag: Sighash::All,
+        sighash: "185c0be5263dce5b4bb50a047973c1b6272bfbd0103a89444597dc40b248ee7c",
+    },
+    Bip143Vector {
+        tx: "010000000136641869ca081e70f394c6948e8af409e18b619df2ed74aa106c1ca29787b96e0100000000ffffffff0200e9a435000000001976a914389ffce9cd9ae88dcc0631e88a821ffdbe9bfe2688acc0832f05000000001976a9147480a33f950689af511e6e84c138dbbd3c3ee41588ac00000000",
+        index: 0,
+        script_code: "cf56210307b8ae49ac90a048e9b53357a2354b3334e9c8bee813ecb98e99a7e07e8c3ba32103b28f0c28bfab54554ae8c658ac5c3e0ce6e79ad336331f78c428dd43eea8449b21034b8113d703413d57761b8b9781957b8c0ac1dfe69f492580ca4195f50376ba4a21033400f6afecb833092a9a21cfdf1ed1376e58c5d1f47de74683123987e967a8f42103a6d48b1131e94ba04d9737d61acdaa1322008af9602b3b14862c07a1789aac162102d8b661b0b3302ee2f162b09e07a55ad5dfbe673a9f01d9f0c19617681024306b56ae",
+        value: 987_654_321,
+        sighash_flag: Sighash::None,
+        sighash: "e9733bc60ea13c95c6527066bb975a2ff29a925e80aa14c213f686cbae5d2f36",
+    },
+    Bip143Vector {
+        tx: "010000000136641869ca081e70f394c6948e8af409e18b619df2ed74aa106c1ca29787b96e0100000000ffffffff0200e9a435000000001976a914389ffce9cd9ae88dcc0631e88a821ffdbe9bfe2688acc0832f05000000001976a9147480a33f950689af511e6e84c138dbbd3c3ee41588ac00000000",
+        index: 0,
+        script_code: "cf56210307b8ae49ac90a048e9b53357a2354b3334e9c8bee813ecb98e99a7e07e8c3ba32103b28f0c28bfab54554ae8c658ac5c3e0ce6e79ad336331f78c428dd43eea8449b21034b8113d703413d57761b8b9781957b8c0ac1dfe69f492580ca4195f50376ba4a21033400f6afecb833092a9a21cfdf1ed1376e58c5d1f47de74683123987e967a8f42103a6d48b1131e94ba04d9737d61acdaa1322008af9602b3b14862c07a1789aac162102d8b661b0b3302ee2f162b09e07a55ad5dfbe673a9f01d9f0c19617681024306b56ae",
+        value: 987_654_321,
+        sighash_flag: Sighash::Single,
+        sighash: "1e1f1c303dc025bd664acb72e583e933fae4cff9148bf78c157d1e8f78530aea",
+    },
+    Bip143Vector {
+        tx: "010000000136641869ca081e70f394c6948e8af409e18b619df2ed74aa106c1ca29787b96e0100000000ffffffff0200e9a435000000001976a914389ffce9cd9ae88dcc0631e88a821ffdbe9bfe2688acc0832f05000000001976a9147480a33f950689af511e6e84c138dbbd3c3ee41588ac00000000",
+        index: 0,
+        script_code: "cf56210307b8ae49ac90a048e9b53357a2354b3334e9c8bee813ecb98e99a7e07e8c3ba32103b28f0c28bfab54554ae8c658ac5c3e0ce6e79ad336331f78c428dd43eea8449b21034b8113d703413d57761b8b9781957b8c0ac1dfe69f492580ca4195f50376ba4a21033400f6afecb833092a9a21cfdf1ed1376e58c5d1f47de74683123987e967a8f42103a6d48b1131e94ba04d9737d61acdaa1322008af9602b3b14862c07a1789aac162102d8b661b0b3302ee2f162b09e07a55ad5dfbe673a9f01d9f0c19617681024306b56ae",
+        value: 987_654_321,
+        sighash_flag: Sighash::AllAcp,
+        sighash: "2a67f03e63a6a422125878b40b82da593be8d4efaafe88ee528af6e5a9955c6e",
+    },
+    Bip143Vector {
+        tx: "010000000136641869ca081e70f394c6948e8af409e18b619df2ed74aa106c1ca29787b96e0100000000ffffffff0200e9a435000000001976a914389ffce9cd9ae88dcc0631e88a821ffdbe9bfe2688acc0832f05000000001976a9147480a33f950689af511e6e84c138dbbd3c3ee41588ac00000000",
+        index: 0,
+        script_code: "cf56210307b8ae49ac90a048e9b53357a2354b3334e9c8bee813ecb98e99a7e07e8c3ba32103b28f0c28bfab54554ae8c658ac5c3e0ce6e79ad336331f78c428dd43eea8449b21034b8113d703413d57761b8b9781957b8c0ac1dfe69f492580ca4195f50376ba4a21033400f6afecb833092a9a21cfdf1ed1376e58c5d1f47de74683123987e967a8f42103a6d48b1131e94ba04d9737d61acdaa1322008af9602b3b14862c07a1789aac162102d8b661b0b3302ee2f162b09e07a55ad5dfbe673a9f01d9f0c19617681024306b56ae",
+        value: 987_654_321,
+        sighash_flag: Sighash::NoneAcp,
+        sighash: "781ba15f3779d5542ce8ecb5c18716733a5ee42a6f51488ec96154934e2c890a",
+    },
+    Bip143Vector {
+        tx: "010000000136641869ca081e70f394c6948e8af409e18b619df2ed74aa106c1ca29787b96e0100000000ffffffff0200e9a435000000001976a914389ffce9cd9ae88dcc0631e88a821ffdbe9bfe2688acc0832f05000000001976a9147480a33f950689af511e6e84c138dbbd3c3ee41588ac00000000",
+        index: 0,
+        script_code: "cf56210307b8ae49ac90a048e9b53357a2354b3334e9c8bee813ecb98e99a7e07e8c3ba32103b28f0c28bfab54554ae8c658ac5c3e0ce6e79ad336331f78c428dd43eea8449b21034b8113d703413d57761b8b9781957b8c0ac1dfe69f492580ca4195f50376ba4a21033400f6afecb833092a9a21cfdf1ed1376e58c5d1f47de74683123987e967a8f42103a6d48b1131e94ba04d9737d61acdaa1322008af9602b3b14862c07a1789aac162102d8b661b0b3302ee2f162b09e07a55ad5dfbe673a9f01d9f0c19617681024306b56ae",
+        value: 987_654_321,
+        sighash_flag: Sighash::SingleAcp,
+        sighash: "511e8e52ed574121fc1b654970395502128263f62662e076dc6baf05c2e6a99b",
+    },
+];
+
+/// Run BIP143 sighash vectors against `WitnessTx`. Sighashes are computed with
+/// `SighashCache::witness_sighash_allowing_none`, so that every sighash flag is covered.
+pub fn check_bip143(vectors: &[Bip143Vector]) -> Result<(), ConformanceError> {
+    const SUITE: &str = "BIP143";
+    for (index, vector) in vectors.iter().enumerate() {
+        let fail = |reason: String| ConformanceError {
+            suite: SUITE,
+            index,
+            reason,
+        };
+        let tx = WitnessTx::from_legacy(
+            crate::types::legacy::LegacyTx::deserialize_hex(vector.tx)
+                .map_err(|e| fail(format!("tx: {}", e)))?,
+        );
+        let args = WitnessSighashArgs {
+            index: vector.index,
+            sighash_flag: vector.sighash_flag,
+            prevout_script: Script::deserialize_hex(vector.script_code)
+                .map_err(|e| fail(format!("script code: {}", e)))?,
+            prevout_value: vector.value,
+        };
+        let sighash = SighashCache::new(&tx)
+            .witness_sighash_allowing_none(&args)
+            .map_err(|e| fail(format!("sighash: {}", e)))?;
+        let sighash = hex::encode(&sighash[..]);
+        ensure(sighash == vector.sighash, SUITE, index, || {
+            format!("expected sighash {}, got {}", vector.sighash, sighash)
+        })?;
+        ensure(
+            tx.as_legacy().serialize_hex() == vector.tx,
+            SUITE,
+            index,
+            || "tx did not round-trip".to_owned(),
+        )?;
+    }
+    Ok(())
+}
+
+/// A valid address and its script pubkey.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddressVector {
+    /// The address
+    pub address: &'static str,
+    /// The script pubkey, hex-encoded without a length prefix
+    pub script_pubkey: &'static str,
+}
+
+/// Valid witness v0 mainnet addresses from BIP173.
+pub const BIP173_MAINNET_VALID: &[AddressVector] = &[
+    AddressVector {
+        address: "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+        script_pubkey: "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+    },
+    AddressVector {
+        address: "bc1qr0u2rqcak4zrks4yfuc2zgw3kctdqydtzh0k9dvgwg4ggkryejvsy49jvz",
+        script_pubkey: "00201bf8a1831db5443b42a44f30a121d1b616d011ab15df62b588722a845864cc99",
+    },
+];
+
+/// Valid witness v0 testnet addresses from BIP173.
+pub const BIP173_TESTNET_VALID: &[AddressVector] = &[
+    AddressVector {
+        address: "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+        script_pubkey: "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+    },
+    AddressVector {
+        address: "tb1qqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesrxh6hy",
+        script_pubkey: "0020000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433",
+    },
+];
+
+/// Invalid addresses from BIP173. These must be rejected on every network.
+pub const BIP173_INVALID: &[&str] = &[
+    // Invalid human-readable part
+    "tc1qw508d6qejxtdg4y5r3zarvary0c5xw7kg3g4ty",
+    // Invalid checksum
+    "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5",
+    // Invalid program length
+    "bc1rw5uspcuh",
+    "bc10w508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kw5rljs90",
+    // Invalid program length for witness version 0
+    "BC1QR508D6QEJXTDG4Y5R3ZARVARY0C5XW7KN40WF2",
+    // Mixed case
+    "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sL5k7",
+    // Empty data section
+    "bc1gmk9yu",
+];
+
+/// Invalid addresses from BIP350. These must be rejected on every network.
+pub const BIP350_INVALID: &[&str] = &[
+    // Invalid human-readable part
+    "tc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq5zuyut",
+    // Bech32 checksum instead of bech32m
+    "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd",
+    "BC1S0XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ54WELL",
+    // Bech32m checksum instead of bech32
+    "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh",
+    // Invalid character in checksum
+    "bc1p38j9r5y49hruaue7wxjce0updqjuyyx0kh56v8s25huc6995vvpql3jow4",
+    // Invalid witness version
+    "BC130XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ7ZWS8R",
+    // Invalid program length
+    "bc1pw5dgrnzv",
+    "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v8n0nx0muaewav253zgeav",
+    // Mixed case
+    "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq47Zagq",
+    // More than 4 bits of zero padding
+    "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v07qwwzcrf",
+    // Non-zero padding
+    "tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vpggkg4j",
+    // Empty data section
+    "bc1gmk9yu",
+];
+
+/// Run BIP173 address vectors against the encoder `T`. Valid addresses must parse, decode to
+/// their script pubkey, and re-encode to the same address. Invalid addresses must be rejected.
+pub fn check_bip173<T: BitcoinEncoderMarker>(
+    valid: &[AddressVector],
+    invalid: &[&str],
+) -> Result<(), ConformanceError> {
+    const SUITE: &str = "BIP173";
+    for (index, vector) in valid.iter().enumerate() {
+        let address = T::string_to_address(vector.address).map_err(|e| ConformanceError {
+            suite: SUITE,
+            index,
+            reason: format!("address rejected: {}", e),
+        })?;
+        let script_pubkey = hex::encode(T::decode_address(&address).items());
+        ensure(script_pubkey == vector.script_pubkey, SUITE, index, || {
+            format!(
+                "expected script pubkey {}, got {}",
+                vector.script_pubkey, script_pubkey
+            )
+        })?;
+        let round_trip = match T::encode_address(&T::decode_address(&address)) {
+            Ok(encoded) => encoded == address,
+            Err(_) => false,
+        };
+        ensure(round_trip, SUITE, index, || {
+            "address did not round-trip".to_owned()
+        })?;
+    }
+    for (i, address) in invalid.iter().enumerate() {
+        ensure(
+            T::string_to_address(address).is_err(),
+            SUITE,
+            valid.len() + i,
+            || format!("invalid address {} was accepted", address),
+        )?;
+    }
+    Ok(())
+}
+
+/// An extended key derived from a BIP32 vector's seed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bip32Derivation {
+    /// The derivation path from the root
+    pub path: &'static [u32],
+    /// The expected xpub
+    pub xpub: &'static str,
+    /// The expected xpriv
+    pub xpriv: &'static str,
+}
+
+/// A BIP32 seed and derivations from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bip32Vector {
+    /// The seed, hex-encoded
+    pub seed: &'static str,
+    /// The derived keys
+    pub derivations: &'static [Bip32Derivation],
+}
+
+/// Test vector 1 from BIP32. The extended keys use mainnet version bytes.
+pub const BIP32_VECTORS: &[Bip32Vector] = &[Bip32Vector {
+    seed: "000102030405060708090a0b0c0d0e0f",
+    derivations: &[
+        Bip32Derivation {
+            path: &[],
+            xpub: "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8",
+            xpriv: "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi",
+        },
+        Bip32Derivation {
+            path: &[BIP32_HARDEN],
+            xpub: "xpub68Gmy5EdvgibQVfPdqkBBCHxA5htiqg55crXYuXoQRKfDBFA1WEjWgP6LHhwBZeNK1VTsfTFUHCdrfp1bgwQ9xv5ski8PX9rL2dZXvgGDnw",
+            xpriv: "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7",
+        },
+        Bip32Derivation {
+            path: &[BIP32_HARDEN, 1],
+            xpub: "xpub6ASuArnXKPbfEwhqN6e3mwBcDTgzisQN1wXN9BJcM47sSikHjJf3UFHKkNAWbWMiGj7Wf5uMash7SyYq527Hqck2AxYysAA7xmALppuCkwQ",
+            xpriv: "xprv9wTYmMFdV23N2TdNG573QoEsfRrWKQgWeibmLntzniatZvR9BmLnvSxqu53Kw1UmYPxLgboyZQaXwTCg8MSY3H2EU4pWcQDnRnrVA1xe8fs",
+        },
+        Bip32Derivation {
+            path: &[BIP32_HARDEN, 1, 2 + BIP32_HARDEN],
+            xpub: "xpub6D4BDPcP2GT577Vvch3R8wDkScZWzQzMMUm3PWbmWvVJrZwQY4VUNgqFJPMM3No2dFDFGTsxxpG5uJh7n7epu4trkrX7x7DogT5Uv6fcLW5",
+            xpriv: "xprv9z4pot5VBttmtdRTWfWQmoH1taj2axGVzFqSb8C9xaxKymcFzXBDptWmT7FwuEzG3ryjH4ktypQSAewRiNMjANTtpgP4mLTj34bhnZX7UiM",
+        },
+        Bip32Derivation {
+            path: &[BIP32_HARDEN, 1, 2 + BIP32_HARDEN, 2],
+            xpub: "xpub6FHa3pjLCk84BayeJxFW2SP4XRrFd1JYnxeLeU8EqN3vDfZmbqBqaGJAyiLjTAwm6ZLRQUMv1ZACTj37sR62cfN7fe5JnJ7dh8zL4fiyLHV",
+            xpriv: "xprvA2JDeKCSNNZky6uBCviVfJSKyQ1mDYahRjijr5idH2WwLsEd4Hsb2Tyh8RfQMuPh7f7RtyzTtdrbdqqsunu5Mm3wDvUAKRHSC34sJ7in334",
+        },
+        Bip32Derivation {
+            path: &[BIP32_HARDEN, 1, 2 + BIP32_HARDEN, 2, 1_000_000_000],
+            xpub: "xpub6H1LXWLaKsWFhvm6RVpEL9P4KfRZSW7abD2ttkWP3SSQvnyA8FSVqNTEcYFgJS2UaFcxupHiYkro49S8yGasTvXEYBVPamhGW6cFJodrTHy",
+            xpriv: "xprvA41z7zogVVwxVSgdKUHDy1SKmdb533PjDz7J6N6mV6uS3ze1ai8FHa8kmHScGpWmj4WggLyQjgPie1rFSruoUihUZREPSL39UNdE3BBDu76",
+        },
+    ],
+}];
+
+/// Run BIP32 derivation vectors, encoding extended keys with `E`.
+pub fn check_bip32<E: XKeyEncoder>(vectors: &[Bip32Vector]) -> Result<(), ConformanceError> {
+    const SUITE: &str = "BIP32";
+    let mut index = 0;
+    for vector in vectors.iter() {
+        let fail = |index: usize, reason: String| ConformanceError {
+            suite: SUITE,
+            index,
+            reason,
+        };
+        let seed = hex::decode(vector.seed).map_err(|e| fail(index, format!("seed: {}", e)))?;
+        let root = XPriv::root_from_seed(&seed, Some(Hint::Legacy))
+            .map_err(|e| fail(index, format!("root: {}", e)))?;
+        for derivation in vector.derivations.iter() {
+            let xpriv = root
+                .derive_path(derivation.path)
+                .map_err(|e| fail(index, format!("derivation: {}", e)))?;
+            let encoded = E::xpriv_to_base58(&xpriv)
+                .map_err(|e| fail(index, format!("xpriv encoding: {}", e)))?;
+            ensure(encoded == derivation.xpriv, SUITE, index, || {
+                format!("expected xpriv {}, got {}", derivation.xpriv, encoded)
+            })?;
+            let encoded = E::xpub_to_base58(&xpriv.verify_key())
+                .map_err(|e| fail(index, format!("xpub encoding: {}", e)))?;
+            ensure(encoded == derivation.xpub, SUITE, index, || {
+                format!("expected xpub {}, got {}", derivation.xpub, encoded)
+            })?;
+            index += 1;
+        }
+    }
+    Ok(())
+}
+
+/// A BIP39 mnemonic and the root key of its seed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bip39Vector {
+    /// The mnemonic phrase
+    pub phrase: &'static str,
+    /// The root xpriv of the seed, with the passphrase `TREZOR` and mainnet version bytes
+    pub xpriv: &'static str,
+}
+
+/// The English vectors from BIP39.
+pub const BIP39_VECTORS: &[Bip39Vector] = &[
+    Bip39Vector {
+        phrase: "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+        xpriv: "xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF",
+    },
+    Bip39Vector {
+        phrase: "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        xpriv: "xprv9s21ZrQH143K2gA81bYFHqU68xz1cX2APaSq5tt6MFSLeXnCKV1RVUJt9FWNTbrrryem4ZckN8k4Ls1H6nwdvDTvnV7zEXs2HgPezuVccsq",
+    },
+    Bip39Vector {
+        phrase: "letter advice cage absurd amount doctor acoustic avoid letter advice cage above",
+        xpriv: "xprv9s21ZrQH143K2shfP28KM3nr5Ap1SXjz8gc2rAqqMEynmjt6o1qboCDpxckqXavCwdnYds6yBHZGKHv7ef2eTXy461PXUjBFQg6PrwY4Gzq",
+    },
+    Bip39Vector {
+        phrase: "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong",
+        xpriv: "xprv9s21ZrQH143K2V4oox4M8Zmhi2Fjx5XK4Lf7GKRvPSgydU3mjZuKGCTg7UPiBUD7ydVPvSLtg9hjp7MQTYsW67rZHAXeccqYqrsx8LcXnyd",
+    },
+    Bip39Vector {
+        phrase: "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon agent",
+        xpriv: "xprv9s21ZrQH143K3mEDrypcZ2usWqFgzKB6jBBx9B6GfC7fu26X6hPRzVjzkqkPvDqp6g5eypdk6cyhGnBngbjeHTe4LsuLG1cCmKJka5SMkmU",
+    },
+    Bip39Vector {
+        phrase: "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal will",
+        xpriv: "xprv9s21ZrQH143K3Lv9MZLj16np5GzLe7tDKQfVusBni7toqJGcnKRtHSxUwbKUyUWiwpK55g1DUSsw76TF1T93VT4gz4wt5RM23pkaQLnvBh7",
+    },
+    Bip39Vector {
+        phrase: "letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic avoid letter always",
+        xpriv: "xprv9s21ZrQH143K3VPCbxbUtpkh9pRG371UCLDz3BjceqP1jz7XZsQ5EnNkYAEkfeZp62cDNj13ZTEVG1TEro9sZ9grfRmcYWLBhCocViKEJae",
+    },
+    Bip39Vector {
+        phrase: "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo when",
+        xpriv: "xprv9s21ZrQH143K36Ao5jHRVhFGDbLP6FCx8BEEmpru77ef3bmA928BxsqvVM27WnvvyfWywiFN8K6yToqMaGYfzS6Db1EHAXT5TuyCLBXUfdm",
+    },
+    Bip39Vector {
+        phrase: "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art",
+        xpriv: "xprv9s21ZrQH143K32qBagUJAMU2LsHg3ka7jqMcV98Y7gVeVyNStwYS3U7yVVoDZ4btbRNf4h6ibWpY22iRmXq35qgLs79f312g2kj5539ebPM",
+    },
+    Bip39Vector {
+        phrase: "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth title",
+        xpriv: "xprv9s21ZrQH143K3Y1sd2XVu9wtqxJRvybCfAetjUrMMco6r3v9qZTBeXiBZkS8JxWbcGJZyio8TrZtm6pkbzG8SYt1sxwNLh3Wx7to5pgiVFU",
+    },
+    Bip39Vector {
+        phrase: "letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic bless",
+        xpriv: "xprv9s21ZrQH143K3CSnQNYC3MqAAqHwxeTLhDbhF43A4ss4ciWNmCY9zQGvAKUSqVUf2vPHBTSE1rB2pg4avopqSiLVzXEU8KziNnVPauTqLRo",
+    },
+    Bip39Vector {
+        phrase: "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo vote",
+        xpriv: "xprv9s21ZrQH143K2WFF16X85T2QCpndrGwx6GueB72Zf3AHwHJaknRXNF37ZmDrtHrrLSHvbuRejXcnYxoZKvRquTPyp2JiNG3XcjQyzSEgqCB",
+    },
+    Bip39Vector {
+        phrase: "ozone drill grab fiber curtain grace pudding thank cruise elder eight picnic",
+        xpriv: "xprv9s21ZrQH143K2oZ9stBYpoaZ2ktHj7jLz7iMqpgg1En8kKFTXJHsjxry1JbKH19YrDTicVwKPehFKTbmaxgVEc5TpHdS1aYhB2s9aFJBeJH",
+    },
+    Bip39Vector {
+        phrase: "gravity machine north sort system female filter attitude volume fold club stay feature office ecology stable narrow fog",
+        xpriv: "xprv9s21ZrQH143K3uT8eQowUjsxrmsA9YUuQQK1RLqFufzybxD6DH6gPY7NjJ5G3EPHjsWDrs9iivSbmvjc9DQJbJGatfa9pv4MZ3wjr8qWPAK",
+    },
+    Bip39Vector {
+        phrase: "hamster diagram private dutch cause delay private meat slide toddler razor book happy fancy gospel tennis maple dilemma loan word shrug inflict delay length",
+        xpriv: "xprv9s21ZrQH143K2XTAhys3pMNcGn261Fi5Ta2Pw8PwaVPhg3D8DWkzWQwjTJfskj8ofb81i9NP2cUNKxwjueJHHMQAnxtivTA75uUFqPFeWzk",
+    },
+    Bip39Vector {
+        phrase: "scheme spot photo card baby mountain device kick cradle pact join borrow",
+        xpriv: "xprv9s21ZrQH143K3FperxDp8vFsFycKCRcJGAFmcV7umQmcnMZaLtZRt13QJDsoS5F6oYT6BB4sS6zmTmyQAEkJKxJ7yByDNtRe5asP2jFGhT6",
+    },
+    Bip39Vector {
+        phrase: "horn tenant knee talent sponsor spell gate clip pulse soap slush warm silver nephew swap uncle crack brave",
+        xpriv: "xprv9s21ZrQH143K3R1SfVZZLtVbXEB9ryVxmVtVMsMwmEyEvgXN6Q84LKkLRmf4ST6QrLeBm3jQsb9gx1uo23TS7vo3vAkZGZz71uuLCcywUkt",
+    },
+    Bip39Vector {
+        phrase: "panda eyebrow bullet gorilla call smoke muffin taste mesh discover soft ostrich alcohol speed nation flash devote level hobby quick inner drive ghost inside",
+        xpriv: "xprv9s21ZrQH143K2WNnKmssvZYM96VAr47iHUQUTUyUXH3sAGNjhJANddnhw3i3y3pBbRAVk5M5qUGFr4rHbEWwXgX4qrvrceifCYQJbbFDems",
+    },
+    Bip39Vector {
+        phrase: "cat swing flag economy stadium alone churn speed unique patch report train",
+        xpriv: "xprv9s21ZrQH143K4G28omGMogEoYgDQuigBo8AFHAGDaJdqQ99QKMQ5J6fYTMfANTJy6xBmhvsNZ1CJzRZ64PWbnTFUn6CDV2FxoMDLXdk95DQ",
+    },
+    Bip39Vector {
+        phrase: "light rule cinnamon wrap drastic word pride squirrel upgrade then income fatal apart sustain crack supply proud access",
+        xpriv: "xprv9s21ZrQH143K3wtsvY8L2aZyxkiWULZH4vyQE5XkHTXkmx8gHo6RUEfH3Jyr6NwkJhvano7Xb2o6UqFKWHVo5scE31SGDCAUsgVhiUuUDyh",
+    },
+    Bip39Vector {
+        phrase: "all hour make first leader extend hole alien behind guard gospel lava path output census museum junior mass reopen famous sing advance salt reform",
+        xpriv: "xprv9s21ZrQH143K3rEfqSM4QZRVmiMuSWY9wugscmaCjYja3SbUD3KPEB1a7QXJoajyR2T1SiXU7rFVRXMV9XdYVSZe7JoUXdP4SRHTxsT1nzm",
+    },
+    Bip39Vector {
+        phrase: "vessel ladder alter error federal sibling chat ability sun glass valve picture",
+        xpriv: "xprv9s21ZrQH143K2QWV9Wn8Vvs6jbqfF1YbTCdURQW9dLFKDovpKaKrqS3SEWsXCu6ZNky9PSAENg6c9AQYHcg4PjopRGGKmdD313ZHszymnps",
+    },
+    Bip39Vector {
+        phrase: "scissors invite lock maple supreme raw rapid void congress muscle digital elegant little brisk hair mango congress clump",
+        xpriv: "xprv9s21ZrQH143K4aERa2bq7559eMCCEs2QmmqVjUuzfy5eAeDX4mqZffkYwpzGQRE2YEEeLVRoH4CSHxianrFaVnMN2RYaPUZJhJx8S5j6puX",
+    },
+    Bip39Vector {
+        phrase: "void come effort suffer camp survey warrior heavy shoot primary clutch crush open amazing screen patrol group space point ten exist slush involve unfold",
+        xpriv: "xprv9s21ZrQH143K39rnQJknpH1WEPFJrzmAqqasiDcVrNuk926oizzJDDQkdiTvNPr2FYDYzWgiMiC63YmfPAa2oPyNB23r2g7d1yiK6WpqaQS",
+    },
+];
+
+/// Run BIP39 vectors against the wordlist `W`, decoding root keys with `E`. Each phrase must
+/// parse, re-encode to itself, and derive its root key.
+pub fn check_bip39<W: Wordlist, E: XKeyEncoder>(
+    vectors: &[Bip39Vector],
+) -> Result<(), ConformanceError> {
+    const SUITE: &str = "BIP39";
+    for (index, vector) in vectors.iter().enumerate() {
+        let fail = |reason: String| ConformanceError {
+            suite: SUITE,
+            index,
+            reason,
+        };
+        let mnemonic = Mnemonic::<W>::new_from_phrase(vector.phrase)
+            .map_err(|e| fail(format!("phrase: {}", e)))?;
+        let phrase = mnemonic
+            .to_phrase()
+            .map_err(|e| fail(format!("phrase: {}", e)))?;
+        ensure(phrase == vector.phrase, SUITE, index, || {
+            "phrase did not round-trip".to_owned()
+        })?;
+        let root = mnemonic
+            .master_key(Some("TREZOR"))
+            .map_err(|e| fail(format!("seed: {}", e)))?;
+        let expected =
+            E::xpriv_from_base58(vector.xpriv).map_err(|e| fail(format!("xpriv: {}", e)))?;
+        ensure(root == expected, SUITE, index, || {
+            format!("expected root key {}", vector.xpriv)
+        })?;
+    }
+    Ok(())
+}
+
+/// Run every bundled vector set against the mainnet implementations in this crate.
+pub fn run_all() -> Result<(), ConformanceError> {
+    check_bip143(BIP143_VECTORS)?;
+    check_bip173::<MainnetEncoder>(BIP173_MAINNET_VALID, BIP173_INVALID)?;
+    check_bip173::<crate::enc::encoder::TestnetEncoder>(BIP173_TESTNET_VALID, BIP173_INVALID)?;
+    check_bip173::<MainnetEncoder>(&[], BIP350_INVALID)?;
+    check_bip173::<crate::enc::encoder::TestnetEncoder>(&[], BIP350_INVALID)?;
+    check_bip32::<<MainnetEncoder as BitcoinEncoderMarker>::XKeyEncoder>(BIP32_VECTORS)?;
+    check_bip39::<coins_bip39::English, <MainnetEncoder as BitcoinEncoderMarker>::XKeyEncoder>(
+        BIP39_VECTORS,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_reports_failing_vectors() {
+        let mut bad = BIP143_VECTORS.to_vec();
+        bad[1].value += 1;
+        let err = check_bip143(&bad).unwrap_err();
+        assert_eq!(err.suite, "BIP143");
+        assert_eq!(err.index, 1);
+
+        let err = check_bip173::<MainnetEncoder>(BIP173_TESTNET_VALID, &[]).unwrap_err();
+        assert_eq!(err.index, 0);
+        let err = check_bip173::<MainnetEncoder>(
+            &[],
+            BIP173_MAINNET_VALID
+                .iter()
+                .map(|v| v.address)
+                .collect::<Vec<_>>()
+                .as_slice(),
+        )
+        .unwrap_err();
+        assert_eq!(err.suite, "BIP173");
+
+        let mut bad = BIP39_VECTORS[..2].to_vec();
+        bad[1].xpriv = bad[0].xpriv;
+        let err = check_bip39::<coins_bip39::English, coins_bip32::enc::MainnetEncoder>(&bad)
+            .unwrap_err();
+        assert_eq!((err.suite, err.index), ("BIP39", 1));
+    }
+}
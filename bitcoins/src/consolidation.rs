@@ -0,0 +1,315 @@
+//! A planner for consolidating many small UTXOs into fewer, larger ones.
+//!
+//! Spending an output costs fees in proportion to its input weight, not its value. A wallet
+//! holding many small UTXOs will pay more to spend them when feerates rise. The planner proposes
+//! consolidation transactions at the current feerate when it is lower than the predicted feerate,
+//! and estimates the fees saved by doing so.
+//!
+//! Coins are prioritized by the cost of spending them relative to their value. Coins whose value
+//! after the cost of spending them would be dust under the relay policy are left untouched.
+//!
+//! ```
+//! use bitcoins::{
+//!     consolidation::ConsolidationPlanner,
+//!     builder::InputType,
+//!     types::{FeeRate, ScriptPubkey},
+//! };
+//!
+//! let destination = ScriptPubkey::from_hex("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928").unwrap();
+//! let current = FeeRate::from_sat_per_vb(2);
+//! let predicted = FeeRate::from_sat_per_vb(20);
+//! let planner = ConsolidationPlanner::new(destination, InputType::Wpkh, current, predicted)
+//!     .max_inputs(100);
+//! let plan = planner.plan(&[]);
+//! assert!(plan.batches.is_empty());
+//! ```
+
+use coins_core::builder::TxBuilder;
+
+use crate::{
+    builder::{BitcoinTxBuilder, InputType},
+    enc::encoder::{BitcoinEncoderMarker, MainnetEncoder},
+    policy::Policy,
+    types::{FeeRate, ScriptPubkey, Utxo},
+};
+
+/// The default maximum number of inputs in a single consolidation transaction.
+pub const DEFAULT_MAX_INPUTS: usize = 200;
+
+/// When a consolidation plan should be executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsolidationTiming {
+    /// The current feerate is low enough that consolidating now saves fees
+    Now,
+    /// Consolidating now would not save fees. Wait until the feerate drops below the predicted
+    /// feerate.
+    Wait,
+}
+
+/// A single proposed consolidation transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsolidationBatch {
+    /// The UTXOs to spend, in order
+    pub inputs: Vec<Utxo>,
+    /// The spend type of each input, in order
+    pub input_types: Vec<InputType>,
+    /// The estimated virtual size of the transaction, in vbytes
+    pub vsize: usize,
+    /// The fee paid by the transaction
+    pub fee: u64,
+    /// The value of the consolidated output
+    pub output_value: u64,
+    /// The estimated fees saved by consolidating now, rather than spending each input
+    /// individually at the predicted feerate. This may be negative.
+    pub savings: i64,
+}
+
+impl ConsolidationBatch {
+    /// Return a builder for the consolidation transaction, paying the output to `destination`.
+    /// Inputs use the specified sequence number.
+    pub fn builder<T>(&self, destination: &ScriptPubkey, sequence: u32) -> BitcoinTxBuilder<T>
+    where
+        T: BitcoinEncoderMarker,
+    {
+        self.inputs
+            .iter()
+            .fold(BitcoinTxBuilder::<T>::new().version(2), |b, utxo| {
+                b.spend(utxo.outpoint, sequence)
+            })
+            .pay_script_pubkey(self.output_value, destination.clone())
+    }
+}
+
+/// A set of proposed consolidation transactions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsolidationPlan {
+    /// The feerate used by each proposed transaction
+    pub feerate: FeeRate,
+    /// When the plan should be executed
+    pub timing: ConsolidationTiming,
+    /// The proposed transactions
+    pub batches: Vec<ConsolidationBatch>,
+}
+
+impl ConsolidationPlan {
+    /// The total estimated fees saved by executing the plan. This may be negative.
+    pub fn savings(&self) -> i64 {
+        self.batches.iter().map(|b| b.savings).sum()
+    }
+
+    /// The total fee paid by all proposed transactions
+    pub fn fee(&self) -> u64 {
+        self.batches.iter().map(|b| b.fee).sum()
+    }
+}
+
+/// Proposes consolidation transactions for a UTXO set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsolidationPlanner {
+    destination: ScriptPubkey,
+    destination_type: InputType,
+    current_feerate: FeeRate,
+    predicted_feerate: FeeRate,
+    policy: Policy,
+    max_inputs: usize,
+}
+
+impl ConsolidationPlanner {
+    /// Instantiate a planner that consolidates to `destination`, which is spent as
+    /// `destination_type`.
+    pub fn new(
+        destination: ScriptPubkey,
+        destination_type: InputType,
+        current_feerate: FeeRate,
+        predicted_feerate: FeeRate,
+    ) -> Self {
+        Self {
+            destination,
+            destination_type,
+            current_feerate,
+            predicted_feerate,
+            policy: Policy::default(),
+            max_inputs: DEFAULT_MAX_INPUTS,
+        }
+    }
+
+    /// Set the relay policy that determines whether the consolidated output would be dust.
+    /// Defaults to Bitcoin Core's default policy
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Set the maximum number of inputs in a single consolidation transaction
+    pub fn max_inputs(mut self, max_inputs: usize) -> Self {
+        self.max_inputs = std::cmp::max(max_inputs, 2);
+        self
+    }
+
+    /// Return the consolidation destination
+    pub fn destination(&self) -> &ScriptPubkey {
+        &self.destination
+    }
+
+    /// Propose consolidation transactions for a set of UTXOs and their spend types. Coins that
+    /// are uneconomical to spend at the current feerate are excluded. Batches with fewer than 2
+    /// inputs, or whose output would be dust, are discarded.
+    pub fn plan(&self, utxos: &[(Utxo, InputType)]) -> ConsolidationPlan {
+        let dust_limit = self.policy.dust_limit(&self.destination);
+        let mut candidates: Vec<&(Utxo, InputType)> = utxos
+            .iter()
+            .filter(|(utxo, t)| {
+                let cost = self.current_feerate.fee_for_weight(t.weight());
+                utxo.value.saturating_sub(cost) >= dust_limit
+            })
+            .collect();
+
+        // Highest spend cost relative to value first. Cross-multiply to avoid float comparisons
+        candidates.sort_by(|(a, a_type), (b, b_type)| {
+            let a_ratio = a_type.weight() as u128 * b.value as u128;
+            let b_ratio = b_type.weight() as u128 * a.value as u128;
+            b_ratio.cmp(&a_ratio)
+        });
+
+        let batches = candidates
+            .chunks(self.max_inputs)
+            .filter(|chunk| chunk.len() >= 2)
+            .filter_map(|chunk| self.batch(chunk))
+            .collect();
+
+        let mut plan = ConsolidationPlan {
+            feerate: self.current_feerate,
+            timing: ConsolidationTiming::Wait,
+            batches,
+        };
+        if self.current_feerate < self.predicted_feerate && plan.savings() > 0 {
+            plan.timing = ConsolidationTiming::Now;
+        }
+        plan
+    }
+
+    fn batch(&self, chunk: &[&(Utxo, InputType)]) -> Option<ConsolidationBatch> {
+        let inputs: Vec<Utxo> = chunk.iter().map(|(utxo, _)| utxo.clone()).collect();
+        let input_types: Vec<InputType> = chunk.iter().map(|(_, t)| *t).collect();
+
+        // The encoder does not affect size estimation
+        let builder = inputs
+            .iter()
+            .fold(BitcoinTxBuilder::<MainnetEncoder>::new(), |b, utxo| {
+                b.spend(utxo.outpoint, 0)
+            })
+            .pay_script_pubkey(0, self.destination.clone());
+        let weight = builder.estimate_weight(&input_types)?;
+        let vsize = weight.div_ceil(4);
+        let fee = self.current_feerate.fee_for_vsize(vsize);
+
+        let total: u64 = inputs.iter().map(|utxo| utxo.value).sum();
+        let output_value = total.checked_sub(fee)?;
+        if output_value < self.policy.dust_limit(&self.destination) {
+            return None;
+        }
+
+        // Spending each input later, versus paying now and spending the output later
+        let cost_later: u64 = input_types
+            .iter()
+            .map(|t| self.predicted_feerate.fee_for_weight(t.weight()))
+            .sum();
+        let cost_now = fee
+            + self
+                .predicted_feerate
+                .fee_for_weight(self.destination_type.weight());
+
+        Some(ConsolidationBatch {
+            inputs,
+            input_types,
+            vsize,
+            fee,
+            output_value,
+            savings: cost_later as i64 - cost_now as i64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::{BitcoinOutpoint, SpendScript};
+    use coins_core::types::tx::Transaction;
+
+    fn utxo(idx: u32, value: u64) -> (Utxo, InputType) {
+        let spk = ScriptPubkey::from_hex("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928").unwrap();
+        let outpoint = BitcoinOutpoint::new(Default::default(), idx);
+        (
+            Utxo::new(outpoint, value, spk, SpendScript::None),
+            InputType::Wpkh,
+        )
+    }
+
+    fn planner(current: u64, predicted: u64) -> ConsolidationPlanner {
+        let destination =
+            ScriptPubkey::from_hex("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928").unwrap();
+        ConsolidationPlanner::new(
+            destination,
+            InputType::Wpkh,
+            FeeRate::from_sat_per_vb(current),
+            FeeRate::from_sat_per_vb(predicted),
+        )
+    }
+
+    #[test]
+    fn it_plans_consolidations() {
+        // 68 vbyte inputs cost 68 sat at 1 sat/vbyte
+        let utxos: Vec<_> = (0..5)
+            .map(|i| utxo(i, 10_000 + i as u64))
+            .chain(std::iter::once(utxo(5, 300)))
+            .collect();
+
+        let plan = planner(1, 50).max_inputs(2).plan(&utxos);
+        assert_eq!(plan.timing, ConsolidationTiming::Now);
+        assert_eq!(plan.feerate, FeeRate::from_sat_per_vb(1));
+        // 5 economical coins in batches of 2. The last single coin is not batched
+        assert_eq!(plan.batches.len(), 2);
+        // smallest coins first
+        assert_eq!(plan.batches[0].inputs[0].value, 10_000);
+        assert_eq!(plan.batches[0].inputs[1].value, 10_001);
+
+        let batch = &plan.batches[0];
+        // 10.5 overhead + 2 * 68 in + 31 out
+        assert_eq!(batch.vsize, 178);
+        assert_eq!(batch.fee, 178);
+        assert_eq!(batch.output_value, 20_001 - 178);
+        // 2 * 68 * 50 later, vs 178 now + 68 * 50 later
+        assert_eq!(batch.savings, 6800 - 178 - 3400);
+        assert_eq!(plan.savings(), 2 * batch.savings);
+        assert_eq!(plan.fee(), 2 * 178);
+
+        let tx = batch
+            .builder::<MainnetEncoder>(planner(1, 50).destination(), 0xffff_fffd)
+            .build()
+            .unwrap();
+        assert_eq!(tx.inputs().len(), 2);
+        assert_eq!(tx.outputs()[0].value, batch.output_value);
+        assert_eq!(tx.inputs()[1].outpoint.idx, 1);
+    }
+
+    #[test]
+    fn it_waits_for_lower_feerates() {
+        let utxos: Vec<_> = (0..4).map(|i| utxo(i, 10_000)).collect();
+        let plan = planner(20, 10).plan(&utxos);
+        assert_eq!(plan.timing, ConsolidationTiming::Wait);
+        assert!(plan.savings() < 0);
+
+        // At 140 sat/vbyte, each input costs 9520 sat. The batch fee exceeds the value
+        let plan = planner(140, 200).plan(&utxos);
+        assert!(plan.batches.is_empty());
+        assert_eq!(plan.timing, ConsolidationTiming::Wait);
+
+        // A stricter dust policy leaves the coins untouched
+        let policy = Policy::default().dust_relay_feerate(FeeRate::from_sat_per_vb(200));
+        assert!(planner(1, 50)
+            .policy(policy)
+            .plan(&utxos)
+            .batches
+            .is_empty());
+    }
+}
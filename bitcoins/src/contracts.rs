@@ -0,0 +1,292 @@
+//! Script builders for hash-timelock contracts, and for keys gated by absolute or relative
+//! timelocks.
+//!
+//! Each builder returns a `WshContract`, holding the witness script and the P2WSH script pubkey
+//! that pays to it. Witnesses for each spend path are assembled by the builders, so that callers
+//! need only supply signatures and preimages.
+//!
+//! ```
+//! use bitcoins::{contracts::WshContract, types::LockTime};
+//!
+//! let contract = WshContract::new(LockTime::from_height(700_000).unwrap().check_script());
+//! let witness = contract.witness(vec![]);
+//! assert_eq!(witness.len(), 1);
+//! ```
+
+use coins_bip32::ecdsa::VerifyingKey;
+use coins_core::hashes::{Digest, Sha256};
+
+use crate::types::{
+    opcodes::Opcode,
+    script::{push_data, Script, ScriptPubkey, Witness, WitnessStackItem},
+    timelock::{LockTime, Sequence},
+};
+
+/// The length of an HTLC payment preimage. Claims with preimages of other lengths fail.
+pub const HTLC_PREIMAGE_LEN: u8 = 32;
+
+/// A P2WSH contract. The witness script, and the script pubkey that pays to it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WshContract {
+    /// The witness script
+    pub witness_script: Script,
+    /// The P2WSH script pubkey committing to the witness script
+    pub script_pubkey: ScriptPubkey,
+}
+
+impl WshContract {
+    /// Wrap a witness script in P2WSH
+    pub fn new(witness_script: Script) -> Self {
+        Self {
+            script_pubkey: ScriptPubkey::p2wsh(&witness_script),
+            witness_script,
+        }
+    }
+
+    /// Assemble the witness spending the contract. This is the arguments to the witness script,
+    /// followed by the witness script.
+    pub fn witness(&self, args: Vec<WitnessStackItem>) -> Witness {
+        let mut witness = args;
+        witness.push(self.witness_script.items().into());
+        witness
+    }
+
+    /// Instantiate the contract `<locktime> OP_CHECKLOCKTIMEVERIFY OP_DROP <key> OP_CHECKSIG`.
+    /// The spending transaction must have a locktime of at least `locktime`, of the same type,
+    /// and the spending input a non-final sequence. Spend it with `WshContract::key_witness`.
+    pub fn cltv_key<K>(locktime: LockTime, key: &K) -> Self
+    where
+        K: AsRef<VerifyingKey>,
+    {
+        Self::new(with_checksig(locktime.check_script(), key))
+    }
+
+    /// Instantiate the contract `<sequence> OP_CHECKSEQUENCEVERIFY OP_DROP <key> OP_CHECKSIG`.
+    /// The spending transaction must be version 2, and the spending input's sequence must encode
+    /// a relative locktime at least as long as `sequence`, of the same type. Spend it with
+    /// `WshContract::key_witness`.
+    pub fn csv_key<K>(sequence: Sequence, key: &K) -> Self
+    where
+        K: AsRef<VerifyingKey>,
+    {
+        Self::new(with_checksig(sequence.check_script(), key))
+    }
+
+    /// Assemble the witness spending a `cltv_key` or `csv_key` contract with a signature. The
+    /// signature must carry its sighash flag byte.
+    pub fn key_witness(&self, signature: &[u8]) -> Witness {
+        self.witness(vec![signature.into()])
+    }
+}
+
+/// Append `<key> OP_CHECKSIG` to a script
+fn with_checksig<K>(script: Script, key: &K) -> Script
+where
+    K: AsRef<VerifyingKey>,
+{
+    let mut v = script.items().to_vec();
+    push_data(&mut v, &key.as_ref().to_bytes());
+    v.push(Opcode::CheckSig.to_u8());
+    v.into()
+}
+
+/// A hash-timelock contract. The recipient may claim the funds by revealing the preimage of the
+/// payment hash. After the timeout, the sender may refund them.
+///
+/// The witness script is:
+///
+/// ```text
+/// OP_IF
+///     OP_SIZE 32 OP_EQUALVERIFY OP_SHA256 <payment_hash> OP_EQUALVERIFY <recipient>
+/// OP_ELSE
+///     <timeout> OP_CHECKLOCKTIMEVERIFY OP_DROP <refund>
+/// OP_ENDIF
+/// OP_CHECKSIG
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Htlc {
+    payment_hash: [u8; 32],
+    timeout: LockTime,
+    contract: WshContract,
+}
+
+impl Htlc {
+    /// Instantiate an HTLC. `payment_hash` is the SHA256 of the 32-byte preimage. The
+    /// `recipient` key claims with the preimage, and the `refund` key refunds after `timeout`.
+    pub fn new<K>(payment_hash: [u8; 32], recipient: &K, refund: &K, timeout: LockTime) -> Self
+    where
+        K: AsRef<VerifyingKey>,
+    {
+        let mut v = vec![Opcode::If.to_u8(), Opcode::Size.to_u8()];
+        push_data(&mut v, &[HTLC_PREIMAGE_LEN]);
+        v.extend(&[Opcode::EqualVerify.to_u8(), Opcode::Sha256.to_u8()]);
+        push_data(&mut v, &payment_hash);
+        v.push(Opcode::EqualVerify.to_u8());
+        push_data(&mut v, &recipient.as_ref().to_bytes());
+        v.push(Opcode::Else.to_u8());
+        v.extend(timeout.check_script().items());
+        push_data(&mut v, &refund.as_ref().to_bytes());
+        v.extend(&[Opcode::EndIf.to_u8(), Opcode::CheckSig.to_u8()]);
+        Self {
+            payment_hash,
+            timeout,
+            contract: WshContract::new(v.into()),
+        }
+    }
+
+    /// Instantiate an HTLC from the payment preimage, rather than its hash
+    pub fn from_preimage<K>(
+        preimage: &[u8; 32],
+        recipient: &K,
+        refund: &K,
+        timeout: LockTime,
+    ) -> Self
+    where
+        K: AsRef<VerifyingKey>,
+    {
+        let mut payment_hash = [0u8; 32];
+        payment_hash.copy_from_slice(&Sha256::digest(preimage));
+        Self::new(payment_hash, recipient, refund, timeout)
+    }
+
+    /// The SHA256 of the payment preimage
+    pub fn payment_hash(&self) -> [u8; 32] {
+        self.payment_hash
+    }
+
+    /// The locktime after which the HTLC may be refunded
+    pub fn timeout(&self) -> LockTime {
+        self.timeout
+    }
+
+    /// True if `preimage` is the preimage of the payment hash
+    pub fn is_preimage(&self, preimage: &[u8; 32]) -> bool {
+        Sha256::digest(preimage)[..] == self.payment_hash[..]
+    }
+
+    /// The witness script and P2WSH script pubkey of the HTLC
+    pub fn contract(&self) -> &WshContract {
+        &self.contract
+    }
+
+    /// The witness script of the HTLC
+    pub fn witness_script(&self) -> &Script {
+        &self.contract.witness_script
+    }
+
+    /// The P2WSH script pubkey paying to the HTLC
+    pub fn script_pubkey(&self) -> &ScriptPubkey {
+        &self.contract.script_pubkey
+    }
+
+    /// Assemble the witness claiming the HTLC with the recipient's signature and the payment
+    /// preimage. The signature must carry its sighash flag byte.
+    pub fn claim_witness(&self, signature: &[u8], preimage: &[u8; 32]) -> Witness {
+        self.contract.witness(vec![
+            signature.into(),
+            preimage[..].into(),
+            vec![0x01].into(), // take the OP_IF branch
+        ])
+    }
+
+    /// Assemble the witness refunding the HTLC with the refund key's signature. The signature
+    /// must carry its sighash flag byte. The spending transaction must have a locktime of at
+    /// least the timeout, and the spending input a non-final sequence.
+    pub fn refund_witness(&self, signature: &[u8]) -> Witness {
+        self.contract.witness(vec![
+            signature.into(),
+            vec![].into(), // take the OP_ELSE branch
+        ])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use coins_bip32::{path::KeyDerivation, prelude::DerivedPubkey};
+
+    use crate::types::{
+        script::BitcoinScript,
+        stack::{WitnessData, WitnessStack},
+        timelock::{RelativeLock, ScriptTimelocks},
+    };
+
+    fn key(h: &str) -> DerivedPubkey {
+        let derivation = KeyDerivation {
+            root: [0u8; 4].into(),
+            path: vec![].into(),
+        };
+        let key = VerifyingKey::from_sec1_bytes(&hex::decode(h).unwrap()).unwrap();
+        DerivedPubkey::new(key, derivation)
+    }
+
+    const A: &str = "02ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f8";
+    const B: &str = "02fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f";
+
+    #[test]
+    fn it_builds_htlcs() {
+        let preimage = [7u8; 32];
+        let timeout = LockTime::from_height(700_000).unwrap();
+        let htlc = Htlc::from_preimage(&preimage, &key(A), &key(B), timeout);
+        let payment_hash = hex::encode(Sha256::digest(&preimage));
+
+        assert_eq!(
+            htlc.witness_script().to_asm(),
+            format!(
+                "OP_IF OP_SIZE <20> OP_EQUALVERIFY OP_SHA256 <{}> OP_EQUALVERIFY <{}> OP_ELSE <60ae0a> OP_CHECKLOCKTIMEVERIFY OP_DROP <{}> OP_ENDIF OP_CHECKSIG",
+                payment_hash, A, B
+            )
+        );
+        assert_eq!(
+            htlc.script_pubkey(),
+            &ScriptPubkey::p2wsh(htlc.witness_script())
+        );
+        assert_eq!(
+            ScriptTimelocks::from_script_branches(htlc.witness_script(), &[false])
+                .unwrap()
+                .locktimes,
+            vec![700_000]
+        );
+
+        let claim = htlc.claim_witness(&[0x30, 0x01], &preimage);
+        assert_eq!(claim.len(), 4);
+        assert_eq!(claim[1].items(), &preimage[..]);
+        assert_eq!(claim[2].items(), &[0x01]);
+        let refund = htlc.refund_witness(&[0x30, 0x01]);
+        assert_eq!(refund.len(), 3);
+        assert!(refund[1].is_empty());
+        match refund.interpret(htlc.script_pubkey()) {
+            WitnessData::Wsh(wsh) => {
+                assert_eq!(&wsh.witness_script, htlc.witness_script());
+                assert_eq!(wsh.args.len(), 2);
+            }
+            other => panic!("expected a P2WSH witness, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn it_builds_timelocked_key_contracts() {
+        let cltv = WshContract::cltv_key(LockTime::from_height(16).unwrap(), &key(A));
+        assert_eq!(
+            cltv.witness_script.to_asm(),
+            format!("OP_16 OP_CHECKLOCKTIMEVERIFY OP_DROP <{}> OP_CHECKSIG", A)
+        );
+
+        let sequence = RelativeLock::Blocks(144).to_sequence();
+        let csv = WshContract::csv_key(sequence, &key(B));
+        assert_eq!(
+            csv.witness_script.to_asm(),
+            format!("<9000> OP_CHECKSEQUENCEVERIFY OP_DROP <{}> OP_CHECKSIG", B)
+        );
+        assert_eq!(
+            ScriptTimelocks::from_script(&csv.witness_script)
+                .unwrap()
+                .sequences,
+            vec![144]
+        );
+
+        let witness = csv.key_witness(&[0x30, 0x01]);
+        assert_eq!(witness.len(), 2);
+        assert_eq!(witness[1].items(), csv.witness_script.items());
+    }
+}
@@ -1,37 +1,24 @@
 //! Contains simplified access to `bech32` and `base58check` encoder/decoder for Bitcoin
 //! addresses.
 
-use bech32::Error as BechError;
-use coins_core::enc::{
-    decode_bech32 as core_decode_bech32, encode_bech32 as core_encode_bech32, EncodingError,
-    EncodingResult,
-};
+use coins_core::enc::{decode_segwit_address, encode_segwit_address, EncodingResult};
 
-/// Encode a byte vector to bech32. This function expects `v` to be a witness program, and will
-/// return an `UnknownScriptType` if it does not meet the witness program format.
-pub fn encode_bech32(hrp: &str, v: &[u8]) -> EncodingResult<String> {
-    if v.len() < 2 || v.len() > 40 {
-        return Err(BechError::InvalidLength.into());
-    }
-
-    let (version_and_len, payload) = v.split_at(2);
-    if version_and_len[0] > 16 || version_and_len[1] as usize != payload.len() {
-        return Err(EncodingError::UnknownScriptType);
-    };
+use crate::types::witness_program::WitnessProgram;
 
-    core_encode_bech32(hrp, version_and_len[0], &payload)
+/// Encode a byte vector to bech32, or bech32m for witness versions 1 and up. This function
+/// expects `v` to be a witness program, and will return an `UnknownScriptType` if it does not
+/// meet the witness program format.
+pub fn encode_bech32(hrp: &str, v: &[u8]) -> EncodingResult<String> {
+    let program = WitnessProgram::from_script(v)?;
+    encode_segwit_address(hrp, program.version(), program.program())
 }
 
-/// Decode a witness program from a bech32 string. Caller specifies an expected HRP. If a
-/// different HRP is found, returns `WrongHrp`.
+/// Decode a witness program from a segwit address. Caller specifies an expected HRP. If a
+/// different HRP is found, returns `WrongHrp`. Enforces BIP173 and BIP350. See
+/// `decode_segwit_address` for the errors returned.
 pub fn decode_bech32(expected_hrp: &str, s: &str) -> EncodingResult<Vec<u8>> {
-    let (version, data) = core_decode_bech32(expected_hrp, s)?;
-
-    // Encode as witness program: witness version 0, then len(payload), then payload.
-    let mut s: Vec<u8> = vec![version, data.len() as u8];
-    s.extend(&data);
-
-    Ok(s)
+    let (version, data) = decode_segwit_address(expected_hrp, s)?;
+    Ok(WitnessProgram::new(version, data)?.to_script())
 }
 
 #[cfg(test)]
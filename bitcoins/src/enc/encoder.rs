@@ -2,6 +2,7 @@
 
 use std::marker::PhantomData;
 
+use coins_bip32::{enc::XKeyEncoder, path::DerivationPath, primitives::Hint, BIP32_HARDEN};
 use coins_core::{
     bases::{decode_base58, encode_base58},
     enc::{AddressEncoder, EncodingError, EncodingResult},
@@ -10,7 +11,10 @@ use coins_core::{
 
 use crate::{
     enc::bases::{decode_bech32, encode_bech32},
-    types::script::{ScriptPubkey, ScriptType},
+    types::{
+        script::{ScriptPubkey, ScriptType},
+        witness_program::WitnessProgram,
+    },
 };
 
 /// The available Bitcoin Address types, implemented as a type enum around strings.
@@ -64,12 +68,197 @@ impl Address {
     pub fn to_descriptor(&self) -> String {
         format!("addr({})", self.as_string())
     }
+
+    /// Get the type of the address
+    pub fn address_type(&self) -> AddressType {
+        match &self {
+            Address::Pkh(_) => AddressType::Pkh,
+            Address::Sh(_) => AddressType::Sh,
+            Address::Wpkh(_) => AddressType::Wpkh,
+            Address::Wsh(_) => AddressType::Wsh,
+        }
+    }
+
+    /// Get the script pubkey the address pays to, under the encoder `E`. Errors if the address
+    /// does not belong to `E`'s network.
+    pub fn to_script_pubkey<E: BitcoinEncoderMarker>(&self) -> EncodingResult<ScriptPubkey> {
+        let address = E::string_to_address(self.as_ref())?;
+        Ok(E::decode_address(&address))
+    }
+}
+
+impl ScriptPubkey {
+    /// Encode the script pubkey as an address under the encoder `E`. Errors if the script pubkey
+    /// is not a PKH, SH, WPKH, or WSH script.
+    pub fn to_address<E: BitcoinEncoderMarker>(&self) -> EncodingResult<Address> {
+        E::encode_address(self)
+    }
+}
+
+/// The Bitcoin networks supported by this crate's encoders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NetworkKind {
+    /// Bitcoin Mainnet
+    Mainnet,
+    /// Bitcoin Testnet
+    Testnet,
+    /// Bitcoin Signet
+    Signet,
+    /// Bitcoin Regtest
+    Regtest,
+}
+
+impl NetworkKind {
+    /// All supported networks, in the order `Address::parse_any` tries them
+    pub const ALL: [NetworkKind; 4] = [
+        NetworkKind::Mainnet,
+        NetworkKind::Testnet,
+        NetworkKind::Signet,
+        NetworkKind::Regtest,
+    ];
+
+    /// Parse an address string with this network's encoder
+    pub fn string_to_address(self, s: &str) -> EncodingResult<Address> {
+        match self {
+            NetworkKind::Mainnet => MainnetEncoder::string_to_address(s),
+            NetworkKind::Testnet => TestnetEncoder::string_to_address(s),
+            NetworkKind::Signet => SignetEncoder::string_to_address(s),
+            NetworkKind::Regtest => RegtestEncoder::string_to_address(s),
+        }
+    }
+
+    /// Decode an address to a script pubkey with this network's encoder. The address must
+    /// belong to this network.
+    pub fn decode_address(self, address: &Address) -> ScriptPubkey {
+        match self {
+            NetworkKind::Mainnet => MainnetEncoder::decode_address(address),
+            NetworkKind::Testnet => TestnetEncoder::decode_address(address),
+            NetworkKind::Signet => SignetEncoder::decode_address(address),
+            NetworkKind::Regtest => RegtestEncoder::decode_address(address),
+        }
+    }
+}
+
+/// An address of previously unknown origin, with the network it was found to belong to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedAddress {
+    /// The address
+    pub address: Address,
+    /// The network the address belongs to
+    pub network: NetworkKind,
+}
+
+impl ParsedAddress {
+    /// The type of the address
+    pub fn address_type(&self) -> AddressType {
+        self.address.address_type()
+    }
+
+    /// The script pubkey the address pays to
+    pub fn script_pubkey(&self) -> ScriptPubkey {
+        self.network.decode_address(&self.address)
+    }
+}
+
+impl Address {
+    /// Parse an address string of unknown network. Each supported network's encoding is tried
+    /// in turn. Returns the address, and the network it belongs to.
+    ///
+    /// Regtest shares testnet's base58check version bytes, so legacy regtest addresses are
+    /// reported as testnet. Only `bcrt` bech32 addresses are reported as regtest.
+    pub fn parse_any(s: &str) -> EncodingResult<ParsedAddress> {
+        NetworkKind::ALL
+            .iter()
+            .find_map(|network| {
+                network
+                    .string_to_address(s)
+                    .ok()
+                    .map(|address| ParsedAddress {
+                        address,
+                        network: *network,
+                    })
+            })
+            .ok_or(EncodingError::UnknownScriptType)
+    }
+}
+
+/// The standard address types. This is the common vocabulary for selecting an output type, a
+/// spend type, a BIP44-style purpose, and an extended key version.
+///
+/// `Sh` is treated as P2WPKH nested in P2SH wherever a single spend type is required. This
+/// matches BIP49.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressType {
+    /// Legacy Pay to Pubkeyhash (P2PKH)
+    Pkh,
+    /// Legacy Pay to Scripthash (P2SH)
+    Sh,
+    /// Witness Pay to Pubkeyhash (P2WPKH)
+    Wpkh,
+    /// Witness Pay to Scripthash (P2WSH)
+    Wsh,
+    /// Taproot (P2TR)
+    Tr,
+}
+
+impl AddressType {
+    /// True if spending this address type requires a witness. `Sh` is assumed to wrap a
+    /// witness program.
+    pub fn is_witness(&self) -> bool {
+        !matches!(self, AddressType::Pkh)
+    }
+
+    /// The BIP44-style purpose used to derive keys for this address type. 44 for `Pkh`, 49 for
+    /// `Sh`, 84 for `Wpkh` and `Wsh`, and 86 for `Tr`.
+    pub fn purpose(&self) -> u32 {
+        match self {
+            AddressType::Pkh => 44,
+            AddressType::Sh => 49,
+            AddressType::Wpkh | AddressType::Wsh => 84,
+            AddressType::Tr => 86,
+        }
+    }
+
+    /// The address type associated with a BIP44-style purpose, if any. Purpose 84 is `Wpkh`.
+    pub fn from_purpose(purpose: u32) -> Option<Self> {
+        match purpose {
+            44 => Some(AddressType::Pkh),
+            49 => Some(AddressType::Sh),
+            84 => Some(AddressType::Wpkh),
+            86 => Some(AddressType::Tr),
+            _ => None,
+        }
+    }
+
+    /// The derivation path of an account for this address type. I.e.
+    /// `m/purpose'/coin_type'/account'`.
+    pub fn account_path(&self, coin_type: u32, account: u32) -> DerivationPath {
+        vec![
+            self.purpose() + BIP32_HARDEN,
+            coin_type + BIP32_HARDEN,
+            account + BIP32_HARDEN,
+        ]
+        .into()
+    }
+
+    /// The extended key version hint for this address type. Taproot uses the standard BIP32
+    /// version bytes.
+    pub fn hint(&self) -> Hint {
+        match self {
+            AddressType::Pkh | AddressType::Tr => Hint::Legacy,
+            AddressType::Sh => Hint::Compatibility,
+            AddressType::Wpkh | AddressType::Wsh => Hint::SegWit,
+        }
+    }
 }
 
 /// NetworkParams holds the encoding paramteres for a bitcoin-like network. Currently this is
-/// composed of the address version bytes for Legacy PKH and SH addresses, and the bech32
-/// human-readable prefix for witness addresses.
+/// composed of the address version bytes for Legacy PKH and SH addresses, the bech32
+/// human-readable prefix for witness addresses, and the extended key encoder.
 pub trait NetworkParams {
+    /// The encoder for BIP32 extended keys on this network. E.g. xpub/xprv on mainnet.
+    type XKeyEncoder: XKeyEncoder;
+
     /// The BECH32 HRP. "bc" for mainnet.
     const HRP: &'static str;
     /// The Legacy PKH base58check version byte. 0x00 for mainnet.
@@ -82,6 +271,8 @@ pub trait NetworkParams {
 pub trait BitcoinEncoderMarker:
     AddressEncoder<Address = Address, Error = EncodingError, RecipientIdentifier = ScriptPubkey>
 {
+    /// The encoder for BIP32 extended keys on this network
+    type XKeyEncoder: XKeyEncoder;
 }
 
 /// The standard encoder for Bitcoin networks. Parameterized by a `NetworkParams` type and an
@@ -95,62 +286,110 @@ impl<P: NetworkParams> AddressEncoder for BitcoinEncoder<P> {
     type RecipientIdentifier = ScriptPubkey;
 
     fn encode_address(s: &ScriptPubkey) -> EncodingResult<Address> {
-        match s.standard_type() {
-            ScriptType::Pkh(payload) => {
-                // s.items contains the op codes. we want only the pkh
-                Ok(Address::Pkh(encode_base58(
-                    P::PKH_VERSION,
-                    payload.as_slice(),
-                )))
-            }
-            ScriptType::Sh(payload) => {
-                // s.items contains the op codes. we want only the sh
-                Ok(Address::Sh(encode_base58(
-                    P::SH_VERSION,
-                    payload.as_slice(),
-                )))
-            }
-            ScriptType::Wsh(_) => Ok(Address::Wsh(encode_bech32(P::HRP, &s.items())?)),
-            ScriptType::Wpkh(_) => Ok(Address::Wpkh(encode_bech32(P::HRP, &s.items())?)),
-            ScriptType::OpReturn(_) => Err(EncodingError::NullDataScript),
-            ScriptType::NonStandard => Err(EncodingError::UnknownScriptType),
-        }
+        encode_address_with(P::HRP, P::PKH_VERSION, P::SH_VERSION, s)
     }
 
     fn decode_address(addr: &Address) -> ScriptPubkey {
-        match &addr {
-            Address::Pkh(s) => decode_base58(P::PKH_VERSION, s).unwrap().into(),
-            Address::Sh(s) => decode_base58(P::SH_VERSION, s).unwrap().into(),
-            Address::Wpkh(s) | Address::Wsh(s) => decode_bech32(P::HRP, &s).unwrap().into(),
-        }
+        decode_address_with(P::HRP, P::PKH_VERSION, P::SH_VERSION, addr).unwrap()
     }
 
     fn string_to_address(string: &str) -> EncodingResult<Address> {
-        let s = string.to_owned();
-        if s.starts_with(P::HRP) {
-            let result = decode_bech32(P::HRP, &s)?;
-            match result.len() {
-                22 => Ok(Address::Wpkh(s)),
-                34 => Ok(Address::Wsh(s)),
-                _ => Err(EncodingError::UnknownScriptType),
-            }
-        } else if decode_base58(P::PKH_VERSION, &s).is_ok() {
-            Ok(Address::Pkh(s))
-        } else if decode_base58(P::SH_VERSION, &s).is_ok() {
-            Ok(Address::Sh(s))
+        string_to_address_with(P::HRP, P::PKH_VERSION, P::SH_VERSION, string)
+    }
+}
+
+/// Encode a script pubkey as an address, using the given bech32 HRP and base58check version
+/// bytes
+pub(crate) fn encode_address_with(
+    hrp: &str,
+    pkh_version: u8,
+    sh_version: u8,
+    s: &ScriptPubkey,
+) -> EncodingResult<Address> {
+    match s.standard_type() {
+        ScriptType::Pkh(payload) => {
+            // s.items contains the op codes. we want only the pkh
+            Ok(Address::Pkh(encode_base58(pkh_version, payload.as_slice())))
+        }
+        ScriptType::Sh(payload) => {
+            // s.items contains the op codes. we want only the sh
+            Ok(Address::Sh(encode_base58(sh_version, payload.as_slice())))
+        }
+        ScriptType::Wsh(_) => Ok(Address::Wsh(encode_bech32(hrp, s.items())?)),
+        ScriptType::Wpkh(_) => Ok(Address::Wpkh(encode_bech32(hrp, s.items())?)),
+        ScriptType::OpReturn(_) => Err(EncodingError::NullDataScript),
+        ScriptType::Pk(_) | ScriptType::Multisig { .. } | ScriptType::NonStandard => {
+            Err(EncodingError::UnknownScriptType)
+        }
+    }
+}
+
+/// Decode an address to a script pubkey, using the given bech32 HRP and base58check version
+/// bytes. Errors if the address does not belong to the network.
+pub(crate) fn decode_address_with(
+    hrp: &str,
+    pkh_version: u8,
+    sh_version: u8,
+    addr: &Address,
+) -> EncodingResult<ScriptPubkey> {
+    match &addr {
+        Address::Pkh(s) => {
+            let mut v = vec![0x76, 0xa9, 0x14]; // DUP, HASH160, PUSH_20
+            v.extend(decode_base58(pkh_version, s)?);
+            v.extend(&[0x88, 0xac]); // EQUALVERIFY, CHECKSIG
+            Ok(v.into())
+        }
+        Address::Sh(s) => {
+            let mut v = vec![0xa9, 0x14]; // HASH160, PUSH_20
+            v.extend(decode_base58(sh_version, s)?);
+            v.push(0x87); // EQUAL
+            Ok(v.into())
+        }
+        Address::Wpkh(s) | Address::Wsh(s) => Ok(decode_bech32(hrp, s)?.into()),
+    }
+}
+
+/// Parse an address string, using the given bech32 HRP and base58check version bytes. Segwit
+/// addresses are validated strictly, and report why they are invalid. A segwit address for
+/// another network returns `WrongHrp`.
+pub(crate) fn string_to_address_with(
+    hrp: &str,
+    pkh_version: u8,
+    sh_version: u8,
+    string: &str,
+) -> EncodingResult<Address> {
+    let s = string.to_owned();
+    if s.to_ascii_lowercase().starts_with(&format!("{}1", hrp)) {
+        let program = WitnessProgram::from_script(&decode_bech32(hrp, &s)?)?;
+        if program.is_p2wpkh() {
+            Ok(Address::Wpkh(s))
+        } else if program.is_p2wsh() {
+            Ok(Address::Wsh(s))
         } else {
             Err(EncodingError::UnknownScriptType)
         }
+    } else if decode_base58(pkh_version, &s).is_ok() {
+        Ok(Address::Pkh(s))
+    } else if decode_base58(sh_version, &s).is_ok() {
+        Ok(Address::Sh(s))
+    } else {
+        match decode_bech32(hrp, &s) {
+            Err(e @ EncodingError::WrongHrp { .. }) => Err(e),
+            _ => Err(EncodingError::UnknownScriptType),
+        }
     }
 }
 
-impl<P: NetworkParams> BitcoinEncoderMarker for BitcoinEncoder<P> {}
+impl<P: NetworkParams> BitcoinEncoderMarker for BitcoinEncoder<P> {
+    type XKeyEncoder = P::XKeyEncoder;
+}
 
 /// A param struct for Bitcoin Mainnet
 #[derive(Debug, Clone)]
 pub struct Main;
 
 impl NetworkParams for Main {
+    type XKeyEncoder = coins_bip32::enc::MainnetEncoder;
     const HRP: &'static str = "bc";
     const PKH_VERSION: u8 = 0x00;
     const SH_VERSION: u8 = 0x05;
@@ -161,6 +400,7 @@ impl NetworkParams for Main {
 pub struct Test;
 
 impl NetworkParams for Test {
+    type XKeyEncoder = coins_bip32::enc::TestnetEncoder;
     const HRP: &'static str = "tb";
     const PKH_VERSION: u8 = 0x6f;
     const SH_VERSION: u8 = 0xc4;
@@ -171,11 +411,23 @@ impl NetworkParams for Test {
 pub struct Sig;
 
 impl NetworkParams for Sig {
+    type XKeyEncoder = coins_bip32::enc::TestnetEncoder;
     const HRP: &'static str = "sb";
     const PKH_VERSION: u8 = 0x7d;
     const SH_VERSION: u8 = 0x57;
 }
 
+/// A param struct for Bitcoin Regtest. Legacy addresses share testnet's version bytes.
+#[derive(Debug, Clone)]
+pub struct Reg;
+
+impl NetworkParams for Reg {
+    type XKeyEncoder = coins_bip32::enc::TestnetEncoder;
+    const HRP: &'static str = "bcrt";
+    const PKH_VERSION: u8 = 0x6f;
+    const SH_VERSION: u8 = 0xc4;
+}
+
 /// An encoder for Bitcoin Mainnet
 pub type MainnetEncoder = BitcoinEncoder<Main>;
 
@@ -185,6 +437,9 @@ pub type TestnetEncoder = BitcoinEncoder<Test>;
 /// An encoder for Bitcoin Signet
 pub type SignetEncoder = BitcoinEncoder<Sig>;
 
+/// An encoder for Bitcoin Regtest
+pub type RegtestEncoder = BitcoinEncoder<Reg>;
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -215,11 +470,7 @@ mod test {
             assert_eq!(MainnetEncoder::string_to_address(&case.0).unwrap(), case.1);
         }
 
-        let errors = [
-            "hello",
-            "this isn't a real address",
-            "bc10pu8s7rc0pu8s7rc0putt44am", // valid bech32, bad length
-        ];
+        let errors = ["hello", "this isn't a real address"];
         for case in errors.iter() {
             match MainnetEncoder::string_to_address(case) {
                 Err(EncodingError::UnknownScriptType) => {}
@@ -228,6 +479,45 @@ mod test {
         }
     }
 
+    #[test]
+    fn it_reports_why_segwit_addresses_are_invalid() {
+        // Uppercase addresses are valid
+        let address =
+            MainnetEncoder::string_to_address("BC1QZA7DFGL2Q83CF68FQKKDD754QX546H4U9VD9TG")
+                .unwrap();
+        assert_eq!(
+            MainnetEncoder::encode_address(&MainnetEncoder::decode_address(&address)).unwrap(),
+            Address::Wpkh("bc1qza7dfgl2q83cf68fqkkdd754qx546h4u9vd9tg".to_owned())
+        );
+
+        assert!(matches!(
+            MainnetEncoder::string_to_address("bc1qza7dfgl2q83cf68fqkkdd754qx546h4u9vD9tg"),
+            Err(EncodingError::BechError(bech32::Error::MixedCase))
+        ));
+        assert!(matches!(
+            MainnetEncoder::string_to_address(
+                "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7"
+            ),
+            Err(EncodingError::WrongHrp { .. })
+        ));
+        assert!(matches!(
+            TestnetEncoder::string_to_address("bc1qza7dfgl2q83cf68fqkkdd754qx546h4u9vd9tg"),
+            Err(EncodingError::WrongHrp { .. })
+        ));
+        // Witness version 15 with a bech32 checksum
+        assert!(matches!(
+            MainnetEncoder::string_to_address("bc10pu8s7rc0pu8s7rc0putt44am"),
+            Err(EncodingError::WrongChecksumVariant { version: 15, .. })
+        ));
+        // A valid taproot address has no `Address` variant yet
+        assert!(matches!(
+            MainnetEncoder::string_to_address(
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0"
+            ),
+            Err(EncodingError::UnknownScriptType)
+        ));
+    }
+
     #[test]
     fn it_encodes_addresses() {
         let cases = [
@@ -263,6 +553,9 @@ mod test {
         ];
         for case in cases.iter() {
             assert_eq!(MainnetEncoder::encode_address(&case.0).unwrap(), case.1);
+            assert_eq!(case.0.to_address::<MainnetEncoder>().unwrap(), case.1);
+            assert_eq!(case.1.to_script_pubkey::<MainnetEncoder>().unwrap(), case.0);
+            assert!(case.1.to_script_pubkey::<TestnetEncoder>().is_err());
         }
         let errors = [
             (ScriptPubkey::new(hex::decode("01201bf8a1831db5443b42a44f30a121d1b616d011ab15df62b588722a845864cc99").unwrap())), // wrong witness program version
@@ -283,6 +576,22 @@ mod test {
         }
     }
 
+    #[test]
+    fn it_encodes_regtest_addresses() {
+        let wpkh = ScriptPubkey::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        let address = RegtestEncoder::encode_address(&wpkh).unwrap();
+        assert_eq!(
+            address,
+            Address::Wpkh("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080".to_owned())
+        );
+        assert_eq!(
+            RegtestEncoder::string_to_address(address.as_ref()).unwrap(),
+            address
+        );
+        assert_eq!(RegtestEncoder::decode_address(&address), wpkh);
+        assert!(MainnetEncoder::string_to_address(address.as_ref()).is_err());
+    }
+
     #[test]
     fn it_allows_you_to_unwrap_strings_from_addresses() {
         let cases = [
@@ -309,4 +618,105 @@ mod test {
             assert_eq!(case.1.as_string(), case.0);
         }
     }
+
+    #[test]
+    fn it_maps_address_types() {
+        let cases = [
+            (
+                "bc1qza7dfgl2q83cf68fqkkdd754qx546h4u9vd9tg",
+                AddressType::Wpkh,
+            ),
+            (
+                "bc1qwqdg6squsna38e46795at95yu9atm8azzmyvckulcc7kytlcckxswvvzej",
+                AddressType::Wsh,
+            ),
+            ("1AqE7oGF1EUoJviX1uuYrwpRBdEBTuGhES", AddressType::Pkh),
+            ("3HXNFmJpxjgTVFN35Y9f6Waje5YFsLEQZ2", AddressType::Sh),
+        ];
+        for case in cases.iter() {
+            let addr = MainnetEncoder::string_to_address(case.0).unwrap();
+            assert_eq!(addr.address_type(), case.1);
+        }
+
+        let scripts = [
+            (
+                "76a9141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b92888ac",
+                AddressType::Pkh,
+            ),
+            (
+                "a914e88869b88866281ab166541ad8aafba8f8aba47a87",
+                AddressType::Sh,
+            ),
+            (
+                "00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928",
+                AddressType::Wpkh,
+            ),
+            (
+                "00201bf8a1831db5443b42a44f30a121d1b616d011ab15df62b588722a845864cc99",
+                AddressType::Wsh,
+            ),
+            (
+                "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+                AddressType::Tr,
+            ),
+        ];
+        for case in scripts.iter() {
+            let script = ScriptPubkey::from_hex(case.0).unwrap();
+            assert_eq!(script.address_type(), Some(case.1));
+        }
+        assert_eq!(ScriptPubkey::op_return(&[0x01]).address_type(), None);
+
+        // Wsh shares purpose 84 with Wpkh
+        for t in [
+            AddressType::Pkh,
+            AddressType::Sh,
+            AddressType::Wpkh,
+            AddressType::Tr,
+        ]
+        .iter()
+        {
+            assert_eq!(AddressType::from_purpose(t.purpose()), Some(*t));
+        }
+        assert_eq!(AddressType::Wsh.purpose(), 84);
+
+        assert_eq!(
+            AddressType::Tr.account_path(0, 1).derivation_string(),
+            "m/86'/0'/1'"
+        );
+        assert_eq!(AddressType::Sh.hint(), Hint::Compatibility);
+    }
+
+    #[test]
+    fn it_parses_addresses_of_any_network() {
+        let wpkh = ScriptPubkey::from_hex("00141bf8a1831db5443b42a44f30a121d1b616d011ab").unwrap();
+        let pkh =
+            ScriptPubkey::from_hex("76a9140e5c3c8d420c7f11e88d76f7b860d471e6517a4488ac").unwrap();
+        let sh = ScriptPubkey::from_hex("a914e88869b88866281ab166541ad8aafba8f8aba47a87").unwrap();
+
+        for network in NetworkKind::ALL.iter() {
+            for spk in [&wpkh, &pkh, &sh].iter() {
+                let address = match network {
+                    NetworkKind::Mainnet => MainnetEncoder::encode_address(spk),
+                    NetworkKind::Testnet => TestnetEncoder::encode_address(spk),
+                    NetworkKind::Signet => SignetEncoder::encode_address(spk),
+                    NetworkKind::Regtest => RegtestEncoder::encode_address(spk),
+                }
+                .unwrap();
+                let parsed = Address::parse_any(address.as_ref()).unwrap();
+                match (network, address.address_type()) {
+                    (NetworkKind::Regtest, AddressType::Pkh)
+                    | (NetworkKind::Regtest, AddressType::Sh) => {
+                        assert_eq!(parsed.network, NetworkKind::Testnet)
+                    }
+                    _ => assert_eq!(parsed.network, *network),
+                }
+                assert_eq!(parsed.address, address);
+                assert_eq!(parsed.address_type(), address.address_type());
+                assert_eq!(&parsed.script_pubkey(), *spk);
+            }
+        }
+
+        assert!(Address::parse_any("bc1qr0u2rqcak4zrks4yfuc2zgw3kctdqydt3wy5yi").is_err());
+        assert!(Address::parse_any("not an address").is_err());
+    }
 }
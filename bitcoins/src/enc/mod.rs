@@ -4,6 +4,8 @@
 
 pub mod bases;
 pub mod encoder;
+pub mod params;
 
 pub use bases::*;
 pub use encoder::*;
+pub use params::*;
@@ -0,0 +1,183 @@
+//! Network parameters selected at runtime.
+//!
+//! The `BitcoinEncoder` types fix their network at compile time. `AddressParams` holds the same
+//! address parameters as a value, so that a single binary can encode and decode addresses for
+//! mainnet, testnet, signet, regtest, or a custom chain chosen by configuration. It implements
+//! `serde` traits, so it can be read directly from a config file.
+//!
+//! Extended keys are not covered. They are still encoded with the compile-time `XKeyEncoder`s.
+//!
+//! ```
+//! use bitcoins::enc::AddressParams;
+//!
+//! let params = AddressParams::by_name("regtest").unwrap();
+//! let address = params
+//!     .string_to_address("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080")
+//!     .unwrap();
+//! let script_pubkey = params.decode_address(&address).unwrap();
+//! assert_eq!(params.encode_address(&script_pubkey).unwrap(), address);
+//! ```
+
+use coins_core::enc::EncodingResult;
+
+use crate::{
+    enc::encoder::{
+        decode_address_with, encode_address_with, string_to_address_with, Address, Main,
+        NetworkKind, NetworkParams, Reg, Sig, Test,
+    },
+    types::script::ScriptPubkey,
+};
+
+/// The address encoding parameters of a bitcoin-like network, chosen at runtime.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AddressParams {
+    /// The bech32 HRP. "bc" for mainnet.
+    pub hrp: String,
+    /// The Legacy PKH base58check version byte. 0x00 for mainnet.
+    pub pkh_version: u8,
+    /// The Legacy SH base58check version byte. 0x05 for mainnet.
+    pub sh_version: u8,
+}
+
+impl AddressParams {
+    /// Instantiate parameters for a custom network
+    pub fn new<S: Into<String>>(hrp: S, pkh_version: u8, sh_version: u8) -> Self {
+        Self {
+            hrp: hrp.into(),
+            pkh_version,
+            sh_version,
+        }
+    }
+
+    /// Copy the parameters of a compile-time `NetworkParams` type
+    pub fn from_params<P: NetworkParams>() -> Self {
+        Self::new(P::HRP, P::PKH_VERSION, P::SH_VERSION)
+    }
+
+    /// The parameters of Bitcoin Mainnet
+    pub fn mainnet() -> Self {
+        Self::from_params::<Main>()
+    }
+
+    /// The parameters of Bitcoin Testnet
+    pub fn testnet() -> Self {
+        Self::from_params::<Test>()
+    }
+
+    /// The parameters of Bitcoin Signet
+    pub fn signet() -> Self {
+        Self::from_params::<Sig>()
+    }
+
+    /// The parameters of Bitcoin Regtest
+    pub fn regtest() -> Self {
+        Self::from_params::<Reg>()
+    }
+
+    /// Look up the parameters of a standard network by name. Accepts "mainnet", "testnet",
+    /// "signet", and "regtest", as well as Bitcoin Core's "main", "test", and "bitcoin".
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "mainnet" | "main" | "bitcoin" => Some(Self::mainnet()),
+            "testnet" | "test" => Some(Self::testnet()),
+            "signet" => Some(Self::signet()),
+            "regtest" => Some(Self::regtest()),
+            _ => None,
+        }
+    }
+
+    /// Encode a script pubkey as an address on this network
+    pub fn encode_address(&self, s: &ScriptPubkey) -> EncodingResult<Address> {
+        encode_address_with(&self.hrp, self.pkh_version, self.sh_version, s)
+    }
+
+    /// Decode an address to a script pubkey. Errors if the address does not belong to this
+    /// network.
+    pub fn decode_address(&self, address: &Address) -> EncodingResult<ScriptPubkey> {
+        decode_address_with(&self.hrp, self.pkh_version, self.sh_version, address)
+    }
+
+    /// Parse an address string, checking that it belongs to this network
+    pub fn string_to_address(&self, s: &str) -> EncodingResult<Address> {
+        string_to_address_with(&self.hrp, self.pkh_version, self.sh_version, s)
+    }
+}
+
+impl From<NetworkKind> for AddressParams {
+    fn from(network: NetworkKind) -> Self {
+        match network {
+            NetworkKind::Mainnet => Self::mainnet(),
+            NetworkKind::Testnet => Self::testnet(),
+            NetworkKind::Signet => Self::signet(),
+            NetworkKind::Regtest => Self::regtest(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::enc::encoder::MainnetEncoder;
+    use coins_core::enc::{AddressEncoder, EncodingError};
+
+    #[test]
+    fn it_matches_compile_time_encoders() {
+        let params = AddressParams::from(NetworkKind::Mainnet);
+        let addrs = [
+            "bc1qvyyvsdcd0t9863stt7u9rf37wx443lzasg0usy",
+            "bc1qwqdg6squsna38e46795at95yu9atm8azzmyvckulcc7kytlcckxswvvzej",
+            "1AqE7oGF1EUoJviX1uuYrwpRBdEBTuGhES",
+            "377mKFYsaJPsxYSB5aFfx8SW3RaN5BzZVh",
+        ];
+        for addr in addrs.iter() {
+            let address = params.string_to_address(addr).unwrap();
+            assert_eq!(address, MainnetEncoder::string_to_address(addr).unwrap());
+            let script_pubkey = params.decode_address(&address).unwrap();
+            assert_eq!(script_pubkey, MainnetEncoder::decode_address(&address));
+            assert_eq!(params.encode_address(&script_pubkey).unwrap(), address);
+        }
+
+        let testnet = AddressParams::by_name("testnet").unwrap();
+        let address = params.string_to_address(addrs[2]).unwrap();
+        assert!(testnet.decode_address(&address).is_err());
+        assert!(testnet.string_to_address(addrs[0]).is_err());
+
+        let regtest = AddressParams::from(NetworkKind::Regtest);
+        assert_eq!(regtest, AddressParams::by_name("regtest").unwrap());
+        let address = regtest
+            .string_to_address("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080")
+            .unwrap();
+        assert!(testnet.decode_address(&address).is_err());
+    }
+
+    #[test]
+    fn it_encodes_custom_networks() {
+        // Litecoin's parameters
+        let params: AddressParams =
+            serde_json::from_str(r#"{"hrp":"ltc","pkh_version":48,"sh_version":50}"#).unwrap();
+        assert_eq!(params, AddressParams::new("ltc", 0x30, 0x32));
+        assert!(AddressParams::by_name("litecoin").is_none());
+
+        let script_pubkey = ScriptPubkey::from(
+            hex::decode("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928").unwrap(),
+        );
+        let address = params.encode_address(&script_pubkey).unwrap();
+        assert!(address.as_string().starts_with("ltc1q"));
+        assert_eq!(params.decode_address(&address).unwrap(), script_pubkey);
+
+        let pkh = ScriptPubkey::from(
+            hex::decode("76a914000000000000000000000000000000000000000088ac").unwrap(),
+        );
+        let address = params.encode_address(&pkh).unwrap();
+        assert!(address.as_string().starts_with('L'));
+        assert_eq!(
+            params.string_to_address(&address.as_string()).unwrap(),
+            address
+        );
+
+        assert!(matches!(
+            AddressParams::mainnet().decode_address(&address),
+            Err(EncodingError::WrongVersion { .. })
+        ));
+    }
+}
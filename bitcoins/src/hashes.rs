@@ -1,7 +1,13 @@
 //! This module holds `MarkedDigest` types used by Bitcoin transactions. Currently we represent
 //! only `TXID`s and `WTXID`s. In the future we may also represent sighash digests this way.
 
-use coins_core::{hashes, impl_hex_serde, marked_digest};
+use std::{fmt, str::FromStr};
+
+use coins_core::{
+    hashes::{self, MarkedDigestOutput},
+    impl_hex_serde, marked_digest,
+    ser::{SerError, SerResult},
+};
 
 marked_digest!(
     /// A marked Hash256Digest representing transaction IDs
@@ -25,6 +31,45 @@ impl_hex_serde!(TXID);
 impl_hex_serde!(WTXID);
 impl_hex_serde!(BlockHash);
 
+/// Implement big-endian hex conversions for a marked digest. Bitcoin Core RPC and block
+/// explorers display txids and block hashes in big-endian (reversed) byte order, while
+/// serialization uses the internal little-endian order.
+macro_rules! impl_be_hex {
+    ($name:ident) => {
+        impl $name {
+            /// Parse from big-endian hex, as displayed by RPC and block explorers
+            pub fn from_be_hex(be: &str) -> SerResult<Self> {
+                <Self as MarkedDigestOutput>::from_be_hex(be)
+            }
+
+            /// Convert to big-endian hex, as displayed by RPC and block explorers
+            pub fn to_be_hex(&self) -> String {
+                <Self as MarkedDigestOutput>::to_be_hex(self)
+            }
+        }
+
+        /// Formats as big-endian hex, as displayed by RPC and block explorers
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.to_be_hex())
+            }
+        }
+
+        /// Parses big-endian hex, as displayed by RPC and block explorers
+        impl FromStr for $name {
+            type Err = SerError;
+
+            fn from_str(s: &str) -> SerResult<Self> {
+                Self::from_be_hex(s)
+            }
+        }
+    };
+}
+
+impl_be_hex!(TXID);
+impl_be_hex!(WTXID);
+impl_be_hex!(BlockHash);
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -44,4 +89,19 @@ mod test {
             assert_eq!(case.0.serialize_hex(), case.1);
         }
     }
+
+    #[test]
+    fn it_displays_and_parses_big_endian_hex() {
+        let le = "15e180dc28a2327e687facc33f10f2a20da717e5548406f7ae8b4c811072f856";
+        let be = "56f87210814c8baef7068454e517a70da2f2103fc3ac7f687e32a228dc80e115";
+        let txid = TXID::deserialize_hex(le).unwrap();
+        assert_eq!(txid.to_string(), be);
+        assert_eq!(txid.to_be_hex(), be);
+        assert_eq!(be.parse::<TXID>().unwrap(), txid);
+        assert_eq!(TXID::from_be_hex(be).unwrap(), txid);
+        assert!("not hex".parse::<TXID>().is_err());
+
+        let block_hash: BlockHash = be.parse().unwrap();
+        assert_eq!(format!("{}", block_hash), be);
+    }
 }
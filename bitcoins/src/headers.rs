@@ -0,0 +1,676 @@
+//! A header chain, for SPV verification.
+//!
+//! `HeaderChain` stores every valid header it is given, including forks. Each header must
+//! connect to a known header, carry the difficulty bits required by the network's retargeting
+//! rules, and meet its target. The chain tracks the cumulative work of each header, and selects
+//! the header with the most work as its tip. Ties are broken in favor of the first header seen.
+//!
+//! Header timestamps are not checked against the median time past, and checkpoint rules are not
+//! checked. `median_time_past` and `relative_lock_start` are exposed for evaluating time-based
+//! locktimes and relative locks. The chain must start at the network's genesis block, or at a
+//! trusted checkpoint. Retargeting needs the first header of each difficulty period, so chains
+//! started from a checkpoint should start at a period boundary.
+//!
+//! ```
+//! use bitcoins::{headers::HeaderChain, nets::ChainParams, types::BlockHeader};
+//! use coins_core::ser::ByteFormat;
+//!
+//! let genesis = BlockHeader::deserialize_hex("0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c").unwrap();
+//! let chain = HeaderChain::new(ChainParams::mainnet(), genesis).unwrap();
+//! assert_eq!(chain.height(), 0);
+//! assert_eq!(chain.tip().header.block_hash(), ChainParams::mainnet().genesis_hash);
+//! ```
+
+use std::{cmp::Ordering, collections::HashMap};
+
+use thiserror::Error;
+
+use crate::{
+    hashes::BlockHash,
+    nets::ChainParams,
+    types::block::{compact_to_target, target_to_compact, BlockHeader, PowError, Target},
+};
+
+/// Cumulative proof of work, as a big-endian 256-bit integer
+pub type Work = [u8; 32];
+
+/// The number of headers whose timestamps make up the median time past
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+/// An error appending a header to a `HeaderChain`.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum HeaderChainError {
+    /// The first header is not the network's genesis block
+    #[error("Header {0} is not the genesis block of this network")]
+    WrongGenesis(BlockHash),
+
+    /// The header's parent is not in the chain
+    #[error("Parent {0} of the header is not in the chain")]
+    UnknownParent(BlockHash),
+
+    /// The header's bits do not match the network's retargeting rules
+    #[error("Header has bits {got:#010x}. Expected {expected:#010x}")]
+    BadDifficulty {
+        /// The bits in the header
+        got: u32,
+        /// The bits required by the retargeting rules
+        expected: u32,
+    },
+
+    /// Retargeting needs an ancestor that is not in the chain. This happens when a chain
+    /// started from a checkpoint that is not at a difficulty period boundary.
+    #[error("Ancestor at height {0} is needed for retargeting, but is not in the chain")]
+    MissingAncestor(u32),
+
+    /// The header does not meet its proof of work target
+    #[error(transparent)]
+    Pow(#[from] PowError),
+}
+
+/// A header in the chain, with its height and the cumulative work of the chain ending in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainEntry {
+    /// The header
+    pub header: BlockHeader,
+    /// The header's height
+    pub height: u32,
+    /// The total work of the chain up to and including this header
+    pub chainwork: Work,
+}
+
+/// A 256-bit unsigned integer, as little-endian 64-bit limbs. Only the operations needed for
+/// work and retargeting calculations are implemented.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct U256([u64; 4]);
+
+impl U256 {
+    const MAX: U256 = U256([u64::MAX; 4]);
+
+    fn from_be_bytes(bytes: &[u8; 32]) -> Self {
+        let mut limbs = [0u64; 4];
+        for (i, chunk) in bytes.chunks(8).enumerate() {
+            let mut limb = [0u8; 8];
+            limb.copy_from_slice(chunk);
+            limbs[3 - i] = u64::from_be_bytes(limb);
+        }
+        U256(limbs)
+    }
+
+    fn to_be_bytes(self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (i, limb) in self.0.iter().rev().enumerate() {
+            bytes[i * 8..(i + 1) * 8].copy_from_slice(&limb.to_be_bytes());
+        }
+        bytes
+    }
+
+    fn overflowing_add(self, other: Self) -> (Self, bool) {
+        let mut result = [0u64; 4];
+        let mut carry = false;
+        for (i, limb) in result.iter_mut().enumerate() {
+            let (sum, c1) = self.0[i].overflowing_add(other.0[i]);
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = c1 || c2;
+        }
+        (U256(result), carry)
+    }
+
+    fn wrapping_sub(self, other: Self) -> Self {
+        let mut result = [0u64; 4];
+        let mut borrow = false;
+        for (i, limb) in result.iter_mut().enumerate() {
+            let (diff, b1) = self.0[i].overflowing_sub(other.0[i]);
+            let (diff, b2) = diff.overflowing_sub(borrow as u64);
+            *limb = diff;
+            borrow = b1 || b2;
+        }
+        U256(result)
+    }
+
+    /// Multiply by `mul` and divide by non-zero `div`, without overflowing in between.
+    /// `None` if the result does not fit in 256 bits.
+    fn mul_div_u64(self, mul: u64, div: u64) -> Option<Self> {
+        let mut product = [0u64; 5];
+        let mut carry = 0u128;
+        for (i, limb) in self.0.iter().enumerate() {
+            let p = u128::from(*limb) * u128::from(mul) + carry;
+            product[i] = p as u64;
+            carry = p >> 64;
+        }
+        product[4] = carry as u64;
+
+        let mut quotient = [0u64; 5];
+        let mut rem = 0u128;
+        for i in (0..5).rev() {
+            let acc = rem << 64 | u128::from(product[i]);
+            quotient[i] = (acc / u128::from(div)) as u64;
+            rem = acc % u128::from(div);
+        }
+        if quotient[4] != 0 {
+            return None;
+        }
+        Some(U256([quotient[0], quotient[1], quotient[2], quotient[3]]))
+    }
+
+    /// Divide by a non-zero integer, by binary long division
+    fn div(self, other: Self) -> Self {
+        let mut quotient = U256::default();
+        let mut rem = U256::default();
+        for bit in (0..256).rev() {
+            // rem = rem << 1 | bit of self
+            let top = rem.0[3] >> 63;
+            for i in (1..4).rev() {
+                rem.0[i] = rem.0[i] << 1 | rem.0[i - 1] >> 63;
+            }
+            rem.0[0] = rem.0[0] << 1 | (self.0[bit / 64] >> (bit % 64)) & 1;
+            if top == 1 || rem >= other {
+                rem = rem.wrapping_sub(other);
+                quotient.0[bit / 64] |= 1 << (bit % 64);
+            }
+        }
+        quotient
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for U256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.iter().rev().cmp(other.0.iter().rev())
+    }
+}
+
+/// The expected number of hashes needed to meet a target. This is 2^256 / (target + 1).
+pub fn target_work(target: &Target) -> Work {
+    let target = U256::from_be_bytes(target);
+    if target == U256::MAX {
+        return U256([1, 0, 0, 0]).to_be_bytes();
+    }
+    let (divisor, _) = target.overflowing_add(U256([1, 0, 0, 0]));
+    // 2^256 / (target + 1) == (2^256 - 1 - target) / (target + 1) + 1
+    let (work, _) = U256::MAX
+        .wrapping_sub(target)
+        .div(divisor)
+        .overflowing_add(U256([1, 0, 0, 0]));
+    work.to_be_bytes()
+}
+
+/// Calculate the bits of the first header of a difficulty period. `last_bits` and `last_time`
+/// are those of the last header of the previous period, and `first_time` the time of its first
+/// header. Follows Bitcoin Core's `CalculateNextWorkRequired`, except that intermediate values
+/// do not overflow.
+pub fn calculate_next_bits(
+    params: &ChainParams,
+    last_bits: u32,
+    first_time: u32,
+    last_time: u32,
+) -> Result<u32, PowError> {
+    if params.pow_no_retargeting {
+        return Ok(last_bits);
+    }
+    let timespan = i64::from(params.pow_target_timespan);
+    let actual = (i64::from(last_time) - i64::from(first_time)).clamp(timespan / 4, timespan * 4);
+
+    let limit = U256::from_be_bytes(&params.pow_limit);
+    let target = U256::from_be_bytes(&compact_to_target(last_bits)?)
+        .mul_div_u64(actual as u64, timespan as u64)
+        .unwrap_or(limit);
+    Ok(target_to_compact(&target.min(limit).to_be_bytes()))
+}
+
+/// A tree of validated headers, with the most-work tip selected.
+#[derive(Debug, Clone)]
+pub struct HeaderChain {
+    params: ChainParams,
+    entries: HashMap<BlockHash, ChainEntry>,
+    tip: BlockHash,
+}
+
+impl HeaderChain {
+    /// Start a chain at the network's genesis block
+    pub fn new(params: ChainParams, genesis: BlockHeader) -> Result<Self, HeaderChainError> {
+        let hash = genesis.block_hash();
+        if hash != params.genesis_hash {
+            return Err(HeaderChainError::WrongGenesis(hash));
+        }
+        let chainwork = target_work(&genesis.target()?);
+        Ok(Self::from_checkpoint(params, genesis, 0, chainwork))
+    }
+
+    /// Start a chain at a trusted checkpoint, with its height and cumulative chainwork. The
+    /// checkpoint is not validated.
+    pub fn from_checkpoint(
+        params: ChainParams,
+        header: BlockHeader,
+        height: u32,
+        chainwork: Work,
+    ) -> Self {
+        let tip = header.block_hash();
+        let mut entries = HashMap::new();
+        entries.insert(
+            tip,
+            ChainEntry {
+                header,
+                height,
+                chainwork,
+            },
+        );
+        Self {
+            params,
+            entries,
+            tip,
+        }
+    }
+
+    /// The network parameters
+    pub fn params(&self) -> &ChainParams {
+        &self.params
+    }
+
+    /// The most-work header
+    pub fn tip(&self) -> &ChainEntry {
+        &self.entries[&self.tip]
+    }
+
+    /// The height of the most-work header
+    pub fn height(&self) -> u32 {
+        self.tip().height
+    }
+
+    /// The number of headers stored, including forks
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// True if the chain holds no headers. Chains always hold their first header, so this is
+    /// always false.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Look up a header by hash
+    pub fn get(&self, hash: &BlockHash) -> Option<&ChainEntry> {
+        self.entries.get(hash)
+    }
+
+    /// True if the chain holds the header
+    pub fn contains(&self, hash: &BlockHash) -> bool {
+        self.entries.contains_key(hash)
+    }
+
+    /// Find the ancestor of a header at `height`. `None` if the header is unknown, below
+    /// `height`, or its ancestry is not in the chain.
+    pub fn ancestor(&self, hash: &BlockHash, height: u32) -> Option<&ChainEntry> {
+        let mut entry = self.get(hash)?;
+        while entry.height > height {
+            entry = self.get(&entry.header.prev_block())?;
+        }
+        if entry.height == height {
+            Some(entry)
+        } else {
+            None
+        }
+    }
+
+    /// True if the header is an ancestor of the tip, or the tip itself
+    pub fn is_in_best_chain(&self, hash: &BlockHash) -> bool {
+        match self.get(hash) {
+            Some(entry) => self
+                .ancestor(&self.tip, entry.height)
+                .is_some_and(|a| a.header.block_hash() == *hash),
+            None => false,
+        }
+    }
+
+    /// The median timestamp of the header and up to 10 of its ancestors, as defined in BIP113.
+    /// Time-based locktimes are compared against the median time past of the previous block.
+    /// `None` if the header is unknown. Ancestors missing from the chain are skipped, so headers
+    /// near a checkpoint use fewer timestamps.
+    pub fn median_time_past(&self, hash: &BlockHash) -> Option<u32> {
+        let mut entry = self.get(hash)?;
+        let mut times = Vec::with_capacity(MEDIAN_TIME_SPAN);
+        loop {
+            times.push(entry.header.time());
+            if times.len() == MEDIAN_TIME_SPAN {
+                break;
+            }
+            match self.get(&entry.header.prev_block()) {
+                Some(parent) => entry = parent,
+                None => break,
+            }
+        }
+        times.sort_unstable();
+        Some(times[times.len() / 2])
+    }
+
+    /// The median time past that BIP68 time-based relative locks on outputs confirmed in this
+    /// block are measured from. This is the median time past of the block's parent. `None` if
+    /// the block or its parent is unknown.
+    pub fn relative_lock_start(&self, hash: &BlockHash) -> Option<u32> {
+        self.median_time_past(&self.get(hash)?.header.prev_block())
+    }
+
+    /// The median time past of the tip. A transaction in the next block must have a locktime
+    /// below this, if the locktime is a timestamp.
+    pub fn tip_median_time_past(&self) -> u32 {
+        self.median_time_past(&self.tip)
+            .expect("tip is always in the chain")
+    }
+
+    /// The bits required of a header at `time` following `prev`. Follows Bitcoin Core's
+    /// `GetNextWorkRequired`.
+    fn next_bits(&self, prev: &ChainEntry, time: u32) -> Result<u32, HeaderChainError> {
+        let params = &self.params;
+        let interval = params.difficulty_adjustment_interval();
+        let height = prev.height + 1;
+
+        if !height.is_multiple_of(interval) {
+            if !params.pow_allow_min_difficulty_blocks {
+                return Ok(prev.header.bits());
+            }
+            // Blocks more than 20 minutes after their parent may use the minimum difficulty
+            if u64::from(time)
+                > u64::from(prev.header.time()) + u64::from(params.pow_target_spacing) * 2
+            {
+                return Ok(params.pow_limit_bits);
+            }
+            // Otherwise use the bits of the last block not mined under that rule
+            let mut entry = prev;
+            while !entry.height.is_multiple_of(interval)
+                && entry.header.bits() == params.pow_limit_bits
+            {
+                match self.get(&entry.header.prev_block()) {
+                    Some(parent) => entry = parent,
+                    None => break,
+                }
+            }
+            return Ok(entry.header.bits());
+        }
+
+        let first_height = height - interval;
+        let first = self
+            .ancestor(&prev.header.block_hash(), first_height)
+            .ok_or(HeaderChainError::MissingAncestor(first_height))?;
+        Ok(calculate_next_bits(
+            params,
+            prev.header.bits(),
+            first.header.time(),
+            prev.header.time(),
+        )?)
+    }
+
+    /// Validate and append a header. It may extend the tip, or any other known header. Returns
+    /// true if the header became the new tip. Headers already in the chain are ignored.
+    pub fn append(&mut self, header: BlockHeader) -> Result<bool, HeaderChainError> {
+        let hash = header.block_hash();
+        if self.contains(&hash) {
+            return Ok(false);
+        }
+        let prev = *self
+            .get(&header.prev_block())
+            .ok_or_else(|| HeaderChainError::UnknownParent(header.prev_block()))?;
+
+        let expected = self.next_bits(&prev, header.time())?;
+        if header.bits() != expected {
+            return Err(HeaderChainError::BadDifficulty {
+                got: header.bits(),
+                expected,
+            });
+        }
+        header.validate_pow_with_limit(&self.params.pow_limit)?;
+
+        let (chainwork, _) = U256::from_be_bytes(&prev.chainwork)
+            .overflowing_add(U256::from_be_bytes(&target_work(&header.target()?)));
+        let chainwork = chainwork.to_be_bytes();
+        self.entries.insert(
+            hash,
+            ChainEntry {
+                header,
+                height: prev.height + 1,
+                chainwork,
+            },
+        );
+        if chainwork > self.tip().chainwork {
+            self.tip = hash;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Validate and append headers in order. Stops at the first invalid header. Returns true if
+    /// the tip changed.
+    pub fn extend<I>(&mut self, headers: I) -> Result<bool, HeaderChainError>
+    where
+        I: IntoIterator<Item = BlockHeader>,
+    {
+        let mut changed = false;
+        for header in headers {
+            changed |= self.append(header)?;
+        }
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use coins_core::{hashes::Hash256Digest, ser::ByteFormat};
+
+    fn be(hex_str: &str) -> [u8; 32] {
+        let mut buf = [0u8; 32];
+        let bytes = hex::decode(format!("{:0>64}", hex_str)).unwrap();
+        buf.copy_from_slice(&bytes);
+        buf
+    }
+
+    #[test]
+    fn it_calculates_work() {
+        // The genesis block's work
+        assert_eq!(
+            target_work(&compact_to_target(0x1d00_ffff).unwrap()),
+            be("100010001")
+        );
+        assert_eq!(
+            target_work(&be(
+                "7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff"
+            )),
+            be("2")
+        );
+        assert_eq!(target_work(&[0xff; 32]), be("1"));
+    }
+
+    #[test]
+    fn it_retargets_like_core() {
+        // From Bitcoin Core's pow tests
+        let params = ChainParams::mainnet();
+        let cases = [
+            (0x1d00_ffff, 1_261_130_161, 1_262_152_739, 0x1d00_d86a),
+            (0x1d00_ffff, 1_231_006_505, 1_233_061_996, 0x1d00_ffff),
+            (0x1c05_a3f4, 1_279_008_237, 1_279_297_671, 0x1c01_68fd),
+            (0x1c38_7f6f, 1_263_163_443, 1_269_211_443, 0x1d00_e1fd),
+        ];
+        for (bits, first, last, expected) in cases.iter() {
+            assert_eq!(
+                calculate_next_bits(&params, *bits, *first, *last).unwrap(),
+                *expected
+            );
+        }
+        assert_eq!(
+            calculate_next_bits(&ChainParams::regtest(), 0x207f_ffff, 0, 1).unwrap(),
+            0x207f_ffff
+        );
+    }
+
+    /// Regtest-like params that retarget every 4 blocks
+    fn test_params() -> ChainParams {
+        ChainParams {
+            pow_target_timespan: 4 * 600,
+            pow_allow_min_difficulty_blocks: false,
+            pow_no_retargeting: false,
+            ..ChainParams::regtest()
+        }
+    }
+
+    /// Grind the nonce until the header meets its target
+    fn mine(prev: &BlockHeader, time: u32, bits: u32, tag: u8) -> BlockHeader {
+        let merkle_root =
+            Hash256Digest::deserialize_hex(&format!("{:02x}", tag).repeat(32)).unwrap();
+        (0..)
+            .map(|nonce| BlockHeader::new(4, prev.block_hash(), merkle_root, time, bits, nonce))
+            .find(|header| header.validate_pow().is_ok())
+            .unwrap()
+    }
+
+    #[test]
+    fn it_validates_and_selects_the_best_chain() {
+        let params = test_params();
+        let genesis = BlockHeader::new(
+            1,
+            BlockHash::default(),
+            Default::default(),
+            0,
+            0x207f_ffff,
+            0,
+        );
+        let genesis = mine(&genesis, 0, 0x207f_ffff, 0);
+        let mut chain = HeaderChain::from_checkpoint(
+            params,
+            genesis,
+            0,
+            target_work(&genesis.target().unwrap()),
+        );
+        assert!(matches!(
+            HeaderChain::new(params, genesis),
+            Err(HeaderChainError::WrongGenesis(_))
+        ));
+
+        // Blocks 1 to 3 keep the genesis bits. Fast blocks make block 4 harder.
+        let mut headers = vec![genesis];
+        for height in 1..4 {
+            let header = mine(&headers[height - 1], height as u32, 0x207f_ffff, 0);
+            assert!(chain.append(header).unwrap());
+            headers.push(header);
+        }
+        let expected = calculate_next_bits(&params, 0x207f_ffff, 0, 3).unwrap();
+        assert_eq!(expected, 0x201f_ffff);
+
+        let wrong = mine(&headers[3], 4, 0x207f_ffff, 0);
+        assert_eq!(
+            chain.append(wrong),
+            Err(HeaderChainError::BadDifficulty {
+                got: 0x207f_ffff,
+                expected
+            })
+        );
+        let header = mine(&headers[3], 4, expected, 0);
+        assert!(chain.append(header).unwrap());
+        headers.push(header);
+        assert_eq!(chain.height(), 4);
+        assert_eq!(
+            chain.ancestor(&header.block_hash(), 1).unwrap().header,
+            headers[1]
+        );
+
+        // A fork from block 2 with less work does not become the tip
+        let fork = mine(&headers[2], 3, 0x207f_ffff, 1);
+        assert!(!chain.append(fork).unwrap());
+        assert_eq!(chain.tip().header, headers[4]);
+        assert!(!chain.is_in_best_chain(&fork.block_hash()));
+        assert!(chain.is_in_best_chain(&headers[2].block_hash()));
+
+        // Until it overtakes the best chain
+        let fork_4 = mine(&fork, 4, expected, 1);
+        assert!(!chain.append(fork_4).unwrap());
+        let fork_5 = mine(&fork_4, 5, expected, 1);
+        assert!(chain.append(fork_5).unwrap());
+        assert_eq!(chain.tip().header, fork_5);
+        assert_eq!(chain.height(), 5);
+        assert!(!chain.is_in_best_chain(&headers[3].block_hash()));
+        assert_eq!(chain.len(), 8);
+
+        // Duplicates are ignored, and headers must connect
+        assert!(!chain.append(fork_5).unwrap());
+        let orphan = BlockHeader::new(
+            4,
+            BlockHash::default(),
+            Default::default(),
+            0,
+            0x207f_ffff,
+            0,
+        );
+        assert!(matches!(
+            chain.append(orphan),
+            Err(HeaderChainError::UnknownParent(_))
+        ));
+    }
+
+    #[test]
+    fn it_calculates_median_time_past() {
+        let params = ChainParams::regtest();
+        let genesis = mine(&BlockHeader::default(), 100, 0x207f_ffff, 0);
+        let mut chain = HeaderChain::from_checkpoint(params, genesis, 0, Work::default());
+        assert_eq!(chain.tip_median_time_past(), 100);
+
+        // Timestamps need not increase
+        let times = [110, 105, 130, 90, 120, 140, 125, 135, 95, 150, 145, 160];
+        let mut headers = vec![genesis];
+        for (i, time) in times.iter().enumerate() {
+            let header = mine(&headers[i], *time, 0x207f_ffff, 0);
+            chain.append(header).unwrap();
+            headers.push(header);
+        }
+        // Fewer than 11 headers: 100, 110, 105, 130
+        assert_eq!(chain.median_time_past(&headers[3].block_hash()), Some(110));
+        // The first 11 headers: 90, 95, 100, 105, 110, 120, 125, 130, 135, 140, 150
+        assert_eq!(chain.median_time_past(&headers[10].block_hash()), Some(120));
+        // The genesis drops out: 90, 95, 105, 110, 120, 125, 130, 135, 140, 145, 150
+        assert_eq!(chain.median_time_past(&headers[11].block_hash()), Some(125));
+        assert_eq!(chain.tip_median_time_past(), 130);
+        assert_eq!(chain.median_time_past(&BlockHash::default()), None);
+        assert_eq!(
+            chain.relative_lock_start(&headers[4].block_hash()),
+            Some(110)
+        );
+        assert_eq!(chain.relative_lock_start(&headers[0].block_hash()), None);
+    }
+
+    #[test]
+    fn it_handles_timestamps_near_u32_max() {
+        // Regtest allows min difficulty blocks, so the next bits depend on the parent's time
+        let params = ChainParams::regtest();
+        let genesis = mine(&BlockHeader::default(), u32::MAX - 100, 0x207f_ffff, 0);
+        let mut chain = HeaderChain::from_checkpoint(params, genesis, 0, Work::default());
+        let header = mine(&genesis, u32::MAX, 0x207f_ffff, 0);
+        assert!(chain.append(header).unwrap());
+    }
+
+    #[test]
+    fn it_rejects_insufficient_work() {
+        let params = ChainParams::regtest();
+        let genesis = mine(&BlockHeader::default(), 0, 0x207f_ffff, 0);
+        let mut chain = HeaderChain::from_checkpoint(params, genesis, 0, Work::default());
+        let header = (0..)
+            .map(|nonce| {
+                BlockHeader::new(
+                    4,
+                    genesis.block_hash(),
+                    Default::default(),
+                    1,
+                    0x207f_ffff,
+                    nonce,
+                )
+            })
+            .find(|header| header.validate_pow().is_err())
+            .unwrap();
+        assert!(matches!(
+            chain.append(header),
+            Err(HeaderChainError::Pow(PowError::InsufficientWork(_)))
+        ));
+        assert_eq!(chain.len(), 1);
+    }
+}
@@ -5,20 +5,37 @@
 #![warn(missing_docs)]
 #![warn(unused_extern_crates)]
 
+pub mod bip322;
 pub mod builder;
+pub mod classify;
+pub mod conformance;
+pub mod consolidation;
+pub mod contracts;
 pub mod enc;
 pub mod hashes;
+pub mod headers;
+pub mod lightning;
+pub mod malleability;
 pub mod nets;
+pub mod payouts;
+pub mod policy;
+pub mod signet;
+pub mod signing;
+pub mod snapshot;
+pub mod swap;
 pub mod types;
+pub mod utxo_set;
+
+mod virtual_tx;
 
 /// Common re-exports
 pub mod prelude;
 
 #[doc(hidden)]
-#[cfg(any(feature = "mainnet", feature = "testnet"))]
+#[cfg(any(feature = "mainnet", feature = "testnet", feature = "signet"))]
 pub mod defaults;
 
-#[cfg(any(feature = "mainnet", feature = "testnet"))]
+#[cfg(any(feature = "mainnet", feature = "testnet", feature = "signet"))]
 pub use defaults::network::{Encoder, Net};
 
 pub use nets::*;
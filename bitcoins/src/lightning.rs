@@ -0,0 +1,329 @@
+//! Lightning channel output scripts, per BOLT 3.
+//!
+//! Each output type holds its witness script and P2WSH script pubkey as a `WshContract`, and
+//! assembles the witnesses for its spend paths. Keys are the per-commitment keys derived as in
+//! BOLT 3. Key derivation and commitment transaction construction are left to the caller.
+//!
+//! Signatures passed to the witness helpers must carry their sighash flag byte.
+
+use coins_bip32::ecdsa::VerifyingKey;
+
+use crate::{
+    contracts::WshContract,
+    types::{
+        opcodes::Opcode,
+        script::{push_data, Script, ScriptPubkey, Witness},
+        script_num::ScriptNum,
+        timelock::RelativeLock,
+    },
+};
+
+/// The value of each anchor output, in satoshis
+pub const ANCHOR_OUTPUT_VALUE: u64 = 330;
+
+/// The number of blocks after which anyone may sweep an anchor output
+pub const ANCHOR_SWEEP_DELAY: u16 = 16;
+
+/// Serialize a key in compressed form
+fn key_bytes<K: AsRef<VerifyingKey>>(key: &K) -> [u8; 33] {
+    let mut buf = [0u8; 33];
+    buf.copy_from_slice(&key.as_ref().to_bytes());
+    buf
+}
+
+/// The 2-of-2 multisig output funding a channel. The funding keys are sorted, so that both
+/// peers produce the same script.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FundingOutput {
+    local: [u8; 33],
+    remote: [u8; 33],
+    contract: WshContract,
+}
+
+impl FundingOutput {
+    /// Instantiate the funding output of a channel from the local and remote funding keys
+    pub fn new<K>(local_funding_key: &K, remote_funding_key: &K) -> Self
+    where
+        K: AsRef<VerifyingKey>,
+    {
+        let keys = [local_funding_key, remote_funding_key];
+        let script = Script::sorted_multisig(2, &keys).expect("2 of 2 keys is valid");
+        Self {
+            local: key_bytes(local_funding_key),
+            remote: key_bytes(remote_funding_key),
+            contract: WshContract::new(script),
+        }
+    }
+
+    /// The witness script and P2WSH script pubkey of the funding output
+    pub fn contract(&self) -> &WshContract {
+        &self.contract
+    }
+
+    /// Assemble the witness spending the funding output. The signatures are ordered to match
+    /// the sorted keys.
+    pub fn witness(&self, local_sig: &[u8], remote_sig: &[u8]) -> Witness {
+        let (first, second) = if self.local < self.remote {
+            (local_sig, remote_sig)
+        } else {
+            (remote_sig, local_sig)
+        };
+        // CHECKMULTISIG consumes an extra stack item
+        self.contract
+            .witness(vec![vec![].into(), first.into(), second.into()])
+    }
+}
+
+/// The `to_local` output of a commitment transaction. It pays the local node after
+/// `to_self_delay` blocks, or the remote node immediately with the revocation key.
+///
+/// ```text
+/// OP_IF
+///     <revocationpubkey>
+/// OP_ELSE
+///     <to_self_delay> OP_CHECKSEQUENCEVERIFY OP_DROP <local_delayedpubkey>
+/// OP_ENDIF
+/// OP_CHECKSIG
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ToLocalOutput {
+    to_self_delay: u16,
+    contract: WshContract,
+}
+
+impl ToLocalOutput {
+    /// Instantiate a `to_local` output
+    pub fn new<K>(revocation_key: &K, local_delayed_key: &K, to_self_delay: u16) -> Self
+    where
+        K: AsRef<VerifyingKey>,
+    {
+        let mut v = vec![Opcode::If.to_u8()];
+        push_data(&mut v, &key_bytes(revocation_key));
+        v.push(Opcode::Else.to_u8());
+        ScriptNum::from(to_self_delay as i64).push_to(&mut v);
+        v.extend(&[Opcode::CheckSequenceVerify.to_u8(), Opcode::Drop.to_u8()]);
+        push_data(&mut v, &key_bytes(local_delayed_key));
+        v.extend(&[Opcode::EndIf.to_u8(), Opcode::CheckSig.to_u8()]);
+        Self {
+            to_self_delay,
+            contract: WshContract::new(v.into()),
+        }
+    }
+
+    /// The witness script and P2WSH script pubkey of the output
+    pub fn contract(&self) -> &WshContract {
+        &self.contract
+    }
+
+    /// The relative locktime that an input spending the output with the delayed key must set in
+    /// its sequence number
+    pub fn relative_lock(&self) -> RelativeLock {
+        RelativeLock::Blocks(self.to_self_delay)
+    }
+
+    /// Assemble the witness spending the output with the local delayed key, after the delay
+    pub fn delayed_witness(&self, local_delayed_sig: &[u8]) -> Witness {
+        self.contract
+            .witness(vec![local_delayed_sig.into(), vec![].into()])
+    }
+
+    /// Assemble the witness spending the output with the revocation key
+    pub fn revocation_witness(&self, revocation_sig: &[u8]) -> Witness {
+        self.contract
+            .witness(vec![revocation_sig.into(), vec![0x01].into()])
+    }
+}
+
+/// The `to_remote` output of a commitment transaction. With `option_anchors`, it pays the remote
+/// node after 1 block:
+///
+/// ```text
+/// <remotepubkey> OP_CHECKSIGVERIFY 1 OP_CHECKSEQUENCEVERIFY
+/// ```
+///
+/// Without anchors, it is a P2WPKH output paying the remote key. See
+/// `ToRemoteOutput::static_remotekey`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ToRemoteOutput {
+    contract: WshContract,
+}
+
+impl ToRemoteOutput {
+    /// Instantiate an `option_anchors` `to_remote` output
+    pub fn new<K>(remote_key: &K) -> Self
+    where
+        K: AsRef<VerifyingKey>,
+    {
+        let mut v = vec![];
+        push_data(&mut v, &key_bytes(remote_key));
+        v.extend(&[
+            Opcode::CheckSigVerify.to_u8(),
+            Opcode::PushNum(1).to_u8(),
+            Opcode::CheckSequenceVerify.to_u8(),
+        ]);
+        Self {
+            contract: WshContract::new(v.into()),
+        }
+    }
+
+    /// The P2WPKH `to_remote` script pubkey of channels without anchors
+    pub fn static_remotekey<K>(remote_key: &K) -> ScriptPubkey
+    where
+        K: AsRef<VerifyingKey>,
+    {
+        ScriptPubkey::p2wpkh(remote_key)
+    }
+
+    /// The witness script and P2WSH script pubkey of the output
+    pub fn contract(&self) -> &WshContract {
+        &self.contract
+    }
+
+    /// The relative locktime that an input spending the output must set in its sequence number
+    pub fn relative_lock(&self) -> RelativeLock {
+        RelativeLock::Blocks(1)
+    }
+
+    /// Assemble the witness spending the output with the remote key
+    pub fn witness(&self, remote_sig: &[u8]) -> Witness {
+        self.contract.key_witness(remote_sig)
+    }
+}
+
+/// An anchor output of a commitment transaction. It is spendable by its funding key
+/// immediately, or by anyone after `ANCHOR_SWEEP_DELAY` blocks.
+///
+/// ```text
+/// <funding_pubkey> OP_CHECKSIG OP_IFDUP
+/// OP_NOTIF
+///     OP_16 OP_CHECKSEQUENCEVERIFY
+/// OP_ENDIF
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AnchorOutput {
+    contract: WshContract,
+}
+
+impl AnchorOutput {
+    /// Instantiate an anchor output for a funding key
+    pub fn new<K>(funding_key: &K) -> Self
+    where
+        K: AsRef<VerifyingKey>,
+    {
+        let mut v = vec![];
+        push_data(&mut v, &key_bytes(funding_key));
+        v.extend(&[
+            Opcode::CheckSig.to_u8(),
+            Opcode::IfDup.to_u8(),
+            Opcode::NotIf.to_u8(),
+        ]);
+        ScriptNum::from(ANCHOR_SWEEP_DELAY as i64).push_to(&mut v);
+        v.extend(&[Opcode::CheckSequenceVerify.to_u8(), Opcode::EndIf.to_u8()]);
+        Self {
+            contract: WshContract::new(v.into()),
+        }
+    }
+
+    /// The witness script and P2WSH script pubkey of the output
+    pub fn contract(&self) -> &WshContract {
+        &self.contract
+    }
+
+    /// Assemble the witness spending the output with the funding key
+    pub fn witness(&self, funding_sig: &[u8]) -> Witness {
+        self.contract.key_witness(funding_sig)
+    }
+
+    /// Assemble the witness sweeping the output without a signature. The spending input must
+    /// set a relative locktime of at least `ANCHOR_SWEEP_DELAY` blocks.
+    pub fn sweep_witness(&self) -> Witness {
+        self.contract.witness(vec![vec![].into()])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use coins_bip32::{path::KeyDerivation, prelude::DerivedPubkey};
+
+    use crate::types::script::BitcoinScript;
+
+    fn key(h: &str) -> DerivedPubkey {
+        let derivation = KeyDerivation {
+            root: [0u8; 4].into(),
+            path: vec![].into(),
+        };
+        let key = VerifyingKey::from_sec1_bytes(&hex::decode(h).unwrap()).unwrap();
+        DerivedPubkey::new(key, derivation)
+    }
+
+    // From the BOLT 3 test vectors
+    const LOCAL_FUNDING: &str =
+        "023da092f6980e58d2c037173180e9a465476026ee50f96695963e8efe436f54eb";
+    const REMOTE_FUNDING: &str =
+        "030e9f7b623d2ccc7c9bd44d66d5ce21ce504c0acf6385a132cec6d3c39fa711c1";
+
+    #[test]
+    fn it_builds_funding_outputs() {
+        let local = key(LOCAL_FUNDING);
+        let remote = key(REMOTE_FUNDING);
+        let funding = FundingOutput::new(&local, &remote);
+        assert_eq!(
+            hex::encode(funding.contract().witness_script.items()),
+            format!("5221{}21{}52ae", LOCAL_FUNDING, REMOTE_FUNDING)
+        );
+        assert_eq!(
+            FundingOutput::new(&remote, &local).contract(),
+            funding.contract()
+        );
+
+        // Signatures follow the key order, regardless of which side is local
+        let witness = FundingOutput::new(&remote, &local).witness(&[0x02], &[0x01]);
+        assert!(witness[0].is_empty());
+        assert_eq!(witness[1].items(), &[0x01]);
+        assert_eq!(witness[2].items(), &[0x02]);
+    }
+
+    #[test]
+    fn it_builds_commitment_outputs() {
+        let local = key(LOCAL_FUNDING);
+        let remote = key(REMOTE_FUNDING);
+
+        let to_local = ToLocalOutput::new(&remote, &local, 144);
+        assert_eq!(
+            to_local.contract().witness_script.to_asm(),
+            format!(
+                "OP_IF <{}> OP_ELSE <9000> OP_CHECKSEQUENCEVERIFY OP_DROP <{}> OP_ENDIF OP_CHECKSIG",
+                REMOTE_FUNDING, LOCAL_FUNDING
+            )
+        );
+        assert_eq!(to_local.relative_lock(), RelativeLock::Blocks(144));
+        assert_eq!(to_local.delayed_witness(&[0x01])[1].len(), 0);
+        assert_eq!(to_local.revocation_witness(&[0x01])[1].items(), &[0x01]);
+
+        let to_remote = ToRemoteOutput::new(&remote);
+        assert_eq!(
+            to_remote.contract().witness_script.to_asm(),
+            format!(
+                "<{}> OP_CHECKSIGVERIFY OP_1 OP_CHECKSEQUENCEVERIFY",
+                REMOTE_FUNDING
+            )
+        );
+        assert_eq!(to_remote.witness(&[0x01]).len(), 2);
+        assert_eq!(
+            ToRemoteOutput::static_remotekey(&remote),
+            ScriptPubkey::p2wpkh(&remote)
+        );
+
+        let anchor = AnchorOutput::new(&local);
+        assert_eq!(
+            anchor.contract().witness_script.to_asm(),
+            format!(
+                "<{}> OP_CHECKSIG OP_IFDUP OP_NOTIF OP_16 OP_CHECKSEQUENCEVERIFY OP_ENDIF",
+                LOCAL_FUNDING
+            )
+        );
+        assert_eq!(anchor.witness(&[0x01]).len(), 2);
+        assert!(anchor.sweep_witness()[0].is_empty());
+    }
+}
@@ -0,0 +1,225 @@
+//! Pre-broadcast checks for transaction malleability.
+//!
+//! A third party relaying a transaction can change its txid without invalidating it if a legacy
+//! input carries a high-S signature. Witness data is not committed to by the txid, so segwit
+//! inputs are not affected, provided their script sigs are empty as BIP141 requires.
+//! `check_malleability` reports these issues. Callers may also pass a txid claimed for the
+//! transaction, e.g. one reported by a provider or PSBT, which is checked against the hash of
+//! the transaction stripped of its witnesses.
+//!
+//! Whether an input is native segwit depends on its prevout. Callers supply the prevouts they
+//! know, keyed by outpoint. Inputs with unknown prevouts are treated as native segwit if they
+//! have a witness, and their script sig is not a P2SH-wrapped witness program.
+//!
+//! ```
+//! use std::collections::HashMap;
+//! use bitcoins::{malleability::check_malleability, types::WitnessTx};
+//!
+//! let issues = check_malleability(&WitnessTx::default(), None, &HashMap::new());
+//! assert!(issues.is_empty());
+//! ```
+
+use std::collections::HashMap;
+
+use coins_core::{
+    hashes::{Hash256, MarkedDigest},
+    ser::ByteFormat,
+    types::tx::Transaction,
+};
+use thiserror::Error;
+
+use crate::{
+    hashes::TXID,
+    types::{
+        script::ScriptSig,
+        stack::SpendData,
+        tx::BitcoinTransaction,
+        txin::BitcoinOutpoint,
+        txout::{is_witness_program, TxOut},
+        witness::WitnessTx,
+        witness_program::split_witness_program,
+    },
+};
+
+/// A malleability issue found in a transaction.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum MalleabilityIssue {
+    /// The claimed txid is not the hash of the transaction stripped of its witnesses
+    #[error("Claimed TXID {claimed} does not match the stripped serialization hash {stripped}")]
+    TxidMismatch {
+        /// The txid claimed for the transaction
+        claimed: TXID,
+        /// The hash of the stripped serialization
+        stripped: TXID,
+    },
+
+    /// The input spends a native witness program, but has a non-empty script sig
+    #[error("Input {} spends a native witness program with a non-empty script sig", .0)]
+    ScriptSigOnNativeWitness(usize),
+
+    /// The legacy input carries a signature with a high S value. Anyone may replace it with its
+    /// low-S equivalent, changing the txid.
+    #[error("Input {input} signature {signature} has a high S value")]
+    HighS {
+        /// The input index
+        input: usize,
+        /// The index of the signature among the input's signatures
+        signature: usize,
+    },
+}
+
+/// Calculate the hash of the transaction serialized without witnesses. This is the txid.
+pub fn stripped_txid(tx: &WitnessTx) -> TXID {
+    let mut w = Hash256::default();
+    tx.as_legacy()
+        .write_to(&mut w)
+        .expect("No IOError from hash functions");
+    w.finalize_marked()
+}
+
+/// True if the script sig is a single push of a witness program, as in a P2SH-wrapped segwit
+/// input
+fn is_wrapped_witness_program(script_sig: &ScriptSig) -> bool {
+    match script_sig.items().split_first() {
+        Some((len, program)) => {
+            *len as usize == program.len() && split_witness_program(program).is_some()
+        }
+        None => false,
+    }
+}
+
+/// Find the malleability issues in a transaction. If `claimed_txid` is passed, it is checked
+/// against the stripped serialization hash. Returns an empty vector if there are no issues.
+pub fn check_malleability(
+    tx: &WitnessTx,
+    claimed_txid: Option<TXID>,
+    prevouts: &HashMap<BitcoinOutpoint, TxOut>,
+) -> Vec<MalleabilityIssue> {
+    let mut issues = vec![];
+
+    if let Some(claimed) = claimed_txid {
+        let stripped = stripped_txid(tx);
+        if claimed != stripped {
+            issues.push(MalleabilityIssue::TxidMismatch { claimed, stripped });
+        }
+    }
+
+    for (i, (input, witness)) in tx.inputs().iter().zip(tx.witnesses()).enumerate() {
+        let native_witness = match prevouts.get(&input.outpoint) {
+            Some(prevout) => is_witness_program(&prevout.script_pubkey),
+            None => !witness.is_empty() && !is_wrapped_witness_program(&input.script_sig),
+        };
+        if native_witness {
+            if !input.script_sig.is_empty() {
+                issues.push(MalleabilityIssue::ScriptSigOnNativeWitness(i));
+            }
+            continue;
+        }
+        // Witnesses are not committed to by the txid, so only script sig signatures matter
+        if !witness.is_empty() {
+            continue;
+        }
+        let spend_data = SpendData::from_input(&input.script_sig, witness);
+        for (j, sig) in spend_data.signatures.iter().enumerate() {
+            let high_s = sig
+                .signature()
+                .and_then(|mut s| s.normalize_s().ok())
+                .unwrap_or(false);
+            if high_s {
+                issues.push(MalleabilityIssue::HighS {
+                    input: i,
+                    signature: j,
+                });
+            }
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use coins_bip32::ecdsa::Signature;
+    use std::convert::TryFrom;
+
+    use crate::types::{legacy::LegacyTx, script::ScriptPubkey, tx::Sighash};
+
+    // From the BIP143 native P2WPKH example. Input 0 is P2PK, input 1 is P2WPKH.
+    const TX: &str = "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000";
+
+    #[test]
+    fn it_finds_no_issues_in_well_formed_txns() {
+        let tx = WitnessTx::deserialize_hex(TX).unwrap();
+        assert!(check_malleability(&tx, None, &HashMap::new()).is_empty());
+        assert!(check_malleability(&tx, Some(tx.txid()), &HashMap::new()).is_empty());
+    }
+
+    #[test]
+    fn it_finds_mismatched_claimed_txids() {
+        let tx = WitnessTx::deserialize_hex(TX).unwrap();
+        let claimed = TXID::default();
+        assert_eq!(
+            check_malleability(&tx, Some(claimed), &HashMap::new()),
+            vec![MalleabilityIssue::TxidMismatch {
+                claimed,
+                stripped: tx.txid()
+            }]
+        );
+    }
+
+    #[test]
+    fn it_finds_script_sigs_on_native_witness_inputs() {
+        let mut tx = WitnessTx::deserialize_hex(TX).unwrap();
+        tx.legacy_tx.vin[1].script_sig = ScriptSig::from(vec![0x51]);
+        assert_eq!(
+            check_malleability(&tx, None, &HashMap::new()),
+            vec![MalleabilityIssue::ScriptSigOnNativeWitness(1)]
+        );
+
+        // A known non-witness prevout is not native segwit, regardless of the witness
+        let mut prevouts = HashMap::new();
+        prevouts.insert(
+            tx.inputs()[1].outpoint,
+            TxOut::new(0, ScriptPubkey::from(vec![0x51])),
+        );
+        assert!(check_malleability(&tx, None, &prevouts).is_empty());
+    }
+
+    #[test]
+    fn it_finds_high_s_signatures() {
+        // A high-S signature, and its low-S equivalent. From the k256 normalization tests
+        let r = "20c01a910ebb2610af2d763fa09b3b30923c8e408b11df2c61ad76d970a2f1bc";
+        let high = hex::decode(format!(
+            "{}ee2f11ef8cb00a49617d1357f4d55641090a48f201e9b959c48f6f6bec6f938f",
+            r
+        ))
+        .unwrap();
+        let high = Signature::try_from(&high[..]).unwrap();
+        let mut low = high;
+        assert!(low.normalize_s().unwrap());
+
+        let script_sig = |sig: &Signature| {
+            let mut item = sig.to_der().as_bytes().to_vec();
+            item.push(Sighash::All as u8);
+            let mut v = vec![item.len() as u8];
+            v.extend(item);
+            ScriptSig::from(v)
+        };
+
+        let mut tx = LegacyTx::deserialize_hex(
+            "0100000001fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffff0100000000000000000000000000",
+        )
+        .unwrap();
+        tx.vin[0].script_sig = script_sig(&low);
+        assert!(check_malleability(&tx.clone().into_witness(), None, &HashMap::new()).is_empty());
+
+        tx.vin[0].script_sig = script_sig(&high);
+        assert_eq!(
+            check_malleability(&tx.into_witness(), None, &HashMap::new()),
+            vec![MalleabilityIssue::HighS {
+                input: 0,
+                signature: 0
+            }]
+        );
+    }
+}
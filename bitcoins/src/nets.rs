@@ -29,6 +29,7 @@
 //! ```
 use std::marker::PhantomData;
 
+use coins_bip32::enc::XKeyEncoder;
 use coins_core::{
     enc::{AddressEncoder, EncodingError},
     nets::Network,
@@ -36,7 +37,16 @@ use coins_core::{
 
 use crate::{
     builder::BitcoinTxBuilder,
-    enc::encoder::{Address, BitcoinEncoderMarker, MainnetEncoder, SignetEncoder, TestnetEncoder},
+    enc::{
+        encoder::{
+            Address, BitcoinEncoderMarker, MainnetEncoder, NetworkKind, RegtestEncoder,
+            SignetEncoder, TestnetEncoder,
+        },
+        params::AddressParams,
+    },
+    hashes::BlockHash,
+    signet::SignetParams,
+    types::block::Target,
     types::{
         BitcoinTransaction, BitcoinTx, BitcoinTxIn, ScriptPubkey, TxOut, WitnessTransaction,
         WitnessTx,
@@ -48,6 +58,9 @@ use crate::{
 pub trait BitcoinNetwork: Network {
     /// An associated witness transaction type.
     type WTx: WitnessTransaction + BitcoinTransaction;
+    /// The BIP32 extended key encoder for this network. Keys decoded with it are checked against
+    /// the network's version bytes.
+    type XKeyEncoder: XKeyEncoder;
 }
 
 /// A newtype for Bitcoin networks, parameterized by an encoder. We change the encoder to
@@ -74,6 +87,7 @@ where
     T: BitcoinEncoderMarker,
 {
     type WTx = WitnessTx;
+    type XKeyEncoder = T::XKeyEncoder;
 }
 
 /// A fully-parameterized BitcoinMainnet. This is the main interface for accessing the library.
@@ -85,6 +99,197 @@ pub type BitcoinTestnet = Bitcoin<TestnetEncoder>;
 /// A fully-parameterized BitcoinSignet. This is the main interface for accessing the library.
 pub type BitcoinSignet = Bitcoin<SignetEncoder>;
 
+/// A fully-parameterized BitcoinRegtest, for local test networks.
+pub type BitcoinRegtest = Bitcoin<RegtestEncoder>;
+
+/// The consensus and P2P parameters of a Bitcoin network. Header validation and P2P code
+/// should read network constants from here rather than hardcoding them.
+///
+/// Address encoding parameters are in `enc::AddressParams`, available via `address_params`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainParams {
+    /// The network name, as used by Bitcoin Core's `-chain` option
+    pub name: &'static str,
+    /// The P2P message start bytes
+    pub magic: [u8; 4],
+    /// The hash of the genesis block
+    pub genesis_hash: BlockHash,
+    /// The default P2P port
+    pub default_port: u16,
+    /// The height at which BIP34 (height in coinbase) activated
+    pub bip34_height: u32,
+    /// The height at which BIP65 (`OP_CHECKLOCKTIMEVERIFY`) activated
+    pub bip65_height: u32,
+    /// The height at which BIP66 (strict DER signatures) activated
+    pub bip66_height: u32,
+    /// The number of blocks between block subsidy halvings
+    pub subsidy_halving_interval: u32,
+    /// The highest allowed proof of work target, big-endian
+    pub pow_limit: Target,
+    /// The compact encoding of the genesis block's target
+    pub pow_limit_bits: u32,
+    /// The target duration of a difficulty adjustment period, in seconds
+    pub pow_target_timespan: u32,
+    /// The target time between blocks, in seconds
+    pub pow_target_spacing: u32,
+    /// True if blocks may use the minimum difficulty after 20 minutes without a block
+    pub pow_allow_min_difficulty_blocks: bool,
+    /// True if the difficulty never adjusts
+    pub pow_no_retargeting: bool,
+}
+
+/// Parse a big-endian 32-byte hex constant
+fn be_bytes(hex: &str) -> Target {
+    let mut buf = [0u8; 32];
+    buf.copy_from_slice(&hex::decode(hex).expect("valid hex"));
+    buf
+}
+
+impl ChainParams {
+    /// The parameters of Bitcoin Mainnet
+    pub fn mainnet() -> Self {
+        Self {
+            name: "main",
+            magic: [0xf9, 0xbe, 0xb4, 0xd9],
+            genesis_hash: BlockHash::from_be_hex(
+                "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            )
+            .expect("valid hex"),
+            default_port: 8333,
+            bip34_height: 227_931,
+            bip65_height: 388_381,
+            bip66_height: 363_725,
+            subsidy_halving_interval: 210_000,
+            pow_limit: be_bytes("00000000ffffffffffffffffffffffffffffffffffffffffffffffffffffffff"),
+            pow_limit_bits: 0x1d00_ffff,
+            pow_target_timespan: 14 * 24 * 60 * 60,
+            pow_target_spacing: 10 * 60,
+            pow_allow_min_difficulty_blocks: false,
+            pow_no_retargeting: false,
+        }
+    }
+
+    /// The parameters of Bitcoin Testnet (testnet3)
+    pub fn testnet() -> Self {
+        Self {
+            name: "test",
+            magic: [0x0b, 0x11, 0x09, 0x07],
+            genesis_hash: BlockHash::from_be_hex(
+                "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943",
+            )
+            .expect("valid hex"),
+            default_port: 18333,
+            bip34_height: 21_111,
+            bip65_height: 581_885,
+            bip66_height: 330_776,
+            pow_allow_min_difficulty_blocks: true,
+            ..Self::mainnet()
+        }
+    }
+
+    /// The parameters of the default Bitcoin Signet. Use `custom_signet` for other signets.
+    pub fn signet() -> Self {
+        Self {
+            name: "signet",
+            magic: [0x0a, 0x03, 0xcf, 0x40],
+            genesis_hash: BlockHash::from_be_hex(
+                "00000008819873e925422c1ff0f99f7cc9bbb232af63a077a480a3633bee1ef6",
+            )
+            .expect("valid hex"),
+            default_port: 38333,
+            bip34_height: 1,
+            bip65_height: 1,
+            bip66_height: 1,
+            pow_limit: be_bytes("00000377ae000000000000000000000000000000000000000000000000000000"),
+            pow_limit_bits: 0x1e03_77ae,
+            ..Self::mainnet()
+        }
+    }
+
+    /// The parameters of a custom signet. All signets share the default signet's genesis
+    /// block and consensus rules. The magic is derived from the challenge.
+    pub fn custom_signet(params: &SignetParams) -> Self {
+        Self {
+            magic: params.magic(),
+            ..Self::signet()
+        }
+    }
+
+    /// The parameters of Bitcoin Regtest
+    pub fn regtest() -> Self {
+        Self {
+            name: "regtest",
+            magic: [0xfa, 0xbf, 0xb5, 0xda],
+            genesis_hash: BlockHash::from_be_hex(
+                "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206",
+            )
+            .expect("valid hex"),
+            default_port: 18444,
+            bip34_height: 1,
+            bip65_height: 1,
+            bip66_height: 1,
+            subsidy_halving_interval: 150,
+            pow_limit: be_bytes("7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff"),
+            pow_limit_bits: 0x207f_ffff,
+            pow_allow_min_difficulty_blocks: true,
+            pow_no_retargeting: true,
+            ..Self::mainnet()
+        }
+    }
+
+    /// All standard networks
+    pub fn all() -> [Self; 4] {
+        [
+            Self::mainnet(),
+            Self::testnet(),
+            Self::signet(),
+            Self::regtest(),
+        ]
+    }
+
+    /// Look up a standard network by name. Accepts Bitcoin Core's names, "main", "test",
+    /// "signet", and "regtest".
+    pub fn by_name(name: &str) -> Option<Self> {
+        Self::all().iter().find(|p| p.name == name).copied()
+    }
+
+    /// Identify a standard network by its P2P magic bytes. Custom signets are not identified.
+    pub fn by_magic(magic: [u8; 4]) -> Option<Self> {
+        Self::all().iter().find(|p| p.magic == magic).copied()
+    }
+
+    /// The number of blocks between difficulty adjustments
+    pub fn difficulty_adjustment_interval(&self) -> u32 {
+        self.pow_target_timespan / self.pow_target_spacing
+    }
+
+    /// The network kind. Custom signets are `NetworkKind::Signet`.
+    pub fn network_kind(&self) -> NetworkKind {
+        match self.name {
+            "main" => NetworkKind::Mainnet,
+            "test" => NetworkKind::Testnet,
+            "signet" => NetworkKind::Signet,
+            _ => NetworkKind::Regtest,
+        }
+    }
+
+    /// The address encoding parameters of the network
+    pub fn address_params(&self) -> AddressParams {
+        AddressParams::from(self.network_kind())
+    }
+}
+
+impl From<NetworkKind> for ChainParams {
+    fn from(network: NetworkKind) -> Self {
+        match network {
+            NetworkKind::Mainnet => Self::mainnet(),
+            NetworkKind::Testnet => Self::testnet(),
+            NetworkKind::Signet => Self::signet(),
+            NetworkKind::Regtest => Self::regtest(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -122,4 +327,56 @@ mod test {
         let u = BitcoinMainnet::decode_address(&address);
         assert_eq!(&address, &BitcoinMainnet::encode_address(&u).unwrap())
     }
+
+    #[test]
+    fn it_selects_xkey_encoders_by_network() {
+        let xpriv_str = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+        let xpriv =
+            <BitcoinMainnet as BitcoinNetwork>::XKeyEncoder::xpriv_from_base58(xpriv_str).unwrap();
+
+        let tprv =
+            <BitcoinTestnet as BitcoinNetwork>::XKeyEncoder::xpriv_to_base58(&xpriv).unwrap();
+        let sprv = <BitcoinSignet as BitcoinNetwork>::XKeyEncoder::xpriv_to_base58(&xpriv).unwrap();
+        assert!(tprv.starts_with("tprv"));
+        assert_eq!(tprv, sprv);
+
+        assert!(matches!(
+            <BitcoinMainnet as BitcoinNetwork>::XKeyEncoder::xpriv_from_base58(&tprv),
+            Err(coins_bip32::Bip32Error::WrongNetworkVersionBytes { .. })
+        ));
+    }
+
+    #[test]
+    fn it_exposes_consensus_params() {
+        use crate::types::block::{target_to_compact, BlockHeader};
+
+        let genesis = BlockHeader::deserialize_hex("0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c").unwrap();
+        let main = ChainParams::from(NetworkKind::Mainnet);
+        assert_eq!(genesis.block_hash(), main.genesis_hash);
+        assert_eq!(genesis.bits(), main.pow_limit_bits);
+        genesis.validate_pow_with_limit(&main.pow_limit).unwrap();
+        assert_eq!(main.difficulty_adjustment_interval(), 2016);
+
+        for params in ChainParams::all().iter() {
+            assert_eq!(target_to_compact(&params.pow_limit), params.pow_limit_bits);
+            assert_eq!(ChainParams::by_name(params.name), Some(*params));
+            assert_eq!(ChainParams::by_magic(params.magic), Some(*params));
+        }
+        for network in NetworkKind::ALL.iter() {
+            assert_eq!(ChainParams::from(*network).network_kind(), *network);
+        }
+        assert_eq!(ChainParams::regtest().address_params().hrp, "bcrt");
+        let parsed = Address::parse_any("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080").unwrap();
+        assert_eq!(ChainParams::from(parsed.network), ChainParams::regtest());
+        assert!(ChainParams::by_name("testnet4").is_none());
+
+        assert_eq!(
+            ChainParams::custom_signet(&SignetParams::default()),
+            ChainParams::signet()
+        );
+        let custom = ChainParams::custom_signet(&SignetParams::new(ScriptPubkey::from(vec![0x51])));
+        assert_eq!(custom.genesis_hash, ChainParams::signet().genesis_hash);
+        assert!(ChainParams::by_magic(custom.magic).is_none());
+        assert_eq!(custom.network_kind(), NetworkKind::Signet);
+    }
 }
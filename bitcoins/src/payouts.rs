@@ -0,0 +1,390 @@
+//! Splitting large payout batches into standard-sized transactions.
+//!
+//! A batch paying thousands of recipients may exceed the standard transaction weight, and be
+//! refused by nodes. The `PayoutSplitter` divides the payouts evenly between as few transactions
+//! as fit under a maximum vsize. Each transaction pays the same feerate.
+//!
+//! The transactions form a chain. Each one sends its change to the next, which spends it
+//! alongside any further UTXOs it needs. UTXOs are selected in the order given. Because the
+//! txid of a transaction with legacy inputs changes when it is signed, each transaction must be
+//! signed before the next is built. See `PayoutBatch::builder`.
+//!
+//! ```
+//! use bitcoins::{
+//!     builder::InputType,
+//!     payouts::PayoutSplitter,
+//!     types::{FeeRate, ScriptPubkey},
+//! };
+//!
+//! let change = ScriptPubkey::from_hex("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928").unwrap();
+//! let feerate = FeeRate::from_sat_per_vb(2);
+//! let splitter = PayoutSplitter::new(change, InputType::Wpkh, feerate).max_vsize(50_000);
+//! assert!(splitter.split(&[], &[]).is_err());
+//! ```
+
+use thiserror::Error;
+
+use coins_core::builder::TxBuilder;
+
+use crate::{
+    builder::{BitcoinTxBuilder, InputType},
+    enc::encoder::{BitcoinEncoderMarker, MainnetEncoder},
+    policy::MAX_STANDARD_TX_WEIGHT,
+    types::{BitcoinOutpoint, FeeRate, ScriptPubkey, TxOut, Utxo, DEFAULT_DUST_RELAY_FEERATE},
+};
+
+/// The default maximum vsize of each transaction. This is the standard weight limit in vbytes.
+pub const DEFAULT_MAX_BATCH_VSIZE: usize = MAX_STANDARD_TX_WEIGHT / 4;
+
+/// An error produced while splitting a payout batch.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum SplitError {
+    /// There are no payouts to split
+    #[error("No payouts to split")]
+    NoPayouts,
+
+    /// The UTXOs cannot pay for the payouts and the fees
+    #[error("Insufficient funds. Required {required} sat. Available {available} sat")]
+    InsufficientFunds {
+        /// The value required by the batch that could not be funded, including its fee
+        required: u64,
+        /// The total value of the UTXOs, plus any change from earlier batches
+        available: u64,
+    },
+
+    /// Some payout cannot be made in a transaction under the maximum vsize, even alone
+    #[error("Payouts cannot fit in transactions of at most {} vbytes", .0)]
+    TooLarge(usize),
+}
+
+/// A single transaction in a split payout batch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayoutBatch {
+    /// The value of the previous batch's change spent by this batch, if any
+    pub chained: Option<u64>,
+    /// The UTXOs spent by this batch, in order, after the chained change
+    pub inputs: Vec<Utxo>,
+    /// The spend type of each UTXO, in order
+    pub input_types: Vec<InputType>,
+    /// The payouts made by this batch
+    pub outputs: Vec<TxOut>,
+    /// The change output, if any. It follows the payouts
+    pub change: Option<TxOut>,
+    /// The estimated virtual size of the transaction, in vbytes
+    pub vsize: usize,
+    /// The fee paid by the transaction
+    pub fee: u64,
+}
+
+impl PayoutBatch {
+    /// The index of the change output, if any
+    pub fn change_index(&self) -> Option<usize> {
+        self.change.as_ref().map(|_| self.outputs.len())
+    }
+
+    /// Return a builder for the transaction. `prev_change` is the outpoint of the previous
+    /// batch's change output. It is required if `chained` is set, and must use the txid of the
+    /// previous transaction as signed. Inputs use the specified sequence number.
+    pub fn builder<T>(
+        &self,
+        prev_change: Option<BitcoinOutpoint>,
+        sequence: u32,
+    ) -> BitcoinTxBuilder<T>
+    where
+        T: BitcoinEncoderMarker,
+    {
+        let builder = prev_change
+            .into_iter()
+            .chain(self.inputs.iter().map(|utxo| utxo.outpoint))
+            .fold(BitcoinTxBuilder::<T>::new().version(2), |b, outpoint| {
+                b.spend(outpoint, sequence)
+            });
+        self.outputs
+            .iter()
+            .chain(self.change.iter())
+            .fold(builder, |b, output| {
+                b.pay_script_pubkey(output.value, output.script_pubkey.clone())
+            })
+    }
+}
+
+/// A payout batch split into a chain of transactions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayoutPlan {
+    /// The feerate paid by each transaction
+    pub feerate: FeeRate,
+    /// The transactions, in chain order
+    pub batches: Vec<PayoutBatch>,
+}
+
+impl PayoutPlan {
+    /// The total fee paid by all transactions
+    pub fn fee(&self) -> u64 {
+        self.batches.iter().map(|b| b.fee).sum()
+    }
+}
+
+/// Splits payout batches into chains of transactions under a maximum vsize.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayoutSplitter {
+    change: ScriptPubkey,
+    change_type: InputType,
+    feerate: FeeRate,
+    max_vsize: usize,
+}
+
+impl PayoutSplitter {
+    /// Instantiate a splitter that sends change to `change`, which is spent as `change_type`.
+    pub fn new(change: ScriptPubkey, change_type: InputType, feerate: FeeRate) -> Self {
+        Self {
+            change,
+            change_type,
+            feerate,
+            max_vsize: DEFAULT_MAX_BATCH_VSIZE,
+        }
+    }
+
+    /// Set the maximum vsize of each transaction
+    pub fn max_vsize(mut self, max_vsize: usize) -> Self {
+        self.max_vsize = max_vsize;
+        self
+    }
+
+    /// Split the payouts into as few transactions as fit under the maximum vsize, funded by the
+    /// UTXOs in order. Payouts keep their order, and are divided as evenly as possible. UTXOs
+    /// not needed to fund the payouts are not spent.
+    pub fn split(
+        &self,
+        utxos: &[(Utxo, InputType)],
+        payouts: &[TxOut],
+    ) -> Result<PayoutPlan, SplitError> {
+        if payouts.is_empty() {
+            return Err(SplitError::NoPayouts);
+        }
+        for n in 1..=payouts.len() {
+            match self.split_into(utxos, payouts, n) {
+                Ok(batches) => {
+                    return Ok(PayoutPlan {
+                        feerate: self.feerate,
+                        batches,
+                    })
+                }
+                Err(SplitError::TooLarge(_)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(SplitError::TooLarge(self.max_vsize))
+    }
+
+    /// Split the payouts into exactly `n` transactions
+    fn split_into(
+        &self,
+        utxos: &[(Utxo, InputType)],
+        payouts: &[TxOut],
+        n: usize,
+    ) -> Result<Vec<PayoutBatch>, SplitError> {
+        let mut batches = vec![];
+        let mut utxos = utxos.iter();
+        let mut chained = None;
+        let mut start = 0;
+        for i in 0..n {
+            // The first `payouts.len() % n` batches take one extra payout
+            let end = start + payouts.len() / n + usize::from(i < payouts.len() % n);
+            let batch = self.batch(&mut utxos, chained, &payouts[start..end], i == n - 1)?;
+            chained = batch.change.as_ref().map(|c| c.value);
+            batches.push(batch);
+            start = end;
+        }
+        Ok(batches)
+    }
+
+    /// Build a single batch, selecting UTXOs until it is funded. Every batch but the last must
+    /// have change, so that the chain continues.
+    fn batch<'a, I>(
+        &self,
+        utxos: &mut I,
+        chained: Option<u64>,
+        outputs: &[TxOut],
+        last: bool,
+    ) -> Result<PayoutBatch, SplitError>
+    where
+        I: Iterator<Item = &'a (Utxo, InputType)>,
+    {
+        let change_limit =
+            TxOut::new(0, self.change.clone()).dust_limit(DEFAULT_DUST_RELAY_FEERATE);
+        let payout_value: u64 = outputs.iter().map(|o| o.value).sum();
+
+        // The encoder does not affect size estimation
+        let mut without_change = outputs
+            .iter()
+            .fold(BitcoinTxBuilder::<MainnetEncoder>::new(), |b, o| {
+                b.pay_script_pubkey(o.value, o.script_pubkey.clone())
+            });
+        let mut with_change = without_change
+            .clone()
+            .pay_script_pubkey(0, self.change.clone());
+        let mut input_types = vec![];
+        if chained.is_some() {
+            without_change = without_change.spend(BitcoinOutpoint::default(), 0);
+            with_change = with_change.spend(BitcoinOutpoint::default(), 0);
+            input_types.push(self.change_type);
+        }
+
+        let mut inputs = vec![];
+        let mut available = chained.unwrap_or(0);
+        loop {
+            let vsize_without = without_change
+                .estimate_vsize(&input_types)
+                .expect("counted");
+            let vsize_with = with_change.estimate_vsize(&input_types).expect("counted");
+            let fee_without = self.feerate.fee_for_vsize(vsize_without);
+            let fee_with = self.feerate.fee_for_vsize(vsize_with);
+
+            if !input_types.is_empty() {
+                let change = available.checked_sub(payout_value + fee_with);
+                let funded = match change {
+                    Some(value) if value >= change_limit => {
+                        Some((vsize_with, fee_with, Some(value)))
+                    }
+                    _ if last && available >= payout_value + fee_without => {
+                        Some((vsize_without, available - payout_value, None))
+                    }
+                    _ => None,
+                };
+                if let Some((vsize, fee, change)) = funded {
+                    if vsize > self.max_vsize {
+                        return Err(SplitError::TooLarge(self.max_vsize));
+                    }
+                    return Ok(PayoutBatch {
+                        chained,
+                        inputs,
+                        input_types: input_types[chained.is_some() as usize..].to_vec(),
+                        outputs: outputs.to_vec(),
+                        change: change.map(|value| TxOut::new(value, self.change.clone())),
+                        vsize,
+                        fee,
+                    });
+                }
+            }
+            if vsize_without > self.max_vsize {
+                return Err(SplitError::TooLarge(self.max_vsize));
+            }
+
+            let (utxo, input_type) = match utxos.next() {
+                Some(next) => next,
+                None => {
+                    let required = if last {
+                        payout_value + fee_without
+                    } else {
+                        payout_value + fee_with + change_limit
+                    };
+                    return Err(SplitError::InsufficientFunds {
+                        required,
+                        available,
+                    });
+                }
+            };
+            without_change = without_change.spend(utxo.outpoint, 0);
+            with_change = with_change.spend(utxo.outpoint, 0);
+            input_types.push(*input_type);
+            inputs.push(utxo.clone());
+            available += utxo.value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::{SpendScript, TxOut};
+    use coins_core::types::tx::Transaction;
+
+    fn spk(byte: &str) -> ScriptPubkey {
+        ScriptPubkey::from_hex(&format!("0014{}", byte.repeat(20))).unwrap()
+    }
+
+    fn utxo(idx: u32, value: u64) -> (Utxo, InputType) {
+        let outpoint = BitcoinOutpoint::new(Default::default(), idx);
+        (
+            Utxo::new(outpoint, value, spk("01"), SpendScript::None),
+            InputType::Wpkh,
+        )
+    }
+
+    #[test]
+    fn it_splits_payouts() {
+        let splitter = PayoutSplitter::new(spk("02"), InputType::Wpkh, FeeRate::from_sat_per_vb(2));
+        let utxos: Vec<_> = (0..4).map(|i| utxo(i, 200_000)).collect();
+        let payouts: Vec<_> = (0..10).map(|_| TxOut::new(10_000, spk("03"))).collect();
+
+        // Everything fits in one transaction
+        let plan = splitter.split(&utxos, &payouts).unwrap();
+        assert_eq!(plan.batches.len(), 1);
+        assert_eq!(plan.batches[0].inputs.len(), 1);
+        assert_eq!(plan.batches[0].change_index(), Some(10));
+        assert_eq!(plan.batches[0].vsize, 420);
+
+        // Limited to 300 vbytes, the payouts are split between two transactions
+        let plan = splitter
+            .clone()
+            .max_vsize(300)
+            .split(&utxos, &payouts)
+            .unwrap();
+        assert_eq!(plan.batches.len(), 2);
+        let (first, second) = (&plan.batches[0], &plan.batches[1]);
+        assert_eq!((first.outputs.len(), second.outputs.len()), (5, 5));
+        assert_eq!(first.chained, None);
+        assert_eq!(second.chained, Some(first.change.as_ref().unwrap().value));
+        assert!(second.inputs.is_empty());
+        for batch in plan.batches.iter() {
+            assert!(batch.vsize <= 300);
+            assert_eq!(batch.fee, plan.feerate.fee_for_vsize(batch.vsize));
+        }
+        assert_eq!(
+            second.change.as_ref().unwrap().value,
+            200_000 - 100_000 - plan.fee()
+        );
+
+        let prev_change = BitcoinOutpoint::new(Default::default(), 5);
+        let builder = second.builder::<MainnetEncoder>(Some(prev_change), 0xffff_fffd);
+        assert_eq!(
+            builder.estimate_vsize(&[InputType::Wpkh]),
+            Some(second.vsize)
+        );
+        let tx = builder.build().unwrap();
+        assert_eq!(tx.inputs()[0].outpoint, prev_change);
+        assert_eq!(tx.outputs().len(), 6);
+    }
+
+    #[test]
+    fn it_reports_split_errors() {
+        let splitter = PayoutSplitter::new(spk("02"), InputType::Wpkh, FeeRate::from_sat_per_vb(2));
+        let utxos = vec![utxo(0, 30_000), utxo(1, 30_000)];
+        let payouts: Vec<_> = (0..4).map(|_| TxOut::new(10_000, spk("03"))).collect();
+
+        assert_eq!(splitter.split(&utxos, &[]), Err(SplitError::NoPayouts));
+        assert_eq!(
+            splitter.clone().max_vsize(100).split(&utxos, &payouts),
+            Err(SplitError::TooLarge(100))
+        );
+        match splitter.split(&utxos[..1], &payouts) {
+            Err(SplitError::InsufficientFunds { available, .. }) => assert_eq!(available, 30_000),
+            other => panic!("expected InsufficientFunds. Got {:?}", other),
+        }
+
+        // Later batches spend the previous change and further UTXOs
+        let utxos: Vec<_> = (0..4).map(|i| utxo(i, 12_000)).collect();
+        let plan = splitter.max_vsize(250).split(&utxos, &payouts).unwrap();
+        let inputs: Vec<_> = plan.batches.iter().map(|b| b.inputs.len()).collect();
+        let outputs: Vec<_> = plan.batches.iter().map(|b| b.outputs.len()).collect();
+        assert_eq!(inputs, vec![2, 1, 1]);
+        assert_eq!(outputs, vec![2, 1, 1]);
+        for pair in plan.batches.windows(2) {
+            assert_eq!(pair[1].chained, pair[0].change.as_ref().map(|c| c.value));
+        }
+        assert_eq!(
+            plan.batches[2].change.as_ref().unwrap().value,
+            48_000 - 40_000 - plan.fee()
+        );
+    }
+}
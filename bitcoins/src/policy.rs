@@ -0,0 +1,650 @@
+//! Standardness checks against Bitcoin Core's default relay policy.
+//!
+//! A transaction may be valid by consensus, and still be refused by nodes because it is
+//! non-standard. `Policy::check` finds the policy rules a transaction breaks, so that it can be
+//! fixed before broadcast. It checks the transaction weight, output script types, dust outputs,
+//! the sigop cost, script sig size and push rules, and bare multisig outputs.
+//!
+//! Some rules depend on the prevouts being spent. Callers supply the prevouts they know, keyed by
+//! outpoint. Inputs with unknown prevouts are checked without them, and their P2SH and witness
+//! sigops are not counted.
+//!
+//! Whether a transaction's locktime and BIP68 relative locks allow it into the next block depends
+//! on the chain. `Policy::check_final` evaluates them against a `HeaderChain`, using the median
+//! time past of the tip, and of the blocks that confirmed the prevouts, for time-based locks.
+//!
+//! ```
+//! use std::collections::HashMap;
+//! use bitcoins::{policy::Policy, types::LegacyTx};
+//!
+//! let violations = Policy::default().check(&LegacyTx::default(), &HashMap::new());
+//! assert!(violations.is_empty());
+//! ```
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::{
+    hashes::BlockHash,
+    headers::HeaderChain,
+    types::{
+        feerate::FeeRate,
+        opcodes::{Instruction, Instructions, Opcode},
+        script::{ScriptPubkey, ScriptType},
+        timelock::{LockTime, Sequence},
+        tx::BitcoinTransaction,
+        txin::BitcoinOutpoint,
+        txout::{dust_limit, TxOut, DEFAULT_DUST_RELAY_FEERATE},
+        witness_program::split_witness_program,
+    },
+};
+
+/// The maximum weight of a standard transaction.
+pub const MAX_STANDARD_TX_WEIGHT: usize = 400_000;
+
+/// The maximum sigop cost of a standard transaction.
+pub const MAX_STANDARD_TX_SIGOPS_COST: usize = 16_000;
+
+/// The maximum size of a standard script sig.
+pub const MAX_STANDARD_SCRIPTSIG_SIZE: usize = 1650;
+
+/// The maximum size of a standard OP_RETURN script pubkey.
+pub const MAX_OP_RETURN_RELAY: usize = 83;
+
+/// The maximum number of keys in a standard bare multisig output.
+pub const MAX_STANDARD_BARE_MULTISIG_KEYS: usize = 3;
+
+/// The cost of each non-witness sigop, relative to witness sigops.
+const WITNESS_SCALE_FACTOR: usize = 4;
+
+/// A relay policy rule broken by a transaction.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum PolicyViolation {
+    /// The transaction is heavier than the maximum standard weight
+    #[error("Transaction weight {weight} exceeds the standard limit of {limit}")]
+    TxWeight {
+        /// The transaction weight
+        weight: usize,
+        /// The maximum standard weight
+        limit: usize,
+    },
+
+    /// The output's script pubkey is not a standard type
+    #[error("Output {} has a non-standard script pubkey", .0)]
+    NonStandardOutput(usize),
+
+    /// The output is a bare multisig, and bare multisig is not permitted
+    #[error("Output {} is a bare multisig", .0)]
+    BareMultisig(usize),
+
+    /// The transaction has more than one OP_RETURN output
+    #[error("Transaction has {} OP_RETURN outputs. Only 1 is standard", .0)]
+    MultipleOpReturn(usize),
+
+    /// The output's value is below its dust limit
+    #[error("Output {index} value {value} is below the dust limit of {limit}")]
+    DustOutput {
+        /// The index of the output
+        index: usize,
+        /// The value of the output
+        value: u64,
+        /// The dust limit of the output
+        limit: u64,
+    },
+
+    /// The transaction's sigop cost exceeds the standard limit
+    #[error("Sigop cost {cost} exceeds the standard limit of {limit}")]
+    SigopsCost {
+        /// The sigop cost of the transaction
+        cost: usize,
+        /// The maximum standard sigop cost
+        limit: usize,
+    },
+
+    /// The input's script sig is larger than the standard limit
+    #[error(
+        "Input {input} script sig is {size} bytes. The standard limit is {}",
+        MAX_STANDARD_SCRIPTSIG_SIZE
+    )]
+    ScriptSigSize {
+        /// The input index
+        input: usize,
+        /// The script sig size
+        size: usize,
+    },
+
+    /// The input's script sig contains opcodes other than pushes, or can't be parsed
+    #[error("Input {} script sig is not push-only", .0)]
+    ScriptSigNotPushOnly(usize),
+
+    /// The input's script sig contains a push that does not use the smallest encoding
+    #[error("Input {} script sig contains a non-minimal push", .0)]
+    NonMinimalPush(usize),
+
+    /// The transaction's locktime does not allow it into the next block
+    #[error("Locktime {} is not final in the next block", .0)]
+    NonFinalLocktime(u32),
+
+    /// The input's BIP68 relative lock does not allow it into the next block
+    #[error("Input {} relative lock is not satisfied in the next block", .0)]
+    RelativeLock(usize),
+}
+
+/// Parse a script into instructions. `None` if a push runs past the end of the script.
+fn instructions(script: &[u8]) -> Option<Vec<Instruction<'_>>> {
+    Instructions::new(script).collect::<Result<_, _>>().ok()
+}
+
+/// Count the sigops in a script. When `accurate`, `CHECKMULTISIG` counts the number of keys
+/// preceding it, as in P2SH and witness scripts. Otherwise it counts 20. Scripts that can't be
+/// parsed count the sigops before the parse error, as in Core.
+fn count_sigops(script: &[u8], accurate: bool) -> usize {
+    let mut count = 0;
+    let mut last_op = Opcode::Invalid(0xff);
+    for ins in Instructions::new(script).map_while(Result::ok) {
+        match (ins.opcode, last_op) {
+            (Opcode::CheckSig, _) | (Opcode::CheckSigVerify, _) => count += 1,
+            (Opcode::CheckMultisig, Opcode::PushNum(n))
+            | (Opcode::CheckMultisigVerify, Opcode::PushNum(n))
+                if accurate =>
+            {
+                count += n as usize
+            }
+            (Opcode::CheckMultisig, _) | (Opcode::CheckMultisigVerify, _) => count += 20,
+            _ => {}
+        }
+        last_op = ins.opcode;
+    }
+    count
+}
+
+/// True if the script is a standard OP_RETURN script
+fn is_null_data(script: &[u8]) -> bool {
+    script.first() == Some(&0x6a)
+        && script.len() <= MAX_OP_RETURN_RELAY
+        && instructions(&script[1..]).is_some_and(|ins| ins.iter().all(|i| i.opcode.is_push()))
+}
+
+/// Configuration for relay policy checks. The default matches Bitcoin Core's default policy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Policy {
+    max_weight: usize,
+    max_sigops_cost: usize,
+    dust_relay_feerate: FeeRate,
+    permit_bare_multisig: bool,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            max_weight: MAX_STANDARD_TX_WEIGHT,
+            max_sigops_cost: MAX_STANDARD_TX_SIGOPS_COST,
+            dust_relay_feerate: DEFAULT_DUST_RELAY_FEERATE,
+            permit_bare_multisig: true,
+        }
+    }
+}
+
+impl Policy {
+    /// Set the maximum transaction weight
+    pub fn max_weight(mut self, max_weight: usize) -> Self {
+        self.max_weight = max_weight;
+        self
+    }
+
+    /// Set the maximum transaction sigop cost
+    pub fn max_sigops_cost(mut self, max_sigops_cost: usize) -> Self {
+        self.max_sigops_cost = max_sigops_cost;
+        self
+    }
+
+    /// Set the dust relay feerate
+    pub fn dust_relay_feerate(mut self, dust_relay_feerate: FeeRate) -> Self {
+        self.dust_relay_feerate = dust_relay_feerate;
+        self
+    }
+
+    /// Set whether bare multisig outputs of up to 3 keys are standard
+    pub fn permit_bare_multisig(mut self, permit_bare_multisig: bool) -> Self {
+        self.permit_bare_multisig = permit_bare_multisig;
+        self
+    }
+
+    /// The dust limit of an output paying to `script_pubkey` at the policy's dust relay feerate
+    pub fn dust_limit(&self, script_pubkey: &ScriptPubkey) -> u64 {
+        dust_limit(script_pubkey, self.dust_relay_feerate)
+    }
+
+    /// Check an output's script pubkey. Returns a violation if it is non-standard
+    fn check_output(&self, index: usize, script_pubkey: &ScriptPubkey) -> Option<PolicyViolation> {
+        match script_pubkey.standard_type() {
+            ScriptType::Pkh(_)
+            | ScriptType::Sh(_)
+            | ScriptType::Wpkh(_)
+            | ScriptType::Wsh(_)
+            | ScriptType::Pk(_) => return None,
+            ScriptType::Multisig { pubkeys, .. } => {
+                return if pubkeys.len() > MAX_STANDARD_BARE_MULTISIG_KEYS {
+                    Some(PolicyViolation::NonStandardOutput(index))
+                } else if self.permit_bare_multisig {
+                    None
+                } else {
+                    Some(PolicyViolation::BareMultisig(index))
+                };
+            }
+            _ => {}
+        }
+        if is_null_data(script_pubkey.items()) {
+            return None;
+        }
+        // v0 programs must be P2WPKH or P2WSH. Other versions are reserved for upgrades, and
+        // are standard
+        if let Some((version, _)) = split_witness_program(script_pubkey.items()) {
+            return if version == 0 {
+                Some(PolicyViolation::NonStandardOutput(index))
+            } else {
+                None
+            };
+        }
+        Some(PolicyViolation::NonStandardOutput(index))
+    }
+
+    /// The sigop cost of the transaction. Inputs with unknown prevouts contribute only the
+    /// sigops in their script sigs.
+    pub fn sigops_cost<T: BitcoinTransaction>(
+        &self,
+        tx: &T,
+        prevouts: &HashMap<BitcoinOutpoint, TxOut>,
+    ) -> usize {
+        let mut legacy = 0;
+        for input in tx.inputs() {
+            legacy += count_sigops(input.script_sig.items(), false);
+        }
+        for output in tx.outputs() {
+            legacy += count_sigops(output.script_pubkey.items(), false);
+        }
+        let mut cost = legacy * WITNESS_SCALE_FACTOR;
+        if tx.is_coinbase() {
+            return cost;
+        }
+
+        let witnesses = tx.witnesses();
+        for (i, input) in tx.inputs().iter().enumerate() {
+            let prevout = match prevouts.get(&input.outpoint) {
+                Some(prevout) => prevout,
+                None => continue,
+            };
+            let last_push = instructions(input.script_sig.items())
+                .and_then(|ins| ins.last().and_then(|i| i.data.map(<[u8]>::to_vec)));
+
+            // The script pubkey that determines the witness sigops. For nested witness
+            // programs, this is the redeem script
+            let mut program = prevout.script_pubkey.clone();
+            if let ScriptType::Sh(_) = prevout.script_pubkey.standard_type() {
+                if let Some(redeem_script) = last_push {
+                    cost += count_sigops(&redeem_script, true) * WITNESS_SCALE_FACTOR;
+                    program = ScriptPubkey::new(redeem_script);
+                }
+            }
+
+            let witness = witnesses.get(i);
+            cost += match program.standard_type() {
+                ScriptType::Wpkh(_) => 1,
+                ScriptType::Wsh(_) => witness
+                    .and_then(|w| w.last())
+                    .map_or(0, |script| count_sigops(script.items(), true)),
+                _ => 0,
+            };
+        }
+        cost
+    }
+
+    /// Check a transaction against the policy. `prevouts` holds the outputs spent by the
+    /// transaction, where known. Returns every violation found, or an empty vector if the
+    /// transaction is standard.
+    pub fn check<T: BitcoinTransaction>(
+        &self,
+        tx: &T,
+        prevouts: &HashMap<BitcoinOutpoint, TxOut>,
+    ) -> Vec<PolicyViolation> {
+        let mut violations = vec![];
+
+        let weight = tx.weight();
+        if weight > self.max_weight {
+            violations.push(PolicyViolation::TxWeight {
+                weight,
+                limit: self.max_weight,
+            });
+        }
+
+        for (input, txin) in tx.inputs().iter().enumerate() {
+            let script_sig = txin.script_sig.items();
+            if script_sig.len() > MAX_STANDARD_SCRIPTSIG_SIZE {
+                violations.push(PolicyViolation::ScriptSigSize {
+                    input,
+                    size: script_sig.len(),
+                });
+            }
+            match instructions(script_sig) {
+                Some(ins) if ins.iter().all(|i| i.opcode.is_push()) => {
+                    if !ins.iter().all(Instruction::is_minimal_push) {
+                        violations.push(PolicyViolation::NonMinimalPush(input));
+                    }
+                }
+                _ => violations.push(PolicyViolation::ScriptSigNotPushOnly(input)),
+            }
+        }
+
+        let mut op_returns = 0;
+        for (index, output) in tx.outputs().iter().enumerate() {
+            if let Some(violation) = self.check_output(index, &output.script_pubkey) {
+                violations.push(violation);
+                continue;
+            }
+            if output.is_op_return() {
+                op_returns += 1;
+            } else if output.value < self.dust_limit(&output.script_pubkey) {
+                violations.push(PolicyViolation::DustOutput {
+                    index,
+                    value: output.value,
+                    limit: self.dust_limit(&output.script_pubkey),
+                });
+            }
+        }
+        if op_returns > 1 {
+            violations.push(PolicyViolation::MultipleOpReturn(op_returns));
+        }
+
+        let cost = self.sigops_cost(tx, prevouts);
+        if cost > self.max_sigops_cost {
+            violations.push(PolicyViolation::SigopsCost {
+                cost,
+                limit: self.max_sigops_cost,
+            });
+        }
+
+        violations
+    }
+
+    /// Check that a transaction's locktime and BIP68 relative locks allow it into the block
+    /// after the tip of `chain`, as Core checks before accepting it to the mempool. Locktimes
+    /// and time-based relative locks are compared against median times past. `confirmations`
+    /// maps each confirmed prevout to the block that confirmed it. Prevouts that are missing, or
+    /// whose block is unknown to the chain, are treated as confirming in the next block.
+    pub fn check_final<T: BitcoinTransaction>(
+        &self,
+        tx: &T,
+        chain: &HeaderChain,
+        confirmations: &HashMap<BitcoinOutpoint, BlockHash>,
+    ) -> Vec<PolicyViolation> {
+        let mut violations = vec![];
+        let height = chain.height() + 1;
+        let median_time_past = chain.tip_median_time_past();
+
+        let locktime = LockTime::from_consensus(tx.locktime());
+        let all_final = tx
+            .inputs()
+            .iter()
+            .all(|txin| Sequence::from_consensus(txin.sequence).is_final());
+        if !all_final && !locktime.is_final_at(height, median_time_past) {
+            violations.push(PolicyViolation::NonFinalLocktime(tx.locktime()));
+        }
+
+        if tx.version() < 2 {
+            return violations;
+        }
+        for (input, txin) in tx.inputs().iter().enumerate() {
+            let lock = match Sequence::from_consensus(txin.sequence).relative_lock() {
+                Some(lock) => lock,
+                None => continue,
+            };
+            let (prevout_height, prevout_median_time_past) = confirmations
+                .get(&txin.outpoint)
+                .and_then(|hash| {
+                    let entry = chain.get(hash)?;
+                    Some((entry.height, chain.relative_lock_start(hash)?))
+                })
+                .unwrap_or((height, median_time_past));
+            if !lock.is_final_at(
+                height,
+                median_time_past,
+                prevout_height,
+                prevout_median_time_past,
+            ) {
+                violations.push(PolicyViolation::RelativeLock(input));
+            }
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        hashes::TXID,
+        types::{legacy::LegacyTx, script::Script, txin::BitcoinTxIn},
+    };
+    use coins_core::{ser::ByteFormat, types::tx::Transaction};
+
+    fn spk(hex: &str) -> ScriptPubkey {
+        ScriptPubkey::from_hex(hex).unwrap()
+    }
+
+    fn tx(script_sigs: Vec<Vec<u8>>, vout: Vec<TxOut>) -> LegacyTx {
+        let txid = TXID::deserialize_hex(&"ab".repeat(32)).unwrap();
+        let vin: Vec<_> = script_sigs
+            .into_iter()
+            .enumerate()
+            .map(|(i, s)| BitcoinTxIn::new(BitcoinOutpoint::new(txid, i as u32), s, 0xffff_ffff))
+            .collect();
+        LegacyTx::new(2, vin, vout, 0).unwrap()
+    }
+
+    #[test]
+    fn it_checks_outputs() {
+        let key = format!("21{}", "02".repeat(33));
+        let multisig = |n: usize| spk(&format!("51{}{:02x}ae", key.repeat(n), 0x50 + n));
+        let wpkh = spk("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928");
+        let outputs = vec![
+            TxOut::new(10_000, wpkh.clone()),
+            TxOut::new(10_000, spk(&format!("5120{}", "11".repeat(32)))),
+            TxOut::new(10_000, spk(&format!("0018{}", "11".repeat(24)))),
+            TxOut::new(10_000, multisig(3)),
+            TxOut::new(10_000, multisig(4)),
+            TxOut::new(10_000, spk("51")),
+            TxOut::new(0, ScriptPubkey::op_return(&[1; 80])),
+            TxOut::new(0, ScriptPubkey::op_return(&[1; 81])),
+            TxOut::new(293, wpkh),
+        ];
+        let tx = tx(vec![vec![]], outputs);
+
+        assert_eq!(
+            Policy::default().check(&tx, &HashMap::new()),
+            vec![
+                PolicyViolation::NonStandardOutput(2),
+                PolicyViolation::NonStandardOutput(4),
+                PolicyViolation::NonStandardOutput(5),
+                PolicyViolation::NonStandardOutput(7),
+                PolicyViolation::DustOutput {
+                    index: 8,
+                    value: 293,
+                    limit: 294
+                },
+            ]
+        );
+        let strict = Policy::default()
+            .permit_bare_multisig(false)
+            .dust_relay_feerate(FeeRate::ZERO);
+        assert_eq!(
+            strict.check(&tx, &HashMap::new())[1],
+            PolicyViolation::BareMultisig(3)
+        );
+
+        let op_returns = vec![TxOut::op_return(&[1]), TxOut::op_return(&[2])];
+        assert_eq!(
+            Policy::default().check(&self::tx(vec![vec![]], op_returns), &HashMap::new()),
+            vec![PolicyViolation::MultipleOpReturn(2)]
+        );
+    }
+
+    #[test]
+    fn it_checks_script_sigs_and_weight() {
+        let out = || {
+            vec![TxOut::new(
+                10_000,
+                ScriptPubkey::p2sh(&Script::new(vec![0x51])),
+            )]
+        };
+        let script_sigs = vec![
+            vec![0x01, 0x20, 0x02, 0xab, 0xcd],
+            vec![0x01, 0x05],
+            vec![0x4c, 0x02, 0xab, 0xcd],
+            vec![0x00, 0x76],
+            vec![0x02, 0xab],
+            vec![0x4d, 0x90, 0x06]
+                .into_iter()
+                .chain(vec![0; 1680])
+                .collect(),
+        ];
+        assert_eq!(
+            Policy::default().check(&tx(script_sigs, out()), &HashMap::new()),
+            vec![
+                PolicyViolation::NonMinimalPush(1),
+                PolicyViolation::NonMinimalPush(2),
+                PolicyViolation::ScriptSigNotPushOnly(3),
+                PolicyViolation::ScriptSigNotPushOnly(4),
+                PolicyViolation::ScriptSigSize {
+                    input: 5,
+                    size: 1683
+                },
+            ]
+        );
+
+        let tx = tx(vec![vec![]], out());
+        let weight = tx.weight();
+        assert_eq!(
+            Policy::default()
+                .max_weight(weight - 1)
+                .check(&tx, &HashMap::new()),
+            vec![PolicyViolation::TxWeight {
+                weight,
+                limit: weight - 1
+            }]
+        );
+    }
+
+    #[test]
+    fn it_counts_sigops() {
+        let key = format!("21{}", "02".repeat(33));
+        let redeem_script = hex::decode(format!("52{}52ae", key.repeat(2))).unwrap();
+        assert_eq!(count_sigops(&redeem_script, true), 2);
+        assert_eq!(count_sigops(&redeem_script, false), 20);
+        assert_eq!(count_sigops(&[0xac, 0x4c], true), 1);
+
+        let p2sh = ScriptPubkey::p2sh(&Script::new(redeem_script.clone()));
+        let mut script_sig = vec![0x00];
+        crate::types::script::push_data(&mut script_sig, &redeem_script);
+        let tx = tx(
+            vec![script_sig],
+            vec![TxOut::new(
+                10_000,
+                spk("76a914000000000000000000000000000000000000000088ac"),
+            )],
+        );
+
+        let policy = Policy::default();
+        assert_eq!(policy.sigops_cost(&tx, &HashMap::new()), 4);
+        let mut prevouts = HashMap::new();
+        prevouts.insert(tx.inputs()[0].outpoint, TxOut::new(20_000, p2sh));
+        assert_eq!(policy.sigops_cost(&tx, &prevouts), 12);
+        assert_eq!(
+            policy.max_sigops_cost(11).check(&tx, &prevouts),
+            vec![PolicyViolation::SigopsCost {
+                cost: 12,
+                limit: 11
+            }]
+        );
+    }
+
+    #[test]
+    fn it_checks_finality_against_the_chain() {
+        use crate::{nets::ChainParams, types::BlockHeader};
+
+        let mine = |prev: BlockHash, time: u32| {
+            (0..)
+                .map(|nonce| {
+                    BlockHeader::new(4, prev, Default::default(), time, 0x207f_ffff, nonce)
+                })
+                .find(|header| header.validate_pow().is_ok())
+                .unwrap()
+        };
+        let genesis = mine(BlockHash::default(), 1000);
+        let mut chain =
+            HeaderChain::from_checkpoint(ChainParams::regtest(), genesis, 0, Default::default());
+        let mut hashes = vec![genesis.block_hash()];
+        for i in 1..=20 {
+            let header = mine(hashes[i - 1], 1000 + 600 * i as u32);
+            chain.append(header).unwrap();
+            hashes.push(header.block_hash());
+        }
+        // Tip at height 20, with median time past 10000. Block 10 starts relative locks at 4000,
+        // the median time past of block 9
+        assert_eq!(chain.tip_median_time_past(), 10_000);
+        assert_eq!(chain.relative_lock_start(&hashes[10]), Some(4000));
+
+        let txid = TXID::deserialize_hex(&"ab".repeat(32)).unwrap();
+        let spend = |sequences: &[Sequence], locktime: u32| {
+            let vin: Vec<_> = sequences
+                .iter()
+                .enumerate()
+                .map(|(i, s)| {
+                    let outpoint = BitcoinOutpoint::new(txid, i as u32);
+                    BitcoinTxIn::new(outpoint, vec![], s.to_consensus_u32())
+                })
+                .collect();
+            LegacyTx::new(2, vin, vec![TxOut::op_return(&[])], locktime).unwrap()
+        };
+        let confirmations: HashMap<_, _> = (0..3)
+            .map(|i| (BitcoinOutpoint::new(txid, i), hashes[10]))
+            .collect();
+        let policy = Policy::default();
+
+        // Heights compare against the next block, timestamps against the median time past
+        let nonfinal = Sequence::ENABLE_LOCKTIME_NO_RBF;
+        assert!(policy
+            .check_final(&spend(&[nonfinal], 20), &chain, &confirmations)
+            .is_empty());
+        assert_eq!(
+            policy.check_final(&spend(&[nonfinal], 21), &chain, &confirmations),
+            vec![PolicyViolation::NonFinalLocktime(21)]
+        );
+        assert_eq!(
+            policy.check_final(&spend(&[nonfinal], 10_000), &chain, &HashMap::new()),
+            vec![PolicyViolation::NonFinalLocktime(10_000)]
+        );
+        assert!(policy
+            .check_final(&spend(&[Sequence::FINAL], 10_000), &chain, &HashMap::new())
+            .is_empty());
+
+        // Confirmed at 10, so 11 blocks are satisfied in the next block, and 11 intervals
+        // (5632 seconds) are satisfied by 10000 - 4000 = 6000 seconds.
+        let sequences = [
+            Sequence::from_blocks(11),
+            Sequence::from_blocks(12),
+            Sequence::from_512_second_intervals(11),
+            Sequence::from_512_second_intervals(12),
+            Sequence::from_blocks(1),
+            Sequence::from_blocks(0),
+        ];
+        assert_eq!(
+            policy.check_final(&spend(&sequences, 0), &chain, &confirmations),
+            vec![
+                PolicyViolation::RelativeLock(1),
+                PolicyViolation::RelativeLock(3),
+                PolicyViolation::RelativeLock(4),
+            ]
+        );
+    }
+}
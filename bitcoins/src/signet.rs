@@ -0,0 +1,598 @@
+//! Custom signet parameters and block solutions.
+//!
+//! A signet is a test network whose blocks must be signed. Each signet is defined by its
+//! challenge script. The network magic is derived from the challenge, so tools can identify a
+//! custom signet from the challenge alone. Address and extended key encoding are shared by all
+//! signets, and use the `SignetEncoder`.
+//!
+//! A block's signature, its "solution", is a script sig and witness stored in the coinbase
+//! witness commitment output, in a push prefixed with `SIGNET_HEADER`. The solution spends a
+//! virtual `to_spend` transaction paying to the challenge, and committing to the block header
+//! with the solution removed. This follows BIP325.
+//!
+//! Bare multisig (as used by the default signet), P2PK, P2WPKH, and `OP_TRUE` challenges can be
+//! verified. Other challenges, including P2WSH, are not yet supported.
+//!
+//! ```
+//! use bitcoins::signet::SignetParams;
+//!
+//! let params = SignetParams::default();
+//! assert_eq!(params.magic(), [0x0a, 0x03, 0xcf, 0x40]);
+//! ```
+
+use coins_core::{
+    hashes::{Digest, Hash256, Hash256Digest},
+    ser::{self, ByteFormat, SerError},
+    types::tx::Transaction,
+};
+use thiserror::Error;
+
+use crate::{
+    types::{
+        block::{merkle_root, Block},
+        legacy::LegacyTx,
+        opcodes::Instructions,
+        script::{push_data, ScriptPubkey, ScriptSig, ScriptType, Witness, WitnessStackItem},
+        tx::{BitcoinTransaction, BitcoinTx, TxError},
+        witness::WitnessTx,
+    },
+    virtual_tx::{self, SpendError},
+};
+
+/// The challenge script of the default signet. A 1-of-2 bare multisig.
+pub const DEFAULT_SIGNET_CHALLENGE: &str = "512103ad5e0edad18cb1f0fc0d28a3d4f1f3e445640337489abb10404f2d1e086be430210359ef5021964fe22d6f8e05b2463c9540ce96883fe3b278760f048f5189f2e6c452ae";
+
+/// The prefix of the signet solution push in the witness commitment output
+pub const SIGNET_HEADER: [u8; 4] = [0xec, 0xc7, 0xda, 0xa2];
+
+/// The prefix of the witness commitment output script. OP_RETURN, PUSH_36, and the commitment
+/// header.
+const WITNESS_COMMITMENT_PREFIX: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+
+/// An error verifying a signet block.
+#[derive(Debug, Error)]
+pub enum SignetError {
+    /// The block has no transactions
+    #[error("Block has no coinbase transaction")]
+    NoCoinbase,
+
+    /// The coinbase has no witness commitment output
+    #[error("Coinbase has no witness commitment output")]
+    NoWitnessCommitment,
+
+    /// The solution could not be parsed, or does not have the shape the challenge requires
+    #[error("Malformed signet solution")]
+    MalformedSolution,
+
+    /// Solutions for this challenge type are not supported
+    #[error("Signet challenges of this type are not supported")]
+    UnsupportedChallenge,
+
+    /// The solution does not satisfy the challenge
+    #[error("Signet solution does not satisfy the challenge")]
+    InvalidSolution,
+
+    /// Bubbled up from building or hashing the virtual transactions
+    #[error(transparent)]
+    TxError(#[from] TxError),
+}
+
+/// The parameters of a signet. Its challenge script, and the network magic derived from it.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SignetParams {
+    challenge: ScriptPubkey,
+}
+
+impl Default for SignetParams {
+    /// The parameters of the default signet
+    fn default() -> Self {
+        Self::new(ScriptPubkey::from_hex(DEFAULT_SIGNET_CHALLENGE).expect("valid hex"))
+    }
+}
+
+impl SignetParams {
+    /// Instantiate the parameters of a custom signet from its challenge script
+    pub fn new(challenge: ScriptPubkey) -> Self {
+        Self { challenge }
+    }
+
+    /// The challenge script. Block solutions must satisfy it.
+    pub fn challenge(&self) -> &ScriptPubkey {
+        &self.challenge
+    }
+
+    /// The network magic. This is the first 4 bytes of the Hash256 of the length-prefixed
+    /// challenge.
+    pub fn magic(&self) -> [u8; 4] {
+        let mut w = Hash256::default();
+        self.challenge
+            .write_to(&mut w)
+            .expect("No IOError from hash functions");
+        let mut magic = [0u8; 4];
+        magic.copy_from_slice(&w.finalize()[..4]);
+        magic
+    }
+
+    /// Build the unsigned `to_sign` transaction for a block. Signers sign its only input, spending
+    /// the challenge, and insert the resulting solution with `insert_solution`. Any existing
+    /// solution is ignored.
+    pub fn to_sign(&self, block: &Block) -> Result<WitnessTx, SignetError> {
+        // The solution commits to the block with an empty solution in place
+        let coinbase = insert_solution(block, &SignetSolution::default())?;
+        let mut txns = block.txns().to_vec();
+        txns[0] = coinbase;
+        let unsigned = Block::new(*block.header(), txns);
+        let (_, signet_merkle) = extract_solution(&unsigned)?;
+        Ok(self.to_sign_with_merkle(block, signet_merkle))
+    }
+
+    fn to_sign_with_merkle(&self, block: &Block, signet_merkle: Hash256Digest) -> WitnessTx {
+        to_sign(&to_spend(
+            &self.challenge,
+            &signet_block_data(block, signet_merkle),
+        ))
+    }
+
+    /// Verify the block's signet solution against the challenge. The genesis block has no
+    /// solution, and callers should skip it.
+    pub fn verify_block(&self, block: &Block) -> Result<(), SignetError> {
+        let (solution, signet_merkle) = extract_solution(block)?;
+        let mut tx = self.to_sign_with_merkle(block, signet_merkle);
+        let solution = solution.unwrap_or_default();
+        tx.legacy_tx.vin[0].script_sig = solution.script_sig;
+        tx.witnesses[0] = solution.witness;
+        verify_spend(&self.challenge, &tx)
+    }
+}
+
+/// A signet block solution. The script sig and witness spending the challenge.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SignetSolution {
+    /// The script sig
+    pub script_sig: ScriptSig,
+    /// The witness
+    pub witness: Witness,
+}
+
+impl SignetSolution {
+    /// Deserialize a solution, erroring if any length prefix exceeds `limit`. Solutions are read
+    /// from untrusted blocks, so callers should pass the length of the solution data.
+    pub fn read_limited<R>(reader: &mut R, limit: usize) -> ser::SerResult<Self>
+    where
+        R: std::io::Read,
+    {
+        let script_sig = ser::read_prefix_vec_limited(reader, limit)?.into();
+        let items = ser::read_prefix_len(reader, limit)?;
+        let witness = (0..items)
+            .map(|_| ser::read_prefix_vec_limited(reader, limit).map(WitnessStackItem::from))
+            .collect::<ser::SerResult<_>>()?;
+        Ok(Self {
+            script_sig,
+            witness,
+        })
+    }
+}
+
+impl ByteFormat for SignetSolution {
+    type Error = SerError;
+
+    fn serialized_length(&self) -> usize {
+        let mut len = self.script_sig.serialized_length();
+        len += ser::prefix_byte_len(self.witness.len() as u64) as usize;
+        len += self
+            .witness
+            .iter()
+            .map(|w| w.serialized_length())
+            .sum::<usize>();
+        len
+    }
+
+    fn read_from<R>(reader: &mut R) -> Result<Self, Self::Error>
+    where
+        R: std::io::Read,
+        Self: std::marker::Sized,
+    {
+        Self::read_limited(reader, usize::MAX)
+    }
+
+    fn write_to<W>(&self, writer: &mut W) -> Result<usize, Self::Error>
+    where
+        W: std::io::Write,
+    {
+        let mut len = self.script_sig.write_to(writer)?;
+        len += ser::write_prefix_vec(writer, &self.witness)?;
+        Ok(len)
+    }
+}
+
+/// Find the index of the coinbase witness commitment output. This is the last output whose
+/// script starts with the commitment prefix.
+fn witness_commitment_index(coinbase: &BitcoinTx) -> Option<usize> {
+    coinbase.outputs().iter().rposition(|output| {
+        output
+            .script_pubkey
+            .items()
+            .starts_with(&WITNESS_COMMITMENT_PREFIX)
+    })
+}
+
+/// Find the solution in a witness commitment script. This is the data after `SIGNET_HEADER` in
+/// the first push that starts with it and carries data. Returns the script with that push
+/// replaced by `replacement`, and the solution bytes, or `None` if the script has no solution.
+fn replace_solution(script: &[u8], replacement: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    let mut rewritten = vec![];
+    let mut solution = None;
+    for ins in Instructions::new(script) {
+        let ins = ins.ok()?;
+        match ins.data {
+            Some(data) if !data.is_empty() => {
+                if solution.is_none()
+                    && data.len() > SIGNET_HEADER.len()
+                    && data.starts_with(&SIGNET_HEADER)
+                {
+                    solution = Some(data[SIGNET_HEADER.len()..].to_vec());
+                    push_data(&mut rewritten, replacement);
+                } else {
+                    push_data(&mut rewritten, data);
+                }
+            }
+            _ => rewritten.push(ins.opcode.to_u8()),
+        }
+    }
+    solution.map(|solution| (rewritten, solution))
+}
+
+/// Replace the coinbase output script at `index`
+fn set_output_script(tx: &mut BitcoinTx, index: usize, script_pubkey: ScriptPubkey) {
+    match tx {
+        BitcoinTx::Legacy(tx) => tx.vout[index].script_pubkey = script_pubkey,
+        BitcoinTx::Witness(tx) => tx.legacy_tx.vout[index].script_pubkey = script_pubkey,
+    }
+}
+
+/// Extract the signet solution from a block. Returns the solution, if any, and the signet
+/// merkle root. This is the merkle root of the block's txids, with the solution removed from
+/// the coinbase. Errors if the block has no witness commitment, or the solution is malformed.
+pub fn extract_solution(
+    block: &Block,
+) -> Result<(Option<SignetSolution>, Hash256Digest), SignetError> {
+    let coinbase = block.coinbase().ok_or(SignetError::NoCoinbase)?;
+    let index = witness_commitment_index(coinbase).ok_or(SignetError::NoWitnessCommitment)?;
+
+    let mut coinbase = coinbase.clone();
+    let script = coinbase.outputs()[index].script_pubkey.items().to_vec();
+    // The solution commits to the coinbase with the solution truncated to its header
+    let solution = match replace_solution(&script, &SIGNET_HEADER) {
+        Some((replacement, data)) => {
+            set_output_script(&mut coinbase, index, replacement.into());
+            let mut cursor = std::io::Cursor::new(&data);
+            let solution = SignetSolution::read_limited(&mut cursor, data.len())
+                .map_err(|_| SignetError::MalformedSolution)?;
+            if cursor.position() as usize != data.len() {
+                return Err(SignetError::MalformedSolution);
+            }
+            Some(solution)
+        }
+        None => None,
+    };
+
+    let mut txids = block.txids();
+    txids[0] = coinbase.txid();
+    Ok((solution, merkle_root(&txids)))
+}
+
+/// Insert a solution into the block's witness commitment output, replacing any existing
+/// solution. The header's merkle root is not updated. Returns the new coinbase.
+pub fn insert_solution(block: &Block, solution: &SignetSolution) -> Result<BitcoinTx, SignetError> {
+    let coinbase = block.coinbase().ok_or(SignetError::NoCoinbase)?;
+    let index = witness_commitment_index(coinbase).ok_or(SignetError::NoWitnessCommitment)?;
+
+    let mut data = SIGNET_HEADER.to_vec();
+    solution.write_to(&mut data).expect("No IOError from Vec");
+
+    let script = coinbase.outputs()[index].script_pubkey.items();
+    let script = match replace_solution(script, &data) {
+        Some((rewritten, _)) => rewritten,
+        None => {
+            let mut script = script.to_vec();
+            push_data(&mut script, &data);
+            script
+        }
+    };
+
+    let mut coinbase = coinbase.clone();
+    set_output_script(&mut coinbase, index, script.into());
+    Ok(coinbase)
+}
+
+/// The header data committed to by the solution. The version, previous block hash, signet
+/// merkle root, and time.
+pub fn signet_block_data(block: &Block, signet_merkle: Hash256Digest) -> Vec<u8> {
+    let header = block.header();
+    let mut data = vec![];
+    data.extend(&header.version().to_le_bytes());
+    header
+        .prev_block()
+        .write_to(&mut data)
+        .expect("No IOError from Vec");
+    signet_merkle
+        .write_to(&mut data)
+        .expect("No IOError from Vec");
+    data.extend(&header.time().to_le_bytes());
+    data
+}
+
+/// Build the virtual `to_spend` transaction. Its only output pays to the challenge, and its
+/// only input commits to the block data.
+pub fn to_spend(challenge: &ScriptPubkey, block_data: &[u8]) -> LegacyTx {
+    virtual_tx::to_spend(challenge, block_data)
+}
+
+/// Build the unsigned virtual `to_sign` transaction, spending the output of `to_spend`. The
+/// solution is its signed input's script sig and witness.
+pub fn to_sign(to_spend: &LegacyTx) -> WitnessTx {
+    virtual_tx::to_sign(to_spend)
+}
+
+/// Verify that the `to_sign` input satisfies the challenge
+fn verify_spend(challenge: &ScriptPubkey, tx: &WitnessTx) -> Result<(), SignetError> {
+    let script_sig = &tx.inputs()[0].script_sig;
+    let witness = &tx.witnesses()[0];
+    let valid = match challenge.standard_type() {
+        ScriptType::Multisig { m, pubkeys } => {
+            if !witness.is_empty() {
+                return Err(SignetError::MalformedSolution);
+            }
+            let items = virtual_tx::pushes(script_sig).ok_or(SignetError::MalformedSolution)?;
+            // CHECKMULTISIG consumes an extra item, which must be empty
+            match items.split_first() {
+                Some((dummy, sigs)) if dummy.is_empty() && sigs.len() == m => {
+                    // Signatures must match keys in script order
+                    let mut keys = pubkeys.iter();
+                    sigs.iter().all(|sig| {
+                        keys.any(|key| {
+                            virtual_tx::verify_legacy_sig(tx, challenge, sig, key).is_ok()
+                        })
+                    })
+                }
+                _ => return Err(SignetError::MalformedSolution),
+            }
+        }
+        ScriptType::Pk(key) => {
+            if !witness.is_empty() {
+                return Err(SignetError::MalformedSolution);
+            }
+            match virtual_tx::pushes(script_sig).as_deref() {
+                Some([sig]) => virtual_tx::verify_legacy_sig(tx, challenge, sig, &key).is_ok(),
+                _ => return Err(SignetError::MalformedSolution),
+            }
+        }
+        ScriptType::Wpkh(hash) => {
+            if !script_sig.is_empty() {
+                return Err(SignetError::MalformedSolution);
+            }
+            match virtual_tx::verify_wpkh(tx, &hash) {
+                Ok(()) => true,
+                Err(SpendError::Malformed) => return Err(SignetError::MalformedSolution),
+                Err(_) => false,
+            }
+        }
+        ScriptType::NonStandard if challenge.items() == [0x51] => true, // OP_TRUE
+        _ => return Err(SignetError::UnsupportedChallenge),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(SignetError::InvalidSolution)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use coins_bip32::prelude::*;
+
+    use crate::types::{
+        block::BlockHeader,
+        legacy::LegacySighashArgs,
+        script::Script,
+        tx::Sighash,
+        txin::{BitcoinOutpoint, BitcoinTxIn},
+        txout::TxOut,
+        witness::{WitnessSighashArgs, WitnessTransaction},
+    };
+    use coins_core::hashes::Hash256;
+
+    fn unsigned_block() -> Block {
+        let mut commitment = WITNESS_COMMITMENT_PREFIX.to_vec();
+        commitment.extend(&[0u8; 32]);
+        let coinbase = LegacyTx::new(
+            2,
+            vec![BitcoinTxIn::new(
+                BitcoinOutpoint::null(),
+                ScriptSig::from(vec![0x01, 0x01]),
+                0xffff_ffff,
+            )],
+            vec![
+                TxOut::new(50_0000_0000, ScriptPubkey::from(vec![0x51])),
+                TxOut::new(0, ScriptPubkey::from(commitment)),
+            ],
+            0,
+        )
+        .unwrap();
+        let header = BlockHeader::new(
+            0x2000_0000,
+            Default::default(),
+            Default::default(),
+            1_598_918_400,
+            0x1e03_77ae,
+            0,
+        );
+        Block::new(header, vec![coinbase.into()])
+    }
+
+    /// Sign the block's `to_sign` input, and insert the solution
+    fn sign_block(params: &SignetParams, block: &Block, key: &DerivedXPriv) -> Block {
+        let pubkey = key.verify_key();
+        let to_sign = params.to_sign(block).unwrap();
+        let mut hasher = Hash256::default();
+        let mut solution = SignetSolution::default();
+        let sig = |signature: Signature| {
+            let mut sig = signature.to_der().as_bytes().to_vec();
+            sig.push(Sighash::All as u8);
+            sig
+        };
+        match params.challenge().standard_type() {
+            ScriptType::Wpkh(_) => {
+                let args = WitnessSighashArgs::wpkh(0, Sighash::All, &pubkey, 0);
+                to_sign
+                    .write_witness_sighash_preimage(&mut hasher, &args)
+                    .unwrap();
+                solution.witness = vec![
+                    sig(key.sign_digest(hasher)).into(),
+                    pubkey.to_bytes().to_vec().into(),
+                ];
+            }
+            _ => {
+                let args = LegacySighashArgs {
+                    index: 0,
+                    sighash_flag: Sighash::All,
+                    prevout_script: params.challenge().into(),
+                };
+                to_sign
+                    .write_legacy_sighash_preimage(&mut hasher, &args)
+                    .unwrap();
+                let mut script_sig = vec![0x00]; // CHECKMULTISIG dummy
+                push_data(&mut script_sig, &sig(key.sign_digest(hasher)));
+                solution.script_sig = script_sig.into();
+            }
+        }
+        let mut txns = block.txns().to_vec();
+        txns[0] = insert_solution(block, &solution).unwrap();
+        let signed = Block::new(*block.header(), txns.clone());
+        assert_eq!(extract_solution(&signed).unwrap().0, Some(solution));
+        // The signet merkle root excludes the solution, so updating the header keeps it valid
+        let root = signed.compute_merkle_root();
+        let header = block.header();
+        let header = BlockHeader::new(
+            header.version(),
+            header.prev_block(),
+            root,
+            header.time(),
+            header.bits(),
+            header.nonce(),
+        );
+        Block::new(header, txns)
+    }
+
+    #[test]
+    fn it_derives_network_magic() {
+        assert_eq!(SignetParams::default().magic(), [0x0a, 0x03, 0xcf, 0x40]);
+        let custom = SignetParams::new(ScriptPubkey::from(vec![0x51]));
+        assert_ne!(custom.magic(), SignetParams::default().magic());
+
+        let json = serde_json::to_string(&custom).unwrap();
+        assert_eq!(serde_json::from_str::<SignetParams>(&json).unwrap(), custom);
+    }
+
+    #[test]
+    fn it_verifies_signed_blocks() {
+        let root = DerivedXPriv::root_from_seed(&[7u8; 32], Some(Hint::SegWit)).unwrap();
+        let key = root.derive_child(0).unwrap();
+        let other = root.derive_child(1).unwrap();
+        let pubkey = key.verify_key();
+
+        let multisig = Script::sorted_multisig(1, &[pubkey.clone(), other.verify_key()]).unwrap();
+        let challenges = [
+            ScriptPubkey::from(multisig.items().to_vec()),
+            ScriptPubkey::p2wpkh(&pubkey),
+        ];
+        for challenge in challenges.iter() {
+            let params = SignetParams::new(challenge.clone());
+            let unsigned = unsigned_block();
+            assert!(params.verify_block(&unsigned).is_err());
+
+            let block = sign_block(&params, &unsigned, &key);
+            params.verify_block(&block).unwrap();
+            assert!(block.check_merkle_root());
+
+            // Re-signing replaces the solution
+            let resigned = sign_block(&params, &block, &key);
+            assert_eq!(resigned.txns()[0].outputs().len(), 2);
+            params.verify_block(&resigned).unwrap();
+
+            // The solution commits to the header
+            let header = block.header();
+            let tampered = BlockHeader::new(
+                header.version(),
+                header.prev_block(),
+                header.merkle_root(),
+                header.time() + 1,
+                header.bits(),
+                header.nonce(),
+            );
+            assert!(matches!(
+                params.verify_block(&Block::new(tampered, block.txns().to_vec())),
+                Err(SignetError::InvalidSolution)
+            ));
+
+            // A solution from a key not in the challenge
+            let wrong_key = sign_block(&params, &unsigned, &root.derive_child(2).unwrap());
+            assert!(matches!(
+                params.verify_block(&wrong_key),
+                Err(SignetError::InvalidSolution)
+            ));
+        }
+    }
+
+    #[test]
+    fn it_rejects_malformed_solution_lengths() {
+        let huge = [0xff, 0, 0, 0, 0, 0, 1, 0, 0];
+        // A script sig, a witness item count, and a witness item each claiming 2^40 bytes
+        let cases = [vec![], vec![0x00], vec![0x00, 0x01]];
+        for prefix in cases.iter() {
+            let mut data = SIGNET_HEADER.to_vec();
+            data.extend(prefix);
+            data.extend(&huge);
+
+            let block = unsigned_block();
+            let mut txns = block.txns().to_vec();
+            if let BitcoinTx::Legacy(tx) = &mut txns[0] {
+                let mut script = tx.vout[1].script_pubkey.items().to_vec();
+                push_data(&mut script, &data);
+                tx.vout[1].script_pubkey = script.into();
+            }
+            assert!(matches!(
+                extract_solution(&Block::new(*block.header(), txns)),
+                Err(SignetError::MalformedSolution)
+            ));
+        }
+    }
+
+    #[test]
+    fn it_handles_trivial_and_unsupported_challenges() {
+        let block = unsigned_block();
+        let op_true = SignetParams::new(ScriptPubkey::from(vec![0x51]));
+        assert_eq!(extract_solution(&block).unwrap().0, None);
+        op_true.verify_block(&block).unwrap();
+
+        let unsupported = SignetParams::new(ScriptPubkey::p2wsh(&Script::from(vec![0x51])));
+        assert!(matches!(
+            unsupported.verify_block(&block),
+            Err(SignetError::UnsupportedChallenge)
+        ));
+
+        let mut txns = block.txns().to_vec();
+        if let BitcoinTx::Legacy(tx) = &mut txns[0] {
+            tx.vout.pop();
+        }
+        assert!(matches!(
+            op_true.verify_block(&Block::new(*block.header(), txns)),
+            Err(SignetError::NoWitnessCommitment)
+        ));
+        assert!(matches!(
+            op_true.verify_block(&Block::new(*block.header(), vec![])),
+            Err(SignetError::NoCoinbase)
+        ));
+    }
+}
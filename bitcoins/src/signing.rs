@@ -0,0 +1,416 @@
+//! Signing via raw sighash digests.
+//!
+//! Some signers, such as HSMs, sign only raw 32-byte digests and know nothing of Bitcoin
+//! transactions. A `SigningSession` exports, for each input of an unsigned transaction, the
+//! digest to sign along with its sighash flag, key derivation, and script code. The signatures
+//! are accepted back by input index, and assembled into the script sigs and witnesses of the
+//! signed transaction.
+//!
+//! P2PKH, P2WPKH, and P2SH-nested P2WPKH prevouts are supported. Nested P2WPKH prevouts must have
+//! a `Known` spend script holding their redeem script.
+
+use coins_bip32::{
+    ecdsa::{Signature, VerifyingKey},
+    path::KeyDerivation,
+};
+use coins_core::{
+    hashes::{Digest, Hash160, Hash160Digest, Hash256Digest},
+    types::tx::Transaction,
+};
+use thiserror::Error;
+
+use crate::types::{
+    legacy::LegacySighashArgs,
+    script::{
+        push_data, NestedWitnessProgram, Script, ScriptPubkey, ScriptSig, ScriptType, Witness,
+    },
+    tx::{BitcoinTransaction, BitcoinTx, Sighash, TxError},
+    utxo::{SpendScript, Utxo},
+    witness::{SighashCache, WitnessSighashArgs, WitnessTransaction, WitnessTx},
+};
+
+/// An error in assembling an externally-signed transaction.
+#[derive(Debug, Error)]
+pub enum SigningError {
+    /// The number of prevouts does not match the number of inputs
+    #[error("Expected {expected} prevouts. Got {got}")]
+    PrevoutCount {
+        /// The number of inputs
+        expected: usize,
+        /// The number of prevouts
+        got: usize,
+    },
+
+    /// The input's prevout is not a supported script type, or its redeem script is unknown
+    #[error("Input {} spends an unsupported prevout", .0)]
+    UnsupportedInput(usize),
+
+    /// There is no input at the index
+    #[error("No input at index {}", .0)]
+    BadInputIndex(usize),
+
+    /// The public key does not hash to the input's prevout
+    #[error("Public key does not match the prevout of input {}", .0)]
+    WrongKey(usize),
+
+    /// The input has not been signed
+    #[error("Input {} is not signed", .0)]
+    MissingSignature(usize),
+
+    /// Bubbled up from the transaction
+    #[error(transparent)]
+    TxError(#[from] TxError),
+}
+
+/// The information an external signer needs to sign one input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigningRequest {
+    /// The index of the input
+    pub index: usize,
+    /// The sighash digest to sign
+    pub digest: Hash256Digest,
+    /// The sighash flag committed to by the digest. Its byte is appended to the signature
+    pub sighash_flag: Sighash,
+    /// The derivation of the signing key, if known
+    pub derivation: Option<KeyDerivation>,
+    /// The script code committed to by the digest
+    pub script_code: Script,
+}
+
+/// How an input is spent, and the pubkey hash its signing key must match
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SpendKind {
+    Pkh(Hash160Digest),
+    Wpkh(Hash160Digest),
+    ShWpkh(Hash160Digest, Script),
+}
+
+impl SpendKind {
+    fn from_utxo(utxo: &Utxo) -> Option<Self> {
+        match utxo.standard_type() {
+            ScriptType::Pkh(hash) => Some(SpendKind::Pkh(hash)),
+            ScriptType::Wpkh(hash) => Some(SpendKind::Wpkh(hash)),
+            ScriptType::Sh(_) => match utxo.spend_script() {
+                SpendScript::Known(redeem_script) => {
+                    let spk = ScriptPubkey::from(redeem_script.items().to_vec());
+                    match spk.standard_type() {
+                        ScriptType::Wpkh(hash) => {
+                            Some(SpendKind::ShWpkh(hash, redeem_script.clone()))
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn pubkey_hash(&self) -> &Hash160Digest {
+        match self {
+            SpendKind::Pkh(hash) | SpendKind::Wpkh(hash) | SpendKind::ShWpkh(hash, _) => hash,
+        }
+    }
+}
+
+/// An unsigned transaction, and the signatures collected for it so far.
+#[derive(Debug, Clone)]
+pub struct SigningSession {
+    tx: WitnessTx,
+    kinds: Vec<SpendKind>,
+    requests: Vec<SigningRequest>,
+    signatures: Vec<Option<(Signature, VerifyingKey)>>,
+}
+
+impl SigningSession {
+    /// Prepare to sign every input of `tx` with `sighash_flag`. `prevouts` holds the UTXO spent
+    /// by each input, in order. `derivations` holds the derivation of each input's signing key,
+    /// in order, if known. It may be shorter than the input vector.
+    pub fn new(
+        tx: &BitcoinTx,
+        prevouts: &[Utxo],
+        derivations: &[Option<KeyDerivation>],
+        sighash_flag: Sighash,
+    ) -> Result<Self, SigningError> {
+        let tx = match tx {
+            BitcoinTx::Witness(tx) => tx.clone(),
+            BitcoinTx::Legacy(tx) => WitnessTx::from_legacy(tx.clone()),
+        };
+        if prevouts.len() != tx.inputs().len() {
+            return Err(SigningError::PrevoutCount {
+                expected: tx.inputs().len(),
+                got: prevouts.len(),
+            });
+        }
+
+        let cache = SighashCache::new(&tx);
+        let mut kinds = vec![];
+        let mut requests = vec![];
+        for (index, utxo) in prevouts.iter().enumerate() {
+            let kind = SpendKind::from_utxo(utxo).ok_or(SigningError::UnsupportedInput(index))?;
+            let (digest, script_code) = match &kind {
+                SpendKind::Pkh(_) => {
+                    let prevout_script: Script = utxo.script_pubkey().into();
+                    let args = LegacySighashArgs {
+                        index,
+                        sighash_flag,
+                        prevout_script: prevout_script.clone(),
+                    };
+                    (tx.legacy_sighash(&args)?, prevout_script)
+                }
+                SpendKind::Wpkh(hash) | SpendKind::ShWpkh(hash, _) => {
+                    let prevout_script = Script::from(&ScriptPubkey::p2pkh_from_hash(hash));
+                    let args = WitnessSighashArgs {
+                        index,
+                        sighash_flag,
+                        prevout_script: prevout_script.clone(),
+                        prevout_value: utxo.value,
+                    };
+                    (cache.witness_sighash(&args)?, prevout_script)
+                }
+            };
+            requests.push(SigningRequest {
+                index,
+                digest: digest.into(),
+                sighash_flag,
+                derivation: derivations.get(index).cloned().flatten(),
+                script_code,
+            });
+            kinds.push(kind);
+        }
+
+        Ok(Self {
+            signatures: vec![None; requests.len()],
+            tx,
+            kinds,
+            requests,
+        })
+    }
+
+    /// The signing requests, one per input, in order
+    pub fn requests(&self) -> &[SigningRequest] {
+        &self.requests
+    }
+
+    /// Add the signature for an input's digest, and the public key that produced it. The key is
+    /// checked against the prevout, but the signature is not verified. High-S signatures are
+    /// normalized. Replaces any signature previously added for the input.
+    pub fn insert_signature(
+        &mut self,
+        index: usize,
+        signature: &Signature,
+        pubkey: &VerifyingKey,
+    ) -> Result<(), SigningError> {
+        let kind = self
+            .kinds
+            .get(index)
+            .ok_or(SigningError::BadInputIndex(index))?;
+        let hash: Hash160Digest = Hash160::digest(&pubkey.to_bytes()).into();
+        if &hash != kind.pubkey_hash() {
+            return Err(SigningError::WrongKey(index));
+        }
+
+        let mut signature = *signature;
+        // Only fails for an invalid `s`, which the signature's constructor forbids
+        let _ = signature.normalize_s();
+        self.signatures[index] = Some((signature, *pubkey));
+        Ok(())
+    }
+
+    /// True if every input has a signature
+    pub fn is_complete(&self) -> bool {
+        self.signatures.iter().all(Option::is_some)
+    }
+
+    /// Assemble the signed transaction. This is a witness transaction if any input spends a
+    /// witness prevout, and a legacy transaction otherwise.
+    pub fn finalize(self) -> Result<BitcoinTx, SigningError> {
+        let mut tx = self.tx;
+        for (index, (kind, signature)) in self.kinds.iter().zip(self.signatures.iter()).enumerate()
+        {
+            let (signature, pubkey) = signature.ok_or(SigningError::MissingSignature(index))?;
+            let mut sig = signature.to_der().as_bytes().to_vec();
+            sig.push(self.requests[index].sighash_flag as u8);
+            let pubkey = pubkey.to_bytes().to_vec();
+
+            let witness: Witness = vec![sig.clone().into(), pubkey.clone().into()];
+            let (script_sig, witness) = match kind {
+                SpendKind::Pkh(_) => {
+                    let mut script_sig = vec![];
+                    push_data(&mut script_sig, &sig);
+                    push_data(&mut script_sig, &pubkey);
+                    (script_sig.into(), Witness::default())
+                }
+                SpendKind::Wpkh(_) => (ScriptSig::null(), witness),
+                SpendKind::ShWpkh(_, redeem_script) => {
+                    let nested = NestedWitnessProgram::from_witness_program(redeem_script.clone());
+                    (nested.script_sig, witness)
+                }
+            };
+            tx.legacy_tx.vin[index].script_sig = script_sig;
+            tx.witnesses[index] = witness;
+        }
+
+        if tx.witnesses.iter().all(Vec::is_empty) {
+            Ok(BitcoinTx::Legacy(tx.into_legacy()))
+        } else {
+            Ok(BitcoinTx::Witness(tx))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        builder::BitcoinTxBuilder,
+        enc::MainnetEncoder,
+        types::{BitcoinOutpoint, ScriptPubkey},
+    };
+    use coins_bip32::{prelude::*, BIP32_HARDEN};
+    use coins_core::{builder::TxBuilder, hashes::Hash256};
+
+    #[test]
+    fn it_signs_raw_digests() {
+        let root = DerivedXPriv::root_from_seed(&[7u8; 32], Some(Hint::SegWit)).unwrap();
+        let keys: Vec<DerivedXPriv> = (0..3)
+            .map(|i| {
+                root.derive_path(&[84 | BIP32_HARDEN, BIP32_HARDEN, BIP32_HARDEN, 0, i][..])
+                    .unwrap()
+            })
+            .collect();
+        let pubkeys: Vec<VerifyingKey> = keys
+            .iter()
+            .map(|k| *AsRef::<VerifyingKey>::as_ref(&k.verify_key()))
+            .collect();
+
+        let wpkh = ScriptPubkey::p2wpkh(&keys[0].verify_key());
+        let pkh = ScriptPubkey::p2pkh(&keys[1].verify_key());
+        let redeem_script: Script = ScriptPubkey::p2wpkh(&keys[2].verify_key())
+            .items()
+            .to_vec()
+            .into();
+        let sh = ScriptPubkey::p2sh(&redeem_script);
+
+        let outpoint = |idx| BitcoinOutpoint::new(Default::default(), idx);
+        let prevouts = vec![
+            Utxo::new(outpoint(0), 10_000, wpkh.clone(), SpendScript::None),
+            Utxo::new(outpoint(1), 20_000, pkh, SpendScript::None),
+            Utxo::new(
+                outpoint(2),
+                30_000,
+                sh,
+                SpendScript::Known(redeem_script.clone()),
+            ),
+        ];
+        let tx = prevouts
+            .iter()
+            .fold(BitcoinTxBuilder::<MainnetEncoder>::new(), |b, u| {
+                b.spend(u.outpoint, 0xffff_fffd)
+            })
+            .pay_script_pubkey(59_000, wpkh)
+            .build()
+            .unwrap();
+
+        let derivations: Vec<_> = keys.iter().map(|k| Some(k.derivation().clone())).collect();
+        let mut session = SigningSession::new(&tx, &prevouts, &derivations, Sighash::All).unwrap();
+        assert_eq!(session.requests().len(), 3);
+        assert_eq!(
+            Some(session.requests()[0].script_code.clone()),
+            prevouts[0].signing_script()
+        );
+        assert_eq!(
+            session.requests()[1].script_code,
+            prevouts[1].script_pubkey().into()
+        );
+        assert_eq!(session.requests()[2].derivation, derivations[2]);
+
+        // Reproduce each digest by hashing its preimage, as the signer would receive it
+        let witness_tx = match &tx {
+            BitcoinTx::Witness(tx) => tx.clone(),
+            BitcoinTx::Legacy(tx) => WitnessTx::from_legacy(tx.clone()),
+        };
+        for (i, request) in session.requests().to_vec().iter().enumerate() {
+            let mut hasher = Hash256::default();
+            if i == 1 {
+                let args = prevouts[i].sighash_args(i, Sighash::All).unwrap();
+                witness_tx
+                    .write_legacy_sighash_preimage(&mut hasher, &args)
+                    .unwrap();
+            } else {
+                let args = WitnessSighashArgs {
+                    index: i,
+                    sighash_flag: Sighash::All,
+                    prevout_script: request.script_code.clone(),
+                    prevout_value: prevouts[i].value,
+                };
+                witness_tx
+                    .write_witness_sighash_preimage(&mut hasher, &args)
+                    .unwrap();
+            }
+            assert_eq!(
+                Hash256Digest::from(hasher.clone().finalize()),
+                request.digest
+            );
+
+            let signature: Signature = keys[i].sign_digest(hasher.clone());
+            assert!(matches!(
+                session.insert_signature(i, &signature, &pubkeys[(i + 1) % 3]),
+                Err(SigningError::WrongKey(_))
+            ));
+            assert!(matches!(
+                session.clone().finalize(),
+                Err(SigningError::MissingSignature(_))
+            ));
+            session
+                .insert_signature(i, &signature, &pubkeys[i])
+                .unwrap();
+            keys[i]
+                .verify_key()
+                .verify_digest(hasher, &signature)
+                .unwrap();
+        }
+        assert!(session.is_complete());
+
+        let signed = session.finalize().unwrap();
+        assert!(signed.is_witness());
+        let signed = match signed {
+            BitcoinTx::Witness(tx) => tx,
+            _ => unreachable!(),
+        };
+        assert_eq!(signed.witnesses[0].len(), 2);
+        assert_eq!(signed.witnesses[0][1].items(), &pubkeys[0].to_bytes()[..]);
+        assert!(signed.witnesses[1].is_empty());
+        // <sig> <pubkey>
+        let script_sig = signed.inputs()[1].script_sig.items();
+        assert_eq!(script_sig[0] as usize, script_sig.len() - 35);
+        assert_eq!(
+            signed.inputs()[2].script_sig.items()[1..],
+            redeem_script.items()[..]
+        );
+        assert_eq!(signed.witnesses[2].len(), 2);
+    }
+
+    #[test]
+    fn it_rejects_unsupported_inputs() {
+        let outpoint = BitcoinOutpoint::default();
+        let tx = BitcoinTxBuilder::<MainnetEncoder>::new()
+            .spend(outpoint, 0xffff_ffff)
+            .pay_script_pubkey(1000, ScriptPubkey::null())
+            .build()
+            .unwrap();
+        let sh = ScriptPubkey::p2sh(&Script::null());
+        let utxo = Utxo::new(outpoint, 2000, sh, SpendScript::Missing);
+        assert!(matches!(
+            SigningSession::new(&tx, &[utxo], &[], Sighash::All),
+            Err(SigningError::UnsupportedInput(0))
+        ));
+        assert!(matches!(
+            SigningSession::new(&tx, &[], &[], Sighash::All),
+            Err(SigningError::PrevoutCount {
+                expected: 1,
+                got: 0
+            })
+        ));
+    }
+}
@@ -0,0 +1,382 @@
+//! Snapshots of a wallet's known UTXOs and watched scripts.
+//!
+//! A `UtxoSnapshot` is a versioned export of the state a wallet needs to spend and track its
+//! coins. It can be moved between storage backends, or captured from a running system for
+//! debugging.
+//!
+//! Snapshots serialize with serde (e.g. to JSON), and with `ByteFormat` to a compact binary
+//! format. The binary format is a 4-byte magic, a little-endian `u32` version, then the
+//! length-prefixed UTXOs and scripts. Each UTXO is followed by a flag byte marking which of its
+//! confirmation height, block hash, and key derivation follow. Version 1 snapshots omit the
+//! metadata, and load without it. Both formats reject snapshots with an unknown version.
+//!
+//! ```
+//! use bitcoins::{snapshot::UtxoSnapshot, types::ScriptPubkey};
+//! use coins_core::ser::ByteFormat;
+//!
+//! let spk = ScriptPubkey::from_hex("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928").unwrap();
+//! let snapshot = UtxoSnapshot::new(vec![], vec![spk]);
+//! let hex = snapshot.serialize_hex();
+//! assert_eq!(UtxoSnapshot::deserialize_hex(&hex).unwrap(), snapshot);
+//! ```
+
+use std::io::{Error as IOError, Read, Write};
+
+use coins_bip32::path::KeyDerivation;
+use coins_core::ser::{self, ByteFormat, SerError};
+use serde::{Deserialize, Deserializer, Serialize};
+use thiserror::Error;
+
+use crate::{
+    hashes::BlockHash,
+    types::{script::ScriptPubkey, utxo::Utxo},
+};
+
+/// The current snapshot format version.
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// The format version that introduced UTXO metadata.
+const METADATA_VERSION: u32 = 2;
+
+/// Flags marking the UTXO metadata present in a binary snapshot.
+const HAS_HEIGHT: u8 = 0x01;
+const HAS_BLOCK_HASH: u8 = 0x02;
+const HAS_DERIVATION: u8 = 0x04;
+
+/// The magic bytes at the start of a binary snapshot.
+const SNAPSHOT_MAGIC: [u8; 4] = *b"utxs";
+
+/// An error (de)serializing a `UtxoSnapshot`.
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    /// Serialization-related errors
+    #[error(transparent)]
+    SerError(#[from] SerError),
+
+    /// IoError bubbled up from a `Read` or `Write`
+    #[error(transparent)]
+    IoError(#[from] IOError),
+
+    /// The data does not start with the snapshot magic bytes
+    #[error("Not a UTXO snapshot. Got magic {:?}", .0)]
+    BadMagic([u8; 4]),
+
+    /// The snapshot was written by an unknown version of the format
+    #[error("Unsupported snapshot version {}. Expected {}", .0, SNAPSHOT_VERSION)]
+    UnsupportedVersion(u32),
+}
+
+/// Check a snapshot version. Older versions are upgraded to the current version on load.
+fn check_version(version: u32) -> Result<u32, SnapshotError> {
+    if version == 0 || version > SNAPSHOT_VERSION {
+        return Err(SnapshotError::UnsupportedVersion(version));
+    }
+    Ok(SNAPSHOT_VERSION)
+}
+
+/// Deserialize a snapshot version, rejecting unknown versions.
+fn deserialize_version<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    check_version(u32::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+}
+
+/// The serialized length of a UTXO and its metadata
+fn utxo_length(utxo: &Utxo) -> usize {
+    let mut len = utxo.serialized_length();
+    len += 1; // flags
+    if utxo.height.is_some() {
+        len += 4;
+    }
+    if utxo.block_hash.is_some() {
+        len += 32;
+    }
+    if let Some(derivation) = &utxo.derivation {
+        len += 4; // root fingerprint
+        len += ser::prefix_byte_len(derivation.path.len() as u64) as usize;
+        len += 4 * derivation.path.len();
+    }
+    len
+}
+
+/// Read a UTXO, followed by its metadata if the format version includes it
+fn read_utxo<R>(reader: &mut R, version: u32) -> Result<Utxo, SnapshotError>
+where
+    R: Read,
+{
+    let mut utxo = Utxo::read_from(reader)?;
+    if version < METADATA_VERSION {
+        return Ok(utxo);
+    }
+
+    let mut flags = [0u8; 1];
+    reader.read_exact(&mut flags)?;
+    if flags[0] & HAS_HEIGHT != 0 {
+        utxo.height = Some(ser::read_u32_le(reader)?);
+    }
+    if flags[0] & HAS_BLOCK_HASH != 0 {
+        utxo.block_hash = Some(BlockHash::read_from(reader)?);
+    }
+    if flags[0] & HAS_DERIVATION != 0 {
+        let mut root = [0u8; 4];
+        reader.read_exact(&mut root)?;
+        let len = ser::read_compact_int(reader)?;
+        let path = (0..len)
+            .map(|_| ser::read_u32_le(reader))
+            .collect::<Result<Vec<_>, _>>()?;
+        utxo.derivation = Some(KeyDerivation {
+            root: root.into(),
+            path: path.into(),
+        });
+    }
+    Ok(utxo)
+}
+
+/// Write a UTXO, followed by its metadata
+fn write_utxo<W>(writer: &mut W, utxo: &Utxo) -> Result<usize, SnapshotError>
+where
+    W: Write,
+{
+    let mut len = utxo.write_to(writer)?;
+
+    let mut flags = 0;
+    if utxo.height.is_some() {
+        flags |= HAS_HEIGHT;
+    }
+    if utxo.block_hash.is_some() {
+        flags |= HAS_BLOCK_HASH;
+    }
+    if utxo.derivation.is_some() {
+        flags |= HAS_DERIVATION;
+    }
+    len += writer.write(&[flags])?;
+
+    if let Some(height) = utxo.height {
+        len += ser::write_u32_le(writer, height)?;
+    }
+    if let Some(block_hash) = &utxo.block_hash {
+        len += block_hash.write_to(writer)?;
+    }
+    if let Some(derivation) = &utxo.derivation {
+        len += writer.write(&derivation.root.0)?;
+        len += ser::write_compact_int(writer, derivation.path.len() as u64)?;
+        for index in derivation.path.iter() {
+            len += ser::write_u32_le(writer, *index)?;
+        }
+    }
+    Ok(len)
+}
+
+/// A versioned snapshot of a wallet's known UTXOs and watched script pubkeys.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UtxoSnapshot {
+    #[serde(deserialize_with = "deserialize_version")]
+    version: u32,
+    /// The UTXOs known to the wallet
+    pub utxos: Vec<Utxo>,
+    /// The script pubkeys watched by the wallet
+    pub scripts: Vec<ScriptPubkey>,
+}
+
+impl Default for UtxoSnapshot {
+    fn default() -> Self {
+        Self::new(vec![], vec![])
+    }
+}
+
+impl UtxoSnapshot {
+    /// Instantiate a snapshot in the current format version
+    pub fn new(utxos: Vec<Utxo>, scripts: Vec<ScriptPubkey>) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            utxos,
+            scripts,
+        }
+    }
+
+    /// The format version of the snapshot
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The total value of the UTXOs in the snapshot. Saturates on overflow
+    pub fn total_value(&self) -> u64 {
+        self.utxos
+            .iter()
+            .fold(0u64, |total, utxo| total.saturating_add(utxo.value))
+    }
+}
+
+impl ByteFormat for UtxoSnapshot {
+    type Error = SnapshotError;
+
+    fn serialized_length(&self) -> usize {
+        let mut len = SNAPSHOT_MAGIC.len();
+        len += 4; // version
+        len += ser::prefix_byte_len(self.utxos.len() as u64) as usize;
+        len += self.utxos.iter().map(utxo_length).sum::<usize>();
+        len += ser::prefix_byte_len(self.scripts.len() as u64) as usize;
+        len += self
+            .scripts
+            .iter()
+            .map(ByteFormat::serialized_length)
+            .sum::<usize>();
+        len
+    }
+
+    fn read_from<R>(reader: &mut R) -> Result<Self, Self::Error>
+    where
+        R: Read,
+        Self: std::marker::Sized,
+    {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::BadMagic(magic));
+        }
+        let version = ser::read_u32_le(reader)?;
+        check_version(version)?;
+        let count = ser::read_compact_int(reader)?;
+        let utxos = (0..count)
+            .map(|_| read_utxo(reader, version))
+            .collect::<Result<_, _>>()?;
+        let scripts = ser::read_prefix_vec::<_, SerError, _>(reader)?;
+        Ok(Self::new(utxos, scripts))
+    }
+
+    fn write_to<W>(&self, writer: &mut W) -> Result<usize, Self::Error>
+    where
+        W: Write,
+    {
+        let mut len = writer.write(&SNAPSHOT_MAGIC)?;
+        len += ser::write_u32_le(writer, self.version)?;
+        len += ser::write_compact_int(writer, self.utxos.len() as u64)?;
+        for utxo in self.utxos.iter() {
+            len += write_utxo(writer, utxo)?;
+        }
+        len += ser::write_prefix_vec::<_, SerError, _>(writer, &self.scripts)?;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        hashes::TXID,
+        types::{script::Script, txin::BitcoinOutpoint, utxo::SpendScript},
+    };
+
+    fn snapshot() -> UtxoSnapshot {
+        let txid = TXID::deserialize_hex(&"ab".repeat(32)).unwrap();
+        let wpkh = ScriptPubkey::from_hex("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928").unwrap();
+        let redeem_script = Script::new(vec![0x51]);
+        let sh = ScriptPubkey::p2sh(&redeem_script);
+
+        let mut known = Utxo::new(
+            BitcoinOutpoint::new(txid, 1),
+            20_000,
+            sh.clone(),
+            SpendScript::Missing,
+        );
+        assert!(known.set_spend_script(redeem_script));
+        let derivation = KeyDerivation {
+            root: [0xde, 0xad, 0xbe, 0xef].into(),
+            path: "m/84'/0'/0'/0/3".parse().unwrap(),
+        };
+        let utxos = vec![
+            Utxo::new(
+                BitcoinOutpoint::new(txid, 0),
+                10_000,
+                wpkh.clone(),
+                SpendScript::None,
+            )
+            .confirmed_in(
+                700_000,
+                BlockHash::deserialize_hex(&"cd".repeat(32)).unwrap(),
+            )
+            .derivation(derivation),
+            known.derivation(KeyDerivation {
+                root: [1, 2, 3, 4].into(),
+                path: vec![].into(),
+            }),
+            Utxo::new(
+                BitcoinOutpoint::new(txid, 2),
+                30_000,
+                sh.clone(),
+                SpendScript::Missing,
+            ),
+        ];
+        UtxoSnapshot::new(utxos, vec![wpkh, sh])
+    }
+
+    #[test]
+    fn it_round_trips_binary_snapshots() {
+        let snapshot = snapshot();
+        assert_eq!(snapshot.total_value(), 60_000);
+
+        let bytes = snapshot.serialize_hex();
+        assert_eq!(bytes.len() / 2, snapshot.serialized_length());
+        assert!(bytes.starts_with("7574787302000000"));
+        let read = UtxoSnapshot::deserialize_hex(&bytes).unwrap();
+        assert_eq!(read, snapshot);
+        assert_eq!(read.utxos[0].height, Some(700_000));
+        assert!(read.utxos[0].block_hash.is_some());
+        assert_eq!(read.utxos[0].derivation, snapshot.utxos[0].derivation);
+        assert_eq!(read.utxos[1].height, None);
+        assert!(read.utxos[1].derivation.is_some());
+        assert_eq!(read.utxos[2].derivation, None);
+        assert_eq!(
+            UtxoSnapshot::deserialize_hex(&UtxoSnapshot::default().serialize_hex()).unwrap(),
+            UtxoSnapshot::default()
+        );
+
+        match UtxoSnapshot::deserialize_hex(&bytes.replacen("02", "03", 1)) {
+            Err(SnapshotError::UnsupportedVersion(3)) => {}
+            other => panic!("expected UnsupportedVersion. Got {:?}", other),
+        }
+        match UtxoSnapshot::deserialize_hex(&bytes[2..]) {
+            Err(SnapshotError::BadMagic(_)) => {}
+            other => panic!("expected BadMagic. Got {:?}", other),
+        }
+        assert!(UtxoSnapshot::deserialize_hex(&bytes[..bytes.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn it_loads_version_1_binary_snapshots() {
+        let snapshot = snapshot();
+        let mut v1 = SNAPSHOT_MAGIC.to_vec();
+        ser::write_u32_le(&mut v1, 1).unwrap();
+        ser::write_prefix_vec::<_, SerError, _>(&mut v1, &snapshot.utxos).unwrap();
+        ser::write_prefix_vec::<_, SerError, _>(&mut v1, &snapshot.scripts).unwrap();
+
+        let upgraded = UtxoSnapshot::read_from(&mut &v1[..]).unwrap();
+        assert_eq!(upgraded.version(), SNAPSHOT_VERSION);
+        assert_eq!(upgraded.total_value(), snapshot.total_value());
+        assert_eq!(upgraded.scripts, snapshot.scripts);
+        assert!(upgraded
+            .utxos
+            .iter()
+            .all(|utxo| utxo.height.is_none() && utxo.derivation.is_none()));
+    }
+
+    #[test]
+    fn it_round_trips_json_snapshots() {
+        let snapshot = snapshot();
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(
+            serde_json::from_str::<UtxoSnapshot>(&json).unwrap(),
+            snapshot
+        );
+
+        let future = json.replacen("\"version\":2", "\"version\":3", 1);
+        let err = serde_json::from_str::<UtxoSnapshot>(&future).unwrap_err();
+        assert!(err.to_string().contains("Unsupported snapshot version 3"));
+
+        let v1 = json.replacen("\"version\":2", "\"version\":1", 1);
+        let upgraded = serde_json::from_str::<UtxoSnapshot>(&v1).unwrap();
+        assert_eq!(upgraded.version(), SNAPSHOT_VERSION);
+        assert_eq!(upgraded, snapshot);
+    }
+}
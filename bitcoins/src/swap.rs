@@ -0,0 +1,298 @@
+//! Transactions for HTLC-based atomic swaps.
+//!
+//! Each side of a cross-chain swap locks funds in an `Htlc`. An `AtomicSwap` builds the three
+//! transactions of one side: the funding output paying to the HTLC, the claim transaction that
+//! spends it with the payment preimage, and the refund transaction that spends it after the
+//! timeout. Claim and refund transactions spend the HTLC output to a single destination, paying a
+//! fixed fee.
+//!
+//! Signing follows the external-signer flow of `signing::SigningSession`. Compute the sighash of
+//! the unsigned transaction with `AtomicSwap::sighash_args`, sign it, and pass the signature to
+//! `finalize_claim` or `finalize_refund`.
+
+use coins_bip32::ecdsa::Signature;
+use coins_core::hashes::{DigestOutput, Hash256};
+use thiserror::Error;
+
+use crate::{
+    builder::{NON_FINAL_SEQUENCE, RBF_SEQUENCE},
+    contracts::Htlc,
+    types::{
+        script::{ScriptPubkey, ScriptSig},
+        tx::{Sighash, TxError},
+        txin::{BitcoinOutpoint, BitcoinTxIn},
+        txout::TxOut,
+        witness::{WitnessSighashArgs, WitnessTransaction, WitnessTx},
+    },
+};
+
+/// An error building or finalizing a swap transaction.
+#[derive(Debug, Error)]
+pub enum SwapError {
+    /// The fee is larger than the value locked in the HTLC
+    #[error("Fee {fee} exceeds the swap value {value}")]
+    FeeExceedsValue {
+        /// The value locked in the HTLC
+        value: u64,
+        /// The fee
+        fee: u64,
+    },
+
+    /// The preimage does not hash to the HTLC's payment hash
+    #[error("Preimage does not match the payment hash")]
+    WrongPreimage,
+
+    /// Bubbled up from the transaction
+    #[error(transparent)]
+    TxError(#[from] TxError),
+}
+
+/// One side of an atomic swap. An HTLC, and the value locked in it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AtomicSwap {
+    htlc: Htlc,
+    value: u64,
+}
+
+impl AtomicSwap {
+    /// Instantiate a swap locking `value` in `htlc`
+    pub fn new(htlc: Htlc, value: u64) -> Self {
+        Self { htlc, value }
+    }
+
+    /// The HTLC
+    pub fn htlc(&self) -> &Htlc {
+        &self.htlc
+    }
+
+    /// The value locked in the HTLC
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    /// The output funding the swap. Add it to a funding transaction, e.g. with
+    /// `BitcoinTxBuilder::pay_script_pubkey`.
+    pub fn funding_output(&self) -> TxOut {
+        TxOut::new(self.value, self.htlc.script_pubkey().clone())
+    }
+
+    /// Build a transaction spending the HTLC output at `funding` to `destination`
+    fn spend_tx(
+        &self,
+        funding: BitcoinOutpoint,
+        destination: ScriptPubkey,
+        fee: u64,
+        sequence: u32,
+        locktime: u32,
+    ) -> Result<WitnessTx, SwapError> {
+        let value = self
+            .value
+            .checked_sub(fee)
+            .ok_or(SwapError::FeeExceedsValue {
+                value: self.value,
+                fee,
+            })?;
+        let vin = vec![BitcoinTxIn::new(funding, ScriptSig::null(), sequence)];
+        let vout = vec![TxOut::new(value, destination)];
+        Ok(<WitnessTx as WitnessTransaction>::new(
+            2,
+            vin,
+            vout,
+            vec![vec![]],
+            locktime,
+        )?)
+    }
+
+    /// Build the unsigned transaction claiming the HTLC output at `funding` to `destination`.
+    /// The transaction signals replace-by-fee.
+    pub fn claim_tx(
+        &self,
+        funding: BitcoinOutpoint,
+        destination: ScriptPubkey,
+        fee: u64,
+    ) -> Result<WitnessTx, SwapError> {
+        self.spend_tx(funding, destination, fee, RBF_SEQUENCE, 0)
+    }
+
+    /// Build the unsigned transaction refunding the HTLC output at `funding` to `destination`.
+    /// Its locktime is the HTLC timeout, so it is not valid until the timeout has passed.
+    pub fn refund_tx(
+        &self,
+        funding: BitcoinOutpoint,
+        destination: ScriptPubkey,
+        fee: u64,
+    ) -> Result<WitnessTx, SwapError> {
+        let locktime = self.htlc.timeout().into();
+        self.spend_tx(funding, destination, fee, NON_FINAL_SEQUENCE, locktime)
+    }
+
+    /// The sighash args of the HTLC input of a claim or refund transaction
+    pub fn sighash_args(&self, sighash_flag: Sighash) -> WitnessSighashArgs {
+        WitnessSighashArgs::wsh(0, sighash_flag, self.htlc.witness_script(), self.value)
+    }
+
+    /// Calculates the BIP143 sighash of the HTLC input of a claim or refund transaction
+    pub fn sighash(
+        &self,
+        tx: &WitnessTx,
+        sighash_flag: Sighash,
+    ) -> Result<DigestOutput<Hash256>, SwapError> {
+        Ok(tx.witness_sighash(&self.sighash_args(sighash_flag))?)
+    }
+
+    /// Add the recipient's signature and the payment preimage to a claim transaction. The
+    /// signature is not verified. High-S signatures are normalized.
+    pub fn finalize_claim(
+        &self,
+        mut tx: WitnessTx,
+        signature: &Signature,
+        sighash_flag: Sighash,
+        preimage: &[u8; 32],
+    ) -> Result<WitnessTx, SwapError> {
+        if !self.htlc.is_preimage(preimage) {
+            return Err(SwapError::WrongPreimage);
+        }
+        let sig = encode_signature(signature, sighash_flag);
+        tx.witnesses[0] = self.htlc.claim_witness(&sig, preimage);
+        Ok(tx)
+    }
+
+    /// Add the refund key's signature to a refund transaction. The signature is not verified.
+    /// High-S signatures are normalized.
+    pub fn finalize_refund(
+        &self,
+        mut tx: WitnessTx,
+        signature: &Signature,
+        sighash_flag: Sighash,
+    ) -> WitnessTx {
+        let sig = encode_signature(signature, sighash_flag);
+        tx.witnesses[0] = self.htlc.refund_witness(&sig);
+        tx
+    }
+}
+
+/// DER-encode a low-S signature, and append its sighash flag
+fn encode_signature(signature: &Signature, sighash_flag: Sighash) -> Vec<u8> {
+    let mut signature = *signature;
+    // Only fails for an invalid `s`, which the signature's constructor forbids
+    let _ = signature.normalize_s();
+    let mut sig = signature.to_der().as_bytes().to_vec();
+    sig.push(sighash_flag as u8);
+    sig
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use coins_bip32::prelude::*;
+    use coins_core::{
+        hashes::{Digest, Hash256Digest},
+        types::tx::Transaction,
+    };
+
+    use crate::{
+        hashes::TXID,
+        types::{
+            timelock::{
+                validate_spend_timelocks, validate_timelocks, LockTime, ScriptTimelocks,
+                TimelockError,
+            },
+            tx::BitcoinTransaction,
+        },
+    };
+
+    #[test]
+    fn it_builds_swap_transactions() {
+        let root = DerivedXPriv::root_from_seed(&[7u8; 32], Some(Hint::SegWit)).unwrap();
+        let recipient = root.derive_child(0).unwrap();
+        let refund = root.derive_child(1).unwrap();
+        let preimage = [9u8; 32];
+        let timeout = LockTime::from_height(800_000).unwrap();
+        let htlc = Htlc::from_preimage(
+            &preimage,
+            &recipient.verify_key(),
+            &refund.verify_key(),
+            timeout,
+        );
+        let swap = AtomicSwap::new(htlc, 100_000);
+        assert_eq!(
+            swap.funding_output().script_pubkey,
+            *swap.htlc().script_pubkey()
+        );
+
+        let funding = BitcoinOutpoint::new(TXID::default(), 1);
+        let destination =
+            ScriptPubkey::from_hex("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928").unwrap();
+        assert!(matches!(
+            swap.claim_tx(funding, destination.clone(), 100_001),
+            Err(SwapError::FeeExceedsValue { .. })
+        ));
+
+        let claim = swap.claim_tx(funding, destination.clone(), 1_000).unwrap();
+        assert_eq!(claim.outputs()[0].value, 99_000);
+        assert!(claim.signals_rbf());
+        let mut hasher = Hash256::default();
+        claim
+            .write_witness_sighash_preimage(&mut hasher, &swap.sighash_args(Sighash::All))
+            .unwrap();
+        assert_eq!(
+            Hash256Digest::from(hasher.clone().finalize()),
+            Hash256Digest::from(swap.sighash(&claim, Sighash::All).unwrap())
+        );
+        let signature: Signature = recipient.sign_digest(hasher);
+        assert!(matches!(
+            swap.finalize_claim(claim.clone(), &signature, Sighash::All, &[0u8; 32]),
+            Err(SwapError::WrongPreimage)
+        ));
+        let claim = swap
+            .finalize_claim(claim, &signature, Sighash::All, &preimage)
+            .unwrap();
+        assert_eq!(claim.witnesses()[0].len(), 4);
+        assert_eq!(claim.witnesses()[0][1].items(), &preimage[..]);
+        assert_eq!(
+            claim.witnesses()[0][3].items(),
+            swap.htlc().witness_script().items()
+        );
+        assert!(claim.check_sanity().is_ok());
+
+        // Neither spend path's timelock is required by the other
+        let witness_script = swap.htlc().witness_script();
+        let claim_path = [ScriptTimelocks::from_script_branches(
+            witness_script,
+            &[true],
+        )];
+        let refund_path = [ScriptTimelocks::from_script_branches(
+            witness_script,
+            &[false],
+        )];
+        assert!(validate_timelocks(&claim, &[Some(witness_script)]).is_ok());
+        assert!(validate_spend_timelocks(&claim, &claim_path).is_ok());
+        assert_eq!(
+            validate_spend_timelocks(&claim, &refund_path),
+            Err(TimelockError::InsufficientLocktime {
+                input: 0,
+                required: 800_000,
+                locktime: 0
+            })
+        );
+
+        let refund_tx = swap.refund_tx(funding, destination, 1_000).unwrap();
+        assert_eq!(refund_tx.locktime(), 800_000);
+        assert_eq!(refund_tx.inputs()[0].sequence, NON_FINAL_SEQUENCE);
+        let mut hasher = Hash256::default();
+        refund_tx
+            .write_witness_sighash_preimage(&mut hasher, &swap.sighash_args(Sighash::All))
+            .unwrap();
+        let signature: Signature = refund.sign_digest(hasher);
+        let refund_tx = swap.finalize_refund(refund_tx, &signature, Sighash::All);
+        assert_eq!(refund_tx.witnesses()[0].len(), 3);
+        assert!(refund_tx.witnesses()[0][1].is_empty());
+        assert_eq!(
+            *refund_tx.witnesses()[0][0].items().last().unwrap(),
+            Sighash::All as u8
+        );
+        assert!(refund_tx.check_sanity().is_ok());
+        assert!(validate_timelocks(&refund_tx, &[Some(witness_script)]).is_ok());
+        assert!(validate_spend_timelocks(&refund_tx, &refund_path).is_ok());
+    }
+}
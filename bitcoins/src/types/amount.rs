@@ -0,0 +1,280 @@
+//! A satoshi amount with checked arithmetic.
+//!
+//! `TxOut`, `Utxo`, and the builder carry values as raw `u64` satoshis. `Amount` wraps a satoshi
+//! value, and guards arithmetic against overflow and against exceeding `MAX_MONEY`. It parses
+//! and formats decimal BTC strings without floating point rounding.
+//!
+//! ```
+//! use bitcoins::types::Amount;
+//!
+//! let amount: Amount = "0.015".parse().unwrap();
+//! assert_eq!(amount.to_sat(), 1_500_000);
+//! assert_eq!(amount.to_string(), "0.01500000");
+//! assert!(Amount::MAX_MONEY.checked_add(Amount::ONE_SAT).is_none());
+//! ```
+
+use std::{fmt, str::FromStr};
+
+use thiserror::Error;
+
+use crate::types::{txout::TxOut, utxo::Utxo};
+
+/// The number of satoshis in one bitcoin.
+pub const SATS_PER_BTC: u64 = 100_000_000;
+
+/// The number of decimal places in a BTC amount.
+const BTC_DECIMALS: usize = 8;
+
+/// An error parsing or validating an `Amount`.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum AmountError {
+    /// The amount is larger than `MAX_MONEY`
+    #[error("Amount of {} sat exceeds MAX_MONEY", .0)]
+    ExceedsMaxMoney(u64),
+
+    /// The string is not a decimal BTC amount
+    #[error("Invalid BTC amount {:?}", .0)]
+    InvalidFormat(String),
+
+    /// The string has more than 8 decimal places
+    #[error("BTC amount {:?} is more precise than 1 satoshi", .0)]
+    TooPrecise(String),
+}
+
+/// An amount of satoshis. Arithmetic is checked, and fails on overflow or when the result
+/// exceeds `MAX_MONEY`.
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+)]
+#[serde(try_from = "u64", into = "u64")]
+pub struct Amount(u64);
+
+impl Amount {
+    /// Zero satoshis
+    pub const ZERO: Amount = Amount(0);
+
+    /// One satoshi
+    pub const ONE_SAT: Amount = Amount(1);
+
+    /// One bitcoin
+    pub const ONE_BTC: Amount = Amount(SATS_PER_BTC);
+
+    /// The 21 million BTC supply cap. No valid output or sum of outputs exceeds this.
+    pub const MAX_MONEY: Amount = Amount(21_000_000 * SATS_PER_BTC);
+
+    /// Instantiate an amount from satoshis. Errors if the amount exceeds `MAX_MONEY`.
+    pub fn from_sat(sats: u64) -> Result<Self, AmountError> {
+        if sats > Self::MAX_MONEY.0 {
+            return Err(AmountError::ExceedsMaxMoney(sats));
+        }
+        Ok(Self(sats))
+    }
+
+    /// Return the amount in satoshis
+    pub fn to_sat(self) -> u64 {
+        self.0
+    }
+
+    /// Parse a decimal BTC string, e.g. `"0.015"`. At most 8 decimal places are accepted.
+    pub fn from_btc_str(s: &str) -> Result<Self, AmountError> {
+        let invalid = || AmountError::InvalidFormat(s.to_owned());
+        let (whole, frac) = match s.find('.') {
+            Some(i) => (&s[..i], &s[i + 1..]),
+            None => (s, ""),
+        };
+        if whole.is_empty() && frac.is_empty() {
+            return Err(invalid());
+        }
+        if !whole
+            .chars()
+            .chain(frac.chars())
+            .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        if frac.len() > BTC_DECIMALS {
+            return Err(AmountError::TooPrecise(s.to_owned()));
+        }
+
+        let whole: u64 = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().map_err(|_| invalid())?
+        };
+        let frac: u64 = format!("{:0<width$}", frac, width = BTC_DECIMALS)
+            .parse()
+            .map_err(|_| invalid())?;
+        let sats = whole
+            .checked_mul(SATS_PER_BTC)
+            .and_then(|sats| sats.checked_add(frac))
+            .ok_or(AmountError::ExceedsMaxMoney(u64::MAX))?;
+        Self::from_sat(sats)
+    }
+
+    /// Format the amount as a decimal BTC string with 8 decimal places
+    pub fn to_btc_string(self) -> String {
+        format!(
+            "{}.{:0width$}",
+            self.0 / SATS_PER_BTC,
+            self.0 % SATS_PER_BTC,
+            width = BTC_DECIMALS
+        )
+    }
+
+    /// Add two amounts. `None` if the result exceeds `MAX_MONEY`.
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0
+            .checked_add(other.0)
+            .and_then(|sats| Self::from_sat(sats).ok())
+    }
+
+    /// Subtract an amount. `None` if the result is negative.
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        self.0.checked_sub(other.0).map(Amount)
+    }
+
+    /// Multiply the amount by a scalar. `None` if the result exceeds `MAX_MONEY`.
+    pub fn checked_mul(self, scalar: u64) -> Option<Amount> {
+        self.0
+            .checked_mul(scalar)
+            .and_then(|sats| Self::from_sat(sats).ok())
+    }
+
+    /// Sum a sequence of amounts. `None` if the total exceeds `MAX_MONEY`.
+    pub fn checked_sum<I>(amounts: I) -> Option<Amount>
+    where
+        I: IntoIterator<Item = Amount>,
+    {
+        amounts
+            .into_iter()
+            .try_fold(Amount::ZERO, |acc, amount| acc.checked_add(amount))
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_btc_string())
+    }
+}
+
+impl FromStr for Amount {
+    type Err = AmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_btc_str(s)
+    }
+}
+
+impl std::convert::TryFrom<u64> for Amount {
+    type Error = AmountError;
+
+    fn try_from(sats: u64) -> Result<Amount, AmountError> {
+        Amount::from_sat(sats)
+    }
+}
+
+impl From<Amount> for u64 {
+    fn from(amount: Amount) -> u64 {
+        amount.0
+    }
+}
+
+impl TxOut {
+    /// The value of the output as an `Amount`. Errors if the value exceeds `MAX_MONEY`, as in
+    /// the null output.
+    pub fn amount(&self) -> Result<Amount, AmountError> {
+        Amount::from_sat(self.value)
+    }
+}
+
+impl Utxo {
+    /// The value of the UTXO as an `Amount`. Errors if the value exceeds `MAX_MONEY`.
+    pub fn amount(&self) -> Result<Amount, AmountError> {
+        Amount::from_sat(self.value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_parses_and_formats_btc_amounts() {
+        let cases = [
+            ("0", 0, "0.00000000"),
+            ("1", SATS_PER_BTC, "1.00000000"),
+            ("0.00000001", 1, "0.00000001"),
+            (".5", 50_000_000, "0.50000000"),
+            ("12.", 12 * SATS_PER_BTC, "12.00000000"),
+            ("20999999.9769", 2_099_999_997_690_000, "20999999.97690000"),
+            ("21000000", 21_000_000 * SATS_PER_BTC, "21000000.00000000"),
+        ];
+        for (s, sats, formatted) in cases.iter() {
+            let amount: Amount = s.parse().unwrap();
+            assert_eq!(amount.to_sat(), *sats);
+            assert_eq!(amount.to_string(), *formatted);
+            assert_eq!(formatted.parse::<Amount>().unwrap(), amount);
+        }
+
+        let errors = [
+            ("", AmountError::InvalidFormat("".to_owned())),
+            (".", AmountError::InvalidFormat(".".to_owned())),
+            ("-1", AmountError::InvalidFormat("-1".to_owned())),
+            ("1.2.3", AmountError::InvalidFormat("1.2.3".to_owned())),
+            ("1e8", AmountError::InvalidFormat("1e8".to_owned())),
+            (
+                "0.000000001",
+                AmountError::TooPrecise("0.000000001".to_owned()),
+            ),
+            (
+                "21000000.00000001",
+                AmountError::ExceedsMaxMoney(2_100_000_000_000_001),
+            ),
+            ("999999999999", AmountError::ExceedsMaxMoney(u64::MAX)),
+        ];
+        for (s, e) in errors.iter() {
+            assert_eq!(&s.parse::<Amount>().unwrap_err(), e);
+        }
+    }
+
+    #[test]
+    fn it_checks_amount_arithmetic() {
+        let one = Amount::ONE_BTC;
+        assert_eq!(one.checked_add(one).unwrap().to_sat(), 2 * SATS_PER_BTC);
+        assert_eq!(
+            one.checked_sub(Amount::ONE_SAT).unwrap().to_sat(),
+            99_999_999
+        );
+        assert_eq!(Amount::ZERO.checked_sub(Amount::ONE_SAT), None);
+        assert_eq!(one.checked_mul(21_000_000), Some(Amount::MAX_MONEY));
+        assert_eq!(one.checked_mul(21_000_001), None);
+        assert_eq!(one.checked_mul(u64::MAX), None);
+        assert_eq!(Amount::MAX_MONEY.checked_add(Amount::ONE_SAT), None);
+        assert_eq!(Amount::checked_sum(vec![one, one, one]), one.checked_mul(3));
+        assert_eq!(
+            Amount::checked_sum(vec![Amount::MAX_MONEY, Amount::ONE_SAT]),
+            None
+        );
+
+        assert_eq!(TxOut::new(5, vec![]).amount().unwrap().to_sat(), 5);
+        assert!(TxOut::null().amount().is_err());
+    }
+
+    #[test]
+    fn it_checks_deserialized_amounts() {
+        let amount: Amount = serde_json::from_str("2100000000000000").unwrap();
+        assert_eq!(amount, Amount::MAX_MONEY);
+        assert_eq!(serde_json::to_string(&amount).unwrap(), "2100000000000000");
+        assert!(serde_json::from_str::<Amount>("2100000000000001").is_err());
+    }
+}
@@ -0,0 +1,509 @@
+//! Block headers and blocks.
+//!
+//! Headers are 80 bytes. The block hash is the Hash256 of the serialized header. The header
+//! commits to the block's transactions via the merkle root of their txids.
+//!
+//! Proof of work targets are 256-bit integers, represented as big-endian byte arrays. Headers
+//! carry them in the compact "bits" encoding. `compact_to_target` and `target_to_compact`
+//! convert between the two, and `BlockHeader::validate_pow` checks a header's hash against its
+//! claimed target.
+
+use std::io::{Read, Write};
+
+use coins_core::{
+    hashes::{Hash256, Hash256Digest, MarkedDigest, MarkedDigestOutput},
+    ser::{self, ByteFormat, SerError},
+    types::tx::Transaction,
+};
+use thiserror::Error;
+
+use crate::{
+    hashes::{BlockHash, TXID},
+    types::tx::{BitcoinTx, TxError},
+};
+
+/// The length of a serialized block header.
+pub const HEADER_LEN: usize = 80;
+
+/// The maximum weight of a block, in weight units.
+pub const MAX_BLOCK_WEIGHT: usize = 4_000_000;
+
+/// A proof of work target, as a big-endian 256-bit integer
+pub type Target = [u8; 32];
+
+/// An error decoding a compact target, or validating a header's proof of work.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum PowError {
+    /// The compact target has its sign bit set
+    #[error("Compact target {:#010x} is negative", .0)]
+    NegativeTarget(u32),
+
+    /// The compact target does not fit in 256 bits
+    #[error("Compact target {:#010x} overflows 256 bits", .0)]
+    TargetOverflow(u32),
+
+    /// The target is zero. No hash can meet it.
+    #[error("Target is zero")]
+    ZeroTarget,
+
+    /// The target is easier than the network's proof of work limit
+    #[error("Target exceeds the proof of work limit")]
+    AboveLimit,
+
+    /// The block hash does not meet the target
+    #[error("Block hash {0} does not meet its target")]
+    InsufficientWork(BlockHash),
+}
+
+/// Decode a compact target, as found in a header's bits. Errors if the target is negative or
+/// overflows 256 bits. Zero targets are returned as-is.
+pub fn compact_to_target(bits: u32) -> Result<Target, PowError> {
+    let size = (bits >> 24) as usize;
+    let mut word = bits & 0x007f_ffff;
+    if size <= 3 {
+        word >>= 8 * (3 - size);
+    }
+    if word != 0 && bits & 0x0080_0000 != 0 {
+        return Err(PowError::NegativeTarget(bits));
+    }
+    if word != 0 && (size > 34 || (word > 0xff && size > 33) || (word > 0xffff && size > 32)) {
+        return Err(PowError::TargetOverflow(bits));
+    }
+
+    let mut target = [0u8; 32];
+    if size <= 3 {
+        target[28..].copy_from_slice(&word.to_be_bytes());
+    } else {
+        // The 3 mantissa bytes start at the `size`-th byte from the end
+        for (i, byte) in word.to_be_bytes()[1..].iter().enumerate() {
+            if let Some(pos) = (32 + i).checked_sub(size) {
+                target[pos] = *byte;
+            }
+        }
+    }
+    Ok(target)
+}
+
+/// Encode a target in the compact bits encoding. Precision beyond the 3 most significant bytes
+/// is lost.
+pub fn target_to_compact(target: &Target) -> u32 {
+    let mut size = target.iter().position(|b| *b != 0).map_or(0, |i| 32 - i);
+    let start = 32 - size;
+    let mut compact = target[start..]
+        .iter()
+        .chain(std::iter::repeat(&0))
+        .take(3)
+        .fold(0u32, |acc, b| acc << 8 | u32::from(*b));
+    // The mantissa is signed. Shift it down a byte if its high bit is set.
+    if compact & 0x0080_0000 != 0 {
+        compact >>= 8;
+        size += 1;
+    }
+    compact | (size as u32) << 24
+}
+
+/// Hash two merkle tree nodes into their parent
+fn merkle_parent(left: &[u8], right: &[u8]) -> Hash256Digest {
+    let mut w = Hash256::default();
+    w.write_all(left).expect("No IOError from hash functions");
+    w.write_all(right).expect("No IOError from hash functions");
+    w.finalize_marked()
+}
+
+/// Compute the merkle root of a list of txids, in block order. When a level has an odd number
+/// of nodes, the last node is paired with itself. Returns the zero digest for an empty list.
+///
+/// # Note
+///
+/// Due to the duplication of odd nodes, different lists of txids may produce the same root
+/// (CVE-2012-2459). Callers validating untrusted blocks must reject duplicate txids.
+pub fn merkle_root(txids: &[TXID]) -> Hash256Digest {
+    if txids.is_empty() {
+        return Hash256Digest::default();
+    }
+    let mut level: Vec<Hash256Digest> =
+        txids.iter().map(|txid| txid.to_internal().into()).collect();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => merkle_parent(left.as_slice(), right.as_slice()),
+                [single] => merkle_parent(single.as_slice(), single.as_slice()),
+                _ => unreachable!("chunks of 2"),
+            })
+            .collect();
+    }
+    level[0]
+}
+
+/// A Bitcoin block header.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BlockHeader {
+    version: u32,
+    prev_block: BlockHash,
+    merkle_root: Hash256Digest,
+    time: u32,
+    bits: u32,
+    nonce: u32,
+}
+
+impl BlockHeader {
+    /// Instantiate a new header
+    pub fn new(
+        version: u32,
+        prev_block: BlockHash,
+        merkle_root: Hash256Digest,
+        time: u32,
+        bits: u32,
+        nonce: u32,
+    ) -> Self {
+        Self {
+            version,
+            prev_block,
+            merkle_root,
+            time,
+            bits,
+            nonce,
+        }
+    }
+
+    /// The block version
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The hash of the previous block
+    pub fn prev_block(&self) -> BlockHash {
+        self.prev_block
+    }
+
+    /// The merkle root of the block's txids
+    pub fn merkle_root(&self) -> Hash256Digest {
+        self.merkle_root
+    }
+
+    /// The block timestamp, in seconds since the unix epoch
+    pub fn time(&self) -> u32 {
+        self.time
+    }
+
+    /// The compact encoding of the block's difficulty target
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// The nonce
+    pub fn nonce(&self) -> u32 {
+        self.nonce
+    }
+
+    /// Calculate the block hash. This is the Hash256 of the serialized header.
+    pub fn block_hash(&self) -> BlockHash {
+        let mut w = Hash256::default();
+        self.write_to(&mut w)
+            .expect("No IOError from hash functions");
+        w.finalize_marked()
+    }
+
+    /// Decode the target claimed by the header's bits
+    pub fn target(&self) -> Result<Target, PowError> {
+        compact_to_target(self.bits)
+    }
+
+    /// Check that the header's hash meets the target claimed by its bits. This does not check
+    /// that the bits are correct for the header's position in the chain.
+    pub fn validate_pow(&self) -> Result<(), PowError> {
+        let target = self.target()?;
+        if target == Target::default() {
+            return Err(PowError::ZeroTarget);
+        }
+        let hash = self.block_hash();
+        let mut be_hash = [0u8; 32];
+        be_hash.copy_from_slice(hash.as_slice());
+        be_hash.reverse();
+        if be_hash > target {
+            return Err(PowError::InsufficientWork(hash));
+        }
+        Ok(())
+    }
+
+    /// Check the header's proof of work, and that its target does not exceed the network's
+    /// limit. See `nets::ChainParams::pow_limit`.
+    pub fn validate_pow_with_limit(&self, pow_limit: &Target) -> Result<(), PowError> {
+        if &self.target()? > pow_limit {
+            return Err(PowError::AboveLimit);
+        }
+        self.validate_pow()
+    }
+}
+
+impl ByteFormat for BlockHeader {
+    type Error = SerError;
+
+    fn serialized_length(&self) -> usize {
+        HEADER_LEN
+    }
+
+    fn read_from<R>(reader: &mut R) -> Result<Self, Self::Error>
+    where
+        R: Read,
+        Self: std::marker::Sized,
+    {
+        let version = ser::read_u32_le(reader)?;
+        let prev_block = BlockHash::read_from(reader)?;
+        let merkle_root = Hash256Digest::read_from(reader)?;
+        Ok(Self {
+            version,
+            prev_block,
+            merkle_root,
+            time: ser::read_u32_le(reader)?,
+            bits: ser::read_u32_le(reader)?,
+            nonce: ser::read_u32_le(reader)?,
+        })
+    }
+
+    fn write_to<W>(&self, writer: &mut W) -> Result<usize, Self::Error>
+    where
+        W: Write,
+    {
+        let mut len = ser::write_u32_le(writer, self.version)?;
+        len += self.prev_block.write_to(writer)?;
+        len += self.merkle_root.write_to(writer)?;
+        len += ser::write_u32_le(writer, self.time)?;
+        len += ser::write_u32_le(writer, self.bits)?;
+        len += ser::write_u32_le(writer, self.nonce)?;
+        Ok(len)
+    }
+}
+
+/// A Bitcoin block. A header and its transactions.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Block {
+    header: BlockHeader,
+    txns: Vec<BitcoinTx>,
+}
+
+impl Block {
+    /// Instantiate a new block. The header's merkle root is not checked. Use
+    /// `check_merkle_root` to validate it.
+    pub fn new(header: BlockHeader, txns: Vec<BitcoinTx>) -> Self {
+        Self { header, txns }
+    }
+
+    /// The block header
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    /// The block's transactions, in order
+    pub fn txns(&self) -> &[BitcoinTx] {
+        &self.txns
+    }
+
+    /// The coinbase transaction, if any
+    pub fn coinbase(&self) -> Option<&BitcoinTx> {
+        self.txns.first()
+    }
+
+    /// The txids of the block's transactions, in order
+    pub fn txids(&self) -> Vec<TXID> {
+        self.txns.iter().map(Transaction::txid).collect()
+    }
+
+    /// Calculate the block hash. This is the Hash256 of the serialized header.
+    pub fn block_hash(&self) -> BlockHash {
+        self.header.block_hash()
+    }
+
+    /// Compute the merkle root of the block's transactions.
+    pub fn compute_merkle_root(&self) -> Hash256Digest {
+        merkle_root(&self.txids())
+    }
+
+    /// True if the header commits to the block's transactions. See the note on `merkle_root`
+    /// regarding duplicate txids.
+    pub fn check_merkle_root(&self) -> bool {
+        self.compute_merkle_root() == self.header.merkle_root
+    }
+}
+
+impl ByteFormat for Block {
+    type Error = TxError;
+
+    fn serialized_length(&self) -> usize {
+        let mut len = HEADER_LEN;
+        len += ser::prefix_byte_len(self.txns.len() as u64) as usize;
+        len += self
+            .txns
+            .iter()
+            .map(|tx| tx.serialized_length())
+            .sum::<usize>();
+        len
+    }
+
+    fn read_from<R>(reader: &mut R) -> Result<Self, Self::Error>
+    where
+        R: Read,
+        Self: std::marker::Sized,
+    {
+        let header = BlockHeader::read_from(reader)?;
+        let txns = ser::read_prefix_vec(reader)?;
+        Ok(Self { header, txns })
+    }
+
+    fn write_to<W>(&self, writer: &mut W) -> Result<usize, Self::Error>
+    where
+        W: Write,
+    {
+        let mut len = self.header.write_to(writer)?;
+        len += ser::write_prefix_vec(writer, &self.txns)?;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::tx::BitcoinTransaction;
+
+    const GENESIS_HEADER: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+    const GENESIS_COINBASE: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+
+    #[test]
+    fn it_parses_the_genesis_block() {
+        let header = BlockHeader::deserialize_hex(GENESIS_HEADER).unwrap();
+        assert_eq!(header.serialize_hex(), GENESIS_HEADER);
+        assert_eq!(header.version(), 1);
+        assert_eq!(header.prev_block(), BlockHash::default());
+        assert_eq!(header.time(), 1_231_006_505);
+        assert_eq!(header.bits(), 0x1d00_ffff);
+        assert_eq!(header.nonce(), 2_083_236_893);
+        assert_eq!(
+            header.block_hash().to_be_hex(),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+
+        let block_hex = format!("{}01{}", GENESIS_HEADER, GENESIS_COINBASE);
+        let block = Block::deserialize_hex(&block_hex).unwrap();
+        assert_eq!(block.serialize_hex(), block_hex);
+        assert_eq!(block.serialized_length(), block_hex.len() / 2);
+        assert_eq!(block.block_hash(), header.block_hash());
+        assert_eq!(block.txns().len(), 1);
+        assert_eq!(
+            block.coinbase().unwrap().txid().to_be_hex(),
+            "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"
+        );
+        assert!(block.coinbase().unwrap().check_coinbase().is_ok());
+        assert!(block.check_merkle_root());
+
+        let wrong = Block::new(BlockHeader::default(), block.txns().to_vec());
+        assert!(!wrong.check_merkle_root());
+    }
+
+    #[test]
+    fn it_converts_compact_targets() {
+        // From Bitcoin Core's arith_uint256 tests
+        let cases: &[(u32, &str, u32)] = &[
+            (0x0000_0000, "00", 0x0000_0000),
+            (0x0012_3456, "00", 0x0000_0000),
+            (0x0100_3456, "00", 0x0000_0000),
+            (0x0400_0000, "00", 0x0000_0000),
+            (0x0092_3456, "00", 0x0000_0000),
+            (0x0180_3456, "00", 0x0000_0000),
+            (0x0112_3456, "12", 0x0112_0000),
+            (0x0212_3456, "1234", 0x0212_3400),
+            (0x0312_3456, "123456", 0x0312_3456),
+            (0x0412_3456, "12345600", 0x0412_3456),
+            (0x0500_9234, "92340000", 0x0500_9234),
+            (0x2012_3456, "123456", 0x2012_3456),
+            (0x1d00_ffff, "00000000ffff", 0x1d00_ffff),
+        ];
+        for (bits, prefix, compact) in cases.iter() {
+            let target = compact_to_target(*bits).unwrap();
+            // Left-align prefixes of full-size targets, and right-align small values
+            let expected = if *bits >> 24 >= 0x1d {
+                format!("{:0<64}", prefix)
+            } else {
+                format!("{:0>64}", prefix)
+            };
+            assert_eq!(hex::encode(target), expected, "{:#010x}", bits);
+            assert_eq!(target_to_compact(&target), *compact);
+        }
+
+        assert_eq!(
+            compact_to_target(0x04923456),
+            Err(PowError::NegativeTarget(0x04923456))
+        );
+        assert_eq!(
+            compact_to_target(0xff12_3456),
+            Err(PowError::TargetOverflow(0xff12_3456))
+        );
+
+        let mut target = [0u8; 32];
+        target[31] = 0x80;
+        assert_eq!(target_to_compact(&target), 0x0200_8000);
+    }
+
+    #[test]
+    fn it_validates_header_pow() {
+        let header = BlockHeader::deserialize_hex(GENESIS_HEADER).unwrap();
+        header.validate_pow().unwrap();
+        let limit = compact_to_target(0x1d00_ffff).unwrap();
+        header.validate_pow_with_limit(&limit).unwrap();
+
+        let easier = compact_to_target(0x1e00_ffff).unwrap();
+        let header = BlockHeader::new(
+            1,
+            header.prev_block(),
+            header.merkle_root(),
+            header.time(),
+            0x1e00_ffff,
+            header.nonce(),
+        );
+        assert_eq!(
+            header.validate_pow_with_limit(&limit),
+            Err(PowError::AboveLimit)
+        );
+        assert!(header.validate_pow_with_limit(&easier).is_err());
+
+        let mut wrong_nonce = BlockHeader::deserialize_hex(GENESIS_HEADER).unwrap();
+        wrong_nonce.nonce += 1;
+        assert!(matches!(
+            wrong_nonce.validate_pow(),
+            Err(PowError::InsufficientWork(_))
+        ));
+        wrong_nonce.bits = 0;
+        assert_eq!(wrong_nonce.validate_pow(), Err(PowError::ZeroTarget));
+    }
+
+    #[test]
+    fn it_round_trips_blocks_through_serde() {
+        let block_hex = format!("{}01{}", GENESIS_HEADER, GENESIS_COINBASE);
+        let block = Block::deserialize_hex(&block_hex).unwrap();
+        let json = serde_json::to_value(&block).unwrap();
+        assert_eq!(
+            json["header"]["merkle_root"],
+            "3ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a"
+        );
+        assert_eq!(serde_json::from_value::<Block>(json).unwrap(), block);
+    }
+
+    #[test]
+    fn it_computes_merkle_roots() {
+        let txid = |byte: &str| TXID::deserialize_hex(&byte.repeat(32)).unwrap();
+        let (a, b, c) = (txid("01"), txid("02"), txid("03"));
+
+        assert_eq!(merkle_root(&[]), Hash256Digest::default());
+        assert_eq!(merkle_root(&[a]), a.to_internal().into());
+
+        let ab = merkle_parent(a.as_slice(), b.as_slice());
+        let cc = merkle_parent(c.as_slice(), c.as_slice());
+        assert_eq!(merkle_root(&[a, b]), ab);
+        assert_eq!(
+            merkle_root(&[a, b, c]),
+            merkle_parent(ab.as_slice(), cc.as_slice())
+        );
+        // The odd node duplication malleability
+        assert_eq!(merkle_root(&[a, b, c]), merkle_root(&[a, b, c, c]));
+    }
+}
@@ -0,0 +1,120 @@
+//! BIP119 `OP_CHECKTEMPLATEVERIFY` template hashes.
+//!
+//! CTV commits an output to the shape of the transaction that spends it. The default template
+//! hash commits to the version, locktime, script sigs, input count, sequences, outputs, and the
+//! index of the spending input. It does not commit to the outpoints being spent, or to witnesses.
+//!
+//! CTV is not active on mainnet. These helpers are intended for experimentation on signet.
+//!
+//! ```
+//! use bitcoins::types::{ctv, BitcoinTransaction, LegacyTx, Script};
+//!
+//! let tx = LegacyTx::default();
+//! let hash = tx.ctv_template_hash(0);
+//! assert_eq!(hash, ctv::default_template_hash(&tx, 0));
+//! let script = Script::ctv(&hash);
+//! assert_eq!(script.len(), 34);
+//! ```
+
+use coins_core::{
+    hashes::{Digest, Sha256},
+    ser::ByteFormat,
+};
+
+use crate::types::{
+    opcodes::Opcode,
+    script::{push_data, Script},
+    tx::BitcoinTransaction,
+};
+
+/// `OP_CHECKTEMPLATEVERIFY`, formerly `OP_NOP4`
+pub const OP_CHECKTEMPLATEVERIFY: Opcode = Opcode::Nop4;
+
+/// The SHA256 of the concatenated serializations of `items`
+fn sha256_of<'a, I, B>(items: I) -> [u8; 32]
+where
+    I: Iterator<Item = &'a B>,
+    B: ByteFormat + 'a,
+{
+    let mut buf = vec![];
+    for item in items {
+        item.write_to(&mut buf).expect("No IOError from Vec");
+    }
+    Sha256::digest(&buf).into()
+}
+
+/// Compute the BIP119 default template hash of `tx`, as spent by the input at `index`. The
+/// script sigs hash is omitted if every script sig is empty, per BIP119.
+pub fn default_template_hash<T: BitcoinTransaction>(tx: &T, index: u32) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(tx.version().to_le_bytes());
+    hasher.update(tx.locktime().to_le_bytes());
+    if tx.inputs().iter().any(|i| !i.script_sig.is_empty()) {
+        hasher.update(sha256_of(tx.inputs().iter().map(|i| &i.script_sig)));
+    }
+    hasher.update((tx.inputs().len() as u32).to_le_bytes());
+    let mut sequences = Sha256::new();
+    for input in tx.inputs() {
+        sequences.update(input.sequence.to_le_bytes());
+    }
+    hasher.update(sequences.finalize());
+    hasher.update((tx.outputs().len() as u32).to_le_bytes());
+    hasher.update(sha256_of(tx.outputs().iter()));
+    hasher.update(index.to_le_bytes());
+    hasher.finalize().into()
+}
+
+impl Script {
+    /// Instantiate the script `<template_hash> OP_CHECKTEMPLATEVERIFY`, which may only be spent
+    /// by a transaction with the given BIP119 template hash
+    pub fn ctv(template_hash: &[u8; 32]) -> Self {
+        let mut v = vec![];
+        push_data(&mut v, template_hash);
+        v.push(OP_CHECKTEMPLATEVERIFY.to_u8());
+        v.into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::{legacy::LegacyTx, script::BitcoinScript};
+
+    #[test]
+    fn it_computes_default_template_hashes() {
+        let cases = [
+            // No script sigs. From the BIP143 native P2WPKH example
+            (
+                "0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000",
+                1,
+                "85898add7fd8ef7b8ef7ace0ebadea5a4c3919dbe12154425b5bce8522136113",
+            ),
+            // With a script sig
+            (
+                "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600",
+                0,
+                "719d1c1f2a0326886802d375794068e8498b287770041be8b06aee1979f2098c",
+            ),
+        ];
+        for (tx_hex, index, expected) in cases.iter() {
+            let tx = LegacyTx::deserialize_hex(tx_hex).unwrap();
+            assert_eq!(hex::encode(default_template_hash(&tx, *index)), *expected);
+        }
+
+        // The outpoints and witnesses are not committed to
+        let mut tx = LegacyTx::deserialize_hex(cases[0].0).unwrap();
+        let hash = tx.ctv_template_hash(0);
+        tx.vin[0].outpoint.idx = 7;
+        assert_eq!(tx.ctv_template_hash(0), hash);
+        assert_eq!(tx.clone().into_witness().ctv_template_hash(0), hash);
+        assert_ne!(tx.ctv_template_hash(1), hash);
+        tx.vin[0].sequence = 0;
+        assert_ne!(tx.ctv_template_hash(0), hash);
+    }
+
+    #[test]
+    fn it_builds_ctv_scripts() {
+        let script = Script::ctv(&[0xab; 32]);
+        assert_eq!(script.to_asm(), format!("<{}> OP_NOP4", "ab".repeat(32)));
+    }
+}
@@ -0,0 +1,243 @@
+//! A structural diff between two transactions. This allows a signer to review a proposal
+//! returned by another party (e.g. a payjoin receiver or a coinjoin coordinator) before signing
+//! it.
+//!
+//! Inputs are matched by outpoint. Outputs are matched by script pubkey, in order, so that a
+//! change to an output's value is reported as a modification rather than a removal and an
+//! addition.
+
+use std::collections::HashMap;
+
+use crate::types::{
+    script::{ScriptPubkey, ScriptSig, Witness},
+    tx::BitcoinTransaction,
+    txin::{BitcoinOutpoint, BitcoinTxIn},
+    txout::TxOut,
+};
+
+/// A change to an input present in both transactions.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InputChange {
+    /// The outpoint spent by the input
+    pub outpoint: BitcoinOutpoint,
+    /// The input's index in the old transaction
+    pub old_index: usize,
+    /// The input's index in the new transaction
+    pub new_index: usize,
+    /// The old and new sequence numbers, if changed
+    pub sequence: Option<(u32, u32)>,
+    /// The old and new script sigs, if changed
+    pub script_sig: Option<(ScriptSig, ScriptSig)>,
+    /// The old and new witnesses, if changed
+    pub witness: Option<(Witness, Witness)>,
+}
+
+/// A change to an output present in both transactions.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OutputChange {
+    /// The script pubkey of the output
+    pub script_pubkey: ScriptPubkey,
+    /// The output's index in the old transaction
+    pub old_index: usize,
+    /// The output's index in the new transaction
+    pub new_index: usize,
+    /// The old and new values, if changed
+    pub value: Option<(u64, u64)>,
+}
+
+/// The differences between two transactions.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct TxDiff {
+    /// The old and new versions, if changed
+    pub version: Option<(u32, u32)>,
+    /// The old and new locktimes, if changed
+    pub locktime: Option<(u32, u32)>,
+    /// Inputs only in the new transaction, with their indices
+    pub added_inputs: Vec<(usize, BitcoinTxIn)>,
+    /// Inputs only in the old transaction, with their indices
+    pub removed_inputs: Vec<(usize, BitcoinTxIn)>,
+    /// Inputs in both transactions that were moved or modified
+    pub modified_inputs: Vec<InputChange>,
+    /// Outputs only in the new transaction, with their indices
+    pub added_outputs: Vec<(usize, TxOut)>,
+    /// Outputs only in the old transaction, with their indices
+    pub removed_outputs: Vec<(usize, TxOut)>,
+    /// Outputs in both transactions that were moved or modified
+    pub modified_outputs: Vec<OutputChange>,
+}
+
+/// Return `Some((old, new))` if they differ
+fn changed<V: PartialEq + Clone>(old: &V, new: &V) -> Option<(V, V)> {
+    if old == new {
+        None
+    } else {
+        Some((old.clone(), new.clone()))
+    }
+}
+
+impl TxDiff {
+    /// Compare two transactions. Signatures are compared as well. Use
+    /// `BitcoinTxBuilder::strip_signatures` first to compare unsigned contents only.
+    pub fn new<A, B>(old: &A, new: &B) -> Self
+    where
+        A: BitcoinTransaction,
+        B: BitcoinTransaction,
+    {
+        let mut diff = TxDiff {
+            version: changed(&old.version(), &new.version()),
+            locktime: changed(&old.locktime(), &new.locktime()),
+            ..Default::default()
+        };
+
+        let empty = Witness::default();
+        let old_witness = |i: usize| old.witnesses().get(i).unwrap_or(&empty);
+        let new_witness = |i: usize| new.witnesses().get(i).unwrap_or(&empty);
+
+        let mut old_inputs: HashMap<BitcoinOutpoint, usize> = old
+            .inputs()
+            .iter()
+            .enumerate()
+            .map(|(i, txin)| (txin.outpoint, i))
+            .collect();
+        for (new_index, txin) in new.inputs().iter().enumerate() {
+            let old_index = match old_inputs.remove(&txin.outpoint) {
+                Some(old_index) => old_index,
+                None => {
+                    diff.added_inputs.push((new_index, txin.clone()));
+                    continue;
+                }
+            };
+            let old_txin = &old.inputs()[old_index];
+            let change = InputChange {
+                outpoint: txin.outpoint,
+                old_index,
+                new_index,
+                sequence: changed(&old_txin.sequence, &txin.sequence),
+                script_sig: changed(&old_txin.script_sig, &txin.script_sig),
+                witness: changed(old_witness(old_index), new_witness(new_index)),
+            };
+            if old_index != new_index
+                || change.sequence.is_some()
+                || change.script_sig.is_some()
+                || change.witness.is_some()
+            {
+                diff.modified_inputs.push(change);
+            }
+        }
+        diff.removed_inputs = old
+            .inputs()
+            .iter()
+            .enumerate()
+            .filter(|(_, txin)| old_inputs.contains_key(&txin.outpoint))
+            .map(|(i, txin)| (i, txin.clone()))
+            .collect();
+
+        let mut unmatched: Vec<Option<&TxOut>> = old.outputs().iter().map(Some).collect();
+        for (new_index, txout) in new.outputs().iter().enumerate() {
+            let old_index = unmatched.iter().position(|o| match o {
+                Some(o) => o.script_pubkey == txout.script_pubkey,
+                None => false,
+            });
+            let old_index = match old_index {
+                Some(old_index) => old_index,
+                None => {
+                    diff.added_outputs.push((new_index, txout.clone()));
+                    continue;
+                }
+            };
+            let old_txout = unmatched[old_index].take().expect("matched above");
+            let value = changed(&old_txout.value, &txout.value);
+            if old_index != new_index || value.is_some() {
+                diff.modified_outputs.push(OutputChange {
+                    script_pubkey: txout.script_pubkey.clone(),
+                    old_index,
+                    new_index,
+                    value,
+                });
+            }
+        }
+        diff.removed_outputs = unmatched
+            .iter()
+            .enumerate()
+            .filter_map(|(i, o)| o.map(|o| (i, o.clone())))
+            .collect();
+
+        diff
+    }
+
+    /// True if the transactions are identical
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{builder::BitcoinTxBuilder, enc::MainnetEncoder};
+    use coins_core::builder::TxBuilder;
+
+    fn spk(hex: &str) -> ScriptPubkey {
+        ScriptPubkey::from_hex(hex).unwrap()
+    }
+
+    #[test]
+    fn it_diffs_payjoin_proposals() {
+        let sender = spk("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928");
+        let receiver = spk("0014e88869b88866281ab166541ad8aafba8f8ab");
+        let outpoint = |idx| BitcoinOutpoint::new(Default::default(), idx);
+
+        let original = BitcoinTxBuilder::<MainnetEncoder>::new()
+            .version(2)
+            .spend(outpoint(0), 0xffff_fffd)
+            .pay_script_pubkey(10_000, receiver.clone())
+            .pay_script_pubkey(5_000, sender.clone())
+            .build()
+            .unwrap();
+        assert!(TxDiff::new(&original, &original).is_empty());
+
+        // The receiver adds an input and its value to their output
+        let proposal = BitcoinTxBuilder::<MainnetEncoder>::from_tx_ref(&original)
+            .insert_input(
+                0,
+                BitcoinTxIn::new(outpoint(1), ScriptSig::default(), 0xffff_fffd),
+            )
+            .set_output_value(0, 30_000)
+            .build()
+            .unwrap();
+
+        let diff = TxDiff::new(&original, &proposal);
+        assert!(!diff.is_empty());
+        assert_eq!(diff.version, None);
+        assert_eq!(diff.added_inputs.len(), 1);
+        assert_eq!(diff.added_inputs[0].0, 0);
+        assert_eq!(diff.added_inputs[0].1.outpoint, outpoint(1));
+        assert!(diff.removed_inputs.is_empty());
+        assert_eq!(diff.modified_inputs.len(), 1);
+        assert_eq!(diff.modified_inputs[0].new_index, 1);
+        assert_eq!(diff.modified_inputs[0].sequence, None);
+        assert!(diff.added_outputs.is_empty());
+        assert!(diff.removed_outputs.is_empty());
+        assert_eq!(
+            diff.modified_outputs,
+            vec![OutputChange {
+                script_pubkey: receiver,
+                old_index: 0,
+                new_index: 0,
+                value: Some((10_000, 30_000)),
+            }]
+        );
+
+        // Reversed, the input and its value are removed
+        let diff = TxDiff::new(&proposal, &original);
+        assert_eq!(diff.removed_inputs.len(), 1);
+        assert_eq!(diff.modified_outputs[0].value, Some((30_000, 10_000)));
+
+        let dropped_change = BitcoinTxBuilder::<MainnetEncoder>::from_tx_ref(&original)
+            .remove_output(1)
+            .build()
+            .unwrap();
+        let diff = TxDiff::new(&original, &dropped_change);
+        assert_eq!(diff.removed_outputs[0].1.script_pubkey, sender);
+    }
+}
@@ -0,0 +1,175 @@
+//! A detailed, multi-line formatter for Bitcoin transactions. This is intended for debugging and
+//! logging. The `Debug` output of the nested prefixed vector types is difficult to read.
+//!
+//! ```
+//! use bitcoins::types::{BitcoinTransaction, BitcoinTx};
+//! use coins_core::ser::ByteFormat;
+//!
+//! let tx_hex = "02000000000101ee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffff0173d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f18700cafd0700";
+//! let tx = BitcoinTx::deserialize_hex(tx_hex).unwrap();
+//! println!("{}", tx.detailed());
+//! ```
+
+use std::fmt;
+
+use coins_core::hashes::MarkedDigestOutput;
+
+use crate::types::{
+    script::{is_pubkey, ScriptPubkey, ScriptType, WitnessStackItem},
+    stack::InputSignature,
+    tx::BitcoinTransaction,
+};
+
+/// Wraps a reference to a transaction, and formats it as an annotated, multi-line description.
+/// Created by `BitcoinTransaction::detailed`.
+#[derive(Debug)]
+pub struct DetailedTx<'a, T: BitcoinTransaction>(pub(crate) &'a T);
+
+/// Describe a script pubkey by its standard type
+fn describe_script_pubkey(spk: &ScriptPubkey) -> String {
+    match spk.standard_type() {
+        ScriptType::Pkh(h) => format!("p2pkh {}", hex::encode(h.as_slice())),
+        ScriptType::Sh(h) => format!("p2sh {}", hex::encode(h.as_slice())),
+        ScriptType::Wpkh(h) => format!("p2wpkh {}", hex::encode(h.as_slice())),
+        ScriptType::Wsh(h) => format!("p2wsh {}", hex::encode(h.as_slice())),
+        ScriptType::Pk(key) => format!("p2pk {}", hex::encode(key)),
+        ScriptType::Multisig { m, pubkeys } => format!("multisig {}-of-{}", m, pubkeys.len()),
+        ScriptType::OpReturn(data) => format!("op_return {}", hex::encode(data)),
+        ScriptType::NonStandard => "non-standard".to_owned(),
+    }
+}
+
+/// Describe a stack item if it looks like a signature or a public key
+fn describe_stack_item(item: &WitnessStackItem) -> Option<String> {
+    if let Some(sig) = InputSignature::from_stack_item(item.items()) {
+        return Some(match sig.sighash() {
+            Ok(flag) => format!("signature, {}", flag),
+            Err(_) => format!("signature, unknown sighash 0x{:02x}", sig.sighash_flag),
+        });
+    }
+    if is_pubkey(item.items()) {
+        return Some("pubkey".to_owned());
+    }
+    None
+}
+
+/// Describe a locktime as a height or a timestamp
+fn describe_locktime(locktime: u32) -> &'static str {
+    match locktime {
+        0 => "none",
+        1..=499_999_999 => "block height",
+        _ => "unix timestamp",
+    }
+}
+
+/// Describe a sequence number's finality and replaceability
+fn describe_sequence(sequence: u32) -> &'static str {
+    match sequence {
+        0xffff_ffff => "final",
+        0xffff_fffe => "non-final",
+        _ => "signals RBF",
+    }
+}
+
+/// Format a byte string as hex, or `<empty>`
+fn hex_or_empty(b: &[u8]) -> String {
+    if b.is_empty() {
+        "<empty>".to_owned()
+    } else {
+        hex::encode(b)
+    }
+}
+
+impl<'a, T: BitcoinTransaction> fmt::Display for DetailedTx<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tx = self.0;
+        let witnesses = tx.witnesses();
+        let kind = if witnesses.is_empty() {
+            "legacy"
+        } else {
+            "witness"
+        };
+
+        writeln!(f, "Transaction {} ({})", tx.txid().to_be_hex(), kind)?;
+        writeln!(f, "  version:  {}", tx.version())?;
+        writeln!(
+            f,
+            "  locktime: {} ({})",
+            tx.locktime(),
+            describe_locktime(tx.locktime())
+        )?;
+
+        writeln!(f, "  inputs:   {}", tx.inputs().len())?;
+        for (i, input) in tx.inputs().iter().enumerate() {
+            writeln!(
+                f,
+                "    [{}] {}:{}",
+                i,
+                input.outpoint.txid_be_hex(),
+                input.outpoint.idx
+            )?;
+            writeln!(
+                f,
+                "        sequence:   0x{:08x} ({})",
+                input.sequence,
+                describe_sequence(input.sequence)
+            )?;
+            writeln!(
+                f,
+                "        script_sig: {}",
+                hex_or_empty(input.script_sig.items())
+            )?;
+            if let Some(witness) = witnesses.get(i) {
+                writeln!(f, "        witness:    {} items", witness.len())?;
+                for (j, item) in witness.iter().enumerate() {
+                    match describe_stack_item(item) {
+                        Some(desc) => writeln!(
+                            f,
+                            "          [{}] {} ({})",
+                            j,
+                            hex_or_empty(item.items()),
+                            desc
+                        )?,
+                        None => writeln!(f, "          [{}] {}", j, hex_or_empty(item.items()))?,
+                    }
+                }
+            }
+        }
+
+        writeln!(f, "  outputs:  {}", tx.outputs().len())?;
+        for (i, output) in tx.outputs().iter().enumerate() {
+            writeln!(f, "    [{}] {} sat", i, output.value)?;
+            writeln!(
+                f,
+                "        script_pubkey: {} ({})",
+                hex_or_empty(output.script_pubkey.items()),
+                describe_script_pubkey(&output.script_pubkey)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::tx::BitcoinTx;
+    use coins_core::{ser::ByteFormat, types::tx::Transaction};
+
+    #[test]
+    fn it_formats_detailed_transactions() {
+        let tx_hex = "0100000000010115e180dc28a2327e687facc33f10f2a20da717e5548406f7ae8b4c811072f8560100000000ffffffff0100b4f505000000001976a9141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b92888ac02483045022100df7b7e5cda14ddf91290e02ea10786e03eb11ee36ec02dd862fe9a326bbcb7fd02203f5b4496b667e6e281cc654a2da9e4f08660c620a1051337fa8965f727eb19190121038262a6c6cec93c2d3ecd6c6072efea86d02ff8e3328bbd0242b20af3425990ac00000000";
+        let tx = BitcoinTx::deserialize_hex(tx_hex).unwrap();
+        let s = tx.detailed().to_string();
+
+        assert!(s.starts_with(&format!("Transaction {} (witness)", tx.txid().to_be_hex())));
+        assert!(s.contains("locktime: 0 (none)"));
+        assert!(s.contains("sequence:   0xffffffff (final)"));
+        assert!(s.contains("script_sig: <empty>"));
+        assert!(s.contains("witness:    2 items"));
+        assert!(s.contains("(signature, SIGHASH_ALL)"));
+        assert!(s.contains("(pubkey)"));
+        assert!(s.contains("[0] 99988480 sat"));
+        assert!(s.contains("(p2pkh 1d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928)"));
+    }
+}
@@ -0,0 +1,160 @@
+//! A feerate, with conversions between common units and Core-compatible fee rounding.
+//!
+//! Feerates are quoted in sat/vbyte by wallets and explorers, in sat/kvbyte (or BTC/kvbyte) by
+//! Bitcoin Core, and in sat/kWU by Lightning implementations. One vbyte is 4 weight units.
+//!
+//! `FeeRate` stores sat/kvbyte, as Core does. Fees are rounded up, matching Core's
+//! `CFeeRate::GetFee`, so a transaction never pays less than the quoted rate.
+//!
+//! ```
+//! use bitcoins::types::FeeRate;
+//!
+//! let rate = FeeRate::from_sat_per_vb(2);
+//! assert_eq!(rate.as_sat_per_kwu(), 500);
+//! // 561 WU is 141 vbytes
+//! assert_eq!(rate.fee_for_weight(561), 282);
+//! ```
+
+use std::fmt;
+
+/// The number of weight units in a vbyte.
+const WITNESS_SCALE_FACTOR: u64 = 4;
+
+/// A feerate. Internally, a number of satoshis per 1000 vbytes.
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+)]
+#[serde(transparent)]
+pub struct FeeRate(u64);
+
+impl FeeRate {
+    /// A feerate of zero
+    pub const ZERO: FeeRate = FeeRate(0);
+
+    /// Instantiate a feerate from sat/kvbyte
+    pub const fn from_sat_per_kvb(sat_per_kvb: u64) -> Self {
+        Self(sat_per_kvb)
+    }
+
+    /// Instantiate a feerate from sat/vbyte. Saturates on overflow
+    pub const fn from_sat_per_vb(sat_per_vb: u64) -> Self {
+        Self(sat_per_vb.saturating_mul(1000))
+    }
+
+    /// Instantiate a feerate from sat/kWU. Saturates on overflow
+    pub const fn from_sat_per_kwu(sat_per_kwu: u64) -> Self {
+        Self(sat_per_kwu.saturating_mul(WITNESS_SCALE_FACTOR))
+    }
+
+    /// Instantiate a feerate from a fractional sat/vbyte value, as returned by many fee
+    /// estimation APIs. Rounds to the nearest sat/kvbyte. Negative and NaN values are zero.
+    pub fn from_sat_per_vb_f64(sat_per_vb: f64) -> Self {
+        // `as` saturates, and maps NaN to 0
+        Self((sat_per_vb * 1000.0).round() as u64)
+    }
+
+    /// Instantiate a feerate from BTC/kvbyte, as returned by Bitcoin Core's RPC. Rounds to the
+    /// nearest sat/kvbyte.
+    pub fn from_btc_per_kvb(btc_per_kvb: f64) -> Self {
+        Self((btc_per_kvb * 100_000_000.0).round() as u64)
+    }
+
+    /// The feerate in sat/kvbyte
+    pub const fn as_sat_per_kvb(self) -> u64 {
+        self.0
+    }
+
+    /// The feerate in sat/kWU, rounded down
+    pub const fn as_sat_per_kwu(self) -> u64 {
+        self.0 / WITNESS_SCALE_FACTOR
+    }
+
+    /// The feerate in sat/vbyte
+    pub fn as_sat_per_vb(self) -> f64 {
+        self.0 as f64 / 1000.0
+    }
+
+    /// The fee for a transaction of `vsize` vbytes, rounded up. Saturates on overflow
+    pub fn fee_for_vsize(self, vsize: usize) -> u64 {
+        let fee = (self.0 as u128 * vsize as u128).div_ceil(1000);
+        fee.min(u64::MAX as u128) as u64
+    }
+
+    /// The fee for a transaction of `weight` weight units. The weight is converted to vbytes
+    /// and the fee rounded up, as in Core. Saturates on overflow
+    pub fn fee_for_weight(self, weight: usize) -> u64 {
+        self.fee_for_vsize(weight.div_ceil(WITNESS_SCALE_FACTOR as usize))
+    }
+
+    /// The feerate paid by a transaction with `fee` and `vsize`, rounded down. `None` if the
+    /// vsize is 0
+    pub fn from_fee_and_vsize(fee: u64, vsize: usize) -> Option<Self> {
+        if vsize == 0 {
+            return None;
+        }
+        let rate = fee as u128 * 1000 / vsize as u128;
+        Some(Self(rate.min(u64::MAX as u128) as u64))
+    }
+}
+
+impl fmt::Display for FeeRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:03} sat/vB", self.0 / 1000, self.0 % 1000)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_converts_feerate_units() {
+        let rate = FeeRate::from_sat_per_vb(10);
+        assert_eq!(rate.as_sat_per_kvb(), 10_000);
+        assert_eq!(rate.as_sat_per_kwu(), 2_500);
+        assert_eq!(rate, FeeRate::from_sat_per_kwu(2_500));
+        assert_eq!(rate, FeeRate::from_btc_per_kvb(0.0001));
+        assert_eq!(rate, FeeRate::from_sat_per_vb_f64(10.0));
+        assert_eq!(rate.as_sat_per_vb(), 10.0);
+        assert_eq!(rate.to_string(), "10.000 sat/vB");
+
+        assert_eq!(FeeRate::from_sat_per_vb_f64(1.2345).as_sat_per_kvb(), 1_235);
+        assert_eq!(FeeRate::from_sat_per_vb_f64(-1.0), FeeRate::ZERO);
+        assert_eq!(FeeRate::from_sat_per_vb_f64(f64::NAN), FeeRate::ZERO);
+        assert_eq!(FeeRate::from_sat_per_kvb(1_001).to_string(), "1.001 sat/vB");
+    }
+
+    #[test]
+    fn it_rounds_fees_up() {
+        let rate = FeeRate::from_sat_per_kvb(1_001);
+        assert_eq!(rate.fee_for_vsize(0), 0);
+        assert_eq!(rate.fee_for_vsize(1), 2);
+        assert_eq!(rate.fee_for_vsize(1000), 1_001);
+        assert_eq!(rate.fee_for_weight(4001), 1_003);
+        assert_eq!(FeeRate::ZERO.fee_for_weight(1000), 0);
+        assert_eq!(
+            FeeRate::from_sat_per_kvb(u64::MAX).fee_for_vsize(2000),
+            u64::MAX
+        );
+
+        assert_eq!(
+            FeeRate::from_fee_and_vsize(1_001, 1000),
+            Some(FeeRate::from_sat_per_kvb(1_001))
+        );
+        assert_eq!(
+            FeeRate::from_fee_and_vsize(1, 3),
+            Some(FeeRate::from_sat_per_kvb(333))
+        );
+        assert_eq!(FeeRate::from_fee_and_vsize(1, 0), None);
+    }
+}
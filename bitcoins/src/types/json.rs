@@ -0,0 +1,240 @@
+//! Structured JSON descriptions of transactions and their components, in the style of Bitcoin
+//! Core's `decoderawtransaction`. Descriptions are built with `serde_json`, so all values are
+//! escaped correctly.
+//!
+//! The address of a script pubkey depends on the network, so addresses are included only when an
+//! encoder is supplied.
+//!
+//! ```
+//! use bitcoins::{enc::MainnetEncoder, types::{BitcoinTransaction, BitcoinTx}};
+//! use coins_core::ser::ByteFormat;
+//!
+//! let tx_hex = "02000000000101ee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffff0173d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f18700cafd0700";
+//! let tx = BitcoinTx::deserialize_hex(tx_hex).unwrap();
+//!
+//! let json = tx.to_json_value_with::<MainnetEncoder>();
+//! assert_eq!(json["vout"][0]["script_pubkey"]["type"], "p2sh");
+//! assert_eq!(json["vout"][0]["script_pubkey"]["address"], "3JoCkhfh6juGAp43jWftoSqJwoPptJ7GwF");
+//! ```
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    enc::encoder::BitcoinEncoderMarker,
+    types::{
+        script::{BitcoinScript, Script, ScriptPubkey, ScriptSig, ScriptType, Witness},
+        tx::BitcoinTransaction,
+        txin::BitcoinTxIn,
+        txout::TxOut,
+    },
+};
+
+/// Encode the address of a script pubkey, if it has one
+pub(crate) type AddressFn = fn(&ScriptPubkey) -> Option<String>;
+
+/// Never produce an address
+pub(crate) fn no_address(_: &ScriptPubkey) -> Option<String> {
+    None
+}
+
+/// Produce an address with the encoder `E`
+pub(crate) fn encoder_address<E: BitcoinEncoderMarker>(spk: &ScriptPubkey) -> Option<String> {
+    spk.to_address::<E>().ok().map(|a| a.as_string())
+}
+
+/// The name of a script type, as used in the `type` field
+fn script_type_name(script_type: &ScriptType) -> &'static str {
+    match script_type {
+        ScriptType::Pkh(_) => "p2pkh",
+        ScriptType::Sh(_) => "p2sh",
+        ScriptType::Wpkh(_) => "p2wpkh",
+        ScriptType::Wsh(_) => "p2wsh",
+        ScriptType::Pk(_) => "p2pk",
+        ScriptType::Multisig { .. } => "multisig",
+        ScriptType::OpReturn(_) => "op_return",
+        ScriptType::NonStandard => "nonstandard",
+    }
+}
+
+/// Describe any script by its hex and asm
+fn script_json<S: BitcoinScript>(script: &S) -> Map<String, Value> {
+    let mut m = Map::new();
+    m.insert("hex".to_owned(), hex::encode(script.as_ref()).into());
+    m.insert("asm".to_owned(), script.to_asm().into());
+    m
+}
+
+fn script_pubkey_json(spk: &ScriptPubkey, address: AddressFn) -> Value {
+    let mut m = script_json(spk);
+    let script_type = spk.standard_type();
+    m.insert("type".to_owned(), script_type_name(&script_type).into());
+    if let ScriptType::Multisig { m: required, .. } = script_type {
+        m.insert("required".to_owned(), required.into());
+    }
+    if let Some(address) = address(spk) {
+        m.insert("address".to_owned(), address.into());
+    }
+    m.into()
+}
+
+fn txout_json(output: &TxOut, address: AddressFn) -> Value {
+    json!({
+        "value": output.value,
+        "script_pubkey": script_pubkey_json(&output.script_pubkey, address),
+    })
+}
+
+fn txin_json(input: &BitcoinTxIn, witness: Option<&Witness>) -> Value {
+    let mut m = Map::new();
+    m.insert("txid".to_owned(), input.outpoint.txid_be_hex().into());
+    m.insert("vout".to_owned(), input.outpoint.idx.into());
+    m.insert(
+        "script_sig".to_owned(),
+        script_json(&input.script_sig).into(),
+    );
+    m.insert("sequence".to_owned(), input.sequence.into());
+    if let Some(witness) = witness {
+        let items: Vec<Value> = witness
+            .iter()
+            .map(|item| hex::encode(item.items()).into())
+            .collect();
+        m.insert("witness".to_owned(), items.into());
+    }
+    m.into()
+}
+
+pub(crate) fn tx_json<T: BitcoinTransaction>(tx: &T, address: AddressFn) -> Value {
+    let witnesses = tx.witnesses();
+    let vin: Vec<Value> = tx
+        .inputs()
+        .iter()
+        .enumerate()
+        .map(|(i, input)| txin_json(input, witnesses.get(i)))
+        .collect();
+    let vout: Vec<Value> = tx
+        .outputs()
+        .iter()
+        .enumerate()
+        .map(|(n, output)| {
+            let mut v = txout_json(output, address);
+            v["n"] = n.into();
+            v
+        })
+        .collect();
+    json!({
+        "txid": tx.txid().to_be_hex(),
+        "version": tx.version(),
+        "locktime": tx.locktime(),
+        "size": tx.serialized_length(),
+        "vsize": tx.vsize(),
+        "weight": tx.weight(),
+        "vin": vin,
+        "vout": vout,
+    })
+}
+
+impl Script {
+    /// Describe the script as a JSON object with its hex and asm
+    pub fn to_json_value(&self) -> Value {
+        script_json(self).into()
+    }
+}
+
+impl ScriptSig {
+    /// Describe the script sig as a JSON object with its hex and asm
+    pub fn to_json_value(&self) -> Value {
+        script_json(self).into()
+    }
+}
+
+impl ScriptPubkey {
+    /// Describe the script pubkey as a JSON object with its hex, asm, and standard type
+    pub fn to_json_value(&self) -> Value {
+        script_pubkey_json(self, no_address)
+    }
+
+    /// Describe the script pubkey as a JSON object with its hex, asm, standard type, and its
+    /// address under the encoder `E`, if it has one
+    pub fn to_json_value_with<E: BitcoinEncoderMarker>(&self) -> Value {
+        script_pubkey_json(self, encoder_address::<E>)
+    }
+}
+
+impl BitcoinTxIn {
+    /// Describe the input as a JSON object. Witnesses are stored in the transaction, so they are
+    /// included only by `BitcoinTransaction::to_json_value`.
+    pub fn to_json_value(&self) -> Value {
+        txin_json(self, None)
+    }
+}
+
+impl TxOut {
+    /// Describe the output as a JSON object with its value and script pubkey
+    pub fn to_json_value(&self) -> Value {
+        txout_json(self, no_address)
+    }
+
+    /// Describe the output as a JSON object, including the address of its script pubkey under
+    /// the encoder `E`, if it has one
+    pub fn to_json_value_with<E: BitcoinEncoderMarker>(&self) -> Value {
+        txout_json(self, encoder_address::<E>)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{enc::encoder::MainnetEncoder, types::tx::BitcoinTx};
+    use coins_core::{ser::ByteFormat, types::tx::Transaction};
+
+    #[test]
+    fn it_describes_transactions_as_json() {
+        let tx_hex = "0100000000010115e180dc28a2327e687facc33f10f2a20da717e5548406f7ae8b4c811072f8560100000000ffffffff0100b4f505000000001976a9141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b92888ac02483045022100df7b7e5cda14ddf91290e02ea10786e03eb11ee36ec02dd862fe9a326bbcb7fd02203f5b4496b667e6e281cc654a2da9e4f08660c620a1051337fa8965f727eb19190121038262a6c6cec93c2d3ecd6c6072efea86d02ff8e3328bbd0242b20af3425990ac00000000";
+        let tx = BitcoinTx::deserialize_hex(tx_hex).unwrap();
+
+        let json = tx.to_json_value();
+        assert_eq!(json["version"], 1);
+        assert_eq!(json["size"], tx_hex.len() / 2);
+        assert_eq!(json["vin"][0]["vout"], 1);
+        assert_eq!(json["vin"][0]["sequence"], 0xffff_ffffu32);
+        assert_eq!(json["vin"][0]["script_sig"]["hex"], "");
+        assert_eq!(json["vin"][0]["witness"].as_array().unwrap().len(), 2);
+        let spk = &json["vout"][0]["script_pubkey"];
+        assert_eq!(json["vout"][0]["value"], 99_988_480);
+        assert_eq!(json["vout"][0]["n"], 0);
+        assert_eq!(spk["type"], "p2pkh");
+        assert_eq!(
+            spk["asm"],
+            "OP_DUP OP_HASH160 <1d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928> OP_EQUALVERIFY OP_CHECKSIG"
+        );
+        assert!(spk.get("address").is_none());
+
+        let json = tx.to_json_value_with::<MainnetEncoder>();
+        assert_eq!(
+            json["vout"][0]["script_pubkey"]["address"],
+            "13gv9XbKJPxxRF8Zm1LsVKeeiMCFguQPqm"
+        );
+
+        // Legacy inputs have no witness field
+        let input = tx.inputs()[0].to_json_value();
+        assert!(input.get("witness").is_none());
+        assert_eq!(
+            input["txid"],
+            "56f87210814c8baef7068454e517a70da2f2103fc3ac7f687e32a228dc80e115"
+        );
+    }
+
+    #[test]
+    fn it_describes_multisig_and_op_return_scripts() {
+        let multisig = ScriptPubkey::from_asm_or_hex("OP_1 <0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798> <02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5> OP_2 OP_CHECKMULTISIG").unwrap();
+        let json = multisig.to_json_value_with::<MainnetEncoder>();
+        assert_eq!(json["type"], "multisig");
+        assert_eq!(json["required"], 1);
+        assert!(json.get("address").is_none());
+
+        let op_return = ScriptPubkey::from_asm_or_hex("OP_RETURN <68656c6c6f>").unwrap();
+        let json = TxOut::new(0, op_return).to_json_value();
+        assert_eq!(json["script_pubkey"]["type"], "op_return");
+        assert_eq!(json["script_pubkey"]["hex"], "6a0568656c6c6f");
+    }
+}
@@ -50,7 +50,7 @@ use crate::{
 ///
 /// After signing the digest, you MUST append the sighash indicator
 /// byte to the resulting signature.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Eq, PartialEq)]
 pub struct LegacySighashArgs {
     /// The index of the input we'd like to sign
     pub index: usize,
@@ -73,7 +73,7 @@ impl From<&crate::types::witness::WitnessSighashArgs> for LegacySighashArgs {
 }
 
 /// A Legacy (non-witness) Transaction.
-#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Eq, PartialEq, Default)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Eq, PartialEq, Hash, Default)]
 pub struct LegacyTx {
     /// The version number. Usually 1 or 2.
     pub(crate) version: u32,
@@ -85,6 +85,24 @@ pub struct LegacyTx {
     pub(crate) locktime: u32,
 }
 
+/// Read a length-prefixed vector of inputs, enforcing the caps in `limits`
+pub(crate) fn read_vin<R: Read>(reader: &mut R, limits: &TxLimits) -> TxResult<Vin> {
+    let len = ser::read_prefix_len(reader, limits.max_inputs)?;
+    let vin = (0..len)
+        .map(|_| BitcoinTxIn::read_limited(reader, limits))
+        .collect::<ser::SerResult<_>>()?;
+    Ok(vin)
+}
+
+/// Read a length-prefixed vector of outputs, enforcing the caps in `limits`
+pub(crate) fn read_vout<R: Read>(reader: &mut R, limits: &TxLimits) -> TxResult<Vout> {
+    let len = ser::read_prefix_len(reader, limits.max_outputs)?;
+    let vout = (0..len)
+        .map(|_| TxOut::read_limited(reader, limits))
+        .collect::<ser::SerResult<_>>()?;
+    Ok(vout)
+}
+
 impl LegacyTx {
     /// Performs steps 6, 7, and 8 of the sighash setup described here:
     /// https://en.bitcoin.it/wiki/OP_CHECKSIG#How_it_works
@@ -106,6 +124,23 @@ impl LegacyTx {
         copy_tx
     }
 
+    /// Deserialize a legacy transaction, enforcing the caps in `limits`
+    pub fn read_from_limited<R>(reader: &mut R, limits: &TxLimits) -> TxResult<Self>
+    where
+        R: Read,
+    {
+        let version = coins_core::ser::read_u32_le(reader)?;
+        let vin = read_vin(reader, limits)?;
+        let vout = read_vout(reader, limits)?;
+        let locktime = coins_core::ser::read_u32_le(reader)?;
+        Ok(Self {
+            version,
+            vin,
+            vout,
+            locktime,
+        })
+    }
+
     /// Modifies copy_tx according to legacy SIGHASH_SINGLE semantics.
     ///
     /// For Legacy sighash documentation, see here:
@@ -137,6 +172,69 @@ impl LegacyTx {
     fn legacy_sighash_anyone_can_pay(copy_tx: &mut Self, index: usize) {
         copy_tx.vin = vec![copy_tx.vin[index].clone()];
     }
+
+    /// Modifies copy_tx according to legacy SIGHASH_NONE semantics. All outputs are removed,
+    /// and the sequence numbers of other inputs are zeroed.
+    fn legacy_sighash_none(copy_tx: &mut Self, index: usize) {
+        copy_tx.vout = vec![];
+        for (i, txin) in copy_tx.vin.iter_mut().enumerate() {
+            if i != index {
+                txin.sequence = 0;
+            }
+        }
+    }
+
+    /// Writes the legacy sighash preimage for any sighash flag, including SIGHASH_NONE.
+    fn write_legacy_preimage<W: Write>(
+        &self,
+        writer: &mut W,
+        args: &LegacySighashArgs,
+    ) -> TxResult<()> {
+        let mut copy_tx: Self = self.legacy_sighash_prep(args.index, &args.prevout_script);
+        match args.sighash_flag {
+            Sighash::Single | Sighash::SingleAcp => {
+                if args.index >= self.outputs().len() {
+                    return Err(TxError::SighashSingleBug(args.index));
+                }
+                Self::legacy_sighash_single(&mut copy_tx, args.index);
+            }
+            Sighash::None | Sighash::NoneAcp => Self::legacy_sighash_none(&mut copy_tx, args.index),
+            _ => {}
+        }
+
+        if args.sighash_flag as u8 & 0x80 == 0x80 {
+            Self::legacy_sighash_anyone_can_pay(&mut copy_tx, args.index);
+        }
+
+        copy_tx.write_to(writer)?;
+        coins_core::ser::write_u32_le(writer, args.sighash_flag as u32)?;
+
+        Ok(())
+    }
+
+    /// Writes the legacy sighash preimage to the provided writer. Unlike
+    /// `write_sighash_preimage`, this permits SIGHASH_NONE and SIGHASH_NONE | ANYONECANPAY.
+    ///
+    /// SIGHASH_NONE signatures do not commit to the outputs, so anyone may redirect the funds
+    /// they authorize. Use this only when a protocol requires it.
+    pub fn write_sighash_preimage_allowing_none<W: Write>(
+        &self,
+        writer: &mut W,
+        args: &LegacySighashArgs,
+    ) -> TxResult<()> {
+        self.write_legacy_preimage(writer, args)
+    }
+
+    /// Calculates the legacy sighash, permitting SIGHASH_NONE. See
+    /// `write_sighash_preimage_allowing_none`.
+    pub fn sighash_allowing_none(
+        &self,
+        args: &LegacySighashArgs,
+    ) -> TxResult<DigestOutput<Hash256>> {
+        let mut w = Hash256::default();
+        self.write_legacy_preimage(&mut w, args)?;
+        Ok(w.finalize())
+    }
 }
 
 impl Transaction for LegacyTx {
@@ -196,23 +294,7 @@ impl Transaction for LegacyTx {
         if args.sighash_flag == Sighash::None || args.sighash_flag == Sighash::NoneAcp {
             return Err(TxError::NoneUnsupported);
         }
-
-        let mut copy_tx: Self = self.legacy_sighash_prep(args.index, &args.prevout_script);
-        if args.sighash_flag == Sighash::Single || args.sighash_flag == Sighash::SingleAcp {
-            if args.index >= self.outputs().len() {
-                return Err(TxError::SighashSingleBug);
-            }
-            Self::legacy_sighash_single(&mut copy_tx, args.index);
-        }
-
-        if args.sighash_flag as u8 & 0x80 == 0x80 {
-            Self::legacy_sighash_anyone_can_pay(&mut copy_tx, args.index);
-        }
-
-        copy_tx.write_to(writer)?;
-        coins_core::ser::write_u32_le(writer, args.sighash_flag as u32)?;
-
-        Ok(())
+        self.write_legacy_preimage(writer, args)
     }
 }
 
@@ -260,16 +342,7 @@ impl ByteFormat for LegacyTx {
         R: Read,
         Self: std::marker::Sized,
     {
-        let version = coins_core::ser::read_u32_le(reader)?;
-        let vin = ser::read_prefix_vec(reader)?;
-        let vout = ser::read_prefix_vec(reader)?;
-        let locktime = coins_core::ser::read_u32_le(reader)?;
-        Ok(Self {
-            version,
-            vin,
-            vout,
-            locktime,
-        })
+        Self::read_from_limited(reader, &TxLimits::default())
     }
 
     fn write_to<W>(&self, writer: &mut W) -> Result<usize, Self::Error>
@@ -2,18 +2,44 @@
 //! Extends the `Transaction` trait to maintain a type distinction between Legacy and Witness
 //! transactions (and allow conversion from one to the other).
 
+pub mod amount;
+pub mod block;
+pub mod ctv;
+pub mod diff;
+pub mod display;
+pub mod feerate;
+pub mod json;
 pub mod legacy;
+pub mod opcodes;
 pub mod script;
+pub mod script_num;
+pub mod sighash;
+pub mod stack;
+pub mod template;
+pub mod timelock;
 pub mod tx;
 pub mod txin;
 pub mod txout;
 pub mod utxo;
 pub mod witness;
+pub mod witness_program;
 
+pub use amount::*;
+pub use block::*;
+pub use diff::*;
+pub use display::*;
+pub use feerate::*;
 pub use legacy::*;
+pub use opcodes::*;
 pub use script::*;
+pub use script_num::*;
+pub use sighash::*;
+pub use stack::*;
+pub use template::*;
+pub use timelock::*;
 pub use tx::*;
 pub use txin::*;
 pub use txout::*;
 pub use utxo::*;
 pub use witness::*;
+pub use witness_program::*;
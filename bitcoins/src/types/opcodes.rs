@@ -0,0 +1,529 @@
+//! Script opcodes, and an iterator over the instructions in a script.
+//!
+//! `BitcoinScript::instructions` splits a script into opcodes and the data they push, so that
+//! scripts can be inspected without a hand-written byte-level parser.
+//!
+//! ```
+//! use bitcoins::types::{BitcoinScript, Opcode, ScriptPubkey};
+//!
+//! let spk = ScriptPubkey::from_hex("76a9141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b92888ac").unwrap();
+//! let ops: Vec<Opcode> = spk.instructions().map(|i| i.unwrap().opcode).collect();
+//! assert_eq!(ops[..2], [Opcode::Dup, Opcode::Hash160]);
+//! assert_eq!(ops[2], Opcode::PushBytes(20));
+//! ```
+
+use std::fmt;
+
+use thiserror::Error;
+
+macro_rules! named_opcodes {
+    ($($(#[$doc:meta])* $variant:ident = $byte:literal => $name:literal,)*) => {
+        /// A script opcode. Pushes and small integers are grouped into `PushBytes` and `PushNum`.
+        /// Undefined opcodes are `Invalid`.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Opcode {
+            /// Push the next 0 to 75 bytes. `OP_0` is `PushBytes(0)`
+            PushBytes(u8),
+            /// Push a number of bytes given by the next byte
+            PushData1,
+            /// Push a number of bytes given by the next 2 bytes
+            PushData2,
+            /// Push a number of bytes given by the next 4 bytes
+            PushData4,
+            /// Push the number 1 through 16. `OP_1` through `OP_16`
+            PushNum(u8),
+            $($(#[$doc])* $variant,)*
+            /// An undefined opcode
+            Invalid(u8),
+        }
+
+        impl Opcode {
+            /// Return the byte value of the opcode
+            pub fn to_u8(self) -> u8 {
+                match self {
+                    Opcode::PushBytes(n) => n,
+                    Opcode::PushData1 => 0x4c,
+                    Opcode::PushData2 => 0x4d,
+                    Opcode::PushData4 => 0x4e,
+                    Opcode::PushNum(n) => 0x50 + n,
+                    $(Opcode::$variant => $byte,)*
+                    Opcode::Invalid(b) => b,
+                }
+            }
+
+            /// Return the opcode with the byte value `b`
+            pub fn from_u8(b: u8) -> Self {
+                match b {
+                    0x00..=0x4b => Opcode::PushBytes(b),
+                    0x4c => Opcode::PushData1,
+                    0x4d => Opcode::PushData2,
+                    0x4e => Opcode::PushData4,
+                    0x51..=0x60 => Opcode::PushNum(b - 0x50),
+                    $($byte => Opcode::$variant,)*
+                    _ => Opcode::Invalid(b),
+                }
+            }
+
+            /// Look up a named opcode, without the `OP_` prefix. Small integers are `"0"`
+            /// through `"16"`. Pushes of 1 to 75 bytes have no name.
+            pub fn from_name(name: &str) -> Option<Self> {
+                let op = match name {
+                    "0" | "FALSE" => Opcode::PushBytes(0),
+                    "TRUE" => Opcode::PushNum(1),
+                    "PUSHDATA1" => Opcode::PushData1,
+                    "PUSHDATA2" => Opcode::PushData2,
+                    "PUSHDATA4" => Opcode::PushData4,
+                    "NOP2" => Opcode::CheckLockTimeVerify,
+                    "NOP3" => Opcode::CheckSequenceVerify,
+                    $($name => Opcode::$variant,)*
+                    _ => match name.parse::<u8>() {
+                        Ok(n @ 1..=16) => Opcode::PushNum(n),
+                        _ => return None,
+                    },
+                };
+                Some(op)
+            }
+
+            /// The name of the opcode, without the `OP_` prefix. `None` for pushes of 1 to 75
+            /// bytes and undefined opcodes.
+            pub fn name(self) -> Option<String> {
+                let name = match self {
+                    Opcode::PushBytes(0) => "0",
+                    Opcode::PushBytes(_) | Opcode::Invalid(_) => return None,
+                    Opcode::PushData1 => "PUSHDATA1",
+                    Opcode::PushData2 => "PUSHDATA2",
+                    Opcode::PushData4 => "PUSHDATA4",
+                    Opcode::PushNum(n) => return Some(n.to_string()),
+                    $(Opcode::$variant => $name,)*
+                };
+                Some(name.to_owned())
+            }
+        }
+    };
+}
+
+named_opcodes! {
+    /// `OP_1NEGATE`
+    Negative1 = 0x4f => "1NEGATE",
+    /// `OP_RESERVED`
+    Reserved = 0x50 => "RESERVED",
+    /// `OP_NOP`
+    Nop = 0x61 => "NOP",
+    /// `OP_VER`
+    Ver = 0x62 => "VER",
+    /// `OP_IF`
+    If = 0x63 => "IF",
+    /// `OP_NOTIF`
+    NotIf = 0x64 => "NOTIF",
+    /// `OP_VERIF`
+    VerIf = 0x65 => "VERIF",
+    /// `OP_VERNOTIF`
+    VerNotIf = 0x66 => "VERNOTIF",
+    /// `OP_ELSE`
+    Else = 0x67 => "ELSE",
+    /// `OP_ENDIF`
+    EndIf = 0x68 => "ENDIF",
+    /// `OP_VERIFY`
+    Verify = 0x69 => "VERIFY",
+    /// `OP_RETURN`
+    Return = 0x6a => "RETURN",
+    /// `OP_TOALTSTACK`
+    ToAltStack = 0x6b => "TOALTSTACK",
+    /// `OP_FROMALTSTACK`
+    FromAltStack = 0x6c => "FROMALTSTACK",
+    /// `OP_2DROP`
+    TwoDrop = 0x6d => "2DROP",
+    /// `OP_2DUP`
+    TwoDup = 0x6e => "2DUP",
+    /// `OP_3DUP`
+    ThreeDup = 0x6f => "3DUP",
+    /// `OP_2OVER`
+    TwoOver = 0x70 => "2OVER",
+    /// `OP_2ROT`
+    TwoRot = 0x71 => "2ROT",
+    /// `OP_2SWAP`
+    TwoSwap = 0x72 => "2SWAP",
+    /// `OP_IFDUP`
+    IfDup = 0x73 => "IFDUP",
+    /// `OP_DEPTH`
+    Depth = 0x74 => "DEPTH",
+    /// `OP_DROP`
+    Drop = 0x75 => "DROP",
+    /// `OP_DUP`
+    Dup = 0x76 => "DUP",
+    /// `OP_NIP`
+    Nip = 0x77 => "NIP",
+    /// `OP_OVER`
+    Over = 0x78 => "OVER",
+    /// `OP_PICK`
+    Pick = 0x79 => "PICK",
+    /// `OP_ROLL`
+    Roll = 0x7a => "ROLL",
+    /// `OP_ROT`
+    Rot = 0x7b => "ROT",
+    /// `OP_SWAP`
+    Swap = 0x7c => "SWAP",
+    /// `OP_TUCK`
+    Tuck = 0x7d => "TUCK",
+    /// `OP_CAT`
+    Cat = 0x7e => "CAT",
+    /// `OP_SUBSTR`
+    Substr = 0x7f => "SUBSTR",
+    /// `OP_LEFT`
+    Left = 0x80 => "LEFT",
+    /// `OP_RIGHT`
+    Right = 0x81 => "RIGHT",
+    /// `OP_SIZE`
+    Size = 0x82 => "SIZE",
+    /// `OP_INVERT`
+    Invert = 0x83 => "INVERT",
+    /// `OP_AND`
+    And = 0x84 => "AND",
+    /// `OP_OR`
+    Or = 0x85 => "OR",
+    /// `OP_XOR`
+    Xor = 0x86 => "XOR",
+    /// `OP_EQUAL`
+    Equal = 0x87 => "EQUAL",
+    /// `OP_EQUALVERIFY`
+    EqualVerify = 0x88 => "EQUALVERIFY",
+    /// `OP_RESERVED1`
+    Reserved1 = 0x89 => "RESERVED1",
+    /// `OP_RESERVED2`
+    Reserved2 = 0x8a => "RESERVED2",
+    /// `OP_1ADD`
+    OneAdd = 0x8b => "1ADD",
+    /// `OP_1SUB`
+    OneSub = 0x8c => "1SUB",
+    /// `OP_2MUL`
+    TwoMul = 0x8d => "2MUL",
+    /// `OP_2DIV`
+    TwoDiv = 0x8e => "2DIV",
+    /// `OP_NEGATE`
+    Negate = 0x8f => "NEGATE",
+    /// `OP_ABS`
+    Abs = 0x90 => "ABS",
+    /// `OP_NOT`
+    Not = 0x91 => "NOT",
+    /// `OP_0NOTEQUAL`
+    ZeroNotEqual = 0x92 => "0NOTEQUAL",
+    /// `OP_ADD`
+    Add = 0x93 => "ADD",
+    /// `OP_SUB`
+    Sub = 0x94 => "SUB",
+    /// `OP_MUL`
+    Mul = 0x95 => "MUL",
+    /// `OP_DIV`
+    Div = 0x96 => "DIV",
+    /// `OP_MOD`
+    Mod = 0x97 => "MOD",
+    /// `OP_LSHIFT`
+    LShift = 0x98 => "LSHIFT",
+    /// `OP_RSHIFT`
+    RShift = 0x99 => "RSHIFT",
+    /// `OP_BOOLAND`
+    BoolAnd = 0x9a => "BOOLAND",
+    /// `OP_BOOLOR`
+    BoolOr = 0x9b => "BOOLOR",
+    /// `OP_NUMEQUAL`
+    NumEqual = 0x9c => "NUMEQUAL",
+    /// `OP_NUMEQUALVERIFY`
+    NumEqualVerify = 0x9d => "NUMEQUALVERIFY",
+    /// `OP_NUMNOTEQUAL`
+    NumNotEqual = 0x9e => "NUMNOTEQUAL",
+    /// `OP_LESSTHAN`
+    LessThan = 0x9f => "LESSTHAN",
+    /// `OP_GREATERTHAN`
+    GreaterThan = 0xa0 => "GREATERTHAN",
+    /// `OP_LESSTHANOREQUAL`
+    LessThanOrEqual = 0xa1 => "LESSTHANOREQUAL",
+    /// `OP_GREATERTHANOREQUAL`
+    GreaterThanOrEqual = 0xa2 => "GREATERTHANOREQUAL",
+    /// `OP_MIN`
+    Min = 0xa3 => "MIN",
+    /// `OP_MAX`
+    Max = 0xa4 => "MAX",
+    /// `OP_WITHIN`
+    Within = 0xa5 => "WITHIN",
+    /// `OP_RIPEMD160`
+    Ripemd160 = 0xa6 => "RIPEMD160",
+    /// `OP_SHA1`
+    Sha1 = 0xa7 => "SHA1",
+    /// `OP_SHA256`
+    Sha256 = 0xa8 => "SHA256",
+    /// `OP_HASH160`
+    Hash160 = 0xa9 => "HASH160",
+    /// `OP_HASH256`
+    Hash256 = 0xaa => "HASH256",
+    /// `OP_CODESEPARATOR`
+    CodeSeparator = 0xab => "CODESEPARATOR",
+    /// `OP_CHECKSIG`
+    CheckSig = 0xac => "CHECKSIG",
+    /// `OP_CHECKSIGVERIFY`
+    CheckSigVerify = 0xad => "CHECKSIGVERIFY",
+    /// `OP_CHECKMULTISIG`
+    CheckMultisig = 0xae => "CHECKMULTISIG",
+    /// `OP_CHECKMULTISIGVERIFY`
+    CheckMultisigVerify = 0xaf => "CHECKMULTISIGVERIFY",
+    /// `OP_NOP1`
+    Nop1 = 0xb0 => "NOP1",
+    /// `OP_CHECKLOCKTIMEVERIFY`, formerly `OP_NOP2`
+    CheckLockTimeVerify = 0xb1 => "CHECKLOCKTIMEVERIFY",
+    /// `OP_CHECKSEQUENCEVERIFY`, formerly `OP_NOP3`
+    CheckSequenceVerify = 0xb2 => "CHECKSEQUENCEVERIFY",
+    /// `OP_NOP4`
+    Nop4 = 0xb3 => "NOP4",
+    /// `OP_NOP5`
+    Nop5 = 0xb4 => "NOP5",
+    /// `OP_NOP6`
+    Nop6 = 0xb5 => "NOP6",
+    /// `OP_NOP7`
+    Nop7 = 0xb6 => "NOP7",
+    /// `OP_NOP8`
+    Nop8 = 0xb7 => "NOP8",
+    /// `OP_NOP9`
+    Nop9 = 0xb8 => "NOP9",
+    /// `OP_NOP10`
+    Nop10 = 0xb9 => "NOP10",
+    /// `OP_CHECKSIGADD`, in tapscript only
+    CheckSigAdd = 0xba => "CHECKSIGADD",
+}
+
+impl Opcode {
+    /// True if the opcode only pushes data or a number. Scripts made only of these are
+    /// push-only.
+    pub fn is_push(self) -> bool {
+        self.to_u8() <= 0x60
+    }
+
+    /// The number pushed by `OP_1NEGATE`, `OP_0`, and `OP_1` through `OP_16`, if any
+    pub fn small_int(self) -> Option<i8> {
+        match self {
+            Opcode::Negative1 => Some(-1),
+            Opcode::PushBytes(0) => Some(0),
+            Opcode::PushNum(n) => Some(n as i8),
+            _ => None,
+        }
+    }
+}
+
+impl From<u8> for Opcode {
+    fn from(b: u8) -> Self {
+        Self::from_u8(b)
+    }
+}
+
+impl From<Opcode> for u8 {
+    fn from(op: Opcode) -> u8 {
+        op.to_u8()
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "OP_{}", name),
+            None => write!(f, "0x{:02x}", self.to_u8()),
+        }
+    }
+}
+
+/// An error iterating over the instructions in a script.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum InstructionError {
+    /// A push at this offset runs past the end of the script
+    #[error("Push at offset {} runs past the end of the script", .0)]
+    Truncated(usize),
+
+    /// A push at this offset does not use the smallest possible encoding
+    #[error("Push at offset {} is not minimal", .0)]
+    NonMinimalPush(usize),
+}
+
+/// A single script instruction. Pushes carry the pushed data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction<'a> {
+    /// The opcode
+    pub opcode: Opcode,
+    /// The data pushed by `PushBytes` and `PushData` opcodes
+    pub data: Option<&'a [u8]>,
+}
+
+impl<'a> Instruction<'a> {
+    /// True if the instruction is not a data push, or pushes its data with the smallest
+    /// possible encoding. Single bytes 1 through 16 and 0x81 must use `OP_1` through `OP_16`
+    /// and `OP_1NEGATE`. This is Bitcoin Core's `MINIMALDATA` rule.
+    pub fn is_minimal_push(&self) -> bool {
+        let data = match self.data {
+            Some(data) => data,
+            None => return true,
+        };
+        let op = self.opcode.to_u8();
+        match data.len() {
+            0 => op == 0x00,
+            1 if (1..=16).contains(&data[0]) || data[0] == 0x81 => false,
+            len if len <= 75 => op as usize == len,
+            len if len <= 0xff => op == 0x4c,
+            len if len <= 0xffff => op == 0x4d,
+            _ => true,
+        }
+    }
+}
+
+/// An iterator over the instructions in a script. Yields an error and stops if a push runs
+/// past the end of the script, or, if enforcing minimal pushes, at the first non-minimal push.
+#[derive(Debug, Clone)]
+pub struct Instructions<'a> {
+    script: &'a [u8],
+    offset: usize,
+    enforce_minimal: bool,
+}
+
+impl<'a> Instructions<'a> {
+    /// Iterate over the instructions in `script`
+    pub fn new(script: &'a [u8]) -> Self {
+        Self {
+            script,
+            offset: 0,
+            enforce_minimal: false,
+        }
+    }
+
+    /// Iterate over the instructions in `script`, erroring at the first non-minimal push
+    pub fn minimal(script: &'a [u8]) -> Self {
+        Self {
+            enforce_minimal: true,
+            ..Self::new(script)
+        }
+    }
+
+    /// The offset of the next instruction in the script
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Read the next instruction. `None` if a push runs past the end of the script
+    fn read(&mut self) -> Option<Instruction<'a>> {
+        let script = self.script;
+        let mut i = self.offset;
+        let opcode = Opcode::from_u8(script[i]);
+        i += 1;
+        let len = match opcode {
+            Opcode::PushBytes(n) => n as usize,
+            Opcode::PushData1 => {
+                i += 1;
+                *script.get(i - 1)? as usize
+            }
+            Opcode::PushData2 => {
+                i += 2;
+                u16::from_le_bytes([*script.get(i - 2)?, *script.get(i - 1)?]) as usize
+            }
+            Opcode::PushData4 => {
+                let b = script.get(i..i + 4)?;
+                i += 4;
+                u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize
+            }
+            _ => {
+                self.offset = i;
+                return Some(Instruction { opcode, data: None });
+            }
+        };
+        let data = script.get(i..i.checked_add(len)?)?;
+        self.offset = i + len;
+        Some(Instruction {
+            opcode,
+            data: Some(data),
+        })
+    }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<Instruction<'a>, InstructionError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.script.len() {
+            return None;
+        }
+        let offset = self.offset;
+        let result = match self.read() {
+            Some(ins) if self.enforce_minimal && !ins.is_minimal_push() => {
+                Err(InstructionError::NonMinimalPush(offset))
+            }
+            Some(ins) => Ok(ins),
+            None => Err(InstructionError::Truncated(offset)),
+        };
+        if result.is_err() {
+            self.offset = self.script.len();
+        }
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::script::{BitcoinScript, Script, ScriptPubkey};
+
+    #[test]
+    fn it_round_trips_opcodes() {
+        for b in 0..=255u8 {
+            let op = Opcode::from_u8(b);
+            assert_eq!(op.to_u8(), b);
+            if let Some(name) = op.name() {
+                assert_eq!(Opcode::from_name(&name), Some(op));
+            }
+        }
+        assert_eq!(Opcode::from_name("NOP2"), Some(Opcode::CheckLockTimeVerify));
+        assert_eq!(Opcode::from_name("17"), None);
+        assert_eq!(Opcode::PushNum(16).to_string(), "OP_16");
+        assert_eq!(Opcode::CheckSig.to_string(), "OP_CHECKSIG");
+        assert_eq!(Opcode::PushBytes(20).to_string(), "0x14");
+        assert_eq!(Opcode::Invalid(0xff).to_string(), "0xff");
+        assert_eq!(Opcode::Negative1.small_int(), Some(-1));
+        assert!(Opcode::PushNum(16).is_push());
+        assert!(!Opcode::Nop.is_push());
+    }
+
+    #[test]
+    fn it_iterates_over_instructions() {
+        let spk = ScriptPubkey::from_asm_or_hex("OP_0 <0102> OP_CHECKSEQUENCEVERIFY").unwrap();
+        let ins: Vec<_> = spk.instructions().map(Result::unwrap).collect();
+        assert_eq!(
+            ins,
+            vec![
+                Instruction {
+                    opcode: Opcode::PushBytes(0),
+                    data: Some(&[]),
+                },
+                Instruction {
+                    opcode: Opcode::PushBytes(2),
+                    data: Some(&[1, 2]),
+                },
+                Instruction {
+                    opcode: Opcode::CheckSequenceVerify,
+                    data: None,
+                },
+            ]
+        );
+
+        let long = Script::new([vec![0x4d, 0x00, 0x01], vec![7; 256]].concat());
+        let ins: Vec<_> = long.instructions_minimal().collect();
+        assert_eq!(ins.len(), 1);
+        assert_eq!(ins[0].unwrap().data.map(<[u8]>::len), Some(256));
+
+        let truncated = Script::new(vec![0x76, 0x4c, 0x02, 0x01]);
+        let mut ins = truncated.instructions();
+        assert_eq!(ins.next().unwrap().unwrap().opcode, Opcode::Dup);
+        assert_eq!(ins.next(), Some(Err(InstructionError::Truncated(1))));
+        assert_eq!(ins.next(), None);
+
+        for non_minimal in [vec![0x01, 0x05], vec![0x4c, 0x01, 0x20], vec![0x01, 0x81]].iter() {
+            let script = Script::new(non_minimal.clone());
+            assert!(script.instructions().all(|i| i.is_ok()));
+            assert_eq!(
+                script.instructions_minimal().next(),
+                Some(Err(InstructionError::NonMinimalPush(0)))
+            );
+        }
+    }
+}
@@ -1,8 +1,11 @@
 //! Simple types for Bitcoin Script Witness stack datastructures, each of which are treated as
 //! opaque, wrapped `Vec<u8>` instance.
 //!
-//! We do not handle assembly, disassembly, or Script execution in `bitcoins`. Scripts are treated
-//! as opaque bytes vectors with no semantics.
+//! We do not handle Script execution in `bitcoins`. Scripts are treated as opaque bytes vectors
+//! with no semantics. `BitcoinScript::to_asm` and `BitcoinScript::from_asm` convert scripts to and
+//! from the familiar `OP_DUP OP_HASH160 <hex> ...` format, for debugging and interop with Bitcoin
+//! Core's RPC output. `BitcoinScript::instructions` splits a script into opcodes and pushed data,
+//! for inspection.
 //!
 //! Scripts can be freely converted between eachother using `From` and `Into`. This merely rewraps
 //! the underlying `Vec<u8>` in the new type.
@@ -23,12 +26,72 @@
 use coins_core::{
     hashes::{Digest, Hash160, Hash160Digest, Hash256Digest, MarkedDigestOutput, Sha256},
     impl_hex_serde, impl_script_conversion,
+    ser::SerError,
     types::tx::RecipientIdentifier,
     wrap_prefixed_byte_vector,
 };
 
+use crate::{
+    enc::encoder::AddressType,
+    types::{
+        opcodes::{Instruction, InstructionError, Instructions, Opcode},
+        witness_program::WitnessProgram,
+    },
+};
+
 /// A wrapped script.
-pub trait BitcoinScript {}
+pub trait BitcoinScript: AsRef<[u8]> {
+    /// Iterate over the instructions in the script
+    fn instructions(&self) -> Instructions<'_> {
+        Instructions::new(self.as_ref())
+    }
+
+    /// Iterate over the instructions in the script, erroring at the first push that does not use
+    /// the smallest possible encoding
+    fn instructions_minimal(&self) -> Instructions<'_> {
+        Instructions::minimal(self.as_ref())
+    }
+
+    /// Disassemble the script to a whitespace-separated ASM string, like
+    /// `OP_DUP OP_HASH160 <0e5c...4a44> OP_EQUALVERIFY OP_CHECKSIG`. Pushes are written as hex
+    /// in angle brackets, and `OP_0` through `OP_16` by name. Undefined opcodes, and the
+    /// remainder of a script whose last push is truncated, are written as raw `0x`-prefixed hex.
+    fn to_asm(&self) -> String {
+        let script = self.as_ref();
+        self.instructions()
+            .map(|ins| match ins {
+                Ok(Instruction {
+                    opcode: Opcode::PushBytes(0),
+                    ..
+                }) => "OP_0".to_owned(),
+                Ok(Instruction {
+                    data: Some(data), ..
+                }) => format!("<{}>", hex::encode(data)),
+                Ok(Instruction { opcode, .. }) => opcode.to_string(),
+                Err(InstructionError::Truncated(offset))
+                | Err(InstructionError::NonMinimalPush(offset)) => {
+                    format!("0x{}", hex::encode(&script[offset..]))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Assemble a script from a whitespace-separated ASM string. Opcodes are written by name,
+    /// with the `OP_` prefix. Data pushes may be written as bare hex or wrapped in angle
+    /// brackets, and are encoded minimally. The tokens `-1` and `0` through `16` are the
+    /// small-integer opcodes, matching Bitcoin Core's ASM output. Tokens prefixed with `0x` are
+    /// inserted as raw bytes.
+    ///
+    /// Scripts output by `to_asm` assemble to the same bytes, unless they contain non-minimal
+    /// pushes.
+    fn from_asm(s: &str) -> Result<Self, SerError>
+    where
+        Self: From<Vec<u8>> + Sized,
+    {
+        parse_asm(s).map(Self::from)
+    }
+}
 
 wrap_prefixed_byte_vector!(
     /// A Script is marked Vec<u8> for use as an opaque `Script` in `SighashArgs`
@@ -91,16 +154,58 @@ pub type Witness = Vec<WitnessStackItem>;
 /// A TxWitness is the UNPREFIXED vector of witnesses
 pub type TxWitness = Vec<Witness>;
 
+/// The largest number of keys in a multisig script built by `Script::sorted_multisig`. Larger
+/// key counts can't be encoded as a small-integer opcode.
+pub const MAX_SORTED_MULTISIG_KEYS: usize = 16;
+
+impl Script {
+    /// Instantiate an `m`-of-`n` `CHECKMULTISIG` script. The keys are serialized in compressed
+    /// form and sorted lexicographically, per BIP67, so that all cosigners produce the same script
+    /// regardless of key order. Returns `None` if `m` is 0, `m` exceeds the number of keys, or
+    /// there are more than `MAX_SORTED_MULTISIG_KEYS` keys.
+    pub fn sorted_multisig<K>(m: usize, keys: &[K]) -> Option<Self>
+    where
+        K: AsRef<coins_bip32::ecdsa::VerifyingKey>,
+    {
+        let n = keys.len();
+        if m == 0 || m > n || n > MAX_SORTED_MULTISIG_KEYS {
+            return None;
+        }
+
+        let mut keys: Vec<[u8; 33]> = keys
+            .iter()
+            .map(|k| {
+                let mut buf = [0u8; 33];
+                buf.copy_from_slice(&k.as_ref().to_bytes());
+                buf
+            })
+            .collect();
+        keys.sort_unstable();
+
+        let mut v: Vec<u8> = vec![0x50 + m as u8]; // OP_m
+        for key in keys.iter() {
+            push_data(&mut v, key);
+        }
+        v.push(0x50 + n as u8); // OP_n
+        v.push(0xae); // CHECKMULTISIG
+        Some(v.into())
+    }
+}
+
 impl ScriptPubkey {
     /// Instantiate a standard p2pkh script pubkey from a pubkey.
     pub fn p2pkh<K>(key: &K) -> Self
     where
         K: AsRef<coins_bip32::ecdsa::VerifyingKey>,
     {
-        let digest = Hash160::digest(&key.as_ref().to_bytes());
+        Self::p2pkh_from_hash(&Hash160::digest(&key.as_ref().to_bytes()).into())
+    }
 
+    /// Instantiate a standard p2pkh script pubkey from a pubkey hash. This is also the BIP143
+    /// script code of P2WPKH inputs.
+    pub fn p2pkh_from_hash(pubkey_hash: &Hash160Digest) -> Self {
         let mut v: Vec<u8> = vec![0x76, 0xa9, 0x14]; // DUP, HASH160, PUSH_20
-        v.extend(&digest);
+        v.extend(pubkey_hash.as_slice());
         v.extend(&[0x88, 0xac]); // EQUALVERIFY, CHECKSIG
         v.into()
     }
@@ -131,6 +236,147 @@ impl ScriptPubkey {
         v.extend(Sha256::digest(script.as_ref()));
         v.into()
     }
+
+    /// Instantiate a p2sh script pubkey wrapping a BIP67 sorted multisig script. See
+    /// `Script::sorted_multisig`.
+    pub fn sorted_multisig_p2sh<K>(m: usize, keys: &[K]) -> Option<Self>
+    where
+        K: AsRef<coins_bip32::ecdsa::VerifyingKey>,
+    {
+        Script::sorted_multisig(m, keys).map(|script| Self::p2sh(&script))
+    }
+
+    /// Instantiate a p2wsh script pubkey wrapping a BIP67 sorted multisig script. See
+    /// `Script::sorted_multisig`.
+    pub fn sorted_multisig_p2wsh<K>(m: usize, keys: &[K]) -> Option<Self>
+    where
+        K: AsRef<coins_bip32::ecdsa::VerifyingKey>,
+    {
+        Script::sorted_multisig(m, keys).map(|script| Self::p2wsh(&script))
+    }
+
+    /// Instantiate an OP_RETURN script pubkey carrying `data` in a single minimal push. This does
+    /// not enforce the standard relay size limit.
+    pub fn op_return(data: &[u8]) -> Self {
+        let mut v: Vec<u8> = vec![0x6a]; // OP_RETURN
+        push_data(&mut v, data);
+        v.into()
+    }
+
+    /// Parse a script pubkey from either a raw (unprefixed) hex string, or a whitespace-separated
+    /// ASM string like `OP_DUP OP_HASH160 <hex> OP_EQUALVERIFY OP_CHECKSIG`.
+    ///
+    /// A string containing a single valid hex token is treated as raw hex. In ASM, data pushes
+    /// may be written as bare hex or wrapped in angle brackets, and are encoded minimally. The
+    /// tokens `-1` and `0` through `16` are interpreted as the small-integer opcodes, matching
+    /// Bitcoin Core's ASM output.
+    pub fn from_asm_or_hex(s: &str) -> Result<Self, SerError> {
+        let s = s.trim();
+        if !s.contains(char::is_whitespace) {
+            if let Ok(spk) = Self::from_hex(s) {
+                return Ok(spk);
+            }
+        }
+        parse_asm(s).map(Self::new)
+    }
+}
+
+/// A witness program nested in a P2SH output. This is used to spend segwit outputs from wallets
+/// that can only pay to legacy addresses.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NestedWitnessProgram {
+    /// The witness program, which is the P2SH redeem script
+    pub redeem_script: Script,
+    /// The P2SH script pubkey committing to the redeem script
+    pub script_pubkey: ScriptPubkey,
+    /// The script sig of inputs spending the P2SH output. It pushes the redeem script. The
+    /// signatures go in the witness.
+    pub script_sig: ScriptSig,
+}
+
+impl NestedWitnessProgram {
+    /// Wrap a witness program in P2SH. The program is not checked.
+    pub fn from_witness_program(redeem_script: Script) -> Self {
+        let mut script_sig = vec![];
+        push_data(&mut script_sig, redeem_script.items());
+        Self {
+            script_pubkey: ScriptPubkey::p2sh(&redeem_script),
+            script_sig: script_sig.into(),
+            redeem_script,
+        }
+    }
+
+    /// Instantiate a P2SH-P2WPKH program from a pubkey.
+    pub fn sh_wpkh<K>(key: &K) -> Self
+    where
+        K: AsRef<coins_bip32::ecdsa::VerifyingKey>,
+    {
+        Self::from_witness_program(ScriptPubkey::p2wpkh(key).items().into())
+    }
+
+    /// Instantiate a P2SH-P2WSH program from a witness script.
+    pub fn sh_wsh(witness_script: &Script) -> Self {
+        Self::from_witness_program(ScriptPubkey::p2wsh(witness_script).items().into())
+    }
+}
+
+/// Look up the byte value of an ASM opcode token. Named opcodes must carry the `OP_` prefix. Bare
+/// small integers are accepted, as Bitcoin Core's ASM output uses them.
+pub(crate) fn opcode_from_name(token: &str) -> Option<u8> {
+    if token == "-1" {
+        return Some(Opcode::Negative1.to_u8());
+    }
+    let op = match token.strip_prefix("OP_") {
+        Some(name) => Opcode::from_name(name)?,
+        None => match token.parse::<u8>() {
+            Ok(n) if n <= 16 => Opcode::from_name(token)?,
+            _ => return None,
+        },
+    };
+    Some(op.to_u8())
+}
+
+/// Append a minimally-encoded data push to a script
+pub(crate) fn push_data(script: &mut Vec<u8>, data: &[u8]) {
+    let len = data.len();
+    if len <= 75 {
+        script.push(len as u8);
+    } else if len <= 0xff {
+        script.push(0x4c); // PUSHDATA1
+        script.push(len as u8);
+    } else if len <= 0xffff {
+        script.push(0x4d); // PUSHDATA2
+        script.extend(&(len as u16).to_le_bytes());
+    } else {
+        script.push(0x4e); // PUSHDATA4
+        script.extend(&(len as u32).to_le_bytes());
+    }
+    script.extend(data);
+}
+
+/// Parse a whitespace-separated ASM string into script bytes
+fn parse_asm(s: &str) -> Result<Vec<u8>, SerError> {
+    let mut script = vec![];
+    for token in s.split_whitespace() {
+        if let Some(op) = opcode_from_name(token) {
+            script.push(op);
+            continue;
+        }
+        if let Some(raw) = token.strip_prefix("0x") {
+            let raw = hex::decode(raw)
+                .map_err(|_| SerError::ComponentError(format!("Invalid raw bytes: {}", token)))?;
+            script.extend(raw);
+            continue;
+        }
+        let data = token
+            .strip_prefix('<')
+            .and_then(|t| t.strip_suffix('>'))
+            .unwrap_or(token);
+        let data = hex::decode(data)
+            .map_err(|_| SerError::ComponentError(format!("Unknown ASM token: {}", token)))?;
+        push_data(&mut script, &data);
+    }
+    Ok(script)
 }
 
 /// Standard script types, and a non-standard type for all other scripts.
@@ -144,6 +390,15 @@ pub enum ScriptType {
     Wpkh(Hash160Digest),
     /// Pay to Witness Scripthash.
     Wsh(Hash256Digest),
+    /// Bare Pay to Pubkey. Holds the pubkey.
+    Pk(Vec<u8>),
+    /// Bare m-of-n multisig, with 1 to 16 pubkeys.
+    Multisig {
+        /// The number of signatures required
+        m: usize,
+        /// The pubkeys, in script order
+        pubkeys: Vec<Vec<u8>>,
+    },
     /// OP_RETURN
     OpReturn(Vec<u8>),
     /// Nonstandard or unknown `Script` type. May be a newer witness version.
@@ -152,7 +407,7 @@ pub enum ScriptType {
 
 impl ScriptPubkey {
     /// Extract the op return payload. None if not an op return. Does not extract OP_RETURN blobs
-    /// larger than 75 bytes.
+    /// larger than 255 bytes.
     pub fn extract_op_return_data(&self) -> Option<Vec<u8>> {
         // check before indexing to avoid potential panic on malformed input
         if self.len() < 2 {
@@ -162,9 +417,37 @@ impl ScriptPubkey {
         if self[0] == 0x6a && self[1] <= 75 && self[1] as usize == (self.len() - 2) {
             return Some(self.0[2..].to_vec());
         }
+        // PUSHDATA1, which is minimal only for pushes longer than 75 bytes
+        if self.len() > 3
+            && self[0] == 0x6a
+            && self[1] == 0x4c
+            && self[2] > 75
+            && self[2] as usize == (self.len() - 3)
+        {
+            return Some(self.0[3..].to_vec());
+        }
         None
     }
 
+    /// Determine the address type of the script pubkey. `None` for OP_RETURN and non-standard
+    /// scripts. Unlike `standard_type`, this recognizes taproot outputs.
+    pub fn address_type(&self) -> Option<AddressType> {
+        match self.standard_type() {
+            ScriptType::Pkh(_) => Some(AddressType::Pkh),
+            ScriptType::Sh(_) => Some(AddressType::Sh),
+            ScriptType::Wpkh(_) => Some(AddressType::Wpkh),
+            ScriptType::Wsh(_) => Some(AddressType::Wsh),
+            _ if self.witness_program().is_some_and(|w| w.is_p2tr()) => Some(AddressType::Tr),
+            _ => None,
+        }
+    }
+
+    /// The script pubkey's witness program. `None` if it is not a witness program, or is a v0
+    /// program of invalid length.
+    pub fn witness_program(&self) -> Option<WitnessProgram> {
+        WitnessProgram::from_script_pubkey(self).ok()
+    }
+
     /// Inspect the `Script` to determine its type.
     pub fn standard_type(&self) -> ScriptType {
         if let Some(data) = self.extract_op_return_data() {
@@ -173,41 +456,85 @@ impl ScriptPubkey {
 
         let items = &self.0;
         match self.0.len() {
-            0x19 => {
-                // PKH;
-                if items[0..3] == [0x76, 0xa9, 0x14] && items[0x17..] == [0x88, 0xac] {
-                    let mut buf = Hash160Digest::default();
-                    buf.as_mut_slice().copy_from_slice(&items[3..23]);
-                    return ScriptType::Pkh(buf);
-                }
+            // PKH
+            0x19 if items[0..3] == [0x76, 0xa9, 0x14] && items[0x17..] == [0x88, 0xac] => {
+                let mut buf = Hash160Digest::default();
+                buf.as_mut_slice().copy_from_slice(&items[3..23]);
+                ScriptType::Pkh(buf)
             }
-            0x17 => {
-                // SH
-                if items[0..2] == [0xa9, 0x14] && items[0x16..] == [0x87] {
-                    let mut buf = Hash160Digest::default();
-                    buf.as_mut_slice().copy_from_slice(&items[2..22]);
-                    return ScriptType::Sh(buf);
-                }
+            // SH
+            0x17 if items[0..2] == [0xa9, 0x14] && items[0x16..] == [0x87] => {
+                let mut buf = Hash160Digest::default();
+                buf.as_mut_slice().copy_from_slice(&items[2..22]);
+                ScriptType::Sh(buf)
             }
-            0x16 => {
-                // WPKH
-                if items[0..2] == [0x00, 0x14] {
-                    let mut buf = Hash160Digest::default();
-                    buf.as_mut_slice().copy_from_slice(&items[2..22]);
-                    return ScriptType::Wpkh(buf);
-                }
+            // WPKH
+            0x16 if self.witness_program().is_some_and(|w| w.is_p2wpkh()) => {
+                let mut buf = Hash160Digest::default();
+                buf.as_mut_slice().copy_from_slice(&items[2..22]);
+                ScriptType::Wpkh(buf)
             }
-            0x22 => {
-                if items[0..2] == [0x00, 0x20] {
-                    let mut buf = Hash256Digest::default();
-                    buf.as_mut_slice().copy_from_slice(&items[2..34]);
-                    return ScriptType::Wsh(buf);
-                }
+            // WSH
+            0x22 if self.witness_program().is_some_and(|w| w.is_p2wsh()) => {
+                let mut buf = Hash256Digest::default();
+                buf.as_mut_slice().copy_from_slice(&items[2..34]);
+                ScriptType::Wsh(buf)
             }
-            _ => return ScriptType::NonStandard,
+            _ => self
+                .bare_pk()
+                .or_else(|| self.bare_multisig())
+                .unwrap_or(ScriptType::NonStandard),
         }
-        // fallthrough
-        ScriptType::NonStandard
+    }
+
+    /// Parse `<pubkey> OP_CHECKSIG`
+    fn bare_pk(&self) -> Option<ScriptType> {
+        let mut instructions = self.instructions_minimal();
+        let key = instructions.next()?.ok()?.data?;
+        match (instructions.next()?.ok()?.opcode, instructions.next()) {
+            (Opcode::CheckSig, None) if is_pubkey(key) => Some(ScriptType::Pk(key.to_vec())),
+            _ => None,
+        }
+    }
+
+    /// Parse `OP_m <pubkey>... OP_n OP_CHECKMULTISIG`
+    fn bare_multisig(&self) -> Option<ScriptType> {
+        let instructions = self
+            .instructions_minimal()
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+        if instructions.len() < 4 {
+            return None;
+        }
+        let (last, rest) = instructions.split_last()?;
+        let (n, rest) = rest.split_last()?;
+        let (m, keys) = rest.split_first()?;
+
+        let (m, n) = match (m.opcode, n.opcode, last.opcode) {
+            (Opcode::PushNum(m), Opcode::PushNum(n), Opcode::CheckMultisig) => {
+                (m as usize, n as usize)
+            }
+            _ => return None,
+        };
+        if m > n || keys.len() != n {
+            return None;
+        }
+
+        let pubkeys = keys
+            .iter()
+            .map(|k| k.data.filter(|d| is_pubkey(d)).map(<[u8]>::to_vec))
+            .collect::<Option<Vec<_>>>()?;
+        Some(ScriptType::Multisig { m, pubkeys })
+    }
+}
+
+/// Check a pushed key's size and prefix, as Core does for bare P2PK and multisig outputs. This
+/// does not check that the key is on the curve.
+pub(crate) fn is_pubkey(key: &[u8]) -> bool {
+    match key.len() {
+        33 => key[0] == 0x02 || key[0] == 0x03,
+        65 => key[0] == 0x04 || key[0] == 0x06 || key[0] == 0x07,
+        _ => false,
     }
 }
 
@@ -293,6 +620,101 @@ mod test {
         ScriptSig::from(&spk);
     }
 
+    #[test]
+    fn it_parses_scripts_from_hex_strings() {
+        use std::convert::TryFrom;
+
+        let hex_str = "0014758ce550380d964051086798d6546bebdca27a73";
+        let expected = hex::decode(hex_str).unwrap();
+
+        assert_eq!(Script::from_hex(hex_str).unwrap().items(), &expected[..]);
+        assert_eq!(ScriptSig::try_from(hex_str).unwrap().items(), &expected[..]);
+        assert_eq!(
+            WitnessStackItem::try_from("").unwrap(),
+            WitnessStackItem::null()
+        );
+        assert!(ScriptPubkey::try_from("0g").is_err());
+        assert!(Script::from_hex("001").is_err());
+    }
+
+    #[test]
+    fn it_parses_script_pubkeys_from_asm_or_hex() {
+        let cases = [
+            (
+                "76a9140e5c3c8d420c7f11e88d76f7b860d471e6517a4488ac",
+                "76a9140e5c3c8d420c7f11e88d76f7b860d471e6517a4488ac",
+            ),
+            (
+                "OP_DUP OP_HASH160 0e5c3c8d420c7f11e88d76f7b860d471e6517a44 OP_EQUALVERIFY OP_CHECKSIG",
+                "76a9140e5c3c8d420c7f11e88d76f7b860d471e6517a4488ac",
+            ),
+            (
+                "  OP_HASH160 <e88869b88866281ab166541ad8aafba8f8aba47a> OP_EQUAL ",
+                "a914e88869b88866281ab166541ad8aafba8f8aba47a87",
+            ),
+            (
+                "0 1bf8a1831db5443b42a44f30a121d1b616d011ab",
+                "00141bf8a1831db5443b42a44f30a121d1b616d011ab",
+            ),
+            ("OP_2 -1 16 OP_TRUE", "524f6051"),
+            ("OP_RETURN", "6a"),
+        ];
+        for case in cases.iter() {
+            let spk = ScriptPubkey::from_asm_or_hex(case.0).unwrap();
+            assert_eq!(spk.items(), &hex::decode(case.1).unwrap()[..]);
+        }
+
+        let long_push = format!("OP_RETURN {}", "ab".repeat(80));
+        let spk = ScriptPubkey::from_asm_or_hex(&long_push).unwrap();
+        assert_eq!(spk[0..3], [0x6a, 0x4c, 80]);
+        assert_eq!(spk.len(), 83);
+
+        assert!(ScriptPubkey::from_asm_or_hex("OP_DUP OP_NOTANOPCODE").is_err());
+        assert!(ScriptPubkey::from_asm_or_hex("OP_DUP abc").is_err());
+    }
+
+    #[test]
+    fn it_round_trips_asm() {
+        let cases = [
+            (
+                "76a9140e5c3c8d420c7f11e88d76f7b860d471e6517a4488ac",
+                "OP_DUP OP_HASH160 <0e5c3c8d420c7f11e88d76f7b860d471e6517a44> OP_EQUALVERIFY OP_CHECKSIG",
+            ),
+            (
+                "00141bf8a1831db5443b42a44f30a121d1b616d011ab",
+                "OP_0 <1bf8a1831db5443b42a44f30a121d1b616d011ab>",
+            ),
+            (
+                "512102020202020202020202020202020202020202020202020202020202020202020251ae",
+                "OP_1 <020202020202020202020202020202020202020202020202020202020202020202> OP_1 OP_CHECKMULTISIG",
+            ),
+            ("4f60b1b2ba", "OP_1NEGATE OP_16 OP_CHECKLOCKTIMEVERIFY OP_CHECKSEQUENCEVERIFY OP_CHECKSIGADD"),
+            ("6aff", "OP_RETURN 0xff"),
+            ("764c0501", "OP_DUP 0x4c0501"),
+            ("", ""),
+        ];
+        for case in cases.iter() {
+            let script =
+                Script::deserialize_hex(&format!("{:02x}{}", case.0.len() / 2, case.0)).unwrap();
+            assert_eq!(script.to_asm(), case.1);
+            assert_eq!(Script::from_asm(case.1).unwrap(), script);
+            assert_eq!(
+                ScriptPubkey::from_asm(case.1).unwrap(),
+                ScriptPubkey::from(&script)
+            );
+        }
+
+        // Non-minimal pushes are re-encoded minimally
+        let non_minimal = Script::new(vec![0x4c, 0x01, 0x20]);
+        assert_eq!(non_minimal.to_asm(), "<20>");
+        assert_eq!(
+            Script::from_asm("<20>").unwrap(),
+            Script::new(vec![0x01, 0x20])
+        );
+
+        assert!(Script::from_asm("OP_DUP 0xabc").is_err());
+    }
+
     #[test]
     fn it_determines_script_pubkey_types_accurately() {
         let cases = [
@@ -309,6 +731,12 @@ mod test {
             (ScriptPubkey::new(hex::decode("0011223344").unwrap()), ScriptType::NonStandard), // junk
             (ScriptPubkey::new(hex::decode("deadbeefdeadbeefdeadbeefdeadbeef").unwrap()), ScriptType::NonStandard), // junk
             (ScriptPubkey::new(hex::decode("02031bf8a1831db5443b42a44f30a121d1b616d011ab15df62b588722a845864cc99041bf8a1831db5443b42a44f30a121d1b616d011ab15df62b588722a845864cc9902af").unwrap()), ScriptType::NonStandard), // Raw msig
+            (ScriptPubkey::new(hex::decode("210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac").unwrap()), ScriptType::Pk(hex::decode("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap())),
+            (ScriptPubkey::new(hex::decode("210579be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac").unwrap()), ScriptType::NonStandard), // bad key prefix
+            (ScriptPubkey::new(hex::decode("210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ad").unwrap()), ScriptType::NonStandard), // wrong last byte
+            (ScriptPubkey::new(hex::decode("51210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f817982102c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee552ae").unwrap()), ScriptType::Multisig { m: 1, pubkeys: vec![hex::decode("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap(), hex::decode("02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5").unwrap()] }),
+            (ScriptPubkey::new(hex::decode("53210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f817982102c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee552ae").unwrap()), ScriptType::NonStandard), // m > n
+            (ScriptPubkey::new(hex::decode("51210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f817982102c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee553ae").unwrap()), ScriptType::NonStandard), // wrong key count
         ];
 
         for case in cases.iter() {
@@ -316,4 +744,66 @@ mod test {
             assert_eq!(script.standard_type(), *t);
         }
     }
+
+    #[test]
+    fn it_builds_bip67_sorted_multisig_scripts() {
+        use coins_bip32::{ecdsa::VerifyingKey, path::KeyDerivation, prelude::DerivedPubkey};
+
+        let key = |h: &str| {
+            let derivation = KeyDerivation {
+                root: [0u8; 4].into(),
+                path: vec![].into(),
+            };
+            let key = VerifyingKey::from_sec1_bytes(&hex::decode(h).unwrap()).unwrap();
+            DerivedPubkey::new(key, derivation)
+        };
+        // BIP67 test vector 1
+        let a = "02ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f8";
+        let b = "02fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f";
+        let keys = [key(a), key(b)];
+        let expected = "522102fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f2102ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f852ae";
+
+        let script = Script::sorted_multisig(2, &keys).unwrap();
+        assert_eq!(script.items(), &hex::decode(expected).unwrap()[..]);
+        let reversed = [key(b), key(a)];
+        assert_eq!(Script::sorted_multisig(2, &reversed).unwrap(), script);
+
+        assert_eq!(
+            ScriptPubkey::sorted_multisig_p2sh(2, &keys).unwrap(),
+            ScriptPubkey::p2sh(&script)
+        );
+        assert_eq!(
+            ScriptPubkey::sorted_multisig_p2wsh(2, &reversed).unwrap(),
+            ScriptPubkey::p2wsh(&script)
+        );
+
+        assert_eq!(Script::sorted_multisig(0, &keys), None);
+        assert_eq!(Script::sorted_multisig(3, &keys), None);
+        let too_many: Vec<_> = (0..=MAX_SORTED_MULTISIG_KEYS).map(|_| key(a)).collect();
+        assert_eq!(Script::sorted_multisig(1, &too_many), None);
+    }
+
+    #[test]
+    fn it_wraps_witness_programs_in_p2sh() {
+        let witness_script = Script::from_hex("51").unwrap();
+        let nested = NestedWitnessProgram::sh_wsh(&witness_script);
+        assert_eq!(
+            nested.redeem_script.items(),
+            &hex::decode("00204ae81572f06e1b88fd5ced7a1a000945432e83e1551e6f721ee9c00b8cc33260")
+                .unwrap()[..]
+        );
+        assert_eq!(
+            nested.script_pubkey,
+            ScriptPubkey::p2sh(&nested.redeem_script)
+        );
+        assert_eq!(nested.script_sig[0], 34);
+        assert_eq!(
+            nested.script_sig.items()[1..],
+            nested.redeem_script.items()[..]
+        );
+        match nested.script_pubkey.standard_type() {
+            ScriptType::Sh(_) => {}
+            _ => panic!("expected p2sh"),
+        }
+    }
 }
@@ -0,0 +1,220 @@
+//! Script numbers, as consumed by arithmetic opcodes, `OP_CHECKLOCKTIMEVERIFY`, and
+//! `OP_CHECKSEQUENCEVERIFY`.
+//!
+//! Script numbers are little-endian, sign-magnitude integers. The high bit of the last byte is
+//! the sign. A minimal encoding has no trailing zero bytes, except where needed to clear the sign
+//! bit, and zero is the empty byte string. Most opcodes accept numbers of at most 4 bytes.
+//! Timelock opcodes accept 5 bytes, so that they can express all `u32` values.
+//!
+//! ```
+//! use bitcoins::types::ScriptNum;
+//!
+//! assert_eq!(ScriptNum::new(-255).to_bytes(), vec![0xff, 0x80]);
+//! assert_eq!(ScriptNum::from_slice(&[0xff, 0x80]).unwrap().value(), -255);
+//! assert!(ScriptNum::from_slice(&[0x01, 0x00]).is_err());
+//! ```
+
+use thiserror::Error;
+
+use crate::types::{opcodes::Opcode, script::push_data};
+
+/// The largest number of bytes in a number consumed by most opcodes.
+pub const DEFAULT_MAX_NUM_LEN: usize = 4;
+
+/// The largest number of bytes that fit in a `ScriptNum`.
+pub const MAX_SCRIPT_NUM_LEN: usize = 8;
+
+/// An error decoding a script number.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ScriptNumError {
+    /// The number is longer than allowed
+    #[error("Script number is {got} bytes. Limit is {max}.")]
+    Overflow {
+        /// The largest length allowed
+        max: usize,
+        /// The length of the number
+        got: usize,
+    },
+
+    /// The number has unnecessary trailing zero bytes
+    #[error("Script number is not minimally encoded")]
+    NonMinimal,
+}
+
+/// A script number.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ScriptNum(i64);
+
+impl ScriptNum {
+    /// Instantiate a script number
+    pub fn new(value: i64) -> Self {
+        Self(value)
+    }
+
+    /// Return the value of the number
+    pub fn value(self) -> i64 {
+        self.0
+    }
+
+    /// Encode the number minimally. Zero is the empty byte string.
+    pub fn to_bytes(self) -> Vec<u8> {
+        let mut data = vec![];
+        let negative = self.0 < 0;
+        let mut abs = self.0.unsigned_abs();
+        while abs > 0 {
+            data.push(abs as u8);
+            abs >>= 8;
+        }
+        match data.last_mut() {
+            // Add a byte to hold the sign, if the magnitude uses the high bit
+            Some(last) if *last & 0x80 != 0 => data.push(if negative { 0x80 } else { 0 }),
+            Some(last) if negative => *last |= 0x80,
+            _ => {}
+        }
+        data
+    }
+
+    /// Decode a minimally-encoded number of at most 4 bytes
+    pub fn from_slice(data: &[u8]) -> Result<Self, ScriptNumError> {
+        Self::decode(data, DEFAULT_MAX_NUM_LEN, true)
+    }
+
+    /// Decode a number of at most `max_len` bytes. If `require_minimal` is false, trailing zero
+    /// bytes and negative zero are accepted. `max_len` may not exceed `MAX_SCRIPT_NUM_LEN`.
+    pub fn decode(
+        data: &[u8],
+        max_len: usize,
+        require_minimal: bool,
+    ) -> Result<Self, ScriptNumError> {
+        let max = max_len.min(MAX_SCRIPT_NUM_LEN);
+        if data.len() > max {
+            return Err(ScriptNumError::Overflow {
+                max,
+                got: data.len(),
+            });
+        }
+        let last = match data.last() {
+            Some(last) => *last,
+            None => return Ok(Self(0)),
+        };
+        // The last byte may only be 0x00 or 0x80 if the previous byte uses its high bit
+        if require_minimal
+            && last & 0x7f == 0
+            && (data.len() == 1 || data[data.len() - 2] & 0x80 == 0)
+        {
+            return Err(ScriptNumError::NonMinimal);
+        }
+        let abs = data
+            .iter()
+            .enumerate()
+            .fold(0u64, |acc, (i, b)| acc | (*b as u64) << (8 * i))
+            & !(0x80u64 << (8 * (data.len() - 1)));
+        if last & 0x80 != 0 {
+            Ok(Self(-(abs as i64)))
+        } else {
+            Ok(Self(abs as i64))
+        }
+    }
+
+    /// Append the number to a script, using `OP_0`, `OP_1NEGATE`, and `OP_1` through `OP_16`
+    /// where possible
+    pub fn push_to(self, script: &mut Vec<u8>) {
+        match self.0 {
+            0 => script.push(Opcode::PushBytes(0).to_u8()),
+            -1 => script.push(Opcode::Negative1.to_u8()),
+            1..=16 => script.push(Opcode::PushNum(self.0 as u8).to_u8()),
+            _ => push_data(script, &self.to_bytes()),
+        }
+    }
+}
+
+impl From<i64> for ScriptNum {
+    fn from(value: i64) -> Self {
+        Self(value)
+    }
+}
+
+impl From<u32> for ScriptNum {
+    fn from(value: u32) -> Self {
+        Self(value as i64)
+    }
+}
+
+impl From<ScriptNum> for i64 {
+    fn from(num: ScriptNum) -> i64 {
+        num.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_round_trips_script_nums() {
+        let cases: &[(i64, &str)] = &[
+            (0, ""),
+            (1, "01"),
+            (-1, "81"),
+            (127, "7f"),
+            (128, "8000"),
+            (-128, "8080"),
+            (255, "ff00"),
+            (256, "0001"),
+            (-256, "0081"),
+            (0x7fff_ffff, "ffffff7f"),
+            (-0x7fff_ffff, "ffffffff"),
+            (0xffff_ffff, "ffffffff00"),
+        ];
+        for (value, hex) in cases.iter() {
+            let num = ScriptNum::new(*value);
+            assert_eq!(hex::encode(num.to_bytes()), *hex);
+            let bytes = hex::decode(hex).unwrap();
+            assert_eq!(ScriptNum::decode(&bytes, 5, true).unwrap(), num);
+        }
+    }
+
+    #[test]
+    fn it_enforces_length_and_minimality() {
+        assert_eq!(
+            ScriptNum::from_slice(&[0xff, 0xff, 0xff, 0xff, 0x00]),
+            Err(ScriptNumError::Overflow { max: 4, got: 5 })
+        );
+        assert_eq!(
+            ScriptNum::decode(&[0xff, 0xff, 0xff, 0xff, 0x00], 5, true).unwrap(),
+            ScriptNum::new(0xffff_ffff)
+        );
+
+        let non_minimal: &[&[u8]] = &[&[0x00], &[0x80], &[0x01, 0x00], &[0x7f, 0x80]];
+        for data in non_minimal.iter() {
+            assert_eq!(ScriptNum::from_slice(data), Err(ScriptNumError::NonMinimal));
+        }
+        assert_eq!(
+            ScriptNum::decode(&[0x01, 0x00], 4, false).unwrap().value(),
+            1
+        );
+        assert_eq!(ScriptNum::decode(&[0x80], 4, false).unwrap().value(), 0);
+        assert_eq!(
+            ScriptNum::decode(&[0x7f, 0x80], 4, false).unwrap().value(),
+            -127
+        );
+    }
+
+    #[test]
+    fn it_pushes_script_nums() {
+        let cases: &[(i64, &str)] = &[
+            (0, "00"),
+            (-1, "4f"),
+            (1, "51"),
+            (16, "60"),
+            (17, "0111"),
+            (-2, "0182"),
+            (500_000, "0320a107"),
+        ];
+        for (value, hex) in cases.iter() {
+            let mut script = vec![];
+            ScriptNum::new(*value).push_to(&mut script);
+            assert_eq!(hex::encode(script), *hex);
+        }
+    }
+}
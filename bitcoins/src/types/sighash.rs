@@ -0,0 +1,192 @@
+//! Sighash computation from prevout data keyed by outpoint.
+//!
+//! Signing data is often organized by outpoint rather than by input index, e.g. in PSBTs or in a
+//! wallet's UTXO set. A `SighashComputer` holds a transaction and the value and script of each
+//! prevout it spends, and computes the legacy or BIP143 digest of any input with any sighash
+//! flag, without the caller assembling `LegacySighashArgs` or `WitnessSighashArgs`.
+
+use std::collections::HashMap;
+
+use coins_core::{hashes::Hash256Digest, types::tx::Transaction};
+use thiserror::Error;
+
+use crate::types::{
+    legacy::LegacySighashArgs,
+    script::{Script, ScriptPubkey, ScriptType},
+    tx::{BitcoinTransaction, Sighash, TxError},
+    txin::BitcoinOutpoint,
+    witness::{SighashCache, WitnessSighashArgs},
+};
+
+/// An error computing a sighash from prevout data.
+#[derive(Debug, Error)]
+pub enum SighashError {
+    /// There is no input at the index
+    #[error("No input at index {}", .0)]
+    BadInputIndex(usize),
+
+    /// The value and script of the input's prevout are unknown
+    #[error("Unknown prevout {:?}", .0)]
+    MissingPrevout(BitcoinOutpoint),
+
+    /// Bubbled up from the transaction
+    #[error(transparent)]
+    TxError(#[from] TxError),
+}
+
+/// Computes sighash digests for the inputs of a transaction, given the value and script of each
+/// prevout by outpoint.
+///
+/// The script is the script committed to by the signature. For legacy inputs this is the prevout
+/// script pubkey, or the redeem script of P2SH prevouts. For witness inputs it is the BIP143
+/// script code, e.g. the witness script of P2WSH prevouts. As a convenience, a P2WPKH script
+/// pubkey is converted to its P2PKH script code.
+///
+/// BIP143 components shared by all inputs are computed once, and reused across inputs.
+///
+/// SIGHASH_NONE is rejected unless enabled with `allow_sighash_none`.
+#[derive(Debug, Clone)]
+pub struct SighashComputer {
+    cache: SighashCache<'static>,
+    prevouts: HashMap<BitcoinOutpoint, (u64, Script)>,
+    allow_none: bool,
+}
+
+impl SighashComputer {
+    /// Instantiate a computer for `tx`, with the value and script of each prevout. Prevouts
+    /// that the transaction does not spend are ignored.
+    pub fn new<T: BitcoinTransaction>(
+        tx: &T,
+        prevouts: HashMap<BitcoinOutpoint, (u64, Script)>,
+    ) -> Self {
+        Self {
+            cache: SighashCache::from_tx(tx),
+            prevouts,
+            allow_none: false,
+        }
+    }
+
+    /// Permit SIGHASH_NONE and SIGHASH_NONE | ANYONECANPAY. These signatures do not commit to
+    /// the outputs, so anyone may redirect the funds they authorize.
+    pub fn allow_sighash_none(mut self) -> Self {
+        self.allow_none = true;
+        self
+    }
+
+    /// Add or replace the value and script of a prevout.
+    pub fn insert_prevout(&mut self, outpoint: BitcoinOutpoint, value: u64, script: Script) {
+        self.prevouts.insert(outpoint, (value, script));
+    }
+
+    /// Return the value and script of the prevout spent by the input at `index`.
+    pub fn prevout(&self, index: usize) -> Result<&(u64, Script), SighashError> {
+        let outpoint = self
+            .cache
+            .tx()
+            .inputs()
+            .get(index)
+            .ok_or(SighashError::BadInputIndex(index))?
+            .outpoint;
+        self.prevouts
+            .get(&outpoint)
+            .ok_or(SighashError::MissingPrevout(outpoint))
+    }
+
+    /// Compute the legacy sighash of the input at `index`.
+    pub fn legacy_sighash(
+        &self,
+        index: usize,
+        sighash_flag: Sighash,
+    ) -> Result<Hash256Digest, SighashError> {
+        let (_, script) = self.prevout(index)?;
+        let args = LegacySighashArgs {
+            index,
+            sighash_flag,
+            prevout_script: script.clone(),
+        };
+        let legacy = self.cache.tx().as_legacy();
+        let digest = if self.allow_none {
+            legacy.sighash_allowing_none(&args)?
+        } else {
+            legacy.sighash(&args)?
+        };
+        Ok(digest.into())
+    }
+
+    /// Compute the BIP143 sighash of the input at `index`.
+    pub fn witness_sighash(
+        &self,
+        index: usize,
+        sighash_flag: Sighash,
+    ) -> Result<Hash256Digest, SighashError> {
+        let (value, script) = self.prevout(index)?;
+        let script_code = match ScriptPubkey::from(script.items()).standard_type() {
+            ScriptType::Wpkh(hash) => Script::from(&ScriptPubkey::p2pkh_from_hash(&hash)),
+            _ => script.clone(),
+        };
+        let args = WitnessSighashArgs {
+            index,
+            sighash_flag,
+            prevout_script: script_code,
+            prevout_value: *value,
+        };
+        let digest = if self.allow_none {
+            self.cache.witness_sighash_allowing_none(&args)?
+        } else {
+            self.cache.witness_sighash(&args)?
+        };
+        Ok(digest.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use coins_core::ser::ByteFormat;
+
+    use crate::types::legacy::LegacyTx;
+
+    #[test]
+    fn it_computes_sighashes_by_outpoint() {
+        // The native P2WPKH example from BIP143
+        let tx = LegacyTx::deserialize_hex("0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000").unwrap();
+        let p2pk = Script::from_hex(
+            "2103c9f4836b9a4f77fc0d81f7bcb01b7f1b35916864b9476c241ce9fc198bd25432ac",
+        )
+        .unwrap();
+        let p2wpkh = Script::from_hex("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap();
+
+        let mut computer = SighashComputer::new(&tx, HashMap::new());
+        match computer.witness_sighash(1, Sighash::All) {
+            Err(SighashError::MissingPrevout(outpoint)) => {
+                assert_eq!(outpoint, tx.inputs()[1].outpoint)
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        computer.insert_prevout(tx.inputs()[0].outpoint, 625_000_000, p2pk.clone());
+        computer.insert_prevout(tx.inputs()[1].outpoint, 600_000_000, p2wpkh);
+
+        assert_eq!(
+            computer.witness_sighash(1, Sighash::All).unwrap(),
+            Hash256Digest::deserialize_hex(
+                "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670"
+            )
+            .unwrap()
+        );
+
+        let args = LegacySighashArgs {
+            index: 0,
+            sighash_flag: Sighash::AllAcp,
+            prevout_script: p2pk,
+        };
+        assert_eq!(
+            computer.legacy_sighash(0, Sighash::AllAcp).unwrap(),
+            tx.sighash(&args).unwrap().into()
+        );
+
+        assert!(matches!(
+            computer.legacy_sighash(2, Sighash::All),
+            Err(SighashError::BadInputIndex(2))
+        ));
+    }
+}
@@ -0,0 +1,497 @@
+//! Helpers for interpreting common witness stack patterns, and for extracting the signatures
+//! and pubkeys revealed by script sigs and witnesses. These are intended for explorers and
+//! debuggers, and do NOT validate signatures or execute scripts.
+
+use coins_bip32::ecdsa::{Signature, VerifyingKey};
+use coins_core::hashes::{Digest, Hash160, MarkedDigestOutput, Sha256};
+
+use crate::types::{
+    script::{
+        is_pubkey, BitcoinScript, Script, ScriptPubkey, ScriptSig, ScriptType, Witness,
+        WitnessStackItem,
+    },
+    tx::{Sighash, TxResult},
+};
+
+/// The first byte of a taproot annex, if present.
+pub const ANNEX_TAG: u8 = 0x50;
+
+/// The items of a P2WPKH witness.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WpkhWitness {
+    /// The DER-encoded signature, with its sighash flag byte
+    pub signature: WitnessStackItem,
+    /// The SEC1-encoded compressed public key
+    pub pubkey: WitnessStackItem,
+}
+
+/// The items of a P2WSH witness.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WshWitness {
+    /// The arguments to the witness script. Typically signatures.
+    pub args: Vec<WitnessStackItem>,
+    /// The witness script
+    pub witness_script: Script,
+}
+
+/// The items of a Taproot witness.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TaprootWitness {
+    /// A key path spend
+    KeyPath {
+        /// The Schnorr signature, with an optional sighash flag byte
+        signature: WitnessStackItem,
+        /// The annex, if any
+        annex: Option<WitnessStackItem>,
+    },
+    /// A script path spend
+    ScriptPath {
+        /// The arguments to the leaf script
+        args: Vec<WitnessStackItem>,
+        /// The leaf script
+        script: Script,
+        /// The control block, containing the leaf version, internal key, and merkle path
+        control_block: WitnessStackItem,
+        /// The annex, if any
+        annex: Option<WitnessStackItem>,
+    },
+}
+
+/// An interpretation of a witness stack.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WitnessData {
+    /// An empty witness. E.g. a legacy input in a witness transaction
+    Empty,
+    /// A P2WPKH witness
+    Wpkh(WpkhWitness),
+    /// A P2WSH witness
+    Wsh(WshWitness),
+    /// A Taproot witness
+    Taproot(TaprootWitness),
+    /// A witness that does not match the expected pattern
+    Unknown,
+}
+
+/// A DER-encoded ECDSA signature and its sighash flag, as found in a script sig or witness.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InputSignature {
+    /// The DER-encoded signature
+    pub der: Vec<u8>,
+    /// The sighash flag byte
+    pub sighash_flag: u8,
+}
+
+impl InputSignature {
+    /// Split a stack item into its signature and sighash flag. `None` unless the item is a
+    /// strictly DER-encoded signature (per BIP66) followed by a flag byte.
+    pub fn from_stack_item(item: &[u8]) -> Option<Self> {
+        if !is_der_signature(item) {
+            return None;
+        }
+        let (sighash_flag, der) = item.split_last()?;
+        Some(Self {
+            der: der.to_vec(),
+            sighash_flag: *sighash_flag,
+        })
+    }
+
+    /// The sighash flag. Errors if the flag is not a known sighash type.
+    pub fn sighash(&self) -> TxResult<Sighash> {
+        Sighash::from_u8(self.sighash_flag)
+    }
+
+    /// Parse the signature for verification. `None` if it is not a valid secp256k1 signature.
+    pub fn signature(&self) -> Option<Signature> {
+        Signature::from_der(&self.der).ok()
+    }
+
+    /// The stack item: the DER-encoded signature followed by the sighash flag
+    pub fn to_stack_item(&self) -> Vec<u8> {
+        let mut item = self.der.clone();
+        item.push(self.sighash_flag);
+        item
+    }
+}
+
+/// The signatures and pubkeys revealed by an input's script sig or witness.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpendData {
+    /// The ECDSA signatures, in stack order
+    pub signatures: Vec<InputSignature>,
+    /// The SEC1-encoded pubkeys, in stack order. Pubkeys committed to by a P2PK or multisig
+    /// redeem or witness script follow any pushed directly.
+    pub pubkeys: Vec<Vec<u8>>,
+}
+
+impl SpendData {
+    /// Extract the signatures and pubkeys from a sequence of stack items. The last item is also
+    /// parsed as a redeem or witness script.
+    fn from_items<'a, I>(items: I) -> Self
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        let items: Vec<&[u8]> = items.into_iter().collect();
+        let mut data = SpendData {
+            signatures: items
+                .iter()
+                .filter_map(|i| InputSignature::from_stack_item(i))
+                .collect(),
+            pubkeys: items
+                .iter()
+                .filter(|i| is_pubkey(i))
+                .map(|i| i.to_vec())
+                .collect(),
+        };
+        if let Some(last) = items.last() {
+            match ScriptPubkey::new(last.to_vec()).standard_type() {
+                ScriptType::Pk(key) => data.pubkeys.push(key),
+                ScriptType::Multisig { pubkeys, .. } => data.pubkeys.extend(pubkeys),
+                _ => {}
+            }
+        }
+        data
+    }
+
+    /// Extract the signatures and pubkeys from an input's script sig and witness. Pass an empty
+    /// witness for legacy inputs.
+    pub fn from_input(script_sig: &ScriptSig, witness: &Witness) -> Self {
+        let mut data = script_sig.spend_data();
+        let witness_data = witness.spend_data();
+        data.signatures.extend(witness_data.signatures);
+        data.pubkeys.extend(witness_data.pubkeys);
+        data
+    }
+
+    /// True if no signatures or pubkeys were found
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty() && self.pubkeys.is_empty()
+    }
+
+    /// Parse the pubkeys for verification. Pubkeys that are not valid curve points are skipped.
+    pub fn verifying_keys(&self) -> Vec<VerifyingKey> {
+        self.pubkeys
+            .iter()
+            .filter_map(|k| VerifyingKey::from_sec1_bytes(k).ok())
+            .collect()
+    }
+}
+
+/// True if the item is a strictly DER-encoded ECDSA signature followed by a sighash flag byte.
+/// This is the BIP66 encoding check, and does not check the flag's value.
+pub fn is_der_signature(item: &[u8]) -> bool {
+    // 0x30 <len> 0x02 <r len> <r> 0x02 <s len> <s> <sighash>
+    let len = item.len();
+    if !(9..=73).contains(&len) || item[0] != 0x30 || item[1] as usize != len - 3 {
+        return false;
+    }
+    let r_len = item[3] as usize;
+    if 5 + r_len >= len {
+        return false;
+    }
+    let s_len = item[5 + r_len] as usize;
+    if r_len + s_len + 7 != len {
+        return false;
+    }
+
+    // Both integers must be positive, non-empty and minimally encoded
+    let minimal_int = |tag: u8, int: &[u8]| {
+        tag == 0x02
+            && !int.is_empty()
+            && int[0] & 0x80 == 0
+            && !(int.len() > 1 && int[0] == 0x00 && int[1] & 0x80 == 0)
+    };
+    minimal_int(item[2], &item[4..4 + r_len])
+        && minimal_int(item[4 + r_len], &item[6 + r_len..len - 1])
+}
+
+impl ScriptSig {
+    /// Extract the signatures and pubkeys pushed by the script sig, and those committed to by
+    /// its redeem script. Empty if the script sig is not push-only.
+    pub fn spend_data(&self) -> SpendData {
+        let pushes: Option<Vec<&[u8]>> = self
+            .instructions()
+            .map(|i| {
+                let i = i.ok()?;
+                if !i.opcode.is_push() {
+                    return None;
+                }
+                Some(i.data.unwrap_or(&[]))
+            })
+            .collect();
+        pushes.map(SpendData::from_items).unwrap_or_default()
+    }
+}
+
+/// Typed helpers for interpreting the common patterns found in a `Witness`.
+pub trait WitnessStack {
+    /// Interpret the witness as a P2WPKH witness. Succeeds if the stack has exactly 2 items and
+    /// the second is a compressed public key.
+    fn as_wpkh(&self) -> Option<WpkhWitness>;
+
+    /// Interpret the witness as a P2WSH witness. The last item is treated as the witness script.
+    fn as_wsh(&self) -> Option<WshWitness>;
+
+    /// Interpret the witness as a Taproot witness, per BIP341. Separates the annex (if any), and
+    /// distinguishes key path from script path spends.
+    fn as_taproot(&self) -> Option<TaprootWitness>;
+
+    /// Interpret the witness using the script pubkey of the output it spends. This checks that
+    /// the pubkey or script hashes to the committed program, and returns `Unknown` on mismatch.
+    fn interpret(&self, prevout_script: &ScriptPubkey) -> WitnessData;
+
+    /// Extract the ECDSA signatures and pubkeys in the witness, and those committed to by its
+    /// witness script. Taproot Schnorr signatures are not extracted.
+    fn spend_data(&self) -> SpendData;
+}
+
+/// True if the script pubkey is a segwit v1 (Taproot) output.
+fn is_taproot(spk: &ScriptPubkey) -> bool {
+    spk.len() == 34 && spk[0] == 0x51 && spk[1] == 0x20
+}
+
+impl WitnessStack for Witness {
+    fn as_wpkh(&self) -> Option<WpkhWitness> {
+        if self.len() != 2 {
+            return None;
+        }
+        let pubkey = &self[1];
+        if pubkey.len() != 33 || (pubkey[0] != 0x02 && pubkey[0] != 0x03) {
+            return None;
+        }
+        Some(WpkhWitness {
+            signature: self[0].clone(),
+            pubkey: pubkey.clone(),
+        })
+    }
+
+    fn as_wsh(&self) -> Option<WshWitness> {
+        let (last, args) = self.split_last()?;
+        Some(WshWitness {
+            args: args.to_vec(),
+            witness_script: last.into(),
+        })
+    }
+
+    fn as_taproot(&self) -> Option<TaprootWitness> {
+        let (stack, annex) = match self.split_last() {
+            Some((last, rest)) if !rest.is_empty() && !last.is_empty() && last[0] == ANNEX_TAG => {
+                (rest, Some(last.clone()))
+            }
+            Some(_) => (&self[..], None),
+            None => return None,
+        };
+
+        if stack.len() == 1 {
+            let signature = &stack[0];
+            if signature.len() != 64 && signature.len() != 65 {
+                return None;
+            }
+            return Some(TaprootWitness::KeyPath {
+                signature: signature.clone(),
+                annex,
+            });
+        }
+
+        let (control_block, rest) = stack.split_last()?;
+        let (script, args) = rest.split_last()?;
+        // 1 byte leaf version and parity, 32 byte internal key, up to 128 32-byte path elements
+        let cb_len = control_block.len();
+        if cb_len < 33 || (cb_len - 33) % 32 != 0 || (cb_len - 33) / 32 > 128 {
+            return None;
+        }
+        Some(TaprootWitness::ScriptPath {
+            args: args.to_vec(),
+            script: script.into(),
+            control_block: control_block.clone(),
+            annex,
+        })
+    }
+
+    fn interpret(&self, prevout_script: &ScriptPubkey) -> WitnessData {
+        if self.is_empty() {
+            return WitnessData::Empty;
+        }
+        if is_taproot(prevout_script) {
+            return self
+                .as_taproot()
+                .map(WitnessData::Taproot)
+                .unwrap_or(WitnessData::Unknown);
+        }
+        match prevout_script.standard_type() {
+            ScriptType::Wpkh(digest) => match self.as_wpkh() {
+                Some(w) if Hash160::digest(w.pubkey.items())[..] == *digest.as_slice() => {
+                    WitnessData::Wpkh(w)
+                }
+                _ => WitnessData::Unknown,
+            },
+            ScriptType::Wsh(digest) => match self.as_wsh() {
+                Some(w) if Sha256::digest(w.witness_script.items())[..] == *digest.as_slice() => {
+                    WitnessData::Wsh(w)
+                }
+                _ => WitnessData::Unknown,
+            },
+            _ => WitnessData::Unknown,
+        }
+    }
+
+    fn spend_data(&self) -> SpendData {
+        SpendData::from_items(self.iter().map(WitnessStackItem::items))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn item(s: &str) -> WitnessStackItem {
+        WitnessStackItem::from_hex(s).unwrap()
+    }
+
+    #[test]
+    fn it_interprets_wpkh_witnesses() {
+        // from the BIP143 native p2wpkh example
+        let witness: Witness = vec![
+            item("304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee01"),
+            item("025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357"),
+        ];
+        let spk = ScriptPubkey::from_hex("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap();
+        match witness.interpret(&spk) {
+            WitnessData::Wpkh(w) => {
+                assert_eq!(w.signature, witness[0]);
+                assert_eq!(w.pubkey, witness[1]);
+            }
+            other => panic!("unexpected interpretation {:?}", other),
+        }
+
+        let wrong_spk =
+            ScriptPubkey::from_hex("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a2").unwrap();
+        assert_eq!(witness.interpret(&wrong_spk), WitnessData::Unknown);
+        assert_eq!(Witness::new().interpret(&spk), WitnessData::Empty);
+    }
+
+    #[test]
+    fn it_extracts_spend_data() {
+        let sig = "304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee01";
+        let pubkey = "025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee6357";
+        let expected_sig = InputSignature::from_stack_item(&hex::decode(sig).unwrap()).unwrap();
+        assert_eq!(expected_sig.sighash().unwrap(), Sighash::All);
+        assert!(expected_sig.signature().is_some());
+        assert_eq!(expected_sig.to_stack_item(), hex::decode(sig).unwrap());
+
+        // P2PKH: <sig> <pubkey>
+        let script_sig = ScriptSig::from_hex(&format!("47{}21{}", sig, pubkey)).unwrap();
+        let data = script_sig.spend_data();
+        assert_eq!(data.signatures, vec![expected_sig.clone()]);
+        assert_eq!(data.pubkeys, vec![hex::decode(pubkey).unwrap()]);
+        assert_eq!(data.verifying_keys().len(), 1);
+
+        // P2WPKH
+        let witness: Witness = vec![item(sig), item(pubkey)];
+        assert_eq!(witness.spend_data(), data);
+        assert_eq!(SpendData::from_input(&ScriptSig::null(), &witness), data);
+
+        // P2SH 1-of-2 multisig: OP_0 <sig> <redeem script>
+        let other = "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+        let redeem_script = format!("5121{}21{}52ae", pubkey, other);
+        let script_sig = ScriptSig::from_hex(&format!("0047{}47{}", sig, redeem_script)).unwrap();
+        let data = script_sig.spend_data();
+        assert_eq!(data.signatures, vec![expected_sig]);
+        assert_eq!(
+            data.pubkeys,
+            vec![hex::decode(pubkey).unwrap(), hex::decode(other).unwrap()]
+        );
+
+        // Not push-only
+        assert!(ScriptSig::from_hex("76a9").unwrap().spend_data().is_empty());
+    }
+
+    #[test]
+    fn it_checks_der_signature_encoding() {
+        let sig = "304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee01";
+        assert!(is_der_signature(&hex::decode(sig).unwrap()));
+        assert!(is_der_signature(
+            &hex::decode("300602010102010101").unwrap()
+        ));
+
+        let invalid = [
+            "3006020101020101",     // no sighash byte
+            "300602018102010101",   // negative r
+            "30070202000102010101", // non-minimal r
+            "300602000202010101",   // empty r
+            "300602010103010101",   // wrong s tag
+        ];
+        for case in invalid.iter() {
+            assert!(!is_der_signature(&hex::decode(case).unwrap()), "{}", case);
+        }
+    }
+
+    #[test]
+    fn it_interprets_wsh_witnesses() {
+        let script = Script::from_hex("51").unwrap(); // OP_TRUE
+        let witness: Witness = vec![item(""), item("51")];
+        let spk = ScriptPubkey::p2wsh(&script);
+        match witness.interpret(&spk) {
+            WitnessData::Wsh(w) => {
+                assert_eq!(w.args, vec![WitnessStackItem::null()]);
+                assert_eq!(w.witness_script, script);
+            }
+            other => panic!("unexpected interpretation {:?}", other),
+        }
+        assert!(witness.as_wpkh().is_none());
+    }
+
+    #[test]
+    fn it_interprets_taproot_witnesses() {
+        let spk = ScriptPubkey::from_hex(
+            "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+        )
+        .unwrap();
+        let sig = item(&"11".repeat(64));
+        let annex = item("50aa");
+        let control_block = item(&format!("c0{}", "22".repeat(32)));
+
+        let cases = [
+            (
+                vec![sig.clone()],
+                Some(TaprootWitness::KeyPath {
+                    signature: sig.clone(),
+                    annex: None,
+                }),
+            ),
+            (
+                vec![sig.clone(), annex.clone()],
+                Some(TaprootWitness::KeyPath {
+                    signature: sig.clone(),
+                    annex: Some(annex.clone()),
+                }),
+            ),
+            (
+                vec![
+                    sig.clone(),
+                    item("51"),
+                    control_block.clone(),
+                    annex.clone(),
+                ],
+                Some(TaprootWitness::ScriptPath {
+                    args: vec![sig.clone()],
+                    script: Script::from_hex("51").unwrap(),
+                    control_block: control_block.clone(),
+                    annex: Some(annex.clone()),
+                }),
+            ),
+            (vec![item("51"), item("c022")], None), // bad control block
+            (vec![item("0011")], None),             // bad signature length
+        ];
+
+        for case in cases.iter() {
+            let witness: Witness = case.0.clone();
+            assert_eq!(witness.as_taproot(), case.1);
+            let expected = case
+                .1
+                .clone()
+                .map(WitnessData::Taproot)
+                .unwrap_or(WitnessData::Unknown);
+            assert_eq!(witness.interpret(&spk), expected);
+        }
+    }
+}
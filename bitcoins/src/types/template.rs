@@ -0,0 +1,221 @@
+//! Script templates, for recognizing custom scripts without byte-offset matching.
+//!
+//! A template is a sequence of fixed opcodes and data pushes. Pushes may be exact data, or
+//! wildcards that accept any data of a specified size. Templates may be written in the same ASM
+//! format accepted by `ScriptPubkey::from_asm_or_hex`, with wildcard pushes written in braces:
+//!
+//! - `{20}` matches a push of exactly 20 bytes
+//! - `{33-65}` matches a push of 33 to 65 bytes, inclusive
+//! - `{*}` matches any push
+//!
+//! ```
+//! use bitcoins::types::{ScriptPubkey, Template};
+//!
+//! let template = Template::from_asm("OP_DUP OP_HASH160 {20} OP_EQUALVERIFY OP_CHECKSIG").unwrap();
+//! let spk = ScriptPubkey::from_hex("76a9140e5c3c8d420c7f11e88d76f7b860d471e6517a4488ac").unwrap();
+//! assert!(spk.matches_template(&template));
+//! ```
+
+use coins_core::ser::SerError;
+
+use crate::types::{
+    opcodes::{Instruction, Instructions, Opcode},
+    script::{opcode_from_name, Script, ScriptPubkey},
+};
+
+/// A single element of a `Template`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TemplateItem {
+    /// A fixed opcode. Data pushes do not match, except that `0x00` matches `OP_0`
+    Op(u8),
+    /// A push of exactly this data
+    Data(Vec<u8>),
+    /// A push of any data between `min` and `max` bytes long, inclusive
+    Push {
+        /// The minimum data length
+        min: usize,
+        /// The maximum data length
+        max: usize,
+    },
+}
+
+impl TemplateItem {
+    /// A push of any data exactly `len` bytes long
+    pub fn push(len: usize) -> Self {
+        TemplateItem::Push { min: len, max: len }
+    }
+
+    /// A push of any data
+    pub fn any_push() -> Self {
+        TemplateItem::Push {
+            min: 0,
+            max: usize::MAX,
+        }
+    }
+
+    fn matches(&self, instruction: &Instruction) -> bool {
+        match (self, pushed_data(instruction)) {
+            (TemplateItem::Op(op), None) => *op == instruction.opcode.to_u8(),
+            (TemplateItem::Data(data), Some(other)) => data[..] == other[..],
+            (TemplateItem::Push { min, max }, Some(data)) => {
+                *min <= data.len() && data.len() <= *max
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A script template. See the module documentation for the ASM format.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Template(Vec<TemplateItem>);
+
+/// The data pushed by an instruction. `OP_0` is matched as an opcode, not a push
+fn pushed_data<'a>(instruction: &Instruction<'a>) -> Option<&'a [u8]> {
+    match instruction.opcode {
+        Opcode::PushBytes(0) => None,
+        _ => instruction.data,
+    }
+}
+
+/// Parse a wildcard token like `{20}`, `{33-65}`, or `{*}`
+fn parse_wildcard(token: &str) -> Option<TemplateItem> {
+    let inner = token.strip_prefix('{')?.strip_suffix('}')?;
+    if inner == "*" {
+        return Some(TemplateItem::any_push());
+    }
+    let mut bounds = inner.splitn(2, '-');
+    let min = bounds.next()?.parse().ok()?;
+    let max = match bounds.next() {
+        Some(max) => max.parse().ok()?,
+        None => min,
+    };
+    if min > max {
+        return None;
+    }
+    Some(TemplateItem::Push { min, max })
+}
+
+impl Template {
+    /// Instantiate a template from its items
+    pub fn new(items: Vec<TemplateItem>) -> Self {
+        Self(items)
+    }
+
+    /// Parse a template from a whitespace-separated ASM string. See the module documentation.
+    pub fn from_asm(s: &str) -> Result<Self, SerError> {
+        let mut items = vec![];
+        for token in s.split_whitespace() {
+            if let Some(op) = opcode_from_name(token) {
+                items.push(TemplateItem::Op(op));
+                continue;
+            }
+            if token.starts_with('{') {
+                let item = parse_wildcard(token).ok_or_else(|| {
+                    SerError::ComponentError(format!("Invalid template wildcard: {}", token))
+                })?;
+                items.push(item);
+                continue;
+            }
+            let data = token
+                .strip_prefix('<')
+                .and_then(|t| t.strip_suffix('>'))
+                .unwrap_or(token);
+            let data = hex::decode(data)
+                .map_err(|_| SerError::ComponentError(format!("Unknown ASM token: {}", token)))?;
+            items.push(TemplateItem::Data(data));
+        }
+        Ok(Self(items))
+    }
+
+    /// Return the template items
+    pub fn items(&self) -> &[TemplateItem] {
+        &self.0
+    }
+
+    /// Match a serialized script against the template. If it matches, return the data of each
+    /// wildcard push, in order.
+    pub fn captures<'a>(&self, script: &'a [u8]) -> Option<Vec<&'a [u8]>> {
+        let instructions = Instructions::new(script)
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+        if instructions.len() != self.0.len() {
+            return None;
+        }
+        let mut captures = vec![];
+        for (item, instruction) in self.0.iter().zip(instructions.iter()) {
+            if !item.matches(instruction) {
+                return None;
+            }
+            if let (TemplateItem::Push { .. }, Some(data)) = (item, pushed_data(instruction)) {
+                captures.push(data);
+            }
+        }
+        Some(captures)
+    }
+}
+
+impl Script {
+    /// True if the script matches the template
+    pub fn matches_template(&self, template: &Template) -> bool {
+        template.captures(self.items()).is_some()
+    }
+}
+
+impl ScriptPubkey {
+    /// True if the script pubkey matches the template
+    pub fn matches_template(&self, template: &Template) -> bool {
+        template.captures(self.items()).is_some()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_matches_script_templates() {
+        // A hash timelocked contract
+        let template = Template::from_asm(
+            "OP_IF OP_SHA256 {32} OP_EQUALVERIFY {33} OP_ELSE {1-5} OP_CHECKLOCKTIMEVERIFY \
+             OP_DROP {33} OP_ENDIF OP_CHECKSIG",
+        )
+        .unwrap();
+        let hash = "ab".repeat(32);
+        let recipient = format!("02{}", "11".repeat(32));
+        let refund = format!("03{}", "22".repeat(32));
+        let htlc = ScriptPubkey::from_asm_or_hex(&format!(
+            "OP_IF OP_SHA256 {} OP_EQUALVERIFY {} OP_ELSE <e803> OP_CHECKLOCKTIMEVERIFY \
+             OP_DROP {} OP_ENDIF OP_CHECKSIG",
+            hash, recipient, refund
+        ))
+        .unwrap();
+
+        assert!(htlc.matches_template(&template));
+        let captures = template.captures(htlc.items()).unwrap();
+        assert_eq!(captures.len(), 4);
+        assert_eq!(captures[0], &hex::decode(&hash).unwrap()[..]);
+        assert_eq!(captures[2], &[0xe8, 0x03][..]);
+        assert_eq!(captures[3], &hex::decode(&refund).unwrap()[..]);
+
+        // Wrong push size, extra opcodes, and truncated pushes do not match
+        let p2pkh =
+            Template::from_asm("OP_DUP OP_HASH160 {20} OP_EQUALVERIFY OP_CHECKSIG").unwrap();
+        assert!(!htlc.matches_template(&p2pkh));
+        let short =
+            ScriptPubkey::from_hex("76a9130e5c3c8d420c7f11e88d76f7b860d471e6517a88ac").unwrap();
+        assert!(!short.matches_template(&p2pkh));
+        let truncated = Script::from_hex("76a914").unwrap();
+        assert!(!truncated.matches_template(&p2pkh));
+
+        // Exact data
+        let op_return = Template::from_asm("OP_RETURN <deadbeef> {*}").unwrap();
+        let spk = ScriptPubkey::from_asm_or_hex("OP_RETURN deadbeef 00ff").unwrap();
+        assert!(spk.matches_template(&op_return));
+        let spk = ScriptPubkey::from_asm_or_hex("OP_RETURN deadbeee 00ff").unwrap();
+        assert!(!spk.matches_template(&op_return));
+
+        assert!(Template::from_asm("{5-4}").is_err());
+        assert!(Template::from_asm("{x}").is_err());
+        assert!(Template::from_asm("OP_NOTANOPCODE").is_err());
+    }
+}
@@ -0,0 +1,717 @@
+//! Consistency checks between a transaction's locktime, its input sequence numbers, and the
+//! timelocks required by the scripts it spends.
+//!
+//! Inconsistent timelocks do not produce an invalid transaction. The transaction is simply
+//! rejected at broadcast, or its locktime is silently ignored. `validate_timelocks` catches these
+//! mistakes before broadcast.
+//!
+//! `LockTime`, `Sequence`, and `RelativeLock` wrap the raw consensus values, and distinguish
+//! heights from timestamps, and block-based from time-based relative locks.
+
+use std::convert::TryFrom;
+
+use thiserror::Error;
+
+use crate::types::{
+    opcodes::{Instructions, Opcode},
+    script::Script,
+    script_num::ScriptNum,
+    tx::BitcoinTransaction,
+};
+
+/// Locktimes and `OP_CHECKLOCKTIMEVERIFY` arguments below this value are block heights. Values at
+/// or above it are unix timestamps.
+pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+/// The sequence number that disables the locktime, if set on every input.
+pub const SEQUENCE_FINAL: u32 = 0xffff_ffff;
+
+/// If set in a sequence number, the sequence has no relative locktime meaning.
+pub const SEQUENCE_DISABLE_FLAG: u32 = 1 << 31;
+
+/// If set in a sequence number, its relative locktime is in units of 512 seconds. Otherwise it is
+/// in blocks.
+pub const SEQUENCE_TYPE_FLAG: u32 = 1 << 22;
+
+/// The bits of a sequence number that hold its relative locktime.
+pub const SEQUENCE_MASK: u32 = 0xffff;
+
+/// The maximum length of a number consumed by `OP_CHECKLOCKTIMEVERIFY` and
+/// `OP_CHECKSEQUENCEVERIFY`.
+const MAX_LOCKTIME_NUM_LEN: usize = 5;
+
+/// An inconsistency between a transaction's timelocks.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum TimelockError {
+    /// The locktime is set, but every input sequence is final, so it is not enforced
+    #[error("Locktime {} is not enforced, as all input sequences are final", .0)]
+    LocktimeNotEnforced(u32),
+
+    /// The input's script requires `OP_CHECKLOCKTIMEVERIFY`, but its sequence is final
+    #[error("Input {} requires a locktime, but its sequence is final", .0)]
+    FinalSequence(usize),
+
+    /// The input's script requires a later locktime, or a locktime of a different type
+    #[error("Input {input} requires locktime {required}. Got {locktime}")]
+    InsufficientLocktime {
+        /// The input index
+        input: usize,
+        /// The locktime required by the script
+        required: u32,
+        /// The transaction's locktime
+        locktime: u32,
+    },
+
+    /// The input's script requires `OP_CHECKSEQUENCEVERIFY`, which requires tx version 2
+    #[error("Input {input} requires a relative locktime, which requires version 2. Got {version}")]
+    RelativeLockVersion {
+        /// The input index
+        input: usize,
+        /// The transaction's version
+        version: u32,
+    },
+
+    /// The input's script requires a longer relative locktime, or one of a different type
+    #[error("Input {input} requires sequence {required:#x}. Got {sequence:#x}")]
+    InsufficientSequence {
+        /// The input index
+        input: usize,
+        /// The relative locktime required by the script
+        required: u32,
+        /// The input's sequence number
+        sequence: u32,
+    },
+}
+
+/// An absolute locktime, as used in the transaction locktime field and as the argument to
+/// `OP_CHECKLOCKTIMEVERIFY` (BIP65). Either a block height or a unix timestamp, which is compared
+/// against the median time past.
+#[derive(
+    serde::Serialize, serde::Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, Hash,
+)]
+#[serde(transparent)]
+pub struct LockTime(u32);
+
+impl LockTime {
+    /// The zero locktime, which imposes no restriction
+    pub const ZERO: LockTime = LockTime(0);
+
+    /// Instantiate a locktime from its consensus encoding
+    pub const fn from_consensus(locktime: u32) -> Self {
+        Self(locktime)
+    }
+
+    /// Instantiate a block height locktime. `None` if the height is not below
+    /// `LOCKTIME_THRESHOLD`
+    pub fn from_height(height: u32) -> Option<Self> {
+        if height < LOCKTIME_THRESHOLD {
+            Some(Self(height))
+        } else {
+            None
+        }
+    }
+
+    /// Instantiate a unix timestamp locktime. `None` if the timestamp is below
+    /// `LOCKTIME_THRESHOLD`
+    pub fn from_time(time: u32) -> Option<Self> {
+        if time >= LOCKTIME_THRESHOLD {
+            Some(Self(time))
+        } else {
+            None
+        }
+    }
+
+    /// Return the consensus encoding of the locktime
+    pub const fn to_consensus_u32(self) -> u32 {
+        self.0
+    }
+
+    /// True if the locktime is a block height
+    pub fn is_height(self) -> bool {
+        self.0 < LOCKTIME_THRESHOLD
+    }
+
+    /// Return the block height, if the locktime is a height
+    pub fn height(self) -> Option<u32> {
+        Some(self.0).filter(|_| self.is_height())
+    }
+
+    /// Return the unix timestamp, if the locktime is a timestamp
+    pub fn time(self) -> Option<u32> {
+        Some(self.0).filter(|_| !self.is_height())
+    }
+
+    /// True if both locktimes are heights, or both are timestamps
+    pub fn is_same_type(self, other: LockTime) -> bool {
+        self.is_height() == other.is_height()
+    }
+
+    /// True if a transaction with `locktime` satisfies an `OP_CHECKLOCKTIMEVERIFY` requiring
+    /// this locktime. The input's sequence must also be non-final.
+    pub fn is_satisfied_by(self, locktime: LockTime) -> bool {
+        self.is_same_type(locktime) && locktime.0 >= self.0
+    }
+
+    /// True if a transaction with this locktime may be included in a block at `height`, whose
+    /// previous block has `median_time_past` (BIP113). Transactions whose inputs all have final
+    /// sequence numbers are final regardless of their locktime.
+    pub fn is_final_at(self, height: u32, median_time_past: u32) -> bool {
+        if self.is_height() {
+            self.0 < height
+        } else {
+            self.0 < median_time_past
+        }
+    }
+
+    /// Return the script `<locktime> OP_CHECKLOCKTIMEVERIFY OP_DROP`
+    pub fn check_script(self) -> Script {
+        check_script(self.0, Opcode::CheckLockTimeVerify)
+    }
+}
+
+impl From<LockTime> for u32 {
+    fn from(locktime: LockTime) -> u32 {
+        locktime.0
+    }
+}
+
+/// A relative locktime, as encoded in an input's sequence number and as the argument to
+/// `OP_CHECKSEQUENCEVERIFY` (BIP68 and BIP112).
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RelativeLock {
+    /// A number of blocks
+    Blocks(u16),
+    /// A number of 512-second intervals
+    Time(u16),
+}
+
+impl RelativeLock {
+    /// Instantiate a time-based relative lock of at least `seconds`, rounded up to the next
+    /// 512-second interval. `None` if the duration is too long to encode.
+    pub fn from_seconds_ceil(seconds: u32) -> Option<Self> {
+        let intervals = seconds.div_ceil(512);
+        if intervals > SEQUENCE_MASK {
+            return None;
+        }
+        Some(RelativeLock::Time(intervals as u16))
+    }
+
+    /// Return the sequence number that enforces this relative lock
+    pub fn to_sequence(self) -> Sequence {
+        match self {
+            RelativeLock::Blocks(blocks) => Sequence(blocks as u32),
+            RelativeLock::Time(intervals) => Sequence(SEQUENCE_TYPE_FLAG | intervals as u32),
+        }
+    }
+
+    /// True if an input with `sequence` satisfies an `OP_CHECKSEQUENCEVERIFY` requiring this
+    /// relative lock. The transaction version must also be at least 2.
+    pub fn is_satisfied_by(self, sequence: Sequence) -> bool {
+        match (self, sequence.relative_lock()) {
+            (RelativeLock::Blocks(required), Some(RelativeLock::Blocks(n))) => n >= required,
+            (RelativeLock::Time(required), Some(RelativeLock::Time(n))) => n >= required,
+            _ => false,
+        }
+    }
+
+    /// True if an input with this relative lock, spending an output confirmed at
+    /// `prevout_height`, may be included in a block at `height` (BIP68). Time-based locks are
+    /// measured from `prevout_median_time_past`, the median time past of the block before the
+    /// prevout's block, to `median_time_past`, that of the block before `height`.
+    pub fn is_final_at(
+        self,
+        height: u32,
+        median_time_past: u32,
+        prevout_height: u32,
+        prevout_median_time_past: u32,
+    ) -> bool {
+        match self {
+            RelativeLock::Blocks(blocks) => height >= prevout_height.saturating_add(blocks as u32),
+            RelativeLock::Time(intervals) => {
+                median_time_past >= prevout_median_time_past.saturating_add((intervals as u32) << 9)
+            }
+        }
+    }
+}
+
+/// An input sequence number. Determines whether the transaction locktime is enforced, whether
+/// the input signals BIP125 replace-by-fee, and the input's BIP68 relative locktime.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+pub struct Sequence(u32);
+
+impl Default for Sequence {
+    fn default() -> Self {
+        Self::FINAL
+    }
+}
+
+impl Sequence {
+    /// The final sequence number. Disables the locktime if set on every input
+    pub const FINAL: Sequence = Sequence(SEQUENCE_FINAL);
+
+    /// The highest sequence number that enforces the locktime without signalling RBF
+    pub const ENABLE_LOCKTIME_NO_RBF: Sequence = Sequence(0xffff_fffe);
+
+    /// The highest sequence number that signals RBF
+    pub const ENABLE_RBF_NO_LOCKTIME: Sequence = Sequence(0xffff_fffd);
+
+    /// Instantiate a sequence number from its consensus encoding
+    pub const fn from_consensus(sequence: u32) -> Self {
+        Self(sequence)
+    }
+
+    /// Instantiate a sequence number with a relative lock of `blocks`
+    pub fn from_blocks(blocks: u16) -> Self {
+        RelativeLock::Blocks(blocks).to_sequence()
+    }
+
+    /// Instantiate a sequence number with a relative lock of `intervals` times 512 seconds
+    pub fn from_512_second_intervals(intervals: u16) -> Self {
+        RelativeLock::Time(intervals).to_sequence()
+    }
+
+    /// Return the consensus encoding of the sequence number
+    pub const fn to_consensus_u32(self) -> u32 {
+        self.0
+    }
+
+    /// True if the sequence number is final
+    pub fn is_final(self) -> bool {
+        self.0 == SEQUENCE_FINAL
+    }
+
+    /// True if the sequence number signals BIP125 replace-by-fee
+    pub fn signals_rbf(self) -> bool {
+        self.0 <= Self::ENABLE_RBF_NO_LOCKTIME.0
+    }
+
+    /// Return the relative lock, or `None` if the disable flag is set. Only enforced in
+    /// transactions with version 2 or higher.
+    pub fn relative_lock(self) -> Option<RelativeLock> {
+        if self.0 & SEQUENCE_DISABLE_FLAG != 0 {
+            return None;
+        }
+        let value = (self.0 & SEQUENCE_MASK) as u16;
+        if self.0 & SEQUENCE_TYPE_FLAG != 0 {
+            Some(RelativeLock::Time(value))
+        } else {
+            Some(RelativeLock::Blocks(value))
+        }
+    }
+
+    /// Return the script `<sequence> OP_CHECKSEQUENCEVERIFY OP_DROP`
+    pub fn check_script(self) -> Script {
+        check_script(self.0, Opcode::CheckSequenceVerify)
+    }
+}
+
+impl From<Sequence> for u32 {
+    fn from(sequence: Sequence) -> u32 {
+        sequence.0
+    }
+}
+
+impl From<RelativeLock> for Sequence {
+    fn from(lock: RelativeLock) -> Sequence {
+        lock.to_sequence()
+    }
+}
+
+/// Build `<n> <op> OP_DROP`
+fn check_script(n: u32, op: Opcode) -> Script {
+    let mut script = vec![];
+    ScriptNum::from(n).push_to(&mut script);
+    script.push(op.to_u8());
+    script.push(Opcode::Drop.to_u8());
+    Script::new(script)
+}
+
+/// The timelocks a spend of a script must satisfy.
+///
+/// Heights and timestamps are not comparable, so every argument is kept. A spend whose
+/// arguments mix heights and timestamps can never be satisfied.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ScriptTimelocks {
+    /// The arguments to `OP_CHECKLOCKTIMEVERIFY`
+    pub locktimes: Vec<u32>,
+    /// The arguments to `OP_CHECKSEQUENCEVERIFY`, ignoring arguments with the disable flag
+    pub sequences: Vec<u32>,
+}
+
+/// Decode a timelock argument. Returns `None` for negative or oversized numbers, which always
+/// fail timelock checks.
+fn decode_num(data: &[u8]) -> Option<u32> {
+    let n = ScriptNum::decode(data, MAX_LOCKTIME_NUM_LEN, false)
+        .ok()?
+        .value();
+    if n < 0 || n > u32::MAX as i64 {
+        None
+    } else {
+        Some(n as u32)
+    }
+}
+
+impl ScriptTimelocks {
+    /// Find the timelocks every spend of a script must satisfy. Timelocks inside `OP_IF` or
+    /// `OP_NOTIF` branches only guard some spends, and are not reported. Use
+    /// `from_script_branches` to find the timelocks of a particular spend path. Returns `None`
+    /// if the script can't be parsed.
+    pub fn from_script(script: &Script) -> Option<Self> {
+        Self::from_script_branches(script, &[])
+    }
+
+    /// Find the timelocks of a spend path through a script. `branches` holds the branch taken
+    /// at each `OP_IF` or `OP_NOTIF` the spend executes, in execution order. `true` takes the
+    /// first branch, and `false` the `OP_ELSE` branch. Timelocks inside conditionals beyond the
+    /// end of `branches` are not reported. Returns `None` if the script can't be parsed, or its
+    /// conditionals are unbalanced.
+    ///
+    /// The script is not executed, so whether the spend may take the path is not checked.
+    pub fn from_script_branches(script: &Script, branches: &[bool]) -> Option<Self> {
+        let mut timelocks = Self::default();
+        let mut last_num: Option<u32> = None;
+        let mut branches = branches.iter().copied();
+        // Whether each open conditional is executing, if known
+        let mut conditionals: Vec<Option<bool>> = vec![];
+
+        for ins in Instructions::new(script.items()) {
+            let ins = ins.ok()?;
+            let executing = conditionals.iter().all(|c| *c == Some(true));
+            let num = match ins.opcode {
+                Opcode::If | Opcode::NotIf => {
+                    let branch = if executing { branches.next() } else { None };
+                    conditionals.push(branch);
+                    None
+                }
+                Opcode::Else => {
+                    let last = conditionals.last_mut()?;
+                    *last = last.map(|b| !b);
+                    None
+                }
+                Opcode::EndIf => {
+                    conditionals.pop()?;
+                    None
+                }
+                Opcode::CheckLockTimeVerify => {
+                    if let Some(n) = last_num.filter(|_| executing) {
+                        timelocks.locktimes.push(n);
+                    }
+                    None
+                }
+                Opcode::CheckSequenceVerify => {
+                    let n = last_num.filter(|n| executing && n & SEQUENCE_DISABLE_FLAG == 0);
+                    if let Some(n) = n {
+                        timelocks.sequences.push(n);
+                    }
+                    None
+                }
+                _ => match (ins.data, ins.opcode.small_int()) {
+                    (Some(data), _) => decode_num(data),
+                    (None, Some(n)) => u32::try_from(n).ok(),
+                    (None, None) => None,
+                },
+            };
+            last_num = num;
+        }
+        if conditionals.is_empty() {
+            Some(timelocks)
+        } else {
+            None
+        }
+    }
+}
+
+/// Check the transaction's locktime and sequence numbers for consistency. `spend_scripts` holds
+/// the script executed by each input, in order, if known. This is the redeem script, witness
+/// script, or prevout script pubkey. Inputs without a known script are only checked against the
+/// locktime.
+///
+/// Only the timelocks every spend of a script must satisfy are checked. To check timelocks
+/// that guard a conditional branch, use `validate_spend_timelocks` with the timelocks of the
+/// spend path.
+pub fn validate_timelocks<T: BitcoinTransaction>(
+    tx: &T,
+    spend_scripts: &[Option<&Script>],
+) -> Result<(), TimelockError> {
+    let timelocks: Vec<_> = spend_scripts
+        .iter()
+        .map(|script| script.and_then(ScriptTimelocks::from_script))
+        .collect();
+    validate_spend_timelocks(tx, &timelocks)
+}
+
+/// Check the transaction's locktime and sequence numbers for consistency. `spend_timelocks`
+/// holds the timelocks of each input's spend path, in order, if known. See
+/// `ScriptTimelocks::from_script_branches`.
+pub fn validate_spend_timelocks<T: BitcoinTransaction>(
+    tx: &T,
+    spend_timelocks: &[Option<ScriptTimelocks>],
+) -> Result<(), TimelockError> {
+    let locktime = tx.locktime();
+    if locktime != 0 && tx.inputs().iter().all(|i| i.sequence == SEQUENCE_FINAL) {
+        return Err(TimelockError::LocktimeNotEnforced(locktime));
+    }
+
+    for (input, (txin, timelocks)) in tx.inputs().iter().zip(spend_timelocks.iter()).enumerate() {
+        let timelocks = match timelocks {
+            Some(timelocks) => timelocks,
+            None => continue,
+        };
+
+        if !timelocks.locktimes.is_empty() && Sequence::from_consensus(txin.sequence).is_final() {
+            return Err(TimelockError::FinalSequence(input));
+        }
+        for &required in timelocks.locktimes.iter() {
+            if !LockTime::from_consensus(required)
+                .is_satisfied_by(LockTime::from_consensus(locktime))
+            {
+                return Err(TimelockError::InsufficientLocktime {
+                    input,
+                    required,
+                    locktime,
+                });
+            }
+        }
+
+        for &required in timelocks.sequences.iter() {
+            if tx.version() < 2 {
+                return Err(TimelockError::RelativeLockVersion {
+                    input,
+                    version: tx.version(),
+                });
+            }
+            let sequence = txin.sequence;
+            let satisfied = Sequence::from_consensus(required)
+                .relative_lock()
+                .is_some_and(|lock| lock.is_satisfied_by(Sequence::from_consensus(sequence)));
+            if !satisfied {
+                return Err(TimelockError::InsufficientSequence {
+                    input,
+                    required,
+                    sequence,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        builder::BitcoinTxBuilder,
+        enc::MainnetEncoder,
+        types::{script::ScriptPubkey, tx::BitcoinTx, txin::BitcoinOutpoint},
+    };
+    use coins_core::builder::TxBuilder;
+
+    fn tx(version: u32, sequence: u32, locktime: u32) -> BitcoinTx {
+        BitcoinTxBuilder::<MainnetEncoder>::new()
+            .version(version)
+            .spend(BitcoinOutpoint::default(), sequence)
+            .pay_script_pubkey(1000, ScriptPubkey::null())
+            .locktime(locktime)
+            .build()
+            .unwrap()
+    }
+
+    fn script(asm: &str) -> Script {
+        Script::new(ScriptPubkey::from_asm_or_hex(asm).unwrap().items().to_vec())
+    }
+
+    #[test]
+    fn it_finds_script_timelocks() {
+        let csv = script("<9000> OP_CHECKSEQUENCEVERIFY OP_DROP OP_TRUE");
+        let cltv = script(
+            "OP_IF 16 OP_CHECKLOCKTIMEVERIFY OP_ELSE <0065cd1d> OP_CHECKLOCKTIMEVERIFY OP_ENDIF",
+        );
+        let disabled = script("<0000008000> OP_CHECKSEQUENCEVERIFY");
+        let truncated = script("OP_PUSHDATA1");
+        let unbalanced = script("OP_IF 16 OP_CHECKLOCKTIMEVERIFY");
+
+        assert_eq!(
+            ScriptTimelocks::from_script(&csv),
+            Some(ScriptTimelocks {
+                locktimes: vec![],
+                sequences: vec![144]
+            })
+        );
+        // Each branch has its own locktime, and neither is required by every spend
+        assert_eq!(
+            ScriptTimelocks::from_script(&cltv),
+            Some(Default::default())
+        );
+        let branch = |branches: &[bool]| {
+            ScriptTimelocks::from_script_branches(&cltv, branches)
+                .unwrap()
+                .locktimes
+        };
+        assert_eq!(branch(&[true]), vec![16]);
+        assert_eq!(branch(&[false]), vec![500_000_000]);
+        assert_eq!(
+            ScriptTimelocks::from_script(&disabled),
+            Some(Default::default())
+        );
+        assert_eq!(ScriptTimelocks::from_script(&truncated), None);
+        assert_eq!(ScriptTimelocks::from_script(&unbalanced), None);
+        assert_eq!(ScriptTimelocks::from_script(&script("OP_ENDIF")), None);
+    }
+
+    #[test]
+    fn it_validates_htlc_spend_paths() {
+        let htlc = script(
+            "OP_IF OP_SHA256 <0707070707070707070707070707070707070707070707070707070707070707> OP_EQUAL OP_ELSE <60ae0a> OP_CHECKLOCKTIMEVERIFY OP_DROP OP_TRUE OP_ENDIF",
+        );
+        // The claim path has no timelock
+        assert!(validate_timelocks(&tx(2, SEQUENCE_FINAL, 0), &[Some(&htlc)]).is_ok());
+        let claim = ScriptTimelocks::from_script_branches(&htlc, &[true]);
+        assert!(validate_spend_timelocks(&tx(2, SEQUENCE_FINAL, 0), &[claim]).is_ok());
+
+        // The refund path requires the locktime
+        let refund = [ScriptTimelocks::from_script_branches(&htlc, &[false])];
+        assert!(validate_spend_timelocks(&tx(2, 0xffff_fffe, 700_000), &refund).is_ok());
+        assert_eq!(
+            validate_spend_timelocks(&tx(2, 0xffff_fffe, 0), &refund),
+            Err(TimelockError::InsufficientLocktime {
+                input: 0,
+                required: 700_000,
+                locktime: 0
+            })
+        );
+        assert_eq!(
+            validate_spend_timelocks(&tx(2, SEQUENCE_FINAL, 0), &refund),
+            Err(TimelockError::FinalSequence(0))
+        );
+
+        // A path requiring both a height and a time can't be satisfied
+        let mixed = script(
+            "16 OP_CHECKLOCKTIMEVERIFY OP_DROP <0065cd1d> OP_CHECKLOCKTIMEVERIFY OP_DROP OP_TRUE",
+        );
+        for locktime in [16, 500_000_000].iter() {
+            assert!(validate_timelocks(&tx(2, 0xffff_fffe, *locktime), &[Some(&mixed)]).is_err());
+        }
+    }
+
+    #[test]
+    fn it_evaluates_relative_locks_against_the_prevout() {
+        // Confirmed at 100. 10 blocks later is 110
+        let blocks = RelativeLock::Blocks(10);
+        assert!(!blocks.is_final_at(109, u32::MAX, 100, 0));
+        assert!(blocks.is_final_at(110, 0, 100, u32::MAX));
+
+        // Measured between median times past, not header timestamps
+        let time = RelativeLock::Time(2);
+        assert!(!time.is_final_at(u32::MAX, 1_600_001_023, 0, 1_600_000_000));
+        assert!(time.is_final_at(0, 1_600_001_024, u32::MAX, 1_600_000_000));
+    }
+
+    #[test]
+    fn it_encodes_locktimes_and_sequences() {
+        let height = LockTime::from_height(700_000).unwrap();
+        assert_eq!(height.height(), Some(700_000));
+        assert_eq!(height.time(), None);
+        assert_eq!(LockTime::from_height(LOCKTIME_THRESHOLD), None);
+        assert_eq!(LockTime::from_time(LOCKTIME_THRESHOLD - 1), None);
+        let time = LockTime::from_consensus(1_600_000_000);
+        assert_eq!(time.time(), Some(1_600_000_000));
+        assert!(height.is_satisfied_by(LockTime::from_consensus(700_001)));
+        assert!(!height.is_satisfied_by(LockTime::from_consensus(699_999)));
+        assert!(!height.is_satisfied_by(time));
+        assert!(height.is_final_at(700_001, 0));
+        assert!(!height.is_final_at(700_000, u32::MAX));
+        assert!(time.is_final_at(0, 1_600_000_001));
+        assert!(!time.is_final_at(u32::MAX, 1_600_000_000));
+
+        assert_eq!(Sequence::from_blocks(144).to_consensus_u32(), 144);
+        assert_eq!(
+            Sequence::from_512_second_intervals(2).to_consensus_u32(),
+            SEQUENCE_TYPE_FLAG | 2
+        );
+        assert_eq!(
+            RelativeLock::from_seconds_ceil(1025),
+            Some(RelativeLock::Time(3))
+        );
+        assert_eq!(RelativeLock::from_seconds_ceil(512 * 0x1_0000), None);
+        assert_eq!(Sequence::FINAL.relative_lock(), None);
+        assert_eq!(
+            Sequence::from_consensus(0x0040_0005).relative_lock(),
+            Some(RelativeLock::Time(5))
+        );
+        assert!(RelativeLock::Blocks(10).is_satisfied_by(Sequence::from_blocks(10)));
+        assert!(!RelativeLock::Blocks(10).is_satisfied_by(Sequence::from_blocks(9)));
+        assert!(!RelativeLock::Blocks(10).is_satisfied_by(Sequence::from_512_second_intervals(10)));
+        assert!(Sequence::ENABLE_RBF_NO_LOCKTIME.signals_rbf());
+        assert!(!Sequence::ENABLE_LOCKTIME_NO_RBF.signals_rbf());
+        assert!(Sequence::default().is_final());
+
+        // Check scripts round-trip through script parsing
+        for n in [0, 16, 17, 127, 128, 0x8000, LOCKTIME_THRESHOLD, 0xffff_ffff].iter() {
+            let locktime = LockTime::from_consensus(*n);
+            assert_eq!(
+                ScriptTimelocks::from_script(&locktime.check_script())
+                    .unwrap()
+                    .locktimes,
+                vec![*n]
+            );
+        }
+        assert_eq!(
+            LockTime::from_consensus(144).check_script(),
+            script("<9000> OP_CHECKLOCKTIMEVERIFY OP_DROP")
+        );
+        assert_eq!(
+            Sequence::from_blocks(16).check_script(),
+            script("16 OP_CHECKSEQUENCEVERIFY OP_DROP")
+        );
+        assert_eq!(
+            Sequence::from_512_second_intervals(1).check_script(),
+            script("<010040> OP_CHECKSEQUENCEVERIFY OP_DROP")
+        );
+    }
+
+    #[test]
+    fn it_validates_timelocks() {
+        assert!(validate_timelocks(&tx(1, SEQUENCE_FINAL, 0), &[]).is_ok());
+        assert_eq!(
+            validate_timelocks(&tx(1, SEQUENCE_FINAL, 100), &[]),
+            Err(TimelockError::LocktimeNotEnforced(100))
+        );
+
+        let cltv = script("<e803> OP_CHECKLOCKTIMEVERIFY OP_DROP OP_TRUE");
+        assert!(validate_timelocks(&tx(1, 0xffff_fffe, 1000), &[Some(&cltv)]).is_ok());
+        assert_eq!(
+            validate_timelocks(&tx(1, SEQUENCE_FINAL, 0), &[Some(&cltv)]),
+            Err(TimelockError::FinalSequence(0))
+        );
+        assert_eq!(
+            validate_timelocks(&tx(1, 0xffff_fffe, LOCKTIME_THRESHOLD), &[Some(&cltv)]),
+            Err(TimelockError::InsufficientLocktime {
+                input: 0,
+                required: 1000,
+                locktime: LOCKTIME_THRESHOLD
+            })
+        );
+
+        let csv = script("<9000> OP_CHECKSEQUENCEVERIFY OP_DROP OP_TRUE");
+        assert!(validate_timelocks(&tx(2, 144, 0), &[Some(&csv)]).is_ok());
+        assert!(validate_timelocks(&tx(2, 143, 0), &[None]).is_ok());
+        assert_eq!(
+            validate_timelocks(&tx(1, 144, 0), &[Some(&csv)]),
+            Err(TimelockError::RelativeLockVersion {
+                input: 0,
+                version: 1
+            })
+        );
+        for sequence in [143, 144 | SEQUENCE_TYPE_FLAG, SEQUENCE_FINAL].iter() {
+            assert_eq!(
+                validate_timelocks(&tx(2, *sequence, 0), &[Some(&csv)]),
+                Err(TimelockError::InsufficientSequence {
+                    input: 0,
+                    required: 144,
+                    sequence: *sequence
+                })
+            );
+        }
+    }
+}
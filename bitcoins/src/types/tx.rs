@@ -1,5 +1,8 @@
 //! Bitcoin transaction types and associated sighash arguments.
-use std::io::{Error as IOError, Read, Write};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Error as IOError, Read, Write},
+};
 use thiserror::Error;
 
 use coins_core::{
@@ -9,10 +12,17 @@ use coins_core::{
 };
 
 use crate::{
+    enc::encoder::BitcoinEncoderMarker,
     hashes::TXID,
     types::{
+        amount::Amount,
+        block::MAX_BLOCK_WEIGHT,
+        ctv,
+        display::DetailedTx,
+        json,
         legacy::*,
-        script::Witness,
+        script::{Script, Witness},
+        timelock::{self, TimelockError},
         txin::{BitcoinOutpoint, BitcoinTxIn},
         txout::TxOut,
         witness::*,
@@ -22,7 +32,10 @@ use crate::{
 /// Wrapper enum for returning values that may be EITHER a Witness OR a Legacy tx and the type is
 /// not known in advance. While a few transaction methods have been implemented for convenience,
 /// This wrapper must be explicitly unwrapped before the tx object can be signed.
-#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Eq, PartialEq)]
+///
+/// Deserializing a `BitcoinTx` detects the serialization format, so raw transactions from RPC or
+/// Esplora can be parsed without knowing their type in advance.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum BitcoinTx {
     /// Witness
     Witness(WitnessTx),
@@ -49,20 +62,50 @@ impl From<LegacyTx> for BitcoinTx {
 }
 
 impl BitcoinTx {
-    // /// Deserialize a hex string. Determine type information from the segwit marker `0001`
-    // /// immediately following the version bytes. This produces a `BitcoinTx` enum that must be
-    // /// explicitly cast to the desired type via `into_witness` or `into_legacy`.
-    // ///
-    // /// # Note
-    // ///
-    // /// Casting directly to legacy may drop witness information if the tx is witness
-    // pub fn deserialize_hex(hex: &str) -> Result<BitcoinTx, TxError> {
-    //     if &hex[8..12] == "0001" {
-    //         WitnessTx::deserialize_hex(hex).map(BitcoinTx::Witness)
-    //     } else {
-    //         LegacyTx::deserialize_hex(hex).map(BitcoinTx::Legacy)
-    //     }
-    // }
+    /// Deserialize a transaction, enforcing the caps in `limits`. The BIP144 segwit marker
+    /// following the version determines whether a `Witness` or `Legacy` tx is returned.
+    ///
+    /// A 0 marker byte is also the input count of a legacy transaction with no inputs. If the
+    /// flag byte following it is not 1, the transaction is read as legacy instead. If that fails
+    /// on a malformed encoding, the unknown flag is reported as `BadWitnessFlag`. IO errors,
+    /// truncated input, and `limits` violations are returned as is.
+    ///
+    /// This retry is deliberately more lenient than Bitcoin Core, which rejects any unknown flag
+    /// ("Unknown transaction optional data"). Some byte strings Core rejects decode here as
+    /// legacy transactions with no inputs. Validate such transactions before relaying them.
+    pub fn read_from_limited<R>(reader: &mut R, limits: &TxLimits) -> TxResult<Self>
+    where
+        R: Read,
+    {
+        // Read the first 6 bytes, look for the witness marker, then chain them back on the front
+        // of the reader
+        let mut tag = [0u8; 6];
+        reader.read_exact(&mut tag)?;
+        let mut chain = tag.chain(reader);
+        match (tag[4], tag[5]) {
+            (0, 1) => Ok(BitcoinTx::Witness(WitnessTx::read_from_limited(
+                &mut chain, limits,
+            )?)),
+            (0, flag) => LegacyTx::read_from_limited(&mut chain, limits)
+                .map(BitcoinTx::Legacy)
+                .map_err(|e| match e {
+                    TxError::IoError(_)
+                    | TxError::SerError(SerError::IoError(_))
+                    | TxError::SerError(SerError::InsufficientSeqItems { .. })
+                    | TxError::SerError(SerError::ExcessiveLength { .. }) => e,
+                    _ => TxError::BadWitnessFlag([0, flag]),
+                }),
+            _ => Ok(BitcoinTx::Legacy(LegacyTx::read_from_limited(
+                &mut chain, limits,
+            )?)),
+        }
+    }
+
+    /// Deserialize a transaction from hex, enforcing the caps in `limits`
+    pub fn deserialize_hex_limited(hex: &str, limits: &TxLimits) -> TxResult<Self> {
+        let v = hex::decode(hex).map_err(SerError::from)?;
+        Self::read_from_limited(&mut v.as_slice(), limits)
+    }
 
     /// True if the wrapped tx is a witness transaction. False otherwise
     pub fn is_witness(&self) -> bool {
@@ -91,16 +134,7 @@ impl ByteFormat for BitcoinTx {
         R: Read,
         Self: std::marker::Sized,
     {
-        // Read the first 6 bytes, look for the witness tag, then chain them back on the front
-        // of the reader
-        let mut tag = [0u8; 6];
-        reader.read_exact(&mut tag)?;
-        let mut chain = tag.chain(reader);
-        if tag[4..=5] == [0, 1] {
-            Ok(BitcoinTx::Witness(WitnessTx::read_from(&mut chain)?))
-        } else {
-            Ok(BitcoinTx::Legacy(LegacyTx::read_from(&mut chain)?))
-        }
+        Self::read_from_limited(reader, &TxLimits::default())
     }
 
     fn write_to<W>(&self, writer: &mut W) -> Result<usize, <Self as ByteFormat>::Error>
@@ -190,6 +224,12 @@ impl Transaction for BitcoinTx {
     }
 }
 
+/// The minimum length of a coinbase script sig.
+const MIN_COINBASE_SCRIPT_LEN: usize = 2;
+
+/// The maximum length of a coinbase script sig.
+const MAX_COINBASE_SCRIPT_LEN: usize = 100;
+
 /// An Error type for transaction objects
 #[derive(Debug, Error)]
 pub enum TxError {
@@ -201,13 +241,14 @@ pub enum TxError {
     #[error(transparent)]
     IoError(#[from] IOError),
 
-    /// Sighash NONE is unsupported
+    /// Sighash NONE is unsupported, unless opted in with a `*_allowing_none` method
     #[error("SIGHASH_NONE is unsupported")]
     NoneUnsupported,
 
-    /// Satoshi's sighash single bug. Throws an error here.
-    #[error("SIGHASH_SINGLE bug is unsupported")]
-    SighashSingleBug,
+    /// Satoshi's sighash single bug. Throws an error here. Holds the index of the input, which
+    /// has no output at the same index
+    #[error("SIGHASH_SINGLE bug is unsupported. Input {} has no matching output", .0)]
+    SighashSingleBug(usize),
 
     /// Caller provided an unknown sighash type to `Sighash::from_u8`
     #[error("Unknown Sighash: {}", .0)]
@@ -229,11 +270,160 @@ pub enum TxError {
     /// No inputs in vin
     #[error("Vin may not be empty")]
     EmptyVin,
+
+    /// An output's value is below its dust limit
+    #[error("Output {index} value {value} is below the dust limit of {limit}")]
+    DustOutput {
+        /// The index of the output
+        index: usize,
+        /// The value of the output
+        value: u64,
+        /// The dust limit of the output
+        limit: u64,
+    },
+
+    /// The transaction is not a coinbase. A coinbase has exactly one input, which spends the
+    /// null outpoint
+    #[error("Transaction is not a coinbase")]
+    NotCoinbase,
+
+    /// The coinbase script sig must be between 2 and 100 bytes
+    #[error("Coinbase script sig length {} is outside the range 2..=100", .0)]
+    CoinbaseScriptLength(usize),
+
+    /// An output's value exceeds `MAX_MONEY`
+    #[error("Output {index} value {value} exceeds MAX_MONEY")]
+    OutputValueTooLarge {
+        /// The index of the output
+        index: usize,
+        /// The value of the output
+        value: u64,
+    },
+
+    /// The sum of the output values exceeds `MAX_MONEY`
+    #[error("Total output value exceeds MAX_MONEY")]
+    TotalOutputValueTooLarge,
+
+    /// A prevout's value exceeds `MAX_MONEY`
+    #[error("Input {index} spends value {value}, which exceeds MAX_MONEY")]
+    InputValueTooLarge {
+        /// The index of the input
+        index: usize,
+        /// The value of the prevout
+        value: u64,
+    },
+
+    /// The sum of the prevout values exceeds `MAX_MONEY`
+    #[error("Total input value exceeds MAX_MONEY")]
+    TotalInputValueTooLarge,
+
+    /// No prevout was provided for the input at this index
+    #[error("Missing prevout for input {}", .0)]
+    MissingPrevout(usize),
+
+    /// The transaction's size without witness data exceeds the block weight limit
+    #[error("Transaction weight without witnesses {} exceeds MAX_BLOCK_WEIGHT", .0)]
+    Oversize(usize),
+
+    /// The input spends the same outpoint as an earlier input
+    #[error("Input {} spends a duplicate outpoint", .0)]
+    DuplicateInput(usize),
+
+    /// The input spends the null outpoint, but the transaction is not a coinbase
+    #[error("Input {} spends the null outpoint outside a coinbase", .0)]
+    NullOutpoint(usize),
+
+    /// The outputs spend more than the inputs provide
+    #[error("Input value {input_value} is less than output value {output_value}")]
+    InsufficientInputValue {
+        /// The sum of the prevout values
+        input_value: u64,
+        /// The sum of the output values
+        output_value: u64,
+    },
+}
+
+impl TxError {
+    /// A stable, machine-readable `tx.*` code for the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            TxError::SerError(_) => "tx.ser",
+            TxError::IoError(_) => "tx.io",
+            TxError::NoneUnsupported => "tx.sighash_none_unsupported",
+            TxError::SighashSingleBug(_) => "tx.sighash_single_bug",
+            TxError::UnknownSighash(_) => "tx.unknown_sighash",
+            TxError::BadWitnessFlag(_) => "tx.bad_witness_flag",
+            TxError::WrongSighashArgs => "tx.wrong_sighash_args",
+            TxError::EmptyVout => "tx.empty_vout",
+            TxError::EmptyVin => "tx.empty_vin",
+            TxError::DustOutput { .. } => "tx.dust_output",
+            TxError::NotCoinbase => "tx.not_coinbase",
+            TxError::CoinbaseScriptLength(_) => "tx.coinbase_script_length",
+            TxError::OutputValueTooLarge { .. } => "tx.output_value_too_large",
+            TxError::TotalOutputValueTooLarge => "tx.total_output_value_too_large",
+            TxError::InputValueTooLarge { .. } => "tx.input_value_too_large",
+            TxError::TotalInputValueTooLarge => "tx.total_input_value_too_large",
+            TxError::MissingPrevout(_) => "tx.missing_prevout",
+            TxError::InsufficientInputValue { .. } => "tx.insufficient_input_value",
+            TxError::Oversize(_) => "tx.oversize",
+            TxError::DuplicateInput(_) => "tx.duplicate_input",
+            TxError::NullOutpoint(_) => "tx.null_outpoint",
+        }
+    }
+
+    /// True if the operation may succeed if retried unchanged. Transaction errors are
+    /// deterministic, so this is always false.
+    pub fn is_retryable(&self) -> bool {
+        false
+    }
+
+    /// True if the error was caused by the caller's input, e.g. a malformed transaction or an
+    /// invalid sighash request, rather than by an IO failure.
+    pub fn is_user_error(&self) -> bool {
+        !matches!(self, TxError::IoError(_))
+    }
 }
 
 /// Type alias for result with TxError
 pub type TxResult<T> = Result<T, TxError>;
 
+/// Caps on the sizes of transaction components, enforced during deserialization. Length
+/// prefixes are checked before the data they describe is read, so malformed or malicious
+/// payloads error with `SerError::ExcessiveLength` instead of causing huge reads or allocations.
+///
+/// The default caps are the largest values in any transaction that fits in a block, so no
+/// consensus-valid transaction is rejected. Set tighter caps when parsing untrusted data that is
+/// expected to be standard.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TxLimits {
+    /// The maximum number of inputs
+    pub max_inputs: usize,
+    /// The maximum number of outputs
+    pub max_outputs: usize,
+    /// The maximum length of a script sig or script pubkey, in bytes
+    pub max_script_len: usize,
+    /// The maximum number of witness stack items in each input's witness
+    pub max_witness_items: usize,
+    /// The maximum length of a witness stack item, in bytes
+    pub max_witness_item_len: usize,
+}
+
+impl Default for TxLimits {
+    fn default() -> Self {
+        // Non-witness bytes weigh 4 units each
+        let max_base_size = MAX_BLOCK_WEIGHT / 4;
+        Self {
+            // Outpoint, empty script sig, and sequence
+            max_inputs: max_base_size / 41,
+            // Value and empty script pubkey
+            max_outputs: max_base_size / 9,
+            max_script_len: max_base_size,
+            max_witness_items: MAX_BLOCK_WEIGHT,
+            max_witness_item_len: MAX_BLOCK_WEIGHT,
+        }
+    }
+}
+
 /// Functions common to Bitcoin transactions. This provides a small abstraction layer over the
 /// Legacy/SegWit tx divide by implementing a small common interface between them.
 pub trait BitcoinTransaction:
@@ -262,6 +452,182 @@ pub trait BitcoinTransaction:
     /// For witness txns, this will ALWAYS be the same length as the input vector.
     fn witnesses(&self) -> &[Witness];
 
+    /// The length of the transaction serialized without witness data. For legacy transactions
+    /// this is the serialized length.
+    fn stripped_size(&self) -> usize {
+        self.as_legacy().serialized_length()
+    }
+
+    /// The BIP141 weight of the transaction. Non-witness bytes count as 4 weight units, and
+    /// witness bytes (including the marker and flag) count as 1.
+    fn weight(&self) -> usize {
+        self.stripped_size() * 3 + self.serialized_length()
+    }
+
+    /// The BIP141 virtual size of the transaction. This is the weight divided by 4, rounded up.
+    fn vsize(&self) -> usize {
+        self.weight().div_ceil(4)
+    }
+
+    /// True if the transaction is a coinbase. A coinbase has exactly one input, which spends the
+    /// null outpoint.
+    fn is_coinbase(&self) -> bool {
+        self.inputs().len() == 1 && self.inputs()[0].outpoint.is_null()
+    }
+
+    /// True if the transaction signals BIP125 replace-by-fee. A transaction signals if any of its
+    /// inputs has a sequence number below `0xffff_fffe`. Replaceability inherited from
+    /// unconfirmed ancestors is not considered.
+    fn signals_rbf(&self) -> bool {
+        self.inputs().iter().any(BitcoinTxIn::signals_rbf)
+    }
+
+    /// Check that the transaction is a coinbase, and that its script sig length is within the
+    /// consensus limits of 2 to 100 bytes.
+    fn check_coinbase(&self) -> TxResult<()> {
+        if !self.is_coinbase() {
+            return Err(TxError::NotCoinbase);
+        }
+        let len = self.inputs()[0].script_sig.len();
+        if !(MIN_COINBASE_SCRIPT_LEN..=MAX_COINBASE_SCRIPT_LEN).contains(&len) {
+            return Err(TxError::CoinbaseScriptLength(len));
+        }
+        Ok(())
+    }
+
+    /// Check that each output value, and the sum of the output values, is at most `MAX_MONEY`.
+    ///
+    /// If `prevouts` is provided, also check the values of the prevouts spent by each input, and
+    /// that the inputs provide at least as much value as the outputs spend. Every input must
+    /// have a prevout, except in a coinbase, whose input values are not checked.
+    fn validate(&self, prevouts: Option<&HashMap<BitcoinOutpoint, TxOut>>) -> TxResult<()> {
+        let mut output_value = Amount::ZERO;
+        for (index, output) in self.outputs().iter().enumerate() {
+            let value = Amount::from_sat(output.value).map_err(|_| {
+                TxError::OutputValueTooLarge {
+                    index,
+                    value: output.value,
+                }
+            })?;
+            output_value = output_value
+                .checked_add(value)
+                .ok_or(TxError::TotalOutputValueTooLarge)?;
+        }
+
+        let prevouts = match prevouts {
+            Some(prevouts) if !self.is_coinbase() => prevouts,
+            _ => return Ok(()),
+        };
+        let mut input_value = Amount::ZERO;
+        for (index, input) in self.inputs().iter().enumerate() {
+            let prevout = prevouts
+                .get(&input.outpoint)
+                .ok_or(TxError::MissingPrevout(index))?;
+            let value = Amount::from_sat(prevout.value).map_err(|_| {
+                TxError::InputValueTooLarge {
+                    index,
+                    value: prevout.value,
+                }
+            })?;
+            input_value = input_value
+                .checked_add(value)
+                .ok_or(TxError::TotalInputValueTooLarge)?;
+        }
+
+        if input_value < output_value {
+            return Err(TxError::InsufficientInputValue {
+                input_value: input_value.to_sat(),
+                output_value: output_value.to_sat(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Run the context-free consensus checks on the transaction, like Bitcoin Core's
+    /// `CheckTransaction`. Checks that vin and vout are non-empty, the transaction fits in a
+    /// block, output values are in range, and no outpoint is spent twice. A coinbase must have a
+    /// valid script sig length, and other transactions may not spend the null outpoint.
+    ///
+    /// A transaction that passes may still be non-standard. Oversize scripts are consensus-valid
+    /// until executed, and are caught by `policy::Policy::check`. An unenforced locktime is
+    /// valid, and is caught by `validate_timelocks`.
+    fn check_sanity(&self) -> TxResult<()>
+    where
+        Self: Sized,
+    {
+        if self.inputs().is_empty() {
+            return Err(TxError::EmptyVin);
+        }
+        if self.outputs().is_empty() {
+            return Err(TxError::EmptyVout);
+        }
+        let stripped_weight = self.stripped_size() * 4;
+        if stripped_weight > MAX_BLOCK_WEIGHT {
+            return Err(TxError::Oversize(stripped_weight));
+        }
+        self.validate(None)?;
+
+        let mut outpoints = HashSet::new();
+        for (index, input) in self.inputs().iter().enumerate() {
+            if !outpoints.insert(input.outpoint) {
+                return Err(TxError::DuplicateInput(index));
+            }
+        }
+
+        if self.is_coinbase() {
+            self.check_coinbase()?;
+        } else if let Some(index) = self.inputs().iter().position(|i| i.outpoint.is_null()) {
+            return Err(TxError::NullOutpoint(index));
+        }
+        Ok(())
+    }
+
+    /// The BIP119 default template hash of the transaction, as spent by the input at `index`.
+    /// See `ctv::default_template_hash`.
+    fn ctv_template_hash(&self, index: u32) -> [u8; 32]
+    where
+        Self: Sized,
+    {
+        ctv::default_template_hash(self, index)
+    }
+
+    /// Return a formatter that describes the transaction in detail, over multiple lines. Useful
+    /// for debugging and logging.
+    fn detailed(&self) -> DetailedTx<'_, Self>
+    where
+        Self: Sized,
+    {
+        DetailedTx(self)
+    }
+
+    /// Describe the transaction as a JSON object, in the style of Bitcoin Core's
+    /// `decoderawtransaction`
+    fn to_json_value(&self) -> serde_json::Value
+    where
+        Self: Sized,
+    {
+        json::tx_json(self, json::no_address)
+    }
+
+    /// Describe the transaction as a JSON object, including the addresses of its outputs under
+    /// the encoder `E`
+    fn to_json_value_with<E: BitcoinEncoderMarker>(&self) -> serde_json::Value
+    where
+        Self: Sized,
+    {
+        json::tx_json(self, json::encoder_address::<E>)
+    }
+
+    /// Check the locktime and input sequence numbers for consistency with each other, and with
+    /// the timelocks required by the scripts spent. `spend_scripts` holds the script executed by
+    /// each input, if known. See `timelock::validate_timelocks`.
+    fn validate_timelocks(&self, spend_scripts: &[Option<&Script>]) -> Result<(), TimelockError>
+    where
+        Self: Sized,
+    {
+        timelock::validate_timelocks(self, spend_scripts)
+    }
+
     /// Get a reference to the output by
     fn txout_from_outpoint(&self, outpoint: &BitcoinOutpoint) -> Option<&TxOut> {
         if outpoint.txid == self.txid() && (outpoint.idx as usize) < self.outputs().len() {
@@ -303,8 +669,9 @@ impl BitcoinTransaction for BitcoinTx {
 }
 
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
-/// All possible Sighash modes
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+#[serde(try_from = "u8", into = "u8")]
+/// All possible Sighash modes. Serializes as its flag byte.
 pub enum Sighash {
     /// Sign ALL inputs and ALL outputs
     All = 0x01,
@@ -340,10 +707,44 @@ impl Sighash {
     }
 }
 
+impl std::convert::TryFrom<u8> for Sighash {
+    type Error = TxError;
+
+    fn try_from(flag: u8) -> Result<Sighash, TxError> {
+        Sighash::from_u8(flag)
+    }
+}
+
+impl From<Sighash> for u8 {
+    fn from(flag: Sighash) -> u8 {
+        flag.to_u8()
+    }
+}
+
+impl std::fmt::Display for Sighash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Sighash::All => "SIGHASH_ALL",
+            Sighash::None => "SIGHASH_NONE",
+            Sighash::Single => "SIGHASH_SINGLE",
+            Sighash::AllAcp => "SIGHASH_ALL|ANYONECANPAY",
+            Sighash::NoneAcp => "SIGHASH_NONE|ANYONECANPAY",
+            Sighash::SingleAcp => "SIGHASH_SINGLE|ANYONECANPAY",
+        };
+        write!(f, "{}", s)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use super::*;
-    use crate::prelude::*;
+    use crate::{
+        policy::{Policy, PolicyViolation},
+        prelude::*,
+        types::{timelock::LockTime, txout::MAX_SCRIPT_SIZE},
+    };
 
     #[test]
     fn it_calculates_legacy_sighashes_and_txids() {
@@ -582,6 +983,357 @@ mod tests {
         assert_eq!(tx.wtxid(), wtxid);
     }
 
+    #[test]
+    fn it_calculates_weight_and_vsize() {
+        // from mainnet: 3c7fb4af9b7bd2ba6f155318e0bc8a50432d4732ab6e36293ef45b304567b46a
+        let tx_hex = "01000000000101b77bebb3ac480e99c0d95a4c812137b116e65e2f3b3a66a36d0e252928d460180100000000ffffffff03982457000000000017a91417b8e0f150215cc70bf2fb58070041d655b162dd8740e133000000000017a9142535e444f7d55f0500c1f86609d6cfc289576b698747abfb0100000000220020701a8d401c84fb13e6baf169d59684e17abd9fa216c8cc5b9fc63d622ff8c58d040047304402205c6a889efa26955bef7ce2b08792e63e25eac9859080f0d83912b0ea833d7eb402205f859f4640f1600db5012b467ec05bb4ae1779640c1b5fadc8908960740e52b30147304402201c239ea25cfeadfa9493a1b0d136d70f50f821385972b7188c4329c2bf2d23a302201ee790e4b6794af6567f85a226a387d5b0222c3dc90d2fc558d09e08062b8271016952210375e00eb72e29da82b89367947f29ef34afb75e8654f6ea368e0acdfd92976b7c2103a1b26313f430c4b15bb1fdce663207659d8cac749a0e53d70eff01874496feff2103c96d495bfdd5ba4145e3e046fee45e84a8a48ad05bd8dbb395c011a32cf9f88053ae00000000";
+        let tx = WitnessTx::deserialize_hex(tx_hex).unwrap();
+        assert_eq!(tx.serialized_length(), 412);
+        assert_eq!(tx.stripped_size(), 158);
+        assert_eq!(tx.weight(), 886);
+        assert_eq!(tx.vsize(), 222);
+
+        let tx = BitcoinTx::from(tx);
+        assert_eq!(tx.vsize(), 222);
+
+        let legacy = tx.into_legacy();
+        assert_eq!(legacy.stripped_size(), 158);
+        assert_eq!(legacy.weight(), 158 * 4);
+        assert_eq!(legacy.vsize(), 158);
+    }
+
+    #[test]
+    fn it_checks_coinbases() {
+        let coinbase = |script_sig: Vec<u8>| {
+            let vin = vec![BitcoinTxIn::new(
+                BitcoinOutpoint::null(),
+                script_sig,
+                0xffff_ffff,
+            )];
+            LegacyTx::new(1, vin, vec![TxOut::default()], 0).unwrap()
+        };
+        assert!(coinbase(vec![0; 2]).check_coinbase().is_ok());
+        assert!(coinbase(vec![0; 100]).check_coinbase().is_ok());
+        for len in [0, 1, 101].iter() {
+            let tx = coinbase(vec![0; *len]);
+            assert!(tx.is_coinbase());
+            match tx.check_coinbase() {
+                Err(TxError::CoinbaseScriptLength(l)) => assert_eq!(l, *len),
+                _ => panic!("expected CoinbaseScriptLength"),
+            }
+        }
+
+        let spend = BitcoinTxIn::new(BitcoinOutpoint::new(TXID::default(), 0), vec![], 0);
+        let not_coinbase = LegacyTx::new(1, vec![spend], vec![TxOut::default()], 0).unwrap();
+        assert!(!not_coinbase.is_coinbase());
+        match not_coinbase.check_coinbase() {
+            Err(TxError::NotCoinbase) => {}
+            _ => panic!("expected NotCoinbase"),
+        }
+
+        let utxo = Utxo::from_tx_output(&coinbase(vec![0; 2]), 0);
+        assert!(!utxo.is_mature(100, 198));
+        assert!(utxo.is_mature(100, 199));
+        assert!(!utxo.is_mature(u32::MAX, u32::MAX));
+    }
+
+    #[test]
+    fn it_checks_transaction_sanity() {
+        let outpoint = |idx| BitcoinOutpoint::new(TXID::default(), idx);
+        let input = |idx| BitcoinTxIn::new(outpoint(idx), ScriptSig::null(), 0);
+        let output = || TxOut::new(1000, ScriptPubkey::null());
+        let tx = |vin: Vec<BitcoinTxIn>, vout: Vec<TxOut>| LegacyTx {
+            version: 2,
+            vin,
+            vout,
+            locktime: 0,
+        };
+
+        assert!(tx(vec![input(0), input(1)], vec![output()])
+            .check_sanity()
+            .is_ok());
+        match tx(vec![], vec![output()]).check_sanity() {
+            Err(TxError::EmptyVin) => {}
+            _ => panic!("expected EmptyVin"),
+        }
+        match tx(vec![input(0)], vec![]).check_sanity() {
+            Err(TxError::EmptyVout) => {}
+            _ => panic!("expected EmptyVout"),
+        }
+        match tx(vec![input(0), input(1), input(0)], vec![output()]).check_sanity() {
+            Err(TxError::DuplicateInput(2)) => {}
+            _ => panic!("expected DuplicateInput"),
+        }
+        match tx(vec![input(0), input(0xffff_ffff)], vec![output()]).check_sanity() {
+            Err(TxError::NullOutpoint(1)) => {}
+            _ => panic!("expected NullOutpoint"),
+        }
+        match tx(vec![input(0)], vec![TxOut::null()]).check_sanity() {
+            Err(TxError::OutputValueTooLarge { index: 0, .. }) => {}
+            _ => panic!("expected OutputValueTooLarge"),
+        }
+
+        // Oversize scripts are consensus-valid until executed, but non-standard
+        let big_script = vec![0u8; MAX_SCRIPT_SIZE + 1];
+        let big_input = BitcoinTxIn::new(outpoint(0), big_script.clone(), 0);
+        let big_output = TxOut::new(1000, big_script);
+        let oversize = tx(vec![big_input], vec![output(), big_output]);
+        assert!(oversize.check_sanity().is_ok());
+        let violations = Policy::default().check(&oversize, &HashMap::new());
+        assert!(violations.contains(&PolicyViolation::ScriptSigSize {
+            input: 0,
+            size: MAX_SCRIPT_SIZE + 1
+        }));
+        assert!(violations.contains(&PolicyViolation::NonStandardOutput(1)));
+
+        let huge_output = TxOut::new(0, vec![0u8; MAX_BLOCK_WEIGHT / 4]);
+        match tx(vec![input(0)], vec![huge_output]).check_sanity() {
+            Err(TxError::Oversize(_)) => {}
+            _ => panic!("expected Oversize"),
+        }
+
+        let coinbase_input = BitcoinTxIn::new(BitcoinOutpoint::null(), vec![0u8; 1], 0);
+        match tx(vec![coinbase_input], vec![output()]).check_sanity() {
+            Err(TxError::CoinbaseScriptLength(1)) => {}
+            _ => panic!("expected CoinbaseScriptLength"),
+        }
+
+        let mut unenforced = tx(vec![input(0)], vec![output()]);
+        unenforced.vin[0].sequence = 0xffff_ffff;
+        unenforced.locktime = 100;
+        assert!(unenforced.check_sanity().is_ok());
+        assert_eq!(
+            unenforced.validate_timelocks(&[]),
+            Err(TimelockError::LocktimeNotEnforced(100))
+        );
+    }
+
+    #[test]
+    fn it_accepts_coinbases_with_unenforced_locktimes() {
+        // A coinbase for height 840,000 with its locktime set to the previous height, and a
+        // final sequence, as proposed by BIP54. The locktime is not enforced, which is valid.
+        let script_sig = hex::decode("0340d10c0f2f736c7573682f").unwrap();
+        let input = BitcoinTxIn::new(BitcoinOutpoint::null(), script_sig, 0xffff_ffff);
+        let output = TxOut::new(
+            312_500_000,
+            ScriptPubkey::from_hex("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928").unwrap(),
+        );
+        let coinbase = LegacyTx::new(2, vec![input], vec![output], 839_999).unwrap();
+        assert!(coinbase.is_coinbase());
+        assert!(coinbase.check_sanity().is_ok());
+        assert!(LockTime::from_height(coinbase.locktime())
+            .unwrap()
+            .is_final_at(840_000, 0));
+        assert_eq!(
+            coinbase.validate_timelocks(&[]),
+            Err(TimelockError::LocktimeNotEnforced(839_999))
+        );
+    }
+
+    #[test]
+    fn it_validates_output_and_input_values() {
+        let max = Amount::MAX_MONEY.to_sat();
+        let outpoint = |idx| BitcoinOutpoint::new(TXID::default(), idx);
+        let vin = vec![
+            BitcoinTxIn::new(outpoint(0), ScriptSig::null(), 0),
+            BitcoinTxIn::new(outpoint(1), ScriptSig::null(), 0),
+        ];
+        let tx = |values: &[u64]| {
+            let vout: Vec<TxOut> = values
+                .iter()
+                .map(|v| TxOut::new(*v, ScriptPubkey::null()))
+                .collect();
+            LegacyTx::new(2, vin.clone(), vout, 0).unwrap()
+        };
+
+        assert!(tx(&[max]).validate(None).is_ok());
+        match tx(&[1, max + 1]).validate(None) {
+            Err(TxError::OutputValueTooLarge { index: 1, value }) => assert_eq!(value, max + 1),
+            _ => panic!("expected OutputValueTooLarge"),
+        }
+        match tx(&[max, 1]).validate(None) {
+            Err(TxError::TotalOutputValueTooLarge) => {}
+            _ => panic!("expected TotalOutputValueTooLarge"),
+        }
+
+        let mut prevouts = HashMap::new();
+        prevouts.insert(outpoint(0), TxOut::new(600, ScriptPubkey::null()));
+        match tx(&[500]).validate(Some(&prevouts)) {
+            Err(TxError::MissingPrevout(1)) => {}
+            _ => panic!("expected MissingPrevout"),
+        }
+
+        prevouts.insert(outpoint(1), TxOut::new(400, ScriptPubkey::null()));
+        assert!(tx(&[1000]).validate(Some(&prevouts)).is_ok());
+        match tx(&[600, 401]).validate(Some(&prevouts)) {
+            Err(TxError::InsufficientInputValue {
+                input_value: 1000,
+                output_value: 1001,
+            }) => {}
+            _ => panic!("expected InsufficientInputValue"),
+        }
+
+        prevouts.insert(outpoint(1), TxOut::new(max, ScriptPubkey::null()));
+        match tx(&[500]).validate(Some(&prevouts)) {
+            Err(TxError::TotalInputValueTooLarge) => {}
+            _ => panic!("expected TotalInputValueTooLarge"),
+        }
+        prevouts.insert(outpoint(1), TxOut::null());
+        match tx(&[500]).validate(Some(&prevouts)) {
+            Err(TxError::InputValueTooLarge { index: 1, .. }) => {}
+            _ => panic!("expected InputValueTooLarge"),
+        }
+    }
+
+    #[test]
+    fn it_round_trips_types_through_serde() {
+        assert_eq!(serde_json::to_string(&Sighash::AllAcp).unwrap(), "129");
+        assert_eq!(
+            serde_json::from_str::<Sighash>("3").unwrap(),
+            Sighash::Single
+        );
+        assert!(serde_json::from_str::<Sighash>("4").is_err());
+
+        assert_eq!(
+            serde_json::to_string(&Amount::ONE_BTC).unwrap(),
+            "100000000"
+        );
+        assert_eq!(
+            serde_json::to_string(&timelock::Sequence::ENABLE_RBF_NO_LOCKTIME).unwrap(),
+            "4294967293"
+        );
+
+        // Scripts serialize as length-prefixed hex
+        let spk = "160014751e76e8199196d454941c45d1b3a323f1433bd6";
+        let program: WitnessProgram = serde_json::from_str(&format!("\"{}\"", spk)).unwrap();
+        assert!(program.is_p2wpkh());
+        assert_eq!(
+            serde_json::to_string(&program).unwrap(),
+            format!("\"{}\"", spk)
+        );
+        let p2pkh = "\"1976a914751e76e8199196d454941c45d1b3a323f1433bd688ac\"";
+        assert!(serde_json::from_str::<WitnessProgram>(p2pkh).is_err());
+
+        let args = WitnessSighashArgs {
+            index: 1,
+            sighash_flag: Sighash::All,
+            prevout_script: Script::deserialize_hex(spk).unwrap(),
+            prevout_value: 120000,
+        };
+        let json = serde_json::to_string(&args).unwrap();
+        assert_eq!(
+            serde_json::from_str::<WitnessSighashArgs>(&json).unwrap(),
+            args
+        );
+    }
+
+    #[test]
+    fn it_detects_the_serialization_format() {
+        let legacy_hex = "0100000001813f79011acb80925dfe69b3def355fe914bd1d96a3f5f71bf8303c6a989c7d1000000006b483045022100ed81ff192e75a3fd2304004dcadb746fa5e24c5031ccfcf21320b0277457c98f02207a986d955c6e0cb35d446a89d3f56100f4d7f67801c31967743a9c8e10615bed01210349fc4e631e3624a545de3f89f5d8684c7b8138bd94bdd531d2e213bf016b278afeffffff02a135ef01000000001976a914bc3b654dca7e56b04dca18f2566cdaf02e8d9ada88ac99c39800000000001976a9141c4bc762dd5423e332166702cb75f40df79fea1288ac19430600";
+        let tx = BitcoinTx::deserialize_hex(legacy_hex).unwrap();
+        assert!(tx.is_legacy());
+        assert_eq!(tx.serialize_hex(), legacy_hex);
+
+        let witness_hex = "02000000000102ee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffffee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffff0273d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f18773d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f1870000cafd0700";
+        let tx = BitcoinTx::deserialize_hex(witness_hex).unwrap();
+        assert!(tx.is_witness());
+        assert_eq!(tx.serialize_hex(), witness_hex);
+
+        // A segwit marker with an unknown flag is read as a legacy tx with no inputs. Here the
+        // flag is the output count, and the witness tx's inputs are read as 2 garbage outputs.
+        let bad_flag = witness_hex.replacen("0001", "0002", 1);
+        let tx = BitcoinTx::deserialize_hex(&bad_flag).unwrap();
+        assert!(tx.is_legacy());
+        assert!(tx.inputs().is_empty());
+        assert_eq!(tx.outputs().len(), 2);
+        assert_eq!(tx.outputs()[0].value, 0xab79_9ec8_4292_ee02);
+        // Truncated input is reported as such, rather than as a bad flag
+        match BitcoinTx::deserialize_hex(&bad_flag[..60]) {
+            Err(TxError::SerError(SerError::IoError(_)))
+            | Err(TxError::SerError(SerError::InsufficientSeqItems { .. }))
+            | Err(TxError::IoError(_)) => {}
+            other => panic!("expected a truncation error, got {:?}", other),
+        }
+
+        // An unknown flag that is not a valid legacy output count either
+        match BitcoinTx::deserialize_hex("0200000000fd0100") {
+            Err(TxError::BadWitnessFlag([0, 0xfd])) => {}
+            other => panic!("expected bad witness flag, got {:?}", other),
+        }
+
+        // A legacy tx with no inputs, and 2 outputs. The input count looks like a segwit
+        // marker, and the output count like an unknown flag.
+        let no_inputs = "010000000002102700000000000001516400000000000000015100000000";
+        let tx = BitcoinTx::deserialize_hex(no_inputs).unwrap();
+        assert!(tx.is_legacy());
+        assert!(tx.inputs().is_empty());
+        assert_eq!(tx.outputs().len(), 2);
+        assert_eq!(tx.serialize_hex(), no_inputs);
+        let limits = TxLimits {
+            max_outputs: 1,
+            ..Default::default()
+        };
+        match BitcoinTx::deserialize_hex_limited(no_inputs, &limits) {
+            Err(TxError::SerError(SerError::ExcessiveLength { limit: 1, got: 2 })) => {}
+            other => panic!("expected excessive length, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn it_enforces_deserialization_limits() {
+        let tx_hex = "02000000000102ee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffffee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffff0273d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f18773d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f1870000cafd0700";
+        let limits = TxLimits::default();
+        let tx = BitcoinTx::deserialize_hex_limited(tx_hex, &limits).unwrap();
+        assert_eq!(tx, BitcoinTx::deserialize_hex(tx_hex).unwrap());
+
+        let cases = [
+            TxLimits {
+                max_inputs: 1,
+                ..limits
+            },
+            TxLimits {
+                max_outputs: 1,
+                ..limits
+            },
+            TxLimits {
+                max_script_len: 22,
+                ..limits
+            },
+        ];
+        for limits in cases.iter() {
+            match BitcoinTx::deserialize_hex_limited(tx_hex, limits) {
+                Err(TxError::SerError(SerError::ExcessiveLength { .. })) => {}
+                _ => panic!("expected excessive length"),
+            }
+        }
+
+        // A legacy tx claiming 2^32 inputs errors before reading them
+        let malicious = "01000000feffffffff";
+        match LegacyTx::deserialize_hex(malicious) {
+            Err(TxError::SerError(SerError::ExcessiveLength { got, .. })) => {
+                assert_eq!(got, 0xffff_ffff)
+            }
+            _ => panic!("expected excessive length"),
+        }
+
+        let mut witness_tx = tx.into_witness();
+        witness_tx.witnesses[0] = vec![vec![0u8; 72].into(), vec![2u8; 33].into()];
+        let witness_hex = witness_tx.serialize_hex();
+        let cases = [
+            TxLimits {
+                max_witness_items: 1,
+                ..limits
+            },
+            TxLimits {
+                max_witness_item_len: 33,
+                ..limits
+            },
+        ];
+        for limits in cases.iter() {
+            assert!(BitcoinTx::deserialize_hex_limited(&witness_hex, limits).is_err());
+        }
+    }
+
     #[test]
     fn it_rejects_sighash_none() {
         let tx_hex = "02000000000102ee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffffee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffff0273d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f18773d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f1870000cafd0700";
@@ -600,6 +1352,55 @@ mod tests {
         }
     }
 
+    #[test]
+    fn it_calculates_sighash_none_when_allowed() {
+        // BIP143 P2SH-P2WSH 6-of-6 multisig example
+        let tx_hex = "010000000136641869ca081e70f394c6948e8af409e18b619df2ed74aa106c1ca29787b96e0100000000ffffffff0200e9a435000000001976a914389ffce9cd9ae88dcc0631e88a821ffdbe9bfe2688acc0832f05000000001976a9147480a33f950689af511e6e84c138dbbd3c3ee41588ac00000000";
+        let tx = LegacyTx::deserialize_hex(tx_hex).unwrap().into_witness();
+        let witness_script = Script::from(hex::decode("56210307b8ae49ac90a048e9b53357a2354b3334e9c8bee813ecb98e99a7e07e8c3ba32103b28f0c28bfab54554ae8c658ac5c3e0ce6e79ad336331f78c428dd43eea8449b21034b8113d703413d57761b8b9781957b8c0ac1dfe69f492580ca4195f50376ba4a21033400f6afecb833092a9a21cfdf1ed1376e58c5d1f47de74683123987e967a8f42103a6d48b1131e94ba04d9737d61acdaa1322008af9602b3b14862c07a1789aac162102d8b661b0b3302ee2f162b09e07a55ad5dfbe673a9f01d9f0c19617681024306b56ae").unwrap());
+
+        let cases = [
+            (
+                Sighash::All,
+                "185c0be5263dce5b4bb50a047973c1b6272bfbd0103a89444597dc40b248ee7c",
+            ),
+            (
+                Sighash::None,
+                "e9733bc60ea13c95c6527066bb975a2ff29a925e80aa14c213f686cbae5d2f36",
+            ),
+            (
+                Sighash::NoneAcp,
+                "781ba15f3779d5542ce8ecb5c18716733a5ee42a6f51488ec96154934e2c890a",
+            ),
+        ];
+        let cache = SighashCache::new(&tx);
+        for (flag, expected) in cases.iter() {
+            let args = WitnessSighashArgs {
+                index: 0,
+                sighash_flag: *flag,
+                prevout_script: witness_script.clone(),
+                prevout_value: 987654321,
+            };
+            let digest: Hash256Digest = cache.witness_sighash_allowing_none(&args).unwrap().into();
+            assert_eq!(digest, Hash256Digest::deserialize_hex(expected).unwrap());
+        }
+
+        // Legacy SIGHASH_NONE does not commit to the outputs
+        let args = LegacySighashArgs {
+            index: 0,
+            sighash_flag: Sighash::None,
+            prevout_script: witness_script,
+        };
+        let legacy = tx.as_legacy();
+        assert!(legacy.sighash(&args).is_err());
+        let mut no_outputs = legacy.clone();
+        no_outputs.vout.truncate(1);
+        assert_eq!(
+            legacy.sighash_allowing_none(&args).unwrap(),
+            no_outputs.sighash_allowing_none(&args).unwrap()
+        );
+    }
+
     #[test]
     fn it_rejects_sighash_single_bug() {
         let tx_hex = "02000000000102ee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffffee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffff0173d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f1870000cafd0700";
@@ -613,7 +1414,7 @@ mod tests {
         };
 
         match tx.sighash(&args) {
-            Err(TxError::SighashSingleBug) => {}
+            Err(TxError::SighashSingleBug(1)) => {}
             _ => assert!(false, "expected sighash single bug unsupported"),
         }
     }
@@ -1,6 +1,15 @@
 //! Bitcoin Outpoint, TxIn, and Vin types.
 
-use std::io::{Read, Write};
+use std::{
+    cmp::Ordering,
+    convert::TryFrom,
+    fmt,
+    io::{Read, Write},
+    num::ParseIntError,
+    str::FromStr,
+};
+
+use thiserror::Error;
 
 use coins_core::{
     hashes::MarkedDigestOutput,
@@ -8,14 +17,24 @@ use coins_core::{
     types::tx::{Input, TxoIdentifier},
 };
 
-use crate::{hashes::TXID, types::script::ScriptSig};
+use crate::{
+    hashes::TXID,
+    types::{script::ScriptSig, timelock::Sequence, tx::TxLimits},
+};
 /// An Outpoint. This is a unique identifier for a UTXO, and is composed of a transaction ID (in
 /// Bitcoin-style LE format), and the index of the output being spent within that transactions
 /// output vectour (vout).
 ///
 /// `Outpoint::null()` and `Outpoint::default()` return the null Outpoint, which references a txid
 /// of all 0, and a index 0xffff_ffff. This null outpoint is used in every coinbase transaction.
+///
+/// Outpoints display and parse in the `<txid>:<index>` notation used by Bitcoin Core and
+/// Electrum, with the txid in big-endian hex. They serialize with serde as a struct, and
+/// deserialize from either the struct or the `<txid>:<index>` string.
+///
+/// Outpoints are ordered per BIP69: by txid in big-endian byte order, then by index.
 #[derive(serde::Serialize, serde::Deserialize, Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[serde(try_from = "OutpointRepr<M>")]
 pub struct Outpoint<M>
 where
     M: MarkedDigestOutput,
@@ -45,6 +64,11 @@ where
         }
     }
 
+    /// True if this is the null outpoint, as spent by coinbase inputs
+    pub fn is_null(&self) -> bool {
+        self.idx == 0xffff_ffff && self.txid.as_ref().iter().all(|b| *b == 0)
+    }
+
     /// Return the BE txid as hex, suitable for block explorers
     pub fn txid_be_hex(&self) -> String {
         self.txid.reversed().serialize_hex()
@@ -68,6 +92,97 @@ where
     }
 }
 
+impl<M> Outpoint<M>
+where
+    M: MarkedDigestOutput,
+{
+    /// Compare outpoints per BIP69: by big-endian txid, then by index
+    fn bip69_cmp(&self, other: &Self) -> Ordering {
+        let txid = self.txid.as_ref().iter().rev();
+        txid.cmp(other.txid.as_ref().iter().rev())
+            .then(self.idx.cmp(&other.idx))
+    }
+}
+
+impl<M> Ord for Outpoint<M>
+where
+    M: MarkedDigestOutput + Eq,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.bip69_cmp(other)
+    }
+}
+
+impl<M> PartialOrd for Outpoint<M>
+where
+    M: MarkedDigestOutput + PartialEq,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.bip69_cmp(other))
+    }
+}
+
+/// An error parsing an outpoint from `<txid>:<index>` notation
+#[derive(Debug, Error)]
+pub enum OutpointParseError {
+    /// The string has no `:` separating the txid and index
+    #[error("Outpoint must be in <txid>:<index> notation")]
+    MissingSeparator,
+
+    /// The txid is not valid big-endian hex
+    #[error("Invalid outpoint txid: {0}")]
+    InvalidTxid(#[from] SerError),
+
+    /// The index is not a valid u32
+    #[error("Invalid outpoint index: {0}")]
+    InvalidIndex(#[from] ParseIntError),
+}
+
+impl<M> fmt::Display for Outpoint<M>
+where
+    M: MarkedDigestOutput,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.txid_be_hex(), self.idx)
+    }
+}
+
+impl<M> FromStr for Outpoint<M>
+where
+    M: MarkedDigestOutput,
+{
+    type Err = OutpointParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (txid, idx) = s
+            .rsplit_once(':')
+            .ok_or(OutpointParseError::MissingSeparator)?;
+        Ok(Self::new(M::from_be_hex(txid)?, idx.parse()?))
+    }
+}
+
+/// The serde representations an outpoint is deserialized from
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum OutpointRepr<M> {
+    Str(String),
+    Struct { txid: M, idx: u32 },
+}
+
+impl<M> TryFrom<OutpointRepr<M>> for Outpoint<M>
+where
+    M: MarkedDigestOutput,
+{
+    type Error = OutpointParseError;
+
+    fn try_from(repr: OutpointRepr<M>) -> Result<Self, Self::Error> {
+        match repr {
+            OutpointRepr::Str(s) => s.parse(),
+            OutpointRepr::Struct { txid, idx } => Ok(Self::new(txid, idx)),
+        }
+    }
+}
+
 impl<M> ByteFormat for Outpoint<M>
 where
     M: MarkedDigestOutput + ByteFormat,
@@ -111,7 +226,20 @@ where
 ///
 /// Sequence encoding is complex and the field also encodes information about locktimes and RBF.
 /// See [my blogpost on the subject](https://prestwi.ch/bitcoin-time-locks/).
-#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Eq, PartialEq, Default)]
+///
+/// Inputs are ordered by outpoint, per BIP69, then by script sig and sequence.
+#[derive(
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Debug,
+    Eq,
+    PartialEq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Default,
+)]
 pub struct TxInput<M>
 where
     M: MarkedDigestOutput,
@@ -147,10 +275,38 @@ where
         }
     }
 
+    /// Deserialize an input, erroring if its script sig is longer than
+    /// `limits.max_script_len`
+    pub fn read_limited<T>(reader: &mut T, limits: &TxLimits) -> SerResult<Self>
+    where
+        T: Read,
+    {
+        Ok(TxInput {
+            outpoint: Outpoint::read_from(reader)?,
+            script_sig: coins_core::ser::read_prefix_vec_limited(reader, limits.max_script_len)?
+                .into(),
+            sequence: coins_core::ser::read_u32_le(reader)?,
+        })
+    }
+
     /// Copy the input, stripping the scriptsig information.
     pub fn unsigned(&self) -> TxInput<M> {
         Self::new(self.outpoint, vec![], self.sequence)
     }
+
+    /// True if the input's sequence number signals BIP125 replace-by-fee, i.e. it is below
+    /// `0xffff_fffe`
+    pub fn signals_rbf(&self) -> bool {
+        Sequence::from_consensus(self.sequence).signals_rbf()
+    }
+
+    /// Signal BIP125 replace-by-fee. A higher sequence number is lowered to `0xffff_fffd`. Lower
+    /// sequence numbers already signal, and are left unchanged to preserve any relative lock.
+    pub fn enable_rbf(&mut self) {
+        if !self.signals_rbf() {
+            self.sequence = Sequence::ENABLE_RBF_NO_LOCKTIME.to_consensus_u32();
+        }
+    }
 }
 
 impl<M> ByteFormat for TxInput<M>
@@ -171,11 +327,7 @@ where
         T: Read,
         Self: std::marker::Sized,
     {
-        Ok(TxInput {
-            outpoint: Outpoint::read_from(reader)?,
-            script_sig: ScriptSig::read_from(reader)?,
-            sequence: coins_core::ser::read_u32_le(reader)?,
-        })
+        Self::read_limited(reader, &TxLimits::default())
     }
 
     fn write_to<T>(&self, writer: &mut T) -> SerResult<usize>
@@ -226,6 +378,83 @@ mod test {
         }
     }
 
+    #[test]
+    fn it_displays_and_parses_outpoints() {
+        let s = "56f87210814c8baef7068454e517a70da2f2103fc3ac7f687e32a228dc80e115:1";
+        let outpoint: BitcoinOutpoint = s.parse().unwrap();
+        assert_eq!(outpoint.idx, 1);
+        assert_eq!(
+            outpoint.txid.serialize_hex(),
+            "15e180dc28a2327e687facc33f10f2a20da717e5548406f7ae8b4c811072f856"
+        );
+        assert_eq!(outpoint.to_string(), s);
+
+        let errors = [
+            "56f87210814c8baef7068454e517a70da2f2103fc3ac7f687e32a228dc80e115",
+            "56f87210814c8baef7068454e517a70da2f2103fc3ac7f687e32a228dc80e115:-1",
+            "56f87210814c8baef7068454e517a70da2f2103fc3ac7f687e32a228dc80e115:4294967296",
+            "zz:0",
+        ];
+        for e in errors.iter() {
+            assert!(e.parse::<BitcoinOutpoint>().is_err());
+        }
+
+        // Deserializes from either the string or the struct
+        let json = serde_json::to_string(&outpoint).unwrap();
+        assert_eq!(
+            serde_json::from_str::<BitcoinOutpoint>(&json).unwrap(),
+            outpoint
+        );
+        let from_str: BitcoinOutpoint = serde_json::from_str(&format!("\"{}\"", s)).unwrap();
+        assert_eq!(from_str, outpoint);
+        assert!(serde_json::from_str::<BitcoinOutpoint>("\"zz:0\"").is_err());
+    }
+
+    #[test]
+    fn it_orders_outpoints_per_bip69() {
+        // The little-endian txid bytes sort the other way
+        let a: BitcoinOutpoint =
+            "0100000000000000000000000000000000000000000000000000000000000002:1"
+                .parse()
+                .unwrap();
+        let b: BitcoinOutpoint =
+            "0200000000000000000000000000000000000000000000000000000000000001:0"
+                .parse()
+                .unwrap();
+        let c = BitcoinOutpoint::new(b.txid, 1);
+        assert!(a.txid > b.txid);
+
+        let mut outpoints = vec![c, b, a];
+        outpoints.sort();
+        assert_eq!(outpoints, vec![a, b, c]);
+
+        let mut inputs: Vec<BitcoinTxIn> = outpoints
+            .iter()
+            .rev()
+            .map(|o| BitcoinTxIn::new(*o, ScriptSig::null(), 0))
+            .collect();
+        inputs.sort();
+        assert_eq!(inputs[0].outpoint, a);
+        assert_eq!(inputs[2].outpoint, c);
+    }
+
+    #[test]
+    fn it_detects_and_enables_rbf() {
+        let cases = [
+            (0xffff_ffff, false, 0xffff_fffd),
+            (0xffff_fffe, false, 0xffff_fffd),
+            (0xffff_fffd, true, 0xffff_fffd),
+            (144, true, 144), // relative locks are preserved
+        ];
+        for (sequence, signals, enabled) in cases.iter() {
+            let mut txin = BitcoinTxIn::new(BitcoinOutpoint::null(), vec![], *sequence);
+            assert_eq!(txin.signals_rbf(), *signals);
+            txin.enable_rbf();
+            assert!(txin.signals_rbf());
+            assert_eq!(txin.sequence, *enabled);
+        }
+    }
+
     #[test]
     fn it_serializes_and_derializes_inputs() {
         let cases = [
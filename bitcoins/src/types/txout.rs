@@ -7,7 +7,45 @@ use coins_core::{
     types::tx::Output,
 };
 
-use crate::types::script::{ScriptPubkey, ScriptType};
+use crate::types::{
+    feerate::FeeRate,
+    script::{ScriptPubkey, ScriptType},
+    tx::TxLimits,
+    witness_program::split_witness_program,
+};
+
+/// The default dust relay feerate in Bitcoin Core, 3 sat/vbyte.
+pub const DEFAULT_DUST_RELAY_FEERATE: FeeRate = FeeRate::from_sat_per_vb(3);
+
+/// The maximum size of a script. Larger scripts are unspendable.
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+
+/// True if the script pubkey is a witness program of any version
+pub(crate) fn is_witness_program(script_pubkey: &ScriptPubkey) -> bool {
+    split_witness_program(script_pubkey.items()).is_some()
+}
+
+/// The dust limit of an output paying to `script_pubkey`, in satoshis, at `relay_feerate`. An
+/// output with a lower value is dust, and is non-standard. This matches Bitcoin
+/// Core's policy. The limit is the fee to create and later spend the output. Unspendable outputs
+/// have no dust limit.
+///
+/// At the default relay feerate, this is 546 for P2PKH, 540 for P2SH, 294 for P2WPKH, and 330
+/// for P2WSH and P2TR.
+pub fn dust_limit(script_pubkey: &ScriptPubkey, relay_feerate: FeeRate) -> u64 {
+    if script_pubkey.items().first() == Some(&0x6a) || script_pubkey.len() > MAX_SCRIPT_SIZE {
+        return 0;
+    }
+    let output_size = 8 + script_pubkey.serialized_length();
+    // outpoint, script sig length, sequence. Plus an estimated script sig, or witness discounted
+    // to vbytes
+    let input_size = if is_witness_program(script_pubkey) {
+        32 + 4 + 1 + 107 / 4 + 4
+    } else {
+        32 + 4 + 1 + 107 + 4
+    };
+    relay_feerate.fee_for_vsize(output_size + input_size)
+}
 
 /// An Output. This describes a new UTXO to be created. The value is encoded as an LE u64. The
 /// script pubkey encodes the spending constraints.
@@ -15,7 +53,11 @@ use crate::types::script::{ScriptPubkey, ScriptType};
 /// `TxOut::null()` and `TxOut::default()` return the "null" TxOut, which has a value of
 /// 0xffff_ffff_ffff_ffff, and an empty `script_pubkey`. This null output is used within legacy
 /// sighash calculations.
-#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Eq, PartialEq)]
+///
+/// Outputs are ordered by value, then by script pubkey bytes, per BIP69.
+#[derive(
+    serde::Serialize, serde::Deserialize, Clone, Debug, Eq, PartialEq, Hash, PartialOrd, Ord,
+)]
 pub struct TxOut {
     /// The value of the output in satoshis
     pub value: u64,
@@ -35,6 +77,20 @@ impl Default for TxOut {
 }
 
 impl TxOut {
+    /// Deserialize an output, erroring if its script pubkey is longer than
+    /// `limits.max_script_len`
+    pub fn read_limited<R>(reader: &mut R, limits: &TxLimits) -> SerResult<Self>
+    where
+        R: Read,
+    {
+        let value = coins_core::ser::read_u64_le(reader)?;
+        Ok(TxOut {
+            value,
+            script_pubkey: coins_core::ser::read_prefix_vec_limited(reader, limits.max_script_len)?
+                .into(),
+        })
+    }
+
     /// Instantiate a new TxOut.
     pub fn new<T>(value: u64, script_pubkey: T) -> Self
     where
@@ -54,16 +110,12 @@ impl TxOut {
         }
     }
 
-    /// Instantiate an OP_RETURN output with some data. Discards all but the first 75 bytes.
+    /// Instantiate an OP_RETURN output with some data. This does not enforce the standard relay
+    /// size limit. See `BitcoinTxBuilder::op_return`.
     pub fn op_return(data: &[u8]) -> Self {
-        let mut data = data.to_vec();
-        data.truncate(75);
-
-        let mut payload = vec![0x6a, data.len() as u8];
-        payload.extend(data);
         TxOut {
             value: 0,
-            script_pubkey: ScriptPubkey::from(payload),
+            script_pubkey: ScriptPubkey::op_return(data),
         }
     }
 
@@ -76,6 +128,21 @@ impl TxOut {
     pub fn extract_op_return_data(&self) -> Option<Vec<u8>> {
         self.script_pubkey.extract_op_return_data()
     }
+
+    /// The dust limit of the output at `relay_feerate`. See `dust_limit`.
+    pub fn dust_limit(&self, relay_feerate: FeeRate) -> u64 {
+        dust_limit(&self.script_pubkey, relay_feerate)
+    }
+
+    /// True if the output's value is below its dust limit at `relay_feerate`.
+    pub fn is_dust(&self, relay_feerate: FeeRate) -> bool {
+        self.value < self.dust_limit(relay_feerate)
+    }
+
+    /// True if the output is a provably unspendable OP_RETURN output, regardless of its payload.
+    pub fn is_op_return(&self) -> bool {
+        self.script_pubkey.items().first() == Some(&0x6a)
+    }
 }
 
 impl ByteFormat for TxOut {
@@ -92,11 +159,7 @@ impl ByteFormat for TxOut {
         R: Read,
         Self: std::marker::Sized,
     {
-        let value = coins_core::ser::read_u64_le(reader)?;
-        Ok(TxOut {
-            value,
-            script_pubkey: ScriptPubkey::read_from(reader)?,
-        })
+        Self::read_limited(reader, &TxLimits::default())
     }
 
     fn write_to<W>(&self, writer: &mut W) -> SerResult<usize>
@@ -130,4 +193,30 @@ mod test {
             assert_eq!(TxOut::deserialize_hex(case.1).unwrap(), case.0);
         }
     }
+
+    #[test]
+    fn it_calculates_dust_limits() {
+        let cases = [
+            ("76a9141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b92888ac", 546),
+            ("a914e88869b88866281ab166541ad8aafba8f8aba47a87", 540),
+            ("00141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b928", 294),
+            (
+                "00201bf8a1831db5443b42a44f30a121d1b616d011ab15df62b588722a845864cc99",
+                330,
+            ),
+            (
+                "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+                330,
+            ),
+            ("6a0101", 0),
+        ];
+        for case in cases.iter() {
+            let spk = ScriptPubkey::from_hex(case.0).unwrap();
+            assert_eq!(dust_limit(&spk, DEFAULT_DUST_RELAY_FEERATE), case.1);
+            assert!(!TxOut::new(case.1, spk.clone()).is_dust(DEFAULT_DUST_RELAY_FEERATE));
+            if case.1 > 0 {
+                assert!(TxOut::new(case.1 - 1, spk).is_dust(DEFAULT_DUST_RELAY_FEERATE));
+            }
+        }
+    }
 }
@@ -4,19 +4,36 @@
 //! hold the underlying witness script or redeem script if any. It aims to provide all necessary
 //! info for future UTXO signers.
 //!
+//! Wallets may also record where the UTXO was confirmed, and the derivation of the key that
+//! controls it. This metadata is preserved by serde, so UTXO sets can be persisted as JSON. The
+//! `ByteFormat` encoding omits it.
+//!
 //! # Note:
 //!
 //! This functionality does NOT currently support nested witness-via-p2sh prevouts. If you' like
 //! to use those, you'll need a processing step in your tx signer.
-use crate::types::{
-    BitcoinOutpoint, BitcoinTransaction, LegacySighashArgs, Script, ScriptPubkey, ScriptType,
-    Sighash, TxOut, WitnessSighashArgs,
+use std::io::{Read, Write};
+
+use crate::{
+    hashes::BlockHash,
+    types::{
+        BitcoinOutpoint, BitcoinTransaction, LegacySighashArgs, Script, ScriptPubkey, ScriptType,
+        Sighash, TxOut, WitnessSighashArgs,
+    },
+};
+use coins_bip32::path::KeyDerivation;
+use coins_core::{
+    hashes::{Digest, Hash160, MarkedDigest, MarkedDigestOutput, Sha256},
+    ser::{ByteFormat, SerError, SerResult},
 };
-use coins_core::hashes::{Digest, Hash160, MarkedDigest, MarkedDigestOutput, Sha256};
 use serde::{Deserialize, Serialize};
 
+/// The number of blocks after which a coinbase output may be spent. A coinbase output confirmed
+/// at height `h` may be spent in a block at height `h + COINBASE_MATURITY` or later.
+pub const COINBASE_MATURITY: u32 = 100;
+
 /// This type specifies whether a script is known to be none, or whether it is unknown.
-#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
 pub enum SpendScript {
     /// ScriptPubkey is known to have no spend script
     None,
@@ -38,7 +55,9 @@ impl SpendScript {
 }
 
 /// Information necessary to spend an output.
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+///
+/// UTXOs are ordered by outpoint, per BIP69, then by their remaining fields.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Utxo {
     /// UTXO outpoint
     pub outpoint: BitcoinOutpoint,
@@ -48,6 +67,15 @@ pub struct Utxo {
     pub script_pubkey: ScriptPubkey,
     /// The prevout redeem script or witness script hashed into the script pubkey (if any)
     spend_script: SpendScript,
+    /// The height of the block that confirmed the UTXO. `None` if unconfirmed or unknown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// The hash of the block that confirmed the UTXO. `None` if unconfirmed or unknown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_hash: Option<BlockHash>,
+    /// The derivation of the key controlling the UTXO, if it belongs to an HD wallet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derivation: Option<KeyDerivation>,
 }
 
 impl Utxo {
@@ -70,31 +98,56 @@ impl Utxo {
             value,
             script_pubkey,
             spend_script,
+            height: None,
+            block_hash: None,
+            derivation: None,
         }
     }
 
+    /// Record the height and hash of the block that confirmed the UTXO
+    pub fn confirmed_in(mut self, height: u32, block_hash: BlockHash) -> Self {
+        self.height = Some(height);
+        self.block_hash = Some(block_hash);
+        self
+    }
+
+    /// Record the derivation of the key controlling the UTXO
+    pub fn derivation(mut self, derivation: KeyDerivation) -> Self {
+        self.derivation = Some(derivation);
+        self
+    }
+
+    /// The number of confirmations the UTXO has at `tip`. `None` if the confirmation height is
+    /// unknown, and `Some(0)` if `tip` is below it.
+    pub fn confirmations(&self, tip: u32) -> Option<u32> {
+        self.height.map(|h| tip.saturating_add(1).saturating_sub(h))
+    }
+
+    /// True if a coinbase output confirmed at `height` may be spent in the block after `tip`.
+    /// The UTXO does not record whether it was created by a coinbase. Outputs of other
+    /// transactions may be spent as soon as they are confirmed, so callers should only apply
+    /// this check to coinbase outputs.
+    pub fn is_mature(&self, height: u32, tip: u32) -> bool {
+        tip as u64 + 1 >= height as u64 + COINBASE_MATURITY as u64
+    }
+
     /// Produce a UTXO from a transaction output
     pub fn from_tx_output<T>(tx: &T, idx: usize) -> Utxo
     where
         T: BitcoinTransaction,
     {
         let output = &tx.outputs()[idx];
-        Utxo {
-            outpoint: BitcoinOutpoint::new(tx.txid(), idx as u32),
-            value: output.value,
-            script_pubkey: output.script_pubkey.clone(),
-            spend_script: SpendScript::from_script_pubkey(&output.script_pubkey),
-        }
+        Utxo::from_output_and_outpoint(output, &BitcoinOutpoint::new(tx.txid(), idx as u32))
     }
 
     /// Produce a UTXO from an output and the outpoint that identifies it
     pub fn from_output_and_outpoint(output: &TxOut, outpoint: &BitcoinOutpoint) -> Utxo {
-        Utxo {
-            outpoint: *outpoint,
-            value: output.value,
-            script_pubkey: output.script_pubkey.clone(),
-            spend_script: SpendScript::from_script_pubkey(&output.script_pubkey),
-        }
+        Utxo::new(
+            *outpoint,
+            output.value,
+            output.script_pubkey.clone(),
+            SpendScript::from_script_pubkey(&output.script_pubkey),
+        )
     }
 
     /// Return a reference to the script pubkey
@@ -115,12 +168,8 @@ impl Utxo {
                 let spk = self.script_pubkey();
                 match spk.standard_type() {
                     ScriptType::Pkh(_) => Some(spk.into()),
-                    // TODO: break this out into a function
                     ScriptType::Wpkh(payload) => {
-                        let mut v = vec![0x76, 0xa9, 0x14];
-                        v.extend(payload.as_slice());
-                        v.extend(&[0x88, 0xac]);
-                        Some(v.into())
+                        Some((&ScriptPubkey::p2pkh_from_hash(&payload)).into())
                     }
                     _ => None, // Should be unreachable
                 }
@@ -190,3 +239,118 @@ impl Utxo {
         }
     }
 }
+
+impl ByteFormat for SpendScript {
+    type Error = SerError;
+
+    fn serialized_length(&self) -> usize {
+        match self {
+            SpendScript::Known(script) => 1 + script.serialized_length(),
+            _ => 1,
+        }
+    }
+
+    fn read_from<R>(reader: &mut R) -> SerResult<Self>
+    where
+        R: Read,
+        Self: std::marker::Sized,
+    {
+        let mut tag = [0u8; 1];
+        reader.read_exact(&mut tag)?;
+        match tag[0] {
+            0 => Ok(SpendScript::None),
+            1 => Ok(SpendScript::Missing),
+            2 => Ok(SpendScript::Known(Script::read_from(reader)?)),
+            t => Err(SerError::ComponentError(format!(
+                "Unknown spend script tag {}",
+                t
+            ))),
+        }
+    }
+
+    fn write_to<W>(&self, writer: &mut W) -> SerResult<usize>
+    where
+        W: Write,
+    {
+        match self {
+            SpendScript::None => Ok(writer.write(&[0])?),
+            SpendScript::Missing => Ok(writer.write(&[1])?),
+            SpendScript::Known(script) => {
+                let len = writer.write(&[2])?;
+                Ok(len + script.write_to(writer)?)
+            }
+        }
+    }
+}
+
+impl ByteFormat for Utxo {
+    type Error = SerError;
+
+    fn serialized_length(&self) -> usize {
+        let mut len = self.outpoint.serialized_length();
+        len += 8; // value
+        len += self.script_pubkey.serialized_length();
+        len += self.spend_script.serialized_length();
+        len
+    }
+
+    fn read_from<R>(reader: &mut R) -> SerResult<Self>
+    where
+        R: Read,
+        Self: std::marker::Sized,
+    {
+        let outpoint = BitcoinOutpoint::read_from(reader)?;
+        let value = coins_core::ser::read_u64_le(reader)?;
+        let script_pubkey = ScriptPubkey::read_from(reader)?;
+        let spend_script = SpendScript::read_from(reader)?;
+        Ok(Utxo::new(outpoint, value, script_pubkey, spend_script))
+    }
+
+    fn write_to<W>(&self, writer: &mut W) -> SerResult<usize>
+    where
+        W: Write,
+    {
+        let mut len = self.outpoint.write_to(writer)?;
+        len += coins_core::ser::write_u64_le(writer, self.value)?;
+        len += self.script_pubkey.write_to(writer)?;
+        len += self.spend_script.write_to(writer)?;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn p2wpkh_utxo() -> Utxo {
+        Utxo::new(
+            BitcoinOutpoint::default(),
+            100_000,
+            ScriptPubkey::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap(),
+            SpendScript::None,
+        )
+    }
+
+    #[test]
+    fn it_persists_metadata_with_serde() {
+        let derivation = KeyDerivation {
+            root: [0xde, 0xad, 0xbe, 0xef].into(),
+            path: "m/84'/0'/0'/0/3".parse().unwrap(),
+        };
+        let utxo = p2wpkh_utxo()
+            .confirmed_in(700_000, BlockHash::default())
+            .derivation(derivation);
+        assert_eq!(utxo.confirmations(700_009), Some(10));
+        assert_eq!(utxo.confirmations(699_999), Some(0));
+
+        let json = serde_json::to_string(&utxo).unwrap();
+        assert_eq!(serde_json::from_str::<Utxo>(&json).unwrap(), utxo);
+
+        // UTXOs persisted without metadata still load
+        let bare = p2wpkh_utxo();
+        assert_eq!(bare.confirmations(700_000), None);
+        let json = serde_json::to_string(&bare).unwrap();
+        assert!(!json.contains("height"));
+        assert_eq!(serde_json::from_str::<Utxo>(&json).unwrap(), bare);
+    }
+}
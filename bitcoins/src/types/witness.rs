@@ -1,7 +1,12 @@
 //! Witness Transactions
 
-use std::io::{Read, Write};
+use std::{
+    borrow::Cow,
+    io::{Read, Write},
+    sync::OnceLock,
+};
 
+use coins_bip32::ecdsa::VerifyingKey;
 use coins_core::{
     hashes::{Digest, DigestOutput, Hash256, Hash256Digest, MarkedDigest, MarkedDigestOutput},
     ser::{self, ByteFormat},
@@ -12,7 +17,7 @@ use crate::{
     hashes::{TXID, WTXID},
     types::{
         legacy::*,
-        script::{Script, Witness},
+        script::{Script, ScriptPubkey, Witness, WitnessStackItem},
         tx::*,
         txin::BitcoinTxIn,
         txout::TxOut,
@@ -121,7 +126,7 @@ pub trait WitnessTransaction: BitcoinTransaction {
 ///
 /// After signing the digest, you MUST append the sighash indicator byte to the resulting
 /// signature.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Eq, PartialEq)]
 pub struct WitnessSighashArgs {
     /// The index of the input we'd like to sign
     pub index: usize,
@@ -136,88 +141,309 @@ pub struct WitnessSighashArgs {
 }
 
 /// A witness transaction. Any transaction that contains 1 or more witnesses.
-#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Eq, PartialEq, Default)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Eq, PartialEq, Hash, Default)]
 pub struct WitnessTx {
     pub(crate) legacy_tx: LegacyTx,
     pub(crate) witnesses: Vec<Witness>,
 }
 
+impl WitnessSighashArgs {
+    /// Instantiate sighash args for a P2WPKH input, native or nested in P2SH. The script code is
+    /// the P2PKH script of the key, NOT the witness program or the P2SH redeem script.
+    pub fn wpkh<K>(index: usize, sighash_flag: Sighash, key: &K, prevout_value: u64) -> Self
+    where
+        K: AsRef<VerifyingKey>,
+    {
+        Self {
+            index,
+            sighash_flag,
+            prevout_script: ScriptPubkey::p2pkh(key).items().into(),
+            prevout_value,
+        }
+    }
+
+    /// Instantiate sighash args for a P2WSH input, native or nested in P2SH. The script code is
+    /// the witness script, NOT the witness program or the P2SH redeem script.
+    pub fn wsh(
+        index: usize,
+        sighash_flag: Sighash,
+        witness_script: &Script,
+        prevout_value: u64,
+    ) -> Self {
+        Self {
+            index,
+            sighash_flag,
+            prevout_script: witness_script.clone(),
+            prevout_value,
+        }
+    }
+}
+
 impl WitnessTx {
-    /// Calculates `hash_prevouts` according to BIP143 semantics.`
-    ///
-    /// For BIP143 (Witness and Compatibility sighash) documentation, see here:
-    ///
-    /// - https://github.com/bitcoin/bips/blob/master/bip-0143.mediawiki
-    ///
-    /// TODO: memoize
-    fn hash_prevouts(&self, sighash_flag: Sighash) -> TxResult<Hash256Digest> {
+    /// Calculates the BIP143 sighash of a P2SH-P2WPKH input spent by `key`. The script code is
+    /// derived from the key. See `WitnessSighashArgs::wpkh`.
+    pub fn sh_wpkh_sighash<K>(
+        &self,
+        index: usize,
+        sighash_flag: Sighash,
+        key: &K,
+        prevout_value: u64,
+    ) -> TxResult<DigestOutput<Hash256>>
+    where
+        K: AsRef<VerifyingKey>,
+    {
+        self.witness_sighash(&WitnessSighashArgs::wpkh(
+            index,
+            sighash_flag,
+            key,
+            prevout_value,
+        ))
+    }
+
+    /// Calculates the BIP143 sighash of a P2SH-P2WSH input spending `witness_script`. See
+    /// `WitnessSighashArgs::wsh`.
+    pub fn sh_wsh_sighash(
+        &self,
+        index: usize,
+        sighash_flag: Sighash,
+        witness_script: &Script,
+        prevout_value: u64,
+    ) -> TxResult<DigestOutput<Hash256>> {
+        self.witness_sighash(&WitnessSighashArgs::wsh(
+            index,
+            sighash_flag,
+            witness_script,
+            prevout_value,
+        ))
+    }
+
+    /// Consumes a `LegacyTx` and instantiates a new `WitnessTx` with empty witnesses
+    pub fn from_legacy(legacy_tx: LegacyTx) -> Self {
+        let witnesses = (0..legacy_tx.inputs().len())
+            .map(|_| Witness::default())
+            .collect();
+        Self {
+            legacy_tx,
+            witnesses,
+        }
+    }
+
+    /// Deserialize a witness transaction, enforcing the caps in `limits`
+    pub fn read_from_limited<R>(reader: &mut R, limits: &TxLimits) -> TxResult<Self>
+    where
+        R: Read,
+    {
+        let version = ser::read_u32_le(reader)?;
+        let mut flag = [0u8; 2];
+        reader.read_exact(&mut flag)?;
+        if flag != [0u8, 1u8] {
+            return Err(TxError::BadWitnessFlag(flag));
+        };
+        let vin = read_vin(reader, limits)?;
+        let vout = read_vout(reader, limits)?;
+        let mut witnesses = vec![];
+        for _ in vin.iter() {
+            witnesses.push(read_witness(reader, limits)?);
+        }
+        let locktime = ser::read_u32_le(reader)?;
+
+        let legacy_tx = LegacyTx {
+            version,
+            vin,
+            vout,
+            locktime,
+        };
+
+        Ok(Self {
+            legacy_tx,
+            witnesses,
+        })
+    }
+}
+
+/// Read an input's witness, enforcing the caps in `limits`
+fn read_witness<R: Read>(reader: &mut R, limits: &TxLimits) -> TxResult<Witness> {
+    let len = ser::read_prefix_len(reader, limits.max_witness_items)?;
+    let witness = (0..len)
+        .map(|_| {
+            ser::read_prefix_vec_limited(reader, limits.max_witness_item_len)
+                .map(WitnessStackItem::from)
+        })
+        .collect::<ser::SerResult<_>>()?;
+    Ok(witness)
+}
+
+/// Caches the BIP143 sighash components of a transaction, so that they are computed once rather
+/// than once per input. `hash_prevouts`, `hash_sequence`, and `hash_outputs` depend only on the
+/// transaction, and are shared by every input signed with a flag that commits to them. Use one
+/// cache to compute the sighashes of many inputs.
+///
+/// For BIP143 (Witness and Compatibility sighash) documentation, see here:
+///
+/// - https://github.com/bitcoin/bips/blob/master/bip-0143.mediawiki
+///
+/// The cache holds only components that commit to the transaction itself, so it is the place for
+/// any future per-transaction midstates, such as those of the BIP341 sighash. The cache is
+/// `Sync`, so inputs may be signed in parallel from a shared reference.
+#[derive(Clone, Debug)]
+pub struct SighashCache<'a> {
+    tx: Cow<'a, WitnessTx>,
+    hash_prevouts: OnceLock<Hash256Digest>,
+    hash_sequence: OnceLock<Hash256Digest>,
+    hash_outputs: OnceLock<Hash256Digest>,
+}
+
+impl<'a> SighashCache<'a> {
+    /// Instantiate a cache for a witness transaction
+    pub fn new(tx: &'a WitnessTx) -> Self {
+        Self::with_tx(Cow::Borrowed(tx))
+    }
+
+    /// Instantiate a cache that owns a copy of `tx`. Legacy transactions are converted to
+    /// witness transactions.
+    pub fn from_tx<T: BitcoinTransaction>(tx: &T) -> SighashCache<'static> {
+        SighashCache::with_tx(Cow::Owned(WitnessTx::from_legacy(tx.as_legacy().clone())))
+    }
+
+    fn with_tx(tx: Cow<'a, WitnessTx>) -> Self {
+        Self {
+            tx,
+            hash_prevouts: OnceLock::new(),
+            hash_sequence: OnceLock::new(),
+            hash_outputs: OnceLock::new(),
+        }
+    }
+
+    /// Return a reference to the transaction
+    pub fn tx(&self) -> &WitnessTx {
+        &self.tx
+    }
+
+    /// Get `hash_prevouts` for the sighash flag. Zero for `ANYONECANPAY` flags.
+    pub fn hash_prevouts(&self, sighash_flag: Sighash) -> TxResult<Hash256Digest> {
         if sighash_flag as u8 & 0x80 == 0x80 {
-            Ok(Hash256Digest::default())
-        } else {
+            return Ok(Hash256Digest::default());
+        }
+        cached(&self.hash_prevouts, || {
             let mut w = Hash256::default();
-            for input in self.legacy_tx.vin.iter() {
+            for input in self.tx.inputs() {
                 input.outpoint.write_to(&mut w)?;
             }
             Ok(w.finalize_marked())
-        }
+        })
     }
 
-    /// Calculates `hash_sequence` according to BIP143 semantics.`
-    ///
-    /// For BIP143 (Witness and Compatibility sighash) documentation, see here:
-    ///
-    /// - https://github.com/bitcoin/bips/blob/master/bip-0143.mediawiki
-    ///
-    /// TODO: memoize
-    fn hash_sequence(&self, sighash_flag: Sighash) -> TxResult<Hash256Digest> {
-        if sighash_flag == Sighash::Single || sighash_flag as u8 & 0x80 == 0x80 {
-            Ok(Hash256Digest::default())
-        } else {
-            let mut w = Hash256::default();
-            for input in self.legacy_tx.vin.iter() {
-                ser::write_u32_le(&mut w, input.sequence)?;
-            }
-            Ok(w.finalize_marked())
+    /// Get `hash_sequence` for the sighash flag. Zero for `SINGLE`, `NONE`, and `ANYONECANPAY`
+    /// flags.
+    pub fn hash_sequence(&self, sighash_flag: Sighash) -> TxResult<Hash256Digest> {
+        match sighash_flag {
+            Sighash::All => cached(&self.hash_sequence, || {
+                let mut w = Hash256::default();
+                for input in self.tx.inputs() {
+                    ser::write_u32_le(&mut w, input.sequence)?;
+                }
+                Ok(w.finalize_marked())
+            }),
+            _ => Ok(Hash256Digest::default()),
         }
     }
 
-    /// Calculates `hash_outputs` according to BIP143 semantics.`
-    ///
-    /// For BIP143 (Witness and Compatibility sighash) documentation, see here:
-    ///
-    /// - https://github.com/bitcoin/bips/blob/master/bip-0143.mediawiki
-    ///
-    /// TODO: memoize
-    fn hash_outputs(&self, index: usize, sighash_flag: Sighash) -> TxResult<Hash256Digest> {
+    /// Get `hash_outputs` for the input at `index` and the sighash flag. `SINGLE` flags commit
+    /// to the output at `index` only, and are not cached.
+    pub fn hash_outputs(&self, index: usize, sighash_flag: Sighash) -> TxResult<Hash256Digest> {
         match sighash_flag {
-            Sighash::All | Sighash::AllAcp => {
+            Sighash::All | Sighash::AllAcp => cached(&self.hash_outputs, || {
                 let mut w = Hash256::default();
-                for output in self.legacy_tx.vout.iter() {
+                for output in self.tx.outputs() {
                     output.write_to(&mut w)?;
                 }
                 Ok(w.finalize_marked())
-            }
+            }),
             Sighash::Single | Sighash::SingleAcp => {
                 let mut w = Hash256::default();
-                self.legacy_tx.vout[index].write_to(&mut w)?;
+                self.tx.outputs()[index].write_to(&mut w)?;
                 Ok(w.finalize_marked())
             }
             _ => Ok(Hash256Digest::default()),
         }
     }
 
-    /// Consumes a `LegacyTx` and instantiates a new `WitnessTx` with empty witnesses
-    pub fn from_legacy(legacy_tx: LegacyTx) -> Self {
-        let witnesses = (0..legacy_tx.inputs().len())
-            .map(|_| Witness::default())
-            .collect();
-        Self {
-            legacy_tx,
-            witnesses,
+    /// Writes the BIP143 sighash preimage of an input to the provided writer
+    pub fn write_witness_sighash_preimage<W: Write>(
+        &self,
+        writer: &mut W,
+        args: &WitnessSighashArgs,
+    ) -> TxResult<()> {
+        if args.sighash_flag == Sighash::None || args.sighash_flag == Sighash::NoneAcp {
+            return Err(TxError::NoneUnsupported);
         }
+        self.write_witness_sighash_preimage_allowing_none(writer, args)
+    }
+
+    /// Writes the BIP143 sighash preimage of an input to the provided writer. Unlike
+    /// `write_witness_sighash_preimage`, this permits SIGHASH_NONE and
+    /// SIGHASH_NONE | ANYONECANPAY.
+    ///
+    /// SIGHASH_NONE signatures do not commit to the outputs, so anyone may redirect the funds
+    /// they authorize. Use this only when a protocol requires it.
+    pub fn write_witness_sighash_preimage_allowing_none<W: Write>(
+        &self,
+        writer: &mut W,
+        args: &WitnessSighashArgs,
+    ) -> TxResult<()> {
+        if (args.sighash_flag == Sighash::Single || args.sighash_flag == Sighash::SingleAcp)
+            && args.index >= self.tx.outputs().len()
+        {
+            return Err(TxError::SighashSingleBug(args.index));
+        }
+
+        let input = &self.tx.inputs()[args.index];
+
+        ser::write_u32_le(writer, self.tx.version())?;
+        self.hash_prevouts(args.sighash_flag)?.write_to(writer)?;
+        self.hash_sequence(args.sighash_flag)?.write_to(writer)?;
+        input.outpoint.write_to(writer)?;
+        args.prevout_script.write_to(writer)?;
+        ser::write_u64_le(writer, args.prevout_value)?;
+        ser::write_u32_le(writer, input.sequence)?;
+        self.hash_outputs(args.index, args.sighash_flag)?
+            .write_to(writer)?;
+        ser::write_u32_le(writer, self.tx.locktime())?;
+        ser::write_u32_le(writer, args.sighash_flag as u32)?;
+        Ok(())
+    }
+
+    /// Calculates the BIP143 sighash of an input
+    pub fn witness_sighash(&self, args: &WitnessSighashArgs) -> TxResult<DigestOutput<Hash256>> {
+        let mut w = Hash256::default();
+        self.write_witness_sighash_preimage(&mut w, args)?;
+        Ok(w.finalize())
+    }
+
+    /// Calculates the BIP143 sighash of an input, permitting SIGHASH_NONE. See
+    /// `write_witness_sighash_preimage_allowing_none`.
+    pub fn witness_sighash_allowing_none(
+        &self,
+        args: &WitnessSighashArgs,
+    ) -> TxResult<DigestOutput<Hash256>> {
+        let mut w = Hash256::default();
+        self.write_witness_sighash_preimage_allowing_none(&mut w, args)?;
+        Ok(w.finalize())
     }
 }
 
+/// Get the value of a cell, computing it on first use
+fn cached<F>(cell: &OnceLock<Hash256Digest>, f: F) -> TxResult<Hash256Digest>
+where
+    F: FnOnce() -> TxResult<Hash256Digest>,
+{
+    if let Some(digest) = cell.get() {
+        return Ok(*digest);
+    }
+    let digest = f()?;
+    Ok(*cell.get_or_init(|| digest))
+}
+
 impl Transaction for WitnessTx {
     type TxError = TxError;
     type TxIn = BitcoinTxIn;
@@ -348,30 +574,7 @@ impl WitnessTransaction for WitnessTx {
     where
         W: Write,
     {
-        if args.sighash_flag == Sighash::None || args.sighash_flag == Sighash::NoneAcp {
-            return Err(TxError::NoneUnsupported);
-        }
-
-        if (args.sighash_flag == Sighash::Single || args.sighash_flag == Sighash::SingleAcp)
-            && args.index >= self.outputs().len()
-        {
-            return Err(TxError::SighashSingleBug);
-        }
-
-        let input = &self.legacy_tx.vin[args.index];
-
-        ser::write_u32_le(writer, self.legacy_tx.version)?;
-        self.hash_prevouts(args.sighash_flag)?.write_to(writer)?;
-        self.hash_sequence(args.sighash_flag)?.write_to(writer)?;
-        input.outpoint.write_to(writer)?;
-        args.prevout_script.write_to(writer)?;
-        ser::write_u64_le(writer, args.prevout_value)?;
-        ser::write_u32_le(writer, input.sequence)?;
-        self.hash_outputs(args.index, args.sighash_flag)?
-            .write_to(writer)?;
-        ser::write_u32_le(writer, self.legacy_tx.locktime)?;
-        ser::write_u32_le(writer, args.sighash_flag as u32)?;
-        Ok(())
+        SighashCache::new(self).write_witness_sighash_preimage(writer, args)
     }
 }
 
@@ -408,31 +611,7 @@ impl ByteFormat for WitnessTx {
         R: Read,
         Self: std::marker::Sized,
     {
-        let version = ser::read_u32_le(reader)?;
-        let mut flag = [0u8; 2];
-        reader.read_exact(&mut flag)?;
-        if flag != [0u8, 1u8] {
-            return Err(TxError::BadWitnessFlag(flag));
-        };
-        let vin = ser::read_prefix_vec(reader)?;
-        let vout = ser::read_prefix_vec(reader)?;
-        let mut witnesses = vec![];
-        for _ in vin.iter() {
-            witnesses.push(ser::read_prefix_vec(reader)?);
-        }
-        let locktime = ser::read_u32_le(reader)?;
-
-        let legacy_tx = LegacyTx {
-            version,
-            vin,
-            vout,
-            locktime,
-        };
-
-        Ok(Self {
-            legacy_tx,
-            witnesses,
-        })
+        Self::read_from_limited(reader, &TxLimits::default())
     }
 
     fn write_to<W>(&self, writer: &mut W) -> Result<usize, Self::Error>
@@ -481,4 +660,109 @@ mod test {
         assert_eq!(tx.witnesses.len(), expected_size);
         assert_eq!(expected_witness, tx.witnesses[0]);
     }
+
+    #[test]
+    fn it_derives_script_codes_for_nested_witness_inputs() {
+        use coins_bip32::{path::KeyDerivation, prelude::DerivedPubkey};
+
+        // BIP143 P2SH-P2WPKH example
+        let tx_hex = "0100000001db6b1b20aa0fd7b23880be2ecbd4a98130974cf4748fb66092ac4d3ceb1a54770100000000feffffff02b8b4eb0b000000001976a914a457b684d7f0d539a46a45bbc043f35b59d0d96388ac0008af2f000000001976a914fd270b1ee6abcaea97fea7ad0402e8bd8ad6d77c88ac92040000";
+        let tx = WitnessTx::from_legacy(LegacyTx::deserialize_hex(tx_hex).unwrap());
+        let key = VerifyingKey::from_sec1_bytes(
+            &hex::decode("03ad1d8e89212f0b92c74d23bb710c00662ad1470198ac48c43f7d6f93a2a26873")
+                .unwrap(),
+        )
+        .unwrap();
+        let derivation = KeyDerivation {
+            root: [0u8; 4].into(),
+            path: vec![].into(),
+        };
+        let key = DerivedPubkey::new(key, derivation);
+        let args = WitnessSighashArgs::wpkh(0, Sighash::All, &key, 1_000_000_000);
+        assert_eq!(
+            args.prevout_script.serialize_hex(),
+            "1976a91479091972186c449eb1ded22b78e40d009bdf008988ac"
+        );
+        let digest = tx
+            .sh_wpkh_sighash(0, Sighash::All, &key, 1_000_000_000)
+            .unwrap();
+        assert_eq!(
+            hex::encode(digest),
+            "64f3b0f4dd2bb3aa1ce8566d220cc74dda9df97d8490cc81d89d735c92e59fb6"
+        );
+
+        // BIP143 P2SH-P2WSH 6-of-6 multisig example
+        let tx_hex = "010000000136641869ca081e70f394c6948e8af409e18b619df2ed74aa106c1ca29787b96e0100000000ffffffff0200e9a435000000001976a914389ffce9cd9ae88dcc0631e88a821ffdbe9bfe2688acc0832f05000000001976a9147480a33f950689af511e6e84c138dbbd3c3ee41588ac00000000";
+        let tx = WitnessTx::from_legacy(LegacyTx::deserialize_hex(tx_hex).unwrap());
+        let witness_script = Script::from(hex::decode("56210307b8ae49ac90a048e9b53357a2354b3334e9c8bee813ecb98e99a7e07e8c3ba32103b28f0c28bfab54554ae8c658ac5c3e0ce6e79ad336331f78c428dd43eea8449b21034b8113d703413d57761b8b9781957b8c0ac1dfe69f492580ca4195f50376ba4a21033400f6afecb833092a9a21cfdf1ed1376e58c5d1f47de74683123987e967a8f42103a6d48b1131e94ba04d9737d61acdaa1322008af9602b3b14862c07a1789aac162102d8b661b0b3302ee2f162b09e07a55ad5dfbe673a9f01d9f0c19617681024306b56ae").unwrap());
+        let digest = tx
+            .sh_wsh_sighash(0, Sighash::All, &witness_script, 987654321)
+            .unwrap();
+        assert_eq!(
+            hex::encode(digest),
+            "185c0be5263dce5b4bb50a047973c1b6272bfbd0103a89444597dc40b248ee7c"
+        );
+    }
+
+    #[test]
+    fn it_reuses_cached_sighash_components() {
+        // from riemann-py
+        let tx_hex = "02000000000102ee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffffee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffff0273d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f18773d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f1870000cafd0700";
+        let tx = WitnessTx::deserialize_hex(tx_hex).unwrap();
+        let prevout_script =
+            Script::deserialize_hex("160014758ce550380d964051086798d6546bebdca27a73").unwrap();
+        let all = Hash256Digest::deserialize_hex(
+            "75385c87ece4980b581cfd71bc5814f607801a87f6e0973c63dc9fda465c19c4",
+        )
+        .unwrap()
+        .to_internal();
+
+        let cache = SighashCache::new(&tx);
+        let flags = [
+            Sighash::All,
+            Sighash::AllAcp,
+            Sighash::Single,
+            Sighash::SingleAcp,
+        ];
+        for index in 0..2 {
+            for flag in flags.iter() {
+                let args = WitnessSighashArgs {
+                    index,
+                    sighash_flag: *flag,
+                    prevout_script: prevout_script.clone(),
+                    prevout_value: 120000,
+                };
+                assert_eq!(
+                    cache.witness_sighash(&args).unwrap(),
+                    tx.witness_sighash(&args).unwrap()
+                );
+            }
+        }
+
+        let args = WitnessSighashArgs {
+            index: 1,
+            sighash_flag: Sighash::All,
+            prevout_script,
+            prevout_value: 120000,
+        };
+        assert_eq!(cache.witness_sighash(&args).unwrap(), all);
+        let owned = SighashCache::from_tx(&tx);
+        assert_eq!(owned.witness_sighash(&args).unwrap(), all);
+        assert_eq!(
+            cache.hash_prevouts(Sighash::AllAcp).unwrap(),
+            Hash256Digest::default()
+        );
+        assert_eq!(
+            cache.hash_sequence(Sighash::Single).unwrap(),
+            Hash256Digest::default()
+        );
+
+        fn assert_sync<T: Sync + Send>(_: &T) {}
+        assert_sync(&cache);
+        std::thread::scope(|scope| {
+            let cache = &cache;
+            let handle = scope.spawn(move || cache.witness_sighash(&args).unwrap());
+            assert_eq!(handle.join().unwrap(), all);
+        });
+    }
 }
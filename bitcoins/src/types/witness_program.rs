@@ -0,0 +1,199 @@
+//! Witness programs, the script pubkeys of native segwit outputs.
+
+use std::convert::TryFrom;
+
+use coins_core::enc::{EncodingError, EncodingResult};
+
+use crate::types::script::ScriptPubkey;
+
+/// The highest witness version
+pub const MAX_WITNESS_VERSION: u8 = 16;
+
+/// The shortest witness program, in bytes
+pub const MIN_WITNESS_PROGRAM_LENGTH: usize = 2;
+
+/// The longest witness program, in bytes
+pub const MAX_WITNESS_PROGRAM_LENGTH: usize = 40;
+
+/// Split a script into its witness version and program, checking only the BIP141 structure: a
+/// version opcode followed by a single push of 2 to 40 bytes. Does not apply per-version rules.
+pub(crate) fn split_witness_program(script: &[u8]) -> Option<(u8, &[u8])> {
+    let (version, rest) = script.split_first()?;
+    let (len, program) = rest.split_first()?;
+    let version = match version {
+        0x00 => 0,
+        0x51..=0x60 => version - 0x50,
+        _ => return None,
+    };
+    let len_ok = *len as usize == program.len()
+        && (MIN_WITNESS_PROGRAM_LENGTH..=MAX_WITNESS_PROGRAM_LENGTH).contains(&program.len());
+    if len_ok {
+        Some((version, program))
+    } else {
+        None
+    }
+}
+
+/// A witness version and program. The program length is checked against the rules for its
+/// version: v0 programs must be 20 bytes (P2WPKH) or 32 bytes (P2WSH), and all programs must be
+/// 2 to 40 bytes.
+///
+/// Serializes as the hex of its script pubkey.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(try_from = "ScriptPubkey", into = "ScriptPubkey")]
+pub struct WitnessProgram {
+    version: u8,
+    program: Vec<u8>,
+}
+
+impl WitnessProgram {
+    /// Instantiate a witness program. Errors with `SegwitVersionError` if the version is above
+    /// 16, or `InvalidSizeError` if the program length is invalid for the version.
+    pub fn new(version: u8, program: Vec<u8>) -> EncodingResult<Self> {
+        if version > MAX_WITNESS_VERSION {
+            return Err(EncodingError::SegwitVersionError(version));
+        }
+        let len = program.len();
+        if !(MIN_WITNESS_PROGRAM_LENGTH..=MAX_WITNESS_PROGRAM_LENGTH).contains(&len)
+            || (version == 0 && len != 20 && len != 32)
+        {
+            return Err(EncodingError::InvalidSizeError);
+        }
+        Ok(Self { version, program })
+    }
+
+    /// Parse a witness program from a script pubkey. Errors with `UnknownScriptType` if the
+    /// script is not a witness program, or `InvalidSizeError` if it is a v0 program of the wrong
+    /// length.
+    pub fn from_script_pubkey(script_pubkey: &ScriptPubkey) -> EncodingResult<Self> {
+        Self::from_script(script_pubkey.items())
+    }
+
+    /// Parse a witness program from raw script bytes
+    pub(crate) fn from_script(script: &[u8]) -> EncodingResult<Self> {
+        let (version, program) =
+            split_witness_program(script).ok_or(EncodingError::UnknownScriptType)?;
+        Self::new(version, program.to_vec())
+    }
+
+    /// The witness version, from 0 to 16
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// The witness program
+    pub fn program(&self) -> &[u8] {
+        &self.program
+    }
+
+    /// True if this is a v0 program committing to a pubkey hash
+    pub fn is_p2wpkh(&self) -> bool {
+        self.version == 0 && self.program.len() == 20
+    }
+
+    /// True if this is a v0 program committing to a script hash
+    pub fn is_p2wsh(&self) -> bool {
+        self.version == 0 && self.program.len() == 32
+    }
+
+    /// True if this is a v1 program committing to a taproot output key
+    pub fn is_p2tr(&self) -> bool {
+        self.version == 1 && self.program.len() == 32
+    }
+
+    /// The version opcode of the script pubkey. OP_0 for v0, OP_1 through OP_16 otherwise
+    pub fn version_opcode(&self) -> u8 {
+        match self.version {
+            0 => 0x00,
+            v => 0x50 + v,
+        }
+    }
+
+    /// The script pubkey paying to this program
+    pub fn to_script_pubkey(&self) -> ScriptPubkey {
+        self.to_script().into()
+    }
+
+    /// The raw script bytes of the script pubkey paying to this program
+    pub(crate) fn to_script(&self) -> Vec<u8> {
+        let mut v = Vec::with_capacity(2 + self.program.len());
+        v.push(self.version_opcode());
+        v.push(self.program.len() as u8);
+        v.extend_from_slice(&self.program);
+        v
+    }
+}
+
+impl TryFrom<ScriptPubkey> for WitnessProgram {
+    type Error = EncodingError;
+
+    fn try_from(script_pubkey: ScriptPubkey) -> EncodingResult<Self> {
+        Self::from_script_pubkey(&script_pubkey)
+    }
+}
+
+impl From<WitnessProgram> for ScriptPubkey {
+    fn from(w: WitnessProgram) -> ScriptPubkey {
+        w.to_script_pubkey()
+    }
+}
+
+impl From<&WitnessProgram> for ScriptPubkey {
+    fn from(w: &WitnessProgram) -> ScriptPubkey {
+        w.to_script_pubkey()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_round_trips_script_pubkeys() {
+        let cases = [
+            ("0014751e76e8199196d454941c45d1b3a323f1433bd6", 0, 20),
+            ("00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262", 0, 32),
+            ("5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c", 1, 32),
+            ("6002751e", 16, 2),
+            ("5128751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6", 1, 40),
+        ];
+        for (hex, version, len) in cases.iter() {
+            let spk = ScriptPubkey::from_hex(hex).unwrap();
+            let program = WitnessProgram::from_script_pubkey(&spk).unwrap();
+            assert_eq!(program.version(), *version);
+            assert_eq!(program.program().len(), *len);
+            assert_eq!(ScriptPubkey::from(program), spk);
+        }
+    }
+
+    #[test]
+    fn it_enforces_bip141_lengths() {
+        let errors = [
+            (
+                "0013751e76e8199196d454941c45d1b3a323f1433b",
+                EncodingError::InvalidSizeError,
+            ), // v0 length 19
+            ("5101ff", EncodingError::UnknownScriptType), // too short
+            (
+                "0014751e76e8199196d454941c45d1b3a323f1433b",
+                EncodingError::UnknownScriptType,
+            ), // bad push
+            ("4f02751e", EncodingError::UnknownScriptType), // OP_1NEGATE
+            (
+                "76a914751e76e8199196d454941c45d1b3a323f1433bd688ac",
+                EncodingError::UnknownScriptType,
+            ),
+        ];
+        for (hex, err) in errors.iter() {
+            let spk = ScriptPubkey::from_hex(hex).unwrap();
+            let result = WitnessProgram::from_script_pubkey(&spk).unwrap_err();
+            assert_eq!(result.to_string(), err.to_string());
+        }
+
+        assert!(WitnessProgram::new(17, vec![0; 32]).is_err());
+        assert!(WitnessProgram::new(0, vec![0; 33]).is_err());
+        assert!(WitnessProgram::new(2, vec![0; 41]).is_err());
+        let v2 = WitnessProgram::new(2, vec![0; 33]).unwrap();
+        assert_eq!(v2.to_script_pubkey()[0], 0x52);
+    }
+}
@@ -0,0 +1,274 @@
+//! A set of UTXOs, keyed by outpoint.
+//!
+//! `UtxoSet` tracks the coins controlled by a wallet. Applying a transaction removes the UTXOs it
+//! spends and adds the outputs it creates. The set can be filtered by script pubkey, and queried
+//! for balances. `UtxoSet::spendable` produces the UTXOs and spend types expected by
+//! `BitcoinTxBuilder::fund` and the consolidation planner.
+//!
+//! ```
+//! use bitcoins::{prelude::*, utxo_set::UtxoSet};
+//!
+//! let tx_hex = "02000000000101ee9242c89e79ab2aa537408839329895392b97505b3496d5543d6d2f531b94d20000000000fdffffff0173d301000000000017a914bba5acbec4e6e3374a0345bf3609fa7cfea825f18700cafd0700";
+//! let tx = BitcoinTx::deserialize_hex(tx_hex).unwrap();
+//!
+//! let mut set = UtxoSet::new();
+//! set.apply_tx(&tx);
+//! assert_eq!(set.len(), 1);
+//! assert_eq!(set.balance(), 119_667);
+//! ```
+
+use std::collections::{btree_map, BTreeMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    builder::InputType,
+    types::{BitcoinOutpoint, BitcoinTransaction, ScriptPubkey, ScriptType, Utxo},
+};
+
+/// A set of UTXOs, keyed by outpoint. Iteration is in outpoint order.
+///
+/// Serializes as a list of UTXOs, including their metadata.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Vec<Utxo>", into = "Vec<Utxo>")]
+pub struct UtxoSet {
+    utxos: BTreeMap<BitcoinOutpoint, Utxo>,
+}
+
+impl UtxoSet {
+    /// Instantiate an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of UTXOs in the set
+    pub fn len(&self) -> usize {
+        self.utxos.len()
+    }
+
+    /// True if the set holds no UTXOs
+    pub fn is_empty(&self) -> bool {
+        self.utxos.is_empty()
+    }
+
+    /// Add a UTXO. Returns the UTXO previously stored at its outpoint, if any.
+    pub fn insert(&mut self, utxo: Utxo) -> Option<Utxo> {
+        self.utxos.insert(utxo.outpoint, utxo)
+    }
+
+    /// Remove and return the UTXO at `outpoint`, if any
+    pub fn remove(&mut self, outpoint: &BitcoinOutpoint) -> Option<Utxo> {
+        self.utxos.remove(outpoint)
+    }
+
+    /// Return a reference to the UTXO at `outpoint`, if any
+    pub fn get(&self, outpoint: &BitcoinOutpoint) -> Option<&Utxo> {
+        self.utxos.get(outpoint)
+    }
+
+    /// True if the set holds a UTXO at `outpoint`
+    pub fn contains(&self, outpoint: &BitcoinOutpoint) -> bool {
+        self.utxos.contains_key(outpoint)
+    }
+
+    /// Iterate over the UTXOs in outpoint order
+    pub fn iter(&self) -> btree_map::Values<'_, BitcoinOutpoint, Utxo> {
+        self.utxos.values()
+    }
+
+    /// Apply a transaction to the set. UTXOs spent by the transaction are removed, and its
+    /// outputs are added, except for OP_RETURN outputs. Returns the removed UTXOs.
+    pub fn apply_tx<T: BitcoinTransaction>(&mut self, tx: &T) -> Vec<Utxo> {
+        self.apply_tx_filtered(tx, |_| true)
+    }
+
+    /// Apply a transaction to the set, adding only the outputs whose script pubkey matches
+    /// `filter`. Wallets use this to track only the outputs they control. UTXOs spent by the
+    /// transaction are always removed. Returns the removed UTXOs.
+    pub fn apply_tx_filtered<T, F>(&mut self, tx: &T, filter: F) -> Vec<Utxo>
+    where
+        T: BitcoinTransaction,
+        F: Fn(&ScriptPubkey) -> bool,
+    {
+        let spent = tx
+            .inputs()
+            .iter()
+            .filter_map(|input| self.remove(&input.outpoint))
+            .collect();
+        for (idx, output) in tx.outputs().iter().enumerate() {
+            let spk = &output.script_pubkey;
+            if let ScriptType::OpReturn(_) = spk.standard_type() {
+                continue;
+            }
+            if filter(spk) {
+                self.insert(Utxo::from_tx_output(tx, idx));
+            }
+        }
+        spent
+    }
+
+    /// Iterate over the UTXOs paying to `script_pubkey`
+    pub fn for_script_pubkey<'a>(
+        &'a self,
+        script_pubkey: &'a ScriptPubkey,
+    ) -> impl Iterator<Item = &'a Utxo> + 'a {
+        self.iter()
+            .filter(move |u| &u.script_pubkey == script_pubkey)
+    }
+
+    /// Return a new set holding the UTXOs that match `filter`
+    pub fn filter<F>(&self, filter: F) -> UtxoSet
+    where
+        F: Fn(&Utxo) -> bool,
+    {
+        self.iter().filter(|u| filter(u)).cloned().collect()
+    }
+
+    /// The total value of the UTXOs in the set
+    pub fn balance(&self) -> u64 {
+        self.iter().map(|u| u.value).sum()
+    }
+
+    /// The total value of the UTXOs paying to `script_pubkey`
+    pub fn balance_of(&self, script_pubkey: &ScriptPubkey) -> u64 {
+        self.for_script_pubkey(script_pubkey).map(|u| u.value).sum()
+    }
+
+    /// The total value of the UTXOs with at least `min_confs` confirmations at `tip`. UTXOs
+    /// without a known confirmation height are treated as unconfirmed.
+    pub fn confirmed_balance(&self, tip: u32, min_confs: u32) -> u64 {
+        self.iter()
+            .filter(|u| u.confirmations(tip).unwrap_or(0) >= min_confs)
+            .map(|u| u.value)
+            .sum()
+    }
+
+    /// The UTXOs and their spend types, for funding transactions or planning consolidations.
+    /// The spend type is inferred from the script pubkey, as in `InputType::from_address_type`.
+    /// UTXOs whose spend type can't be inferred, such as P2WSH, are omitted.
+    pub fn spendable(&self) -> Vec<(Utxo, InputType)> {
+        self.iter()
+            .filter_map(|u| {
+                let input_type = InputType::from_address_type(u.script_pubkey.address_type()?)?;
+                Some((u.clone(), input_type))
+            })
+            .collect()
+    }
+}
+
+impl From<Vec<Utxo>> for UtxoSet {
+    fn from(utxos: Vec<Utxo>) -> Self {
+        utxos.into_iter().collect()
+    }
+}
+
+impl From<UtxoSet> for Vec<Utxo> {
+    fn from(set: UtxoSet) -> Self {
+        set.utxos.into_values().collect()
+    }
+}
+
+impl std::iter::FromIterator<Utxo> for UtxoSet {
+    fn from_iter<I: IntoIterator<Item = Utxo>>(iter: I) -> Self {
+        let mut set = UtxoSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<Utxo> for UtxoSet {
+    fn extend<I: IntoIterator<Item = Utxo>>(&mut self, iter: I) {
+        for utxo in iter {
+            self.insert(utxo);
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a UtxoSet {
+    type Item = &'a Utxo;
+    type IntoIter = btree_map::Values<'a, BitcoinOutpoint, Utxo>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        builder::BitcoinTxBuilder,
+        enc::encoder::MainnetEncoder,
+        hashes::{BlockHash, TXID},
+        types::{BitcoinTx, SpendScript},
+    };
+    use coins_core::{builder::TxBuilder, types::tx::Transaction};
+
+    fn wpkh() -> ScriptPubkey {
+        ScriptPubkey::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap()
+    }
+
+    fn wsh() -> ScriptPubkey {
+        ScriptPubkey::from_hex(
+            "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+        )
+        .unwrap()
+    }
+
+    fn utxo(idx: u32, value: u64, script_pubkey: ScriptPubkey) -> Utxo {
+        let outpoint = BitcoinOutpoint::new(TXID::default(), idx);
+        Utxo::new(outpoint, value, script_pubkey, SpendScript::Missing)
+    }
+
+    #[test]
+    fn it_applies_transactions() {
+        let mut set: UtxoSet = vec![utxo(0, 10_000, wpkh()), utxo(1, 20_000, wsh())].into();
+        assert_eq!(set.balance(), 30_000);
+        assert_eq!(set.balance_of(&wpkh()), 10_000);
+
+        let tx: BitcoinTx = BitcoinTxBuilder::<MainnetEncoder>::new()
+            .spend(BitcoinOutpoint::new(TXID::default(), 0), 0xffff_fffd)
+            .pay_script_pubkey(4_000, wpkh())
+            .pay_script_pubkey(5_000, wsh())
+            .op_return(b"hello")
+            .unwrap()
+            .build_witness()
+            .unwrap()
+            .into();
+
+        let spent = set.apply_tx_filtered(&tx, |spk| spk == &wpkh());
+        assert_eq!(spent, vec![utxo(0, 10_000, wpkh())]);
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&BitcoinOutpoint::new(tx.txid(), 0)));
+        assert!(!set.contains(&BitcoinOutpoint::new(tx.txid(), 1)));
+        assert_eq!(set.balance_of(&wpkh()), 4_000);
+        assert_eq!(set.for_script_pubkey(&wsh()).count(), 1);
+
+        // Unfiltered application adds every output but the OP_RETURN
+        let mut set = UtxoSet::new();
+        assert!(set.apply_tx(&tx).is_empty());
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.balance(), 9_000);
+    }
+
+    #[test]
+    fn it_queries_balances_and_spendable_utxos() {
+        let set: UtxoSet = vec![
+            utxo(0, 10_000, wpkh()).confirmed_in(100, BlockHash::default()),
+            utxo(1, 20_000, wsh()).confirmed_in(105, BlockHash::default()),
+            utxo(2, 30_000, wpkh()),
+        ]
+        .into();
+        assert_eq!(set.confirmed_balance(105, 1), 30_000);
+        assert_eq!(set.confirmed_balance(105, 6), 10_000);
+        assert_eq!(set.filter(|u| u.value > 15_000).balance(), 50_000);
+
+        // P2WSH spend types can't be inferred
+        let spendable = set.spendable();
+        assert_eq!(spendable.len(), 2);
+        assert!(spendable.iter().all(|(_, t)| *t == InputType::Wpkh));
+
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(serde_json::from_str::<UtxoSet>(&json).unwrap(), set);
+    }
+}
@@ -0,0 +1,160 @@
+//! Virtual transactions shared by BIP322 message signatures and BIP325 signet block solutions.
+//!
+//! Both prove control of a script pubkey by signing a virtual `to_sign` transaction. Its only
+//! input spends the only output of a virtual `to_spend` transaction, which pays to the script
+//! pubkey and commits to some data in its script sig. BIP322 commits to the message hash, and
+//! BIP325 to the block header.
+
+use coins_bip32::ecdsa::{signature::DigestVerifier, Signature, VerifyingKey};
+use coins_core::{
+    hashes::{Digest, Hash160, Hash160Digest, Hash256},
+    types::tx::Transaction,
+};
+
+use crate::{
+    hashes::TXID,
+    types::{
+        legacy::{LegacySighashArgs, LegacyTx},
+        opcodes::{Instructions, Opcode},
+        script::{push_data, ScriptPubkey, ScriptSig},
+        stack::InputSignature,
+        tx::{BitcoinTransaction, Sighash},
+        txin::{BitcoinOutpoint, BitcoinTxIn},
+        txout::TxOut,
+        witness::{WitnessSighashArgs, WitnessTransaction, WitnessTx},
+    },
+};
+
+/// A reason a `to_sign` input does not satisfy its prevout script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SpendError {
+    /// The script sig or witness does not have the shape the script requires
+    Malformed,
+    /// The public key is malformed, or does not match the script
+    WrongKey,
+    /// The signature is malformed, or not valid for the key
+    InvalidSignature,
+}
+
+/// Build the virtual `to_spend` transaction. Its only output pays to `script_pubkey`, and its
+/// only input's script sig is `OP_0 <commitment>`.
+pub(crate) fn to_spend(script_pubkey: &ScriptPubkey, commitment: &[u8]) -> LegacyTx {
+    let mut script_sig = vec![0x00]; // OP_0
+    push_data(&mut script_sig, commitment);
+    let vin = vec![BitcoinTxIn::new(
+        BitcoinOutpoint::new(TXID::default(), 0xffff_ffff),
+        ScriptSig::from(script_sig),
+        0,
+    )];
+    let vout = vec![TxOut::new(0, script_pubkey.clone())];
+    LegacyTx::new(0, vin, vout, 0).expect("vin and vout are not empty")
+}
+
+/// Build the unsigned virtual `to_sign` transaction, spending the output of `to_spend`
+pub(crate) fn to_sign(to_spend: &LegacyTx) -> WitnessTx {
+    let vin = vec![BitcoinTxIn::new(
+        BitcoinOutpoint::new(to_spend.txid(), 0),
+        ScriptSig::null(),
+        0,
+    )];
+    let vout = vec![TxOut::new(0, ScriptPubkey::from(vec![0x6a]))]; // OP_RETURN
+    <WitnessTx as Transaction>::new(0, vin, vout, 0).expect("vin and vout are not empty")
+}
+
+/// Split a script sig into its pushes. `None` if it contains any other opcode.
+pub(crate) fn pushes(script_sig: &ScriptSig) -> Option<Vec<&[u8]>> {
+    Instructions::new(script_sig.items())
+        .map(|ins| match ins {
+            Ok(ins) if ins.opcode == Opcode::PushBytes(0) => Some(&[][..]),
+            Ok(ins) => ins.data,
+            Err(_) => None,
+        })
+        .collect()
+}
+
+/// Check that a public key hashes to `pubkey_hash`, and parse it
+fn parse_key(pubkey: &[u8], pubkey_hash: &Hash160Digest) -> Result<VerifyingKey, SpendError> {
+    let hash: Hash160Digest = Hash160::digest(pubkey).into();
+    if &hash != pubkey_hash {
+        return Err(SpendError::WrongKey);
+    }
+    VerifyingKey::from_sec1_bytes(pubkey).map_err(|_| SpendError::WrongKey)
+}
+
+/// Parse a DER signature with its sighash flag
+fn parse_signature(sig: &[u8]) -> Result<(Signature, Sighash), SpendError> {
+    let sig = InputSignature::from_stack_item(sig).ok_or(SpendError::InvalidSignature)?;
+    match (sig.signature(), sig.sighash()) {
+        (Some(signature), Ok(flag)) => Ok((signature, flag)),
+        _ => Err(SpendError::InvalidSignature),
+    }
+}
+
+/// Verify an ECDSA signature over the digest in `hasher`
+fn verify_signature(
+    key: &VerifyingKey,
+    hasher: Hash256,
+    signature: &Signature,
+) -> Result<(), SpendError> {
+    key.verify_digest(hasher, signature)
+        .map_err(|_| SpendError::InvalidSignature)
+}
+
+/// Verify a signature over the `to_sign` input, using the legacy sighash of `prevout_script`
+pub(crate) fn verify_legacy_sig(
+    tx: &WitnessTx,
+    prevout_script: &ScriptPubkey,
+    sig: &[u8],
+    pubkey: &[u8],
+) -> Result<(), SpendError> {
+    let (signature, sighash_flag) = parse_signature(sig)?;
+    let key = VerifyingKey::from_sec1_bytes(pubkey).map_err(|_| SpendError::WrongKey)?;
+    let args = LegacySighashArgs {
+        index: 0,
+        sighash_flag,
+        prevout_script: prevout_script.into(),
+    };
+    let mut hasher = Hash256::default();
+    tx.write_legacy_sighash_preimage(&mut hasher, &args)
+        .map_err(|_| SpendError::InvalidSignature)?;
+    verify_signature(&key, hasher, &signature)
+}
+
+/// Verify a P2PKH spend of the `to_sign` input, by its script sig
+pub(crate) fn verify_pkh(
+    tx: &WitnessTx,
+    script_pubkey: &ScriptPubkey,
+    pubkey_hash: &Hash160Digest,
+) -> Result<(), SpendError> {
+    if !tx.witnesses()[0].is_empty() {
+        return Err(SpendError::Malformed);
+    }
+    match pushes(&tx.inputs()[0].script_sig).as_deref() {
+        Some([sig, pubkey]) => {
+            parse_key(pubkey, pubkey_hash)?;
+            verify_legacy_sig(tx, script_pubkey, sig, pubkey)
+        }
+        _ => Err(SpendError::Malformed),
+    }
+}
+
+/// Verify a P2WPKH spend of the `to_sign` input, by its witness. The script sig is not checked,
+/// so that nested P2WPKH spends may be verified after their redeem script.
+pub(crate) fn verify_wpkh(tx: &WitnessTx, pubkey_hash: &Hash160Digest) -> Result<(), SpendError> {
+    let witness = &tx.witnesses()[0];
+    if witness.len() != 2 {
+        return Err(SpendError::Malformed);
+    }
+    let key = parse_key(witness[1].items(), pubkey_hash)?;
+    let (signature, sighash_flag) = parse_signature(witness[0].items())?;
+    let args = WitnessSighashArgs {
+        index: 0,
+        sighash_flag,
+        prevout_script: (&ScriptPubkey::p2pkh_from_hash(pubkey_hash)).into(),
+        prevout_value: 0,
+    };
+    let mut hasher = Hash256::default();
+    tx.write_witness_sighash_preimage(&mut hasher, &args)
+        .map_err(|_| SpendError::InvalidSignature)?;
+    verify_signature(&key, hasher, &signature)
+}
@@ -20,6 +20,8 @@ cargo --verbose build --target wasm32-unknown-unknown
 ### Bitcoins ###
 cd ../bitcoins
 cargo --verbose build --target wasm32-unknown-unknown
+cargo --verbose build --no-default-features --features="testnet"
+cargo --verbose build --no-default-features --features="signet"
 
 # default features covered by workspace-level tests
 cargo test --verbose
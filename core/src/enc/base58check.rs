@@ -0,0 +1,135 @@
+//! Base58Check encoding, as used by legacy addresses, WIF private keys, and BIP32 extended keys.
+//!
+//! A Base58Check string is the base58 encoding of a payload followed by a 4-byte checksum. The
+//! checksum is the first 4 bytes of the Hash256 of the payload. Addresses and WIF keys prefix the
+//! payload with a single version byte. Extended keys use a 4-byte version, so `encode` and
+//! `decode` treat the payload as opaque. `encode_versioned` and `decode_versioned` split off a
+//! single version byte.
+//!
+//! ```
+//! use coins_core::enc::base58check;
+//!
+//! let s = base58check::encode_versioned(0x00, &[0u8; 20]);
+//! assert_eq!(s, "1111111111111111111114oLvT2");
+//! assert_eq!(base58check::decode_versioned(&s).unwrap(), (0x00, vec![0u8; 20]));
+//! ```
+
+use thiserror::Error;
+
+use crate::hashes::{Digest, Hash256};
+
+/// The length of the checksum appended to the payload
+pub const CHECKSUM_LEN: usize = 4;
+
+/// Errors decoding a Base58Check string.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum Base58CheckError {
+    /// The string is not valid base58
+    #[error(transparent)]
+    Base58(#[from] bs58::decode::Error),
+
+    /// The string decodes to fewer bytes than the checksum
+    #[error(
+        "Base58Check string decodes to {0} bytes. Expected at least {}",
+        CHECKSUM_LEN
+    )]
+    MissingChecksum(usize),
+
+    /// The decoded payload has no version byte
+    #[error("Base58Check payload is empty. Expected a version byte")]
+    MissingVersion,
+
+    /// The checksum does not match the payload
+    #[error("Base58Check checksum mismatch. Got {got:?}, expected {expected:?}")]
+    BadChecksum {
+        /// The checksum found in the string
+        got: [u8; CHECKSUM_LEN],
+        /// The checksum of the payload
+        expected: [u8; CHECKSUM_LEN],
+    },
+}
+
+/// Calculate the checksum of a payload
+pub fn checksum(payload: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let mut checksum = [0u8; CHECKSUM_LEN];
+    checksum.copy_from_slice(&Hash256::digest(payload)[..CHECKSUM_LEN]);
+    checksum
+}
+
+/// Encode a payload to Base58Check
+pub fn encode(payload: &[u8]) -> String {
+    let mut data = payload.to_vec();
+    data.extend(&checksum(payload));
+    bs58::encode(data).into_string()
+}
+
+/// Decode a Base58Check string to its payload, verifying the checksum
+pub fn decode(s: &str) -> Result<Vec<u8>, Base58CheckError> {
+    let mut data = bs58::decode(s).into_vec()?;
+    if data.len() < CHECKSUM_LEN {
+        return Err(Base58CheckError::MissingChecksum(data.len()));
+    }
+    let mut got = [0u8; CHECKSUM_LEN];
+    got.copy_from_slice(&data.split_off(data.len() - CHECKSUM_LEN));
+    let expected = checksum(&data);
+    if got != expected {
+        return Err(Base58CheckError::BadChecksum { got, expected });
+    }
+    Ok(data)
+}
+
+/// Encode a version byte and payload to Base58Check
+pub fn encode_versioned(version: u8, payload: &[u8]) -> String {
+    let mut data = vec![version];
+    data.extend(payload);
+    encode(&data)
+}
+
+/// Decode a Base58Check string to its version byte and payload, verifying the checksum
+pub fn decode_versioned(s: &str) -> Result<(u8, Vec<u8>), Base58CheckError> {
+    let mut data = decode(s)?;
+    if data.is_empty() {
+        return Err(Base58CheckError::MissingVersion);
+    }
+    let version = data.remove(0);
+    Ok((version, data))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_round_trips_base58check() {
+        // P2PKH and P2SH addresses
+        let cases: &[(u8, &str)] = &[
+            (0x00, "1AqE7oGF1EUoJviX1uuYrwpRBdEBTuGhES"),
+            (0x05, "377mKFYsaJPsxYSB5aFfx8SW3RaN5BzZVh"),
+        ];
+        for (version, s) in cases.iter() {
+            let (v, payload) = decode_versioned(s).unwrap();
+            assert_eq!(v, *version);
+            assert_eq!(payload.len(), 20);
+            assert_eq!(encode_versioned(v, &payload), *s);
+        }
+        assert_eq!(encode(&[]), "3QJmnh");
+        assert_eq!(decode("3QJmnh").unwrap(), Vec::<u8>::new());
+        assert_eq!(
+            decode_versioned("3QJmnh"),
+            Err(Base58CheckError::MissingVersion)
+        );
+    }
+
+    #[test]
+    fn it_reports_typed_errors() {
+        assert!(matches!(
+            decode("1AqE7oGF1EUoJviX1uuYrwpRBdEBTuGhE0"),
+            Err(Base58CheckError::Base58(_))
+        ));
+        assert_eq!(decode("11"), Err(Base58CheckError::MissingChecksum(2)));
+        assert!(matches!(
+            decode("1AqE7oGF1EUoJviX1uuYrwpRBdEBTuGhET"),
+            Err(Base58CheckError::BadChecksum { .. })
+        ));
+    }
+}
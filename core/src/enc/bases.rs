@@ -1,14 +1,18 @@
 //! Contains simplified access to `bech32` and `base58check` encoder/decoder for Bitcoin
 //! addresses. Also defines common encoder errors.
+//!
+//! `encode_bech32` and `decode_bech32` handle arbitrary bech32 data. Segwit addresses should use
+//! `encode_segwit_address` and `decode_segwit_address`, which enforce the rules of BIP173 and
+//! BIP350, including the bech32m checksum for witness versions 1 and up.
 
 use bech32::{
     decode as b32_decode, encode as b32_encode, u5, Error as BechError, FromBase32, ToBase32,
 };
 
-use base58check::{FromBase58Check, FromBase58CheckError, ToBase58Check};
-
 use thiserror::Error;
 
+use crate::enc::base58check::{self, Base58CheckError};
+
 /// Errors that can be returned by the Bitcoin `AddressEncoder`.
 #[derive(Debug, Error)]
 pub enum EncodingError {
@@ -34,9 +38,9 @@ pub enum EncodingError {
         expected: u8,
     },
 
-    /// Bubbled up error from base58check library
-    #[error("FromBase58CheckError: {0:?}")]
-    B58Error(FromBase58CheckError),
+    /// Bubbled up error from Base58Check decoding
+    #[error(transparent)]
+    B58Error(#[from] Base58CheckError),
 
     /// Bubbled up error from bech32 library
     #[error(transparent)]
@@ -53,15 +57,61 @@ pub enum EncodingError {
     /// Invalid Address Size
     #[error("Invalid Address Size")]
     InvalidSizeError,
+
+    /// The segwit address has no witness version
+    #[error("Segwit address has an empty data section")]
+    MissingWitnessVersion,
+
+    /// The segwit address checksum does not match its witness version
+    #[error("Witness version {version} requires a {expected:?} checksum. Got {got:?}")]
+    WrongChecksumVariant {
+        /// The witness version
+        version: u8,
+        /// The checksum variant found
+        got: Bech32Variant,
+        /// The checksum variant required by the witness version
+        expected: Bech32Variant,
+    },
+
+    /// The witness program length is invalid for its witness version
+    #[error("Invalid witness program length {length} for witness version {version}")]
+    InvalidProgramLength {
+        /// The witness version
+        version: u8,
+        /// The witness program length in bytes
+        length: usize,
+    },
 }
 
-/// Impl explicitly because FromBase58CheckError doesn't implement the std error format
-impl From<FromBase58CheckError> for EncodingError {
-    fn from(e: FromBase58CheckError) -> Self {
-        EncodingError::B58Error(e)
+impl EncodingError {
+    /// A stable, machine-readable `enc.*` code for the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            EncodingError::UnknownScriptType => "enc.unknown_script_type",
+            EncodingError::WrongHrp { .. } => "enc.wrong_hrp",
+            EncodingError::WrongVersion { .. } => "enc.wrong_version",
+            EncodingError::B58Error(_) => "enc.base58check",
+            EncodingError::BechError(_) => "enc.bech32",
+            EncodingError::NullDataScript => "enc.null_data_script",
+            EncodingError::SegwitVersionError(_) => "enc.segwit_version",
+            EncodingError::InvalidSizeError => "enc.invalid_size",
+            EncodingError::MissingWitnessVersion => "enc.missing_witness_version",
+            EncodingError::WrongChecksumVariant { .. } => "enc.wrong_checksum_variant",
+            EncodingError::InvalidProgramLength { .. } => "enc.invalid_program_length",
+        }
+    }
+
+    /// True if the error was caused by the caller's input. Encoding errors are always caused by
+    /// a malformed address or an unencodable script.
+    pub fn is_user_error(&self) -> bool {
+        true
     }
 }
 
+/// The checksum variant of a bech32 string. BIP350 requires `Bech32` for witness version 0, and
+/// `Bech32m` for all later versions.
+pub use bech32::Variant as Bech32Variant;
+
 /// A simple result type alias
 pub type EncodingResult<T> = Result<T, EncodingError>;
 
@@ -70,13 +120,16 @@ pub type EncodingResult<T> = Result<T, EncodingError>;
 pub fn encode_bech32(hrp: &str, v: u8, h: &[u8]) -> EncodingResult<String> {
     let mut v = vec![u5::try_from_u8(v)?];
     v.extend(&h.to_base32());
-    b32_encode(hrp, &v).map_err(|v| v.into())
+    b32_encode(hrp, &v, Bech32Variant::Bech32).map_err(|v| v.into())
 }
 
 /// Decode a witness program from a bech32 string. Caller specifies an expected HRP. If a
 /// different HRP is found, returns `WrongHrp`.
 pub fn decode_bech32(expected_hrp: &str, s: &str) -> EncodingResult<(u8, Vec<u8>)> {
-    let (hrp, data) = b32_decode(&s)?;
+    let (hrp, data, variant) = b32_decode(s)?;
+    if variant != Bech32Variant::Bech32 {
+        return Err(BechError::InvalidChecksum.into());
+    }
     if hrp != expected_hrp {
         return Err(EncodingError::WrongHrp {
             got: hrp,
@@ -85,21 +138,96 @@ pub fn decode_bech32(expected_hrp: &str, s: &str) -> EncodingResult<(u8, Vec<u8>
     }
 
     // Extract the witness version and payload
+    if data.is_empty() {
+        return Err(EncodingError::InvalidSizeError);
+    }
     let (v, p) = data.split_at(1);
     let payload = Vec::from_base32(&p)?;
 
     Ok((v[0].to_u8(), payload))
 }
 
+const SEGWIT_ADDRESS_MAX_LEN: usize = 90;
+
+/// The checksum variant required by a witness version
+fn witness_version_variant(version: u8) -> Bech32Variant {
+    if version == 0 {
+        Bech32Variant::Bech32
+    } else {
+        Bech32Variant::Bech32m
+    }
+}
+
+/// Check a witness version and program length against BIP141
+fn check_witness_program(version: u8, length: usize) -> EncodingResult<()> {
+    if version > 16 {
+        return Err(EncodingError::SegwitVersionError(version));
+    }
+    if !(2..=40).contains(&length) || (version == 0 && length != 20 && length != 32) {
+        return Err(EncodingError::InvalidProgramLength { version, length });
+    }
+    Ok(())
+}
+
+/// Encode a witness program as a segwit address. Uses the bech32 checksum for witness version
+/// 0, and bech32m for later versions.
+pub fn encode_segwit_address(hrp: &str, version: u8, program: &[u8]) -> EncodingResult<String> {
+    check_witness_program(version, program.len())?;
+    let mut data = vec![u5::try_from_u8(version)?];
+    data.extend(program.to_base32());
+    let s = b32_encode(hrp, &data, witness_version_variant(version))?;
+    if s.len() > SEGWIT_ADDRESS_MAX_LEN {
+        return Err(BechError::InvalidLength.into());
+    }
+    Ok(s)
+}
+
+/// Decode a segwit address to its witness version and program, enforcing BIP173 and BIP350.
+/// Errors precisely: `BechError` for malformed strings (including mixed case and bad
+/// checksums), `WrongHrp` for another network's address, `SegwitVersionError`,
+/// `WrongChecksumVariant`, and `InvalidProgramLength` for invalid witness programs.
+pub fn decode_segwit_address(expected_hrp: &str, s: &str) -> EncodingResult<(u8, Vec<u8>)> {
+    if s.len() > SEGWIT_ADDRESS_MAX_LEN {
+        return Err(BechError::InvalidLength.into());
+    }
+    let (hrp, data, variant) = b32_decode(s)?;
+    if hrp != expected_hrp {
+        return Err(EncodingError::WrongHrp {
+            got: hrp,
+            expected: expected_hrp.to_owned(),
+        });
+    }
+
+    let (version, program) = data
+        .split_first()
+        .ok_or(EncodingError::MissingWitnessVersion)?;
+    let version = version.to_u8();
+    if version > 16 {
+        return Err(EncodingError::SegwitVersionError(version));
+    }
+    let expected = witness_version_variant(version);
+    if variant != expected {
+        return Err(EncodingError::WrongChecksumVariant {
+            version,
+            got: variant,
+            expected,
+        });
+    }
+
+    let program = Vec::from_base32(program)?;
+    check_witness_program(version, program.len())?;
+    Ok((version, program))
+}
+
 /// Encodes a byte slice to base58check with the specified version byte.
 pub fn encode_base58(version: u8, v: &[u8]) -> String {
-    v.to_base58check(version)
+    base58check::encode_versioned(version, v)
 }
 
-/// Decodes base58check into a byte string. Returns a `FromBase58CheckError` if the checksum or
-/// encoding is wrong. Returns a `WrongVersion` if it decodes an unexpected version.
+/// Decodes base58check into a byte string. Returns a `B58Error` if the checksum or encoding is
+/// wrong. Returns a `WrongVersion` if it decodes an unexpected version.
 pub fn decode_base58(expected_version: u8, s: &str) -> EncodingResult<Vec<u8>> {
-    let (version, data) = s.from_base58check()?;
+    let (version, data) = base58check::decode_versioned(s)?;
     if version != expected_version {
         return Err(EncodingError::WrongVersion {
             got: version,
@@ -199,10 +327,144 @@ mod test {
             Err(EncodingError::BechError(_)) => {}
             _ => assert!(false, "Got the wrong error"),
         }
+        assert!(matches!(
+            decode_bech32("bc", "bc1gmk9yu"),
+            Err(EncodingError::InvalidSizeError)
+        ));
         match decode_base58(5, "3HXNf6Waje5YFsLEQZ2") {
             Ok(_) => assert!(false, "expected an error"),
             Err(EncodingError::B58Error(_)) => {}
             _ => assert!(false, "Got the wrong error"),
         }
     }
+
+    #[test]
+    fn it_decodes_bip350_valid_segwit_addresses() {
+        // (address, HRP, script pubkey)
+        let cases = [
+            ("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4", "bc", "0014751e76e8199196d454941c45d1b3a323f1433bd6"),
+            ("tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7", "tb", "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262"),
+            ("bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y", "bc", "5128751e76e8199196d454941c45d1b3a323f1433bd6751e76e8199196d454941c45d1b3a323f1433bd6"),
+            ("BC1SW50QGDZ25J", "bc", "6002751e"),
+            ("tb1qqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesrxh6hy", "tb", "0020000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433"),
+            ("tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c", "tb", "5120000000c4a5cad46221b2a187905e5266362b99d5e91c6ce24d165dab93e86433"),
+            ("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0", "bc", "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"),
+        ];
+        for (address, hrp, script_pubkey) in cases.iter() {
+            let (version, program) = decode_segwit_address(hrp, address).unwrap();
+            let opcode = if version == 0 { 0 } else { 0x50 + version };
+            let mut expected = vec![opcode, program.len() as u8];
+            expected.extend(&program);
+            assert_eq!(hex::encode(expected), *script_pubkey);
+            let encoded = encode_segwit_address(hrp, version, &program).unwrap();
+            assert_eq!(encoded, address.to_lowercase());
+            if version == 0 {
+                assert_eq!(encoded, encode_bech32(hrp, 0, &program).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn it_reports_bip173_and_bip350_invalid_segwit_addresses() {
+        use Bech32Variant::*;
+        let wrong_variant = |version, got, expected| EncodingError::WrongChecksumVariant {
+            version,
+            got,
+            expected,
+        };
+        let program_length =
+            |version, length| EncodingError::InvalidProgramLength { version, length };
+        let cases = [
+            // Invalid human-readable part
+            (
+                "tc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq5zuyut",
+                EncodingError::WrongHrp {
+                    got: "tc".to_owned(),
+                    expected: "bc".to_owned(),
+                },
+            ),
+            // Bech32 instead of Bech32m
+            (
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd",
+                wrong_variant(1, Bech32, Bech32m),
+            ),
+            (
+                "BC1S0XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ54WELL",
+                wrong_variant(16, Bech32, Bech32m),
+            ),
+            // Bech32m instead of Bech32
+            (
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh",
+                wrong_variant(0, Bech32m, Bech32),
+            ),
+            // Invalid checksum
+            (
+                "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5",
+                BechError::InvalidChecksum.into(),
+            ),
+            // Invalid character in checksum
+            (
+                "bc1p38j9r5y49hruaue7wxjce0updqjuyyx0kh56v8s25huc6995vvpql3jow4",
+                BechError::InvalidChar('o').into(),
+            ),
+            // Invalid witness version
+            (
+                "BC130XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ7ZWS8R",
+                EncodingError::SegwitVersionError(17),
+            ),
+            // Invalid program length
+            ("bc1pw5dgrnzv", program_length(1, 1)),
+            (
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v8n0nx0muaewav253zgeav",
+                program_length(1, 41),
+            ),
+            // Mixed case
+            (
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vq47Zagq",
+                BechError::MixedCase.into(),
+            ),
+            // More than 4 bits of zero padding
+            (
+                "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v07qwwzcrf",
+                BechError::InvalidPadding.into(),
+            ),
+            // Empty data section
+            ("bc1gmk9yu", EncodingError::MissingWitnessVersion),
+        ];
+        for (address, expected) in cases.iter() {
+            let err = decode_segwit_address("bc", address).unwrap_err();
+            assert_eq!(
+                format!("{:?}", err),
+                format!("{:?}", expected),
+                "{}",
+                address
+            );
+        }
+
+        // Invalid program length for witness version 0
+        let address = encode_bech32("bc", 0, &[0u8; 16]).unwrap();
+        assert!(matches!(
+            decode_segwit_address("bc", &address),
+            Err(EncodingError::InvalidProgramLength {
+                version: 0,
+                length: 16
+            })
+        ));
+
+        // Non-zero padding
+        assert!(matches!(
+            decode_segwit_address(
+                "tb",
+                "tb1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vpggkg4j"
+            ),
+            Err(EncodingError::BechError(BechError::InvalidPadding))
+        ));
+        assert!(matches!(
+            encode_segwit_address("bc", 0, &[0u8; 21]),
+            Err(EncodingError::InvalidProgramLength {
+                version: 0,
+                length: 21
+            })
+        ));
+    }
 }
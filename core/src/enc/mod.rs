@@ -1,8 +1,10 @@
-//! Holds generalized encoding tools. This includes an `AddressEncoder` and bech32 encoding
-//! and decoding functionality.
+//! Holds generalized encoding tools. This includes an `AddressEncoder`, and bech32 and
+//! Base58Check encoding and decoding functionality.
 
 pub mod address;
+pub mod base58check;
 pub mod bases;
 
 pub use address::*;
+pub use base58check::Base58CheckError;
 pub use bases::*;
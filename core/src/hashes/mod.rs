@@ -226,3 +226,6 @@ marked_digest!(
     Hash256Digest,
     Hash256
 );
+
+impl_hex_serde!(Hash160Digest);
+impl_hex_serde!(Hash256Digest);
@@ -30,3 +30,7 @@ pub mod ser;
 pub mod types;
 
 pub use prelude::*;
+
+// Re-exported for use in exported macros
+#[doc(hidden)]
+pub use hex;
@@ -19,8 +19,8 @@ macro_rules! impl_hex_serde {
             where
                 D: serde::Deserializer<'de>,
             {
-                let s: &str = serde::Deserialize::deserialize(deserializer)?;
-                <$item as $crate::ser::ByteFormat>::deserialize_hex(s)
+                let s: String = serde::Deserialize::deserialize(deserializer)?;
+                <$item as $crate::ser::ByteFormat>::deserialize_hex(&s)
                     .map_err(|e| serde::de::Error::custom(e.to_string()))
             }
         }
@@ -116,6 +116,20 @@ macro_rules! wrap_prefixed_byte_vector {
             pub fn insert(&mut self, index: usize, i: u8) {
                 self.0.insert(index, i)
             }
+
+            /// Instantiate a wrapped vector from a hex string of its items. Unlike
+            /// `deserialize_hex`, this does NOT expect a length prefix.
+            pub fn from_hex(s: &str) -> Result<Self, $crate::ser::SerError> {
+                Ok(Self($crate::hex::decode(s)?))
+            }
+        }
+
+        impl std::convert::TryFrom<&str> for $wrapper_name {
+            type Error = $crate::ser::SerError;
+
+            fn try_from(s: &str) -> Result<Self, Self::Error> {
+                Self::from_hex(s)
+            }
         }
 
         impl From<&[u8]> for $wrapper_name {
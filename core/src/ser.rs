@@ -3,7 +3,7 @@
 use base64::DecodeError;
 use hex::FromHexError;
 use std::{
-    convert::TryInto,
+    convert::TryFrom,
     fmt::Debug,
     io::{Cursor, Error as IOError, Read, Write},
 };
@@ -40,6 +40,39 @@ pub enum SerError {
         /// The number of items succesfully deserialized
         got: usize,
     },
+
+    /// Thrown when a length prefix exceeds the limit set by the caller.
+    #[error("Length prefix {got} exceeds the limit of {limit}")]
+    ExcessiveLength {
+        /// The maximum permitted length
+        limit: usize,
+        /// The length read from the prefix
+        got: u64,
+    },
+}
+
+impl SerError {
+    /// A stable, machine-readable `ser.*` code for the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            SerError::NonMinimalVarInt => "ser.non_minimal_varint",
+            SerError::IoError(_) => "ser.io",
+            SerError::FromHexError(_) => "ser.hex",
+            SerError::DecodeError(_) => "ser.base64",
+            SerError::ComponentError(_) => "ser.component",
+            SerError::InsufficientSeqItems { .. } => "ser.insufficient_seq_items",
+            SerError::ExcessiveLength { .. } => "ser.excessive_length",
+        }
+    }
+
+    /// True if the error was caused by malformed input, rather than by a failing `Read` or
+    /// `Write`. Running out of input while reading is a malformed input.
+    pub fn is_user_error(&self) -> bool {
+        match self {
+            SerError::IoError(e) => e.kind() == std::io::ErrorKind::UnexpectedEof,
+            _ => true,
+        }
+    }
 }
 
 /// Operation mode for `read_seq_from`.
@@ -173,8 +206,32 @@ where
     E: From<SerError> + From<IOError> + std::error::Error,
     I: ByteFormat<Error = E>,
 {
-    let items = read_compact_int(reader)?;
-    I::read_seq_from(reader, ReadSeqMode::Exactly(items.try_into().unwrap())).map_err(Into::into)
+    read_prefix_vec_limited(reader, usize::MAX)
+}
+
+/// Convenience function for reading a length prefix, erroring if it exceeds `limit`. Use this
+/// before reading length-prefixed data from untrusted sources, to avoid reading or allocating
+/// arbitrarily large objects.
+pub fn read_prefix_len<R>(reader: &mut R, limit: usize) -> SerResult<usize>
+where
+    R: Read,
+{
+    let got = read_compact_int(reader)?;
+    match usize::try_from(got) {
+        Ok(len) if len <= limit => Ok(len),
+        _ => Err(SerError::ExcessiveLength { limit, got }),
+    }
+}
+
+/// Convenience function for reading a prefixed vector of at most `limit` items
+pub fn read_prefix_vec_limited<R, E, I>(reader: &mut R, limit: usize) -> Result<Vec<I>, E>
+where
+    R: Read,
+    E: From<SerError> + From<IOError> + std::error::Error,
+    I: ByteFormat<Error = E>,
+{
+    let items = read_prefix_len(reader, limit)?;
+    I::read_seq_from(reader, ReadSeqMode::Exactly(items))
 }
 
 /// Convenience function to write a Bitcoin-style length-prefixed vector.
@@ -366,8 +423,16 @@ impl ByteFormat for u8 {
     {
         match mode {
             ReadSeqMode::Exactly(number) => {
-                let mut v = vec![0u8; number];
-                reader.read_exact(v.as_mut_slice())?;
+                // Read through `take` so that a length prefix larger than the input cannot
+                // cause a large allocation up front
+                let mut v = vec![];
+                reader.take(number as u64).read_to_end(&mut v)?;
+                if v.len() != number {
+                    return Err(SerError::InsufficientSeqItems {
+                        got: v.len(),
+                        expected: number,
+                    });
+                }
                 Ok(v)
             }
             ReadSeqMode::AtMost(limit) => {
@@ -416,6 +481,31 @@ mod test {
         }
     }
 
+    #[test]
+    fn it_limits_prefixed_vectors() {
+        let v = [3u8, 0, 1, 2];
+        let read: Vec<u8> = read_prefix_vec_limited(&mut v.as_ref(), 3).unwrap();
+        assert_eq!(read, vec![0, 1, 2]);
+
+        match read_prefix_vec_limited::<_, SerError, u8>(&mut v.as_ref(), 2) {
+            Err(SerError::ExcessiveLength { limit: 2, got: 3 }) => {}
+            _ => panic!("expected excessive length"),
+        }
+
+        // The prefix is checked before any items are read
+        let huge = [0xffu8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        assert!(read_prefix_len(&mut huge.as_ref(), usize::MAX >> 1).is_err());
+
+        // An unlimited prefix claiming more bytes than the input holds errors without
+        // allocating the claimed length
+        let mut short = vec![0xffu8, 0, 0, 0, 0, 0, 1, 0, 0];
+        short.extend_from_slice(&[1, 2, 3]);
+        match read_prefix_vec::<_, SerError, u8>(&mut short.as_slice()) {
+            Err(SerError::InsufficientSeqItems { got: 3, .. }) => {}
+            _ => panic!("expected insufficient seq items"),
+        }
+    }
+
     #[test]
     fn it_implements_byteformat_for_u8() {
         for i in 0..u8::MAX {
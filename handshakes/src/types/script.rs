@@ -15,14 +15,10 @@ coins_core::wrap_prefixed_byte_vector!(
     ///
     /// `Script::null()` and `Script::default()` return the empty byte vector with a 0
     /// prefix, which represents numerical 0, boolean `false`, or null bytestring.
+    ///
+    /// Parse hex with `Script::from_hex` or `Script::try_from`. `Script` no longer implements
+    /// `From<&str>`, which panicked on invalid hex, and conflicts with `TryFrom<&str>`.
     Script
 );
 
 impl HandshakeScript for Script {}
-
-impl From<&str> for Script {
-    fn from(s: &str) -> Self {
-        let bytes = hex::decode(s).unwrap();
-        bytes.into()
-    }
-}
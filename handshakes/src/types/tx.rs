@@ -720,7 +720,8 @@ mod tests {
         let tx = HandshakeTx::deserialize_hex(hex).unwrap();
         let args = SighashArgs {
             index: 0,
-            prevout_script: Script::from("76c0146d7428ea0c83eee3a74edc53a1f9a7f26ca5d5c988ac"),
+            prevout_script: Script::from_hex("76c0146d7428ea0c83eee3a74edc53a1f9a7f26ca5d5c988ac")
+                .unwrap(),
             sighash_flag: Sighash::All,
             prevout_value: 503306619,
         };
@@ -737,7 +738,8 @@ mod tests {
         let tx = HandshakeTx::deserialize_hex(hex).unwrap();
         let args = SighashArgs {
             index: 0,
-            prevout_script: Script::from("76c0149329051e96615297625c4458b7409f2159a5a3a888ac"),
+            prevout_script: Script::from_hex("76c0149329051e96615297625c4458b7409f2159a5a3a888ac")
+                .unwrap(),
             sighash_flag: Sighash::None,
             prevout_value: 503385487,
         };
@@ -754,7 +756,8 @@ mod tests {
         let tx = HandshakeTx::deserialize_hex(hex).unwrap();
         let args = SighashArgs {
             index: 0,
-            prevout_script: Script::from("76c0149ef33fdf7c4a2e6b37bf462c55de2dc945b5bedd88ac"),
+            prevout_script: Script::from_hex("76c0149ef33fdf7c4a2e6b37bf462c55de2dc945b5bedd88ac")
+                .unwrap(),
             sighash_flag: Sighash::Single,
             prevout_value: 4312704038,
         };
@@ -771,7 +774,8 @@ mod tests {
         let tx = HandshakeTx::deserialize_hex(hex).unwrap();
         let args = SighashArgs {
             index: 0,
-            prevout_script: Script::from("76c014dcf0d847d82f44a99617c9874dbee61be407f77b88ac"),
+            prevout_script: Script::from_hex("76c014dcf0d847d82f44a99617c9874dbee61be407f77b88ac")
+                .unwrap(),
             sighash_flag: Sighash::SingleReverse,
             prevout_value: 1500000,
         };
@@ -788,7 +792,8 @@ mod tests {
         let tx = HandshakeTx::deserialize_hex(hex).unwrap();
         let args = SighashArgs {
             index: 0,
-            prevout_script: Script::from("76c01498c8297a67eb81ec36253828b5621a601ba2328a88ac"),
+            prevout_script: Script::from_hex("76c01498c8297a67eb81ec36253828b5621a601ba2328a88ac")
+                .unwrap(),
             sighash_flag: Sighash::AllNoInput,
             prevout_value: 2005260900,
         };
@@ -805,7 +810,8 @@ mod tests {
         let tx = HandshakeTx::deserialize_hex(hex).unwrap();
         let args = SighashArgs {
             index: 0,
-            prevout_script: Script::from("76c0142d355655e690be3f4aadbbff13622dc0226f991f88ac"),
+            prevout_script: Script::from_hex("76c0142d355655e690be3f4aadbbff13622dc0226f991f88ac")
+                .unwrap(),
             sighash_flag: Sighash::NoneNoInput,
             prevout_value: 10000,
         };
@@ -822,7 +828,8 @@ mod tests {
         let tx = HandshakeTx::deserialize_hex(hex).unwrap();
         let args = SighashArgs {
             index: 0,
-            prevout_script: Script::from("76c014c0113093a59a0eb6f4854d92ed3c8e0477d5bdea88ac"),
+            prevout_script: Script::from_hex("76c014c0113093a59a0eb6f4854d92ed3c8e0477d5bdea88ac")
+                .unwrap(),
             sighash_flag: Sighash::SingleNoInput,
             prevout_value: 5004999,
         };
@@ -839,7 +846,8 @@ mod tests {
         let tx = HandshakeTx::deserialize_hex(hex).unwrap();
         let args = SighashArgs {
             index: 0,
-            prevout_script: Script::from("76c014a646dd759b001e4f1d443d698892dfe33e8a59bf88ac"),
+            prevout_script: Script::from_hex("76c014a646dd759b001e4f1d443d698892dfe33e8a59bf88ac")
+                .unwrap(),
             sighash_flag: Sighash::SingleReverseNoInput,
             prevout_value: 5004999,
         };
@@ -856,7 +864,8 @@ mod tests {
         let tx = HandshakeTx::deserialize_hex(hex).unwrap();
         let args = SighashArgs {
             index: 0,
-            prevout_script: Script::from("76c01431437ee12898865eeb61505a16516b78f821e19b88ac"),
+            prevout_script: Script::from_hex("76c01431437ee12898865eeb61505a16516b78f821e19b88ac")
+                .unwrap(),
             sighash_flag: Sighash::AllAcp,
             prevout_value: 10000000,
         };
@@ -873,7 +882,8 @@ mod tests {
         let tx = HandshakeTx::deserialize_hex(hex).unwrap();
         let args = SighashArgs {
             index: 0,
-            prevout_script: Script::from("76c014ca26dec0d3001bf16679c57af6c4fe0382b66dd188ac"),
+            prevout_script: Script::from_hex("76c014ca26dec0d3001bf16679c57af6c4fe0382b66dd188ac")
+                .unwrap(),
             sighash_flag: Sighash::NoneAcp,
             prevout_value: 68426400,
         };
@@ -890,7 +900,8 @@ mod tests {
         let tx = HandshakeTx::deserialize_hex(hex).unwrap();
         let args = SighashArgs {
             index: 0,
-            prevout_script: Script::from("76c0148c1b0a949896e1521d01e2386629047bd898dd8588ac"),
+            prevout_script: Script::from_hex("76c0148c1b0a949896e1521d01e2386629047bd898dd8588ac")
+                .unwrap(),
             sighash_flag: Sighash::SingleAcp,
             prevout_value: 5799600,
         };
@@ -907,7 +918,8 @@ mod tests {
         let tx = HandshakeTx::deserialize_hex(hex).unwrap();
         let args = SighashArgs {
             index: 0,
-            prevout_script: Script::from("76c014282fa7c6a30266295f9d050284af57578dac4f3388ac"),
+            prevout_script: Script::from_hex("76c014282fa7c6a30266295f9d050284af57578dac4f3388ac")
+                .unwrap(),
             sighash_flag: Sighash::SingleReverseAcp,
             prevout_value: 5358600,
         };
@@ -924,7 +936,8 @@ mod tests {
         let tx = HandshakeTx::deserialize_hex(hex).unwrap();
         let args = SighashArgs {
             index: 0,
-            prevout_script: Script::from("76c014aabc402d54f4b5455614ee56e0eb6c6b4e4374cd88ac"),
+            prevout_script: Script::from_hex("76c014aabc402d54f4b5455614ee56e0eb6c6b4e4374cd88ac")
+                .unwrap(),
             sighash_flag: Sighash::AllNoInputAcp,
             prevout_value: 410000,
         };
@@ -941,7 +954,8 @@ mod tests {
         let tx = HandshakeTx::deserialize_hex(hex).unwrap();
         let args = SighashArgs {
             index: 0,
-            prevout_script: Script::from("76c0144f9747117b3992c7dc0a09ae427c7b78efc731dc88ac"),
+            prevout_script: Script::from_hex("76c0144f9747117b3992c7dc0a09ae427c7b78efc731dc88ac")
+                .unwrap(),
             sighash_flag: Sighash::NoneNoInputAcp,
             prevout_value: 20004999,
         };
@@ -958,7 +972,8 @@ mod tests {
         let tx = HandshakeTx::deserialize_hex(hex).unwrap();
         let args = SighashArgs {
             index: 0,
-            prevout_script: Script::from("76c0143dd4ac1cb78168168c95bed0f615e00b24bd079088ac"),
+            prevout_script: Script::from_hex("76c0143dd4ac1cb78168168c95bed0f615e00b24bd079088ac")
+                .unwrap(),
             sighash_flag: Sighash::SingleNoInputAcp,
             prevout_value: 500000,
         };
@@ -975,7 +990,8 @@ mod tests {
         let tx = HandshakeTx::deserialize_hex(hex).unwrap();
         let args = SighashArgs {
             index: 0,
-            prevout_script: Script::from("76c014cb884746bae24846a6139160b2abc34d256c5e9788ac"),
+            prevout_script: Script::from_hex("76c014cb884746bae24846a6139160b2abc34d256c5e9788ac")
+                .unwrap(),
             sighash_flag: Sighash::SingleReverseNoInputAcp,
             prevout_value: 1250000000,
         };
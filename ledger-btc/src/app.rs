@@ -1,5 +1,9 @@
 use crate::{utils::*, LedgerBTCError};
-use bitcoins::{prelude::Transaction, types::{BitcoinTxIn, Utxo, WitnessTx}};
+use bitcoins::{
+    enc::AddressType,
+    prelude::Transaction,
+    types::{BitcoinTxIn, Utxo, WitnessTx},
+};
 use coins_bip32::{path::DerivationPath, prelude::*};
 use coins_ledger::{
     common::{APDUAnswer, APDUCommand},
@@ -47,6 +51,17 @@ impl LedgerBTC {
     pub fn close(self) {}
 }
 
+/// The device's address format for an address type. P2WSH addresses are not supported.
+fn address_format(address_type: AddressType) -> Result<u8, LedgerBTCError> {
+    match address_type {
+        AddressType::Pkh => Ok(0x00),
+        AddressType::Sh => Ok(0x01),
+        AddressType::Wpkh => Ok(0x02),
+        AddressType::Tr => Ok(0x03),
+        AddressType::Wsh => Err(LedgerBTCError::UnsupportedAddressType(address_type)),
+    }
+}
+
 // XPubs
 impl LedgerBTC {
     /// Get information about the public key at a certain derivation
@@ -54,6 +69,7 @@ impl LedgerBTC {
         &self,
         transport: &Ledger,
         deriv: &DerivationPath,
+        address_type: AddressType,
     ) -> Result<InternalKeyInfo, LedgerBTCError> {
         // Convert to APDU derivation format
         if deriv.len() > 10 {
@@ -64,7 +80,7 @@ impl LedgerBTC {
         let command = APDUCommand {
             ins: Commands::GetWalletPublicKey as u8,
             p1: 0x00,
-            p2: 0x02, // always native segwit address
+            p2: address_format(address_type)?,
             data,
             response_len: None,
         };
@@ -77,17 +93,40 @@ impl LedgerBTC {
         Ok(parse_pubkey_response(deriv, &data))
     }
 
-    /// Get an XPub with as much derivation info as possible.
+    /// Get an XPub with as much derivation info as possible. The XPub is hinted as native SegWit.
     pub async fn get_xpub(&self, deriv: &DerivationPath) -> Result<DerivedXPub, LedgerBTCError> {
+        self.get_typed_xpub(deriv, AddressType::Wpkh).await
+    }
+
+    /// Get the XPub of an account, at `m/purpose'/coin_type'/account'`. The purpose and hint are
+    /// selected by the address type.
+    pub async fn get_account_xpub(
+        &self,
+        address_type: AddressType,
+        coin_type: u32,
+        account: u32,
+    ) -> Result<DerivedXPub, LedgerBTCError> {
+        self.get_typed_xpub(&address_type.account_path(coin_type, account), address_type)
+            .await
+    }
+
+    /// Get an XPub with as much derivation info as possible, hinted for the address type.
+    pub async fn get_typed_xpub(
+        &self,
+        deriv: &DerivationPath,
+        address_type: AddressType,
+    ) -> Result<DerivedXPub, LedgerBTCError> {
         let transport = self.transport.lock().await;
 
-        let child = self.get_key_info(&transport, deriv).await?;
+        let child = self.get_key_info(&transport, deriv, address_type).await?;
 
         if !deriv.is_empty() {
             let parent = self
-                .get_key_info(&transport, &deriv.resized(deriv.len() - 1, 0))
+                .get_key_info(&transport, &deriv.resized(deriv.len() - 1, 0), address_type)
+                .await?;
+            let master = self
+                .get_key_info(&transport, &deriv.resized(0, 0), address_type)
                 .await?;
-            let master = self.get_key_info(&transport, &deriv.resized(0, 0)).await?;
             Ok(DerivedXPub::new(
                 XPub::new(
                     child.pubkey,
@@ -96,7 +135,7 @@ impl LedgerBTC {
                         parent: fingerprint_of(&parent.pubkey),
                         index: *deriv.last().unwrap(),
                         chain_code: child.chain_code,
-                        hint: Hint::SegWit,
+                        hint: address_type.hint(),
                     },
                 ),
                 KeyDerivation {
@@ -114,7 +153,7 @@ impl LedgerBTC {
                         parent: KeyFingerprint([0u8; 4]),
                         index: 0,
                         chain_code: child.chain_code,
-                        hint: Hint::SegWit,
+                        hint: address_type.hint(),
                     },
                 ),
                 KeyDerivation {
@@ -45,4 +45,8 @@ pub enum LedgerBTCError {
         "Received the wrong number of prevouts/key derivtions while signing. Need 1 per witness."
     )]
     SigningInfoLengthMismatch,
+
+    /// The device cannot produce keys for the address type
+    #[error("Address type {:?} is not supported by the device", .0)]
+    UnsupportedAddressType(bitcoins::enc::AddressType),
 }
@@ -1,12 +1,14 @@
 use std::convert::{TryFrom, TryInto};
 
+use serde::{Deserialize, Serialize};
+
 use crate::errors::LedgerError;
 
 const MAX_DATA_SIZE: usize = 255;
 
 /// APDU data blob, limited to 255 bytes. For simplicity, this data does not support 3-byte APDU
 /// prefixes.
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct APDUData(Vec<u8>);
 
 impl APDUData {
@@ -62,7 +64,7 @@ impl AsRef<[u8]> for APDUData {
 /// An APDU Command packet, used to issue instructions to the smart card.
 /// See [wikipedia](https://en.wikipedia.org/wiki/Smart_card_application_protocol_data_unit) for
 /// additional format details
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct APDUCommand {
     /// The instruction code.
     pub ins: u8,
@@ -111,7 +113,7 @@ impl APDUCommand {
 
 /// An APDU response is a wrapper around some response bytes. To avoid unnecessary clones, it
 /// exposes the retcode and response data as getters.
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct APDUAnswer {
     response: Vec<u8>,
 }
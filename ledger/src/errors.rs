@@ -1,6 +1,6 @@
 use thiserror::Error;
 
-use crate::common::APDUResponseCodes;
+use crate::common::{APDUCommand, APDUResponseCodes};
 
 /// APDU-related errors
 #[derive(Debug, Error)]
@@ -17,6 +17,23 @@ pub enum LedgerError {
     #[error("Ledger returned an unknown response status code {0:x}. This is a bug. Please file an issue at https://github.com/summa-tx/bitcoins-rs/issues")]
     UnknownAPDUCode(u16),
 
+    /// A replayed trace had no more exchanges
+    #[error("Replay trace exhausted. Unexpected command {0:?}")]
+    ReplayExhausted(APDUCommand),
+
+    /// A replayed trace expected a different command
+    #[error("Replay trace mismatch. Expected {expected:?}. Got {got:?}")]
+    ReplayMismatch {
+        /// The command recorded in the trace
+        expected: APDUCommand,
+        /// The command issued during replay
+        got: APDUCommand,
+    },
+
+    /// A replayed trace recorded a transport error
+    #[error("Replayed transport error: {0}")]
+    ReplayedError(String),
+
     /// JsValue Error
     #[error("JsValue Error: {0}")]
     #[cfg(target_arch = "wasm32")]
@@ -40,3 +57,47 @@ impl From<APDUResponseCodes> for LedgerError {
         LedgerError::BadRetcode(r)
     }
 }
+
+impl LedgerError {
+    /// A stable, machine-readable `ledger.*` code for the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            LedgerError::ResponseTooShort(_) => "ledger.response_too_short",
+            LedgerError::BadRetcode(_) => "ledger.bad_retcode",
+            LedgerError::UnknownAPDUCode(_) => "ledger.unknown_apdu_code",
+            LedgerError::ReplayExhausted(_) => "ledger.replay_exhausted",
+            LedgerError::ReplayMismatch { .. } => "ledger.replay_mismatch",
+            LedgerError::ReplayedError(_) => "ledger.replayed_error",
+            #[cfg(target_arch = "wasm32")]
+            LedgerError::JsError(_) => "ledger.js",
+            #[cfg(not(target_arch = "wasm32"))]
+            LedgerError::NativeTransportError(_) => "ledger.transport",
+        }
+    }
+
+    /// True if the exchange may succeed if retried, e.g. after the user unlocks or reconnects the
+    /// device.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            LedgerError::BadRetcode(code) => *code == APDUResponseCodes::UnlockDeviceError,
+            #[cfg(not(target_arch = "wasm32"))]
+            LedgerError::NativeTransportError(_) => true,
+            _ => false,
+        }
+    }
+
+    /// True if the device rejected the exchange because of the user or the caller, e.g. the
+    /// device is locked, the user declined on-device, or the request data was invalid.
+    pub fn is_user_error(&self) -> bool {
+        matches!(
+            self,
+            LedgerError::BadRetcode(
+                APDUResponseCodes::UnlockDeviceError
+                    | APDUResponseCodes::ConditionsNotSatisfied
+                    | APDUResponseCodes::DataInvalid
+                    | APDUResponseCodes::WrongLength
+                    | APDUResponseCodes::InvalidP1P2
+            )
+        )
+    }
+}
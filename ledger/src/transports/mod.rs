@@ -16,6 +16,9 @@ pub mod native;
 #[cfg(not(target_arch = "wasm32"))]
 pub use native::NativeTransport as DefaultTransport;
 
+/// APDU trace recording and replay
+pub mod trace;
+
 use crate::{
     common::{APDUAnswer, APDUCommand},
     errors::LedgerError,
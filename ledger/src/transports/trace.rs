@@ -0,0 +1,272 @@
+//! APDU trace recording and replay. `RecordingTransport` wraps any `LedgerAsync` transport and
+//! reports each exchange to an `APDUSink`. `ReplayTransport` plays a recorded trace back,
+//! without a device. Together they allow a device interaction captured in the field to be
+//! reproduced in a test.
+//!
+//! Exchanges may contain sensitive data (e.g. derivation paths or transaction details). A
+//! redaction hook can rewrite each exchange before it reaches the sink.
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{APDUAnswer, APDUCommand},
+    errors::LedgerError,
+    transports::LedgerAsync,
+};
+
+/// A single recorded APDU exchange. Transport errors are recorded by their description, as
+/// `LedgerError` is not `Clone`.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct APDUExchange {
+    /// The command sent to the device
+    pub command: APDUCommand,
+    /// The device's answer, or a description of the transport error
+    pub answer: Result<APDUAnswer, String>,
+}
+
+/// A hook that rewrites an exchange before it is recorded or replayed.
+pub type Redactor = Box<dyn Fn(&mut APDUExchange)>;
+
+/// A destination for recorded APDU exchanges.
+pub trait APDUSink {
+    /// Record an exchange. Called once per exchange, in order, after redaction.
+    fn record(&self, exchange: APDUExchange);
+}
+
+/// An in-memory `APDUSink`. Clones share the same underlying trace, so a handle may be kept
+/// while another is given to a `RecordingTransport`.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySink(Arc<Mutex<Vec<APDUExchange>>>);
+
+impl MemorySink {
+    /// Instantiate an empty sink
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return a copy of the exchanges recorded so far
+    pub fn exchanges(&self) -> Vec<APDUExchange> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl APDUSink for MemorySink {
+    fn record(&self, exchange: APDUExchange) {
+        self.0.lock().unwrap().push(exchange)
+    }
+}
+
+/// A transport wrapper that records every exchange with the inner transport to a sink.
+pub struct RecordingTransport<T: LedgerAsync, S: APDUSink> {
+    inner: T,
+    sink: S,
+    redactor: Option<Redactor>,
+}
+
+impl<T: LedgerAsync, S: APDUSink> RecordingTransport<T, S> {
+    /// Wrap a transport, recording its exchanges to the sink
+    pub fn new(inner: T, sink: S) -> Self {
+        Self {
+            inner,
+            sink,
+            redactor: None,
+        }
+    }
+
+    /// Set a hook to redact each exchange before it is recorded. This does not affect the
+    /// command sent to the device, or the answer returned to the caller.
+    pub fn with_redactor<F>(mut self, redactor: F) -> Self
+    where
+        F: Fn(&mut APDUExchange) + 'static,
+    {
+        self.redactor = Some(Box::new(redactor));
+        self
+    }
+
+    /// Return a reference to the sink
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    /// Consume the wrapper, returning the inner transport and the sink
+    pub fn into_parts(self) -> (T, S) {
+        (self.inner, self.sink)
+    }
+}
+
+#[async_trait(?Send)]
+impl<T, S> LedgerAsync for RecordingTransport<T, S>
+where
+    T: LedgerAsync,
+    S: APDUSink + Default,
+{
+    async fn init() -> Result<Self, LedgerError> {
+        Ok(Self::new(T::init().await?, S::default()))
+    }
+
+    async fn exchange(&self, packet: &APDUCommand) -> Result<APDUAnswer, LedgerError> {
+        let res = self.inner.exchange(packet).await;
+        let mut exchange = APDUExchange {
+            command: packet.clone(),
+            answer: match &res {
+                Ok(answer) => Ok(answer.clone()),
+                Err(e) => Err(e.to_string()),
+            },
+        };
+        if let Some(redactor) = &self.redactor {
+            redactor(&mut exchange);
+        }
+        self.sink.record(exchange);
+        res
+    }
+
+    fn close(self) {
+        self.inner.close()
+    }
+}
+
+/// A mock transport that replays a recorded trace. Each command must match the next command in
+/// the trace, and receives the recorded answer. Recorded transport errors are returned as
+/// `LedgerError::ReplayedError`.
+pub struct ReplayTransport {
+    trace: RefCell<VecDeque<APDUExchange>>,
+    redactor: Option<Redactor>,
+}
+
+impl ReplayTransport {
+    /// Instantiate a transport that will replay the trace
+    pub fn new(trace: Vec<APDUExchange>) -> Self {
+        Self {
+            trace: RefCell::new(trace.into()),
+            redactor: None,
+        }
+    }
+
+    /// Set a hook to redact incoming commands before they are compared to the trace. This should
+    /// be the redactor used when recording.
+    pub fn with_redactor<F>(mut self, redactor: F) -> Self
+    where
+        F: Fn(&mut APDUExchange) + 'static,
+    {
+        self.redactor = Some(Box::new(redactor));
+        self
+    }
+
+    /// Return the number of exchanges not yet replayed
+    pub fn remaining(&self) -> usize {
+        self.trace.borrow().len()
+    }
+}
+
+#[async_trait(?Send)]
+impl LedgerAsync for ReplayTransport {
+    async fn init() -> Result<Self, LedgerError> {
+        Ok(Self::new(vec![]))
+    }
+
+    async fn exchange(&self, packet: &APDUCommand) -> Result<APDUAnswer, LedgerError> {
+        let mut got = APDUExchange {
+            command: packet.clone(),
+            answer: Err(String::new()),
+        };
+        if let Some(redactor) = &self.redactor {
+            redactor(&mut got);
+        }
+
+        let expected = match self.trace.borrow_mut().pop_front() {
+            Some(exchange) => exchange,
+            None => return Err(LedgerError::ReplayExhausted(got.command)),
+        };
+        if expected.command != got.command {
+            return Err(LedgerError::ReplayMismatch {
+                expected: expected.command,
+                got: got.command,
+            });
+        }
+        expected.answer.map_err(LedgerError::ReplayedError)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn command(ins: u8, data: &[u8]) -> APDUCommand {
+        APDUCommand {
+            ins,
+            p1: 0x00,
+            p2: 0x00,
+            data: data.into(),
+            response_len: None,
+        }
+    }
+
+    #[test]
+    fn it_records_and_replays_traces() {
+        let answer = APDUAnswer::from_answer(vec![0xaa, 0x90, 0x00]).unwrap();
+        let source = ReplayTransport::new(vec![
+            APDUExchange {
+                command: command(0x01, &[0x01, 0x02]),
+                answer: Ok(answer.clone()),
+            },
+            APDUExchange {
+                command: command(0x02, &[]),
+                answer: Err("device unplugged".to_owned()),
+            },
+        ]);
+
+        let sink = MemorySink::new();
+        let redact = |exchange: &mut APDUExchange| {
+            if exchange.command.ins == 0x01 {
+                exchange.command.data = vec![0; exchange.command.data.len()].into();
+            }
+        };
+        let recorder = RecordingTransport::new(source, sink.clone()).with_redactor(redact);
+
+        futures::executor::block_on(async {
+            assert_eq!(
+                recorder
+                    .exchange(&command(0x01, &[0x01, 0x02]))
+                    .await
+                    .unwrap(),
+                answer
+            );
+            assert!(recorder.exchange(&command(0x02, &[])).await.is_err());
+        });
+
+        let trace = sink.exchanges();
+        assert_eq!(trace.len(), 2);
+        assert_eq!(trace[0].command.data.as_ref(), &[0, 0]);
+        assert_eq!(
+            trace[1].answer,
+            Err("Replayed transport error: device unplugged".to_owned())
+        );
+
+        let replay = ReplayTransport::new(trace).with_redactor(redact);
+        futures::executor::block_on(async {
+            assert_eq!(
+                replay
+                    .exchange(&command(0x01, &[0x03, 0x04]))
+                    .await
+                    .unwrap(),
+                answer
+            );
+            match replay.exchange(&command(0x03, &[])).await {
+                Err(LedgerError::ReplayMismatch { expected, .. }) => assert_eq!(expected.ins, 0x02),
+                other => panic!("unexpected result {:?}", other),
+            }
+            assert_eq!(replay.remaining(), 0);
+            match replay.exchange(&command(0x02, &[])).await {
+                Err(LedgerError::ReplayExhausted(_)) => {}
+                other => panic!("unexpected result {:?}", other),
+            }
+        });
+    }
+}
@@ -5,9 +5,28 @@ use bitcoins::{
     nets::Bitcoin,
 };
 
+/// Litecoin mainnet extended key version bytes. BIP32 keys use the `Ltub` and `Ltpv` prefixes,
+/// and BIP49 keys `Mtub` and `Mtpv`. BIP84 keys share Bitcoin's `zpub` and `zprv`.
+#[derive(Debug, Clone)]
+pub struct LtcXKeyParams;
+
+impl coins_bip32::enc::NetworkParams for LtcXKeyParams {
+    const NETWORK: &'static str = "litecoin";
+    const PRIV_VERSION: u32 = 0x019d_9cfe;
+    const BIP49_PRIV_VERSION: u32 = 0x01b2_6792;
+    const BIP84_PRIV_VERSION: u32 = 0x04b2_430c;
+    const PUB_VERSION: u32 = 0x019d_a462;
+    const BIP49_PUB_VERSION: u32 = 0x01b2_6ef6;
+    const BIP84_PUB_VERSION: u32 = 0x04b2_4746;
+}
+
+/// XKeyEncoder for Litecoin mainnet xkeys
+pub type LitecoinXKeyEncoder = coins_bip32::enc::BitcoinEncoder<LtcXKeyParams>;
+
 pub struct Ltc;
 
 impl NetworkParams for Ltc {
+    type XKeyEncoder = LitecoinXKeyEncoder;
     const HRP: &'static str = "ltc";
     const PKH_VERSION: u8 = 0x30;
     const SH_VERSION: u8 = 0x30;
@@ -16,6 +35,7 @@ impl NetworkParams for Ltc {
 pub struct LtcTest;
 
 impl NetworkParams for LtcTest {
+    type XKeyEncoder = coins_bip32::enc::TestnetEncoder;
     const HRP: &'static str = "tltc";
     const PKH_VERSION: u8 = 0x6f;
     const SH_VERSION: u8 = 0x3a;
@@ -26,3 +46,31 @@ pub type LitecoinTestEncoder = BitcoinEncoder<LtcTest>;
 
 pub type LitecoinMainnet = Bitcoin<LitecoinMainEncoder>;
 pub type LitecoinTestnet = Bitcoin<LitecoinTestEncoder>;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use coins_bip32::{enc::XKeyEncoder, primitives::Hint, xkeys::XPriv};
+
+    #[test]
+    fn it_encodes_litecoin_xkeys() {
+        let xpriv = XPriv::root_from_seed(&[7u8; 32], Some(Hint::Legacy)).unwrap();
+        let ltpv = LitecoinXKeyEncoder::xpriv_to_base58(&xpriv).unwrap();
+        let ltub = LitecoinXKeyEncoder::xpub_to_base58(&xpriv.verify_key()).unwrap();
+        assert!(ltpv.starts_with("Ltpv"));
+        assert!(ltub.starts_with("Ltub"));
+        assert_eq!(
+            LitecoinXKeyEncoder::xpriv_from_base58(&ltpv).unwrap(),
+            xpriv
+        );
+
+        let mtub = LitecoinXKeyEncoder::xpub_to_base58(
+            &XPriv::root_from_seed(&[7u8; 32], Some(Hint::Compatibility))
+                .unwrap()
+                .verify_key(),
+        )
+        .unwrap();
+        assert!(mtub.starts_with("Mtub"));
+        assert!(coins_bip32::enc::MainnetEncoder::xpub_from_base58(&ltub).is_err());
+    }
+}
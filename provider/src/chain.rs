@@ -1,35 +1,44 @@
 use std::{
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
     time::Duration,
 };
 
-use futures_core::stream::Stream;
 use futures_util::stream::StreamExt;
 use pin_project::pin_project;
 
-use bitcoins::prelude::*;
-
-use crate::{provider::BtcProvider, utils::new_interval, ProviderFut, DEFAULT_POLL_INTERVAL};
+use crate::{
+    clock::{Clock, SystemClock},
+    provider::{BtcProvider, ChainProvider},
+    utils::{new_interval, Interval},
+    ProviderFut, DEFAULT_POLL_INTERVAL,
+};
 
 /// Polls the API for the chain tip. Updates every time the tip changes
+///
+/// The stream is generic over the provider's chain. By default it uses a `dyn BtcProvider`.
 #[pin_project(project = TipsProj)]
 #[must_use = "streams do nothing unless polled"]
-pub struct Tips<'a> {
+pub struct Tips<'a, P: ChainProvider + ?Sized = dyn BtcProvider + 'a> {
     limit: usize,
-    interval: Box<dyn Stream<Item = ()> + Send + Unpin>,
-    provider: &'a dyn BtcProvider,
-    fut_opt: Option<ProviderFut<'a, BlockHash>>,
-    last: Option<BlockHash>,
+    interval: Interval,
+    duration: Duration,
+    clock: Arc<dyn Clock>,
+    provider: &'a P,
+    fut_opt: Option<ProviderFut<'a, P::BlockHash>>,
+    last: Option<P::BlockHash>,
 }
 
-impl<'a> Tips<'a> {
+impl<'a, P: ChainProvider + ?Sized> Tips<'a, P> {
     /// Instantiate a new Tips. Return at most `limit` new chaintips.
-    pub fn new(limit: usize, provider: &'a dyn BtcProvider) -> Self {
+    pub fn new(limit: usize, provider: &'a P) -> Self {
         let fut = Box::pin(provider.tip_hash());
         Self {
             limit,
-            interval: Box::new(new_interval(DEFAULT_POLL_INTERVAL)),
+            interval: new_interval(Arc::new(SystemClock), DEFAULT_POLL_INTERVAL),
+            duration: DEFAULT_POLL_INTERVAL,
+            clock: Arc::new(SystemClock),
             provider,
             fut_opt: Some(fut),
             last: None,
@@ -38,13 +47,21 @@ impl<'a> Tips<'a> {
 
     /// Sets the polling interval
     pub fn interval<T: Into<Duration>>(mut self, duration: T) -> Self {
-        self.interval = Box::new(new_interval(duration.into()));
+        self.duration = duration.into();
+        self.interval = new_interval(self.clock.clone(), self.duration);
+        self
+    }
+
+    /// Sets the clock used to pause between polls
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.interval = new_interval(clock.clone(), self.duration);
+        self.clock = clock;
         self
     }
 }
 
-impl<'a> futures_core::Stream for Tips<'a> {
-    type Item = BlockHash;
+impl<'a, P: ChainProvider + ?Sized> futures_core::Stream for Tips<'a, P> {
+    type Item = P::BlockHash;
 
     fn poll_next(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
         let TipsProj {
@@ -53,6 +70,7 @@ impl<'a> futures_core::Stream for Tips<'a> {
             provider,
             fut_opt,
             last,
+            ..
         } = self.project();
 
         // if our limit has run down, end the stream
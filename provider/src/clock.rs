@@ -0,0 +1,182 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use futures_timer::Delay;
+
+/// A future that resolves when a sleep has elapsed
+#[cfg(target_arch = "wasm32")]
+pub type Sleep = Pin<Box<dyn Future<Output = ()>>>;
+
+/// A future that resolves when a sleep has elapsed
+#[cfg(not(target_arch = "wasm32"))]
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// A source of time for the polling streams. Streams sleep on their clock between polls.
+///
+/// `SystemClock` is used by default. Tests may use a `ManualClock` to advance time instantly, and
+/// other environments may supply their own timer.
+pub trait Clock: Send + Sync {
+    /// Return a future that resolves after `duration` has elapsed
+    fn sleep(&self, duration: Duration) -> Sleep;
+}
+
+/// A clock backed by `futures-timer`, which runs a global timer thread.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn sleep(&self, duration: Duration) -> Sleep {
+        Box::pin(Delay::new(duration))
+    }
+}
+
+#[derive(Debug, Default)]
+struct ManualClockState {
+    elapsed: Duration,
+    next_id: usize,
+    /// The deadline and latest waker of each pending sleep, by id
+    sleepers: HashMap<usize, (Duration, Waker)>,
+}
+
+/// A clock that only advances when told to. Sleeps resolve once the clock has been advanced past
+/// their deadline. Clones share the same time.
+#[derive(Debug, Default, Clone)]
+pub struct ManualClock {
+    state: Arc<Mutex<ManualClockState>>,
+}
+
+impl ManualClock {
+    /// Instantiate a new clock at time 0
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Return the time elapsed since the clock was instantiated
+    pub fn elapsed(&self) -> Duration {
+        self.state.lock().unwrap().elapsed
+    }
+
+    /// Advance the clock, waking any sleeps that have elapsed
+    pub fn advance(&self, duration: Duration) {
+        let woken: Vec<Waker> = {
+            let mut state = self.state.lock().unwrap();
+            state.elapsed += duration;
+            let elapsed = state.elapsed;
+            let (woken, sleeping) = state
+                .sleepers
+                .drain()
+                .partition(|(_, (deadline, _))| *deadline <= elapsed);
+            state.sleepers = sleeping;
+            woken.into_values().map(|(_, waker)| waker).collect()
+        };
+        woken.into_iter().for_each(Waker::wake);
+    }
+}
+
+impl Clock for ManualClock {
+    fn sleep(&self, duration: Duration) -> Sleep {
+        let (id, deadline) = {
+            let mut state = self.state.lock().unwrap();
+            state.next_id += 1;
+            (state.next_id, state.elapsed + duration)
+        };
+        Box::pin(ManualSleep {
+            id,
+            deadline,
+            clock: self.clone(),
+        })
+    }
+}
+
+/// A sleep on a `ManualClock`
+struct ManualSleep {
+    id: usize,
+    deadline: Duration,
+    clock: ManualClock,
+}
+
+impl Future for ManualSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.clock.state.lock().unwrap();
+        if state.elapsed >= self.deadline {
+            return Poll::Ready(());
+        }
+        // Keep only the most recent waker, so that repeated polls do not accumulate
+        match state.sleepers.get_mut(&self.id) {
+            Some((_, waker)) if waker.will_wake(ctx.waker()) => {}
+            Some((_, waker)) => *waker = ctx.waker().clone(),
+            None => {
+                state
+                    .sleepers
+                    .insert(self.id, (self.deadline, ctx.waker().clone()));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for ManualSleep {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.clock.state.lock() {
+            state.sleepers.remove(&self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::new_interval;
+    use futures_util::{stream::StreamExt, task::noop_waker};
+
+    #[test]
+    fn it_advances_manual_clocks() {
+        let clock = ManualClock::new();
+        let waker = noop_waker();
+        let mut ctx = Context::from_waker(&waker);
+
+        let mut interval = new_interval(Arc::new(clock.clone()), Duration::from_secs(10));
+        assert_eq!(interval.poll_next_unpin(&mut ctx), Poll::Pending);
+        clock.advance(Duration::from_secs(9));
+        assert_eq!(interval.poll_next_unpin(&mut ctx), Poll::Pending);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(interval.poll_next_unpin(&mut ctx), Poll::Ready(Some(())));
+
+        // The next sleep starts when the interval is polled again
+        assert_eq!(interval.poll_next_unpin(&mut ctx), Poll::Pending);
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(interval.poll_next_unpin(&mut ctx), Poll::Ready(Some(())));
+        assert_eq!(clock.elapsed(), Duration::from_secs(20));
+    }
+
+    #[test]
+    fn it_stores_one_waker_per_sleep() {
+        let clock = ManualClock::new();
+        let waker = noop_waker();
+        let mut ctx = Context::from_waker(&waker);
+
+        let mut sleep = clock.sleep(Duration::from_secs(10));
+        for _ in 0..5 {
+            assert_eq!(sleep.as_mut().poll(&mut ctx), Poll::Pending);
+        }
+        assert_eq!(clock.state.lock().unwrap().sleepers.len(), 1);
+
+        // Dropped sleeps are forgotten
+        drop(sleep);
+        assert!(clock.state.lock().unwrap().sleepers.is_empty());
+
+        let mut sleep = clock.sleep(Duration::from_secs(10));
+        assert_eq!(sleep.as_mut().poll(&mut ctx), Poll::Pending);
+        clock.advance(Duration::from_secs(10));
+        assert!(clock.state.lock().unwrap().sleepers.is_empty());
+        assert_eq!(sleep.as_mut().poll(&mut ctx), Poll::Ready(()));
+    }
+}
@@ -11,7 +11,7 @@ use bitcoins::prelude::*;
 use coins_core::hashes::MarkedDigestOutput;
 
 use crate::{
-    provider::{BtcProvider, PollingBtcProvider, ProviderError},
+    provider::{BtcProvider, ChainProvider, PollingBtcProvider, ProviderError},
     types::RawHeader,
 };
 
@@ -48,13 +48,72 @@ impl EsploraProvider {
 
 #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
-impl BtcProvider for EsploraProvider {
+impl ChainProvider for EsploraProvider {
+    type Tx = BitcoinTx;
+    type TXID = TXID;
+    type TxoIdentifier = BitcoinOutpoint;
+    type BlockHash = BlockHash;
+
     async fn tip_hash(&self) -> Result<BlockHash, ProviderError> {
         let url = format!("{}/blocks/tip/hash", self.api_root);
         let response = ez_fetch_string(&self.client, &url).await?;
         Ok(BlockHash::from_be_hex(&response)?)
     }
 
+    async fn get_confs(&self, txid: TXID) -> Result<Option<usize>, ProviderError> {
+        let tx =
+            esplora_if_found!(EsploraTx::fetch_by_txid(&self.client, &self.api_root, txid).await);
+
+        if !tx.status.confirmed {
+            return Ok(Some(0));
+        }
+        let digest =
+            BlockHash::from_be_hex(&tx.status.block_hash).expect("No bad hex in API response");
+        if !self.in_best_chain(digest).await? {
+            return Ok(Some(0));
+        }
+        let height = self.tip_height().await?;
+        Ok(Some(height - tx.status.block_height + 1))
+    }
+
+    async fn get_tx(&self, txid: TXID) -> Result<Option<BitcoinTx>, ProviderError> {
+        let tx_hex = fetch_tx_hex_by_id(&self.client, &self.api_root, txid).await?;
+        if let Ok(tx) = BitcoinTx::deserialize_hex(&tx_hex) {
+            Ok(Some(tx))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn broadcast(&self, tx: BitcoinTx) -> Result<TXID, ProviderError> {
+        let url = format!("{}/tx", self.api_root);
+        let mut buf = vec![];
+        tx.write_to(&mut buf).unwrap();
+        let response = post_bytes_as_hex(&self.client, &url, &buf).await?;
+        Ok(TXID::deserialize_hex(&response)?)
+    }
+
+    async fn get_outspend(&self, outpoint: BitcoinOutpoint) -> Result<Option<TXID>, ProviderError> {
+        let outspend_opt =
+            Outspend::fetch_by_outpoint(&self.client, &self.api_root, &outpoint).await?;
+
+        match outspend_opt {
+            Some(outspend) => {
+                if outspend.spent {
+                    let txid = TXID::from_be_hex(&outspend.txid_be)?;
+                    Ok(Some(txid))
+                } else {
+                    Ok(None)
+                }
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl BtcProvider for EsploraProvider {
     async fn tip_height(&self) -> Result<usize, ProviderError> {
         let url = format!("{}/blocks/tip/height", self.api_root);
         let response = ez_fetch_string(&self.client, &url).await?;
@@ -125,54 +184,11 @@ impl BtcProvider for EsploraProvider {
         Ok(Some(tx.block_height))
     }
 
-    async fn get_confs(&self, txid: TXID) -> Result<Option<usize>, ProviderError> {
-        let tx =
-            esplora_if_found!(EsploraTx::fetch_by_txid(&self.client, &self.api_root, txid).await);
-
-        if !tx.status.confirmed {
-            return Ok(Some(0));
-        }
-        let digest =
-            BlockHash::from_be_hex(&tx.status.block_hash).expect("No bad hex in API response");
-        if !self.in_best_chain(digest).await? {
-            return Ok(Some(0));
-        }
-        let height = self.tip_height().await?;
-        Ok(Some(height - tx.status.block_height + 1))
-    }
-
-    async fn get_tx(&self, txid: TXID) -> Result<Option<BitcoinTx>, ProviderError> {
-        let tx_hex = fetch_tx_hex_by_id(&self.client, &self.api_root, txid).await?;
-        if let Ok(tx) = BitcoinTx::deserialize_hex(&tx_hex) {
-            Ok(Some(tx))
-        } else {
-            Ok(None)
-        }
-    }
-
-    async fn broadcast(&self, tx: BitcoinTx) -> Result<TXID, ProviderError> {
-        let url = format!("{}/tx", self.api_root);
-        let mut buf = vec![];
-        tx.write_to(&mut buf).unwrap();
-        let response = post_bytes_as_hex(&self.client, &url, &buf).await?;
-        Ok(TXID::deserialize_hex(&response)?)
-    }
-
-    async fn get_outspend(&self, outpoint: BitcoinOutpoint) -> Result<Option<TXID>, ProviderError> {
-        let outspend_opt =
-            Outspend::fetch_by_outpoint(&self.client, &self.api_root, &outpoint).await?;
-
-        match outspend_opt {
-            Some(outspend) => {
-                if outspend.spent {
-                    let txid = Default::default();
-                    Ok(Some(txid))
-                } else {
-                    Ok(None)
-                }
-            }
-            None => Ok(None),
-        }
+    async fn estimate_feerate(&self, target: usize) -> Result<Option<FeeRate>, ProviderError> {
+        Ok(FeeEstimates::fetch(&self.client, &self.api_root)
+            .await?
+            .for_target(target)
+            .map(FeeRate::from_sat_per_vb_f64))
     }
 
     async fn get_utxos_by_address(&self, address: &Address) -> Result<Vec<Utxo>, ProviderError> {
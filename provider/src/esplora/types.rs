@@ -21,6 +21,38 @@ impl MerkleProof {
     }
 }
 
+/// A map from confirmation target (in blocks) to feerate (in sat/vbyte)
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(transparent)]
+pub(crate) struct FeeEstimates(pub std::collections::HashMap<String, f64>);
+
+impl FeeEstimates {
+    pub(crate) async fn fetch(
+        client: &reqwest::Client,
+        api_root: &str,
+    ) -> Result<Self, FetchError> {
+        let url = format!("{}/fee-estimates", api_root);
+        reqwest_utils::ez_fetch_json(client, &url).await
+    }
+
+    /// Esplora estimates a fixed set of targets. Use the estimate for the longest target not
+    /// exceeding `target`, or for the shortest target if all exceed it.
+    pub(crate) fn for_target(&self, target: usize) -> Option<f64> {
+        let mut estimates: Vec<(usize, f64)> = self
+            .0
+            .iter()
+            .filter_map(|(k, v)| Some((k.parse().ok()?, *v)))
+            .collect();
+        estimates.sort_by_key(|(t, _)| *t);
+        estimates
+            .iter()
+            .rev()
+            .find(|(t, _)| *t <= target)
+            .or_else(|| estimates.first())
+            .map(|(_, v)| *v)
+    }
+}
+
 #[derive(serde::Deserialize, Clone, Debug)]
 pub(crate) struct BlockStatus {
     pub in_best_chain: bool,
@@ -84,6 +116,9 @@ pub(crate) struct EsploraUtxo {
     pub vout: usize,
     /// UTXO value
     pub value: usize,
+    /// Confirmation status
+    #[serde(default)]
+    pub status: Option<EsploraTxStatus>,
 }
 
 impl EsploraUtxo {
@@ -103,12 +138,14 @@ impl EsploraUtxo {
             self.vout as u32,
         );
         let spend_script = SpendScript::from_script_pubkey(&script_pubkey);
-        Ok(Utxo::new(
-            outpoint,
-            self.value as u64,
-            script_pubkey,
-            spend_script,
-        ))
+        let utxo = Utxo::new(outpoint, self.value as u64, script_pubkey, spend_script);
+        match self.status {
+            Some(status) if status.confirmed => Ok(utxo.confirmed_in(
+                status.block_height as u32,
+                BlockHash::from_be_hex(&status.block_hash)?,
+            )),
+            _ => Ok(utxo),
+        }
     }
 }
 
@@ -117,7 +154,7 @@ pub(crate) struct Outspend {
     /// Whether the output has been spent
     pub spent: bool,
     /// The TXID that spend it
-    #[serde(default = "String::new")]
+    #[serde(default = "String::new", rename = "txid")]
     pub txid_be: String,
     /// The index of the spending input in that transaction's Vin
     #[serde(default = "usize::max_value")]
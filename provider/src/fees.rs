@@ -0,0 +1,123 @@
+use bitcoins::types::FeeRate;
+
+use crate::rbf::TxFeeInfo;
+
+/// The default confirmation target, in blocks, of the estimate used by fee checks
+pub const DEFAULT_FEE_CHECK_TARGET: usize = 6;
+
+/// The default multiple of the estimated feerate above which a feerate is considered excessive
+pub const DEFAULT_MAX_FEERATE_MULTIPLE: f64 = 10.0;
+
+/// Configuration for a pre-broadcast feerate sanity check. A transaction fails the check when its
+/// feerate exceeds `max_multiple` times the provider's estimate for `target` blocks. This guards
+/// automated systems against fat-fingered fee parameters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FeeCheck {
+    target: usize,
+    max_multiple: f64,
+}
+
+impl Default for FeeCheck {
+    fn default() -> Self {
+        Self {
+            target: DEFAULT_FEE_CHECK_TARGET,
+            max_multiple: DEFAULT_MAX_FEERATE_MULTIPLE,
+        }
+    }
+}
+
+impl FeeCheck {
+    /// Set the confirmation target, in blocks, of the estimate to compare against
+    pub fn target(mut self, target: usize) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Set the multiple of the estimate above which a feerate is considered excessive
+    pub fn max_multiple(mut self, max_multiple: f64) -> Self {
+        self.max_multiple = max_multiple;
+        self
+    }
+
+    /// Return the confirmation target
+    pub fn get_target(&self) -> usize {
+        self.target
+    }
+
+    /// Return the maximum multiple
+    pub fn get_max_multiple(&self) -> f64 {
+        self.max_multiple
+    }
+}
+
+/// The result of a feerate sanity check.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeeCheckReport {
+    /// Fee information about the checked transaction
+    pub info: TxFeeInfo,
+    /// The provider's feerate estimate, if one is available
+    pub estimate: Option<FeeRate>,
+    /// The multiple of the estimate above which the feerate is considered excessive
+    pub max_multiple: f64,
+}
+
+impl FeeCheckReport {
+    /// The feerate of the transaction, rounded down, if the fee is known
+    pub fn feerate(&self) -> Option<FeeRate> {
+        self.info.feerate()
+    }
+
+    /// The highest acceptable feerate, if an estimate is available. Rounded to the nearest
+    /// sat/kvbyte
+    pub fn limit(&self) -> Option<FeeRate> {
+        self.estimate.map(|estimate| {
+            let limit = estimate.as_sat_per_kvb() as f64 * self.max_multiple;
+            FeeRate::from_sat_per_kvb(limit.round() as u64)
+        })
+    }
+
+    /// True if the transaction is known to pay more than the limit. False if either the fee or
+    /// the estimate is unknown.
+    pub fn is_excessive(&self) -> bool {
+        match (self.info.fee, self.limit()) {
+            // Cross-multiply to avoid rounding the feerate
+            (Some(fee), Some(limit)) => {
+                fee as u128 * 1000 > limit.as_sat_per_kvb() as u128 * self.info.vsize as u128
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoins::hashes::TXID;
+
+    #[test]
+    fn it_flags_excessive_feerates() {
+        let report = |fee, estimate| FeeCheckReport {
+            info: TxFeeInfo {
+                txid: TXID::default(),
+                fee,
+                vsize: 100,
+            },
+            estimate,
+            max_multiple: FeeCheck::default().get_max_multiple(),
+        };
+
+        let estimate = Some(FeeRate::from_sat_per_vb(2));
+        assert_eq!(
+            report(Some(1000), estimate).limit(),
+            Some(FeeRate::from_sat_per_vb(20))
+        );
+        assert_eq!(
+            report(Some(2001), estimate).feerate(),
+            Some(FeeRate::from_sat_per_kvb(20_010))
+        );
+        assert!(!report(Some(2000), estimate).is_excessive());
+        assert!(report(Some(2001), estimate).is_excessive());
+        assert!(!report(None, estimate).is_excessive());
+        assert!(!report(Some(1_000_000), None).is_excessive());
+    }
+}
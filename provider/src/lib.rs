@@ -20,6 +20,18 @@ pub mod watcher;
 /// Chain watcher
 pub mod chain;
 
+/// Time sources for polling streams
+pub mod clock;
+
+/// Replace-by-fee conflict inspection
+pub mod rbf;
+
+/// Feerate sanity checks
+pub mod fees;
+
+/// Package feerates and mempool limits, for CPFP
+pub mod package;
+
 #[doc(hidden)]
 #[cfg(any(feature = "rpc", feature = "esplora"))]
 pub mod reqwest_utils;
@@ -35,6 +47,10 @@ pub mod esplora;
 #[cfg(feature = "rpc")]
 pub mod rpc;
 
+/// Regtest bitcoind harness for integration tests
+#[cfg(all(feature = "test-node", not(target_arch = "wasm32")))]
+pub mod test_node;
+
 /// Common usage
 pub mod prelude;
 
@@ -47,6 +63,12 @@ pub const DEFAULT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from
 /// The default number of cache items to keep in a caching provider
 pub const DEFAULT_CACHE_SIZE: usize = 300;
 
+/// The default number of confirmations after which a caching provider treats a tx as immutable
+pub const DEFAULT_FINAL_CONFIRMATIONS: usize = 6;
+
+/// The default lifetime of volatile data in a caching provider, set to 30 seconds
+pub const DEFAULT_VOLATILE_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+
 // Alias the default encoder
 type Encoder = bitcoins::Encoder;
 
@@ -0,0 +1,459 @@
+use std::collections::{HashMap, HashSet};
+
+use bitcoins::{hashes::TXID, types::*};
+use coins_core::prelude::*;
+
+/// The default maximum number of in-mempool ancestors of a transaction, including itself
+pub const DEFAULT_ANCESTOR_LIMIT: usize = 25;
+
+/// The default maximum total vsize of a transaction and its in-mempool ancestors
+pub const DEFAULT_ANCESTOR_SIZE_LIMIT: usize = 101_000;
+
+/// The default maximum number of in-mempool descendants of a transaction, including itself
+pub const DEFAULT_DESCENDANT_LIMIT: usize = 25;
+
+/// The default maximum total vsize of a transaction and its in-mempool descendants
+pub const DEFAULT_DESCENDANT_SIZE_LIMIT: usize = 101_000;
+
+/// A mempool transaction, and the totals of its in-mempool ancestors and descendants. As in
+/// Bitcoin Core's `getmempoolentry`, the ancestor and descendant totals include the transaction
+/// itself.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MempoolEntry {
+    /// The fee paid by the transaction
+    pub fee: u64,
+    /// The virtual size of the transaction, in vbytes
+    pub vsize: usize,
+    /// The number of in-mempool ancestors
+    pub ancestor_count: usize,
+    /// The total vsize of in-mempool ancestors
+    pub ancestor_vsize: usize,
+    /// The total fees of in-mempool ancestors
+    pub ancestor_fees: u64,
+    /// The number of in-mempool descendants
+    pub descendant_count: usize,
+    /// The total vsize of in-mempool descendants
+    pub descendant_vsize: usize,
+}
+
+/// A transaction in a package, identified by its TXID, with its fee and weight, and the TXIDs of
+/// the transactions whose outputs it spends.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackageTx {
+    /// The TXID of the transaction
+    pub txid: TXID,
+    /// The fee paid by the transaction
+    pub fee: u64,
+    /// The weight of the transaction
+    pub weight: usize,
+    /// The TXIDs of the transactions it spends from
+    pub parents: Vec<TXID>,
+}
+
+impl PackageTx {
+    /// Describe a transaction paying `fee`
+    pub fn new(tx: &BitcoinTx, fee: u64) -> Self {
+        let mut parents = vec![];
+        for input in tx.inputs() {
+            if !parents.contains(&input.outpoint.txid) {
+                parents.push(input.outpoint.txid);
+            }
+        }
+        Self {
+            txid: tx.txid(),
+            fee,
+            weight: tx.weight(),
+            parents,
+        }
+    }
+
+    /// The virtual size of the transaction, in vbytes
+    pub fn vsize(&self) -> usize {
+        self.weight.div_ceil(4)
+    }
+}
+
+/// Mempool ancestor and descendant limits. The default matches Bitcoin Core's default policy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PackageLimits {
+    /// The maximum number of ancestors of a transaction, including itself
+    pub ancestor_count: usize,
+    /// The maximum total vsize of a transaction and its ancestors
+    pub ancestor_vsize: usize,
+    /// The maximum number of descendants of a transaction, including itself
+    pub descendant_count: usize,
+    /// The maximum total vsize of a transaction and its descendants
+    pub descendant_vsize: usize,
+}
+
+impl Default for PackageLimits {
+    fn default() -> Self {
+        Self {
+            ancestor_count: DEFAULT_ANCESTOR_LIMIT,
+            ancestor_vsize: DEFAULT_ANCESTOR_SIZE_LIMIT,
+            descendant_count: DEFAULT_DESCENDANT_LIMIT,
+            descendant_vsize: DEFAULT_DESCENDANT_SIZE_LIMIT,
+        }
+    }
+}
+
+/// A mempool limit exceeded by a package.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PackageLimitViolation {
+    /// The package transaction has too many ancestors
+    AncestorCount {
+        /// The package transaction
+        txid: TXID,
+        /// The number of ancestors, including itself
+        count: usize,
+    },
+    /// The package transaction's ancestors are too large
+    AncestorSize {
+        /// The package transaction
+        txid: TXID,
+        /// The vsize of its ancestors, including itself
+        vsize: usize,
+    },
+    /// The mempool transaction would have too many descendants
+    DescendantCount {
+        /// The mempool transaction
+        txid: TXID,
+        /// The number of descendants, including itself
+        count: usize,
+    },
+    /// The mempool transaction's descendants would be too large
+    DescendantSize {
+        /// The mempool transaction
+        txid: TXID,
+        /// The vsize of its descendants, including itself
+        vsize: usize,
+    },
+}
+
+/// Totals over a set of transactions
+#[derive(Clone, Copy, Debug, Default)]
+struct Totals {
+    count: usize,
+    fee: u64,
+    vsize: usize,
+}
+
+impl Totals {
+    /// The feerate, rounded down
+    fn feerate(&self) -> FeeRate {
+        FeeRate::from_fee_and_vsize(self.fee, self.vsize).unwrap_or(FeeRate::ZERO)
+    }
+}
+
+/// A set of related unconfirmed transactions, such as a parent and a child paying for it (CPFP),
+/// and the mempool entries of the unconfirmed transactions they spend from. Feerates follow the
+/// mempool's ancestor-package math.
+///
+/// Mempool ancestors shared by two mempool parents are counted once per parent, so ancestor
+/// totals are an upper bound.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Package {
+    txs: Vec<PackageTx>,
+    mempool_parents: HashMap<TXID, MempoolEntry>,
+}
+
+impl Package {
+    /// Instantiate a package from its transactions. Transactions spent by the package that are
+    /// not in it are assumed to be confirmed, unless added with `mempool_parents`.
+    pub fn new(txs: Vec<PackageTx>) -> Self {
+        Self {
+            txs,
+            mempool_parents: HashMap::new(),
+        }
+    }
+
+    /// Set the mempool entries of unconfirmed transactions spent by the package
+    pub fn mempool_parents(mut self, entries: HashMap<TXID, MempoolEntry>) -> Self {
+        self.mempool_parents = entries;
+        self
+    }
+
+    /// Return the package transactions
+    pub fn txs(&self) -> &[PackageTx] {
+        &self.txs
+    }
+
+    /// The TXIDs spent by the package that are not in it
+    pub fn external_parents(&self) -> Vec<TXID> {
+        let mut parents = vec![];
+        for parent in self.txs.iter().flat_map(|tx| tx.parents.iter()) {
+            if self.get(parent).is_none() && !parents.contains(parent) {
+                parents.push(*parent);
+            }
+        }
+        parents
+    }
+
+    fn get(&self, txid: &TXID) -> Option<&PackageTx> {
+        self.txs.iter().find(|tx| &tx.txid == txid)
+    }
+
+    /// The total fee paid by the package
+    pub fn fee(&self) -> u64 {
+        self.txs.iter().map(|tx| tx.fee).sum()
+    }
+
+    /// The total vsize of the package
+    pub fn vsize(&self) -> usize {
+        self.txs.iter().map(PackageTx::vsize).sum()
+    }
+
+    /// The feerate of the package as a whole, rounded down. Excludes mempool parents
+    pub fn feerate(&self) -> FeeRate {
+        FeeRate::from_fee_and_vsize(self.fee(), self.vsize()).unwrap_or(FeeRate::ZERO)
+    }
+
+    /// The package transaction and its in-package ancestors, and the mempool parents they spend
+    /// from
+    fn ancestor_set(&self, txid: &TXID) -> (Vec<&PackageTx>, HashSet<TXID>) {
+        let mut ancestors: Vec<&PackageTx> = vec![];
+        let mut mempool = HashSet::new();
+        let mut queue = vec![*txid];
+        while let Some(next) = queue.pop() {
+            let tx = match self.get(&next) {
+                Some(tx) if !ancestors.iter().any(|a| a.txid == next) => tx,
+                _ => continue,
+            };
+            ancestors.push(tx);
+            for parent in tx.parents.iter() {
+                if self.mempool_parents.contains_key(parent) {
+                    mempool.insert(*parent);
+                }
+                queue.push(*parent);
+            }
+        }
+        (ancestors, mempool)
+    }
+
+    fn ancestor_totals(&self, txid: &TXID) -> Option<Totals> {
+        self.get(txid)?;
+        let (ancestors, mempool) = self.ancestor_set(txid);
+        let mut totals = Totals::default();
+        for tx in ancestors {
+            totals.count += 1;
+            totals.fee += tx.fee;
+            totals.vsize += tx.vsize();
+        }
+        for entry in mempool.iter().map(|txid| &self.mempool_parents[txid]) {
+            totals.count += entry.ancestor_count;
+            totals.fee += entry.ancestor_fees;
+            totals.vsize += entry.ancestor_vsize;
+        }
+        Some(totals)
+    }
+
+    /// The feerate of a package transaction together with all its unconfirmed ancestors,
+    /// rounded down. `None` if the transaction is not in the package.
+    pub fn ancestor_feerate(&self, txid: &TXID) -> Option<FeeRate> {
+        self.ancestor_totals(txid).map(|t| t.feerate())
+    }
+
+    /// The feerate at which a package transaction is expected to be mined. This is the highest
+    /// ancestor feerate of the transaction and its in-package descendants, as a high-fee child
+    /// pulls its ancestors into a block with it. `None` if the transaction is not in the package.
+    pub fn effective_feerate(&self, txid: &TXID) -> Option<FeeRate> {
+        let own = self.ancestor_feerate(txid)?;
+        let best = self
+            .txs
+            .iter()
+            .filter(|tx| {
+                tx.txid != *txid
+                    && self
+                        .ancestor_set(&tx.txid)
+                        .0
+                        .iter()
+                        .any(|a| a.txid == *txid)
+            })
+            .filter_map(|tx| self.ancestor_feerate(&tx.txid))
+            .fold(own, FeeRate::max);
+        Some(best)
+    }
+
+    /// The fee a new child must pay to bring the package, and the mempool parents it spends
+    /// from, to `feerate`. The child spends from every package transaction, and its
+    /// vsize is `child_vsize`. Returns 0 if the package already pays enough.
+    pub fn cpfp_fee(&self, feerate: FeeRate, child_vsize: usize) -> u64 {
+        let mut mempool = HashSet::new();
+        for tx in self.txs.iter() {
+            mempool.extend(self.ancestor_set(&tx.txid).1);
+        }
+        let mut fee = self.fee();
+        let mut vsize = self.vsize() + child_vsize;
+        for entry in mempool.iter().map(|txid| &self.mempool_parents[txid]) {
+            fee += entry.ancestor_fees;
+            vsize += entry.ancestor_vsize;
+        }
+        feerate.fee_for_vsize(vsize).saturating_sub(fee)
+    }
+
+    /// Check the package against mempool ancestor and descendant limits. Returns every
+    /// violation found, or an empty vector if the package would be accepted.
+    pub fn check_limits(&self, limits: &PackageLimits) -> Vec<PackageLimitViolation> {
+        let mut violations = vec![];
+        for tx in self.txs.iter() {
+            let totals = self.ancestor_totals(&tx.txid).expect("tx in package");
+            if totals.count > limits.ancestor_count {
+                violations.push(PackageLimitViolation::AncestorCount {
+                    txid: tx.txid,
+                    count: totals.count,
+                });
+            }
+            if totals.vsize > limits.ancestor_vsize {
+                violations.push(PackageLimitViolation::AncestorSize {
+                    txid: tx.txid,
+                    vsize: totals.vsize,
+                });
+            }
+        }
+
+        let mut parents: Vec<_> = self.mempool_parents.iter().collect();
+        parents.sort_by_key(|(txid, _)| txid.to_be_hex());
+        for (parent, entry) in parents {
+            let mut count = entry.descendant_count;
+            let mut vsize = entry.descendant_vsize;
+            for tx in self.txs.iter() {
+                if self.ancestor_set(&tx.txid).1.contains(parent) {
+                    count += 1;
+                    vsize += tx.vsize();
+                }
+            }
+            if count > limits.descendant_count {
+                violations.push(PackageLimitViolation::DescendantCount {
+                    txid: *parent,
+                    count,
+                });
+            }
+            if vsize > limits.descendant_vsize {
+                violations.push(PackageLimitViolation::DescendantSize {
+                    txid: *parent,
+                    vsize,
+                });
+            }
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tx(byte: u8, fee: u64, vsize: usize, parents: &[u8]) -> PackageTx {
+        PackageTx {
+            txid: TXID::from([byte; 32]),
+            fee,
+            weight: vsize * 4,
+            parents: parents.iter().map(|p| TXID::from([*p; 32])).collect(),
+        }
+    }
+
+    fn txid(byte: u8) -> TXID {
+        TXID::from([byte; 32])
+    }
+
+    #[test]
+    fn it_computes_cpfp_feerates() {
+        // A 1 sat/vbyte parent, and a 21 sat/vbyte child spending it and a confirmed tx
+        let package = Package::new(vec![tx(1, 200, 200, &[0]), tx(2, 2100, 100, &[1])]);
+        assert_eq!(package.external_parents(), vec![txid(0)]);
+        assert_eq!(package.fee(), 2300);
+        assert_eq!(package.vsize(), 300);
+        let cpfp = FeeRate::from_sat_per_kvb(7666);
+        assert_eq!(package.feerate(), cpfp);
+        assert_eq!(
+            package.ancestor_feerate(&txid(1)),
+            Some(FeeRate::from_sat_per_vb(1))
+        );
+        assert_eq!(package.ancestor_feerate(&txid(2)), Some(cpfp));
+        assert_eq!(package.effective_feerate(&txid(1)), Some(cpfp));
+        assert_eq!(package.effective_feerate(&txid(2)), Some(cpfp));
+        assert_eq!(package.ancestor_feerate(&txid(9)), None);
+
+        // Bringing the package to 10 sat/vbyte with a 100 vbyte child
+        assert_eq!(package.cpfp_fee(FeeRate::from_sat_per_vb(10), 100), 1700);
+        assert_eq!(package.cpfp_fee(FeeRate::from_sat_per_vb(5), 100), 0);
+
+        // With the confirmed tx in the mempool, paying 0 fees
+        let mut mempool = HashMap::new();
+        mempool.insert(
+            txid(0),
+            MempoolEntry {
+                fee: 0,
+                vsize: 200,
+                ancestor_count: 1,
+                ancestor_vsize: 200,
+                descendant_count: 1,
+                descendant_vsize: 200,
+                ..Default::default()
+            },
+        );
+        let package = package.mempool_parents(mempool);
+        assert_eq!(
+            package.ancestor_feerate(&txid(1)),
+            Some(FeeRate::from_sat_per_kvb(500))
+        );
+        assert_eq!(
+            package.effective_feerate(&txid(1)),
+            Some(FeeRate::from_sat_per_kvb(4600))
+        );
+        assert_eq!(package.cpfp_fee(FeeRate::from_sat_per_vb(10), 100), 3700);
+    }
+
+    #[test]
+    fn it_checks_package_limits() {
+        let mut mempool = HashMap::new();
+        mempool.insert(
+            txid(0),
+            MempoolEntry {
+                fee: 1000,
+                vsize: 1000,
+                ancestor_count: 24,
+                ancestor_vsize: 100_000,
+                ancestor_fees: 24_000,
+                descendant_count: 20,
+                descendant_vsize: 20_000,
+            },
+        );
+        let package = Package::new(vec![tx(1, 200, 200, &[0]), tx(2, 2100, 1000, &[1])])
+            .mempool_parents(mempool);
+
+        assert_eq!(
+            package.check_limits(&PackageLimits::default()),
+            vec![
+                PackageLimitViolation::AncestorCount {
+                    txid: txid(2),
+                    count: 26
+                },
+                PackageLimitViolation::AncestorSize {
+                    txid: txid(2),
+                    vsize: 101_200
+                },
+            ]
+        );
+
+        let limits = PackageLimits {
+            ancestor_count: 26,
+            ancestor_vsize: 200_000,
+            descendant_count: 21,
+            descendant_vsize: 20_000,
+        };
+        assert_eq!(
+            package.check_limits(&limits),
+            vec![
+                PackageLimitViolation::DescendantCount {
+                    txid: txid(0),
+                    count: 22
+                },
+                PackageLimitViolation::DescendantSize {
+                    txid: txid(0),
+                    vsize: 21_200
+                },
+            ]
+        );
+    }
+}
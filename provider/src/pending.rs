@@ -1,23 +1,24 @@
 use std::{
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
     time::Duration,
 };
 
-use futures_core::stream::Stream;
 use futures_util::stream::StreamExt;
 use pin_project::pin_project;
 
-use bitcoins::prelude::*;
+use coins_core::types::tx::Transaction;
 
 use crate::{
-    provider::BtcProvider,
-    utils::{new_interval, StreamLast},
+    clock::{Clock, SystemClock},
+    provider::{BtcProvider, ChainProvider},
+    utils::{new_interval, Interval, StreamLast},
     ProviderFut, DEFAULT_POLL_INTERVAL,
 };
 
-enum PendingTxStates<'a> {
-    Broadcasting(ProviderFut<'a, TXID>),
+enum PendingTxStates<'a, P: ChainProvider + ?Sized> {
+    Broadcasting(ProviderFut<'a, P::TXID>),
     Paused,
     WaitingConfFut(ProviderFut<'a, Option<usize>>),
     // Stream has failed and should not be polled again
@@ -40,21 +41,25 @@ enum PendingTxStates<'a> {
 /// `>= self.confs_wanted` confirmations, the stream will finish.
 ///
 /// To get a future yielding a single event when the stream ends, use `StreamLast::last()`
+///
+/// The stream is generic over the provider's chain. By default it uses a `dyn BtcProvider`.
 #[pin_project(project = PendingTxProj)]
 #[must_use = "streams do nothing unless polled"]
-pub struct PendingTx<'a> {
-    txid: TXID,
-    tx: BitcoinTx,
+pub struct PendingTx<'a, P: ChainProvider + ?Sized = dyn BtcProvider + 'a> {
+    txid: P::TXID,
+    tx: P::Tx,
     confs_wanted: usize,
     confs_have: usize,
-    state: PendingTxStates<'a>,
-    interval: Box<dyn Stream<Item = ()> + Send + Unpin>,
-    provider: &'a dyn BtcProvider,
+    state: PendingTxStates<'a, P>,
+    interval: Interval,
+    duration: Duration,
+    clock: Arc<dyn Clock>,
+    provider: &'a P,
 }
 
-impl<'a> PendingTx<'a> {
+impl<'a, P: ChainProvider + ?Sized> PendingTx<'a, P> {
     /// Creates a new outspend poller
-    pub fn new(tx: BitcoinTx, provider: &'a dyn BtcProvider) -> Self {
+    pub fn new(tx: P::Tx, provider: &'a P) -> Self {
         let txid = tx.txid();
         let fut = Box::pin(provider.broadcast(tx.clone()));
         Self {
@@ -63,7 +68,9 @@ impl<'a> PendingTx<'a> {
             confs_wanted: 0,
             confs_have: 0,
             state: PendingTxStates::Broadcasting(fut),
-            interval: Box::new(new_interval(DEFAULT_POLL_INTERVAL)),
+            interval: new_interval(Arc::new(SystemClock), DEFAULT_POLL_INTERVAL),
+            duration: DEFAULT_POLL_INTERVAL,
+            clock: Arc::new(SystemClock),
             provider,
         }
     }
@@ -76,15 +83,23 @@ impl<'a> PendingTx<'a> {
 
     /// Sets the polling interval
     pub fn interval<T: Into<Duration>>(mut self, duration: T) -> Self {
-        self.interval = Box::new(new_interval(duration.into()));
+        self.duration = duration.into();
+        self.interval = new_interval(self.clock.clone(), self.duration);
+        self
+    }
+
+    /// Sets the clock used to pause between polls
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.interval = new_interval(clock.clone(), self.duration);
+        self.clock = clock;
         self
     }
 }
 
-impl StreamLast for PendingTx<'_> {}
+impl<P: ChainProvider + ?Sized> StreamLast for PendingTx<'_, P> {}
 
-impl<'a> futures_core::stream::Stream for PendingTx<'a> {
-    type Item = Result<(usize, TXID), BitcoinTx>;
+impl<'a, P: ChainProvider + ?Sized> futures_core::stream::Stream for PendingTx<'a, P> {
+    type Item = Result<(usize, P::TXID), P::Tx>;
 
     fn poll_next(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
         let PendingTxProj {
@@ -95,6 +110,7 @@ impl<'a> futures_core::stream::Stream for PendingTx<'a> {
             state,
             interval,
             provider,
+            ..
         } = self.project();
 
         match state {
@@ -1,6 +1,8 @@
 #[cfg(feature = "esplora")]
 pub use crate::esplora::EsploraProvider;
+pub use crate::fees::*;
 pub use crate::provider::*;
+pub use crate::rbf::*;
 #[cfg(feature = "rpc")]
 pub use crate::rpc::BitcoinRpc;
 
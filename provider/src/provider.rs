@@ -1,5 +1,6 @@
 use async_trait::async_trait;
-use std::time::Duration;
+use instant::Instant;
+use std::{sync::Arc, time::Duration};
 use thiserror::Error;
 
 use bitcoins::{
@@ -7,12 +8,20 @@ use bitcoins::{
     hashes::{BlockHash, TXID},
     types::*,
 };
-use coins_core::prelude::*;
+use coins_core::{prelude::*, types::tx::TxoIdentifier};
 use futures_util::lock::Mutex;
 use lru::LruCache;
 
 use crate::{
-    chain::Tips, pending::PendingTx, types::RawHeader, watcher::PollingWatcher, DEFAULT_CACHE_SIZE,
+    chain::Tips,
+    clock::{Clock, SystemClock},
+    fees::{FeeCheck, FeeCheckReport},
+    package::{MempoolEntry, Package, PackageTx},
+    pending::PendingTx,
+    rbf::{ConflictReport, RbfConflict, TxFeeInfo},
+    types::RawHeader,
+    watcher::PollingWatcher,
+    DEFAULT_CACHE_SIZE, DEFAULT_FINAL_CONFIRMATIONS, DEFAULT_VOLATILE_TTL,
 };
 
 /// Errors thrown by providers
@@ -40,6 +49,25 @@ pub enum ProviderError {
     #[error("RPC Error Response: {0}")]
     RpcErrorResponse(crate::rpc::common::ErrorResponse),
 
+    /// A request to the remote API failed before a response was read
+    #[cfg(any(feature = "rpc", feature = "esplora"))]
+    #[error("Request to {} failed: {source}", .url.as_deref().unwrap_or("unknown url"))]
+    NetworkError {
+        /// The URL of the request, if known
+        url: Option<String>,
+        /// The underlying error
+        source: reqwest::Error,
+    },
+
+    /// A transaction failed a feerate sanity check
+    #[error("Feerate of {feerate} exceeds the limit of {limit}")]
+    ExcessiveFeerate {
+        /// The feerate of the transaction, rounded down
+        feerate: FeeRate,
+        /// The highest acceptable feerate
+        limit: FeeRate,
+    },
+
     /// Custom provider error. Indicates whether the request should be retried
     #[error("Proivder error {e}")]
     Custom {
@@ -55,6 +83,64 @@ impl ProviderError {
     pub fn custom(from_parsing: bool, e: Box<dyn std::error::Error>) -> Self {
         Self::Custom { from_parsing, e }
     }
+
+    /// A stable, machine-readable `provider.*` code for the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            #[cfg(any(feature = "rpc", feature = "esplora"))]
+            ProviderError::SerdeJsonError(_) => "provider.json",
+            ProviderError::EncoderError(_) => "provider.encoding",
+            ProviderError::CoinsSerError(_) => "provider.ser",
+            ProviderError::Unsupported(_) => "provider.unsupported",
+            #[cfg(feature = "rpc")]
+            ProviderError::RpcErrorResponse(_) => "provider.rpc_error",
+            #[cfg(any(feature = "rpc", feature = "esplora"))]
+            ProviderError::NetworkError { .. } => "provider.network",
+            ProviderError::ExcessiveFeerate { .. } => "provider.excessive_feerate",
+            ProviderError::Custom { .. } => "provider.custom",
+        }
+    }
+
+    /// The JSON-RPC error code returned by the node, if any
+    #[cfg(feature = "rpc")]
+    pub fn rpc_code(&self) -> Option<i64> {
+        match self {
+            ProviderError::RpcErrorResponse(e) => Some(e.code),
+            _ => None,
+        }
+    }
+
+    /// True if the request may succeed if retried unchanged. This includes transport errors,
+    /// server errors, and nodes that are still starting or syncing.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            #[cfg(feature = "rpc")]
+            ProviderError::RpcErrorResponse(e) => e.is_retryable(),
+            #[cfg(any(feature = "rpc", feature = "esplora"))]
+            ProviderError::NetworkError { source, .. } => {
+                !source.is_builder()
+                    && source.status().is_none_or(|status| {
+                        status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                    })
+            }
+            ProviderError::Custom { from_parsing, .. } => !from_parsing,
+            _ => false,
+        }
+    }
+
+    /// True if the request was rejected because of the caller's input, e.g. an unencodable
+    /// address, an invalid transaction, or an excessive feerate. Serialization errors are not
+    /// user errors, as they are usually raised while parsing the remote API's response.
+    pub fn is_user_error(&self) -> bool {
+        match self {
+            #[cfg(feature = "rpc")]
+            ProviderError::RpcErrorResponse(e) => e.is_user_error(),
+            ProviderError::EncoderError(e) => e.is_user_error(),
+            ProviderError::ExcessiveFeerate { .. } => true,
+            _ => false,
+        }
+    }
+
     /// Returns true if the request failed due to a local parsing error.
     ///
     /// ## Note:
@@ -94,10 +180,52 @@ impl ProviderError {
     }
 }
 
+/// A chain-agnostic provider. This contains the methods used by the polling streams
+/// (`PendingTx`, `PollingWatcher`, and `Tips`), with types taken from the network's
+/// `coins_core::types::tx::Transaction`. Implementing this trait allows providers for other UTXO
+/// networks (e.g. Handshake) to reuse the polling machinery.
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait ChainProvider: Sync + Send {
+    /// The network's transaction type
+    type Tx: Transaction<TXID = Self::TXID> + Clone + Send + Sync;
+    /// The network's transaction ID type
+    type TXID: MarkedDigestOutput + Eq + Send + Sync;
+    /// The network's TXO identifier type. In Bitcoin this is an outpoint.
+    type TxoIdentifier: TxoIdentifier + Copy + Send + Sync;
+    /// The network's block hash type
+    type BlockHash: MarkedDigestOutput + Eq + Send + Sync;
+
+    /// Fetch the LE digest of the chain tip
+    async fn tip_hash(&self) -> Result<Self::BlockHash, ProviderError>;
+
+    /// Get the number of confs a tx has. If the TX is unconfirmed this will be `Ok(Some(0))`. If
+    /// the TX is unknown to the API, it will be `Ok(None)`.
+    async fn get_confs(&self, txid: Self::TXID) -> Result<Option<usize>, ProviderError>;
+
+    /// Fetch a transaction from the remote API. If the tx is not found, the result will be
+    /// `Ok(None)`
+    async fn get_tx(&self, txid: Self::TXID) -> Result<Option<Self::Tx>, ProviderError>;
+
+    /// Broadcast a transaction to the network. Resolves to a TXID when broadcast.
+    async fn broadcast(&self, tx: Self::Tx) -> Result<Self::TXID, ProviderError>;
+
+    /// Fetch the ID of a transaction that spends a TXO. If no TX known to the remote source
+    /// spends that TXO, the result will be `Ok(None)`.
+    ///
+    /// Note: some providers may not implement this functionality.
+    async fn get_outspend(
+        &self,
+        outpoint: Self::TxoIdentifier,
+    ) -> Result<Option<Self::TXID>, ProviderError>;
+}
+
 /// A Bitcoin Provider
 #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
-pub trait BtcProvider: Sync + Send {
+pub trait BtcProvider:
+    ChainProvider<Tx = BitcoinTx, TXID = TXID, TxoIdentifier = BitcoinOutpoint, BlockHash = BlockHash>
+{
     /// Explicitly drop the provider, closing connections and freeing resources
     fn close(self)
     where
@@ -107,9 +235,6 @@ pub trait BtcProvider: Sync + Send {
 
     // -- CHAIN UTILS -- //
 
-    /// Fetch the LE digest of the chain tip
-    async fn tip_hash(&self) -> Result<BlockHash, ProviderError>;
-
     /// Fetch the height of the chain tip
     async fn tip_height(&self) -> Result<usize, ProviderError>;
 
@@ -157,24 +282,144 @@ pub trait BtcProvider: Sync + Send {
     /// Get confirming height of the tx. Ok(None) if unknown
     async fn get_confirmed_height(&self, txid: TXID) -> Result<Option<usize>, ProviderError>;
 
-    /// Get the number of confs a tx has. If the TX is unconfirmed this will be `Ok(Some(0))`. If
-    /// the TX is unknown to the API, it will be `Ok(None)`.
-    async fn get_confs(&self, txid: TXID) -> Result<Option<usize>, ProviderError>;
+    // -- SPEND UTILS -- //
 
-    /// Fetch a transaction from the remote API. If the tx is not found, the result will be
-    /// `Ok(None)`
-    async fn get_tx(&self, txid: TXID) -> Result<Option<BitcoinTx>, ProviderError>;
+    /// Calculate the fee paid by a transaction by fetching its prevouts. If any prevout is unknown
+    /// to the remote API, the result will be `Ok(None)`.
+    async fn get_fee(&self, tx: &BitcoinTx) -> Result<Option<u64>, ProviderError> {
+        let mut input_value = 0u64;
+        for input in tx.inputs() {
+            let prevout = input.outpoint;
+            let value = match self.get_tx(prevout.txid).await? {
+                Some(prev_tx) => prev_tx.outputs().get(prevout.idx as usize).map(|o| o.value),
+                None => None,
+            };
+            match value {
+                Some(v) => input_value += v,
+                None => return Ok(None),
+            }
+        }
+        let output_value: u64 = tx.outputs().iter().map(|o| o.value).sum();
+        Ok(input_value.checked_sub(output_value))
+    }
 
-    /// Broadcast a transaction to the network. Resolves to a TXID when broadcast.
-    async fn broadcast(&self, tx: BitcoinTx) -> Result<TXID, ProviderError>;
+    /// Estimate the feerate needed for a transaction to confirm within `target` blocks. Returns
+    /// `Ok(None)` if the remote API has insufficient data for an estimate.
+    ///
+    /// Note: some providers may not implement this functionality.
+    async fn estimate_feerate(&self, _target: usize) -> Result<Option<FeeRate>, ProviderError> {
+        Err(ProviderError::Unsupported(
+            "This provider does not estimate feerates".to_owned(),
+        ))
+    }
 
-    // -- SPEND UTILS -- //
+    /// Compare the feerate of `tx` against the provider's current estimate. Inspect the report
+    /// to warn on excessive feerates, or use `broadcast_checked` to refuse them. If the provider
+    /// does not estimate feerates, the report's estimate is `None`.
+    async fn check_fee(
+        &self,
+        tx: &BitcoinTx,
+        check: FeeCheck,
+    ) -> Result<FeeCheckReport, ProviderError> {
+        let fee = self.get_fee(tx).await?;
+        let estimate = match self.estimate_feerate(check.get_target()).await {
+            Err(ProviderError::Unsupported(_)) => None,
+            estimate => estimate?,
+        };
+        Ok(FeeCheckReport {
+            info: TxFeeInfo::new(tx, fee),
+            estimate,
+            max_multiple: check.get_max_multiple(),
+        })
+    }
 
-    /// Fetch the ID of a transaction that spends an outpoint. If no TX known to the remote source
-    /// spends that outpoint, the result will be `Ok(None)`.
+    /// Broadcast a transaction, unless its feerate exceeds the limit set by `check`. If the fee
+    /// or the estimate is unknown, including when the provider does not estimate feerates, the
+    /// transaction is broadcast.
+    async fn broadcast_checked(
+        &self,
+        tx: BitcoinTx,
+        check: FeeCheck,
+    ) -> Result<TXID, ProviderError> {
+        let report = self.check_fee(&tx, check).await?;
+        if report.is_excessive() {
+            return Err(ProviderError::ExcessiveFeerate {
+                feerate: report.feerate().expect("excessive implies known"),
+                limit: report.limit().expect("excessive implies known"),
+            });
+        }
+        self.broadcast(tx).await
+    }
+
+    /// Fetch the mempool entry of an unconfirmed transaction, including the totals of its
+    /// in-mempool ancestors and descendants. `Ok(None)` if the tx is not in the mempool.
     ///
     /// Note: some providers may not implement this functionality.
-    async fn get_outspend(&self, outpoint: BitcoinOutpoint) -> Result<Option<TXID>, ProviderError>;
+    async fn get_mempool_entry(&self, _txid: TXID) -> Result<Option<MempoolEntry>, ProviderError> {
+        Err(ProviderError::Unsupported(
+            "This provider does not expose mempool entries".to_owned(),
+        ))
+    }
+
+    /// Assemble a package from `txs`, fetching the mempool entries of the unconfirmed
+    /// transactions it spends from. Use the package to compute CPFP feerates, and check it
+    /// against mempool ancestor and descendant limits.
+    async fn get_package(&self, txs: Vec<PackageTx>) -> Result<Package, ProviderError> {
+        let package = Package::new(txs);
+        let mut entries = std::collections::HashMap::new();
+        for parent in package.external_parents() {
+            if let Some(entry) = self.get_mempool_entry(parent).await? {
+                entries.insert(parent, entry);
+            }
+        }
+        Ok(package.mempool_parents(entries))
+    }
+
+    /// Find transactions that conflict with `tx` by spending one or more of its outpoints. This
+    /// is useful for detecting that a payment has been replaced via RBF. Conflicts are found
+    /// using `get_outspend`, so this requires provider support for that method.
+    async fn get_conflicts_for_tx(&self, tx: &BitcoinTx) -> Result<ConflictReport, ProviderError> {
+        let txid = tx.txid();
+        let mut conflicts: Vec<RbfConflict> = vec![];
+
+        for input in tx.inputs() {
+            let spender = match self.get_outspend(input.outpoint).await? {
+                Some(spender) if spender != txid => spender,
+                _ => continue,
+            };
+            if let Some(conflict) = conflicts.iter_mut().find(|c| c.info.txid == spender) {
+                conflict.outpoints.push(input.outpoint);
+                continue;
+            }
+            // The spender may have been evicted since `get_outspend` was called
+            let spending_tx_opt = self.get_tx(spender).await?;
+            if let Some(spending_tx) = spending_tx_opt {
+                let fee = self.get_fee(&spending_tx).await?;
+                conflicts.push(RbfConflict {
+                    info: TxFeeInfo::new(&spending_tx, fee),
+                    outpoints: vec![input.outpoint],
+                });
+            }
+        }
+
+        let fee = self.get_fee(tx).await?;
+        Ok(ConflictReport {
+            original: TxFeeInfo::new(tx, fee),
+            conflicts,
+        })
+    }
+
+    /// Find transactions that conflict with the transaction with the specified TXID. If the tx is
+    /// unknown to the remote API, the result will be `Ok(None)`. Note that many APIs drop
+    /// replaced transactions, so callers should prefer `get_conflicts_for_tx` when they hold a
+    /// copy of the transaction.
+    async fn get_conflicts(&self, txid: TXID) -> Result<Option<ConflictReport>, ProviderError> {
+        let tx_opt = self.get_tx(txid).await?;
+        match tx_opt {
+            Some(tx) => Ok(Some(self.get_conflicts_for_tx(&tx).await?)),
+            None => Ok(None),
+        }
+    }
 
     /// Fetch the UTXOs belonging to an address from the remote API
     ///
@@ -244,21 +489,27 @@ pub trait PollingBtcProvider: BtcProvider {
     /// Set the polling interval of the provider. Interval is seconds.
     fn set_interval(&mut self, interval: usize);
 
+    /// Return the clock used to pause the provider's polling streams. Defaults to `SystemClock`.
+    fn clock(&self) -> Arc<dyn Clock> {
+        Arc::new(SystemClock)
+    }
+
     /// Broadcast a transaction, get a future that resolves when the tx is confirmed. This
     /// returns a `PendingTx` future. The tx will not be braodcast until that future is scheduled
     /// to run.
-    fn send(&self, tx: BitcoinTx, confirmations: usize) -> PendingTx
+    fn send(&self, tx: BitcoinTx, confirmations: usize) -> PendingTx<'_, Self>
     where
         Self: Sized,
     {
         PendingTx::new(tx, self)
             .confirmations(confirmations)
+            .clock(self.clock())
             .interval(self.interval())
     }
 
     /// Track a txid that may or may not already be in the mempool. Returns `None` if the txid is
     /// not known to the remote node.
-    async fn track(&self, txid: TXID, confirmations: usize) -> Option<PendingTx<'_>>
+    async fn track(&self, txid: TXID, confirmations: usize) -> Option<PendingTx<'_, Self>>
     where
         Self: Sized,
     {
@@ -266,6 +517,7 @@ pub trait PollingBtcProvider: BtcProvider {
         Some(
             PendingTx::new(tx, self)
                 .confirmations(confirmations)
+                .clock(self.clock())
                 .interval(self.interval()),
         )
     }
@@ -274,43 +526,105 @@ pub trait PollingBtcProvider: BtcProvider {
     ///
     /// Note: A new hash does not necessarily mean the chain height has increased. Reorgs may
     /// result in the height remaining the same, or decreasing in rare cases.
-    fn tips(&self, limit: usize) -> Tips
+    fn tips(&self, limit: usize) -> Tips<'_, Self>
     where
         Self: Sized,
     {
-        Tips::new(limit, self).interval(self.interval())
+        Tips::new(limit, self)
+            .clock(self.clock())
+            .interval(self.interval())
     }
 
     /// Watch an outpoint, waiting for a tx to spend it. This returns a `PollingWatcher` future.
     /// The observation will not start until that future is scheduled to run.
     ///
     /// Note: some providers may not implement this functionality.
-    fn watch(&self, outpoint: BitcoinOutpoint, confirmations: usize) -> PollingWatcher
+    fn watch(&self, outpoint: BitcoinOutpoint, confirmations: usize) -> PollingWatcher<'_, Self>
     where
         Self: Sized,
     {
         PollingWatcher::new(outpoint, self)
             .confirmations(confirmations)
+            .clock(self.clock())
             .interval(self.interval())
     }
 }
 
-/// A provider that caches API responses whose values will never change.
+/// Caching rules for a `CachingProvider`. Immutable data (headers, block heights, and
+/// transactions with at least `final_confirmations` confirmations) is cached until evicted by the
+/// LRU. Volatile data (the chain tip, confirmation counts, spend status, and transactions with
+/// fewer confirmations) is cached for `volatile_ttl`. Fetching a transaction does not fetch its
+/// confirmations. It is cached as volatile until `get_confs` reports it final, unless volatile
+/// caching is disabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachePolicy {
+    /// The number of confirmations after which a transaction is treated as immutable
+    pub final_confirmations: usize,
+    /// The lifetime of volatile data. A zero duration disables volatile caching
+    pub volatile_ttl: Duration,
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        Self {
+            final_confirmations: DEFAULT_FINAL_CONFIRMATIONS,
+            volatile_ttl: DEFAULT_VOLATILE_TTL,
+        }
+    }
+}
+
+/// A cached value that expires after some time
+#[derive(Debug, Clone)]
+struct Expiring<V> {
+    value: V,
+    expires: Instant,
+}
+
+impl<V: Clone> Expiring<V> {
+    fn new(value: V, ttl: Duration) -> Self {
+        Self {
+            value,
+            expires: Instant::now() + ttl,
+        }
+    }
+
+    fn fresh(&self) -> Option<V> {
+        if Instant::now() < self.expires {
+            Some(self.value.clone())
+        } else {
+            None
+        }
+    }
+}
+
+type VolatileCache<K, V> = Mutex<LruCache<K, Expiring<V>>>;
+
+/// Get a value from a volatile cache, if it has not expired.
+async fn get_fresh<K, V>(cache: &VolatileCache<K, V>, key: &K) -> Option<V>
+where
+    K: std::hash::Hash + Eq,
+    V: Clone,
+{
+    cache.lock().await.get(key).and_then(Expiring::fresh)
+}
+
+/// A provider that caches API responses. Immutable data is cached until evicted, while volatile
+/// data is cached for a short time. See `CachePolicy`.
 pub struct CachingProvider<T: BtcProvider> {
     provider: T,
+    policy: CachePolicy,
     tx_cache: Mutex<LruCache<TXID, BitcoinTx>>,
     header_cache: Mutex<LruCache<BlockHash, RawHeader>>,
     height_cache: Mutex<LruCache<BlockHash, usize>>,
+    volatile_tx_cache: VolatileCache<TXID, BitcoinTx>,
+    confs_cache: VolatileCache<TXID, Option<usize>>,
+    outspend_cache: VolatileCache<BitcoinOutpoint, Option<TXID>>,
+    tip_cache: VolatileCache<(), (BlockHash, usize)>,
 }
 
 impl<T: BtcProvider> From<T> for CachingProvider<T> {
     fn from(provider: T) -> Self {
-        Self {
-            provider,
-            tx_cache: Mutex::new(LruCache::new(DEFAULT_CACHE_SIZE)),
-            header_cache: Mutex::new(LruCache::new(DEFAULT_CACHE_SIZE)),
-            height_cache: Mutex::new(LruCache::new(DEFAULT_CACHE_SIZE)),
-        }
+        Self::with_policy(provider, Default::default())
     }
 }
 
@@ -324,13 +638,47 @@ where
 }
 
 impl<T: BtcProvider> CachingProvider<T> {
-    /// Return a reference to the TX, if it's in the cache.
+    /// Instantiate a caching provider with a custom caching policy
+    pub fn with_policy(provider: T, policy: CachePolicy) -> Self {
+        Self {
+            provider,
+            policy,
+            tx_cache: Mutex::new(LruCache::new(DEFAULT_CACHE_SIZE)),
+            header_cache: Mutex::new(LruCache::new(DEFAULT_CACHE_SIZE)),
+            height_cache: Mutex::new(LruCache::new(DEFAULT_CACHE_SIZE)),
+            volatile_tx_cache: Mutex::new(LruCache::new(DEFAULT_CACHE_SIZE)),
+            confs_cache: Mutex::new(LruCache::new(DEFAULT_CACHE_SIZE)),
+            outspend_cache: Mutex::new(LruCache::new(DEFAULT_CACHE_SIZE)),
+            tip_cache: Mutex::new(LruCache::new(1)),
+        }
+    }
+
+    /// Return the caching policy
+    pub fn policy(&self) -> CachePolicy {
+        self.policy
+    }
+
+    /// Return a reference to the TX, if it's in the cache and has not expired.
     pub async fn peek_tx(&self, txid: TXID) -> Option<BitcoinTx> {
-        self.tx_cache.lock().await.peek(&txid).cloned()
+        let tx_opt = self.tx_cache.lock().await.peek(&txid).cloned();
+        match tx_opt {
+            Some(tx) => Some(tx),
+            None => self
+                .volatile_tx_cache
+                .lock()
+                .await
+                .peek(&txid)
+                .and_then(Expiring::fresh),
+        }
     }
 
-    /// Return true if the cache has the tx in it
+    /// Return true if the cache has the tx in it, and it has not expired
     pub async fn has_tx(&self, txid: TXID) -> bool {
+        self.peek_tx(txid).await.is_some()
+    }
+
+    /// Return true if the cache has the tx in it, and it is treated as immutable
+    pub async fn has_final_tx(&self, txid: TXID) -> bool {
         self.tx_cache.lock().await.contains(&txid)
     }
 
@@ -343,20 +691,127 @@ impl<T: BtcProvider> CachingProvider<T> {
     pub async fn has_height(&self, digest: BlockHash) -> bool {
         self.height_cache.lock().await.contains(&digest)
     }
+
+    /// Drop all volatile data from the cache. Immutable data is retained.
+    pub async fn clear_volatile(&self) {
+        self.volatile_tx_cache.lock().await.clear();
+        self.confs_cache.lock().await.clear();
+        self.outspend_cache.lock().await.clear();
+        self.tip_cache.lock().await.clear();
+    }
+
+    /// Fetch the tip hash and height together, caching them as volatile data
+    async fn tip(&self) -> Result<(BlockHash, usize), ProviderError> {
+        if let Some(tip) = get_fresh(&self.tip_cache, &()).await {
+            return Ok(tip);
+        }
+        let hash = self.provider.tip_hash().await?;
+        let height = self.provider.tip_height().await?;
+        self.put_volatile(&self.tip_cache, (), (hash, height)).await;
+        Ok((hash, height))
+    }
+
+    /// Insert a value into a volatile cache, unless volatile caching is disabled
+    async fn put_volatile<K, V>(&self, cache: &VolatileCache<K, V>, key: K, value: V)
+    where
+        K: std::hash::Hash + Eq,
+        V: Clone,
+    {
+        if self.policy.volatile_ttl > Duration::from_secs(0) {
+            cache
+                .lock()
+                .await
+                .put(key, Expiring::new(value, self.policy.volatile_ttl));
+        }
+    }
 }
 
 #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
-impl<T> BtcProvider for CachingProvider<T>
+impl<T> ChainProvider for CachingProvider<T>
 where
     T: BtcProvider,
 {
+    type Tx = BitcoinTx;
+    type TXID = TXID;
+    type TxoIdentifier = BitcoinOutpoint;
+    type BlockHash = BlockHash;
+
     async fn tip_hash(&self) -> Result<BlockHash, ProviderError> {
-        self.provider.tip_hash().await
+        Ok(self.tip().await?.0)
+    }
+
+    async fn get_confs(&self, txid: TXID) -> Result<Option<usize>, ProviderError> {
+        if let Some(confs) = get_fresh(&self.confs_cache, &txid).await {
+            return Ok(confs);
+        }
+        let confs = self.provider.get_confs(txid).await?;
+        self.put_volatile(&self.confs_cache, txid, confs).await;
+        if confs.unwrap_or(0) >= self.policy.final_confirmations {
+            let tx = self.volatile_tx_cache.lock().await.pop(&txid);
+            if let Some(tx) = tx {
+                self.tx_cache.lock().await.put(txid, tx.value);
+            }
+        }
+        Ok(confs)
+    }
+
+    async fn get_tx(&self, txid: TXID) -> Result<Option<BitcoinTx>, ProviderError> {
+        let cached = self.tx_cache.lock().await.get(&txid).cloned();
+        if cached.is_some() {
+            return Ok(cached);
+        }
+        if let Some(tx) = get_fresh(&self.volatile_tx_cache, &txid).await {
+            return Ok(Some(tx));
+        }
+
+        let tx_opt = { self.provider.get_tx(txid).await? };
+        if tx_opt.is_none() {
+            return Ok(None);
+        }
+        let tx = tx_opt.unwrap();
+
+        // Unconfirmed and shallow txs may be replaced or reorged out. Confirmations are only
+        // fetched when the tx can't be cached as volatile, and `get_confs` promotes it otherwise.
+        let confs = match get_fresh(&self.confs_cache, &txid).await {
+            Some(confs) => confs,
+            None if self.policy.volatile_ttl == Duration::from_secs(0) => {
+                self.get_confs(txid).await?
+            }
+            None => None,
+        };
+        if confs.unwrap_or(0) >= self.policy.final_confirmations {
+            self.tx_cache.lock().await.put(txid, tx.clone());
+        } else {
+            self.put_volatile(&self.volatile_tx_cache, txid, tx.clone())
+                .await;
+        }
+        Ok(Some(tx))
+    }
+
+    async fn broadcast(&self, tx: BitcoinTx) -> Result<TXID, ProviderError> {
+        self.provider.broadcast(tx).await
+    }
+
+    async fn get_outspend(&self, outpoint: BitcoinOutpoint) -> Result<Option<TXID>, ProviderError> {
+        if let Some(spender) = get_fresh(&self.outspend_cache, &outpoint).await {
+            return Ok(spender);
+        }
+        let spender = self.provider.get_outspend(outpoint).await?;
+        self.put_volatile(&self.outspend_cache, outpoint, spender)
+            .await;
+        Ok(spender)
     }
+}
 
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl<T> BtcProvider for CachingProvider<T>
+where
+    T: BtcProvider,
+{
     async fn tip_height(&self) -> Result<usize, ProviderError> {
-        self.provider.tip_height().await
+        Ok(self.tip().await?.1)
     }
 
     async fn in_best_chain(&self, digest: BlockHash) -> Result<bool, ProviderError> {
@@ -394,7 +849,7 @@ where
     }
 
     async fn get_height_of(&self, digest: BlockHash) -> Result<Option<usize>, ProviderError> {
-        if self.has_header(digest).await {
+        if self.has_height(digest).await {
             return Ok(self.height_cache.lock().await.get(&digest).cloned());
         }
 
@@ -411,30 +866,12 @@ where
         self.provider.get_confirmed_height(txid).await
     }
 
-    async fn get_confs(&self, txid: TXID) -> Result<Option<usize>, ProviderError> {
-        self.provider.get_confs(txid).await
-    }
-
-    async fn get_tx(&self, txid: TXID) -> Result<Option<BitcoinTx>, ProviderError> {
-        if self.has_tx(txid).await {
-            return Ok(self.tx_cache.lock().await.get(&txid).cloned());
-        }
-
-        let tx_opt = { self.provider.get_tx(txid).await? };
-        if tx_opt.is_none() {
-            return Ok(None);
-        }
-        let tx = tx_opt.unwrap();
-        self.tx_cache.lock().await.put(txid, tx.clone());
-        Ok(Some(tx))
-    }
-
-    async fn broadcast(&self, tx: BitcoinTx) -> Result<TXID, ProviderError> {
-        self.provider.broadcast(tx).await
+    async fn estimate_feerate(&self, target: usize) -> Result<Option<FeeRate>, ProviderError> {
+        self.provider.estimate_feerate(target).await
     }
 
-    async fn get_outspend(&self, outpoint: BitcoinOutpoint) -> Result<Option<TXID>, ProviderError> {
-        self.provider.get_outspend(outpoint).await
+    async fn get_mempool_entry(&self, txid: TXID) -> Result<Option<MempoolEntry>, ProviderError> {
+        self.provider.get_mempool_entry(txid).await
     }
 
     async fn get_utxos_by_address(&self, address: &Address) -> Result<Vec<Utxo>, ProviderError> {
@@ -461,4 +898,347 @@ where
     fn set_interval(&mut self, interval: usize) {
         self.provider.set_interval(interval)
     }
+    fn clock(&self) -> Arc<dyn Clock> {
+        self.provider.clock()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use futures_util::{stream::StreamExt, task::noop_waker, FutureExt};
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        task::{Context, Poll},
+    };
+
+    /// A provider that knows a single tx, and counts requests for it
+    struct MockProvider {
+        tx: BitcoinTx,
+        confs: usize,
+        tx_requests: AtomicUsize,
+        confs_requests: AtomicUsize,
+        tip_requests: AtomicUsize,
+        estimates_feerates: bool,
+    }
+
+    fn unsupported<T>() -> Result<T, ProviderError> {
+        Err(ProviderError::Unsupported("mock".to_owned()))
+    }
+
+    #[async_trait]
+    impl ChainProvider for MockProvider {
+        type Tx = BitcoinTx;
+        type TXID = TXID;
+        type TxoIdentifier = BitcoinOutpoint;
+        type BlockHash = BlockHash;
+
+        async fn tip_hash(&self) -> Result<BlockHash, ProviderError> {
+            self.tip_requests.fetch_add(1, Ordering::SeqCst);
+            Ok(BlockHash::default())
+        }
+
+        async fn get_confs(&self, _txid: TXID) -> Result<Option<usize>, ProviderError> {
+            self.confs_requests.fetch_add(1, Ordering::SeqCst);
+            Ok(Some(self.confs))
+        }
+
+        async fn get_tx(&self, txid: TXID) -> Result<Option<BitcoinTx>, ProviderError> {
+            self.tx_requests.fetch_add(1, Ordering::SeqCst);
+            if txid == self.tx.txid() {
+                Ok(Some(self.tx.clone()))
+            } else {
+                Ok(None)
+            }
+        }
+
+        async fn broadcast(&self, _tx: BitcoinTx) -> Result<TXID, ProviderError> {
+            unsupported()
+        }
+
+        async fn get_outspend(
+            &self,
+            _outpoint: BitcoinOutpoint,
+        ) -> Result<Option<TXID>, ProviderError> {
+            Ok(None)
+        }
+    }
+
+    #[async_trait]
+    impl BtcProvider for MockProvider {
+        async fn tip_height(&self) -> Result<usize, ProviderError> {
+            Ok(100)
+        }
+
+        async fn in_best_chain(&self, _digest: BlockHash) -> Result<bool, ProviderError> {
+            unsupported()
+        }
+
+        async fn get_digest_range(
+            &self,
+            _start: usize,
+            _headers: usize,
+        ) -> Result<Vec<BlockHash>, ProviderError> {
+            unsupported()
+        }
+
+        async fn get_raw_header_range(
+            &self,
+            _start: usize,
+            _headers: usize,
+        ) -> Result<Vec<RawHeader>, ProviderError> {
+            unsupported()
+        }
+
+        async fn get_raw_header(
+            &self,
+            _digest: BlockHash,
+        ) -> Result<Option<RawHeader>, ProviderError> {
+            unsupported()
+        }
+
+        async fn get_height_of(&self, _digest: BlockHash) -> Result<Option<usize>, ProviderError> {
+            unsupported()
+        }
+
+        async fn get_confirmed_height(&self, _txid: TXID) -> Result<Option<usize>, ProviderError> {
+            unsupported()
+        }
+
+        async fn estimate_feerate(&self, _target: usize) -> Result<Option<FeeRate>, ProviderError> {
+            if self.estimates_feerates {
+                Ok(Some(FeeRate::from_sat_per_vb(10)))
+            } else {
+                unsupported()
+            }
+        }
+
+        async fn get_utxos_by_address(
+            &self,
+            _address: &Address,
+        ) -> Result<Vec<Utxo>, ProviderError> {
+            unsupported()
+        }
+
+        async fn get_merkle(
+            &self,
+            _txid: TXID,
+        ) -> Result<Option<(usize, Vec<Hash256Digest>)>, ProviderError> {
+            unsupported()
+        }
+    }
+
+    fn caching_provider(confs: usize, volatile_ttl: Duration) -> CachingProvider<MockProvider> {
+        let tx = BitcoinTx::deserialize_hex("0100000000010115e180dc28a2327e687facc33f10f2a20da717e5548406f7ae8b4c811072f8560100000000ffffffff0100b4f505000000001976a9141d7cd6c75c2e86f4cbf98eaed221b30bd9a0b92888ac02483045022100df7b7e5cda14ddf91290e02ea10786e03eb11ee36ec02dd862fe9a326bbcb7fd02203f5b4496b667e6e281cc654a2da9e4f08660c620a1051337fa8965f727eb19190121038262a6c6cec93c2d3ecd6c6072efea86d02ff8e3328bbd0242b20af3425990ac00000000").unwrap();
+        let mock = MockProvider {
+            tx,
+            confs,
+            tx_requests: AtomicUsize::new(0),
+            confs_requests: AtomicUsize::new(0),
+            tip_requests: AtomicUsize::new(0),
+            estimates_feerates: true,
+        };
+        CachingProvider::with_policy(
+            mock,
+            CachePolicy {
+                final_confirmations: 6,
+                volatile_ttl,
+            },
+        )
+    }
+
+    #[test]
+    #[cfg(feature = "rpc")]
+    fn it_classifies_errors() {
+        let rpc_error = |code| {
+            ProviderError::RpcErrorResponse(crate::rpc::common::ErrorResponse {
+                code,
+                message: "".to_owned(),
+                data: None,
+            })
+        };
+        let warmup = rpc_error(-28);
+        assert_eq!(warmup.code(), "provider.rpc_error");
+        assert_eq!(warmup.rpc_code(), Some(-28));
+        assert!(warmup.is_retryable());
+        assert!(!warmup.is_user_error());
+
+        let rejected = rpc_error(-26);
+        assert!(!rejected.is_retryable());
+        assert!(rejected.is_user_error());
+
+        let feerate = ProviderError::ExcessiveFeerate {
+            feerate: FeeRate::from_sat_per_vb(1000),
+            limit: FeeRate::from_sat_per_vb(100),
+        };
+        assert_eq!(feerate.code(), "provider.excessive_feerate");
+        assert_eq!(feerate.rpc_code(), None);
+        assert!(feerate.is_user_error());
+
+        let unsupported = unsupported::<()>().unwrap_err();
+        assert!(!unsupported.is_retryable());
+        assert!(!unsupported.is_user_error());
+
+        let json = serde_json::from_str::<u8>("not found")
+            .map_err(crate::reqwest_utils::FetchError::from)
+            .map_err(ProviderError::from)
+            .unwrap_err();
+        assert_eq!(json.code(), "provider.json");
+        assert!(json.from_parsing());
+        assert!(!json.is_retryable());
+
+        let address = ProviderError::from(coins_core::enc::bases::EncodingError::NullDataScript);
+        assert!(address.is_user_error());
+        let ser = ProviderError::from(coins_core::ser::SerError::NonMinimalVarInt);
+        assert!(ser.from_parsing());
+        assert!(!ser.is_user_error());
+
+        let request = reqwest::Client::new()
+            .get("not a url")
+            .send()
+            .now_or_never()
+            .unwrap()
+            .map_err(crate::reqwest_utils::FetchError::from)
+            .map_err(ProviderError::from)
+            .unwrap_err();
+        assert_eq!(request.code(), "provider.network");
+        assert!(!request.is_retryable());
+        assert!(request
+            .to_string()
+            .starts_with("Request to unknown url failed"));
+    }
+
+    #[test]
+    fn it_caches_final_txs_indefinitely() {
+        let provider = caching_provider(6, Duration::from_secs(0));
+        let txid = provider.provider.tx.txid();
+        for _ in 0..3 {
+            let tx = provider.get_tx(txid).now_or_never().unwrap().unwrap();
+            assert_eq!(tx.unwrap().txid(), txid);
+        }
+        assert_eq!(provider.provider.tx_requests.load(Ordering::SeqCst), 1);
+        assert!(provider.has_final_tx(txid).now_or_never().unwrap());
+
+        // With volatile caching, the tx is promoted once its confirmations are fetched
+        let provider = caching_provider(6, Duration::from_secs(60));
+        provider.get_tx(txid).now_or_never().unwrap().unwrap();
+        assert_eq!(provider.provider.confs_requests.load(Ordering::SeqCst), 0);
+        assert!(!provider.has_final_tx(txid).now_or_never().unwrap());
+        provider.get_confs(txid).now_or_never().unwrap().unwrap();
+        assert!(provider.has_final_tx(txid).now_or_never().unwrap());
+        provider.clear_volatile().now_or_never().unwrap();
+        provider.get_tx(txid).now_or_never().unwrap().unwrap();
+        assert_eq!(provider.provider.tx_requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn it_caches_volatile_data_until_expiry() {
+        let provider = caching_provider(1, Duration::from_secs(60));
+        let txid = provider.provider.tx.txid();
+        for _ in 0..3 {
+            provider.get_tx(txid).now_or_never().unwrap().unwrap();
+            provider.tip_hash().now_or_never().unwrap().unwrap();
+        }
+        assert_eq!(provider.provider.tx_requests.load(Ordering::SeqCst), 1);
+        assert_eq!(provider.provider.tip_requests.load(Ordering::SeqCst), 1);
+        assert_eq!(provider.provider.confs_requests.load(Ordering::SeqCst), 0);
+        assert!(!provider.has_final_tx(txid).now_or_never().unwrap());
+        assert!(provider.has_tx(txid).now_or_never().unwrap());
+
+        provider.clear_volatile().now_or_never().unwrap();
+        assert!(!provider.has_tx(txid).now_or_never().unwrap());
+        provider.get_tx(txid).now_or_never().unwrap().unwrap();
+        assert_eq!(provider.provider.tx_requests.load(Ordering::SeqCst), 2);
+
+        // With volatile caching disabled, every request reaches the provider
+        let provider = caching_provider(1, Duration::from_secs(0));
+        for _ in 0..3 {
+            provider.get_tx(txid).now_or_never().unwrap().unwrap();
+        }
+        assert_eq!(provider.provider.tx_requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn it_polls_on_the_injected_clock() {
+        let provider = caching_provider(0, Duration::from_secs(0));
+        let clock = ManualClock::new();
+        let waker = noop_waker();
+        let mut ctx = Context::from_waker(&waker);
+
+        let mut tips = Tips::new(10, &provider.provider)
+            .clock(Arc::new(clock.clone()))
+            .interval(Duration::from_secs(60));
+        assert_eq!(
+            tips.poll_next_unpin(&mut ctx),
+            Poll::Ready(Some(BlockHash::default()))
+        );
+        assert_eq!(tips.poll_next_unpin(&mut ctx), Poll::Pending);
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(tips.poll_next_unpin(&mut ctx), Poll::Pending);
+        assert_eq!(provider.provider.tip_requests.load(Ordering::SeqCst), 1);
+
+        // The tip is unchanged, so nothing is emitted, but it is polled again
+        clock.advance(Duration::from_secs(1));
+        for _ in 0..3 {
+            assert_eq!(tips.poll_next_unpin(&mut ctx), Poll::Pending);
+        }
+        assert_eq!(provider.provider.tip_requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn it_refuses_excessive_feerates() {
+        let provider = caching_provider(6, Duration::from_secs(0));
+        let parent = &provider.provider.tx;
+        let value = parent.outputs()[0].value;
+        // An 85 vbyte tx spending the mock tx
+        let child = |fee: u64| -> BitcoinTx {
+            let outpoint = BitcoinOutpoint::new(parent.txid(), 0);
+            let vin = vec![BitcoinTxIn::new(outpoint, vec![], 0xffff_ffff)];
+            let vout = vec![TxOut::new(
+                value - fee,
+                parent.outputs()[0].script_pubkey.clone(),
+            )];
+            LegacyTx::new(2, vin, vout, 0).unwrap().into()
+        };
+        let check = FeeCheck::default();
+
+        let report = provider
+            .check_fee(&child(850), check)
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.info.vsize, 85);
+        assert_eq!(report.limit(), Some(FeeRate::from_sat_per_vb(100)));
+        assert!(!report.is_excessive());
+        // The check passes, and the mock refuses to broadcast
+        assert!(matches!(
+            provider.broadcast_checked(child(850), check).now_or_never(),
+            Some(Err(ProviderError::Unsupported(_)))
+        ));
+
+        assert!(matches!(
+            provider
+                .broadcast_checked(child(8501), check)
+                .now_or_never(),
+            Some(Err(ProviderError::ExcessiveFeerate { .. }))
+        ));
+        assert!(matches!(
+            provider
+                .broadcast_checked(child(8501), check.max_multiple(20.0))
+                .now_or_never(),
+            Some(Err(ProviderError::Unsupported(_)))
+        ));
+
+        // Without an estimate, the check passes
+        let mut unestimated = caching_provider(6, Duration::from_secs(0));
+        unestimated.provider.estimates_feerates = false;
+        let report = unestimated
+            .check_fee(&child(8501), check)
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert_eq!(report.estimate, None);
+        assert!(!report.is_excessive());
+    }
 }
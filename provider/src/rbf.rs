@@ -0,0 +1,140 @@
+use bitcoins::{hashes::TXID, types::*};
+use coins_core::prelude::*;
+
+/// Fee information about a transaction, as discovered by a provider. The fee is `None` if the
+/// provider could not locate one or more of the transaction's prevouts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TxFeeInfo {
+    /// The TXID of the transaction
+    pub txid: TXID,
+    /// The fee paid by the transaction, if known
+    pub fee: Option<u64>,
+    /// The virtual size of the transaction, in vbytes
+    pub vsize: usize,
+}
+
+impl TxFeeInfo {
+    /// Instantiate a `TxFeeInfo` from a transaction and its (possibly unknown) fee
+    pub fn new(tx: &BitcoinTx, fee: Option<u64>) -> Self {
+        Self {
+            txid: tx.txid(),
+            fee,
+            vsize: tx.vsize(),
+        }
+    }
+
+    /// The feerate of the transaction, rounded down, if the fee is known
+    pub fn feerate(&self) -> Option<FeeRate> {
+        self.fee
+            .and_then(|fee| FeeRate::from_fee_and_vsize(fee, self.vsize))
+    }
+
+    /// True if this transaction is known to pay a strictly higher feerate than `other`. False if
+    /// either fee is unknown.
+    pub fn pays_more_than(&self, other: &TxFeeInfo) -> bool {
+        match (self.fee, other.fee) {
+            // Cross-multiply to avoid float comparisons
+            (Some(a), Some(b)) => a as u128 * other.vsize as u128 > b as u128 * self.vsize as u128,
+            _ => false,
+        }
+    }
+}
+
+/// A mempool or chain transaction that conflicts with another transaction by spending one or
+/// more of the same outpoints.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RbfConflict {
+    /// Fee information about the conflicting transaction
+    pub info: TxFeeInfo,
+    /// The outpoints spent by both transactions
+    pub outpoints: Vec<BitcoinOutpoint>,
+}
+
+/// A report on the transactions that conflict with a given transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConflictReport {
+    /// Fee information about the transaction being inspected
+    pub original: TxFeeInfo,
+    /// Transactions spending one or more of the same outpoints
+    pub conflicts: Vec<RbfConflict>,
+}
+
+impl ConflictReport {
+    /// True if any conflicting transaction was found. Generally this means the original has been
+    /// replaced, and will not confirm.
+    pub fn is_replaced(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+
+    /// Return the fee information of the transaction paying the highest known feerate. This is
+    /// the original transaction unless a conflict is known to pay strictly more.
+    pub fn highest_feerate(&self) -> &TxFeeInfo {
+        self.conflicts
+            .iter()
+            .map(|c| &c.info)
+            .fold(&self.original, |best, candidate| {
+                if candidate.pays_more_than(best) {
+                    candidate
+                } else {
+                    best
+                }
+            })
+    }
+
+    /// True if a conflicting transaction is known to pay a higher feerate than the original
+    pub fn outbid(&self) -> bool {
+        self.highest_feerate().txid != self.original.txid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(byte: u8, fee: Option<u64>, vsize: usize) -> TxFeeInfo {
+        TxFeeInfo {
+            txid: TXID::from([byte; 32]),
+            fee,
+            vsize,
+        }
+    }
+
+    #[test]
+    fn it_selects_the_highest_feerate() {
+        let cases = [
+            (vec![], 0x00, false),
+            (vec![info(0x01, Some(100), 100)], 0x00, false),
+            (vec![info(0x01, Some(300), 150)], 0x01, true),
+            (
+                vec![info(0x01, Some(300), 150), info(0x02, Some(500), 200)],
+                0x02,
+                true,
+            ),
+            (vec![info(0x01, None, 100)], 0x00, false),
+        ];
+
+        for case in cases.iter() {
+            let report = ConflictReport {
+                original: info(0x00, Some(100), 100),
+                conflicts: case
+                    .0
+                    .iter()
+                    .cloned()
+                    .map(|info| RbfConflict {
+                        info,
+                        outpoints: vec![],
+                    })
+                    .collect(),
+            };
+            assert_eq!(report.is_replaced(), !case.0.is_empty());
+            assert_eq!(report.highest_feerate().txid, TXID::from([case.1; 32]));
+            assert_eq!(report.outbid(), case.2);
+        }
+
+        assert_eq!(
+            info(0x01, Some(300), 150).feerate(),
+            Some(FeeRate::from_sat_per_vb(2))
+        );
+        assert_eq!(info(0x01, None, 150).feerate(), None);
+    }
+}
@@ -30,6 +30,35 @@ impl fmt::Display for ErrorResponse {
     }
 }
 
+impl ErrorResponse {
+    /// True if bitcoind may accept the request later, e.g. while it is warming up or syncing
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.code,
+            -28 // RPC_IN_WARMUP
+            | -9 // RPC_CLIENT_NOT_CONNECTED
+            | -10 // RPC_CLIENT_IN_INITIAL_DOWNLOAD
+        )
+    }
+
+    /// True if bitcoind rejected the request's parameters, or the transaction it submitted
+    pub fn is_user_error(&self) -> bool {
+        matches!(
+            self.code,
+            -3 // RPC_TYPE_ERROR
+            | -5 // RPC_INVALID_ADDRESS_OR_KEY
+            | -8 // RPC_INVALID_PARAMETER
+            | -22 // RPC_DESERIALIZATION_ERROR
+            | -25 // RPC_VERIFY_ERROR
+            | -26 // RPC_VERIFY_REJECTED
+            | -27 // RPC_VERIFY_ALREADY_IN_CHAIN
+            | -32600 // Invalid request
+            | -32601 // Method not found
+            | -32602 // Invalid params
+        )
+    }
+}
+
 impl From<ErrorResponse> for ProviderError {
     fn from(e: ErrorResponse) -> Self {
         ProviderError::RpcErrorResponse(e)
@@ -15,6 +15,7 @@ use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 use crate::{
+    package::MempoolEntry,
     provider::*,
     rpc::{common::*, http::HttpTransport, rpc_types::*},
     types::RawHeader,
@@ -22,6 +23,9 @@ use crate::{
 
 static ERR_NOT_FOUND: i64 = -1;
 
+// Returned by `getmempoolentry` for transactions not in the mempool
+static ERR_NOT_IN_MEMPOOL: i64 = -5;
+
 /// A Bitcoin RPC connection
 #[derive(Debug)]
 pub struct BitcoinRpc<T: JsonRpcTransport> {
@@ -112,6 +116,60 @@ impl<T: JsonRpcTransport> BitcoinRpc<T> {
             .await
     }
 
+    /// Estimate the feerate needed to confirm within `target` blocks
+    pub async fn estimate_smart_fee(
+        &self,
+        target: usize,
+    ) -> Result<EstimateSmartFeeResponse, ProviderError> {
+        self.request("estimatesmartfee", vec![target]).await
+    }
+
+    /// Get the mempool entry of an unconfirmed transaction
+    pub async fn rpc_get_mempool_entry(
+        &self,
+        txid: TXID,
+    ) -> Result<GetMempoolEntryResponse, ProviderError> {
+        self.request("getmempoolentry", vec![txid.to_be_hex()])
+            .await
+    }
+
+    /// Mine `blocks` blocks paying to `address`. Regtest only. Returns the block hashes, in BE
+    /// hex
+    pub async fn generate_to_address(
+        &self,
+        blocks: usize,
+        address: &Address,
+    ) -> Result<Vec<String>, ProviderError> {
+        self.request("generatetoaddress", (blocks, address.as_string()))
+            .await
+    }
+
+    /// Create and load a wallet on the node
+    pub async fn create_wallet(&self, name: &str) -> Result<CreateWalletResponse, ProviderError> {
+        self.request("createwallet", vec![name]).await
+    }
+
+    /// Get a new bech32 address from the node's wallet
+    pub async fn get_new_address(&self) -> Result<String, ProviderError> {
+        self.request("getnewaddress", vec!["", "bech32"]).await
+    }
+
+    /// Get the node wallet's trusted balance, in BTC
+    pub async fn get_balance(&self) -> Result<f64, ProviderError> {
+        self.request("getbalance", Vec::<String>::new()).await
+    }
+
+    /// Send `value` satoshis to `address` from the node's wallet. Returns the txid, in BE hex
+    pub async fn send_to_address(
+        &self,
+        address: &Address,
+        value: u64,
+    ) -> Result<String, ProviderError> {
+        let amount = format!("{}.{:08}", value / 100_000_000, value % 100_000_000);
+        self.request("sendtoaddress", vec![address.as_string(), amount])
+            .await
+    }
+
     /// Start a txout scan. This may take some time, and will be interrupted by future requests.
     /// So we acquire a lock for it
     pub async fn scan_tx_out_set_for_address_start(
@@ -129,11 +187,51 @@ impl<T: JsonRpcTransport> BitcoinRpc<T> {
 
 #[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 #[cfg_attr(not(target_arch = "wasm32"), async_trait)]
-impl<T: JsonRpcTransport + Send + Sync> BtcProvider for BitcoinRpc<T> {
+impl<T: JsonRpcTransport + Send + Sync> ChainProvider for BitcoinRpc<T> {
+    type Tx = BitcoinTx;
+    type TXID = TXID;
+    type TxoIdentifier = BitcoinOutpoint;
+    type BlockHash = BlockHash;
+
     async fn tip_hash(&self) -> Result<BlockHash, ProviderError> {
         Ok(BlockHash::from_be_hex(&self.get_best_block_hash().await?)?)
     }
 
+    async fn get_confs(&self, txid: TXID) -> Result<Option<usize>, ProviderError> {
+        let tx = rpc_if_found!(self.get_raw_transaction(txid).await);
+        if tx.confirmations <= 0 {
+            Ok(Some(0))
+        } else {
+            Ok(Some(tx.confirmations as usize))
+        }
+    }
+
+    async fn get_tx(&self, txid: TXID) -> Result<Option<BitcoinTx>, ProviderError> {
+        let tx = rpc_if_found!(self.get_raw_transaction(txid).await);
+
+        Ok(Some(
+            BitcoinTx::deserialize_hex(&tx.hex).expect("No invalid tx from RPC"),
+        ))
+    }
+
+    async fn broadcast(&self, tx: BitcoinTx) -> Result<TXID, ProviderError> {
+        Ok(TXID::from_be_hex(&self.send_raw_transaction(tx).await?)?)
+    }
+
+    /// Unsupported
+    async fn get_outspend(
+        &self,
+        _outpoint: BitcoinOutpoint,
+    ) -> Result<Option<TXID>, ProviderError> {
+        Err(ProviderError::Unsupported(
+            "get_outspend not currently supported without wallet".to_owned(),
+        ))
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl<T: JsonRpcTransport + Send + Sync> BtcProvider for BitcoinRpc<T> {
     async fn tip_height(&self) -> Result<usize, ProviderError> {
         let tip = self.tip_hash().await?;
         Ok(self.get_block(tip).await?.height)
@@ -202,35 +300,17 @@ impl<T: JsonRpcTransport + Send + Sync> BtcProvider for BitcoinRpc<T> {
         Ok(Some(block.height))
     }
 
-    async fn get_confs(&self, txid: TXID) -> Result<Option<usize>, ProviderError> {
-        let tx = rpc_if_found!(self.get_raw_transaction(txid).await);
-        if tx.confirmations <= 0 {
-            Ok(Some(0))
-        } else {
-            Ok(Some(tx.confirmations as usize))
-        }
-    }
-
-    async fn get_tx(&self, txid: TXID) -> Result<Option<BitcoinTx>, ProviderError> {
-        let tx = rpc_if_found!(self.get_raw_transaction(txid).await);
-
-        Ok(Some(
-            BitcoinTx::deserialize_hex(&tx.hex).expect("No invalid tx from RPC"),
-        ))
-    }
-
-    async fn broadcast(&self, tx: BitcoinTx) -> Result<TXID, ProviderError> {
-        Ok(TXID::from_be_hex(&self.send_raw_transaction(tx).await?)?)
+    async fn estimate_feerate(&self, target: usize) -> Result<Option<FeeRate>, ProviderError> {
+        let resp = self.estimate_smart_fee(target).await?;
+        Ok(resp.feerate.map(FeeRate::from_btc_per_kvb))
     }
 
-    /// Unsupported
-    async fn get_outspend(
-        &self,
-        _outpoint: BitcoinOutpoint,
-    ) -> Result<Option<TXID>, ProviderError> {
-        Err(ProviderError::Unsupported(
-            "get_outspend not currently supported without wallet".to_owned(),
-        ))
+    async fn get_mempool_entry(&self, txid: TXID) -> Result<Option<MempoolEntry>, ProviderError> {
+        match self.rpc_get_mempool_entry(txid).await {
+            Ok(entry) => Ok(Some(entry.into())),
+            Err(ProviderError::RpcErrorResponse(e)) if e.code == ERR_NOT_IN_MEMPOOL => Ok(None),
+            Err(e) => Err(e),
+        }
     }
 
     /// TODO: preflight to make sure scantxoutset is supported
@@ -1,5 +1,7 @@
 use bitcoins::prelude::*;
 
+use crate::package::MempoolEntry;
+
 /// The params for getrawtransaction
 #[derive(serde::Serialize, Debug)]
 pub struct GetRawTxParams(pub String, pub usize);
@@ -70,7 +72,7 @@ impl From<RpcUtxo> for Utxo {
         let script_pubkey =
             ScriptPubkey::deserialize_hex(&src.scriptPubKey).expect("valid API response");
         let spend_script = SpendScript::from_script_pubkey(&script_pubkey);
-        Utxo::new(
+        let mut utxo = Utxo::new(
             BitcoinOutpoint {
                 txid: TXID::from_be_hex(&src.txid).expect("valid API respopnse"),
                 idx: src.vout,
@@ -78,7 +80,10 @@ impl From<RpcUtxo> for Utxo {
             src.amount,
             script_pubkey,
             spend_script,
-        )
+        );
+        // scantxoutset reports the height, but not the hash, of the confirming block
+        utxo.height = Some(src.height as u32);
+        utxo
     }
 }
 
@@ -94,3 +99,69 @@ pub struct ScanTxOutResponse {
     /// The unspent txns
     pub unspents: Vec<RpcUtxo>,
 }
+
+/// The response for the `estimatesmartfee` command
+///
+/// https://bitcoincore.org/en/doc/0.20.0/rpc/util/estimatesmartfee/
+#[derive(serde::Deserialize, Debug)]
+pub struct EstimateSmartFeeResponse {
+    /// The estimated feerate in BTC/kvB. Absent if the node has insufficient data
+    pub feerate: Option<f64>,
+    /// The block number where the estimate was found
+    pub blocks: usize,
+}
+
+/// The response for the `createwallet` command
+///
+/// https://bitcoincore.org/en/doc/0.20.0/rpc/wallet/createwallet/
+#[derive(serde::Deserialize, Debug)]
+pub struct CreateWalletResponse {
+    /// The name of the wallet
+    pub name: String,
+    /// Any warnings from creating the wallet
+    #[serde(default)]
+    pub warning: String,
+}
+
+/// The fees in the `getmempoolentry` response, in BTC
+#[derive(serde::Deserialize, Debug)]
+pub struct MempoolEntryFees {
+    /// The transaction fee
+    pub base: f64,
+    /// The fees of in-mempool ancestors, including this one
+    pub ancestor: f64,
+}
+
+/// The response for the `getmempoolentry` command
+///
+/// https://bitcoincore.org/en/doc/0.20.0/rpc/blockchain/getmempoolentry/
+#[derive(serde::Deserialize, Debug)]
+pub struct GetMempoolEntryResponse {
+    /// The virtual size of the transaction
+    pub vsize: usize,
+    /// The number of in-mempool ancestors, including this one
+    pub ancestorcount: usize,
+    /// The vsize of in-mempool ancestors, including this one
+    pub ancestorsize: usize,
+    /// The number of in-mempool descendants, including this one
+    pub descendantcount: usize,
+    /// The vsize of in-mempool descendants, including this one
+    pub descendantsize: usize,
+    /// The fees of the transaction and its relatives
+    pub fees: MempoolEntryFees,
+}
+
+impl From<GetMempoolEntryResponse> for MempoolEntry {
+    fn from(src: GetMempoolEntryResponse) -> MempoolEntry {
+        let sats = |btc: f64| (btc * 100_000_000.0).round() as u64;
+        MempoolEntry {
+            fee: sats(src.fees.base),
+            vsize: src.vsize,
+            ancestor_count: src.ancestorcount,
+            ancestor_vsize: src.ancestorsize,
+            ancestor_fees: sats(src.fees.ancestor),
+            descendant_count: src.descendantcount,
+            descendant_vsize: src.descendantsize,
+        }
+    }
+}
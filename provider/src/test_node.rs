@@ -0,0 +1,294 @@
+use std::{
+    env,
+    ffi::OsStr,
+    fs,
+    net::TcpListener,
+    path::PathBuf,
+    process::{self, Child, Command, ExitStatus, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use bitcoins::prelude::*;
+use coins_core::{enc::EncodingError, ser::SerError};
+use futures_timer::Delay;
+use secrecy::SecretString;
+use thiserror::Error;
+
+use crate::{
+    provider::ProviderError,
+    rpc::{http::HttpTransport, BitcoinRpc},
+};
+
+/// The environment variable holding the path to the bitcoind binary. If unset, `bitcoind` is
+/// found on the `PATH`.
+pub const BITCOIND_EXE_VAR: &str = "BITCOIND_EXE";
+
+/// The RPC username of spawned nodes
+pub const RPC_USER: &str = "bitcoins";
+
+/// The RPC password of spawned nodes
+pub const RPC_PASSWORD: &str = "bitcoins";
+
+/// How long to wait for a node to accept RPC requests
+pub const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The name of the wallet created on spawned nodes
+const WALLET_NAME: &str = "test-node";
+
+/// How often to poll a starting node
+const STARTUP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Distinguishes the data directories of nodes spawned by this process
+static NEXT_NODE: AtomicUsize = AtomicUsize::new(0);
+
+/// An error starting or using a `TestNode`.
+#[derive(Debug, Error)]
+pub enum TestNodeError {
+    /// IoError from spawning bitcoind, or preparing its data directory
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+
+    /// Bubbled up from the RPC provider
+    #[error(transparent)]
+    ProviderError(#[from] ProviderError),
+
+    /// A script could not be encoded as a regtest address
+    #[error(transparent)]
+    EncodingError(#[from] EncodingError),
+
+    /// The node returned a malformed hash
+    #[error(transparent)]
+    SerError(#[from] SerError),
+
+    /// The node returned a malformed transaction
+    #[error(transparent)]
+    TxError(#[from] TxError),
+
+    /// bitcoind exited before accepting RPC requests
+    #[error("bitcoind exited during startup with {0}")]
+    Exited(ExitStatus),
+
+    /// The node did not accept RPC requests in time
+    #[error("bitcoind did not accept RPC requests within {0:?}")]
+    Timeout(Duration),
+
+    /// The node's wallet can't fund a script, even after mining
+    #[error("Wallet balance of {available} sats is below the {required} sats requested")]
+    InsufficientFunds {
+        /// The value requested
+        required: u64,
+        /// The wallet's balance
+        available: u64,
+    },
+}
+
+/// A regtest bitcoind for integration tests.
+///
+/// `TestNode::spawn` starts a fresh node with its own data directory and wallet. The node is
+/// killed and its data directory deleted when the `TestNode` is dropped. `TestNode::attach`
+/// connects to a running regtest node instead, which must have a wallet loaded.
+///
+/// The node's wallet mines blocks and funds scripts, so tests can create spendable UTXOs and
+/// confirm their transactions. `TestNode::rpc` exposes the node as a provider.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), bitcoins_provider::test_node::TestNodeError> {
+/// use bitcoins::types::ScriptPubkey;
+/// use bitcoins_provider::{provider::ChainProvider, test_node::TestNode};
+///
+/// let node = TestNode::spawn().await?;
+/// let spk = ScriptPubkey::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+/// let utxo = node.fund(&spk, 100_000).await?;
+/// node.mine(1).await?;
+/// assert_eq!(node.rpc().get_confs(utxo.outpoint.txid).await?, Some(1));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TestNode {
+    rpc: BitcoinRpc<HttpTransport>,
+    url: String,
+    process: Option<Child>,
+    datadir: Option<PathBuf>,
+}
+
+impl TestNode {
+    /// Spawn a regtest bitcoind. The binary is read from `BITCOIND_EXE`, or found on the
+    /// `PATH`.
+    pub async fn spawn() -> Result<Self, TestNodeError> {
+        let exe = env::var_os(BITCOIND_EXE_VAR).unwrap_or_else(|| "bitcoind".into());
+        Self::spawn_with(exe).await
+    }
+
+    /// Spawn a regtest node from the bitcoind binary at `exe`
+    pub async fn spawn_with<S: AsRef<OsStr>>(exe: S) -> Result<Self, TestNodeError> {
+        let port = TcpListener::bind(("127.0.0.1", 0))?.local_addr()?.port();
+        let datadir = env::temp_dir().join(format!(
+            "bitcoins-test-node-{}-{}",
+            process::id(),
+            NEXT_NODE.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::create_dir_all(&datadir)?;
+
+        // The node cleans up its data directory on drop, including on errors below
+        let mut node = Self::connect(&format!("127.0.0.1:{}", port), RPC_USER, RPC_PASSWORD);
+        node.datadir = Some(datadir.clone());
+        node.process = Some(
+            Command::new(exe)
+                .arg("-regtest")
+                .arg(format!("-datadir={}", datadir.display()))
+                .arg(format!("-rpcport={}", port))
+                .arg(format!("-rpcuser={}", RPC_USER))
+                .arg(format!("-rpcpassword={}", RPC_PASSWORD))
+                .arg("-rpcbind=127.0.0.1")
+                .arg("-rpcallowip=127.0.0.1")
+                .arg("-server")
+                .arg("-listen=0")
+                .arg("-txindex")
+                .arg("-fallbackfee=0.0001")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()?,
+        );
+        node.wait_until_ready().await?;
+        node.rpc.create_wallet(WALLET_NAME).await?;
+        Ok(node)
+    }
+
+    /// Attach to a running regtest node at `url`, e.g. `127.0.0.1:18443`. The node must have a
+    /// wallet loaded to mine and fund scripts.
+    pub async fn attach(url: &str, username: &str, password: &str) -> Result<Self, TestNodeError> {
+        let mut node = Self::connect(url, username, password);
+        node.wait_until_ready().await?;
+        Ok(node)
+    }
+
+    fn connect(url: &str, username: &str, password: &str) -> Self {
+        Self {
+            rpc: BitcoinRpc::with_credentials_and_url(
+                SecretString::new(username.to_owned()),
+                SecretString::new(password.to_owned()),
+                url,
+            ),
+            url: url.to_owned(),
+            process: None,
+            datadir: None,
+        }
+    }
+
+    /// Poll the node until it accepts RPC requests, or `STARTUP_TIMEOUT` elapses
+    async fn wait_until_ready(&mut self) -> Result<(), TestNodeError> {
+        let start = Instant::now();
+        loop {
+            if let Some(process) = self.process.as_mut() {
+                if let Some(status) = process.try_wait()? {
+                    return Err(TestNodeError::Exited(status));
+                }
+            }
+            if self.rpc.get_best_block_hash().await.is_ok() {
+                return Ok(());
+            }
+            if start.elapsed() > STARTUP_TIMEOUT {
+                return Err(TestNodeError::Timeout(STARTUP_TIMEOUT));
+            }
+            Delay::new(STARTUP_POLL_INTERVAL).await;
+        }
+    }
+
+    /// The node's RPC provider
+    pub fn rpc(&self) -> &BitcoinRpc<HttpTransport> {
+        &self.rpc
+    }
+
+    /// The node's RPC url, without credentials. Spawned nodes use `RPC_USER` and `RPC_PASSWORD`
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The regtest address of a script pubkey
+    pub fn address(&self, script_pubkey: &ScriptPubkey) -> Result<Address, TestNodeError> {
+        Ok(RegtestEncoder::encode_address(script_pubkey)?)
+    }
+
+    /// Mine `blocks` blocks, paying the coinbases to the node's wallet
+    pub async fn mine(&self, blocks: usize) -> Result<Vec<BlockHash>, TestNodeError> {
+        let address = RegtestEncoder::string_to_address(&self.rpc.get_new_address().await?)?;
+        self.mine_to_address(blocks, &address).await
+    }
+
+    /// Mine `blocks` blocks, paying the coinbases to `script_pubkey`
+    pub async fn mine_to(
+        &self,
+        blocks: usize,
+        script_pubkey: &ScriptPubkey,
+    ) -> Result<Vec<BlockHash>, TestNodeError> {
+        self.mine_to_address(blocks, &self.address(script_pubkey)?)
+            .await
+    }
+
+    async fn mine_to_address(
+        &self,
+        blocks: usize,
+        address: &Address,
+    ) -> Result<Vec<BlockHash>, TestNodeError> {
+        let hashes = self.rpc.generate_to_address(blocks, address).await?;
+        Ok(hashes
+            .iter()
+            .map(|h| BlockHash::from_be_hex(h))
+            .collect::<Result<_, _>>()?)
+    }
+
+    /// The node wallet's trusted balance, in sats
+    pub async fn balance(&self) -> Result<u64, TestNodeError> {
+        Ok((self.rpc.get_balance().await? * 100_000_000.0).round() as u64)
+    }
+
+    /// Pay `value` sats to `script_pubkey` from the node's wallet, and return the new UTXO. The
+    /// payment is unconfirmed. If the wallet can't cover `value`, mature coinbases are mined to
+    /// it first.
+    pub async fn fund(
+        &self,
+        script_pubkey: &ScriptPubkey,
+        value: u64,
+    ) -> Result<Utxo, TestNodeError> {
+        if self.balance().await? < value {
+            self.mine(COINBASE_MATURITY as usize + 1).await?;
+        }
+        let available = self.balance().await?;
+        if available < value {
+            return Err(TestNodeError::InsufficientFunds {
+                required: value,
+                available,
+            });
+        }
+
+        let address = self.address(script_pubkey)?;
+        let txid = TXID::from_be_hex(&self.rpc.send_to_address(&address, value).await?)?;
+        let tx = self.rpc.get_raw_transaction(txid).await?;
+        let tx = BitcoinTx::deserialize_hex(&tx.hex)?;
+        let idx = tx
+            .outputs()
+            .iter()
+            .position(|o| o.value == value && &o.script_pubkey == script_pubkey)
+            .expect("node pays the requested script");
+        Ok(Utxo::new(
+            BitcoinOutpoint::new(txid, idx as u32),
+            value,
+            script_pubkey.clone(),
+            SpendScript::from_script_pubkey(script_pubkey),
+        ))
+    }
+}
+
+impl Drop for TestNode {
+    fn drop(&mut self) {
+        if let Some(mut process) = self.process.take() {
+            let _ = process.kill();
+            let _ = process.wait();
+        }
+        if let Some(datadir) = self.datadir.take() {
+            let _ = fs::remove_dir_all(datadir);
+        }
+    }
+}
@@ -8,19 +8,30 @@ use std::{
 use pin_project::pin_project;
 
 use futures_core::Stream;
-use futures_timer::Delay;
 use futures_util::{
     stream::{self, StreamExt},
     FutureExt,
 };
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use bitcoins::prelude::TXID;
 use coins_core::prelude::{Hash256, Hash256Digest, MarkedDigest, MarkedDigestOutput};
 
+use crate::clock::Clock;
+
+// Async delay stream
+#[cfg(target_arch = "wasm32")]
+pub(crate) type Interval = Box<dyn Stream<Item = ()> + Unpin>;
+
 // Async delay stream
-pub(crate) fn new_interval(duration: Duration) -> impl Stream<Item = ()> + Send + Unpin {
-    stream::unfold((), move |_| Delay::new(duration).map(|_| Some(((), ())))).map(drop)
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) type Interval = Box<dyn Stream<Item = ()> + Send + Unpin>;
+
+// Async delay stream, sleeping on the clock
+pub(crate) fn new_interval(clock: Arc<dyn Clock>, duration: Duration) -> Interval {
+    Box::new(stream::unfold(clock, move |clock| {
+        clock.sleep(duration).map(move |_| Some(((), clock)))
+    }))
 }
 
 /// Future for the `last` method. Resolves to the last item in the stream.
@@ -1,27 +1,26 @@
 use std::{
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
     time::Duration,
 };
 
-use futures_core::stream::Stream;
 use futures_util::stream::StreamExt;
 use pin_project::pin_project;
 
-use bitcoins::prelude::*;
-
 use crate::{
-    provider::BtcProvider,
-    utils::{new_interval, StreamLast},
+    clock::{Clock, SystemClock},
+    provider::{BtcProvider, ChainProvider},
+    utils::{new_interval, Interval, StreamLast},
     ProviderFut, DEFAULT_POLL_INTERVAL,
 };
 
-enum WatcherStates<'a> {
+enum WatcherStates<'a, P: ChainProvider + ?Sized> {
     // Waiting for a tx to spend
-    WaitingSpends(ProviderFut<'a, Option<TXID>>),
-    Paused(usize, TXID),
+    WaitingSpends(ProviderFut<'a, Option<P::TXID>>),
+    Paused(usize, P::TXID),
     // Tx known, getting confs
-    WaitingMoreConfs(usize, TXID, ProviderFut<'a, Option<usize>>),
+    WaitingMoreConfs(usize, P::TXID, ProviderFut<'a, Option<usize>>),
     // Future has completed, and should panic if polled again
     Completed,
 }
@@ -37,25 +36,31 @@ enum WatcherStates<'a> {
 /// `>= self.confirmations` confirmations, the stream will finish.
 ///
 /// To get a future yielding a single event when the stream ends, use `StreamLast::last()`
+///
+/// The stream is generic over the provider's chain. By default it uses a `dyn BtcProvider`.
 #[pin_project(project = PollingWatcherProj)]
 #[must_use = "streams do nothing unless polled"]
-pub struct PollingWatcher<'a> {
-    outpoint: BitcoinOutpoint,
+pub struct PollingWatcher<'a, P: ChainProvider + ?Sized = dyn BtcProvider + 'a> {
+    outpoint: P::TxoIdentifier,
     confirmations: usize,
-    state: WatcherStates<'a>,
-    interval: Box<dyn Stream<Item = ()> + Send + Unpin>,
-    provider: &'a dyn BtcProvider,
+    state: WatcherStates<'a, P>,
+    interval: Interval,
+    duration: Duration,
+    clock: Arc<dyn Clock>,
+    provider: &'a P,
 }
 
-impl<'a> PollingWatcher<'a> {
+impl<'a, P: ChainProvider + ?Sized> PollingWatcher<'a, P> {
     /// Creates a new outspend poller
-    pub fn new(outpoint: BitcoinOutpoint, provider: &'a dyn BtcProvider) -> Self {
+    pub fn new(outpoint: P::TxoIdentifier, provider: &'a P) -> Self {
         let fut = Box::pin(provider.get_outspend(outpoint));
         Self {
             outpoint,
             confirmations: 0,
             state: WatcherStates::WaitingSpends(fut),
-            interval: Box::new(new_interval(DEFAULT_POLL_INTERVAL)),
+            interval: new_interval(Arc::new(SystemClock), DEFAULT_POLL_INTERVAL),
+            duration: DEFAULT_POLL_INTERVAL,
+            clock: Arc::new(SystemClock),
             provider,
         }
     }
@@ -68,15 +73,23 @@ impl<'a> PollingWatcher<'a> {
 
     /// Sets the polling interval
     pub fn interval<T: Into<Duration>>(mut self, duration: T) -> Self {
-        self.interval = Box::new(new_interval(duration.into()));
+        self.duration = duration.into();
+        self.interval = new_interval(self.clock.clone(), self.duration);
+        self
+    }
+
+    /// Sets the clock used to pause between polls
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.interval = new_interval(clock.clone(), self.duration);
+        self.clock = clock;
         self
     }
 }
 
-impl StreamLast for PollingWatcher<'_> {}
+impl<P: ChainProvider + ?Sized> StreamLast for PollingWatcher<'_, P> {}
 
-impl<'a> futures_core::stream::Stream for PollingWatcher<'a> {
-    type Item = (usize, Option<TXID>);
+impl<'a, P: ChainProvider + ?Sized> futures_core::stream::Stream for PollingWatcher<'a, P> {
+    type Item = (usize, Option<P::TXID>);
 
     fn poll_next(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Option<Self::Item>> {
         let PollingWatcherProj {
@@ -85,6 +98,7 @@ impl<'a> futures_core::stream::Stream for PollingWatcher<'a> {
             state,
             interval,
             provider,
+            ..
         } = self.project();
 
         match state {
@@ -0,0 +1,55 @@
+#![cfg(feature = "test-node")]
+
+use bitcoins::prelude::*;
+use bitcoins_provider::{
+    provider::{BtcProvider, ChainProvider},
+    test_node::{TestNode, RPC_PASSWORD, RPC_USER},
+};
+
+// These tests spawn bitcoind. Run them with
+// `cargo test --features test-node -- --ignored`
+
+fn script_pubkey() -> ScriptPubkey {
+    ScriptPubkey::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap()
+}
+
+#[tokio::test]
+#[ignore]
+async fn it_funds_and_confirms_scripts() {
+    let node = TestNode::spawn().await.expect("bitcoind not found");
+    let spk = script_pubkey();
+
+    let utxo = node.fund(&spk, 100_000).await.unwrap();
+    let txid = utxo.outpoint.txid;
+    assert_eq!(node.rpc().get_confs(txid).await.unwrap(), Some(0));
+
+    let tip = node.rpc().tip_height().await.unwrap();
+    let blocks = node.mine(1).await.unwrap();
+    assert_eq!(node.rpc().tip_hash().await.unwrap(), blocks[0]);
+    assert_eq!(node.rpc().get_confs(txid).await.unwrap(), Some(1));
+    assert_eq!(
+        node.rpc().get_confirmed_height(txid).await.unwrap(),
+        Some(tip + 1)
+    );
+
+    let tx = node.rpc().get_tx(txid).await.unwrap().unwrap();
+    let output = &tx.outputs()[utxo.outpoint.idx as usize];
+    assert_eq!(output.value, utxo.value);
+    assert_eq!(output.script_pubkey, spk);
+}
+
+#[tokio::test]
+#[ignore]
+async fn it_attaches_to_running_nodes() {
+    let node = TestNode::spawn().await.expect("bitcoind not found");
+    let attached = TestNode::attach(node.url(), RPC_USER, RPC_PASSWORD)
+        .await
+        .unwrap();
+
+    node.mine_to(3, &script_pubkey()).await.unwrap();
+    assert_eq!(attached.rpc().tip_height().await.unwrap(), 3);
+    assert_eq!(
+        attached.rpc().tip_hash().await.unwrap(),
+        node.rpc().tip_hash().await.unwrap()
+    );
+}